@@ -1,94 +1,206 @@
+pub mod air_quality;
+pub mod apt;
 pub mod backlight;
 pub mod battery;
+pub mod binding_mode;
 pub mod bluetooth;
+pub mod btrfs;
+pub mod caffeine;
+pub mod calendar;
+pub mod ci;
+pub mod countdown;
 pub mod cpu;
+pub mod crypto;
+pub mod currency;
 pub mod custom;
 pub mod custom_dbus;
+pub mod disk_io;
 pub mod disk_space;
+pub mod dnf;
 pub mod docker;
+pub mod failed_units;
+pub mod feeds;
+pub mod flatpak;
 pub mod focused_window;
+pub mod fwupd;
 pub mod github;
+pub mod group;
 pub mod hueshift;
 pub mod ibus;
+pub mod imap;
+#[cfg(feature = "journal")]
+pub mod journal;
 pub mod kdeconnect;
 pub mod keyboard_layout;
+pub mod kubernetes;
+pub mod libvirt;
 pub mod load;
+pub mod lock_keys;
 pub mod maildir;
+pub mod matrix;
 pub mod memory;
+pub mod moon;
+pub mod mpd;
+pub mod mqtt;
 pub mod music;
 pub mod net;
 pub mod networkmanager;
+pub mod notification_count;
 pub mod notify;
 #[cfg(feature = "notmuch")]
 pub mod notmuch;
 pub mod nvidia_gpu;
 pub mod pacman;
+pub mod ping;
 pub mod pomodoro;
+pub mod pressure;
+pub mod public_ip;
+pub mod raid;
+pub mod reboot_required;
+pub mod rest;
+pub mod scratchpad;
+pub mod screenshare;
+pub mod smart;
 pub mod sound;
 pub mod speedtest;
+pub mod stocks;
+pub mod stopwatch;
+pub mod sun;
+pub mod systemd;
 pub mod taskwarrior;
+pub mod telegram;
 pub mod temperature;
 pub mod template;
 pub mod time;
+pub mod todotxt;
 pub mod toggle;
+pub mod transit;
+pub mod ups;
 pub mod uptime;
+pub mod uv;
+pub mod vpn;
 pub mod watson;
 pub mod weather;
+pub mod webcam;
+pub mod weechat;
+pub mod wifi;
 pub mod xrandr;
+pub mod zfs;
 
+use self::air_quality::*;
+use self::apt::*;
 use self::backlight::*;
 use self::battery::*;
+use self::binding_mode::*;
 use self::bluetooth::*;
+use self::btrfs::*;
+use self::caffeine::*;
+use self::calendar::*;
+use self::ci::*;
+use self::countdown::*;
 use self::cpu::*;
+use self::crypto::*;
+use self::currency::*;
 use self::custom::*;
 use self::custom_dbus::*;
+use self::disk_io::*;
 use self::disk_space::*;
+use self::dnf::*;
 use self::docker::*;
+use self::failed_units::*;
+use self::feeds::*;
+use self::flatpak::*;
 use self::focused_window::*;
+use self::fwupd::*;
 use self::github::*;
+use self::group::*;
 use self::hueshift::*;
 use self::ibus::*;
+use self::imap::*;
+#[cfg(feature = "journal")]
+use self::journal::*;
 use self::kdeconnect::*;
 use self::keyboard_layout::*;
+use self::kubernetes::*;
+use self::libvirt::*;
 use self::load::*;
+use self::lock_keys::*;
 use self::maildir::*;
+use self::matrix::*;
 use self::memory::*;
+use self::moon::*;
+use self::mpd::*;
+use self::mqtt::*;
 use self::music::*;
 use self::net::*;
 use self::networkmanager::*;
+use self::notification_count::*;
 use self::notify::*;
 #[cfg(feature = "notmuch")]
 use self::notmuch::*;
 use self::nvidia_gpu::*;
 use self::pacman::*;
+use self::ping::*;
 use self::pomodoro::*;
+use self::pressure::*;
+use self::public_ip::*;
+use self::raid::*;
+use self::reboot_required::*;
+use self::rest::*;
+use self::scratchpad::*;
+use self::screenshare::*;
+use self::smart::*;
 use self::sound::*;
 use self::speedtest::*;
+use self::stocks::*;
+use self::stopwatch::*;
+use self::sun::*;
+use self::systemd::*;
 use self::taskwarrior::*;
+use self::telegram::*;
 use self::temperature::*;
 use self::template::*;
 use self::time::*;
+use self::todotxt::*;
 use self::toggle::*;
+use self::transit::*;
+use self::ups::*;
 use self::uptime::*;
+use self::uv::*;
+use self::vpn::*;
 use self::watson::*;
 use self::weather::*;
+use self::webcam::*;
+use self::weechat::*;
+use self::wifi::*;
 use self::xrandr::*;
+use self::zfs::*;
 
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use serde::de::Deserialize;
-use toml::value::Value;
+use serde_derive::Deserialize;
+use toml::value::{Table, Value};
 
 use crate::config::Config;
 use crate::errors::*;
-use crate::input::I3BarEvent;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::ipc::{IpcCommand, IpcRequest};
 use crate::scheduler::Task;
-use crate::widget::I3BarWidget;
+use crate::signals::convert_to_valid_signal;
+use crate::subprocess::spawn_child_async;
+use crate::themes::ThemeOverrides;
+use crate::util::{collect_rendered_numbers, numbers_in};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
 
 #[derive(Clone, Debug, PartialEq)]
 pub enum Update {
     Every(Duration),
+    /// Like `Every`, but the next update is scheduled on the next wall-clock
+    /// multiple of the duration (e.g. the start of the next minute).
+    Aligned(Duration),
     Once,
 }
 
@@ -111,7 +223,21 @@ pub trait Block {
     /// The current "view" of the block, comprised of widgets.
     fn view(&self) -> Vec<&dyn I3BarWidget>;
 
+    /// The views of the block and of the blocks it contains, which are rendered as separate
+    /// blocks with their own ids. Only containers such as `group` need to override this.
+    fn views(&self) -> Vec<(&str, Vec<&dyn I3BarWidget>)> {
+        vec![(self.id(), self.view())]
+    }
+
     /// Forces an update of the internal state of the block.
+    ///
+    /// The returned value decides when the block is updated next. Blocks that are driven by
+    /// their own event source (D-Bus, inotify, ...) should return `None` or `Update::Once`, so
+    /// that they cause no wakeups, and send a `Task` on their update request channel instead.
+    /// Sources with a file descriptor can be watched by the reactor, see
+    /// `reactor::request_updates`, instead of a thread of their own; D-Bus and PulseAudio are
+    /// not supported by the reactor yet. The value returned from such a requested update puts
+    /// the block back on the schedule.
     fn update(&mut self) -> Result<Option<Update>> {
         Ok(None)
     }
@@ -127,6 +253,26 @@ pub trait Block {
     fn click(&mut self, _event: &I3BarEvent) -> Result<()> {
         Ok(())
     }
+
+    /// Whether a click on the main widget of the block does something of its own with this
+    /// button, like toggling the format of `time`, in which case the common click command of
+    /// the button is not run. Clicks that only refresh the block, or mark what it counts as
+    /// read, go along with a command, which can open a client at the same time.
+    fn handles_click(&self, _event: &I3BarEvent) -> bool {
+        false
+    }
+
+    /// The shell that runs the click commands of the common options for this block. Blocks
+    /// that run commands of their own, like `custom`, use the same shell for them.
+    fn shell(&self) -> &str {
+        "sh"
+    }
+
+    /// Sends a command received over IPC to the block. This function is called on every
+    /// block for every command; returns whether the block was the one addressed.
+    fn command(&mut self, _request: &IpcRequest) -> Result<bool> {
+        Ok(false)
+    }
 }
 
 pub trait ConfigBlock: Block {
@@ -143,59 +289,777 @@ pub trait ConfigBlock: Block {
 }
 
 macro_rules! block {
-    ($block_type:ident, $block_config:expr, $config:expr, $update_request:expr) => {{
+    ($block_type:ident, $block_config:expr, $context:expr) => {{
         let block_config: <$block_type as ConfigBlock>::Config =
             <$block_type as ConfigBlock>::Config::deserialize($block_config)
                 .configuration_error("Failed to deserialize block config.")?;
-        Ok(Box::new($block_type::new(block_config, $config, $update_request)?) as Box<dyn Block>)
+        match $context {
+            Some((config, update_request)) => Ok(Some(Box::new($block_type::new(
+                block_config,
+                config,
+                update_request,
+            )?) as Box<dyn Block>)),
+            None => Ok(None),
+        }
     }};
 }
 
+/// A shell command bound to a mouse button and a combination of modifier keys.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ClickCommand {
+    pub button: MouseButton,
+    #[serde(default)]
+    pub modifiers: Vec<String>,
+    pub cmd: String,
+}
+
+/// Options that every block accepts. They are handled here rather than by the blocks.
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CommonConfig {
+    /// Name under which the block can be addressed over IPC. Blocks such as `custom_dbus`
+    /// and `sound` have a `name` option of their own.
+    pub ipc_name: Option<String>,
+    /// Realtime signal, counted from `SIGRTMIN`, that triggers an update
+    pub signal: Option<i32>,
+    pub on_click: Option<String>,
+    pub on_right_click: Option<String>,
+    pub on_middle_click: Option<String>,
+    pub on_scroll_up: Option<String>,
+    pub on_scroll_down: Option<String>,
+    #[serde(default)]
+    pub click: Vec<ClickCommand>,
+    /// Colors that replace those of the global theme for this block
+    pub theme_overrides: Option<ThemeOverrides>,
+    /// Overrides the global `gradient` option for this block
+    pub gradient: Option<bool>,
+    /// Hide the block while its widgets show no text besides their icons
+    #[serde(default)]
+    pub hide_when_empty: bool,
+    /// Hide the block while all numbers it shows are below this value
+    pub hide_below: Option<f64>,
+}
+
+impl CommonConfig {
+    const KEYS: [&'static str; 12] = [
+        "ipc_name",
+        "signal",
+        "on_click",
+        "on_right_click",
+        "on_middle_click",
+        "on_scroll_up",
+        "on_scroll_down",
+        "click",
+        "theme_overrides",
+        "gradient",
+        "hide_when_empty",
+        "hide_below",
+    ];
+
+    /// Removes the common options from a block configuration, so that the block itself
+    /// does not need to know about them.
+    fn take_from(block_config: &mut Value) -> Result<Self> {
+        let mut common = Table::new();
+        if let Value::Table(ref mut table) = block_config {
+            for key in Self::KEYS.iter() {
+                if let Some(value) = table.remove(*key) {
+                    common.insert((*key).to_string(), value);
+                }
+            }
+        }
+        CommonConfig::deserialize(Value::Table(common))
+            .configuration_error("Failed to deserialize common block options.")
+    }
+
+    fn is_empty(&self) -> bool {
+        self.ipc_name.is_none()
+            && self.signal.is_none()
+            && self.on_click.is_none()
+            && self.on_right_click.is_none()
+            && self.on_middle_click.is_none()
+            && self.on_scroll_up.is_none()
+            && self.on_scroll_down.is_none()
+            && self.click.is_empty()
+            && !self.hide_when_empty
+            && self.hide_below.is_none()
+    }
+
+    /// Whether widgets show nothing of interest, according to `hide_when_empty` and
+    /// `hide_below`. `hide_below` compares the numbers behind the placeholders of the block,
+    /// or the numbers in the text of blocks that show no placeholders.
+    fn hides(&self, widgets: &[&dyn I3BarWidget], rendered: &[f64]) -> bool {
+        if self.hide_when_empty && widgets.iter().all(|w| w.content().trim().is_empty()) {
+            return true;
+        }
+        match self.hide_below {
+            Some(threshold) => {
+                let numbers = if rendered.is_empty() {
+                    widgets
+                        .iter()
+                        .flat_map(|w| numbers_in(w.content()))
+                        .collect::<Vec<_>>()
+                } else {
+                    rendered.to_vec()
+                };
+                !numbers.is_empty() && numbers.iter().all(|&n| n < threshold)
+            }
+            None => false,
+        }
+    }
+
+    /// The first `click` entry whose modifiers are all held takes precedence over the
+    /// `on_*` options, which apply regardless of modifiers.
+    fn command(&self, event: &I3BarEvent) -> Option<&String> {
+        let bound = self.click.iter().find(|c| {
+            c.button == event.button && c.modifiers.iter().all(|m| event.has_modifier(m))
+        });
+        if let Some(c) = bound {
+            return Some(&c.cmd);
+        }
+        match event.button {
+            MouseButton::Left => self.on_click.as_ref(),
+            MouseButton::Right => self.on_right_click.as_ref(),
+            MouseButton::Middle => self.on_middle_click.as_ref(),
+            MouseButton::WheelUp => self.on_scroll_up.as_ref(),
+            MouseButton::WheelDown => self.on_scroll_down.as_ref(),
+            _ => None,
+        }
+    }
+}
+
+/// Wraps a block to implement the common options and IPC commands.
+///
+/// A click command runs for the buttons that the block does not handle itself, and the block
+/// is updated once the command has been started.
+struct Common {
+    block: Box<dyn Block>,
+    common: CommonConfig,
+    config: Config,
+    update_request: Sender<Task>,
+    /// `signal` converted to the actual signal number.
+    signal: Option<i32>,
+    hidden: bool,
+    /// Text set over IPC, shown instead of the block's widgets.
+    text: Option<TextWidget>,
+    /// The numbers behind the placeholders that the block rendered last.
+    numbers: Vec<f64>,
+}
+
+impl Common {
+    fn request_update(&self) -> Result<()> {
+        self.update_request.send(Task {
+            id: self.block.id().to_string(),
+            update_time: Instant::now(),
+        })?;
+        Ok(())
+    }
+
+    /// Clicks may render the block again, like the time block switching its format.
+    fn click_block(&mut self, event: &I3BarEvent) -> Result<()> {
+        let block = &mut self.block;
+        let (result, numbers) = collect_rendered_numbers(|| block.click(event));
+        self.keep_numbers(numbers);
+        result
+    }
+
+    /// Keeps the numbers of the last time the block rendered placeholders. Updates that do
+    /// not render anything leave the text of the block as it was.
+    fn keep_numbers(&mut self, numbers: Vec<f64>) {
+        if !numbers.is_empty() {
+            self.numbers = numbers;
+        }
+    }
+}
+
+impl Block for Common {
+    fn id(&self) -> &str {
+        self.block.id()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.hidden {
+            return Vec::new();
+        }
+        match self.text {
+            Some(ref text) => vec![text],
+            None => {
+                let widgets = self.block.view();
+                if self.common.hides(&widgets, &self.numbers) {
+                    return Vec::new();
+                }
+                widgets
+            }
+        }
+    }
+
+    fn views(&self) -> Vec<(&str, Vec<&dyn I3BarWidget>)> {
+        if self.hidden || self.text.is_some() {
+            return vec![(self.id(), self.view())];
+        }
+        let views = self.block.views();
+        let widgets = views
+            .iter()
+            .flat_map(|(_, widgets)| widgets.iter().copied())
+            .collect::<Vec<_>>();
+        if self.common.hides(&widgets, &self.numbers) {
+            return Vec::new();
+        }
+        views
+    }
+
+    fn update(&mut self) -> Result<Option<Update>> {
+        let block = &mut self.block;
+        let (update, numbers) = collect_rendered_numbers(|| block.update());
+        self.keep_numbers(numbers);
+        update
+    }
+
+    fn signal(&mut self, signal: i32) -> Result<()> {
+        if self.signal == Some(signal) {
+            self.request_update()?;
+        }
+        self.block.signal(signal)
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        // Every widget carries its block's id as the instance. Only clicks on the main
+        // widget are considered, named widgets such as music buttons keep their actions.
+        let id = self.block.id();
+        let on_block =
+            event.instance.as_deref() == Some(id) && event.name.iter().all(|name| name == id);
+        match self.common.command(event) {
+            Some(cmd) if on_block && !self.block.handles_click(event) => {
+                spawn_child_async(self.block.shell(), &["-c", cmd])
+                    .block_error("click", "could not spawn child")?;
+                self.click_block(event)?;
+                self.request_update()
+            }
+            _ => self.click_block(event),
+        }
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        self.block.handles_click(event)
+    }
+
+    fn command(&mut self, request: &IpcRequest) -> Result<bool> {
+        if self.common.ipc_name.as_ref() != Some(&request.block) {
+            // Containers such as `group` pass the command on to their blocks
+            return self.block.command(request);
+        }
+        match request.command {
+            IpcCommand::Refresh => self.request_update()?,
+            IpcCommand::SetText(ref text) if text.is_empty() => self.text = None,
+            IpcCommand::SetText(ref text) => {
+                self.text = Some(TextWidget::new(self.config.clone()).with_text(text))
+            }
+            IpcCommand::Show => self.hidden = false,
+            IpcCommand::Hide => self.hidden = true,
+            IpcCommand::Toggle => self.hidden = !self.hidden,
+            // Containers do not know their `ipc_name`, so they are addressed by their id. Other
+            // blocks ignore these commands.
+            IpcCommand::Collapse | IpcCommand::Expand | IpcCommand::ToggleCollapse => {
+                self.block.command(&IpcRequest {
+                    block: self.block.id().to_string(),
+                    command: request.command.clone(),
+                })?;
+            }
+        }
+        Ok(true)
+    }
+}
+
+/// Keeps the bar running when a block fails. The error is shown in place of the block's
+/// widgets until an update succeeds again, and failed updates are retried at the block's
+/// own interval, which is doubled after every failure if `error_backoff` is set.
+struct CatchErrors {
+    block: Box<dyn Block>,
+    config: Config,
+    error: Option<TextWidget>,
+    /// Whether the error is the one of a click, which the next click clears.
+    click_error: bool,
+    /// The interval of the last successful update.
+    interval: Duration,
+    failures: u32,
+    update_request: Sender<Task>,
+}
+
+impl CatchErrors {
+    /// The retry interval for blocks that have no interval of their own.
+    const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+    /// Backoff never delays a retry further than this, unless the block's interval is longer.
+    const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+    /// Shows the error of a click, signal or command until the next click or update. Blocks
+    /// that are not updated on their own, like blocks with `Update::Once`, are asked for an
+    /// update, which clears the error.
+    fn show_event_error(&mut self, error: Error) {
+        self.show_error(error);
+        self.click_error = true;
+        let _ = self.update_request.send(Task {
+            id: self.id().to_string(),
+            update_time: Instant::now() + Self::DEFAULT_INTERVAL,
+        });
+    }
+
+    fn show_error(&mut self, error: Error) {
+        let message = match error {
+            BlockError(_, message) => message,
+            error => error.to_string(),
+        };
+        self.error = Some(
+            TextWidget::new(self.config.clone())
+                .with_icon("error")
+                .with_state(State::Critical)
+                .with_text(&message),
+        );
+    }
+
+    fn retry_interval(&self) -> Duration {
+        if !self.config.error_backoff {
+            return self.interval;
+        }
+        let backoff = self.interval * 2u32.saturating_pow(self.failures.min(16) - 1);
+        backoff.min(Self::MAX_BACKOFF.max(self.interval))
+    }
+}
+
+impl Block for CatchErrors {
+    fn id(&self) -> &str {
+        self.block.id()
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        match self.error {
+            Some(ref error) => vec![error],
+            None => self.block.view(),
+        }
+    }
+
+    fn views(&self) -> Vec<(&str, Vec<&dyn I3BarWidget>)> {
+        match self.error {
+            Some(_) => vec![(self.id(), self.view())],
+            None => self.block.views(),
+        }
+    }
+
+    fn update(&mut self) -> Result<Option<Update>> {
+        match self.block.update() {
+            Ok(update) => {
+                self.error = None;
+                self.click_error = false;
+                self.failures = 0;
+                match update {
+                    Some(Update::Every(interval)) | Some(Update::Aligned(interval)) => {
+                        self.interval = interval
+                    }
+                    _ => {}
+                }
+                Ok(update)
+            }
+            Err(error) => {
+                self.show_error(error);
+                self.click_error = false;
+                self.failures += 1;
+                Ok(Some(Update::Every(self.retry_interval())))
+            }
+        }
+    }
+
+    fn signal(&mut self, signal: i32) -> Result<()> {
+        if let Err(error) = self.block.signal(signal) {
+            self.show_event_error(error);
+        }
+        Ok(())
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        match self.block.click(event) {
+            Ok(()) if self.click_error => {
+                self.error = None;
+                self.click_error = false;
+            }
+            Ok(()) => {}
+            Err(error) => self.show_event_error(error),
+        }
+        Ok(())
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        self.block.handles_click(event)
+    }
+
+    fn command(&mut self, request: &IpcRequest) -> Result<bool> {
+        self.block.command(request).or_else(|error| {
+            self.show_event_error(error);
+            Ok(true)
+        })
+    }
+}
+
+/// Wraps a block so that its errors are shown on the bar instead of stopping it.
+pub fn catch_errors(
+    block: Box<dyn Block>,
+    config: Config,
+    update_request: Sender<Task>,
+) -> Box<dyn Block> {
+    Box::new(CatchErrors {
+        block,
+        config,
+        error: None,
+        click_error: false,
+        interval: CatchErrors::DEFAULT_INTERVAL,
+        failures: 0,
+        update_request,
+    })
+}
+
 pub fn create_block(
     name: &str,
-    block_config: Value,
-    config: Config,
+    mut block_config: Value,
+    mut config: Config,
     update_request: Sender<Task>,
 ) -> Result<Box<dyn Block>> {
+    let mut common = CommonConfig::take_from(&mut block_config)?;
+    if let Some(overrides) = common.theme_overrides.take() {
+        config.theme = config.theme.clone().with_overrides(overrides);
+    }
+    if let Some(gradient) = common.gradient.take() {
+        config.gradient = gradient;
+    }
+    // If the signal is not in the valid range we return an error
+    let signal = common.signal.map(convert_to_valid_signal).transpose()?;
+    let block = create_block_inner(
+        name,
+        block_config,
+        Some((config.clone(), update_request.clone())),
+    )?
+    .internal_error("create_block", "the block was not created")?;
+    if common.is_empty() {
+        return Ok(block);
+    }
+    Ok(Box::new(Common {
+        block,
+        common,
+        config,
+        update_request,
+        signal,
+        hidden: false,
+        text: None,
+        numbers: Vec::new(),
+    }))
+}
+
+/// Checks the settings of a block like `create_block`, without creating the block.
+pub fn check_block(name: &str, mut block_config: Value) -> Result<()> {
+    let common = CommonConfig::take_from(&mut block_config)?;
+    common.signal.map(convert_to_valid_signal).transpose()?;
+    create_block_inner(name, block_config, None).map(|_| ())
+}
+
+/// Creates a block with the global configuration and its update request channel in `context`.
+/// Without a context, the settings of the block are only deserialized.
+fn create_block_inner(
+    name: &str,
+    block_config: Value,
+    context: Option<(Config, Sender<Task>)>,
+) -> Result<Option<Box<dyn Block>>> {
     match name {
         // Please keep these in alphabetical order.
-        "backlight" => block!(Backlight, block_config, config, update_request),
-        "battery" => block!(Battery, block_config, config, update_request),
-        "bluetooth" => block!(Bluetooth, block_config, config, update_request),
-        "cpu" => block!(Cpu, block_config, config, update_request),
-        "custom" => block!(Custom, block_config, config, update_request),
-        "custom_dbus" => block!(CustomDBus, block_config, config, update_request),
-        "disk_space" => block!(DiskSpace, block_config, config, update_request),
-        "docker" => block!(Docker, block_config, config, update_request),
-        "focused_window" => block!(FocusedWindow, block_config, config, update_request),
-        "github" => block!(Github, block_config, config, update_request),
-        "ibus" => block!(IBus, block_config, config, update_request),
-        "kdeconnect" => block!(KDEConnect, block_config, config, update_request),
-        "keyboard_layout" => block!(KeyboardLayout, block_config, config, update_request),
-        "load" => block!(Load, block_config, config, update_request),
-        "maildir" => block!(Maildir, block_config, config, update_request),
-        "memory" => block!(Memory, block_config, config, update_request),
-        "music" => block!(Music, block_config, config, update_request),
-        "net" => block!(Net, block_config, config, update_request),
-        "networkmanager" => block!(NetworkManager, block_config, config, update_request),
-        "notify" => block!(Notify, block_config, config, update_request),
+        "air_quality" => block!(AirQuality, block_config, context),
+        "apt" => block!(Apt, block_config, context),
+        "backlight" => block!(Backlight, block_config, context),
+        "battery" => block!(Battery, block_config, context),
+        "binding_mode" => block!(BindingMode, block_config, context),
+        "bluetooth" => block!(Bluetooth, block_config, context),
+        "btrfs" => block!(Btrfs, block_config, context),
+        "caffeine" => block!(Caffeine, block_config, context),
+        "calendar" => block!(Calendar, block_config, context),
+        "ci" => block!(Ci, block_config, context),
+        "countdown" => block!(Countdown, block_config, context),
+        "cpu" => block!(Cpu, block_config, context),
+        "crypto" => block!(Crypto, block_config, context),
+        "currency" => block!(Currency, block_config, context),
+        "custom" => block!(Custom, block_config, context),
+        "custom_dbus" => block!(CustomDBus, block_config, context),
+        "disk_io" => block!(DiskIo, block_config, context),
+        "disk_space" => block!(DiskSpace, block_config, context),
+        "dnf" => block!(Dnf, block_config, context),
+        "docker" => block!(Docker, block_config, context),
+        "failed_units" => block!(FailedUnits, block_config, context),
+        "feeds" => block!(Feeds, block_config, context),
+        "flatpak" => block!(Flatpak, block_config, context),
+        "focused_window" => block!(FocusedWindow, block_config, context),
+        "fwupd" => block!(Fwupd, block_config, context),
+        "github" => block!(Github, block_config, context),
+        "group" => block!(Group, block_config, context),
+        "ibus" => block!(IBus, block_config, context),
+        "imap" => block!(Imap, block_config, context),
+        #[cfg(feature = "journal")]
+        "journal" => block!(Journal, block_config, context),
+        "kdeconnect" => block!(KDEConnect, block_config, context),
+        "keyboard_layout" => block!(KeyboardLayout, block_config, context),
+        "kubernetes" => block!(Kubernetes, block_config, context),
+        "libvirt" => block!(Libvirt, block_config, context),
+        "load" => block!(Load, block_config, context),
+        "lock_keys" => block!(LockKeys, block_config, context),
+        "maildir" => block!(Maildir, block_config, context),
+        "matrix" => block!(Matrix, block_config, context),
+        "memory" => block!(Memory, block_config, context),
+        "moon" => block!(Moon, block_config, context),
+        "mpd" => block!(Mpd, block_config, context),
+        "mqtt" => block!(Mqtt, block_config, context),
+        "music" => block!(Music, block_config, context),
+        "net" => block!(Net, block_config, context),
+        "networkmanager" => block!(NetworkManager, block_config, context),
+        "notification_count" => block!(NotificationCount, block_config, context),
+        "notify" => block!(Notify, block_config, context),
         #[cfg(feature = "notmuch")]
-        "notmuch" => block!(Notmuch, block_config, config, update_request),
-        "nvidia_gpu" => block!(NvidiaGpu, block_config, config, update_request),
-        "pacman" => block!(Pacman, block_config, config, update_request),
-        "pomodoro" => block!(Pomodoro, block_config, config, update_request),
-        "sound" => block!(Sound, block_config, config, update_request),
-        "speedtest" => block!(SpeedTest, block_config, config, update_request),
-        "taskwarrior" => block!(Taskwarrior, block_config, config, update_request),
-        "temperature" => block!(Temperature, block_config, config, update_request),
-        "template" => block!(Template, block_config, config, update_request),
-        "time" => block!(Time, block_config, config, update_request),
-        "toggle" => block!(Toggle, block_config, config, update_request),
-        "uptime" => block!(Uptime, block_config, config, update_request),
-        "watson" => block!(Watson, block_config, config, update_request),
-        "weather" => block!(Weather, block_config, config, update_request),
-        "xrandr" => block!(Xrandr, block_config, config, update_request),
-        "hueshift" => block!(Hueshift, block_config, config, update_request),
+        "notmuch" => block!(Notmuch, block_config, context),
+        "nvidia_gpu" => block!(NvidiaGpu, block_config, context),
+        "pacman" => block!(Pacman, block_config, context),
+        "ping" => block!(Ping, block_config, context),
+        "pomodoro" => block!(Pomodoro, block_config, context),
+        "pressure" => block!(Pressure, block_config, context),
+        "public_ip" => block!(PublicIp, block_config, context),
+        "raid" => block!(Raid, block_config, context),
+        "reboot_required" => block!(RebootRequired, block_config, context),
+        "rest" => block!(Rest, block_config, context),
+        "scratchpad" => block!(Scratchpad, block_config, context),
+        "screenshare" => block!(Screenshare, block_config, context),
+        "smart" => block!(Smart, block_config, context),
+        "sound" => block!(Sound, block_config, context),
+        "speedtest" => block!(SpeedTest, block_config, context),
+        "stocks" => block!(Stocks, block_config, context),
+        "stopwatch" => block!(Stopwatch, block_config, context),
+        "sun" => block!(Sun, block_config, context),
+        "systemd" => block!(Systemd, block_config, context),
+        "taskwarrior" => block!(Taskwarrior, block_config, context),
+        "telegram" => block!(Telegram, block_config, context),
+        "temperature" => block!(Temperature, block_config, context),
+        "template" => block!(Template, block_config, context),
+        "time" => block!(Time, block_config, context),
+        "todotxt" => block!(Todotxt, block_config, context),
+        "toggle" => block!(Toggle, block_config, context),
+        "transit" => block!(Transit, block_config, context),
+        "ups" => block!(Ups, block_config, context),
+        "uptime" => block!(Uptime, block_config, context),
+        "uv" => block!(Uv, block_config, context),
+        "vpn" => block!(Vpn, block_config, context),
+        "watson" => block!(Watson, block_config, context),
+        "weather" => block!(Weather, block_config, context),
+        "webcam" => block!(Webcam, block_config, context),
+        "weechat" => block!(Weechat, block_config, context),
+        "wifi" => block!(Wifi, block_config, context),
+        "xrandr" => block!(Xrandr, block_config, context),
+        "zfs" => block!(Zfs, block_config, context),
+        "hueshift" => block!(Hueshift, block_config, context),
         other => Err(BlockError(other.to_string(), "Unknown block!".to_string())),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{catch_errors, Block, Common, CommonConfig, CustomDBusConfig, SoundConfig, Update};
+    use crate::config::Config;
+    use crate::errors::*;
+    use crate::input::{I3BarEvent, MouseButton};
+    use crate::util::{FormatTemplate, Number};
+    use crate::widget::I3BarWidget;
+    use crate::widgets::text::TextWidget;
+    use serde::de::Deserialize;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use toml::value::Value;
+
+    /// A block that records the buttons it was clicked with, and handles right clicks.
+    struct Clicked(Rc<RefCell<Vec<MouseButton>>>);
+
+    impl Block for Clicked {
+        fn id(&self) -> &str {
+            "clicked"
+        }
+
+        fn view(&self) -> Vec<&dyn I3BarWidget> {
+            vec![]
+        }
+
+        fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+            self.0.borrow_mut().push(event.button);
+            Ok(())
+        }
+
+        fn handles_click(&self, event: &I3BarEvent) -> bool {
+            event.button == MouseButton::Right
+        }
+    }
+
+    fn click(button: MouseButton) -> I3BarEvent {
+        I3BarEvent {
+            name: Some("clicked".to_string()),
+            instance: Some("clicked".to_string()),
+            x: 0,
+            y: 0,
+            button,
+            modifiers: vec![],
+        }
+    }
+
+    #[test]
+    fn test_click_command_only_for_unhandled_buttons() {
+        let clicks = Rc::new(RefCell::new(vec![]));
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let mut common = Common {
+            block: Box::new(Clicked(clicks.clone())),
+            common: CommonConfig {
+                on_click: Some("true".to_string()),
+                on_right_click: Some("true".to_string()),
+                ..CommonConfig::default()
+            },
+            config: Config::default(),
+            update_request: tx,
+            signal: None,
+            hidden: false,
+            text: None,
+            numbers: Vec::new(),
+        };
+
+        // The command runs for a button that the block does not handle, which still sees the
+        // click, like the mark as read of the telegram block
+        common.click(&click(MouseButton::Left)).unwrap();
+        assert_eq!(*clicks.borrow(), vec![MouseButton::Left]);
+        assert_eq!(rx.try_recv().unwrap().id, "clicked");
+
+        // A button that the block handles, like the long format of the time block, does not
+        // run the command
+        common.click(&click(MouseButton::Right)).unwrap();
+        assert_eq!(
+            *clicks.borrow(),
+            vec![MouseButton::Left, MouseButton::Right]
+        );
+        assert!(rx.try_recv().is_err());
+
+        // Without a command, only the block handles the click
+        common.click(&click(MouseButton::Middle)).unwrap();
+        assert_eq!(clicks.borrow().len(), 3);
+        assert!(rx.try_recv().is_err());
+    }
+
+    /// A block that shows a speed in bytes per second with a prefix, like `1.5KB`.
+    struct Speed(f64, TextWidget);
+
+    impl Block for Speed {
+        fn id(&self) -> &str {
+            "speed"
+        }
+
+        fn view(&self) -> Vec<&dyn I3BarWidget> {
+            vec![&self.1]
+        }
+
+        fn update(&mut self) -> Result<Option<Update>> {
+            let format = FormatTemplate::from_string("{speed;K}")?;
+            let number = Number {
+                value: self.0,
+                unit: "B",
+            };
+            self.1.set_text(
+                format.render_numbers(&map!("{speed}" => self.0), &map!("{speed}" => number))?,
+            );
+            Ok(None)
+        }
+    }
+
+    #[test]
+    fn test_hide_below_compares_the_raw_numbers() {
+        let (tx, _rx) = crossbeam_channel::unbounded();
+        let mut common = Common {
+            block: Box::new(Speed(1500.0, TextWidget::new(Config::default()))),
+            common: CommonConfig {
+                hide_below: Some(1000.0),
+                ..CommonConfig::default()
+            },
+            config: Config::default(),
+            update_request: tx,
+            signal: None,
+            hidden: false,
+            text: None,
+            numbers: Vec::new(),
+        };
+        common.update().unwrap();
+        // The text shows 1.5, which is below the threshold
+        assert_eq!(common.view().len(), 1);
+
+        common.block = Box::new(Speed(500.0, TextWidget::new(Config::default())));
+        common.update().unwrap();
+        assert!(common.view().is_empty());
+    }
+
+    /// A block whose clicks fail until it is told otherwise.
+    struct FailingClick(Rc<RefCell<bool>>);
+
+    impl Block for FailingClick {
+        fn id(&self) -> &str {
+            "clicked"
+        }
+
+        fn view(&self) -> Vec<&dyn I3BarWidget> {
+            vec![]
+        }
+
+        fn click(&mut self, _: &I3BarEvent) -> Result<()> {
+            if *self.0.borrow() {
+                Err(BlockError("test".to_string(), "click failed".to_string()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    #[test]
+    fn test_click_error_is_cleared() {
+        let failing = Rc::new(RefCell::new(true));
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let mut block = catch_errors(
+            Box::new(FailingClick(failing.clone())),
+            Config::default(),
+            tx,
+        );
+
+        block.click(&click(MouseButton::Left)).unwrap();
+        assert_eq!(block.view().len(), 1);
+        // The block is asked for an update, which clears the error of a block that is not
+        // updated on its own
+        assert_eq!(rx.try_recv().unwrap().id, "clicked");
+
+        // The next click that works clears it right away
+        *failing.borrow_mut() = false;
+        block.click(&click(MouseButton::Left)).unwrap();
+        assert!(block.view().is_empty());
+
+        *failing.borrow_mut() = true;
+        block.click(&click(MouseButton::Left)).unwrap();
+        assert_eq!(block.view().len(), 1);
+        assert_eq!(block.update().unwrap(), None);
+        assert!(block.view().is_empty());
+    }
+
+    #[test]
+    fn test_block_name_is_not_common() {
+        let mut block_config: Value =
+            toml::from_str("name = \"status\"\nipc_name = \"dbus\"\nsignal = 2").unwrap();
+        let common = CommonConfig::take_from(&mut block_config).unwrap();
+        assert_eq!(common.ipc_name.as_deref(), Some("dbus"));
+        assert_eq!(common.signal, Some(2));
+        let custom_dbus = CustomDBusConfig::deserialize(block_config).unwrap();
+        assert_eq!(custom_dbus.name, "status");
+
+        let mut block_config: Value =
+            toml::from_str("name = \"Capture\"\non_click = \"pavucontrol\"").unwrap();
+        let common = CommonConfig::take_from(&mut block_config).unwrap();
+        assert_eq!(common.on_click.as_deref(), Some("pavucontrol"));
+        let sound = SoundConfig::deserialize(block_config).unwrap();
+        assert_eq!(sound.name.as_deref(), Some("Capture"));
+    }
+}
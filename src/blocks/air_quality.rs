@@ -0,0 +1,436 @@
+//! A block for the air quality index of a station, from the World Air Quality Index or OpenAQ.
+
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{http_get, pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+const WAQI_TOKEN_ENV: &str = "WAQI_TOKEN";
+const OPENAQ_API_KEY_ENV: &str = "OPENAQ_API_KEY";
+
+/// The breakpoints of the US EPA for PM2.5 in µg/m³ (as of 2024), with their indexes.
+const PM25_BREAKPOINTS: &[(f64, f64, f64, f64)] = &[
+    (0.0, 9.0, 0.0, 50.0),
+    (9.1, 35.4, 51.0, 100.0),
+    (35.5, 55.4, 101.0, 150.0),
+    (55.5, 125.4, 151.0, 200.0),
+    (125.5, 225.4, 201.0, 300.0),
+    (225.5, 325.4, 301.0, 500.0),
+];
+
+/// The breakpoints of the US EPA for PM10 in µg/m³, with their indexes.
+const PM10_BREAKPOINTS: &[(f64, f64, f64, f64)] = &[
+    (0.0, 54.0, 0.0, 50.0),
+    (55.0, 154.0, 51.0, 100.0),
+    (155.0, 254.0, 101.0, 150.0),
+    (255.0, 354.0, 151.0, 200.0),
+    (355.0, 424.0, 201.0, 300.0),
+    (425.0, 604.0, 301.0, 500.0),
+];
+
+/// The index of a concentration, interpolated between the breakpoints. Concentrations are
+/// truncated to the precision of the breakpoints first, and those above them count as 500.
+fn aqi(breakpoints: &[(f64, f64, f64, f64)], precision: f64, concentration: f64) -> f64 {
+    let concentration = (concentration * precision).floor() / precision;
+    for &(c_low, c_high, i_low, i_high) in breakpoints {
+        if concentration <= c_high {
+            let concentration = concentration.max(c_low);
+            return ((i_high - i_low) / (c_high - c_low) * (concentration - c_low) + i_low).round();
+        }
+    }
+    500.0
+}
+
+fn malformed_json_error() -> Error {
+    BlockError("air_quality".to_string(), "Malformed JSON.".to_string())
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "name", rename_all = "lowercase")]
+pub enum AirQualityService {
+    Waqi {
+        #[serde(default = "AirQualityService::getenv_waqi_token")]
+        token: Option<String>,
+        /// A city, or a station id like `@1451`
+        #[serde(default)]
+        station: Option<String>,
+        #[serde(default)]
+        latitude: Option<f64>,
+        #[serde(default)]
+        longitude: Option<f64>,
+    },
+    OpenAq {
+        #[serde(default = "AirQualityService::getenv_openaq_api_key")]
+        api_key: Option<String>,
+        /// The id of a location, like `2178`
+        #[serde(default)]
+        location_id: Option<u64>,
+        #[serde(default)]
+        latitude: Option<f64>,
+        #[serde(default)]
+        longitude: Option<f64>,
+    },
+}
+
+impl AirQualityService {
+    fn getenv_waqi_token() -> Option<String> {
+        env::var(WAQI_TOKEN_ENV).ok()
+    }
+
+    fn getenv_openaq_api_key() -> Option<String> {
+        env::var(OPENAQ_API_KEY_ENV).ok()
+    }
+}
+
+/// The air quality at a station.
+#[derive(Debug, Default, PartialEq)]
+struct Measurement {
+    station: String,
+    aqi: Option<f64>,
+    /// The index of PM2.5
+    pm25: Option<f64>,
+    /// The index of PM10
+    pm10: Option<f64>,
+    /// PM2.5 in µg/m³, if the service reports concentrations
+    pm25_concentration: Option<f64>,
+    /// PM10 in µg/m³, if the service reports concentrations
+    pm10_concentration: Option<f64>,
+    dominant: Option<String>,
+}
+
+/// The answer of `/feed/` of the World Air Quality Index, whose `pm25` and `pm10` are
+/// indexes rather than concentrations, which it does not report.
+fn parse_waqi(json: &Value) -> Result<Measurement> {
+    if json["status"] != "ok" {
+        return Err(BlockError(
+            "air_quality".to_string(),
+            format!(
+                "API Error: {}",
+                json["data"].as_str().unwrap_or("unknown error")
+            ),
+        ));
+    }
+    let data = &json["data"];
+    Ok(Measurement {
+        station: data
+            .pointer("/city/name")
+            .and_then(|v| v.as_str())
+            .ok_or_else(malformed_json_error)?
+            .to_string(),
+        // Stations without a current value give "-"
+        aqi: data["aqi"].as_f64(),
+        pm25: data.pointer("/iaqi/pm25/v").and_then(|v| v.as_f64()),
+        pm10: data.pointer("/iaqi/pm10/v").and_then(|v| v.as_f64()),
+        pm25_concentration: None,
+        pm10_concentration: None,
+        dominant: data["dominentpol"]
+            .as_str()
+            .filter(|s| !s.is_empty())
+            .map(|s| s.to_string()),
+    })
+}
+
+/// A location of OpenAQ v3, with its latest values, which only refer to the sensors of the
+/// location by id. The index is computed from the concentrations like the US EPA does.
+fn parse_openaq(location: &Value, latest: &Value) -> Result<Measurement> {
+    let empty = Vec::new();
+    let parameters: HashMap<i64, &str> = location["sensors"]
+        .as_array()
+        .unwrap_or(&empty)
+        .iter()
+        .filter_map(|sensor| {
+            Some((
+                sensor["id"].as_i64()?,
+                sensor.pointer("/parameter/name")?.as_str()?,
+            ))
+        })
+        .collect();
+    let value = |parameter: &str| {
+        latest["results"]
+            .as_array()
+            .unwrap_or(&empty)
+            .iter()
+            .find(|result| {
+                result["sensorsId"]
+                    .as_i64()
+                    .and_then(|id| parameters.get(&id))
+                    .map_or(false, |name| *name == parameter)
+            })
+            .and_then(|result| result["value"].as_f64())
+    };
+
+    let pm25 = value("pm25");
+    let pm10 = value("pm10");
+    let pm25_aqi = pm25.map(|c| aqi(PM25_BREAKPOINTS, 10.0, c));
+    let pm10_aqi = pm10.map(|c| aqi(PM10_BREAKPOINTS, 1.0, c));
+    let (aqi, dominant) = match (pm25_aqi, pm10_aqi) {
+        (Some(pm25), Some(pm10)) if pm10 > pm25 => (Some(pm10), Some("pm10")),
+        (Some(pm25), _) => (Some(pm25), Some("pm25")),
+        (None, Some(pm10)) => (Some(pm10), Some("pm10")),
+        (None, None) => (None, None),
+    };
+    Ok(Measurement {
+        station: location["name"]
+            .as_str()
+            .ok_or_else(malformed_json_error)?
+            .to_string(),
+        aqi,
+        pm25: pm25_aqi,
+        pm10: pm10_aqi,
+        pm25_concentration: pm25,
+        pm10_concentration: pm10,
+        dominant: dominant.map(|s| s.to_string()),
+    })
+}
+
+pub struct AirQuality {
+    id: String,
+    text: TextWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    service: AirQualityService,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AirQualityConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "AirQualityConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format string
+    #[serde(default = "AirQualityConfig::default_format")]
+    pub format: String,
+
+    /// The service and the station to ask it about
+    pub service: AirQualityService,
+}
+
+impl AirQualityConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(1800)
+    }
+
+    fn default_format() -> String {
+        "AQI {aqi}".to_owned()
+    }
+}
+
+impl ConfigBlock for AirQuality {
+    type Config = AirQualityConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        Ok(AirQuality {
+            id: pseudo_uuid(),
+            text: TextWidget::new(config),
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("air_quality", "Invalid format specified")?,
+            service: block_config.service,
+        })
+    }
+}
+
+fn fetch(url: &str, headers: &[String]) -> Result<Value> {
+    http_get("air_quality", url, headers, Duration::from_secs(10))?.json("air_quality")
+}
+
+fn missing(member: &str, env: &str) -> Error {
+    BlockError(
+        "air_quality".to_string(),
+        format!(
+            "Missing member 'service.{}'. Add the member or configure with the environment variable {}",
+            member, env
+        ),
+    )
+}
+
+impl AirQuality {
+    fn measure(&self) -> Result<Measurement> {
+        match self.service {
+            AirQualityService::Waqi {
+                ref token,
+                ref station,
+                latitude,
+                longitude,
+            } => {
+                let token = token
+                    .as_ref()
+                    .ok_or_else(|| missing("token", WAQI_TOKEN_ENV))?;
+                let feed = match (station, latitude, longitude) {
+                    (Some(station), _, _) => station.clone(),
+                    (None, Some(latitude), Some(longitude)) => {
+                        format!("geo:{};{}", latitude, longitude)
+                    }
+                    // The station closest to the IP address
+                    _ => "here".to_string(),
+                };
+                let json = fetch(
+                    &format!("https://api.waqi.info/feed/{}/?token={}", feed, token),
+                    &[],
+                )?;
+                parse_waqi(&json)
+            }
+            AirQualityService::OpenAq {
+                ref api_key,
+                location_id,
+                latitude,
+                longitude,
+            } => {
+                let api_key = api_key
+                    .as_ref()
+                    .ok_or_else(|| missing("api_key", OPENAQ_API_KEY_ENV))?;
+                let headers = [format!("X-API-Key: {}", api_key)];
+                let locations = match (location_id, latitude, longitude) {
+                    (Some(id), _, _) => fetch(
+                        &format!("https://api.openaq.org/v3/locations/{}", id),
+                        &headers,
+                    )?,
+                    // The closest location within 25 km, the most the API allows
+                    (None, Some(latitude), Some(longitude)) => fetch(
+                        &format!(
+                            "https://api.openaq.org/v3/locations?coordinates={},{}&radius=25000&parameters_id=2&limit=1",
+                            latitude, longitude
+                        ),
+                        &headers,
+                    )?,
+                    _ => {
+                        return Err(BlockError(
+                            "air_quality".to_string(),
+                            "Either 'service.location_id' or 'service.latitude' and 'service.longitude' must be provided.".to_string(),
+                        ))
+                    }
+                };
+                let location = locations.pointer("/results/0").ok_or_else(|| {
+                    BlockError(
+                        "air_quality".to_string(),
+                        "no location of OpenAQ found".to_string(),
+                    )
+                })?;
+                let id = location["id"].as_u64().ok_or_else(malformed_json_error)?;
+                let latest = fetch(
+                    &format!("https://api.openaq.org/v3/locations/{}/latest", id),
+                    &headers,
+                )?;
+                parse_openaq(location, &latest)
+            }
+        }
+    }
+}
+
+impl Block for AirQuality {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let measurement = self.measure()?;
+
+        let show = |value: Option<f64>| value.map_or("-".to_string(), |v| format!("{:.0}", v));
+        let values = map!(
+            "{aqi}" => show(measurement.aqi),
+            "{pm25}" => show(measurement.pm25),
+            "{pm10}" => show(measurement.pm10),
+            "{pm25_concentration}" => show(measurement.pm25_concentration),
+            "{pm10_concentration}" => show(measurement.pm10_concentration),
+            "{dominant}" => measurement.dominant.unwrap_or_else(|| "-".to_string()),
+            "{station}" => measurement.station
+        );
+        self.text.set_text(self.format.render_static_str(&values)?);
+
+        // The colors of the AQI: green, yellow, orange and red or worse
+        self.text.set_state(match measurement.aqi {
+            None => State::Idle,
+            Some(aqi) if aqi <= 50.0 => State::Good,
+            Some(aqi) if aqi <= 100.0 => State::Info,
+            Some(aqi) if aqi <= 150.0 => State::Warning,
+            Some(_) => State::Critical,
+        });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{aqi, parse_openaq, parse_waqi, PM10_BREAKPOINTS, PM25_BREAKPOINTS};
+
+    #[test]
+    fn test_aqi() {
+        assert_eq!(aqi(PM25_BREAKPOINTS, 10.0, 0.0), 0.0);
+        assert_eq!(aqi(PM25_BREAKPOINTS, 10.0, 9.0), 50.0);
+        assert_eq!(aqi(PM25_BREAKPOINTS, 10.0, 9.05), 50.0);
+        assert_eq!(aqi(PM25_BREAKPOINTS, 10.0, 35.4), 100.0);
+        assert_eq!(aqi(PM25_BREAKPOINTS, 10.0, 45.0), 124.0);
+        assert_eq!(aqi(PM25_BREAKPOINTS, 10.0, 1000.0), 500.0);
+        assert_eq!(aqi(PM10_BREAKPOINTS, 1.0, 54.9), 50.0);
+        assert_eq!(aqi(PM10_BREAKPOINTS, 1.0, 154.0), 100.0);
+    }
+
+    #[test]
+    fn test_parse_waqi() {
+        let json = serde_json::json!({"status": "ok", "data": {
+            "aqi": 74,
+            "dominentpol": "pm25",
+            "city": {"name": "Berlin Mitte"},
+            "iaqi": {"pm25": {"v": 74}, "pm10": {"v": 31}, "t": {"v": 12.5}}
+        }});
+        let measurement = parse_waqi(&json).unwrap();
+        assert_eq!(measurement.station, "Berlin Mitte");
+        assert_eq!(measurement.aqi, Some(74.0));
+        assert_eq!(measurement.pm10, Some(31.0));
+        assert_eq!(measurement.pm25_concentration, None);
+        assert_eq!(measurement.dominant, Some("pm25".to_string()));
+
+        let json = serde_json::json!({"status": "ok", "data": {
+            "aqi": "-", "dominentpol": "", "city": {"name": "Offline"}, "iaqi": {}
+        }});
+        let measurement = parse_waqi(&json).unwrap();
+        assert_eq!(measurement.aqi, None);
+        assert_eq!(measurement.dominant, None);
+
+        let json = serde_json::json!({"status": "error", "data": "Invalid key"});
+        assert!(parse_waqi(&json).is_err());
+    }
+
+    #[test]
+    fn test_parse_openaq() {
+        let location = serde_json::json!({"id": 2178, "name": "Del Norte", "sensors": [
+            {"id": 3917, "parameter": {"name": "pm25", "units": "µg/m³"}},
+            {"id": 3918, "parameter": {"name": "pm10", "units": "µg/m³"}},
+            {"id": 3919, "parameter": {"name": "o3", "units": "ppm"}}
+        ]});
+        let latest = serde_json::json!({"results": [
+            {"sensorsId": 3919, "value": 0.03},
+            {"sensorsId": 3918, "value": 200.0},
+            {"sensorsId": 3917, "value": 12.0}
+        ]});
+        let measurement = parse_openaq(&location, &latest).unwrap();
+        assert_eq!(measurement.station, "Del Norte");
+        assert_eq!(measurement.pm25, Some(56.0));
+        assert_eq!(measurement.pm10, Some(123.0));
+        assert_eq!(measurement.pm25_concentration, Some(12.0));
+        assert_eq!(measurement.pm10_concentration, Some(200.0));
+        assert_eq!(measurement.aqi, Some(123.0));
+        assert_eq!(measurement.dominant, Some("pm10".to_string()));
+    }
+}
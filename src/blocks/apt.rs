@@ -0,0 +1,199 @@
+//! A block for the pending updates of apt, on Debian and Ubuntu.
+
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+/// Counts the upgrades, and those from a security archive, in the output of
+/// `apt-get -s dist-upgrade`, where an upgrade looks like
+/// `Inst libc6 [2.36-9] (2.36-9+deb12u4 Debian-Security:12/stable-security [amd64])`.
+fn count_updates(simulation: &str) -> (usize, usize) {
+    let upgrades: Vec<&str> = simulation
+        .lines()
+        .filter(|line| line.starts_with("Inst "))
+        .collect();
+    let security = upgrades
+        .iter()
+        .filter(|line| line.contains("-security") || line.contains("Security"))
+        .count();
+    (upgrades.len(), security)
+}
+
+pub struct Apt {
+    id: String,
+    output: ButtonWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    format_singular: FormatTemplate,
+    format_up_to_date: FormatTemplate,
+    update_command: Option<String>,
+    tx_update_request: Sender<Task>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct AptConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "AptConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format override
+    #[serde(default = "AptConfig::default_format")]
+    pub format: String,
+
+    /// Alternative format override for when exactly 1 update is available
+    #[serde(default = "AptConfig::default_format")]
+    pub format_singular: String,
+
+    /// Alternative format override for when no updates are available
+    #[serde(default = "AptConfig::default_format")]
+    pub format_up_to_date: String,
+
+    /// Optional command to run on a left click, like a terminal running the upgrade.
+    /// The updates are checked again once it exits.
+    #[serde(default)]
+    pub update_command: Option<String>,
+}
+
+impl AptConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(60 * 30)
+    }
+
+    fn default_format() -> String {
+        "{count}".to_owned()
+    }
+}
+
+impl ConfigBlock for Apt {
+    type Config = AptConfig;
+
+    fn new(
+        block_config: Self::Config,
+        config: Config,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let id = pseudo_uuid();
+        Ok(Apt {
+            output: ButtonWidget::new(config, &id).with_icon("update"),
+            id,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("apt", "Invalid format specified for apt::format")?,
+            format_singular: FormatTemplate::from_string(&block_config.format_singular)
+                .block_error("apt", "Invalid format specified for apt::format_singular")?,
+            format_up_to_date: FormatTemplate::from_string(&block_config.format_up_to_date)
+                .block_error("apt", "Invalid format specified for apt::format_up_to_date")?,
+            update_command: block_config.update_command,
+            tx_update_request,
+        })
+    }
+}
+
+impl Block for Apt {
+    fn update(&mut self) -> Result<Option<Update>> {
+        // Simulating needs no root, but only sees the package lists of the last `apt update`
+        let output = Command::new("apt-get")
+            .env("LC_ALL", "C")
+            .args(&["-s", "-q", "dist-upgrade"])
+            .output()
+            .block_error("apt", "failed to run apt-get")?;
+        let (count, security) = count_updates(&String::from_utf8_lossy(&output.stdout));
+
+        let values = map!(
+            "{count}" => count,
+            "{security}" => security
+        );
+        self.output.set_text(match count {
+            0 => self.format_up_to_date.render_static_str(&values)?,
+            1 => self.format_singular.render_static_str(&values)?,
+            _ => self.format.render_static_str(&values)?,
+        });
+        self.output.set_state(match (count, security) {
+            (0, _) => State::Idle,
+            (_, 0) => State::Info,
+            _ => State::Warning,
+        });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if !event.matches_name(&self.id) {
+            return Ok(());
+        }
+        match (event.button, &self.update_command) {
+            (MouseButton::Left, Some(command)) => {
+                // The upgrade takes a while, so the bar must not wait for it
+                let command = command.clone();
+                let id = self.id.clone();
+                let tx = self.tx_update_request.clone();
+                thread::Builder::new()
+                    .name("apt".into())
+                    .spawn(move || {
+                        if Command::new("sh").args(&["-c", &command]).status().is_ok() {
+                            let _ = tx.send(Task {
+                                id,
+                                update_time: Instant::now(),
+                            });
+                        }
+                    })
+                    .block_error("apt", "failed to start the update command")?;
+            }
+            (MouseButton::Left, None) | (MouseButton::Right, _) => {
+                self.update()?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left && self.update_command.is_some()
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::count_updates;
+
+    #[test]
+    fn test_count_updates() {
+        let simulation = "\
+NOTE: This is only a simulation!
+Reading package lists...
+The following packages will be upgraded:
+  curl libc6
+2 upgraded, 0 newly installed, 0 to remove and 0 not upgraded.
+Inst libc6 [2.36-9] (2.36-9+deb12u4 Debian-Security:12/stable-security [amd64])
+Inst curl [7.88.1-10] (7.88.1-10+deb12u5 Debian:12.5/stable [amd64])
+Conf libc6 (2.36-9+deb12u4 Debian-Security:12/stable-security [amd64])
+Conf curl (7.88.1-10+deb12u5 Debian:12.5/stable [amd64])";
+        assert_eq!(count_updates(simulation), (2, 1));
+        assert_eq!(count_updates(""), (0, 0));
+    }
+}
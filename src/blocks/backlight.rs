@@ -5,12 +5,14 @@
 //! levels are read from and written to the `sysfs` filesystem, so this block
 //! does not depend on `xrandr` (and thus it works on Wayland). To set
 //! brightness levels using `xrandr`, see the
-//! [`Xrandr`](../xrandr/struct.Xrandr.html) block.
+//! [`Xrandr`](../xrandr/struct.Xrandr.html) block. External monitors are
+//! supported over DDC/CI, using `ddcutil`.
 
+use std::cell::Cell;
 use std::fs::OpenOptions;
 use std::io::prelude::*;
 use std::path::{Path, PathBuf};
-use std::thread;
+use std::process::Command;
 use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
@@ -20,10 +22,12 @@ use serde_derive::Deserialize;
 use crate::blocks::Update;
 use crate::blocks::{Block, ConfigBlock};
 use crate::config::{Config, LogicalDirection, Scrolling};
+use crate::de::deserialize_duration;
 use crate::errors::*;
-use crate::input::I3BarEvent;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::reactor;
 use crate::scheduler::Task;
-use crate::util::pseudo_uuid;
+use crate::util::{pseudo_uuid, FormatTemplate};
 use crate::widget::I3BarWidget;
 use crate::widgets::button::ButtonWidget;
 
@@ -43,6 +47,15 @@ fn read_brightness(device_file: &Path) -> Result<u64> {
         .block_error("backlight", "Failed to read value from brightness file")
 }
 
+/// A device whose brightness can be queried and set, in percent.
+pub trait BrightnessDevice {
+    /// Query the brightness value for this device, as a percent.
+    fn brightness(&self) -> Result<u64>;
+
+    /// Set the brightness value for this device, as a percent.
+    fn set_brightness(&self, value: u64) -> Result<()>;
+}
+
 /// Represents a physical backlit device whose brightness level can be queried.
 pub struct BacklitDevice {
     max_brightness: u64,
@@ -112,27 +125,7 @@ impl BacklitDevice {
         })
     }
 
-    /// Query the brightness value for this backlit device, as a percent.
-    pub fn brightness(&self) -> Result<u64> {
-        let raw = read_brightness(&self.brightness_file())?;
-        let brightness_ratio =
-            (raw as f64 / self.max_brightness as f64).powf(self.root_scaling.recip());
-        let brightness = (brightness_ratio * 100.0).round() as u64;
-        match brightness {
-            0..=100 => Ok(brightness),
-            _ => Ok(100),
-        }
-    }
-
-    /// Set the brightness value for this backlit device, as a percent.
-    pub fn set_brightness(&self, value: u64) -> Result<()> {
-        let safe_value = match value {
-            0..=100 => value,
-            _ => 100,
-        };
-        let ratio = (safe_value as f64 / 100.0).powf(self.root_scaling);
-        let raw = std::cmp::max(1, (ratio * (self.max_brightness as f64)).round() as u64);
-
+    fn set_brightness_raw(&self, raw: u64) -> Result<()> {
         let file = OpenOptions::new()
             .write(true)
             .open(self.device_path.join("brightness"));
@@ -186,27 +179,265 @@ impl BacklitDevice {
     }
 }
 
+/// Converts a raw brightness value into a percent.
+fn raw_to_percent(raw: u64, max: u64, root_scaling: f64) -> u64 {
+    let brightness_ratio = (raw as f64 / max as f64).powf(root_scaling.recip());
+    let brightness = (brightness_ratio * 100.0).round() as u64;
+    match brightness {
+        0..=100 => brightness,
+        _ => 100,
+    }
+}
+
+/// Converts a percent into a raw brightness value.
+fn percent_to_raw(value: u64, max: u64, root_scaling: f64) -> u64 {
+    let safe_value = match value {
+        0..=100 => value,
+        _ => 100,
+    };
+    let ratio = (safe_value as f64 / 100.0).powf(root_scaling);
+    (ratio * (max as f64)).round() as u64
+}
+
+impl BrightnessDevice for BacklitDevice {
+    fn brightness(&self) -> Result<u64> {
+        let raw = read_brightness(&self.brightness_file())?;
+        Ok(raw_to_percent(raw, self.max_brightness, self.root_scaling))
+    }
+
+    fn set_brightness(&self, value: u64) -> Result<()> {
+        let raw = percent_to_raw(value, self.max_brightness, self.root_scaling);
+        self.set_brightness_raw(std::cmp::max(1, raw))
+    }
+}
+
+/// The brightness VCP feature code of the MCCS standard.
+const VCP_BRIGHTNESS: &str = "10";
+
+/// How long a brightness that was read or set over DDC/CI is trusted, since talking to the
+/// monitor takes a while.
+const DDC_CACHE_DURATION: Duration = Duration::from_secs(1);
+
+/// A monitor, as found by `ddcutil detect --terse`.
+#[derive(Debug, PartialEq)]
+struct DdcDisplay {
+    number: u32,
+    bus: u32,
+    model: String,
+    serial: String,
+}
+
+/// Reads the output of `ddcutil detect --terse`, which lists every display like
+///
+/// ```text
+/// Display 1
+///    I2C bus:  /dev/i2c-4
+///    Monitor:  DEL:DELL U2415:7MT0167B2YNL
+/// ```
+fn parse_ddcutil_detect(output: &str) -> Vec<DdcDisplay> {
+    let mut displays = Vec::new();
+    // Invalid displays are listed too, and their fields are skipped
+    let mut current: Option<DdcDisplay> = None;
+    for line in output.lines() {
+        if !line.starts_with(char::is_whitespace) {
+            displays.extend(current.take());
+            let mut words = line.split_whitespace();
+            if let (Some("Display"), Some(number)) = (words.next(), words.next()) {
+                current = number.parse().ok().map(|number| DdcDisplay {
+                    number,
+                    bus: 0,
+                    model: String::new(),
+                    serial: String::new(),
+                });
+            }
+            continue;
+        }
+        let display = match current {
+            Some(ref mut display) => display,
+            None => continue,
+        };
+        let mut field = line.trim().splitn(2, ':');
+        let key = field.next();
+        let value = field.next().unwrap_or_default().trim();
+        match key {
+            Some("I2C bus") => {
+                display.bus = value.trim_start_matches("/dev/i2c-").parse().unwrap_or(0);
+            }
+            Some("Monitor") => {
+                let mut parts = value.splitn(3, ':');
+                let _manufacturer = parts.next();
+                display.model = parts.next().unwrap_or_default().to_string();
+                display.serial = parts.next().unwrap_or_default().to_string();
+            }
+            _ => {}
+        }
+    }
+    displays.extend(current);
+    displays
+}
+
+/// Reads the output of `ddcutil getvcp 10 --brief`, like `VCP 10 C 50 100`, into the current and
+/// maximum value.
+fn parse_ddcutil_getvcp(output: &str) -> Option<(u64, u64)> {
+    let fields = output.split_whitespace().collect::<Vec<_>>();
+    match fields.as_slice() {
+        ["VCP", _, "C", current, max] => Some((current.parse().ok()?, max.parse().ok()?)),
+        _ => None,
+    }
+}
+
+/// An external monitor whose brightness is queried and set over DDC/CI with `ddcutil`.
+pub struct DdcDevice {
+    bus: u32,
+    root_scaling: f64,
+    /// The maximum of the brightness, as reported by the monitor
+    max_brightness: Cell<Option<u64>>,
+    /// The last brightness that was read or set, in percent
+    cached: Cell<Option<(Instant, u64)>>,
+}
+
+impl DdcDevice {
+    /// Use the monitor whose model or serial number is `device`, or which `ddcutil` numbers
+    /// `device`. Without `device`, the first monitor is used.
+    pub fn from_device(device: Option<String>, root_scaling: f64) -> Result<Self> {
+        let output = Command::new("ddcutil")
+            .args(&["detect", "--terse"])
+            .output()
+            .block_error("backlight", "Failed to execute ddcutil")?;
+        let displays = parse_ddcutil_detect(&String::from_utf8_lossy(&output.stdout));
+        let display = match device {
+            Some(ref device) => displays.into_iter().find(|display| {
+                display.model == *device
+                    || display.serial == *device
+                    || display.number.to_string() == *device
+            }),
+            None => displays.into_iter().next(),
+        }
+        .block_error(
+            "backlight",
+            &format!(
+                "ddcutil found no monitor {}",
+                device.as_deref().unwrap_or("with DDC/CI")
+            ),
+        )?;
+
+        Ok(DdcDevice {
+            bus: display.bus,
+            root_scaling: clamp_root_scaling(root_scaling),
+            max_brightness: Cell::new(None),
+            cached: Cell::new(None),
+        })
+    }
+
+    fn ddcutil(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("ddcutil")
+            .args(&["--bus", &self.bus.to_string()])
+            .args(args)
+            .output()
+            .block_error("backlight", "Failed to execute ddcutil")?;
+        if !output.status.success() {
+            return Err(BlockError(
+                "backlight".to_string(),
+                format!(
+                    "ddcutil failed: {}",
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn read_raw(&self) -> Result<(u64, u64)> {
+        let output = self.ddcutil(&["getvcp", VCP_BRIGHTNESS, "--brief"])?;
+        let (current, max) = parse_ddcutil_getvcp(&output)
+            .block_error("backlight", "Unexpected output of ddcutil getvcp")?;
+        self.max_brightness.set(Some(max));
+        Ok((current, max))
+    }
+}
+
+impl BrightnessDevice for DdcDevice {
+    fn brightness(&self) -> Result<u64> {
+        if let Some((time, brightness)) = self.cached.get() {
+            if time.elapsed() < DDC_CACHE_DURATION {
+                return Ok(brightness);
+            }
+        }
+        let (current, max) = self.read_raw()?;
+        let brightness = raw_to_percent(current, max.max(1), self.root_scaling);
+        self.cached.set(Some((Instant::now(), brightness)));
+        Ok(brightness)
+    }
+
+    fn set_brightness(&self, value: u64) -> Result<()> {
+        let max = match self.max_brightness.get() {
+            Some(max) => max,
+            None => self.read_raw()?.1,
+        };
+        let raw = percent_to_raw(value, max, self.root_scaling);
+        self.ddcutil(&["--noverify", "setvcp", VCP_BRIGHTNESS, &raw.to_string()])?;
+        self.cached.set(Some((
+            Instant::now(),
+            raw_to_percent(raw, max.max(1), self.root_scaling),
+        )));
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BacklightDriver {
+    Sysfs,
+    Ddcutil,
+}
+
+impl Default for BacklightDriver {
+    fn default() -> Self {
+        BacklightDriver::Sysfs
+    }
+}
+
 /// A block for displaying the brightness of a backlit device.
 pub struct Backlight {
     id: String,
     output: ButtonWidget,
-    device: BacklitDevice,
+    device: Box<dyn BrightnessDevice>,
+    driver: BacklightDriver,
+    update_interval: Duration,
+    format: FormatTemplate,
     step_width: u64,
     scrolling: Scrolling,
+    tx_update_request: Sender<Task>,
 }
 
 /// Configuration for the [`Backlight`](./struct.Backlight.html) block.
 #[derive(Deserialize, Debug, Default, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct BacklightConfig {
-    /// The backlight device in `/sys/class/backlight/` to read brightness from.
+    /// The backlight device in `/sys/class/backlight/` to read brightness from, or with
+    /// `ddcutil`, the model, serial number or display number of the monitor.
     #[serde(default = "BacklightConfig::default_device")]
     pub device: Option<String>,
 
+    /// The "driver" to use for the brightness. One of "sysfs" or "ddcutil".
+    #[serde(default)]
+    pub driver: BacklightDriver,
+
+    /// Update interval in seconds, with `ddcutil`
+    #[serde(
+        default = "BacklightConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
     /// The steps brightness is in/decreased for the selected screen (When greater than 50 it gets limited to 50)
     #[serde(default = "BacklightConfig::default_step_width")]
     pub step_width: u64,
 
+    /// Format override
+    #[serde(default = "BacklightConfig::default_format")]
+    pub format: String,
+
     /// Scaling exponent reciprocal (ie. root). Some devices expose raw values
     /// that are best handled with nonlinear scaling. The human perception of
     /// lightness is close to the cube root of relative luminance. Settings
@@ -223,10 +454,18 @@ impl BacklightConfig {
         None
     }
 
+    fn default_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
     fn default_step_width() -> u64 {
         5
     }
 
+    fn default_format() -> String {
+        "{brightness}%".to_owned()
+    }
+
     fn default_root_scaling() -> f64 {
         1f64
     }
@@ -240,53 +479,62 @@ impl ConfigBlock for Backlight {
         config: Config,
         tx_update_request: Sender<Task>,
     ) -> Result<Self> {
-        let device = match block_config.device {
-            Some(path) => BacklitDevice::from_device(path, block_config.root_scaling),
-            None => BacklitDevice::default(block_config.root_scaling),
-        }?;
-
         let id = pseudo_uuid();
-        let brightness_file = device.brightness_file();
+        let (device, brightness_file): (Box<dyn BrightnessDevice>, _) = match block_config.driver {
+            BacklightDriver::Sysfs => {
+                let device = match block_config.device {
+                    Some(path) => BacklitDevice::from_device(path, block_config.root_scaling),
+                    None => BacklitDevice::default(block_config.root_scaling),
+                }?;
+                let brightness_file = device.brightness_file();
+                (Box::new(device), Some(brightness_file))
+            }
+            // Monitors do not announce changes, so they are polled instead
+            BacklightDriver::Ddcutil => (
+                Box::new(DdcDevice::from_device(
+                    block_config.device,
+                    block_config.root_scaling,
+                )?),
+                None,
+            ),
+        };
 
         let scrolling = config.scrolling;
         let backlight = Backlight {
             output: ButtonWidget::new(config, &id),
             id: id.clone(),
             device,
+            driver: block_config.driver,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("backlight", "Invalid format specified for backlight")?,
             step_width: block_config.step_width,
             scrolling,
+            tx_update_request: tx_update_request.clone(),
         };
 
-        // Spin up a thread to watch for changes to the brightness file for the
-        // device, and schedule an update if needed.
-        thread::Builder::new()
-            .name("backlight".into())
-            .spawn(move || {
-                let mut notify = Inotify::init().expect("Failed to start inotify");
-                notify
-                    .add_watch(brightness_file, WatchMask::MODIFY)
-                    .expect("Failed to watch brightness file");
+        let brightness_file = match brightness_file {
+            Some(brightness_file) => brightness_file,
+            None => return Ok(backlight),
+        };
 
+        // Watch for changes to the brightness file for the device, and schedule an update
+        // if needed, at most every 250ms to avoid update spam.
+        let mut notify = Inotify::init().block_error("backlight", "Failed to start inotify")?;
+        notify
+            .add_watch(brightness_file, WatchMask::MODIFY)
+            .block_error("backlight", "Failed to watch brightness file")?;
+        reactor::request_updates(
+            notify,
+            id,
+            tx_update_request,
+            Duration::from_millis(250),
+            |notify| {
                 let mut buffer = [0; 1024];
-                loop {
-                    let mut events = notify
-                        .read_events_blocking(&mut buffer)
-                        .expect("Error while reading inotify events");
-
-                    if events.any(|event| event.mask.contains(EventMask::MODIFY)) {
-                        tx_update_request
-                            .send(Task {
-                                id: id.clone(),
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
-                    }
-
-                    // Avoid update spam.
-                    thread::sleep(Duration::from_millis(250))
-                }
-            })
-            .unwrap();
+                let mut events = notify.read_events(&mut buffer)?;
+                Ok(events.any(|event| event.mask.contains(EventMask::MODIFY)))
+            },
+        )?;
 
         Ok(backlight)
     }
@@ -295,7 +543,9 @@ impl ConfigBlock for Backlight {
 impl Block for Backlight {
     fn update(&mut self) -> Result<Option<Update>> {
         let brightness = self.device.brightness()?;
-        self.output.set_text(format!("{}%", brightness));
+        let values = map!("{brightness}" => brightness);
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
         match brightness {
             0..=19 => self.output.set_icon("backlight_empty"),
             20..=39 => self.output.set_icon("backlight_partial1"),
@@ -303,7 +553,10 @@ impl Block for Backlight {
             60..=79 => self.output.set_icon("backlight_partial3"),
             _ => self.output.set_icon("backlight_full"),
         }
-        Ok(None)
+        match self.driver {
+            BacklightDriver::Sysfs => Ok(None),
+            BacklightDriver::Ddcutil => Ok(Some(self.update_interval.into())),
+        }
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
@@ -326,7 +579,14 @@ impl Block for Backlight {
                             self.device.set_brightness(brightness - self.step_width)?;
                         }
                     }
-                    None => {}
+                    None => return Ok(()),
+                }
+                // Unlike sysfs, the monitor is not watched for changes
+                if self.driver == BacklightDriver::Ddcutil {
+                    self.tx_update_request.send(Task {
+                        id: self.id.clone(),
+                        update_time: Instant::now(),
+                    })?;
                 }
             }
         }
@@ -334,7 +594,55 @@ impl Block for Backlight {
         Ok(())
     }
 
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        matches!(event.button, MouseButton::WheelUp | MouseButton::WheelDown)
+    }
+
     fn id(&self) -> &str {
         &self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_ddcutil_detect, parse_ddcutil_getvcp, DdcDisplay};
+
+    #[test]
+    fn test_parse_ddcutil_detect() {
+        let output = "Display 1
+   I2C bus:             /dev/i2c-4
+   Monitor:             DEL:DELL U2415:7MT0167B2YNL
+
+Invalid display
+   I2C bus:             /dev/i2c-6
+   Monitor:             AUO::
+
+Display 2
+   I2C bus:             /dev/i2c-7
+   Monitor:             GSM:LG ULTRAFINE:
+";
+        assert_eq!(
+            parse_ddcutil_detect(output),
+            vec![
+                DdcDisplay {
+                    number: 1,
+                    bus: 4,
+                    model: "DELL U2415".to_string(),
+                    serial: "7MT0167B2YNL".to_string(),
+                },
+                DdcDisplay {
+                    number: 2,
+                    bus: 7,
+                    model: "LG ULTRAFINE".to_string(),
+                    serial: "".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_ddcutil_getvcp() {
+        assert_eq!(parse_ddcutil_getvcp("VCP 10 C 50 100\n"), Some((50, 100)));
+        assert_eq!(parse_ddcutil_getvcp("VCP 10 ERR"), None);
+    }
+}
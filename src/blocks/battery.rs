@@ -4,6 +4,7 @@
 //! display the status, capacity, and time remaining for (dis)charge for an
 //! internal power supply.
 
+use std::fs::read_dir;
 use std::path::{Path, PathBuf};
 use std::thread;
 use std::time::{Duration, Instant};
@@ -53,6 +54,15 @@ pub trait BatteryDevice {
 
     /// Query the current power consumption, in uW.
     fn power_consumption(&self) -> Result<u64>;
+
+    /// Query the energy that is stored now and when the device is full, in µWh. Used to
+    /// combine several batteries.
+    fn energy(&self) -> Result<(f64, f64)> {
+        Err(BlockError(
+            "battery".to_string(),
+            "Device does not support reading energy".to_string(),
+        ))
+    }
 }
 
 /// Represents a physical power supply device, as known to sysfs.
@@ -285,14 +295,17 @@ impl BatteryDevice for PowerSupplyDevice {
         // voltage in µV
         let voltage_path = self.device_path.join("voltage_now");
 
+        // Some drivers report a negative power or current while discharging
         if power_path.exists() {
             Ok(read_file("battery", &power_path)?
-                .parse::<u64>()
-                .block_error("battery", "failed to parse power_now")?)
+                .parse::<i64>()
+                .block_error("battery", "failed to parse power_now")?
+                .abs() as u64)
         } else if current_path.exists() && voltage_path.exists() {
             let current = read_file("battery", &current_path)?
-                .parse::<u64>()
-                .block_error("battery", "failed to parse current_now")?;
+                .parse::<i64>()
+                .block_error("battery", "failed to parse current_now")?
+                .abs() as u64;
             let voltage = read_file("battery", &voltage_path)?
                 .parse::<u64>()
                 .block_error("battery", "failed to parse voltage_now")?;
@@ -304,6 +317,17 @@ impl BatteryDevice for PowerSupplyDevice {
             ))
         }
     }
+
+    fn energy(&self) -> Result<(f64, f64)> {
+        // Charges are in µAh and cannot be added to the energy of other batteries
+        let full = self
+            .energy_full
+            .block_error("battery", "Device has no energy_full")?;
+        let now = read_file("battery", &self.device_path.join("energy_now"))?
+            .parse::<f64>()
+            .block_error("battery", "failed to parse energy_now")?;
+        Ok((now, full as f64))
+    }
 }
 
 /// Represents a battery known to UPower.
@@ -333,12 +357,23 @@ impl UpowerDevice {
                 "org.freedesktop.UPower",
                 "EnumerateDevices",
             )
-            .unwrap();
-            let dbus_reply = con.send_with_reply_and_block(msg, 2000).unwrap();
+            .block_error("battery", "Failed to create D-Bus message.")?;
+            let dbus_reply = con
+                .send_with_reply_and_block(msg, 2000)
+                .block_error("battery", "Failed to enumerate UPower devices.")?;
 
             // EnumerateDevices returns one argument, which is an array of ObjectPaths (not dbus::tree:ObjectPath).
-            let mut paths: Array<dbus::Path, _> = dbus_reply.get1().unwrap();
-            let path = paths.find(|entry| entry.ends_with(device));
+            let mut paths: Array<dbus::Path, _> = dbus_reply
+                .get1()
+                .block_error("battery", "Failed to read UPower devices.")?;
+            // The name of the device is the last part of the path, with or without `battery_`
+            let names = [device.to_string(), format!("battery_{}", device)];
+            let path = paths.find(|entry| {
+                entry
+                    .rsplit('/')
+                    .next()
+                    .map_or(false, |name| names.iter().any(|n| n == name))
+            });
             if path.is_none() {
                 return Err(BlockError(
                     "battery".into(),
@@ -363,6 +398,25 @@ impl UpowerDevice {
         Ok(UpowerDevice { device_path, con })
     }
 
+    /// Whether UPower is running, so that the battery can be monitored through it.
+    pub fn is_running() -> bool {
+        let con = match dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::System) {
+            Ok(con) => con,
+            Err(_) => return false,
+        };
+        let msg = dbus::Message::new_method_call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "NameHasOwner",
+        )
+        .map(|msg| msg.append1("org.freedesktop.UPower"));
+        msg.ok()
+            .and_then(|msg| con.send_with_reply_and_block(msg, 1000).ok())
+            .and_then(|reply| reply.get1())
+            .unwrap_or(false)
+    }
+
     /// Monitor UPower property changes in a separate thread and send updates
     /// via the `update_request` channel.
     pub fn monitor(&self, id: String, update_request: Sender<Task>) {
@@ -388,12 +442,16 @@ impl UpowerDevice {
 
                 loop {
                     if con.incoming(10_000).next().is_some() {
-                        update_request
+                        // The block is gone, e.g. after a reload of the configuration
+                        if update_request
                             .send(Task {
                                 id: id.clone(),
                                 update_time: Instant::now(),
                             })
-                            .unwrap();
+                            .is_err()
+                        {
+                            break;
+                        }
                         // Avoid update spam.
                         // TODO: Is this necessary?
                         thread::sleep(Duration::from_millis(1000))
@@ -406,7 +464,10 @@ impl UpowerDevice {
 
 impl BatteryDevice for UpowerDevice {
     fn is_available(&self) -> bool {
-        true // TODO: has to be implemented for UPower
+        self.con
+            .with_path("org.freedesktop.UPower", &self.device_path, 1000)
+            .get("org.freedesktop.UPower.Device", "IsPresent")
+            .unwrap_or(false)
     }
 
     fn refresh_device_info(&mut self) -> Result<()> {
@@ -472,6 +533,193 @@ impl BatteryDevice for UpowerDevice {
         // FIXME: Might want to make the interface send Watts instead.
         Ok((energy_rate * 1_000_000.0) as u64)
     }
+
+    fn energy(&self) -> Result<(f64, f64)> {
+        let properties = self
+            .con
+            .with_path("org.freedesktop.UPower", &self.device_path, 1000);
+        let now: f64 = properties
+            .get("org.freedesktop.UPower.Device", "Energy")
+            .block_error("battery", "Failed to read UPower Energy property.")?;
+        let full: f64 = properties
+            .get("org.freedesktop.UPower.Device", "EnergyFull")
+            .block_error("battery", "Failed to read UPower EnergyFull property.")?;
+        // UPower reports Wh
+        Ok((now * 1_000_000.0, full * 1_000_000.0))
+    }
+}
+
+/// Several batteries that are shown as one, such as the internal and the removable battery
+/// of some Thinkpads.
+pub struct BatteryGroup {
+    devices: Vec<Box<dyn BatteryDevice>>,
+    /// Whether to look for the batteries in sysfs again on every refresh, since they can be
+    /// hot-swapped.
+    rescan: bool,
+    allow_missing: bool,
+}
+
+impl BatteryGroup {
+    pub fn new(devices: Vec<Box<dyn BatteryDevice>>, allow_missing: bool) -> Self {
+        BatteryGroup {
+            devices,
+            rescan: false,
+            allow_missing,
+        }
+    }
+
+    /// All batteries in `/sys/class/power_supply/`.
+    pub fn sysfs(allow_missing: bool) -> Self {
+        BatteryGroup {
+            devices: Vec::new(),
+            rescan: true,
+            allow_missing,
+        }
+    }
+
+    /// Whether the power supply in `path` is a battery of the system, and not one of a
+    /// device like a wireless mouse, which the kernel gives the scope `Device`.
+    fn is_system_battery(path: &Path) -> bool {
+        read_file("battery", &path.join("type")).map_or(false, |kind| kind == "Battery")
+            && read_file("battery", &path.join("scope")).map_or(true, |scope| scope != "Device")
+    }
+
+    fn sysfs_batteries() -> Vec<String> {
+        let root = Path::new("/sys/class/power_supply");
+        let mut names = read_dir(root)
+            .map(|entries| {
+                entries
+                    .flatten()
+                    .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                    .filter(|name| Self::is_system_battery(&root.join(name)))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        names.sort();
+        names
+    }
+
+    fn available(&self) -> impl Iterator<Item = &Box<dyn BatteryDevice>> {
+        self.devices.iter().filter(|device| device.is_available())
+    }
+
+    /// The sum of a value over the available devices, or an error if any of them does not
+    /// support it.
+    fn sum<F: Fn(&dyn BatteryDevice) -> Result<f64>>(&self, value: F) -> Result<f64> {
+        self.available().map(|device| value(&**device)).sum()
+    }
+}
+
+impl BatteryDevice for BatteryGroup {
+    fn is_available(&self) -> bool {
+        self.devices.iter().any(|device| device.is_available())
+    }
+
+    fn refresh_device_info(&mut self) -> Result<()> {
+        if self.rescan {
+            self.devices = Self::sysfs_batteries()
+                .iter()
+                .map(|name| {
+                    PowerSupplyDevice::from_device(name, true)
+                        .map(|device| Box::new(device) as Box<dyn BatteryDevice>)
+                })
+                .collect::<Result<_>>()?;
+        }
+        if !self.is_available() && !self.allow_missing {
+            return Err(BlockError(
+                "battery".into(),
+                "None of the batteries exist".into(),
+            ));
+        }
+        for device in self.devices.iter_mut() {
+            if device.is_available() {
+                device.refresh_device_info()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn status(&self) -> Result<String> {
+        let statuses = self
+            .available()
+            .map(|device| device.status())
+            .collect::<Result<Vec<_>>>()?;
+        // One battery is usually used at a time, while the others are idle
+        for &status in ["Charging", "Discharging"].iter() {
+            if statuses.iter().any(|s| s == status) {
+                return Ok(status.to_string());
+            }
+        }
+        if statuses.iter().all(|s| s == "Full" || s == "Not charging") {
+            return Ok("Full".to_string());
+        }
+        Ok("Unknown".to_string())
+    }
+
+    fn capacity(&self) -> Result<u64> {
+        let now = self.sum(|device| device.energy().map(|(now, _)| now));
+        let full = self.sum(|device| device.energy().map(|(_, full)| full));
+        match (now, full) {
+            (Ok(now), Ok(full)) if full > 0.0 => Ok((now / full * 100.0).min(100.0) as u64),
+            // Without the energy, all batteries count the same
+            _ => {
+                let count = self.available().count().max(1) as f64;
+                Ok((self.sum(|device| device.capacity().map(|c| c as f64))? / count) as u64)
+            }
+        }
+    }
+
+    fn time_remaining(&self) -> Result<u64> {
+        let status = self.status()?;
+        let now = self.sum(|device| device.energy().map(|(now, _)| now));
+        let full = self.sum(|device| device.energy().map(|(_, full)| full));
+        let power = self.sum(|device| device.power_consumption().map(|p| p as f64));
+        // The energy is in µWh and the power in µW
+        match (status.as_str(), now, full, power) {
+            ("Discharging", Ok(now), _, Ok(power)) if power > 0.0 => {
+                Ok((now / power * 60.0) as u64)
+            }
+            ("Charging", Ok(now), Ok(full), Ok(power)) if power > 0.0 => {
+                Ok(((full - now).max(0.0) / power * 60.0) as u64)
+            }
+            ("Discharging", ..) | ("Charging", ..) => {
+                // The batteries are (dis)charged one after another
+                Ok(self
+                    .available()
+                    .filter(|device| device.status().map_or(false, |s| s == status))
+                    .map(|device| device.time_remaining())
+                    .collect::<Result<Vec<_>>>()?
+                    .iter()
+                    .sum())
+            }
+            _ => Ok(0),
+        }
+    }
+
+    fn power_consumption(&self) -> Result<u64> {
+        Ok(self.sum(|device| device.power_consumption().map(|p| p as f64))? as u64)
+    }
+
+    fn energy(&self) -> Result<(f64, f64)> {
+        Ok((
+            self.sum(|device| device.energy().map(|(now, _)| now))?,
+            self.sum(|device| device.energy().map(|(_, full)| full))?,
+        ))
+    }
+}
+
+/// Formats a time in minutes as `H:MM`, up to `99:59`. Zero, which the devices report when
+/// the time is unknown, is shown as nothing.
+fn format_time(minutes: u64) -> String {
+    match minutes.min(99 * 60 + 59) {
+        0 => "".into(),
+        minutes => format!("{}:{:02}", minutes / 60, minutes % 60),
+    }
+}
+
+/// Formats a power in µW as watts.
+fn format_power(power: u64) -> String {
+    format!("{:.2}", power as f64 / 1_000_000.0)
 }
 
 /// A block for displaying information about an internal power supply.
@@ -481,6 +729,8 @@ pub struct Battery {
     update_interval: Duration,
     device: Box<dyn BatteryDevice>,
     format: FormatTemplate,
+    charging_format: FormatTemplate,
+    discharging_format: FormatTemplate,
     full_format: FormatTemplate,
     missing_format: FormatTemplate,
     allow_missing: bool,
@@ -505,6 +755,27 @@ impl Default for BatteryDriver {
     }
 }
 
+/// One battery, or several that are shown as one.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum BatteryDevices {
+    One(String),
+    Many(Vec<String>),
+}
+
+impl BatteryDevices {
+    /// Whether none of the devices is in `root`, like `/sys/class/power_supply`.
+    fn missing_from(&self, root: &Path) -> bool {
+        match self {
+            BatteryDevices::One(name) if name == "all" => {
+                BatteryGroup::sysfs_batteries().is_empty()
+            }
+            BatteryDevices::One(name) => !root.join(name).exists(),
+            BatteryDevices::Many(names) => names.iter().all(|name| !root.join(name).exists()),
+        }
+    }
+}
+
 /// Configuration for the [`Battery`](./struct.Battery.html) block.
 #[derive(Deserialize, Debug, Clone)]
 #[serde(deny_unknown_fields)]
@@ -517,9 +788,9 @@ pub struct BatteryConfig {
     pub interval: Duration,
 
     /// The internal power supply device in `/sys/class/power_supply/` to read
-    /// from.
+    /// from, a list of devices, or `"all"`.
     #[serde(default = "BatteryConfig::default_device")]
-    pub device: String,
+    pub device: BatteryDevices,
 
     /// (DEPRECATED) Options for displaying battery information.
     #[serde()]
@@ -530,6 +801,16 @@ pub struct BatteryConfig {
     #[serde(default = "BatteryConfig::default_format")]
     pub format: String,
 
+    /// Format string for displaying battery information while charging.
+    /// Defaults to `format`.
+    #[serde(default)]
+    pub charging_format: Option<String>,
+
+    /// Format string for displaying battery information while discharging.
+    /// Defaults to `format`.
+    #[serde(default)]
+    pub discharging_format: Option<String>,
+
     /// Format string for displaying battery information when battery is full.
     /// placeholders: {percentage}, {bar}, {time} and {power}
     #[serde(default = "BatteryConfig::default_full_format")]
@@ -577,8 +858,8 @@ impl BatteryConfig {
         Duration::from_secs(10)
     }
 
-    fn default_device() -> String {
-        "BAT0".to_string()
+    fn default_device() -> BatteryDevices {
+        BatteryDevices::One("BAT0".to_string())
     }
 
     fn default_format() -> String {
@@ -647,20 +928,56 @@ impl ConfigBlock for Battery {
         let driver = match block_config.driver {
             Some(val) => val,
             None if block_config.upower => BatteryDriver::Upower,
-            _ => BatteryDriver::Sysfs,
+            // UPower may know a battery that is not in sysfs, but cannot find batteries that
+            // are missing when the block is created
+            None if !block_config.allow_missing
+                && block_config
+                    .device
+                    .missing_from(Path::new("/sys/class/power_supply"))
+                && UpowerDevice::is_running() =>
+            {
+                BatteryDriver::Upower
+            }
+            None => BatteryDriver::Sysfs,
         };
 
         let id = pseudo_uuid();
-        let device: Box<dyn BatteryDevice> = match driver {
-            BatteryDriver::Upower => {
-                let out = UpowerDevice::from_device(&block_config.device)?;
+        let allow_missing = block_config.allow_missing;
+        let device: Box<dyn BatteryDevice> = match (driver.clone(), block_config.device) {
+            // UPower already combines all batteries
+            (BatteryDriver::Upower, BatteryDevices::One(ref name)) if name == "all" => {
+                let out = UpowerDevice::from_device("DisplayDevice")?;
+                out.monitor(id.clone(), update_request);
+                Box::new(out)
+            }
+            (BatteryDriver::Upower, BatteryDevices::One(name)) => {
+                let out = UpowerDevice::from_device(&name)?;
                 out.monitor(id.clone(), update_request);
                 Box::new(out)
             }
-            BatteryDriver::Sysfs => Box::new(PowerSupplyDevice::from_device(
-                &block_config.device,
-                block_config.allow_missing,
-            )?),
+            (BatteryDriver::Upower, BatteryDevices::Many(names)) => {
+                let mut devices = Vec::new();
+                for name in names {
+                    let out = UpowerDevice::from_device(&name)?;
+                    out.monitor(id.clone(), update_request.clone());
+                    devices.push(Box::new(out) as Box<dyn BatteryDevice>);
+                }
+                Box::new(BatteryGroup::new(devices, allow_missing))
+            }
+            (BatteryDriver::Sysfs, BatteryDevices::One(ref name)) if name == "all" => {
+                Box::new(BatteryGroup::sysfs(allow_missing))
+            }
+            (BatteryDriver::Sysfs, BatteryDevices::One(name)) => {
+                Box::new(PowerSupplyDevice::from_device(&name, allow_missing)?)
+            }
+            (BatteryDriver::Sysfs, BatteryDevices::Many(names)) => {
+                let mut devices = Vec::new();
+                for name in names {
+                    devices.push(Box::new(PowerSupplyDevice::from_device(&name, true)?)
+                        as Box<dyn BatteryDevice>);
+                }
+                Box::new(BatteryGroup::new(devices, allow_missing))
+            }
         };
 
         Ok(Battery {
@@ -668,6 +985,12 @@ impl ConfigBlock for Battery {
             update_interval: block_config.interval,
             output: TextWidget::new(config),
             device,
+            charging_format: FormatTemplate::from_string(
+                block_config.charging_format.as_ref().unwrap_or(&format),
+            )?,
+            discharging_format: FormatTemplate::from_string(
+                block_config.discharging_format.as_ref().unwrap_or(&format),
+            )?,
             format: FormatTemplate::from_string(&format)?,
             full_format: FormatTemplate::from_string(&block_config.full_format)?,
             missing_format: FormatTemplate::from_string(&block_config.missing_format)?,
@@ -684,8 +1007,6 @@ impl ConfigBlock for Battery {
 
 impl Block for Battery {
     fn update(&mut self) -> Result<Option<Update>> {
-        // TODO: Maybe use dbus to immediately signal when the battery state changes.
-
         // Exit early, if the battery device went missing, but the user
         // allows this device to go missing.
         if !self.device.is_available() && self.allow_missing {
@@ -725,17 +1046,15 @@ impl Block for Battery {
             Err(_) => "×".into(),
         };
         let time = match self.device.time_remaining() {
-            Ok(time) => match time {
-                0 => "".into(),
-                _ => format!("{}:{:02}", std::cmp::min(time / 60, 99), time % 60),
-            },
+            Ok(time) => format_time(time),
             Err(_) => "×".into(),
         };
-        // convert µW to W for display
         let power = match self.device.power_consumption() {
-            Ok(power) => format!("{:.2}", power as f64 / 1000.0 / 1000.0),
+            Ok(power) => format_power(power),
             Err(_) => "×".into(),
         };
+        self.output
+            .set_percentage(capacity.as_ref().ok().map(|&capacity| capacity as f64));
         let values = map!("{percentage}" => percentage,
                             "{bar}" => bar,
                             "{time}" => time,
@@ -746,18 +1065,29 @@ impl Block for Battery {
             self.output
                 .set_text(self.full_format.render_static_str(&values)?);
             self.output.set_state(State::Good);
+            self.output.set_value(None);
             self.output.set_spacing(Spacing::Hidden);
         } else {
-            self.output
-                .set_text(self.format.render_static_str(&values)?);
+            let format = match status.as_str() {
+                "Charging" => &self.charging_format,
+                "Discharging" => &self.discharging_format,
+                _ => &self.format,
+            };
+            self.output.set_text(format.render_static_str(&values)?);
 
             // Check if the battery is in charging mode and change the state to Good.
             // Otherwise, adjust the state depeding the power percentance.
             match status.as_str() {
                 "Charging" => {
                     self.output.set_state(State::Good);
+                    self.output.set_value(None);
                 }
                 _ => {
+                    self.output
+                        .set_value(capacity.as_ref().ok().map(|&capacity| {
+                            self.good.saturating_sub(capacity) as f64
+                                / self.good.saturating_sub(self.critical).max(1) as f64
+                        }));
                     self.output.set_state(match capacity {
                         Ok(capacity) => {
                             if capacity <= self.critical {
@@ -804,3 +1134,57 @@ impl Block for Battery {
         &self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{format_power, format_time, BatteryGroup};
+    use assert_fs::prelude::{FileWriteStr, PathChild};
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_format_time() {
+        assert_eq!(format_time(0), "");
+        assert_eq!(format_time(5), "0:05");
+        assert_eq!(format_time(135), "2:15");
+        assert_eq!(format_time(100 * 60), "99:59");
+    }
+
+    #[test]
+    fn test_format_power() {
+        assert_eq!(format_power(0), "0.00");
+        assert_eq!(format_power(8_460_000), "8.46");
+        assert_eq!(format_power(12_000_000), "12.00");
+    }
+
+    #[test]
+    fn test_is_system_battery() {
+        let root = TempDir::new().unwrap();
+        root.child("BAT0/type").write_str("Battery\n").unwrap();
+        root.child("BAT1/type").write_str("Battery\n").unwrap();
+        root.child("BAT1/scope").write_str("System\n").unwrap();
+        root.child("hidpp_battery_0/type")
+            .write_str("Battery\n")
+            .unwrap();
+        root.child("hidpp_battery_0/scope")
+            .write_str("Device\n")
+            .unwrap();
+        root.child("AC/type").write_str("Mains\n").unwrap();
+        assert!(BatteryGroup::is_system_battery(root.child("BAT0").path()));
+        assert!(BatteryGroup::is_system_battery(root.child("BAT1").path()));
+        assert!(!BatteryGroup::is_system_battery(
+            root.child("hidpp_battery_0").path()
+        ));
+        assert!(!BatteryGroup::is_system_battery(root.child("AC").path()));
+    }
+
+    #[test]
+    fn test_missing_from() {
+        let root = TempDir::new().unwrap();
+        root.child("BAT0/type").write_str("Battery\n").unwrap();
+        let one = |name: &str| BatteryDevices::One(name.to_string());
+        assert!(!one("BAT0").missing_from(root.path()));
+        assert!(one("BAT1").missing_from(root.path()));
+        assert!(!BatteryDevices::Many(vec!["BAT1".into(), "BAT0".into()]).missing_from(root.path()));
+        assert!(BatteryDevices::Many(vec!["BAT1".into()]).missing_from(root.path()));
+    }
+}
@@ -0,0 +1,123 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use swayipc::reply::Event;
+use swayipc::{Connection, EventType};
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+pub struct BindingMode {
+    id: String,
+    text: TextWidget,
+    format: FormatTemplate,
+    mode: Arc<Mutex<String>>,
+    /// Whether the mode of the last update is the default one, in which the block is hidden
+    default_mode: bool,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BindingModeConfig {
+    /// Format string
+    #[serde(default = "BindingModeConfig::default_format")]
+    pub format: String,
+}
+
+impl BindingModeConfig {
+    fn default_format() -> String {
+        "{mode}".to_owned()
+    }
+}
+
+impl ConfigBlock for BindingMode {
+    type Config = BindingModeConfig;
+
+    fn new(block_config: Self::Config, config: Config, tx: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+        let id_clone = id.clone();
+
+        // The mode is only announced when it changes, so the bar starts in the default mode
+        let mode = Arc::new(Mutex::new(String::from("default")));
+        let mode_clone = mode.clone();
+
+        let _test_conn =
+            Connection::new().block_error("binding_mode", "failed to acquire connect to IPC")?;
+
+        thread::Builder::new()
+            .name("binding_mode".into())
+            .spawn(move || {
+                let conn = Connection::new().expect("failed to open connection with swayipc");
+
+                let events = conn
+                    .subscribe(&[EventType::Mode])
+                    .expect("could not subscribe to mode events");
+
+                for event in events {
+                    if let Event::Mode(e) =
+                        event.expect("could not read event in `binding_mode` block")
+                    {
+                        *mode_clone
+                            .lock()
+                            .expect("lock has been poisoned in `binding_mode` block") = e.change;
+
+                        if tx
+                            .send(Task {
+                                id: id_clone.clone(),
+                                update_time: Instant::now(),
+                            })
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                }
+            })
+            .expect("failed to start watching thread for `binding_mode` block");
+
+        Ok(BindingMode {
+            id,
+            text: TextWidget::new(config).with_state(State::Warning),
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("binding_mode", "Invalid format specified")?,
+            mode,
+            default_mode: true,
+        })
+    }
+}
+
+impl Block for BindingMode {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mode = (*self
+            .mode
+            .lock()
+            .block_error("binding_mode", "failed to acquire lock")?)
+        .clone();
+
+        self.default_mode = mode == "default";
+        let values = map!("{mode}" => mode);
+        self.text.set_text(self.format.render_static_str(&values)?);
+
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.default_mode {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
@@ -140,13 +140,15 @@ impl BluetoothDevice {
                     .expect("Failed to add D-Bus match rule.");
 
                 loop {
-                    if con.incoming(10_000).next().is_some() {
-                        update_request
+                    if con.incoming(10_000).next().is_some()
+                        && update_request
                             .send(Task {
                                 id: id.clone(),
                                 update_time: Instant::now(),
                             })
-                            .unwrap();
+                            .is_err()
+                    {
+                        return;
                     }
                 }
             })
@@ -237,6 +239,10 @@ impl Block for Bluetooth {
         Ok(())
     }
 
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Right
+    }
+
     fn view(&self) -> Vec<&dyn I3BarWidget> {
         if !self.device.connected() && self.hide_disconnected {
             vec![]
@@ -0,0 +1,246 @@
+//! A block for the allocation and health of a btrfs filesystem, from `/sys/fs/btrfs`.
+
+use std::fs::read_dir;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, read_file, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+const SYSFS_BTRFS: &str = "/sys/fs/btrfs";
+
+/// Sums the counters of an `error_stats` file, like `write_errs 0`.
+fn parse_error_stats(content: &str) -> u64 {
+    content
+        .lines()
+        .filter_map(|line| line.split_whitespace().nth(1))
+        .filter_map(|n| n.parse::<u64>().ok())
+        .sum()
+}
+
+fn read_u64(path: &Path) -> Result<u64> {
+    read_file("btrfs", path)?.trim().parse().block_error(
+        "btrfs",
+        &format!("failed to parse {}", path.to_string_lossy()),
+    )
+}
+
+/// Finds the directory of a filesystem in `/sys/fs/btrfs` by its UUID or label, or the only
+/// filesystem if neither is given.
+fn find_filesystem(uuid: Option<&str>, label: Option<&str>) -> Result<PathBuf> {
+    let mut found = Vec::new();
+    for entry in read_dir(SYSFS_BTRFS)
+        .block_error(
+            "btrfs",
+            "/sys/fs/btrfs does not exist, is a btrfs filesystem mounted?",
+        )?
+        .flatten()
+    {
+        let path = entry.path();
+        // Leaves out `features` and the like
+        if !path.join("label").exists() {
+            continue;
+        }
+        let name = entry.file_name().to_string_lossy().into_owned();
+        let matches = match (uuid, label) {
+            (Some(uuid), _) => name == uuid,
+            (None, Some(label)) => {
+                read_file("btrfs", &path.join("label")).map_or(false, |l| l.trim() == label)
+            }
+            (None, None) => true,
+        };
+        if matches {
+            found.push(path);
+        }
+    }
+    match found.len() {
+        1 => Ok(found.remove(0)),
+        0 => Err(BlockError(
+            "btrfs".to_string(),
+            "No matching btrfs filesystem found".to_string(),
+        )),
+        _ => Err(BlockError(
+            "btrfs".to_string(),
+            "Several btrfs filesystems found, set `uuid` or `label`".to_string(),
+        )),
+    }
+}
+
+pub struct Btrfs {
+    id: String,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    path: PathBuf,
+    warning: f64,
+    critical: f64,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct BtrfsConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "BtrfsConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// UUID of the filesystem
+    #[serde(default)]
+    pub uuid: Option<String>,
+
+    /// Label of the filesystem, if no UUID is given
+    #[serde(default)]
+    pub label: Option<String>,
+
+    /// Format string
+    #[serde(default = "BtrfsConfig::default_format")]
+    pub format: String,
+
+    /// Percentage of the devices allocated, where state is set to warning
+    #[serde(default = "BtrfsConfig::default_warning")]
+    pub warning: f64,
+
+    /// Percentage of the devices allocated, where state is set to critical
+    #[serde(default = "BtrfsConfig::default_critical")]
+    pub critical: f64,
+}
+
+impl BtrfsConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(30)
+    }
+
+    fn default_format() -> String {
+        "{label} {allocated_percent}%".to_owned()
+    }
+
+    fn default_warning() -> f64 {
+        80.
+    }
+
+    fn default_critical() -> f64 {
+        90.
+    }
+}
+
+impl ConfigBlock for Btrfs {
+    type Config = BtrfsConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        Ok(Btrfs {
+            id: pseudo_uuid(),
+            text: TextWidget::new(config).with_icon("disk_drive"),
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("btrfs", "Invalid format specified")?,
+            update_interval: block_config.interval,
+            path: find_filesystem(block_config.uuid.as_deref(), block_config.label.as_deref())?,
+            warning: block_config.warning,
+            critical: block_config.critical,
+        })
+    }
+}
+
+impl Btrfs {
+    /// The bytes allocated to and used in chunks on the devices, which count every copy of
+    /// a RAID profile.
+    fn allocation(&self) -> Result<(u64, u64)> {
+        let mut allocated = 0;
+        let mut used = 0;
+        for kind in &["data", "metadata", "system"] {
+            let dir = self.path.join("allocation").join(kind);
+            allocated += read_u64(&dir.join("disk_total"))?;
+            used += read_u64(&dir.join("disk_used"))?;
+        }
+        Ok((allocated, used))
+    }
+
+    /// The size of all devices of the filesystem in bytes.
+    fn size(&self) -> Result<u64> {
+        let mut size = 0;
+        for device in read_dir(self.path.join("devices"))
+            .block_error("btrfs", "failed to read the devices of the filesystem")?
+            .flatten()
+        {
+            // The size of a block device is in sectors of 512 bytes
+            size += read_u64(&device.path().join("size"))? * 512;
+        }
+        Ok(size)
+    }
+
+    /// The sum of the error counters of all devices, which only kernels since 5.14 have.
+    fn errors(&self) -> u64 {
+        let devices = match read_dir(self.path.join("devinfo")) {
+            Ok(devices) => devices,
+            Err(_) => return 0,
+        };
+        devices
+            .flatten()
+            .filter_map(|device| read_file("btrfs", &device.path().join("error_stats")).ok())
+            .map(|stats| parse_error_stats(&stats))
+            .sum()
+    }
+}
+
+impl Block for Btrfs {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let label = read_file("btrfs", &self.path.join("label"))?;
+        let (allocated, used) = self.allocation()?;
+        let size = self.size()?.max(1);
+        let errors = self.errors();
+        // Like balance or device removal, which block each other
+        let operation = read_file("btrfs", &self.path.join("exclusive_operation"))
+            .unwrap_or_else(|_| "none".to_string());
+
+        let allocated_percent = allocated as f64 / size as f64 * 100.;
+        let values = map!(
+            "{label}" => label.trim().to_string(),
+            "{allocated}" => format!("{:.1}", allocated as f64 / 1e9),
+            "{allocated_percent}" => format!("{:.0}", allocated_percent),
+            "{used}" => format!("{:.1}", used as f64 / 1e9),
+            "{used_percent}" => format!("{:.0}", used as f64 / size as f64 * 100.),
+            "{size}" => format!("{:.1}", size as f64 / 1e9),
+            "{errors}" => errors.to_string(),
+            "{operation}" => operation.trim().to_string()
+        );
+        self.text.set_text(self.format.render_static_str(&values)?);
+        self.text.set_state(match allocated_percent {
+            _ if errors > 0 => State::Critical,
+            x if x > self.critical => State::Critical,
+            x if x > self.warning => State::Warning,
+            _ => State::Idle,
+        });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_error_stats;
+
+    #[test]
+    fn test_parse_error_stats() {
+        let stats = "write_errs 0\nread_errs 3\nflush_errs 0\ncorruption_errs 1\ngeneration_errs 0";
+        assert_eq!(parse_error_stats(stats), 4);
+        assert_eq!(parse_error_stats(""), 0);
+    }
+}
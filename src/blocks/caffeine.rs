@@ -0,0 +1,147 @@
+//! A block that keeps the screen from blanking or locking while it is enabled.
+
+use std::env;
+use std::process::Command;
+
+use crossbeam_channel::Sender;
+use dbus::arg::OwnedFd;
+use dbus::ffidisp::{BusType, Connection};
+use dbus::Message;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::pseudo_uuid;
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+/// Takes an idle inhibitor lock of logind, which lasts until the file descriptor is closed.
+fn inhibit_idle() -> Result<OwnedFd> {
+    let c = Connection::get_private(BusType::System)
+        .block_error("caffeine", "failed to establish D-Bus connection")?;
+    let m = Message::new_method_call(
+        "org.freedesktop.login1",
+        "/org/freedesktop/login1",
+        "org.freedesktop.login1.Manager",
+        "Inhibit",
+    )
+    .block_error("caffeine", "failed to create D-Bus method call")?
+    .append3("idle", "i3status-rs", "Caffeine block is enabled")
+    .append1("block");
+    c.send_with_reply_and_block(m, 1000)
+        .block_error("caffeine", "logind refused the idle inhibitor")?
+        .get1()
+        .block_error("caffeine", "logind did not return a file descriptor")
+}
+
+/// Whether the session runs on X11, where the screen saver of the X server blanks the screen
+/// on its own.
+fn is_x11() -> bool {
+    env::var_os("DISPLAY").is_some() && env::var_os("WAYLAND_DISPLAY").is_none()
+}
+
+/// Turns the screen saver and DPMS of the X server on or off.
+fn set_x11_screensaver(enabled: bool) -> Result<()> {
+    let args: &[&str] = if enabled {
+        &["s", "on", "+dpms"]
+    } else {
+        &["s", "off", "-dpms"]
+    };
+    Command::new("xset")
+        .args(args)
+        .output()
+        .block_error("caffeine", "failed to run xset")?;
+    Ok(())
+}
+
+pub struct Caffeine {
+    id: String,
+    output: ButtonWidget,
+    /// The idle inhibitor lock, held while the block is enabled
+    inhibitor: Option<OwnedFd>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CaffeineConfig {}
+
+impl ConfigBlock for Caffeine {
+    type Config = CaffeineConfig;
+
+    fn new(_block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+        Ok(Caffeine {
+            output: ButtonWidget::new(config, &id).with_icon("caffeine_off"),
+            id,
+            inhibitor: None,
+        })
+    }
+}
+
+impl Caffeine {
+    fn enable(&mut self) -> Result<()> {
+        self.inhibitor = Some(inhibit_idle()?);
+        if is_x11() {
+            set_x11_screensaver(false)?;
+        }
+        Ok(())
+    }
+
+    fn disable(&mut self) -> Result<()> {
+        // Closing the file descriptor releases the lock
+        self.inhibitor = None;
+        if is_x11() {
+            set_x11_screensaver(true)?;
+        }
+        Ok(())
+    }
+}
+
+impl Block for Caffeine {
+    fn update(&mut self) -> Result<Option<Update>> {
+        if self.inhibitor.is_some() {
+            self.output.set_icon("caffeine_on");
+            self.output.set_state(State::Info);
+        } else {
+            self.output.set_icon("caffeine_off");
+            self.output.set_state(State::Idle);
+        }
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.matches_name(&self.id) && event.button == MouseButton::Left {
+            if self.inhibitor.is_some() {
+                self.disable()?;
+            } else {
+                self.enable()?;
+            }
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+impl Drop for Caffeine {
+    fn drop(&mut self) {
+        // The screen saver of the X server outlives the bar, unlike the lock of logind
+        if self.inhibitor.is_some() && is_x11() {
+            let _ = set_x11_screensaver(true);
+        }
+    }
+}
@@ -0,0 +1,1031 @@
+//! A block for the next event of a calendar, from iCalendar files, khal or a CalDAV server.
+
+use std::fs;
+use std::path::Path;
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use chrono::{offset::Local, DateTime, Datelike, NaiveDate, NaiveDateTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use crossbeam_channel::Sender;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{expand_home, http_request, pseudo_uuid, FormatTemplate, HttpRequest};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+/// How many days ahead to look for events.
+const LOOKAHEAD_DAYS: i64 = 90;
+
+lazy_static! {
+    static ref CALENDAR_DATA_REGEX: Regex = Regex::new(
+        r"(?s)<(?:[[:alnum:]]+:)?calendar-data[^>]*>(.*?)</(?:[[:alnum:]]+:)?calendar-data>"
+    )
+    .unwrap();
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "name", rename_all = "lowercase")]
+pub enum CalendarSource {
+    /// An iCalendar file, or a directory of them like the ones of vdirsyncer
+    Ics { path: String },
+    /// The events listed by khal
+    Khal {
+        /// The `longdatetimeformat` of khal, in the syntax of chrono's strftime
+        #[serde(default = "CalendarSource::default_khal_datetime_format")]
+        datetime_format: String,
+        /// The `longdateformat` of khal, in the syntax of chrono's strftime
+        #[serde(default = "CalendarSource::default_khal_date_format")]
+        date_format: String,
+    },
+    /// A calendar collection on a CalDAV server
+    CalDav {
+        url: String,
+        /// A file with `user:password`
+        #[serde(default)]
+        credentials_file: Option<String>,
+    },
+}
+
+impl CalendarSource {
+    fn default_khal_datetime_format() -> String {
+        "%Y-%m-%d %H:%M".to_owned()
+    }
+
+    fn default_khal_date_format() -> String {
+        "%Y-%m-%d".to_owned()
+    }
+}
+
+/// The timezone in which the date and time of an event are.
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Zone {
+    Utc,
+    Tz(Tz),
+    /// A floating time, which is in the local timezone
+    Local,
+    /// A whole day in the local timezone
+    AllDay,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+struct Time {
+    naive: NaiveDateTime,
+    zone: Zone,
+}
+
+impl Time {
+    fn with_naive(self, naive: NaiveDateTime) -> Time {
+        Time { naive, ..self }
+    }
+
+    fn to_utc(self) -> Option<DateTime<Utc>> {
+        match self.zone {
+            Zone::Utc => Some(DateTime::<Utc>::from_utc(self.naive, Utc)),
+            Zone::Tz(tz) => tz
+                .from_local_datetime(&self.naive)
+                .earliest()
+                .map(|t| t.with_timezone(&Utc)),
+            Zone::Local | Zone::AllDay => Local
+                .from_local_datetime(&self.naive)
+                .earliest()
+                .map(|t| t.with_timezone(&Utc)),
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Frequency {
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+}
+
+/// The part of an `RRULE` that is supported.
+#[derive(Clone, Debug, PartialEq)]
+struct Rule {
+    frequency: Frequency,
+    interval: i64,
+    count: Option<usize>,
+    until: Option<Time>,
+    /// The weekdays, with their position in the month, or 0 for all of them
+    by_day: Vec<(i32, Weekday)>,
+    by_month_day: Vec<i32>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Event {
+    uid: String,
+    title: String,
+    location: String,
+    start: Time,
+    duration: chrono::Duration,
+    rule: Option<Rule>,
+    exdates: Vec<DateTime<Utc>>,
+    /// The occurrence of a recurring event with the same uid that this event replaces
+    recurrence_id: Option<DateTime<Utc>>,
+    cancelled: bool,
+}
+
+/// An occurrence of an event.
+#[derive(Clone, Debug, PartialEq)]
+struct Occurrence {
+    title: String,
+    location: String,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+}
+
+/// Parses an iCalendar date or date and time, given the parameters of its property.
+fn parse_ics_time(params: &str, value: &str) -> Option<Time> {
+    if let Some(value) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|naive| Time {
+                naive,
+                zone: Zone::Utc,
+            });
+    }
+    if let Ok(naive) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        // Unknown timezones, like the ones of Windows, are taken as local
+        let zone = params
+            .split(';')
+            .find_map(|param| param.strip_prefix("TZID="))
+            .and_then(|tzid| tzid.trim_matches('"').parse::<Tz>().ok())
+            .map_or(Zone::Local, Zone::Tz);
+        return Some(Time { naive, zone });
+    }
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .ok()
+        .map(|date| Time {
+            naive: date.and_hms(0, 0, 0),
+            zone: Zone::AllDay,
+        })
+}
+
+/// Parses an iCalendar duration, like `PT1H30M` or `-P1D`.
+fn parse_ics_duration(value: &str) -> Option<chrono::Duration> {
+    let (sign, value) = match value.strip_prefix('-') {
+        Some(value) => (-1, value),
+        None => (1, value.trim_start_matches('+')),
+    };
+    let mut seconds = 0;
+    let mut number = String::new();
+    for c in value.strip_prefix('P')?.chars() {
+        match c {
+            '0'..='9' => number.push(c),
+            'T' => {}
+            _ => {
+                let n: i64 = number.parse().ok()?;
+                number.clear();
+                seconds += n * match c {
+                    'W' => 7 * 86400,
+                    'D' => 86400,
+                    'H' => 3600,
+                    'M' => 60,
+                    'S' => 1,
+                    _ => return None,
+                };
+            }
+        }
+    }
+    Some(chrono::Duration::seconds(sign * seconds))
+}
+
+fn parse_weekday(day: &str) -> Option<Weekday> {
+    match day {
+        "MO" => Some(Weekday::Mon),
+        "TU" => Some(Weekday::Tue),
+        "WE" => Some(Weekday::Wed),
+        "TH" => Some(Weekday::Thu),
+        "FR" => Some(Weekday::Fri),
+        "SA" => Some(Weekday::Sat),
+        "SU" => Some(Weekday::Sun),
+        _ => None,
+    }
+}
+
+/// Parses a `BYDAY` entry, like `TU`, `2TU` or `-1FR`.
+fn parse_by_day(day: &str) -> Option<(i32, Weekday)> {
+    let split = day.len().checked_sub(2)?;
+    let position = match day.get(..split)?.trim_start_matches('+') {
+        "" => 0,
+        position => position.parse().ok()?,
+    };
+    Some((position, parse_weekday(day.get(split..)?)?))
+}
+
+/// Parses an `RRULE`, or returns `None` for frequencies that are not supported.
+fn parse_rule(value: &str) -> Option<Rule> {
+    let mut frequency = None;
+    let mut rule = Rule {
+        frequency: Frequency::Daily,
+        interval: 1,
+        count: None,
+        until: None,
+        by_day: Vec::new(),
+        by_month_day: Vec::new(),
+    };
+    for part in value.split(';') {
+        let mut part = part.splitn(2, '=');
+        match (part.next()?, part.next()?) {
+            ("FREQ", "DAILY") => frequency = Some(Frequency::Daily),
+            ("FREQ", "WEEKLY") => frequency = Some(Frequency::Weekly),
+            ("FREQ", "MONTHLY") => frequency = Some(Frequency::Monthly),
+            ("FREQ", "YEARLY") => frequency = Some(Frequency::Yearly),
+            ("INTERVAL", interval) => rule.interval = interval.parse::<i64>().ok()?.max(1),
+            ("COUNT", count) => rule.count = Some(count.parse().ok()?),
+            ("UNTIL", until) => rule.until = Some(parse_ics_time("", until)?),
+            ("BYDAY", days) => {
+                rule.by_day = days.split(',').map(parse_by_day).collect::<Option<_>>()?
+            }
+            ("BYMONTHDAY", days) => {
+                rule.by_month_day = days
+                    .split(',')
+                    .map(|day| day.parse().ok())
+                    .collect::<Option<_>>()?
+            }
+            _ => {}
+        }
+    }
+    rule.frequency = frequency?;
+    Some(rule)
+}
+
+fn unescape_text(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => unescaped.push(' '),
+            Some(c) => unescaped.push(c),
+            None => {}
+        }
+    }
+    unescaped
+}
+
+/// Parses the `VEVENT`s of iCalendar documents, which may be concatenated.
+fn parse_ics(ics: &str) -> Vec<Event> {
+    // Unfold continuation lines, which start with a space or a tab.
+    let mut lines: Vec<String> = Vec::new();
+    for line in ics.lines() {
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut events = Vec::new();
+    let mut event: Option<Event> = None;
+    let mut start = None;
+    let mut end = None;
+    let mut duration = None;
+    // The depth of components in the event, like alarms, whose properties are skipped
+    let mut nested = 0;
+    for line in &lines {
+        let (property, value) = match line.find(':') {
+            Some(i) => (&line[..i], &line[i + 1..]),
+            None => continue,
+        };
+        let (key, params) = match property.find(';') {
+            Some(i) => (&property[..i], &property[i + 1..]),
+            None => (property, ""),
+        };
+        if key == "BEGIN" && value == "VEVENT" {
+            event = Some(Event {
+                uid: String::new(),
+                title: String::new(),
+                location: String::new(),
+                start: Time {
+                    naive: NaiveDate::from_ymd(1970, 1, 1).and_hms(0, 0, 0),
+                    zone: Zone::Utc,
+                },
+                duration: chrono::Duration::zero(),
+                rule: None,
+                exdates: Vec::new(),
+                recurrence_id: None,
+                cancelled: false,
+            });
+            start = None;
+            end = None;
+            duration = None;
+            nested = 0;
+            continue;
+        }
+        let current = match event.as_mut() {
+            Some(current) => current,
+            None => continue,
+        };
+        match (key, value) {
+            ("BEGIN", _) => nested += 1,
+            ("END", "VEVENT") => {
+                let mut finished = event.take().unwrap();
+                if let Some(start) = start.take() {
+                    finished.start = start;
+                    finished.duration = match (end.take().and_then(Time::to_utc), duration.take()) {
+                        (Some(end), _) => start
+                            .to_utc()
+                            .map_or(chrono::Duration::zero(), |start| end - start),
+                        (None, Some(duration)) => duration,
+                        (None, None) if start.zone == Zone::AllDay => chrono::Duration::days(1),
+                        (None, None) => chrono::Duration::zero(),
+                    };
+                    events.push(finished);
+                }
+            }
+            ("END", _) => nested -= 1,
+            _ if nested > 0 => {}
+            ("UID", _) => current.uid = value.to_string(),
+            ("SUMMARY", _) => current.title = unescape_text(value),
+            ("LOCATION", _) => current.location = unescape_text(value),
+            ("DTSTART", _) => start = parse_ics_time(params, value),
+            ("DTEND", _) => end = parse_ics_time(params, value),
+            ("DURATION", _) => duration = parse_ics_duration(value),
+            ("RRULE", _) => current.rule = parse_rule(value),
+            ("EXDATE", _) => current.exdates.extend(
+                value
+                    .split(',')
+                    .filter_map(|exdate| parse_ics_time(params, exdate)?.to_utc()),
+            ),
+            ("RECURRENCE-ID", _) => {
+                current.recurrence_id = parse_ics_time(params, value).and_then(Time::to_utc)
+            }
+            ("STATUS", "CANCELLED") => current.cancelled = true,
+            _ => {}
+        }
+    }
+    events
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let (next_year, next_month) = if month == 12 {
+        (year + 1, 1)
+    } else {
+        (year, month + 1)
+    };
+    NaiveDate::from_ymd(next_year, next_month, 1).pred().day()
+}
+
+/// The dates of the `period`th period of a rule, like the `period`th week after the start
+/// for a weekly rule, when the interval is 1.
+fn period_dates(rule: &Rule, start: NaiveDate, period: i64) -> Vec<NaiveDate> {
+    let step = period * rule.interval;
+    let mut dates: Vec<NaiveDate> = match rule.frequency {
+        Frequency::Daily => vec![start + chrono::Duration::days(step)],
+        Frequency::Weekly => {
+            let monday = start
+                - chrono::Duration::days(start.weekday().num_days_from_monday() as i64)
+                + chrono::Duration::weeks(step);
+            let weekdays = if rule.by_day.is_empty() {
+                vec![start.weekday()]
+            } else {
+                rule.by_day.iter().map(|(_, weekday)| *weekday).collect()
+            };
+            weekdays
+                .iter()
+                .map(|weekday| {
+                    monday + chrono::Duration::days(weekday.num_days_from_monday() as i64)
+                })
+                .collect()
+        }
+        Frequency::Monthly => {
+            let months = start.year() as i64 * 12 + start.month0() as i64 + step;
+            let year = (months / 12) as i32;
+            let month = (months % 12) as u32 + 1;
+            let days = days_in_month(year, month);
+            if !rule.by_day.is_empty() {
+                let mut dates = Vec::new();
+                for (position, weekday) in &rule.by_day {
+                    let matching: Vec<NaiveDate> = (1..=days)
+                        .map(|day| NaiveDate::from_ymd(year, month, day))
+                        .filter(|date| date.weekday() == *weekday)
+                        .collect();
+                    match *position {
+                        0 => dates.extend(matching),
+                        position if position > 0 => {
+                            dates.extend(matching.get(position as usize - 1).copied())
+                        }
+                        position => dates.extend(
+                            matching
+                                .len()
+                                .checked_sub(-position as usize)
+                                .and_then(|i| matching.get(i))
+                                .copied(),
+                        ),
+                    }
+                }
+                dates
+            } else if !rule.by_month_day.is_empty() {
+                rule.by_month_day
+                    .iter()
+                    .map(|&day| if day < 0 { days as i32 + 1 + day } else { day })
+                    .filter(|&day| day >= 1)
+                    .filter_map(|day| NaiveDate::from_ymd_opt(year, month, day as u32))
+                    .collect()
+            } else {
+                NaiveDate::from_ymd_opt(year, month, start.day())
+                    .into_iter()
+                    .collect()
+            }
+        }
+        Frequency::Yearly => {
+            NaiveDate::from_ymd_opt(start.year() + step as i32, start.month(), start.day())
+                .into_iter()
+                .collect()
+        }
+    };
+    // Daily rules may be limited to some weekdays
+    if rule.frequency == Frequency::Daily && !rule.by_day.is_empty() {
+        dates.retain(|date| {
+            rule.by_day
+                .iter()
+                .any(|(_, weekday)| date.weekday() == *weekday)
+        });
+    }
+    dates.sort();
+    dates.dedup();
+    dates
+}
+
+/// The starts of the occurrences of a recurring event until `limit`.
+fn expand(start: Time, rule: &Rule, limit: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+    let until = rule.until.and_then(|until| match until.zone {
+        // A date includes its whole day
+        Zone::AllDay => until
+            .with_naive(until.naive + chrono::Duration::days(1))
+            .to_utc()
+            .map(|until| until - chrono::Duration::seconds(1)),
+        _ => until.to_utc(),
+    });
+    let min_days = match rule.frequency {
+        Frequency::Daily => 1,
+        Frequency::Weekly => 7,
+        Frequency::Monthly => 28,
+        Frequency::Yearly => 365,
+    };
+    let periods =
+        (limit.naive_utc().date() - start.naive.date()).num_days() / (min_days * rule.interval) + 2;
+
+    let mut starts = Vec::new();
+    let mut count = 0;
+    for period in 0..periods.max(0) {
+        for date in period_dates(rule, start.naive.date(), period) {
+            let naive = date.and_time(start.naive.time());
+            if naive < start.naive {
+                continue;
+            }
+            count += 1;
+            if rule.count.map_or(false, |max| count > max) {
+                return starts;
+            }
+            let time = match start.with_naive(naive).to_utc() {
+                Some(time) => time,
+                // Skipped by a change to daylight saving time
+                None => continue,
+            };
+            if time > limit || until.map_or(false, |until| time > until) {
+                return starts;
+            }
+            starts.push(time);
+        }
+    }
+    starts
+}
+
+/// The next occurrence of the events that starts after `now`.
+fn next_occurrence(events: &[Event], now: DateTime<Utc>, all_day: bool) -> Option<Occurrence> {
+    let limit = now + chrono::Duration::days(LOOKAHEAD_DAYS);
+    let overridden: Vec<(&str, DateTime<Utc>)> = events
+        .iter()
+        .filter_map(|event| Some((event.uid.as_str(), event.recurrence_id?)))
+        .collect();
+    let mut occurrences = Vec::new();
+    for event in events {
+        if event.cancelled || (!all_day && event.start.zone == Zone::AllDay) {
+            continue;
+        }
+        let starts = match (&event.rule, event.recurrence_id) {
+            (Some(rule), None) => expand(event.start, rule, limit),
+            _ => event.start.to_utc().into_iter().collect(),
+        };
+        occurrences.extend(
+            starts
+                .into_iter()
+                .filter(|start| *start > now)
+                .filter(|start| !event.exdates.contains(start))
+                .filter(|start| {
+                    event.recurrence_id.is_some()
+                        || !overridden.contains(&(event.uid.as_str(), *start))
+                })
+                .map(|start| Occurrence {
+                    title: event.title.clone(),
+                    location: event.location.clone(),
+                    start,
+                    end: start + event.duration,
+                }),
+        );
+    }
+    occurrences
+        .into_iter()
+        .min_by_key(|occurrence| occurrence.start)
+}
+
+/// Parses the lines of `khal list` with the format `{start-long}\t{end-long}\t{title}\t{location}`.
+fn parse_khal(output: &str, datetime_format: &str, date_format: &str) -> Vec<Event> {
+    let parse = |time: &str| {
+        NaiveDateTime::parse_from_str(time, datetime_format)
+            .map(|naive| Time {
+                naive,
+                zone: Zone::Local,
+            })
+            .or_else(|_| {
+                NaiveDate::parse_from_str(time, date_format).map(|date| Time {
+                    naive: date.and_hms(0, 0, 0),
+                    zone: Zone::AllDay,
+                })
+            })
+            .ok()
+    };
+    output
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split('\t');
+            let start = parse(fields.next()?)?;
+            let end = fields.next().and_then(parse);
+            Some(Event {
+                uid: String::new(),
+                title: fields.next().unwrap_or_default().to_string(),
+                location: fields.next().unwrap_or_default().to_string(),
+                start,
+                duration: match end {
+                    // khal shows the last day of events that last whole days
+                    Some(end) if end.zone == Zone::AllDay => {
+                        end.naive - start.naive + chrono::Duration::days(1)
+                    }
+                    Some(end) => end.naive - start.naive,
+                    None => chrono::Duration::zero(),
+                },
+                rule: None,
+                exdates: Vec::new(),
+                recurrence_id: None,
+                cancelled: false,
+            })
+        })
+        .collect()
+}
+
+/// Extracts the iCalendar documents from the answer of a CalDAV `calendar-query`.
+fn parse_caldav(xml: &str) -> String {
+    CALENDAR_DATA_REGEX
+        .captures_iter(xml)
+        .map(|captures| {
+            let data = captures[1].trim();
+            let data = data
+                .strip_prefix("<![CDATA[")
+                .and_then(|data| data.strip_suffix("]]>"))
+                .unwrap_or(data);
+            data.replace("&lt;", "<")
+                .replace("&gt;", ">")
+                .replace("&quot;", "\"")
+                .replace("&apos;", "'")
+                .replace("&#13;", "\r")
+                .replace("&#xD;", "\r")
+                .replace("&amp;", "&")
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Reads an iCalendar file, or all of them in a directory and its subdirectories.
+fn read_ics(path: &Path) -> Result<String> {
+    if !path.is_dir() {
+        return fs::read_to_string(path).block_error(
+            "calendar",
+            &format!("failed to read iCalendar file {}", path.display()),
+        );
+    }
+    let mut ics = String::new();
+    let entries = fs::read_dir(path).block_error(
+        "calendar",
+        &format!("failed to read directory {}", path.display()),
+    )?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir()
+            || path
+                .extension()
+                .map_or(false, |extension| extension == "ics")
+        {
+            ics.push_str(&read_ics(&path)?);
+            ics.push('\n');
+        }
+    }
+    Ok(ics)
+}
+
+/// Asks a CalDAV server for the events from now on.
+fn fetch_caldav(url: &str, credentials: Option<&str>) -> Result<String> {
+    let now = Utc::now();
+    let query = format!(
+        "<?xml version='1.0' encoding='utf-8'?>\
+         <c:calendar-query xmlns:d='DAV:' xmlns:c='urn:ietf:params:xml:ns:caldav'>\
+         <d:prop><c:calendar-data/></d:prop>\
+         <c:filter><c:comp-filter name='VCALENDAR'><c:comp-filter name='VEVENT'>\
+         <c:time-range start='{}' end='{}'/>\
+         </c:comp-filter></c:comp-filter></c:filter>\
+         </c:calendar-query>",
+        now.format("%Y%m%dT%H%M%SZ"),
+        (now + chrono::Duration::days(LOOKAHEAD_DAYS)).format("%Y%m%dT%H%M%SZ")
+    );
+    let response = http_request(
+        "calendar",
+        &HttpRequest {
+            method: "REPORT",
+            url,
+            headers: &[
+                "Depth: 1".to_string(),
+                "Content-Type: application/xml; charset=utf-8".to_string(),
+            ],
+            body: Some(&query),
+            user: credentials,
+            timeout: Duration::from_secs(20),
+        },
+    )?;
+    if !response.is_success() {
+        return Err(BlockError(
+            "calendar".to_string(),
+            format!("the CalDAV server answered with status {}", response.status),
+        ));
+    }
+    Ok(parse_caldav(&response.text()))
+}
+
+/// Formats the time until an event using its two largest units, like the countdown block.
+fn format_remaining(remaining: chrono::Duration) -> String {
+    // Rounded up, so that an event in 30 seconds is in 1m
+    let minutes = (remaining.num_seconds() + 59) / 60;
+    if minutes >= 24 * 60 {
+        format!("{}d {}h", minutes / (24 * 60), minutes % (24 * 60) / 60)
+    } else if minutes >= 60 {
+        format!("{}h {}m", minutes / 60, minutes % 60)
+    } else {
+        format!("{}m", minutes)
+    }
+}
+
+pub struct Calendar {
+    id: String,
+    output: ButtonWidget,
+    update_interval: Duration,
+    refresh_interval: Duration,
+    format: FormatTemplate,
+    time_format: String,
+    source: CalendarSource,
+    credentials: Option<String>,
+    all_day: bool,
+    warning: chrono::Duration,
+    events: Vec<Event>,
+    /// When the events were read
+    fetched: Option<Instant>,
+    visible: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CalendarConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "CalendarConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Interval in seconds at which the events are read again
+    #[serde(
+        default = "CalendarConfig::default_refresh_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub refresh_interval: Duration,
+
+    /// Where to read the events
+    pub source: CalendarSource,
+
+    /// Format string
+    #[serde(default = "CalendarConfig::default_format")]
+    pub format: String,
+
+    /// Format of the times, see chrono's strftime
+    #[serde(default = "CalendarConfig::default_time_format")]
+    pub time_format: String,
+
+    /// Whether to show events that last whole days
+    #[serde(default)]
+    pub all_day: bool,
+
+    /// Time before the start of an event, in seconds, at which the state is set to warning
+    #[serde(
+        default = "CalendarConfig::default_warning",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub warning: Duration,
+}
+
+impl CalendarConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    fn default_refresh_interval() -> Duration {
+        Duration::from_secs(300)
+    }
+
+    fn default_format() -> String {
+        "{title} in {remaining}".to_owned()
+    }
+
+    fn default_time_format() -> String {
+        "%H:%M".to_owned()
+    }
+
+    fn default_warning() -> Duration {
+        Duration::from_secs(600)
+    }
+}
+
+impl ConfigBlock for Calendar {
+    type Config = CalendarConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        let credentials = match block_config.source {
+            CalendarSource::CalDav {
+                credentials_file: Some(ref path),
+                ..
+            } => {
+                let path = expand_home(path);
+                let credentials = fs::read_to_string(&path).block_error(
+                    "calendar",
+                    &format!("failed to read the credentials from {}", path.display()),
+                )?;
+                Some(credentials.trim().to_string())
+            }
+            _ => None,
+        };
+
+        let id = pseudo_uuid();
+        Ok(Calendar {
+            output: ButtonWidget::new(config, &id),
+            id,
+            update_interval: block_config.interval,
+            refresh_interval: block_config.refresh_interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("calendar", "Invalid format specified")?,
+            time_format: block_config.time_format,
+            source: block_config.source,
+            credentials,
+            all_day: block_config.all_day,
+            warning: chrono::Duration::from_std(block_config.warning)
+                .block_error("calendar", "invalid warning")?,
+            events: Vec::new(),
+            fetched: None,
+            visible: false,
+        })
+    }
+}
+
+impl Calendar {
+    fn read_events(&self) -> Result<Vec<Event>> {
+        match self.source {
+            CalendarSource::Ics { ref path } => Ok(parse_ics(&read_ics(&expand_home(path))?)),
+            CalendarSource::Khal {
+                ref datetime_format,
+                ref date_format,
+            } => {
+                let output = Command::new("khal")
+                    .args(&[
+                        "list",
+                        "--day-format",
+                        "",
+                        "--format",
+                        "{start-long}\t{end-long}\t{title}\t{location}",
+                        "now",
+                        &format!("{}d", LOOKAHEAD_DAYS),
+                    ])
+                    .output()
+                    .block_error("calendar", "failed to run khal")?;
+                if !output.status.success() {
+                    return Err(BlockError(
+                        "calendar".to_string(),
+                        String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                    ));
+                }
+                Ok(parse_khal(
+                    &String::from_utf8_lossy(&output.stdout),
+                    datetime_format,
+                    date_format,
+                ))
+            }
+            CalendarSource::CalDav { ref url, .. } => {
+                Ok(parse_ics(&fetch_caldav(url, self.credentials.as_deref())?))
+            }
+        }
+    }
+}
+
+impl Block for Calendar {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let due = self
+            .fetched
+            .map_or(true, |fetched| fetched.elapsed() >= self.refresh_interval);
+        if due {
+            self.events = self.read_events()?;
+            self.fetched = Some(Instant::now());
+        }
+
+        let now = Utc::now();
+        let next = next_occurrence(&self.events, now, self.all_day);
+        self.visible = next.is_some();
+        if let Some(next) = next {
+            let values = map!(
+                "{title}" => next.title,
+                "{location}" => next.location,
+                "{remaining}" => format_remaining(next.start - now),
+                "{start}" => next.start.with_timezone(&Local).format(&self.time_format).to_string(),
+                "{end}" => next.end.with_timezone(&Local).format(&self.time_format).to_string()
+            );
+            self.output
+                .set_text(self.format.render_static_str(&values)?);
+            self.output.set_state(if next.start - now <= self.warning {
+                State::Warning
+            } else {
+                State::Idle
+            });
+        }
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.visible {
+            vec![&self.output]
+        } else {
+            vec![]
+        }
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ICS: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+UID:standup\r
+SUMMARY:Standup\\, daily\r
+DTSTART;TZID=Europe/Berlin:20240304T093000\r
+DTEND;TZID=Europe/Berlin:20240304T094500\r
+RRULE:FREQ=WEEKLY;BYDAY=MO,WE,FR;UNTIL=20240331T000000Z\r
+EXDATE;TZID=Europe/Berlin:20240315T093000\r
+BEGIN:VALARM\r
+TRIGGER:-PT10M\r
+SUMMARY:Alarm\r
+END:VALARM\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:standup\r
+RECURRENCE-ID;TZID=Europe/Berlin:20240318T093000\r
+SUMMARY:Moved standup\r
+DTSTART;TZID=Europe/Berlin:20240318T110000\r
+DURATION:PT15M\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+UID:review\r
+SUMMARY:Review with a long\r
+  title\r
+LOCATION:Room 1\r
+DTSTART:20240320T080000Z\r
+DTEND:20240320T090000Z\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    fn berlin(day: u32, hour: u32, minute: u32) -> DateTime<Utc> {
+        chrono_tz::Europe::Berlin
+            .ymd(2024, 3, day)
+            .and_hms(hour, minute, 0)
+            .with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_parse_ics() {
+        let events = parse_ics(ICS);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[0].title, "Standup, daily");
+        assert_eq!(events[0].duration, chrono::Duration::minutes(15));
+        assert_eq!(events[0].exdates, vec![berlin(15, 9, 30)]);
+        assert_eq!(events[1].recurrence_id, Some(berlin(18, 9, 30)));
+        assert_eq!(events[2].title, "Review with a long title");
+        assert_eq!(events[2].location, "Room 1");
+    }
+
+    #[test]
+    fn test_next_occurrence() {
+        let events = parse_ics(ICS);
+        let next = |now| next_occurrence(&events, now, false).unwrap();
+        // Wednesday
+        assert_eq!(next(berlin(13, 8, 0)).start, berlin(13, 9, 30));
+        // Friday, which is excluded, moves to Monday, which is replaced
+        let moved = next(berlin(14, 10, 0));
+        assert_eq!(moved.title, "Moved standup");
+        assert_eq!(moved.start, berlin(18, 11, 0));
+        assert_eq!(moved.end, berlin(18, 11, 15));
+        assert_eq!(next(berlin(18, 12, 0)).start, berlin(20, 9, 0));
+        // The rule ends before the 1st of April
+        assert_eq!(next(berlin(27, 10, 0)).start, berlin(29, 9, 30));
+        assert!(next_occurrence(&events, berlin(29, 10, 0), false).is_none());
+    }
+
+    #[test]
+    fn test_expand_monthly() {
+        let start = Time {
+            naive: NaiveDate::from_ymd(2024, 1, 26).and_hms(12, 0, 0),
+            zone: Zone::Utc,
+        };
+        let limit = Utc.ymd(2024, 12, 31).and_hms(0, 0, 0);
+        // The last Friday of every month, three times
+        let rule = parse_rule("FREQ=MONTHLY;BYDAY=-1FR;COUNT=3").unwrap();
+        assert_eq!(
+            expand(start, &rule, limit),
+            vec![
+                Utc.ymd(2024, 1, 26).and_hms(12, 0, 0),
+                Utc.ymd(2024, 2, 23).and_hms(12, 0, 0),
+                Utc.ymd(2024, 3, 29).and_hms(12, 0, 0),
+            ]
+        );
+        // Every month on the 31st, which some months do not have
+        let start = start.with_naive(NaiveDate::from_ymd(2024, 1, 31).and_hms(12, 0, 0));
+        let rule = parse_rule("FREQ=MONTHLY;UNTIL=20240801").unwrap();
+        assert_eq!(
+            expand(start, &rule, limit),
+            vec![
+                Utc.ymd(2024, 1, 31).and_hms(12, 0, 0),
+                Utc.ymd(2024, 3, 31).and_hms(12, 0, 0),
+                Utc.ymd(2024, 5, 31).and_hms(12, 0, 0),
+                Utc.ymd(2024, 7, 31).and_hms(12, 0, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_khal() {
+        let events = parse_khal(
+            "2024-03-13 09:30\t2024-03-13 10:00\tStandup\tRoom 1\n2024-03-14\t2024-03-14\tHoliday\t\n",
+            "%Y-%m-%d %H:%M",
+            "%Y-%m-%d",
+        );
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].title, "Standup");
+        assert_eq!(events[0].duration, chrono::Duration::minutes(30));
+        assert_eq!(events[1].start.zone, Zone::AllDay);
+        assert_eq!(events[1].duration, chrono::Duration::days(1));
+    }
+
+    #[test]
+    fn test_parse_caldav() {
+        let xml = "<d:multistatus xmlns:d=\"DAV:\" xmlns:cal=\"urn:ietf:params:xml:ns:caldav\">\
+            <d:response><d:propstat><d:prop>\
+            <cal:calendar-data>BEGIN:VCALENDAR&#13;\nSUMMARY:Tom &amp; Jerry&#13;\nEND:VCALENDAR</cal:calendar-data>\
+            </d:prop></d:propstat></d:response></d:multistatus>";
+        assert_eq!(
+            parse_caldav(xml),
+            "BEGIN:VCALENDAR\r\nSUMMARY:Tom & Jerry\r\nEND:VCALENDAR"
+        );
+    }
+
+    #[test]
+    fn test_format_remaining() {
+        assert_eq!(format_remaining(chrono::Duration::seconds(30)), "1m");
+        assert_eq!(format_remaining(chrono::Duration::minutes(80)), "1h 20m");
+        assert_eq!(format_remaining(chrono::Duration::hours(50)), "2d 2h");
+    }
+}
@@ -0,0 +1,373 @@
+//! A block for the latest CI runs of repositories on GitHub Actions and GitLab CI.
+
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::util::{http_get, pseudo_uuid, read_token, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Provider {
+    Github,
+    Gitlab,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PipelineConfig {
+    pub provider: Provider,
+
+    /// The repository, like `owner/repo`, or the path of the project on GitLab
+    pub repo: String,
+
+    /// Only the runs of this branch, the runs of all branches if not set
+    #[serde(default)]
+    pub branch: Option<String>,
+
+    /// The name to show, the repository if not set
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+/// The latest run of a pipeline.
+#[derive(Debug, Clone, PartialEq)]
+struct Run {
+    /// Like `success`, `failure` or `running`
+    status: String,
+    url: String,
+}
+
+/// The latest run of a GitHub Actions workflow, from `/repos/{repo}/actions/runs`.
+fn parse_github_runs(body: &str) -> Option<Run> {
+    let runs: Value = serde_json::from_str(body).ok()?;
+    let run = &runs["workflow_runs"][0];
+    let status = match run["status"].as_str()? {
+        // The conclusion is only known once the run is completed
+        "completed" => run["conclusion"].as_str().unwrap_or("neutral"),
+        "in_progress" => "running",
+        // queued, requested, waiting and pending
+        _ => "pending",
+    };
+    Some(Run {
+        status: status.to_string(),
+        url: run["html_url"].as_str().unwrap_or("").to_string(),
+    })
+}
+
+/// The latest pipeline of a GitLab project, from `/projects/{id}/pipelines`.
+fn parse_gitlab_pipelines(body: &str) -> Option<Run> {
+    let pipelines: Value = serde_json::from_str(body).ok()?;
+    let pipeline = &pipelines[0];
+    let status = match pipeline["status"].as_str()? {
+        "failed" => "failure",
+        "canceled" => "cancelled",
+        "created" | "waiting_for_resource" | "preparing" | "scheduled" => "pending",
+        status => status,
+    };
+    Some(Run {
+        status: status.to_string(),
+        url: pipeline["web_url"].as_str().unwrap_or("").to_string(),
+    })
+}
+
+struct Pipeline {
+    config: PipelineConfig,
+    /// The ETag of the last response, so that unchanged runs are not sent again
+    etag: Option<String>,
+    run: Option<Run>,
+}
+
+pub struct Ci {
+    id: String,
+    update_interval: Duration,
+    format: FormatTemplate,
+    pipelines: Vec<Pipeline>,
+    github_api: String,
+    gitlab_server: String,
+    github_token: Option<String>,
+    gitlab_token: Option<String>,
+    config: Config,
+    widgets: Vec<ButtonWidget>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CiConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "CiConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// The pipelines to show
+    pub pipelines: Vec<PipelineConfig>,
+
+    /// Format string
+    #[serde(default = "CiConfig::default_format")]
+    pub format: String,
+
+    /// File that contains a GitHub token, which private repositories need
+    #[serde(default)]
+    pub github_token_file: Option<String>,
+
+    /// File that contains a GitLab token, which private projects need
+    #[serde(default)]
+    pub gitlab_token_file: Option<String>,
+
+    /// The API of GitHub, or of a GitHub Enterprise server
+    #[serde(default = "CiConfig::default_github_api")]
+    pub github_api: String,
+
+    /// The GitLab server
+    #[serde(default = "CiConfig::default_gitlab_server")]
+    pub gitlab_server: String,
+}
+
+impl CiConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(120)
+    }
+
+    fn default_format() -> String {
+        "{name}".to_owned()
+    }
+
+    fn default_github_api() -> String {
+        "https://api.github.com".to_owned()
+    }
+
+    fn default_gitlab_server() -> String {
+        "https://gitlab.com".to_owned()
+    }
+}
+
+impl ConfigBlock for Ci {
+    type Config = CiConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        let read = |path: &Option<String>| {
+            path.as_deref()
+                .map(|path| read_token("ci", path))
+                .transpose()
+        };
+        Ok(Ci {
+            id: pseudo_uuid(),
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("ci", "Invalid format specified")?,
+            github_token: read(&block_config.github_token_file)?,
+            gitlab_token: read(&block_config.gitlab_token_file)?,
+            pipelines: block_config
+                .pipelines
+                .into_iter()
+                .map(|config| Pipeline {
+                    config,
+                    etag: None,
+                    run: None,
+                })
+                .collect(),
+            github_api: block_config.github_api,
+            gitlab_server: block_config.gitlab_server,
+            config,
+            widgets: Vec::new(),
+        })
+    }
+}
+
+impl Ci {
+    /// The name of the widget of a pipeline, to tell which pipeline was clicked.
+    fn widget_name(&self, index: usize) -> String {
+        format!("{}:{}", self.id, index)
+    }
+
+    /// Asks for the latest run of the pipeline, unless it is the same as the last time.
+    fn check(&self, pipeline: &mut Pipeline) -> Result<()> {
+        let config = &pipeline.config;
+        let (mut url, token, parse): (String, _, fn(&str) -> Option<Run>) = match config.provider {
+            Provider::Github => (
+                format!(
+                    "{}/repos/{}/actions/runs?per_page=1",
+                    self.github_api, config.repo
+                ),
+                self.github_token
+                    .as_ref()
+                    .map(|token| format!("Authorization: Bearer {}", token)),
+                parse_github_runs,
+            ),
+            Provider::Gitlab => (
+                format!(
+                    "{}/api/v4/projects/{}/pipelines?per_page=1",
+                    self.gitlab_server,
+                    config.repo.replace('/', "%2F")
+                ),
+                self.gitlab_token
+                    .as_ref()
+                    .map(|token| format!("PRIVATE-TOKEN: {}", token)),
+                parse_gitlab_pipelines,
+            ),
+        };
+        if let Some(ref branch) = config.branch {
+            let key = match config.provider {
+                Provider::Github => "branch",
+                Provider::Gitlab => "ref",
+            };
+            url.push_str(&format!("&{}={}", key, branch));
+        }
+
+        let mut headers: Vec<String> = token.into_iter().collect();
+        if let Some(ref etag) = pipeline.etag {
+            headers.push(format!("If-None-Match: {}", etag));
+        }
+        let response = http_get("ci", &url, &headers, Duration::from_secs(5))?;
+        match response.status {
+            // Not modified, which does not count against the rate limit of GitHub
+            304 => {}
+            200 => {
+                pipeline.etag = response.headers.get("etag").cloned();
+                pipeline.run = parse(&response.text());
+            }
+            _ => {
+                return Err(BlockError(
+                    "ci".to_string(),
+                    format!("unexpected status {} for {}", response.status, config.repo),
+                ))
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Block for Ci {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mut pipelines = std::mem::replace(&mut self.pipelines, Vec::new());
+        // A pipeline that cannot be checked keeps its last run, so that one unreachable
+        // server does not hide the others
+        let results: Vec<Result<()>> = pipelines
+            .iter_mut()
+            .map(|pipeline| self.check(pipeline))
+            .collect();
+        self.pipelines = pipelines;
+
+        let mut widgets = Vec::new();
+        for (index, (pipeline, result)) in self.pipelines.iter().zip(results).enumerate() {
+            let config = &pipeline.config;
+            let status = match (&pipeline.run, result) {
+                (Some(run), _) => run.status.clone(),
+                (None, Ok(())) => "none".to_string(),
+                (None, Err(_)) => "unknown".to_string(),
+            };
+            let values = map!(
+                "{name}" => config.name.clone().unwrap_or_else(|| config.repo.clone()),
+                "{repo}" => config.repo.clone(),
+                "{branch}" => config.branch.clone().unwrap_or_default(),
+                "{status}" => status.clone()
+            );
+            let state = match status.as_str() {
+                "success" => State::Good,
+                "failure" | "timed_out" => State::Critical,
+                "running" | "pending" => State::Warning,
+                // cancelled, skipped, manual and none
+                _ => State::Idle,
+            };
+            widgets.push(
+                ButtonWidget::new(self.config.clone(), &self.widget_name(index))
+                    .with_text(&self.format.render_static_str(&values)?)
+                    .with_state(state),
+            );
+        }
+        self.widgets = widgets;
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.widgets
+            .iter()
+            .map(|widget| widget as &dyn I3BarWidget)
+            .collect()
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button != MouseButton::Left {
+            return Ok(());
+        }
+        let name = match event.name {
+            Some(ref name) => name,
+            None => return Ok(()),
+        };
+        let prefix = format!("{}:", self.id);
+        let run = if name.starts_with(&prefix) {
+            name[prefix.len()..]
+                .parse::<usize>()
+                .ok()
+                .and_then(|index| self.pipelines.get(index))
+                .and_then(|pipeline| pipeline.run.as_ref())
+        } else {
+            None
+        };
+        if let Some(run) = run.filter(|run| !run.url.is_empty()) {
+            spawn_child_async("xdg-open", &[&run.url])
+                .block_error("ci", "could not spawn child")?;
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_github_runs, parse_gitlab_pipelines, Run};
+
+    #[test]
+    fn test_parse_github_runs() {
+        let body = r#"{"total_count": 2, "workflow_runs": [
+            {"status": "completed", "conclusion": "failure", "html_url": "https://github.com/o/r/actions/runs/2"},
+            {"status": "completed", "conclusion": "success", "html_url": "https://github.com/o/r/actions/runs/1"}
+        ]}"#;
+        assert_eq!(
+            parse_github_runs(body),
+            Some(Run {
+                status: "failure".to_string(),
+                url: "https://github.com/o/r/actions/runs/2".to_string(),
+            })
+        );
+        let body =
+            r#"{"workflow_runs": [{"status": "queued", "conclusion": null, "html_url": ""}]}"#;
+        assert_eq!(parse_github_runs(body).unwrap().status, "pending");
+        assert_eq!(
+            parse_github_runs(r#"{"total_count": 0, "workflow_runs": []}"#),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_gitlab_pipelines() {
+        let body = r#"[{"id": 47, "status": "failed", "ref": "main", "web_url": "https://gitlab.com/g/p/-/pipelines/47"}]"#;
+        assert_eq!(
+            parse_gitlab_pipelines(body),
+            Some(Run {
+                status: "failure".to_string(),
+                url: "https://gitlab.com/g/p/-/pipelines/47".to_string(),
+            })
+        );
+        let body = r#"[{"status": "running", "web_url": ""}]"#;
+        assert_eq!(parse_gitlab_pipelines(body).unwrap().status, "running");
+        assert_eq!(parse_gitlab_pipelines("[]"), None);
+    }
+}
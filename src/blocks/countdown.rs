@@ -0,0 +1,383 @@
+use std::fs::read_to_string;
+use std::time::Duration;
+
+use chrono::{offset::Local, DateTime, NaiveDate, NaiveDateTime, TimeZone, Utc};
+use chrono_tz::Tz;
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+struct Event {
+    name: String,
+    time: DateTime<Utc>,
+}
+
+pub struct Countdown {
+    id: String,
+    text: ButtonWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    warning: Duration,
+    critical: Duration,
+    timezone: Option<Tz>,
+    events: Vec<Event>,
+    ics_file: Option<String>,
+    /// Index into the upcoming events, changed by clicking the block.
+    selected: usize,
+    upcoming: usize,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CountdownEventConfig {
+    /// Name of the event
+    pub name: String,
+
+    /// Date and time of the event, e.g. "2021-12-24 18:00" or "2021-12-24T18:00:00+01:00"
+    pub time: String,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CountdownConfig {
+    /// List of events to count down to
+    #[serde(default = "CountdownConfig::default_events")]
+    pub events: Vec<CountdownEventConfig>,
+
+    /// Path to an iCalendar file to read events from
+    #[serde(default = "CountdownConfig::default_ics_file")]
+    pub ics_file: Option<String>,
+
+    /// Format override
+    #[serde(default = "CountdownConfig::default_format")]
+    pub format: String,
+
+    /// Update interval in seconds
+    #[serde(
+        default = "CountdownConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Time left, in seconds, at which the state is set to warning
+    #[serde(
+        default = "CountdownConfig::default_warning",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub warning: Duration,
+
+    /// Time left, in seconds, at which the state is set to critical
+    #[serde(
+        default = "CountdownConfig::default_critical",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub critical: Duration,
+
+    /// Timezone in which event times without an offset are interpreted
+    #[serde(default = "CountdownConfig::default_timezone")]
+    pub timezone: Option<Tz>,
+}
+
+impl CountdownConfig {
+    fn default_events() -> Vec<CountdownEventConfig> {
+        Vec::new()
+    }
+
+    fn default_ics_file() -> Option<String> {
+        None
+    }
+
+    fn default_format() -> String {
+        "{name} {remaining}".to_owned()
+    }
+
+    fn default_interval() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    fn default_warning() -> Duration {
+        Duration::from_secs(86_400)
+    }
+
+    fn default_critical() -> Duration {
+        Duration::from_secs(3600)
+    }
+
+    fn default_timezone() -> Option<Tz> {
+        None
+    }
+}
+
+/// Interprets a date and time without offset in the given timezone, or the local one.
+fn resolve_local(naive: NaiveDateTime, timezone: Option<Tz>) -> Option<DateTime<Utc>> {
+    match timezone {
+        Some(tz) => tz
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|t| t.with_timezone(&Utc)),
+        None => Local
+            .from_local_datetime(&naive)
+            .earliest()
+            .map(|t| t.with_timezone(&Utc)),
+    }
+}
+
+fn parse_time(time: &str, timezone: Option<Tz>) -> Option<DateTime<Utc>> {
+    if let Ok(t) = DateTime::parse_from_rfc3339(time) {
+        return Some(t.with_timezone(&Utc));
+    }
+    for format in &["%Y-%m-%d %H:%M:%S", "%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(naive) = NaiveDateTime::parse_from_str(time, format) {
+            return resolve_local(naive, timezone);
+        }
+    }
+    NaiveDate::parse_from_str(time, "%Y-%m-%d")
+        .ok()
+        .and_then(|date| resolve_local(date.and_hms(0, 0, 0), timezone))
+}
+
+/// Parses an iCalendar `DTSTART` property, given its parameters and value.
+fn parse_ics_time(params: &str, value: &str, timezone: Option<Tz>) -> Option<DateTime<Utc>> {
+    if let Some(value) = value.strip_suffix('Z') {
+        return NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+            .ok()
+            .map(|naive| DateTime::<Utc>::from_utc(naive, Utc));
+    }
+    let tzid = params
+        .split(';')
+        .find_map(|param| param.strip_prefix("TZID="))
+        .and_then(|tzid| tzid.trim_matches('"').parse::<Tz>().ok())
+        .or(timezone);
+    if let Ok(naive) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S") {
+        return resolve_local(naive, tzid);
+    }
+    NaiveDate::parse_from_str(value, "%Y%m%d")
+        .ok()
+        .and_then(|date| resolve_local(date.and_hms(0, 0, 0), tzid))
+}
+
+/// Reads the `SUMMARY` and `DTSTART` of every `VEVENT` in an iCalendar file.
+/// Recurrence rules are not supported.
+fn read_ics_events(path: &str, timezone: Option<Tz>) -> Result<Vec<Event>> {
+    let content = read_to_string(path).block_error(
+        "countdown",
+        &format!("failed to read iCalendar file {}", path),
+    )?;
+
+    // Unfold continuation lines, which start with a space or a tab.
+    let mut lines: Vec<String> = Vec::new();
+    for line in content.lines() {
+        match (
+            line.strip_prefix(' ').or_else(|| line.strip_prefix('\t')),
+            lines.last_mut(),
+        ) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ => lines.push(line.to_string()),
+        }
+    }
+
+    let mut events = Vec::new();
+    let mut name = None;
+    let mut time = None;
+    for line in &lines {
+        let (property, value) = match line.find(':') {
+            Some(i) => (&line[..i], &line[i + 1..]),
+            None => continue,
+        };
+        let (key, params) = match property.find(';') {
+            Some(i) => (&property[..i], &property[i + 1..]),
+            None => (property, ""),
+        };
+        match (key, value) {
+            ("BEGIN", "VEVENT") => {
+                name = None;
+                time = None;
+            }
+            ("SUMMARY", _) => name = Some(value.replace("\\,", ",").replace("\\;", ";")),
+            ("DTSTART", _) => time = parse_ics_time(params, value, timezone),
+            ("END", "VEVENT") => {
+                if let Some(time) = time.take() {
+                    events.push(Event {
+                        name: name.take().unwrap_or_default(),
+                        time,
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(events)
+}
+
+/// Formats a duration using its two largest units, like the uptime block.
+fn format_remaining(remaining: Duration) -> String {
+    let total = remaining.as_secs();
+    let days = total / 86_400;
+    let hours = total % 86_400 / 3600;
+    let minutes = total % 3600 / 60;
+    let seconds = total % 60;
+    if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else {
+        format!("{}m {}s", minutes, seconds)
+    }
+}
+
+impl ConfigBlock for Countdown {
+    type Config = CountdownConfig;
+
+    fn new(
+        block_config: Self::Config,
+        config: Config,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let id = pseudo_uuid();
+        let timezone = block_config.timezone;
+        let events = block_config
+            .events
+            .into_iter()
+            .map(|event| {
+                let time = parse_time(&event.time, timezone).block_error(
+                    "countdown",
+                    &format!("invalid time '{}' for event '{}'", event.time, event.name),
+                )?;
+                Ok(Event {
+                    name: event.name,
+                    time,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(Countdown {
+            text: ButtonWidget::new(config, &id).with_icon("time"),
+            id,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("countdown", "Invalid format specified for countdown")?,
+            update_interval: block_config.interval,
+            warning: block_config.warning,
+            critical: block_config.critical,
+            timezone,
+            events,
+            ics_file: block_config.ics_file,
+            selected: 0,
+            upcoming: 0,
+        })
+    }
+}
+
+impl Block for Countdown {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let now = Utc::now();
+        let ics_events = match self.ics_file {
+            Some(ref path) => read_ics_events(path, self.timezone)?,
+            None => Vec::new(),
+        };
+        let mut upcoming: Vec<&Event> = self
+            .events
+            .iter()
+            .chain(ics_events.iter())
+            .filter(|event| event.time > now)
+            .collect();
+        upcoming.sort_by_key(|event| event.time);
+
+        self.upcoming = upcoming.len();
+        if self.selected >= self.upcoming {
+            self.selected = 0;
+        }
+
+        let event = match upcoming.get(self.selected) {
+            Some(event) => event,
+            None => {
+                self.text.set_state(State::Idle);
+                self.text.set_text("no events");
+                return Ok(Some(self.update_interval.into()));
+            }
+        };
+
+        let remaining = (event.time - now).to_std().unwrap_or_default();
+        let values = map!(
+            "{name}" => event.name.clone(),
+            "{remaining}" => format_remaining(remaining)
+        );
+        self.text.set_text(self.format.render_static_str(&values)?);
+        self.text.set_state(if remaining <= self.critical {
+            State::Critical
+        } else if remaining <= self.warning {
+            State::Warning
+        } else {
+            State::Idle
+        });
+
+        // Seconds are shown for the last hour, so refresh more often.
+        if remaining.as_secs() < 3600 {
+            Ok(Some(
+                self.update_interval.min(Duration::from_secs(1)).into(),
+            ))
+        } else {
+            Ok(Some(self.update_interval.into()))
+        }
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.matches_name(&self.id) {
+            match event.button {
+                MouseButton::Left if self.upcoming > 0 => {
+                    self.selected = (self.selected + 1) % self.upcoming;
+                }
+                MouseButton::Right => self.selected = 0,
+                _ => return Ok(()),
+            }
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        match event.button {
+            MouseButton::Left => self.upcoming > 0,
+            MouseButton::Right => true,
+            _ => false,
+        }
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_ics_time() {
+        let utc = parse_ics_time("", "20211224T170000Z", None).unwrap();
+        assert_eq!(utc.to_rfc3339(), "2021-12-24T17:00:00+00:00");
+        let berlin = parse_ics_time("TZID=Europe/Berlin", "20211224T180000", None).unwrap();
+        assert_eq!(berlin, utc);
+        let date = parse_ics_time("VALUE=DATE", "20211224", Some(chrono_tz::UTC)).unwrap();
+        assert_eq!(date.to_rfc3339(), "2021-12-24T00:00:00+00:00");
+    }
+
+    #[test]
+    fn test_format_remaining() {
+        assert_eq!(format_remaining(Duration::from_secs(90_061)), "1d 1h");
+        assert_eq!(format_remaining(Duration::from_secs(3_661)), "1h 1m");
+        assert_eq!(format_remaining(Duration::from_secs(61)), "1m 1s");
+    }
+}
@@ -1,7 +1,8 @@
-use std::fs::File;
+use std::fs::{read_dir, File, OpenOptions};
 use std::io::prelude::*;
 use std::io::BufReader;
-use std::time::Duration;
+use std::path::Path;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
@@ -14,27 +15,28 @@ use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::subprocess::spawn_child_async;
-use crate::util::{format_percent_bar, pseudo_uuid, FormatTemplate};
+use crate::util::{format_percent_bar, pseudo_uuid, read_file, FormatTemplate};
 use crate::widget::{I3BarWidget, State};
 use crate::widgets::button::ButtonWidget;
 
-/// Maximum number of CPUs we support.
-const MAX_CPUS: usize = 32;
-
 pub struct Cpu {
     output: ButtonWidget,
-    prev_idles: [u64; MAX_CPUS],
-    prev_non_idles: [u64; MAX_CPUS],
+    /// Counters of the previous update, for all CPUs and then each core
+    prev_idles: Vec<u64>,
+    prev_non_idles: Vec<u64>,
     id: String,
     update_interval: Duration,
     minimum_info: u64,
     minimum_warning: u64,
     minimum_critical: u64,
-    on_click: Option<String>,
     format: FormatTemplate,
     has_barchart: bool,
     has_frequency: bool,
+    has_governor: bool,
     per_core: bool,
+    governors: Vec<String>,
+    governor_command: Option<String>,
+    tx_update_request: Sender<Task>,
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -59,9 +61,6 @@ pub struct CpuConfig {
     #[serde(default = "CpuConfig::default_critical")]
     pub critical: u64,
 
-    #[serde(default = "CpuConfig::default_on_click")]
-    pub on_click: Option<String>,
-
     /// Display frequency
     #[serde(default = "CpuConfig::default_frequency")]
     pub frequency: bool,
@@ -73,6 +72,14 @@ pub struct CpuConfig {
     /// Compute the metrics (utilization and frequency) per core.
     #[serde(default)]
     pub per_core: bool,
+
+    /// Governors to cycle through on click
+    #[serde(default)]
+    pub governors: Vec<String>,
+
+    /// Shell command that sets the governor, instead of writing to sysfs
+    #[serde(default)]
+    pub governor_command: Option<String>,
 }
 
 impl CpuConfig {
@@ -99,10 +106,6 @@ impl CpuConfig {
     fn default_frequency() -> bool {
         false
     }
-
-    fn default_on_click() -> Option<String> {
-        None
-    }
 }
 
 impl ConfigBlock for Cpu {
@@ -111,7 +114,7 @@ impl ConfigBlock for Cpu {
     fn new(
         block_config: Self::Config,
         config: Config,
-        _tx_update_request: Sender<Task>,
+        tx_update_request: Sender<Task>,
     ) -> Result<Self> {
         let format = if block_config.frequency {
             "{utilization}% {frequency}GHz".into()
@@ -120,6 +123,8 @@ impl ConfigBlock for Cpu {
         } else {
             block_config.format
         };
+        let format = FormatTemplate::from_string(&format)
+            .block_error("cpu", "Invalid format specified for cpu")?;
 
         let id = pseudo_uuid();
 
@@ -127,17 +132,20 @@ impl ConfigBlock for Cpu {
             id: id.clone(),
             update_interval: block_config.interval,
             output: ButtonWidget::new(config, &id).with_icon("cpu"),
-            prev_idles: [0; MAX_CPUS],
-            prev_non_idles: [0; MAX_CPUS],
+            prev_idles: Vec::new(),
+            prev_non_idles: Vec::new(),
             minimum_info: block_config.info,
             minimum_warning: block_config.warning,
             minimum_critical: block_config.critical,
-            format: FormatTemplate::from_string(&format)
-                .block_error("cpu", "Invalid format specified for cpu")?,
-            has_frequency: format.contains("{frequency}"),
-            has_barchart: format.contains("{barchart}"),
+            has_frequency: format.has_placeholder("{frequency}")
+                || format.has_placeholder("{frequency_max}"),
+            has_barchart: format.has_placeholder("{barchart}"),
+            has_governor: format.has_placeholder("{governor}"),
+            format,
             per_core: block_config.per_core,
-            on_click: block_config.on_click,
+            governors: block_config.governors,
+            governor_command: block_config.governor_command,
+            tx_update_request,
         })
     }
 }
@@ -148,8 +156,7 @@ impl Block for Cpu {
             .block_error("cpu", "Your system doesn't support /proc/stat")?;
         let f = BufReader::new(f);
 
-        let mut cpu_freqs: [f32; MAX_CPUS] = [0.0; MAX_CPUS];
-        let mut n_cpu = 0;
+        let mut cpu_freqs: Vec<f32> = Vec::new();
         if self.has_frequency {
             let freq_file =
                 File::open("/proc/cpuinfo").block_error("cpu", "failed to read /proc/cpuinfo")?;
@@ -164,19 +171,19 @@ impl Block for Cpu {
                     let numb = last
                         .parse::<f32>()
                         .expect("failed to parse String to f32 while getting cpu frequency");
-                    cpu_freqs[n_cpu] = numb;
-                    n_cpu += 1;
-                    if n_cpu >= MAX_CPUS {
-                        break;
-                    };
+                    cpu_freqs.push(numb);
                 }
             }
+            // Only x86 reports the frequency in /proc/cpuinfo
+            if cpu_freqs.is_empty() {
+                cpu_freqs = cpufreq_frequencies();
+            }
         }
 
-        let mut cpu_utilizations: [f64; MAX_CPUS] = [0.0; MAX_CPUS];
-        let mut cpu_i = 0;
+        let mut cpu_utilizations: Vec<f64> = Vec::new();
         for line in f.lines().scan((), |_, x| x.ok()) {
             if line.starts_with("cpu") {
+                let cpu_i = cpu_utilizations.len();
                 let data: Vec<u64> = (&line)
                     .split(' ')
                     .collect::<Vec<&str>>()
@@ -194,6 +201,11 @@ impl Block for Cpu {
                                 data[6] + // softirq
                                 data[7]; // steal
 
+                // Cores may come online after the first update
+                if self.prev_idles.len() <= cpu_i {
+                    self.prev_idles.resize(cpu_i + 1, 0);
+                    self.prev_non_idles.resize(cpu_i + 1, 0);
+                }
                 let prev_total = self.prev_idles[cpu_i] + self.prev_non_idles[cpu_i];
                 let total = idle + non_idle;
 
@@ -207,17 +219,19 @@ impl Block for Cpu {
                         (1, 1)
                     };
 
-                cpu_utilizations[cpu_i] = (total_delta - idle_delta) as f64 / total_delta as f64;
+                cpu_utilizations.push((total_delta - idle_delta) as f64 / total_delta as f64);
 
                 self.prev_idles[cpu_i] = idle;
                 self.prev_non_idles[cpu_i] = non_idle;
-                cpu_i += 1;
-                if cpu_i >= MAX_CPUS {
-                    break;
-                };
             }
         }
 
+        if cpu_utilizations.is_empty() {
+            return Err(BlockError(
+                "cpu".to_string(),
+                "No CPU found in /proc/stat".to_string(),
+            ));
+        }
         let avg_utilization = (100.0 * cpu_utilizations[0]) as u64;
 
         self.output.set_state(match avg_utilization {
@@ -226,24 +240,37 @@ impl Block for Cpu {
             x if x > self.minimum_info => State::Info,
             _ => State::Idle,
         });
+        self.output.set_value(Some(
+            avg_utilization as f64 / self.minimum_critical.max(1) as f64,
+        ));
+        self.output.set_percentage(Some(avg_utilization as f64));
 
         let mut barchart = String::new();
 
         if self.has_barchart {
             const BOXCHARS: &[char] = &['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
 
-            for i in 1..cpu_i {
+            for utilization in cpu_utilizations.iter().skip(1) {
                 barchart.push(
-                    BOXCHARS[((7.5 * cpu_utilizations[i]) as usize)
+                    BOXCHARS[((7.5 * utilization) as usize)
                         // TODO: Replace with .clamp once the feature is stable
                         // upper bound just in case the value is negative, e.g. USIZE MAX after conversion
                         .min(BOXCHARS.len() - 1)],
                 );
             }
         }
-        let values = map!("{frequency}" => format_frequency(&cpu_freqs, n_cpu, self.per_core),
+        let frequency_max = cpu_freqs.iter().cloned().fold(0.0, f32::max) / 1000.0;
+        let governor = if self.has_governor {
+            current_governor().unwrap_or_default()
+        } else {
+            String::new()
+        };
+        let values = map!("{frequency}" => format_frequency(&cpu_freqs, self.per_core),
                           "{barchart}" => barchart,
-                          "{utilization}" => format_utilization(&cpu_utilizations, cpu_i, self.per_core),
+                          "{core_max}" => format!("{:02.0}", 100.0 * core_max(&cpu_utilizations)),
+                          "{frequency_max}" => format!("{:.1}", frequency_max),
+                          "{governor}" => governor,
+                          "{utilization}" => format_utilization(&cpu_utilizations, self.per_core),
                           "{utilizationbar}" => format_percent_bar(avg_utilization as f32));
 
         self.output
@@ -256,29 +283,42 @@ impl Block for Cpu {
         vec![&self.output]
     }
 
-    fn click(&mut self, e: &I3BarEvent) -> Result<()> {
-        if e.matches_name(self.id()) {
-            if let MouseButton::Left = e.button {
-                if let Some(ref cmd) = self.on_click {
-                    spawn_child_async("sh", &["-c", cmd])
-                        .block_error("cpu", "could not spawn child")?;
-                }
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if !event.matches_name(&self.id)
+            || event.button != MouseButton::Left
+            || self.governors.is_empty()
+        {
+            return Ok(());
+        }
+        let governor = next_governor(&self.governors, current_governor().as_deref());
+        match self.governor_command {
+            Some(ref command) => {
+                spawn_child_async("sh", &["-c", &command.replace("{governor}", governor)])
+                    .block_error("cpu", "could not spawn the governor command")?;
             }
+            None => set_governor(governor)?,
         }
+        self.tx_update_request.send(Task {
+            id: self.id.clone(),
+            update_time: Instant::now(),
+        })?;
         Ok(())
     }
 
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left && !self.governors.is_empty()
+    }
+
     fn id(&self) -> &str {
         &self.id
     }
 }
 
 #[inline]
-fn format_utilization(values: &[f64], count: usize, per_core: bool) -> String {
+fn format_utilization(values: &[f64], per_core: bool) -> String {
     if per_core {
         values
             .iter()
-            .take(count)
             .skip(1) // The first value is a global one.
             .map(|v| format!("{:02.0}%", 100.0 * v))
             .collect::<Vec<String>>()
@@ -288,17 +328,107 @@ fn format_utilization(values: &[f64], count: usize, per_core: bool) -> String {
     }
 }
 
+/// The utilization of the busiest core, which shows spikes of single-threaded work that the
+/// average hides.
+fn core_max(values: &[f64]) -> f64 {
+    values
+        .iter()
+        .skip(1) // The first value is a global one.
+        .cloned()
+        .fold(0.0, f64::max)
+}
+
 #[inline]
-fn format_frequency(cpu_freqs: &[f32], count: usize, per_core: bool) -> String {
+fn format_frequency(cpu_freqs: &[f32], per_core: bool) -> String {
     if per_core {
         cpu_freqs
             .iter()
-            .take(count)
             .map(|v| format!("{0:.1}GHz", v / 1000.0))
             .collect::<Vec<String>>()
             .join(" ")
     } else {
-        let avg = cpu_freqs.iter().take(count).sum::<f32>() / (count as f32) / 1000.0;
+        let avg = cpu_freqs.iter().sum::<f32>() / (cpu_freqs.len() as f32) / 1000.0;
         format!("{:.1}", avg)
     }
 }
+
+/// The cpufreq directories of all cores.
+fn cpufreq_dirs() -> Vec<std::path::PathBuf> {
+    let mut dirs: Vec<_> = read_dir("/sys/devices/system/cpu")
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.path().join("cpufreq"))
+                .filter(|path| path.is_dir())
+                .collect()
+        })
+        .unwrap_or_default();
+    dirs.sort();
+    dirs
+}
+
+/// The current frequency of each core in MHz, from cpufreq.
+fn cpufreq_frequencies() -> Vec<f32> {
+    cpufreq_dirs()
+        .iter()
+        .filter_map(|dir| read_file("cpu", &dir.join("scaling_cur_freq")).ok())
+        .filter_map(|khz| khz.trim().parse::<f32>().ok())
+        .map(|khz| khz / 1000.0)
+        .collect()
+}
+
+/// The governor of the first core, which is usually the one of all cores.
+fn current_governor() -> Option<String> {
+    read_file(
+        "cpu",
+        Path::new("/sys/devices/system/cpu/cpu0/cpufreq/scaling_governor"),
+    )
+    .ok()
+}
+
+/// The governor after the current one in the list, or the first one.
+fn next_governor<'a>(governors: &'a [String], current: Option<&str>) -> &'a str {
+    let next = governors
+        .iter()
+        .position(|governor| Some(governor.as_str()) == current)
+        .map_or(0, |i| (i + 1) % governors.len());
+    &governors[next]
+}
+
+/// Sets the governor of all cores, which needs write access to sysfs, for example through a
+/// udev rule.
+fn set_governor(governor: &str) -> Result<()> {
+    for dir in cpufreq_dirs() {
+        OpenOptions::new()
+            .write(true)
+            .open(dir.join("scaling_governor"))
+            .and_then(|mut file| file.write_all(governor.as_bytes()))
+            .block_error(
+                "cpu",
+                "Failed to set the governor, set governor_command or allow writing to sysfs",
+            )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{core_max, next_governor};
+
+    #[test]
+    fn test_core_max() {
+        assert_eq!(core_max(&[0.3, 0.1, 0.95, 0.2, 0.0]), 0.95);
+        // The global value is not a core
+        assert_eq!(core_max(&[0.5, 0.25, 0.25]), 0.25);
+        assert_eq!(core_max(&[0.5]), 0.0);
+    }
+
+    #[test]
+    fn test_next_governor() {
+        let governors = vec!["powersave".to_string(), "performance".to_string()];
+        assert_eq!(next_governor(&governors, Some("powersave")), "performance");
+        assert_eq!(next_governor(&governors, Some("performance")), "powersave");
+        assert_eq!(next_governor(&governors, Some("schedutil")), "powersave");
+        assert_eq!(next_governor(&governors, None), "powersave");
+    }
+}
@@ -0,0 +1,366 @@
+//! A block for the prices of cryptocurrencies, from CoinGecko or Kraken.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::{Config, LogicalDirection, Scrolling};
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::I3BarEvent;
+use crate::scheduler::Task;
+use crate::util::{http_get, pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+/// The longest time to wait after the API answered that its rate limit is exceeded.
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum CryptoService {
+    CoinGecko,
+    Kraken,
+}
+
+impl Default for CryptoService {
+    fn default() -> Self {
+        CryptoService::CoinGecko
+    }
+}
+
+/// The price of a symbol, with its change in percent.
+#[derive(Clone, Debug, PartialEq)]
+struct Price {
+    price: f64,
+    change: Option<f64>,
+}
+
+/// The prices in the answer of `/simple/price` of CoinGecko, by coin id.
+fn parse_coingecko(json: &Value, currency: &str) -> HashMap<String, Price> {
+    let change_key = format!("{}_24h_change", currency);
+    json.as_object()
+        .map(|coins| {
+            coins
+                .iter()
+                .filter_map(|(id, prices)| {
+                    Some((
+                        id.clone(),
+                        Price {
+                            price: prices[currency].as_f64()?,
+                            change: prices[&change_key].as_f64(),
+                        },
+                    ))
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// The price in the answer of `/public/Ticker` of Kraken for one pair, whose name in the
+/// answer can differ from the requested one, like `XXBTZUSD` for `XBTUSD`. The change is
+/// since the opening of the day at midnight UTC.
+fn parse_kraken(json: &Value) -> Result<Price> {
+    if let Some(error) = json["error"]
+        .as_array()
+        .and_then(|errors| errors.first())
+        .and_then(|e| e.as_str())
+    {
+        return Err(BlockError(
+            "crypto".to_string(),
+            format!("API Error: {}", error),
+        ));
+    }
+    let ticker = json["result"]
+        .as_object()
+        .and_then(|result| result.values().next())
+        .block_error("crypto", "Malformed JSON.")?;
+    let number = |value: &Value| value.as_str().and_then(|s| s.parse::<f64>().ok());
+    let price = number(&ticker["c"][0]).block_error("crypto", "Malformed JSON.")?;
+    let open = number(&ticker["o"]);
+    Ok(Price {
+        price,
+        change: open
+            .filter(|open| *open > 0.0)
+            .map(|open| (price - open) / open * 100.0),
+    })
+}
+
+/// Shows a price with fewer decimals the higher it is, like `67012`, `2.41` or `0.0823`.
+fn format_price(price: f64) -> String {
+    if price >= 1000.0 {
+        format!("{:.0}", price)
+    } else if price >= 1.0 {
+        format!("{:.2}", price)
+    } else {
+        format!("{:.4}", price)
+    }
+}
+
+pub struct Crypto {
+    id: String,
+    output: ButtonWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    service: CryptoService,
+    symbols: Vec<String>,
+    currency: String,
+    api_key: Option<String>,
+    scrolling: Scrolling,
+    /// The index of the symbol that is shown
+    current: usize,
+    /// The latest prices, which scrolling shows without asking the API again
+    prices: HashMap<String, Price>,
+    /// When the prices were fetched
+    fetched: Option<Instant>,
+    /// The number of times in a row the rate limit was exceeded
+    failures: u32,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CryptoConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "CryptoConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// The exchange API to ask
+    #[serde(default)]
+    pub service: CryptoService,
+
+    /// The coin ids of CoinGecko, like `bitcoin`, or the pairs of Kraken, like `XBTUSD`
+    pub symbols: Vec<String>,
+
+    /// The currency of the prices of CoinGecko
+    #[serde(default = "CryptoConfig::default_currency")]
+    pub currency: String,
+
+    /// A demo API key of CoinGecko
+    #[serde(default)]
+    pub api_key: Option<String>,
+
+    /// Format string
+    #[serde(default = "CryptoConfig::default_format")]
+    pub format: String,
+}
+
+impl CryptoConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(300)
+    }
+
+    fn default_currency() -> String {
+        "usd".to_owned()
+    }
+
+    fn default_format() -> String {
+        "{symbol} {price} {change}%".to_owned()
+    }
+}
+
+impl ConfigBlock for Crypto {
+    type Config = CryptoConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        if block_config.symbols.is_empty() {
+            return Err(BlockError(
+                "crypto".to_string(),
+                "at least one symbol must be configured".to_string(),
+            ));
+        }
+        let id = pseudo_uuid();
+        Ok(Crypto {
+            scrolling: config.scrolling,
+            output: ButtonWidget::new(config, &id),
+            id,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("crypto", "Invalid format specified")?,
+            service: block_config.service,
+            symbols: block_config.symbols,
+            currency: block_config.currency.to_lowercase(),
+            api_key: block_config.api_key,
+            current: 0,
+            prices: HashMap::new(),
+            fetched: None,
+            failures: 0,
+        })
+    }
+}
+
+/// Fetches a document, with the HTTP status. Errors like the rate limit may come without JSON.
+fn fetch(url: &str, headers: &[String]) -> Result<(u16, Value)> {
+    let response = http_get("crypto", url, headers, Duration::from_secs(10))?;
+    Ok((
+        response.status,
+        response.json("crypto").unwrap_or(Value::Null),
+    ))
+}
+
+impl Crypto {
+    /// Fetches the prices of all symbols. `None` means the rate limit was exceeded.
+    fn fetch_prices(&self) -> Result<Option<HashMap<String, Price>>> {
+        match self.service {
+            CryptoService::CoinGecko => {
+                let headers: Vec<String> = self
+                    .api_key
+                    .iter()
+                    .map(|key| format!("x-cg-demo-api-key: {}", key))
+                    .collect();
+                let (status, json) = fetch(
+                    &format!(
+                        "https://api.coingecko.com/api/v3/simple/price?ids={}&vs_currencies={}&include_24hr_change=true",
+                        self.symbols.join(","),
+                        self.currency
+                    ),
+                    &headers,
+                )?;
+                if status == 429 {
+                    return Ok(None);
+                }
+                if status >= 400 {
+                    return Err(BlockError(
+                        "crypto".to_string(),
+                        format!("API Error: HTTP status {}", status),
+                    ));
+                }
+                Ok(Some(parse_coingecko(&json, &self.currency)))
+            }
+            // Kraken names the pairs differently in the answer, so each is asked for alone
+            CryptoService::Kraken => {
+                let mut prices = HashMap::new();
+                for symbol in &self.symbols {
+                    let (status, json) = fetch(
+                        &format!("https://api.kraken.com/0/public/Ticker?pair={}", symbol),
+                        &[],
+                    )?;
+                    if status == 429 {
+                        return Ok(None);
+                    }
+                    prices.insert(symbol.clone(), parse_kraken(&json)?);
+                }
+                Ok(Some(prices))
+            }
+        }
+    }
+
+    /// The time until the prices are fetched again, longer after the rate limit was exceeded.
+    fn interval(&self) -> Duration {
+        (self.update_interval * 2u32.saturating_pow(self.failures)).min(MAX_BACKOFF)
+    }
+
+    fn render(&mut self) -> Result<()> {
+        let symbol = &self.symbols[self.current];
+        let price = self.prices.get(symbol);
+        let values = map!(
+            "{symbol}" => symbol.clone(),
+            "{price}" => price.map_or("-".to_string(), |p| format_price(p.price)),
+            "{change}" => price
+                .and_then(|p| p.change)
+                .map_or("-".to_string(), |change| format!("{:+.1}", change))
+        );
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
+        self.output.set_state(match price.and_then(|p| p.change) {
+            Some(change) if change > 0.0 => State::Good,
+            Some(change) if change < 0.0 => State::Critical,
+            _ => State::Idle,
+        });
+        Ok(())
+    }
+}
+
+impl Block for Crypto {
+    fn update(&mut self) -> Result<Option<Update>> {
+        // Signals and clicks may ask for updates early, which the cache answers
+        let due = self
+            .fetched
+            .map_or(true, |fetched| fetched.elapsed() >= self.interval());
+        if due {
+            match self.fetch_prices()? {
+                Some(prices) => {
+                    self.prices = prices;
+                    self.failures = 0;
+                }
+                None => self.failures += 1,
+            }
+            self.fetched = Some(Instant::now());
+        }
+        self.render()?;
+
+        Ok(Some(self.interval().into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if !event.matches_name(&self.id) {
+            return Ok(());
+        }
+        let count = self.symbols.len();
+        self.current = match self.scrolling.to_logical_direction(event.button) {
+            Some(LogicalDirection::Up) => (self.current + 1) % count,
+            Some(LogicalDirection::Down) => (self.current + count - 1) % count,
+            None => return Ok(()),
+        };
+        self.render()
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_price, parse_coingecko, parse_kraken, Price};
+
+    #[test]
+    fn test_parse_coingecko() {
+        let json = serde_json::json!({
+            "bitcoin": {"eur": 61234.5, "eur_24h_change": -1.25},
+            "dogecoin": {"eur": 0.0823}
+        });
+        let prices = parse_coingecko(&json, "eur");
+        assert_eq!(
+            prices["bitcoin"],
+            Price {
+                price: 61234.5,
+                change: Some(-1.25)
+            }
+        );
+        assert_eq!(prices["dogecoin"].change, None);
+        assert!(parse_coingecko(&json, "usd").is_empty());
+    }
+
+    #[test]
+    fn test_parse_kraken() {
+        let json = serde_json::json!({"error": [], "result": {"XXBTZUSD": {
+            "a": ["67010.0", "1", "1.000"],
+            "c": ["67000.0", "0.001"],
+            "o": "64000.0"
+        }}});
+        let price = parse_kraken(&json).unwrap();
+        assert_eq!(price.price, 67000.0);
+        assert!((price.change.unwrap() - 4.6875).abs() < 1e-9);
+
+        let json = serde_json::json!({"error": ["EQuery:Unknown asset pair"]});
+        assert!(parse_kraken(&json).is_err());
+    }
+
+    #[test]
+    fn test_format_price() {
+        assert_eq!(format_price(67012.4), "67012");
+        assert_eq!(format_price(2.4141), "2.41");
+        assert_eq!(format_price(0.082_34), "0.0823");
+    }
+}
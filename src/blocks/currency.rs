@@ -0,0 +1,375 @@
+//! A block for exchange rates between currencies, from the ECB or exchangerate.host.
+
+use std::collections::HashMap;
+use std::env;
+use std::time::Duration;
+
+use chrono::Utc;
+use crossbeam_channel::Sender;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::{Config, LogicalDirection, Scrolling};
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::I3BarEvent;
+use crate::scheduler::Task;
+use crate::util::{http_get, pseudo_uuid, FormatTemplate};
+use crate::widget::I3BarWidget;
+use crate::widgets::button::ButtonWidget;
+
+const EXCHANGERATE_HOST_API_KEY_ENV: &str = "EXCHANGERATE_HOST_API_KEY";
+
+lazy_static! {
+    static ref ECB_CUBE_REGEX: Regex = Regex::new(
+        r#"<Cube\s+(?:time=['"](?P<time>[^'"]+)['"]|currency=['"](?P<currency>[A-Z]{3})['"]\s+rate=['"](?P<rate>[0-9.]+)['"])"#
+    )
+    .unwrap();
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "name", rename_all = "lowercase")]
+pub enum CurrencyService {
+    /// The reference rates of the European Central Bank, published on working days
+    Ecb,
+    ExchangeRateHost {
+        #[serde(default = "CurrencyService::getenv_exchangerate_host_api_key")]
+        api_key: Option<String>,
+    },
+}
+
+impl CurrencyService {
+    fn getenv_exchangerate_host_api_key() -> Option<String> {
+        env::var(EXCHANGERATE_HOST_API_KEY_ENV).ok()
+    }
+}
+
+impl Default for CurrencyService {
+    fn default() -> Self {
+        CurrencyService::Ecb
+    }
+}
+
+/// The rates of the currencies of one day, as the amount of each for one unit of a base
+/// currency, which is in the rates as 1.
+type Rates = HashMap<String, f64>;
+
+/// The rate of a pair like `EUR/USD`, as the amount of the second currency for one unit of
+/// the first.
+fn cross_rate(rates: &Rates, pair: &(String, String)) -> Option<f64> {
+    let (from, to) = pair;
+    Some(rates.get(to)? / rates.get(from)?)
+}
+
+/// The rates of the two latest days in the reference rates of the ECB, newest first, against
+/// the euro.
+fn parse_ecb(xml: &str) -> Vec<Rates> {
+    let mut days: Vec<Rates> = Vec::new();
+    for captures in ECB_CUBE_REGEX.captures_iter(xml) {
+        if captures.name("time").is_some() {
+            if days.len() == 2 {
+                break;
+            }
+            let mut rates = HashMap::new();
+            rates.insert("EUR".to_string(), 1.0);
+            days.push(rates);
+        } else if let (Some(day), Some(currency), Some(rate)) = (
+            days.last_mut(),
+            captures.name("currency"),
+            captures
+                .name("rate")
+                .and_then(|r| r.as_str().parse::<f64>().ok()),
+        ) {
+            day.insert(currency.as_str().to_string(), rate);
+        }
+    }
+    days
+}
+
+/// The rates in an answer of `/live` or `/historical` of exchangerate.host, against the
+/// source currency, whose quotes are named like `USDEUR`.
+fn parse_exchangerate_host(json: &Value) -> Result<Rates> {
+    if let Some(info) = json.pointer("/error/info").and_then(|v| v.as_str()) {
+        return Err(BlockError(
+            "currency".to_string(),
+            format!("API Error: {}", info),
+        ));
+    }
+    let source = json["source"]
+        .as_str()
+        .block_error("currency", "Malformed JSON.")?;
+    let quotes = json["quotes"]
+        .as_object()
+        .block_error("currency", "Malformed JSON.")?;
+    let mut rates: Rates = quotes
+        .iter()
+        .filter(|(name, _)| name.starts_with(source))
+        .filter_map(|(name, rate)| Some((name[source.len()..].to_string(), rate.as_f64()?)))
+        .collect();
+    rates.insert(source.to_string(), 1.0);
+    Ok(rates)
+}
+
+fn fetch(url: &str) -> Result<String> {
+    let response = http_get("currency", url, &[], Duration::from_secs(10))?;
+    if !response.is_success() {
+        return Err(BlockError(
+            "currency".to_string(),
+            format!("API Error: HTTP status {}", response.status),
+        ));
+    }
+    Ok(response.text())
+}
+
+pub struct Currency {
+    id: String,
+    output: ButtonWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    service: CurrencyService,
+    pairs: Vec<(String, String)>,
+    scrolling: Scrolling,
+    /// The index of the pair that is shown
+    current: usize,
+    /// The latest and the previous rates
+    rates: Option<(Rates, Option<Rates>)>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct CurrencyConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "CurrencyConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// The service to ask
+    #[serde(default)]
+    pub service: CurrencyService,
+
+    /// The pairs, like `EUR/USD`
+    pub pairs: Vec<String>,
+
+    /// Format string
+    #[serde(default = "CurrencyConfig::default_format")]
+    pub format: String,
+}
+
+impl CurrencyConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(24 * 60 * 60)
+    }
+
+    fn default_format() -> String {
+        "{pair} {rate}".to_owned()
+    }
+}
+
+impl ConfigBlock for Currency {
+    type Config = CurrencyConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        if block_config.pairs.is_empty() {
+            return Err(BlockError(
+                "currency".to_string(),
+                "at least one pair must be configured".to_string(),
+            ));
+        }
+        let pairs = block_config
+            .pairs
+            .iter()
+            .map(|pair| {
+                let mut currencies = pair.split('/').map(|c| c.trim().to_uppercase());
+                match (currencies.next(), currencies.next(), currencies.next()) {
+                    (Some(from), Some(to), None) => Ok((from, to)),
+                    _ => Err(BlockError(
+                        "currency".to_string(),
+                        format!("invalid pair {}, expected a pair like EUR/USD", pair),
+                    )),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        let id = pseudo_uuid();
+        Ok(Currency {
+            scrolling: config.scrolling,
+            output: ButtonWidget::new(config, &id),
+            id,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("currency", "Invalid format specified")?,
+            service: block_config.service,
+            pairs,
+            current: 0,
+            rates: None,
+        })
+    }
+}
+
+impl Currency {
+    /// Fetches the latest and the previous rates.
+    fn fetch_rates(&self) -> Result<(Rates, Option<Rates>)> {
+        match self.service {
+            CurrencyService::Ecb => {
+                let mut days = parse_ecb(&fetch(
+                    "https://www.ecb.europa.eu/stats/eurofxref/eurofxref-hist-90d.xml",
+                )?)
+                .into_iter();
+                let latest = days
+                    .next()
+                    .block_error("currency", "the ECB published no rates")?;
+                Ok((latest, days.next()))
+            }
+            CurrencyService::ExchangeRateHost { ref api_key } => {
+                let api_key = api_key.as_ref().block_error(
+                    "currency",
+                    &format!(
+                        "Missing member 'service.api_key'. Add the member or configure with the environment variable {}",
+                        EXCHANGERATE_HOST_API_KEY_ENV
+                    ),
+                )?;
+                let mut currencies: Vec<&str> = self
+                    .pairs
+                    .iter()
+                    .flat_map(|(from, to)| vec![from.as_str(), to.as_str()])
+                    .collect();
+                currencies.sort();
+                currencies.dedup();
+                let currencies = currencies.join(",");
+                let latest = parse_exchangerate_host(
+                    &serde_json::from_str(&fetch(&format!(
+                        "https://api.exchangerate.host/live?access_key={}&currencies={}",
+                        api_key, currencies
+                    ))?)
+                    .block_error("currency", "Malformed JSON.")?,
+                )?;
+                let yesterday = Utc::today().pred().format("%Y-%m-%d");
+                // Without the previous rates, the latest are still worth showing
+                let previous = fetch(&format!(
+                    "https://api.exchangerate.host/historical?access_key={}&date={}&currencies={}",
+                    api_key, yesterday, currencies
+                ))
+                .ok()
+                .and_then(|answer| serde_json::from_str(&answer).ok())
+                .and_then(|json| parse_exchangerate_host(&json).ok());
+                Ok((latest, previous))
+            }
+        }
+    }
+
+    fn render(&mut self) -> Result<()> {
+        let pair = &self.pairs[self.current];
+        let (rate, previous) = match self.rates {
+            Some((ref latest, ref previous)) => (
+                cross_rate(latest, pair),
+                previous.as_ref().and_then(|p| cross_rate(p, pair)),
+            ),
+            None => (None, None),
+        };
+        let change = match (rate, previous) {
+            (Some(rate), Some(previous)) => Some((rate - previous) / previous * 100.0),
+            _ => None,
+        };
+        let values = map!(
+            "{pair}" => format!("{}/{}", pair.0, pair.1),
+            "{rate}" => rate.map_or("-".to_string(), |r| format!("{:.4}", r)),
+            "{change}" => change.map_or("-".to_string(), |c| format!("{:+.2}", c))
+        );
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
+        Ok(())
+    }
+}
+
+impl Block for Currency {
+    fn update(&mut self) -> Result<Option<Update>> {
+        self.rates = Some(self.fetch_rates()?);
+        self.render()?;
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if !event.matches_name(&self.id) {
+            return Ok(());
+        }
+        let count = self.pairs.len();
+        self.current = match self.scrolling.to_logical_direction(event.button) {
+            Some(LogicalDirection::Up) => (self.current + 1) % count,
+            Some(LogicalDirection::Down) => (self.current + count - 1) % count,
+            None => return Ok(()),
+        };
+        self.render()
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{cross_rate, parse_ecb, parse_exchangerate_host};
+
+    #[test]
+    fn test_parse_ecb() {
+        let xml = r#"<?xml version="1.0" encoding="UTF-8"?>
+<gesmes:Envelope xmlns:gesmes="http://www.gesmes.org/xml/2002-08-01">
+    <gesmes:subject>Reference rates</gesmes:subject>
+    <Cube>
+        <Cube time="2024-03-13">
+            <Cube currency="USD" rate="1.0939"/>
+            <Cube currency="JPY" rate="161.68"/>
+        </Cube>
+        <Cube time="2024-03-12">
+            <Cube currency="USD" rate="1.0916"/>
+            <Cube currency="JPY" rate="161.12"/>
+        </Cube>
+        <Cube time="2024-03-11">
+            <Cube currency="USD" rate="1.0926"/>
+        </Cube>
+    </Cube>
+</gesmes:Envelope>"#;
+        let days = parse_ecb(xml);
+        assert_eq!(days.len(), 2);
+        assert_eq!(days[0]["USD"], 1.0939);
+        assert_eq!(days[0]["EUR"], 1.0);
+        assert_eq!(days[1]["JPY"], 161.12);
+
+        let pair = ("EUR".to_string(), "USD".to_string());
+        assert_eq!(cross_rate(&days[0], &pair), Some(1.0939));
+        let pair = ("USD".to_string(), "JPY".to_string());
+        assert!((cross_rate(&days[0], &pair).unwrap() - 147.801).abs() < 0.001);
+        let pair = ("USD".to_string(), "CHF".to_string());
+        assert_eq!(cross_rate(&days[0], &pair), None);
+
+        assert!(parse_ecb("<html></html>").is_empty());
+    }
+
+    #[test]
+    fn test_parse_exchangerate_host() {
+        let json = serde_json::json!({
+            "success": true,
+            "source": "USD",
+            "quotes": {"USDEUR": 0.9142, "USDJPY": 147.8}
+        });
+        let rates = parse_exchangerate_host(&json).unwrap();
+        assert_eq!(rates["USD"], 1.0);
+        assert_eq!(rates["EUR"], 0.9142);
+        assert_eq!(rates["JPY"], 147.8);
+
+        let json = serde_json::json!({"success": false, "error": {
+            "code": 101, "info": "You have not supplied an API Access Key."
+        }});
+        assert!(parse_exchangerate_host(&json).is_err());
+    }
+}
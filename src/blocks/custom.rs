@@ -13,8 +13,6 @@ use crate::de::deserialize_update;
 use crate::errors::*;
 use crate::input::I3BarEvent;
 use crate::scheduler::Task;
-use crate::signals::convert_to_valid_signal;
-use crate::subprocess::spawn_child_async;
 use crate::util::pseudo_uuid;
 use crate::widget::{I3BarWidget, State};
 use crate::widgets::button::ButtonWidget;
@@ -24,13 +22,9 @@ pub struct Custom {
     update_interval: Update,
     output: ButtonWidget,
     command: Option<String>,
-    on_click: Option<String>,
     cycle: Option<Peekable<Cycle<vec::IntoIter<String>>>>,
-    signal: Option<i32>,
     tx_update_request: Sender<Task>,
     pub json: bool,
-    hide_when_empty: bool,
-    is_empty: bool,
     shell: String,
 }
 
@@ -47,22 +41,13 @@ pub struct CustomConfig {
     /// Shell Command to execute & display
     pub command: Option<String>,
 
-    /// Command to execute when the button is clicked
-    pub on_click: Option<String>,
-
     /// Commands to execute and change when the button is clicked
     pub cycle: Option<Vec<String>>,
 
-    /// Signal to update upon reception
-    pub signal: Option<i32>,
-
     /// Parse command output if it contains valid bar JSON
     #[serde(default = "CustomConfig::default_json")]
     pub json: bool,
 
-    #[serde(default = "CustomConfig::hide_when_empty")]
-    pub hide_when_empty: bool,
-
     pub shell: Option<String>,
 }
 
@@ -74,10 +59,6 @@ impl CustomConfig {
     fn default_json() -> bool {
         false
     }
-
-    fn hide_when_empty() -> bool {
-        false
-    }
 }
 
 impl ConfigBlock for Custom {
@@ -89,13 +70,9 @@ impl ConfigBlock for Custom {
             update_interval: block_config.interval,
             output: ButtonWidget::new(config.clone(), ""),
             command: None,
-            on_click: None,
             cycle: None,
-            signal: None,
             tx_update_request: tx,
             json: block_config.json,
-            hide_when_empty: block_config.hide_when_empty,
-            is_empty: true,
             shell: if let Some(s) = block_config.shell {
                 s
             } else {
@@ -104,15 +81,6 @@ impl ConfigBlock for Custom {
         };
         custom.output = ButtonWidget::new(config, &custom.id);
 
-        if let Some(on_click) = block_config.on_click {
-            custom.on_click = Some(on_click)
-        };
-
-        if let Some(signal) = block_config.signal {
-            // If the signal is not in the valid range we return an error
-            custom.signal = Some(convert_to_valid_signal(signal)?);
-        };
-
         if block_config.cycle.is_some() && block_config.command.is_some() {
             return Err(BlockError(
                 "custom".to_string(),
@@ -177,10 +145,8 @@ impl Block for Custom {
             };
             self.output.set_icon(&output.icon);
             self.output.set_state(output.state);
-            self.is_empty = output.text.is_empty();
             self.output.set_text(output.text);
         } else {
-            self.is_empty = raw_output.is_empty();
             self.output.set_text(raw_output);
         }
 
@@ -188,23 +154,7 @@ impl Block for Custom {
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        if self.is_empty && self.hide_when_empty {
-            vec![]
-        } else {
-            vec![&self.output]
-        }
-    }
-
-    fn signal(&mut self, signal: i32) -> Result<()> {
-        if let Some(sig) = self.signal {
-            if sig == signal {
-                self.tx_update_request.send(Task {
-                    id: self.id.clone(),
-                    update_time: Instant::now(),
-                })?;
-            }
-        }
-        Ok(())
+        vec![&self.output]
     }
 
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
@@ -216,19 +166,8 @@ impl Block for Custom {
             return Ok(());
         }
 
-        let mut update = false;
-
-        if let Some(ref on_click) = self.on_click {
-            spawn_child_async(&self.shell, &["-c", on_click]).ok();
-            update = true;
-        }
-
         if let Some(ref mut cycle) = self.cycle {
             cycle.next();
-            update = true;
-        }
-
-        if update {
             self.tx_update_request.send(Task {
                 id: self.id.clone(),
                 update_time: Instant::now(),
@@ -238,6 +177,14 @@ impl Block for Custom {
         Ok(())
     }
 
+    fn handles_click(&self, _event: &I3BarEvent) -> bool {
+        self.cycle.is_some()
+    }
+
+    fn shell(&self) -> &str {
+        &self.shell
+    }
+
     fn id(&self) -> &str {
         &self.id
     }
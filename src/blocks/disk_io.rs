@@ -0,0 +1,236 @@
+//! A block for the read and write throughput of disks.
+
+use std::fs::read_dir;
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{format_speed, pseudo_uuid, read_file, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+/// The size of a sector in `/proc/diskstats`, which is the same for all devices.
+const SECTOR_SIZE: u64 = 512;
+
+/// The counters of `/proc/diskstats`, summed over some devices.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct DiskStats {
+    reads: u64,
+    sectors_read: u64,
+    writes: u64,
+    sectors_written: u64,
+    /// Milliseconds spent doing IO
+    io_ms: u64,
+}
+
+/// Sums the counters of the given devices in the content of `/proc/diskstats`.
+fn parse_diskstats(content: &str, devices: &[String]) -> DiskStats {
+    let mut stats = DiskStats::default();
+    for line in content.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 13 || !devices.iter().any(|d| d == fields[2]) {
+            continue;
+        }
+        let field = |i: usize| fields[i].parse::<u64>().unwrap_or(0);
+        stats.reads += field(3);
+        stats.sectors_read += field(5);
+        stats.writes += field(7);
+        stats.sectors_written += field(9);
+        stats.io_ms += field(12);
+    }
+    stats
+}
+
+/// The disks in `/sys/block` that are backed by hardware, leaving out loop, ram, zram and
+/// device mapper devices, whose IO is counted on the disks below them anyway.
+fn physical_disks() -> Vec<String> {
+    let mut disks = Vec::new();
+    if let Ok(entries) = read_dir("/sys/block") {
+        for entry in entries.flatten() {
+            if entry.path().join("device").exists() {
+                disks.push(entry.file_name().to_string_lossy().into_owned());
+            }
+        }
+    }
+    disks.sort();
+    disks
+}
+
+pub struct DiskIo {
+    id: String,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    devices: Vec<String>,
+    speed_digits: usize,
+    speed_min_unit: String,
+    warning: f64,
+    critical: f64,
+    last: Option<(Instant, DiskStats)>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DiskIoConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "DiskIoConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format string
+    #[serde(default = "DiskIoConfig::default_format")]
+    pub format: String,
+
+    /// The devices in `/sys/block` to sum up, all physical disks if empty
+    #[serde(default)]
+    pub devices: Vec<String>,
+
+    /// Number of digits to show for the throughput
+    #[serde(default = "DiskIoConfig::default_speed_digits")]
+    pub speed_digits: usize,
+
+    /// Minimum unit to show the throughput in
+    #[serde(default = "DiskIoConfig::default_speed_min_unit")]
+    pub speed_min_unit: String,
+
+    /// Throughput of reads and writes in MB/s, where state is set to warning
+    #[serde(default = "DiskIoConfig::default_warning")]
+    pub warning: f64,
+
+    /// Throughput of reads and writes in MB/s, where state is set to critical
+    #[serde(default = "DiskIoConfig::default_critical")]
+    pub critical: f64,
+}
+
+impl DiskIoConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(2)
+    }
+
+    fn default_format() -> String {
+        "{read} {write}".to_owned()
+    }
+
+    fn default_speed_digits() -> usize {
+        3
+    }
+
+    fn default_speed_min_unit() -> String {
+        "K".to_owned()
+    }
+
+    fn default_warning() -> f64 {
+        100.
+    }
+
+    fn default_critical() -> f64 {
+        500.
+    }
+}
+
+impl ConfigBlock for DiskIo {
+    type Config = DiskIoConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        let devices = if block_config.devices.is_empty() {
+            physical_disks()
+        } else {
+            block_config.devices
+        };
+
+        Ok(DiskIo {
+            id: pseudo_uuid(),
+            text: TextWidget::new(config).with_icon("disk_drive"),
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("disk_io", "Invalid format specified")?,
+            update_interval: block_config.interval,
+            devices,
+            speed_digits: block_config.speed_digits,
+            speed_min_unit: block_config.speed_min_unit,
+            warning: block_config.warning,
+            critical: block_config.critical,
+            last: None,
+        })
+    }
+}
+
+impl Block for DiskIo {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let now = Instant::now();
+        let content = read_file("disk_io", Path::new("/proc/diskstats"))?;
+        let stats = parse_diskstats(&content, &self.devices);
+
+        // The first update has nothing to compare to and shows no IO
+        let (then, last) = self.last.unwrap_or((now, stats));
+        self.last = Some((now, stats));
+        let secs = now.duration_since(then).as_secs_f64().max(1e-3);
+        let per_sec = |count: u64| (count as f64 / secs) as u64;
+
+        let read = per_sec(stats.sectors_read.saturating_sub(last.sectors_read) * SECTOR_SIZE);
+        let written =
+            per_sec(stats.sectors_written.saturating_sub(last.sectors_written) * SECTOR_SIZE);
+        let utilization = stats.io_ms.saturating_sub(last.io_ms) as f64
+            / (secs * 1000. * self.devices.len().max(1) as f64)
+            * 100.;
+
+        let values = map!(
+            "{read}" => format_speed(read, self.speed_digits, &self.speed_min_unit, false),
+            "{write}" => format_speed(written, self.speed_digits, &self.speed_min_unit, false),
+            "{read_iops}" => per_sec(stats.reads.saturating_sub(last.reads)).to_string(),
+            "{write_iops}" => per_sec(stats.writes.saturating_sub(last.writes)).to_string(),
+            "{utilization}" => format!("{:.0}", utilization.min(100.))
+        );
+        self.text.set_text(self.format.render_static_str(&values)?);
+
+        let megabytes = (read + written) as f64 / 1_000_000.;
+        self.text.set_state(match megabytes {
+            x if x > self.critical => State::Critical,
+            x if x > self.warning => State::Warning,
+            _ => State::Idle,
+        });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_diskstats, DiskStats};
+
+    #[test]
+    fn test_parse_diskstats() {
+        let content = "\
+ 259       0 nvme0n1 1000 10 20000 300 500 5 8000 200 0 400 500 0 0 0 0
+ 259       1 nvme0n1p1 900 10 18000 280 400 5 7000 180 0 350 460 0 0 0 0
+   8       0 sda 10 0 80 5 20 0 160 10 0 15 15";
+        let devices = vec!["nvme0n1".to_string(), "sda".to_string()];
+        assert_eq!(
+            parse_diskstats(content, &devices),
+            DiskStats {
+                reads: 1010,
+                sectors_read: 20080,
+                writes: 520,
+                sectors_written: 8160,
+                io_ms: 415,
+            }
+        );
+        assert_eq!(parse_diskstats(content, &[]), DiskStats::default());
+    }
+}
@@ -11,7 +11,7 @@ use crate::config::Config;
 use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::scheduler::Task;
-use crate::util::{format_percent_bar, pseudo_uuid, FormatTemplate};
+use crate::util::{format_percent_bar, pseudo_uuid, FormatTemplate, Number};
 use crate::widget::{I3BarWidget, State};
 use crate::widgets::text::TextWidget;
 
@@ -49,19 +49,37 @@ pub enum InfoType {
     Used,
 }
 
+/// A path in `paths`, either only the path or with its own alias and thresholds.
+#[derive(Deserialize, Debug, Clone)]
+#[serde(untagged)]
+pub enum DiskSpacePath {
+    Path(String),
+    Detailed {
+        path: String,
+        alias: Option<String>,
+        warning: Option<f64>,
+        alert: Option<f64>,
+    },
+}
+
+/// One of the paths the block shows.
+struct Disk {
+    widget: TextWidget,
+    path: String,
+    alias: String,
+    warning: f64,
+    alert: f64,
+}
+
 pub struct DiskSpace {
-    disk_space: TextWidget,
+    disks: Vec<Disk>,
     id: String,
     update_interval: Duration,
-    alias: String,
-    path: String,
     unit: Unit,
     info_type: InfoType,
-    warning: f64,
-    alert: f64,
-    show_percentage: bool,
-    show_bar: bool,
     alert_absolute: bool,
+    inode_warning: f64,
+    inode_alert: f64,
     format: FormatTemplate,
     icon: String,
 }
@@ -77,6 +95,10 @@ pub struct DiskSpaceConfig {
     #[serde(default = "DiskSpaceConfig::default_alias")]
     pub alias: String,
 
+    /// Several paths to show instead of `path`, each with its own alias and thresholds
+    #[serde(default)]
+    pub paths: Vec<DiskSpacePath>,
+
     /// Currently supported options are available, free, total and used
     /// Sets value used for {percentage} calculation
     /// total is the same as used, use format to set format string for output
@@ -85,7 +107,7 @@ pub struct DiskSpaceConfig {
 
     /// Format string for output
     /// placeholders: {percentage}, {bar}, {path}, {alias}, {available}, {free}, {total}, {used},
-    ///               {unit}
+    ///               {unit}, {iused}, {itotal}, {iused_percent}
     #[serde(default = "DiskSpaceConfig::default_format")]
     pub format: String,
 
@@ -119,6 +141,14 @@ pub struct DiskSpaceConfig {
     /// use absolute (unit) values for disk space alerts
     #[serde(default = "DiskSpaceConfig::default_alert_absolute")]
     pub alert_absolute: bool,
+
+    /// Percentage of inodes used, where state is set to warning
+    #[serde(default = "DiskSpaceConfig::default_inode_warning")]
+    pub inode_warning: f64,
+
+    /// Percentage of inodes used, where state is set to critical
+    #[serde(default = "DiskSpaceConfig::default_inode_alert")]
+    pub inode_alert: f64,
 }
 
 impl DiskSpaceConfig {
@@ -167,6 +197,14 @@ impl DiskSpaceConfig {
     fn default_alert_absolute() -> bool {
         false
     }
+
+    fn default_inode_warning() -> f64 {
+        80.
+    }
+
+    fn default_inode_alert() -> f64 {
+        95.
+    }
 }
 
 enum AlertType {
@@ -213,28 +251,72 @@ impl ConfigBlock for DiskSpace {
             .cloned()
             .unwrap_or_else(|| "".to_string());
 
+        let mut paths = block_config.paths;
+        if paths.is_empty() {
+            paths.push(DiskSpacePath::Detailed {
+                path: block_config.path,
+                alias: Some(block_config.alias),
+                warning: None,
+                alert: None,
+            });
+        }
+        let disks = paths
+            .into_iter()
+            .map(|entry| {
+                let (path, alias, warning, alert) = match entry {
+                    DiskSpacePath::Path(path) => (path, None, None, None),
+                    DiskSpacePath::Detailed {
+                        path,
+                        alias,
+                        warning,
+                        alert,
+                    } => (path, alias, warning, alert),
+                };
+                Disk {
+                    widget: TextWidget::new(config.clone()),
+                    alias: alias.unwrap_or_else(|| path.clone()),
+                    path,
+                    warning: warning.unwrap_or(block_config.warning),
+                    alert: alert.unwrap_or(block_config.alert),
+                }
+            })
+            .collect();
+
+        // The deprecated options only change the format
+        let format = if let InfoType::Total = block_config.info_type {
+            // Deprecated: Same as Used - use format string to set output format
+            // Kept for back-compatibility
+            // Use format: "{used}/{total} {unit}" for previous format
+            "{used}/{total} {unit}"
+        } else {
+            block_config.format.as_str()
+        };
+        let format = if block_config.show_percentage {
+            "{alias} {result} ({percentage}) {unit}"
+        } else if block_config.show_bar {
+            "{alias} {result} {unit} {bar}"
+        } else {
+            format
+        };
+
         Ok(DiskSpace {
             id: pseudo_uuid(),
             update_interval: block_config.interval,
-            disk_space: TextWidget::new(config),
-            alias: block_config.alias,
-            path: block_config.path,
-            format: FormatTemplate::from_string(&block_config.format)?,
+            disks,
+            format: FormatTemplate::from_string(format)?,
             info_type: block_config.info_type,
             unit: block_config.unit,
-            warning: block_config.warning,
-            alert: block_config.alert,
-            show_percentage: block_config.show_percentage,
-            show_bar: block_config.show_bar,
             alert_absolute: block_config.alert_absolute,
+            inode_warning: block_config.inode_warning,
+            inode_alert: block_config.inode_alert,
             icon,
         })
     }
 }
 
-impl Block for DiskSpace {
-    fn update(&mut self) -> Result<Option<Update>> {
-        let statvfs = statvfs(Path::new(self.path.as_str()))
+impl DiskSpace {
+    fn update_disk(&self, disk: &Disk) -> Result<(String, State)> {
+        let statvfs = statvfs(Path::new(disk.path.as_str()))
             .block_error("disk_space", "failed to retrieve statvfs")?;
 
         let result;
@@ -244,6 +326,15 @@ impl Block for DiskSpace {
         let available = (statvfs.blocks_available() as u64) * (statvfs.block_size() as u64);
         let free = (statvfs.blocks_free() as u64) * (statvfs.block_size() as u64);
 
+        // Some filesystems like btrfs have no fixed number of inodes and report none
+        let itotal = statvfs.files() as u64;
+        let iused = itotal.saturating_sub(statvfs.files_free() as u64);
+        let iused_percent = if itotal > 0 {
+            iused as f64 / itotal as f64 * 100.
+        } else {
+            0.
+        };
+
         let alert_type;
         match self.info_type {
             InfoType::Available => {
@@ -254,41 +345,35 @@ impl Block for DiskSpace {
                 result = free;
                 alert_type = AlertType::Below;
             }
-            InfoType::Total => {
-                // Deprecated: Same as Used - use format string to set output format
-                // Kept for back-compatibility
-                // Use format: "{used}/{total} {unit}" for previous format
-                result = used;
-                alert_type = AlertType::Above;
-                self.format = FormatTemplate::from_string("{used}/{total} {unit}")?;
-            }
-            InfoType::Used => {
+            InfoType::Total | InfoType::Used => {
                 result = used;
                 alert_type = AlertType::Above;
             }
         }
 
         let percentage = (result as f32) / (total as f32) * 100f32;
-        if self.show_percentage {
-            self.format = FormatTemplate::from_string("{alias} {result} ({percentage}) {unit}")?;
-        } else if self.show_bar {
-            self.format = FormatTemplate::from_string("{alias} {result} {unit} {bar}")?;
-        }
 
         let values = map!("{percentage}" => format!("{:.2}%", percentage),
         "{bar}" => format_percent_bar(percentage),
-        "{alias}" => self.alias.clone(),
+        "{alias}" => disk.alias.clone(),
         "{unit}" => format!("{:?}", self.unit),
-        "{path}" => self.path.clone(),
+        "{path}" => disk.path.clone(),
         "{total}" => format!("{:.2}", Unit::bytes_in_unit(self.unit, total)),
         "{used}" => format!("{:.2}", Unit::bytes_in_unit(self.unit, used)),
         "{available}" => format!("{:.2}", Unit::bytes_in_unit(self.unit, available)),
         "{free}" => format!("{:.2}", Unit::bytes_in_unit(self.unit, free)),
+        "{itotal}" => format!("{}", itotal),
+        "{iused}" => format!("{}", iused),
+        "{iused_percent}" => format!("{:.2}%", iused_percent),
         "{icon}" => self.icon.to_string(),
         "{result}" => format!("{:.2}", result)
         );
-        self.disk_space
-            .set_text(self.format.render_static_str(&values)?);
+        // The percentages come with their sign, so specifications format the numbers
+        let numbers = map!(
+            "{percentage}" => Number { value: percentage as f64, unit: "%" },
+            "{iused_percent}" => Number { value: iused_percent, unit: "%" }
+        );
+        let text = self.format.render_numbers(&values, &numbers)?;
 
         // Send percentage to alert check if we don't want absolute alerts
         let alert_val = if !self.alert_absolute {
@@ -297,14 +382,42 @@ impl Block for DiskSpace {
             Unit::bytes_in_unit(self.unit, result)
         };
 
-        let state = self.compute_state(alert_val, self.warning, self.alert, alert_type);
-        self.disk_space.set_state(state);
+        // Running out of inodes is as fatal as running out of space
+        let state = match (
+            self.compute_state(alert_val, disk.warning, disk.alert, alert_type),
+            self.compute_state(
+                iused_percent,
+                self.inode_warning,
+                self.inode_alert,
+                AlertType::Above,
+            ),
+        ) {
+            (State::Critical, _) | (_, State::Critical) => State::Critical,
+            (State::Warning, _) | (_, State::Warning) => State::Warning,
+            (state, _) => state,
+        };
+
+        Ok((text, state))
+    }
+}
+
+impl Block for DiskSpace {
+    fn update(&mut self) -> Result<Option<Update>> {
+        for i in 0..self.disks.len() {
+            let (text, state) = self.update_disk(&self.disks[i])?;
+            let disk = &mut self.disks[i];
+            disk.widget.set_text(text);
+            disk.widget.set_state(state);
+        }
 
         Ok(Some(self.update_interval.into()))
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
-        vec![&self.disk_space]
+        self.disks
+            .iter()
+            .map(|disk| &disk.widget as &dyn I3BarWidget)
+            .collect()
     }
 
     fn id(&self) -> &str {
@@ -0,0 +1,295 @@
+//! A block for the pending updates of dnf, or of rpm-ostree on image based systems
+//! like Fedora Silverblue.
+
+use std::path::Path;
+use std::process::Command;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Backend {
+    Dnf,
+    RpmOstree,
+}
+
+impl Backend {
+    /// rpm-ostree creates this file on every system booted from a deployment.
+    fn detect() -> Self {
+        if Path::new("/run/ostree-booted").exists() {
+            Backend::RpmOstree
+        } else {
+            Backend::Dnf
+        }
+    }
+
+    fn check(self) -> Result<usize> {
+        match self {
+            Backend::Dnf => {
+                let output = Command::new("dnf")
+                    .env("LC_ALL", "C")
+                    .args(&["check-update", "--quiet"])
+                    .output()
+                    .block_error("dnf", "failed to run dnf")?;
+                // 100 means that updates are available, 1 that the check failed
+                match output.status.code() {
+                    Some(0) | Some(100) => {
+                        Ok(parse_check_update(&String::from_utf8_lossy(&output.stdout)))
+                    }
+                    _ => Err(BlockError(
+                        "dnf".to_string(),
+                        String::from_utf8_lossy(&output.stderr).trim().to_string(),
+                    )),
+                }
+            }
+            Backend::RpmOstree => {
+                // Downloads the metadata and stores the pending update as `cached-update`.
+                // It exits with 77 if there is none, so only failing to run it is an error.
+                Command::new("rpm-ostree")
+                    .args(&["upgrade", "--check"])
+                    .output()
+                    .block_error("dnf", "failed to run rpm-ostree")?;
+                let output = Command::new("rpm-ostree")
+                    .args(&["status", "--json"])
+                    .output()
+                    .block_error("dnf", "failed to run rpm-ostree")?;
+                parse_ostree_status(&String::from_utf8_lossy(&output.stdout))
+                    .block_error("dnf", "failed to parse the status of rpm-ostree")
+            }
+        }
+    }
+}
+
+/// Counts the packages in the output of `dnf check-update`, like
+/// `kernel.x86_64   6.5.6-300.fc39   updates`. The packages that obsolete others are
+/// listed a second time after `Obsoleting Packages`, so the count stops there.
+fn parse_check_update(output: &str) -> usize {
+    output
+        .lines()
+        .take_while(|line| !line.starts_with("Obsoleting Packages"))
+        .filter(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            fields.len() == 3 && fields[0].contains('.')
+        })
+        .count()
+}
+
+/// Counts the package changes of the update that `rpm-ostree upgrade --check` found.
+fn parse_ostree_status(output: &str) -> Option<usize> {
+    let status: serde_json::Value = serde_json::from_str(output).ok()?;
+    let update = &status["cached-update"];
+    if update.is_null() {
+        return Some(0);
+    }
+    Some(
+        ["upgraded", "downgraded", "added", "removed"]
+            .iter()
+            .filter_map(|change| update["rpm-diff"][change].as_array())
+            .map(|packages| packages.len())
+            .sum(),
+    )
+}
+
+pub struct Dnf {
+    id: String,
+    output: ButtonWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    format_singular: FormatTemplate,
+    format_up_to_date: FormatTemplate,
+    /// The result of a check that finished since the last update
+    result: Arc<Mutex<Option<Result<usize>>>>,
+    /// Asks the checking thread for a check
+    tx_check: Sender<()>,
+    count: Option<usize>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct DnfConfig {
+    /// Interval in seconds between two checks
+    #[serde(
+        default = "DnfConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Whether to ask dnf or rpm-ostree, detected if not set
+    #[serde(default)]
+    pub backend: Option<Backend>,
+
+    /// Format override
+    #[serde(default = "DnfConfig::default_format")]
+    pub format: String,
+
+    /// Alternative format override for when exactly 1 update is available
+    #[serde(default = "DnfConfig::default_format")]
+    pub format_singular: String,
+
+    /// Alternative format override for when no updates are available
+    #[serde(default = "DnfConfig::default_format")]
+    pub format_up_to_date: String,
+}
+
+impl DnfConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(60 * 60)
+    }
+
+    fn default_format() -> String {
+        "{count}".to_owned()
+    }
+}
+
+impl ConfigBlock for Dnf {
+    type Config = DnfConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+        let backend = block_config.backend.unwrap_or_else(Backend::detect);
+
+        // The check can take a while when the metadata has expired, so it runs in a thread
+        // that keeps the last count until the next check is requested
+        let result = Arc::new(Mutex::new(None));
+        let (tx_check, rx_check) = crossbeam_channel::bounded(1);
+        let thread_result = result.clone();
+        let thread_id = id.clone();
+        thread::Builder::new()
+            .name("dnf".into())
+            .spawn(move || {
+                while rx_check.recv().is_ok() {
+                    *thread_result
+                        .lock()
+                        .expect("lock has been poisoned in `dnf` block") = Some(backend.check());
+                    let task = Task {
+                        id: thread_id.clone(),
+                        update_time: Instant::now(),
+                    };
+                    if send.send(task).is_err() {
+                        break;
+                    }
+                }
+            })
+            .block_error("dnf", "failed to start the checking thread")?;
+
+        Ok(Dnf {
+            output: ButtonWidget::new(config, &id)
+                .with_icon("update")
+                .with_text("…"),
+            id,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("dnf", "Invalid format specified for dnf::format")?,
+            format_singular: FormatTemplate::from_string(&block_config.format_singular)
+                .block_error("dnf", "Invalid format specified for dnf::format_singular")?,
+            format_up_to_date: FormatTemplate::from_string(&block_config.format_up_to_date)
+                .block_error("dnf", "Invalid format specified for dnf::format_up_to_date")?,
+            result,
+            tx_check,
+            count: None,
+        })
+    }
+}
+
+impl Block for Dnf {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let result = self
+            .result
+            .lock()
+            .block_error("dnf", "failed to acquire lock")?
+            .take();
+        match result {
+            Some(result) => self.count = Some(result?),
+            // Updates that are not the result of a check, from the schedule, a signal or a
+            // click, ask for the next one. A check that is already pending covers them.
+            None => {
+                let _ = self.tx_check.try_send(());
+            }
+        }
+
+        if let Some(count) = self.count {
+            let values = map!("{count}" => count);
+            self.output.set_text(match count {
+                0 => self.format_up_to_date.render_static_str(&values)?,
+                1 => self.format_singular.render_static_str(&values)?,
+                _ => self.format.render_static_str(&values)?,
+            });
+            self.output.set_state(match count {
+                0 => State::Idle,
+                _ => State::Info,
+            });
+        }
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.matches_name(&self.id) && event.button == MouseButton::Left {
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_check_update, parse_ostree_status};
+
+    #[test]
+    fn test_parse_check_update() {
+        let output = "
+firefox.x86_64                     118.0.1-1.fc39                  updates
+kernel.x86_64                      6.5.6-300.fc39                  updates
+Security: kernel-core-6.5.5-200.fc38.x86_64 is an installed security update
+Obsoleting Packages
+grub2-tools-efi.x86_64             1:2.06-100.fc39                 updates
+    grub2-tools-efi.x86_64         1:2.06-95.fc39                  @updates
+";
+        assert_eq!(parse_check_update(output), 2);
+        assert_eq!(parse_check_update(""), 0);
+    }
+
+    #[test]
+    fn test_parse_ostree_status() {
+        let status = r#"{
+            "deployments": [{"booted": true, "version": "39.20231010.0"}],
+            "cached-update": {
+                "version": "39.20231015.0",
+                "rpm-diff": {
+                    "upgraded": [[["firefox", 0, "118.0.1", "1.fc39", "x86_64"], ["firefox", 0, "118.0.2", "1.fc39", "x86_64"]]],
+                    "downgraded": [],
+                    "removed": [["nano-default-editor", 0, "7.2", "4.fc39", "noarch"]],
+                    "added": []
+                }
+            }
+        }"#;
+        assert_eq!(parse_ostree_status(status), Some(2));
+        assert_eq!(
+            parse_ostree_status(r#"{"deployments": [], "cached-update": null}"#),
+            Some(0)
+        );
+        assert_eq!(parse_ostree_status("error: not an ostree system"), None);
+    }
+}
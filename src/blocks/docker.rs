@@ -1,5 +1,10 @@
-use std::process::Command;
-use std::time::Duration;
+use std::env;
+use std::io::{Read, Write};
+use std::os::unix::net::UnixStream;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
@@ -9,17 +14,106 @@ use crate::blocks::{Block, ConfigBlock};
 use crate::config::Config;
 use crate::de::deserialize_duration;
 use crate::errors::*;
-use crate::input::I3BarEvent;
+use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::util::{pseudo_uuid, FormatTemplate};
-use crate::widget::I3BarWidget;
-use crate::widgets::text::TextWidget;
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+const TIMEOUT: Duration = Duration::from_secs(3);
+/// Stopping waits 10 seconds for the container to exit before killing it
+const ACTION_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Sends a request to the Engine API, and returns the status code and the body of the
+/// response. HTTP/1.0 makes the daemon close the connection after the body instead of
+/// sending it in chunks.
+fn request(socket: &str, method: &str, path: &str, timeout: Duration) -> Result<(u16, String)> {
+    let mut stream = UnixStream::connect(socket)
+        .block_error("docker", "Failed to reach the docker or podman daemon")?;
+    stream
+        .set_read_timeout(Some(timeout))
+        .block_error("docker", "Failed to set a timeout")?;
+    stream
+        .write_all(
+            format!(
+                "{} {} HTTP/1.0\r\nHost: docker\r\nContent-Length: 0\r\n\r\n",
+                method, path
+            )
+            .as_bytes(),
+        )
+        .block_error("docker", "Failed to send to the docker daemon")?;
+    let mut response = String::new();
+    stream
+        .read_to_string(&mut response)
+        .block_error("docker", "Failed to read from the docker daemon")?;
+    parse_response(&response)
+        .map(|(status, body)| (status, body.to_string()))
+        .block_error("docker", "Invalid response from the docker daemon")
+}
+
+/// Splits an HTTP response into its status code and its body.
+fn parse_response(response: &str) -> Option<(u16, &str)> {
+    let status = response.split_whitespace().nth(1)?.parse().ok()?;
+    let body = response.splitn(2, "\r\n\r\n").nth(1).unwrap_or("");
+    Some((status, body))
+}
+
+/// Starts, stops or restarts the container `name`.
+fn container_action(socket: &str, name: &str, action: &str) -> Result<()> {
+    let (status, body) = request(
+        socket,
+        "POST",
+        &format!("/containers/{}/{}", name, action),
+        ACTION_TIMEOUT,
+    )?;
+    // 304 means that the container already was in that state
+    if status >= 400 {
+        let message: serde_json::Value = serde_json::from_str(&body).unwrap_or_default();
+        return Err(BlockError(
+            "docker".to_string(),
+            format!(
+                "Failed to {} {}: {}",
+                action,
+                name,
+                message["message"].as_str().unwrap_or("")
+            ),
+        ));
+    }
+    Ok(())
+}
+
+/// The socket of `DOCKER_HOST` if it is a unix socket, like for rootless docker, then the
+/// socket of the docker daemon, then the socket of rootless podman, which serves the same API.
+fn default_socket() -> String {
+    if let Some(host) = env::var("DOCKER_HOST")
+        .ok()
+        .filter(|host| host.starts_with("unix://"))
+    {
+        return host["unix://".len()..].to_string();
+    }
+    let docker = "/var/run/docker.sock";
+    if Path::new(docker).exists() {
+        return docker.to_string();
+    }
+    env::var("XDG_RUNTIME_DIR")
+        .ok()
+        .map(|dir| format!("{}/podman/podman.sock", dir))
+        .filter(|podman| Path::new(podman).exists())
+        .unwrap_or_else(|| docker.to_string())
+}
 
 pub struct Docker {
-    text: TextWidget,
+    text: ButtonWidget,
     id: String,
     format: FormatTemplate,
     update_interval: Duration,
+    socket_path: String,
+    container: Option<String>,
+    /// The state of the container, like `running` or `exited`
+    container_state: String,
+    send: Sender<Task>,
+    /// The error of the last action on the container, which the next update shows
+    action_error: Arc<Mutex<Option<String>>>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -40,6 +134,28 @@ struct Status {
     images: i64,
 }
 
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+struct Container {
+    #[serde(rename = "State")]
+    state: ContainerState,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+struct ContainerState {
+    #[serde(rename = "Status")]
+    status: String,
+
+    /// Only containers with a health check have a health
+    #[serde(rename = "Health")]
+    health: Option<Health>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+struct Health {
+    #[serde(rename = "Status")]
+    status: String,
+}
+
 #[derive(Deserialize, Debug, Default, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct DockerConfig {
@@ -51,61 +167,102 @@ pub struct DockerConfig {
     pub interval: Duration,
 
     /// Format override
-    #[serde(default = "DockerConfig::default_format")]
-    pub format: String,
+    #[serde(default)]
+    pub format: Option<String>,
+
+    /// Socket of the docker daemon
+    #[serde(default = "DockerConfig::default_socket_path")]
+    pub socket_path: String,
+
+    /// Name or id of a container to show the state of
+    #[serde(default)]
+    pub container: Option<String>,
 }
 
 impl DockerConfig {
     fn default_interval() -> Duration {
         Duration::from_secs(5)
     }
-    fn default_format() -> String {
-        "{running}%".to_owned()
+
+    fn default_socket_path() -> String {
+        default_socket()
     }
 }
 
 impl ConfigBlock for Docker {
     type Config = DockerConfig;
 
-    fn new(block_config: Self::Config, config: Config, _: Sender<Task>) -> Result<Self> {
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let format = block_config.format.unwrap_or_else(|| {
+            if block_config.container.is_some() {
+                "{name} {state}".to_owned()
+            } else {
+                "{running}%".to_owned()
+            }
+        });
+        let id = pseudo_uuid();
         Ok(Docker {
-            id: pseudo_uuid(),
-            text: TextWidget::new(config).with_text("N/A").with_icon("docker"),
-            format: FormatTemplate::from_string(&block_config.format)
+            text: ButtonWidget::new(config, &id)
+                .with_text("N/A")
+                .with_icon("docker"),
+            id,
+            format: FormatTemplate::from_string(&format)
                 .block_error("docker", "Invalid format specified")?,
             update_interval: block_config.interval,
+            socket_path: block_config.socket_path,
+            container: block_config.container,
+            container_state: String::new(),
+            send,
+            action_error: Arc::new(Mutex::new(None)),
         })
     }
 }
 
+impl Docker {
+    fn status(&self) -> Result<Status> {
+        let (_, body) = request(&self.socket_path, "GET", "/info", TIMEOUT)?;
+        serde_json::from_str(&body).block_error("docker", "Failed to parse JSON response.")
+    }
+
+    /// The state of the container, or `None` if there is no such container.
+    fn container(&self, name: &str) -> Result<Option<ContainerState>> {
+        let (status, body) = request(
+            &self.socket_path,
+            "GET",
+            &format!("/containers/{}/json", name),
+            TIMEOUT,
+        )?;
+        if status == 404 {
+            return Ok(None);
+        }
+        let container: Container =
+            serde_json::from_str(&body).block_error("docker", "Failed to parse JSON response.")?;
+        Ok(Some(container.state))
+    }
+}
+
 impl Block for Docker {
     fn update(&mut self) -> Result<Option<Update>> {
-        let output = match Command::new("sh")
-            .args(&[
-                "-c",
-                "curl --fail --unix-socket /var/run/docker.sock http:/api/info",
-            ])
-            .output()
-        {
-            Ok(raw_output) => {
-                String::from_utf8(raw_output.stdout).block_error("docker", "Failed to decode")?
-            }
+        let action_error = self
+            .action_error
+            .lock()
+            .block_error("docker", "failed to acquire lock")?
+            .take();
+        if let Some(error) = action_error {
+            return Err(BlockError("docker".to_string(), error));
+        }
+
+        // We don't want the bar to crash if we can't reach the docker daemon.
+        let status = match self.status() {
+            Ok(status) => status,
             Err(_) => {
-                // We don't want the bar to crash if we can't reach the docker daemon.
                 self.text.set_text("N/A".to_string());
+                self.text.set_state(State::Idle);
                 return Ok(Some(self.update_interval.into()));
             }
         };
 
-        if output.is_empty() {
-            self.text.set_text("N/A".to_string());
-            return Ok(Some(self.update_interval.into()));
-        }
-
-        let status: Status = serde_json::from_str(&output)
-            .block_error("docker", "Failed to parse JSON response.")?;
-
-        let values = map!(
+        let mut values = map!(
             "{total}" => format!("{}", status.total),
             "{running}" => format!("{}", status.running),
             "{paused}" => format!("{}", status.paused),
@@ -113,6 +270,30 @@ impl Block for Docker {
             "{images}" => format!("{}", status.images)
         );
 
+        if let Some(ref name) = self.container {
+            let container = self.container(name)?;
+            let health = container
+                .as_ref()
+                .and_then(|container| container.health.as_ref())
+                .map(|health| health.status.clone())
+                .unwrap_or_default();
+            self.container_state = container
+                .map(|container| container.status)
+                .unwrap_or_else(|| "missing".to_string());
+            self.text
+                .set_state(match (self.container_state.as_str(), health.as_str()) {
+                    (_, "unhealthy") | ("dead", _) => State::Critical,
+                    ("running", "starting") => State::Info,
+                    ("running", _) => State::Good,
+                    ("paused", _) | ("restarting", _) => State::Warning,
+                    // created, exited, removing and missing
+                    _ => State::Idle,
+                });
+            values.insert("{name}", name.clone());
+            values.insert("{state}", self.container_state.clone());
+            values.insert("{health}", health);
+        }
+
         self.text.set_text(self.format.render_static_str(&values)?);
 
         Ok(Some(self.update_interval.into()))
@@ -122,11 +303,91 @@ impl Block for Docker {
         vec![&self.text]
     }
 
-    fn click(&mut self, _: &I3BarEvent) -> Result<()> {
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        let name = match self.container {
+            Some(ref name) if event.matches_name(&self.id) => name.clone(),
+            _ => return Ok(()),
+        };
+        let action = match event.button {
+            MouseButton::Left => match self.container_state.as_str() {
+                "running" | "restarting" => "stop",
+                "paused" => "unpause",
+                _ => "start",
+            },
+            MouseButton::Right => "restart",
+            _ => return Ok(()),
+        };
+
+        // Stopping a container can take a while, so the bar does not wait for it
+        let socket = self.socket_path.clone();
+        let id = self.id.clone();
+        let send = self.send.clone();
+        let action_error = self.action_error.clone();
+        thread::Builder::new()
+            .name("docker".into())
+            .spawn(move || {
+                if let Err(error) = container_action(&socket, &name, action) {
+                    if let Ok(mut action_error) = action_error.lock() {
+                        *action_error = Some(error.to_string());
+                    }
+                }
+                let _ = send.send(Task {
+                    id,
+                    update_time: Instant::now(),
+                });
+            })
+            .block_error("docker", "failed to start a thread for the action")?;
         Ok(())
     }
 
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        self.container.is_some() && matches!(event.button, MouseButton::Left | MouseButton::Right)
+    }
+
     fn id(&self) -> &str {
         &self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_response, Container, ContainerState, Health};
+
+    #[test]
+    fn test_parse_response() {
+        let response = "HTTP/1.0 200 OK\r\nApi-Version: 1.43\r\nContent-Type: application/json\r\n\r\n{\"Containers\":3}\n";
+        assert_eq!(
+            parse_response(response),
+            Some((200, "{\"Containers\":3}\n"))
+        );
+        assert_eq!(
+            parse_response("HTTP/1.0 204 No Content\r\n\r\n"),
+            Some((204, ""))
+        );
+        assert_eq!(parse_response(""), None);
+    }
+
+    #[test]
+    fn test_parse_container() {
+        let json = r#"{"Id":"4fa6e0f0c678","Name":"/web","State":{"Status":"running","Running":true,"Health":{"Status":"unhealthy","FailingStreak":3}}}"#;
+        assert_eq!(
+            serde_json::from_str::<Container>(json).unwrap(),
+            Container {
+                state: ContainerState {
+                    status: "running".to_string(),
+                    health: Some(Health {
+                        status: "unhealthy".to_string()
+                    }),
+                }
+            }
+        );
+        let json = r#"{"Name":"/db","State":{"Status":"exited","Running":false}}"#;
+        assert_eq!(
+            serde_json::from_str::<Container>(json)
+                .unwrap()
+                .state
+                .health,
+            None
+        );
+    }
+}
@@ -0,0 +1,230 @@
+//! A block for the number of failed systemd units.
+
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use dbus::ffidisp::stdintf::org_freedesktop_dbus::Properties;
+use dbus::ffidisp::{BusType, Connection};
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+fn failed_units(conn: &Connection) -> Result<u32> {
+    conn.with_path(
+        "org.freedesktop.systemd1",
+        "/org/freedesktop/systemd1",
+        1000,
+    )
+    .get("org.freedesktop.systemd1.Manager", "NFailedUnits")
+    .block_error(
+        "failed_units",
+        "failed to get the failed units from systemd",
+    )
+}
+
+pub struct FailedUnits {
+    id: String,
+    output: ButtonWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    reset_command: String,
+    system_conn: Option<Connection>,
+    user_conn: Option<Connection>,
+    count: u32,
+    send: Sender<Task>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FailedUnitsConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "FailedUnitsConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format string
+    #[serde(default = "FailedUnitsConfig::default_format")]
+    pub format: String,
+
+    /// Whether to count the units of the system
+    #[serde(default = "FailedUnitsConfig::default_system")]
+    pub system: bool,
+
+    /// Whether to count the units of the user
+    #[serde(default = "FailedUnitsConfig::default_user")]
+    pub user: bool,
+
+    /// Command to run on a left click, which resets the failed units
+    #[serde(default = "FailedUnitsConfig::default_reset_command")]
+    pub reset_command: String,
+}
+
+impl FailedUnitsConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn default_format() -> String {
+        "{count} failed".to_owned()
+    }
+
+    fn default_system() -> bool {
+        true
+    }
+
+    fn default_user() -> bool {
+        true
+    }
+
+    fn default_reset_command() -> String {
+        "systemctl reset-failed; systemctl --user reset-failed".to_owned()
+    }
+}
+
+impl ConfigBlock for FailedUnits {
+    type Config = FailedUnitsConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let connect = |enabled: bool, bus: BusType| -> Result<Option<Connection>> {
+            if !enabled {
+                return Ok(None);
+            }
+            Connection::get_private(bus)
+                .map(Some)
+                .block_error("failed_units", "failed to establish D-Bus connection")
+        };
+
+        let id = pseudo_uuid();
+        Ok(FailedUnits {
+            output: ButtonWidget::new(config, &id),
+            id,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("failed_units", "Invalid format specified")?,
+            reset_command: block_config.reset_command,
+            system_conn: connect(block_config.system, BusType::System)?,
+            user_conn: connect(block_config.user, BusType::Session)?,
+            count: 0,
+            send,
+        })
+    }
+}
+
+impl Block for FailedUnits {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let system = match self.system_conn {
+            Some(ref conn) => failed_units(conn)?,
+            None => 0,
+        };
+        let user = match self.user_conn {
+            Some(ref conn) => failed_units(conn)?,
+            None => 0,
+        };
+        self.count = system + user;
+
+        let values = map!(
+            "{count}" => self.count.to_string(),
+            "{system}" => system.to_string(),
+            "{user}" => user.to_string()
+        );
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
+        self.output.set_state(if self.count > 0 {
+            State::Critical
+        } else {
+            State::Good
+        });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.matches_name(&self.id) && event.button == MouseButton::Left {
+            spawn_child_async("sh", &["-c", &self.reset_command])
+                .block_error("failed_units", "could not spawn child")?;
+            // Check again once the command had time to reset the units
+            self.send.send(Task {
+                id: self.id.clone(),
+                update_time: Instant::now() + Duration::from_secs(1),
+            })?;
+        }
+        Ok(())
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn block(reset_command: &str) -> (FailedUnits, crossbeam_channel::Receiver<Task>) {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let block_config = FailedUnitsConfig {
+            interval: FailedUnitsConfig::default_interval(),
+            format: "{count} failed".to_string(),
+            system: false,
+            user: false,
+            reset_command: reset_command.to_string(),
+        };
+        (
+            FailedUnits::new(block_config, Config::default(), tx).unwrap(),
+            rx,
+        )
+    }
+
+    #[test]
+    fn test_update_without_buses() {
+        let (mut block, _rx) = block("true");
+        assert_eq!(
+            block.update().unwrap(),
+            Some(Update::Every(Duration::from_secs(10)))
+        );
+        assert_eq!(block.count, 0);
+        assert_eq!(block.output.content(), "0 failed");
+    }
+
+    #[test]
+    fn test_click_schedules_check() {
+        let (mut block, rx) = block("true");
+        let mut event = I3BarEvent {
+            name: Some(block.id.clone()),
+            instance: None,
+            x: 0,
+            y: 0,
+            button: MouseButton::Right,
+            modifiers: vec![],
+        };
+        block.click(&event).unwrap();
+        assert!(rx.try_recv().is_err());
+
+        event.button = MouseButton::Left;
+        let clicked = Instant::now();
+        block.click(&event).unwrap();
+        let task = rx.try_recv().unwrap();
+        assert_eq!(task.id, block.id);
+        // The check is scheduled for later instead of right away
+        assert!(task.update_time >= clicked + Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,396 @@
+//! A block for the unread entries of RSS and Atom feeds.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use crossbeam_channel::Sender;
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{http_get, pseudo_uuid, xdg_cache_home, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+lazy_static! {
+    static ref ITEM_REGEX: Regex = Regex::new(r"(?s)<item\b[^>]*>(.*?)</item>").unwrap();
+    static ref ENTRY_REGEX: Regex = Regex::new(r"(?s)<entry\b[^>]*>(.*?)</entry>").unwrap();
+    static ref TITLE_REGEX: Regex = Regex::new(r"(?s)<title\b[^>]*>(.*?)</title>").unwrap();
+    static ref GUID_REGEX: Regex = Regex::new(r"(?s)<guid\b[^>]*>(.*?)</guid>").unwrap();
+    static ref ID_REGEX: Regex = Regex::new(r"(?s)<id\b[^>]*>(.*?)</id>").unwrap();
+    static ref LINK_REGEX: Regex = Regex::new(r"(?s)<link\b[^>]*>(.*?)</link>").unwrap();
+    static ref PUB_DATE_REGEX: Regex = Regex::new(r"(?s)<pubDate\b[^>]*>(.*?)</pubDate>").unwrap();
+    static ref UPDATED_REGEX: Regex =
+        Regex::new(r"(?s)<(?:updated|published)\b[^>]*>(.*?)</(?:updated|published)>").unwrap();
+    static ref ENTITY_REGEX: Regex = Regex::new(r"&(#x[[:xdigit:]]+|#[0-9]+|[a-z]+);").unwrap();
+}
+
+/// An item of an RSS feed or an entry of an Atom feed.
+#[derive(Clone, Debug, PartialEq)]
+struct Entry {
+    id: String,
+    title: String,
+    date: Option<DateTime<Utc>>,
+}
+
+/// Decodes the text of an element, which may be in a CDATA section or have entities.
+fn xml_text(text: &str) -> String {
+    let text = text.trim();
+    if let Some(data) = text
+        .strip_prefix("<![CDATA[")
+        .and_then(|text| text.strip_suffix("]]>"))
+    {
+        return data.trim().to_string();
+    }
+    ENTITY_REGEX
+        .replace_all(text, |captures: &regex::Captures| {
+            let entity = &captures[1];
+            let decoded = if let Some(hex) = entity.strip_prefix("#x") {
+                u32::from_str_radix(hex, 16)
+                    .ok()
+                    .and_then(std::char::from_u32)
+            } else if let Some(decimal) = entity.strip_prefix('#') {
+                decimal.parse().ok().and_then(std::char::from_u32)
+            } else {
+                match entity {
+                    "lt" => Some('<'),
+                    "gt" => Some('>'),
+                    "amp" => Some('&'),
+                    "quot" => Some('"'),
+                    "apos" => Some('\''),
+                    _ => None,
+                }
+            };
+            decoded.map_or_else(|| captures[0].to_string(), |c| c.to_string())
+        })
+        .to_string()
+}
+
+fn element(regex: &Regex, content: &str) -> Option<String> {
+    regex
+        .captures(content)
+        .map(|captures| xml_text(&captures[1]))
+        .filter(|text| !text.is_empty())
+}
+
+/// The entries of an RSS or an Atom feed, in the order of the feed.
+fn parse_feed(xml: &str) -> Vec<Entry> {
+    let items = ITEM_REGEX.captures_iter(xml).map(|captures| {
+        let content = captures.get(1).map_or("", |m| m.as_str());
+        let title = element(&TITLE_REGEX, content).unwrap_or_default();
+        Entry {
+            id: element(&GUID_REGEX, content)
+                .or_else(|| element(&LINK_REGEX, content))
+                .unwrap_or_else(|| title.clone()),
+            date: element(&PUB_DATE_REGEX, content)
+                .and_then(|date| DateTime::parse_from_rfc2822(&date).ok())
+                .map(|date| date.with_timezone(&Utc)),
+            title,
+        }
+    });
+    let entries = ENTRY_REGEX.captures_iter(xml).map(|captures| {
+        let content = captures.get(1).map_or("", |m| m.as_str());
+        let title = element(&TITLE_REGEX, content).unwrap_or_default();
+        Entry {
+            id: element(&ID_REGEX, content).unwrap_or_else(|| title.clone()),
+            date: element(&UPDATED_REGEX, content)
+                .and_then(|date| DateTime::parse_from_rfc3339(&date).ok())
+                .map(|date| date.with_timezone(&Utc)),
+            title,
+        }
+    });
+    items.chain(entries).collect()
+}
+
+/// The unread entries of the feeds, newest first, given the ids of the read entries by feed.
+/// Feeds that were never seen before are added to them as read.
+fn unread<'a>(
+    feeds: &'a [(String, Vec<Entry>)],
+    seen: &mut HashMap<String, Vec<String>>,
+) -> Vec<&'a Entry> {
+    let mut unread = Vec::new();
+    for (url, entries) in feeds {
+        let seen = seen
+            .entry(url.clone())
+            .or_insert_with(|| entries.iter().map(|entry| entry.id.clone()).collect());
+        unread.extend(entries.iter().filter(|entry| !seen.contains(&entry.id)));
+    }
+    // Entries without a date keep the order of their feed, which is usually newest first
+    unread.sort_by(|a, b| b.date.cmp(&a.date));
+    unread
+}
+
+pub struct Feeds {
+    id: String,
+    output: ButtonWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    urls: Vec<String>,
+    state_file: PathBuf,
+    /// The entries of each feed that was fetched
+    feeds: Vec<(String, Vec<Entry>)>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FeedsConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "FeedsConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// The urls of the feeds
+    pub urls: Vec<String>,
+
+    /// Format string
+    #[serde(default = "FeedsConfig::default_format")]
+    pub format: String,
+
+    /// The file in which the read entries are kept
+    #[serde(default = "FeedsConfig::default_state_file")]
+    pub state_file: PathBuf,
+}
+
+impl FeedsConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(900)
+    }
+
+    fn default_format() -> String {
+        "{unread} {headline}".to_owned()
+    }
+
+    fn default_state_file() -> PathBuf {
+        xdg_cache_home().join("i3status-rust/feeds.json")
+    }
+}
+
+impl ConfigBlock for Feeds {
+    type Config = FeedsConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+        Ok(Feeds {
+            output: ButtonWidget::new(config, &id),
+            id,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("feeds", "Invalid format specified")?,
+            urls: block_config.urls,
+            state_file: block_config.state_file,
+            feeds: Vec::new(),
+        })
+    }
+}
+
+impl Feeds {
+    /// The read entries by feed. The file is shared by all feeds blocks.
+    fn read_state(&self) -> HashMap<String, Vec<String>> {
+        fs::read_to_string(&self.state_file)
+            .ok()
+            .and_then(|state| serde_json::from_str(&state).ok())
+            .unwrap_or_default()
+    }
+
+    fn write_state(&self, seen: &HashMap<String, Vec<String>>) -> Result<()> {
+        if let Some(dir) = self.state_file.parent() {
+            fs::create_dir_all(dir).block_error("feeds", "failed to create the state directory")?;
+        }
+        fs::write(
+            &self.state_file,
+            serde_json::to_string(seen).block_error("feeds", "failed to serialize the state")?,
+        )
+        .block_error("feeds", "failed to write the state file")
+    }
+
+    fn render(&mut self) -> Result<()> {
+        let mut seen = self.read_state();
+        let known = seen.len();
+        let unread = unread(&self.feeds, &mut seen);
+        let values = map!(
+            "{unread}" => unread.len().to_string(),
+            "{headline}" => unread.first().map_or(String::new(), |entry| entry.title.clone())
+        );
+        let text = self.format.render_static_str(&values)?;
+        let state = if unread.is_empty() {
+            State::Idle
+        } else {
+            State::Info
+        };
+        // New feeds are taken as read from now on
+        if seen.len() != known {
+            self.write_state(&seen)?;
+        }
+        self.output.set_text(text);
+        self.output.set_state(state);
+        Ok(())
+    }
+}
+
+impl Block for Feeds {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mut errors = Vec::new();
+        for url in &self.urls {
+            let response = match http_get("feeds", url, &[], Duration::from_secs(10)) {
+                Ok(response) if response.is_success() => response,
+                _ => {
+                    errors.push(url.clone());
+                    continue;
+                }
+            };
+            let entries = parse_feed(&response.text());
+            // Failed feeds keep their last entries
+            match self.feeds.iter_mut().find(|(feed, _)| feed == url) {
+                Some(feed) => feed.1 = entries,
+                None => self.feeds.push((url.clone(), entries)),
+            }
+        }
+        if errors.len() == self.urls.len() {
+            return Err(BlockError(
+                "feeds".to_string(),
+                format!("failed to fetch {}", errors.join(", ")),
+            ));
+        }
+        self.render()?;
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if !event.matches_name(&self.id) || event.button != MouseButton::Left {
+            return Ok(());
+        }
+        // Marks all entries as read, and forgets the ones that left the feeds
+        let mut seen = self.read_state();
+        for (url, entries) in &self.feeds {
+            seen.insert(
+                url.clone(),
+                entries.iter().map(|entry| entry.id.clone()).collect(),
+            );
+        }
+        self.write_state(&seen)?;
+        self.render()
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_feed, unread, xml_text, Entry};
+    use chrono::{TimeZone, Utc};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_xml_text() {
+        assert_eq!(
+            xml_text(" Tom &amp; Jerry &#8211; &#x27;1&#39; "),
+            "Tom & Jerry \u{2013} '1'"
+        );
+        assert_eq!(
+            xml_text("<![CDATA[ <b>bold</b> &amp; ]]>"),
+            "<b>bold</b> &amp;"
+        );
+    }
+
+    #[test]
+    fn test_parse_feed() {
+        let rss = r#"<?xml version="1.0"?>
+<rss version="2.0"><channel>
+  <title>Status</title>
+  <item>
+    <title>Degraded performance</title>
+    <link>https://status.example.com/incidents/2</link>
+    <guid isPermaLink="false">incident-2</guid>
+    <pubDate>Wed, 13 Mar 2024 10:00:00 +0000</pubDate>
+  </item>
+  <item>
+    <title><![CDATA[Maintenance & upgrade]]></title>
+    <link>https://status.example.com/incidents/1</link>
+  </item>
+</channel></rss>"#;
+        let entries = parse_feed(rss);
+        assert_eq!(
+            entries,
+            vec![
+                Entry {
+                    id: "incident-2".to_string(),
+                    title: "Degraded performance".to_string(),
+                    date: Some(Utc.ymd(2024, 3, 13).and_hms(10, 0, 0)),
+                },
+                Entry {
+                    id: "https://status.example.com/incidents/1".to_string(),
+                    title: "Maintenance & upgrade".to_string(),
+                    date: None,
+                },
+            ]
+        );
+
+        let atom = r#"<?xml version="1.0" encoding="UTF-8"?>
+<feed xmlns="http://www.w3.org/2005/Atom">
+  <id>tag:github.com,2008:https://github.com/greshake/i3status-rust/releases</id>
+  <title>Release notes from i3status-rust</title>
+  <entry>
+    <id>tag:github.com,2008:Repository/75479022/v0.20.0</id>
+    <updated>2021-07-01T12:00:00Z</updated>
+    <link rel="alternate" type="text/html" href="https://github.com/greshake/i3status-rust/releases/tag/v0.20.0"/>
+    <title>v0.20.0</title>
+  </entry>
+</feed>"#;
+        let entries = parse_feed(atom);
+        assert_eq!(entries.len(), 1);
+        assert_eq!(
+            entries[0].id,
+            "tag:github.com,2008:Repository/75479022/v0.20.0"
+        );
+        assert_eq!(entries[0].title, "v0.20.0");
+        assert_eq!(entries[0].date, Some(Utc.ymd(2021, 7, 1).and_hms(12, 0, 0)));
+    }
+
+    #[test]
+    fn test_unread() {
+        let entry = |id: &str, hour| Entry {
+            id: id.to_string(),
+            title: id.to_string(),
+            date: Some(Utc.ymd(2024, 3, 13).and_hms(hour, 0, 0)),
+        };
+        let feeds = vec![
+            ("a".to_string(), vec![entry("a2", 10), entry("a1", 8)]),
+            ("b".to_string(), vec![entry("b2", 12), entry("b1", 9)]),
+        ];
+        let mut seen = HashMap::new();
+        seen.insert("a".to_string(), vec!["a1".to_string()]);
+
+        // The new feed is taken as read
+        let ids: Vec<&str> = unread(&feeds, &mut seen)
+            .iter()
+            .map(|entry| entry.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["a2"]);
+        assert_eq!(seen["b"], vec!["b2".to_string(), "b1".to_string()]);
+
+        seen.insert("b".to_string(), vec![]);
+        let ids: Vec<&str> = unread(&feeds, &mut seen)
+            .iter()
+            .map(|entry| entry.id.as_str())
+            .collect();
+        assert_eq!(ids, vec!["b2", "a2", "b1"]);
+    }
+}
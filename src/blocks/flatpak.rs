@@ -0,0 +1,203 @@
+//! A block for the pending updates of flatpak apps and runtimes.
+
+use std::process::Command;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+/// Counts the apps and the runtimes in the output of
+/// `flatpak remote-ls --updates --columns=ref`, like `app/org.gimp.GIMP/x86_64/stable`.
+fn count_updates(output: &str) -> (usize, usize) {
+    let count = |kind: &str| {
+        output
+            .lines()
+            .filter(|line| line.trim().starts_with(kind))
+            .count()
+    };
+    (count("app/"), count("runtime/"))
+}
+
+pub struct Flatpak {
+    id: String,
+    output: ButtonWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    format_singular: FormatTemplate,
+    format_up_to_date: FormatTemplate,
+    update_command: Option<String>,
+    tx_update_request: Sender<Task>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FlatpakConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "FlatpakConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format override
+    #[serde(default = "FlatpakConfig::default_format")]
+    pub format: String,
+
+    /// Alternative format override for when exactly 1 update is available
+    #[serde(default = "FlatpakConfig::default_format")]
+    pub format_singular: String,
+
+    /// Alternative format override for when no updates are available
+    #[serde(default = "FlatpakConfig::default_format")]
+    pub format_up_to_date: String,
+
+    /// Optional command to run on a left click, like a terminal running the update.
+    /// The updates are checked again once it exits.
+    #[serde(default)]
+    pub update_command: Option<String>,
+}
+
+impl FlatpakConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(60 * 30)
+    }
+
+    fn default_format() -> String {
+        "{count}".to_owned()
+    }
+}
+
+impl ConfigBlock for Flatpak {
+    type Config = FlatpakConfig;
+
+    fn new(
+        block_config: Self::Config,
+        config: Config,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let id = pseudo_uuid();
+        Ok(Flatpak {
+            output: ButtonWidget::new(config, &id).with_icon("update"),
+            id,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("flatpak", "Invalid format specified for flatpak::format")?,
+            format_singular: FormatTemplate::from_string(&block_config.format_singular)
+                .block_error(
+                    "flatpak",
+                    "Invalid format specified for flatpak::format_singular",
+                )?,
+            format_up_to_date: FormatTemplate::from_string(&block_config.format_up_to_date)
+                .block_error(
+                    "flatpak",
+                    "Invalid format specified for flatpak::format_up_to_date",
+                )?,
+            update_command: block_config.update_command,
+            tx_update_request,
+        })
+    }
+}
+
+impl Block for Flatpak {
+    fn update(&mut self) -> Result<Option<Update>> {
+        // Lists the updates of both the system and the user installation
+        let output = Command::new("flatpak")
+            .args(&["remote-ls", "--updates", "--columns=ref"])
+            .output()
+            .block_error("flatpak", "failed to run flatpak")?;
+        if !output.status.success() {
+            return Err(BlockError(
+                "flatpak".to_string(),
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        let (apps, runtimes) = count_updates(&String::from_utf8_lossy(&output.stdout));
+        let count = apps + runtimes;
+
+        let values = map!(
+            "{count}" => count,
+            "{apps}" => apps,
+            "{runtimes}" => runtimes
+        );
+        self.output.set_text(match count {
+            0 => self.format_up_to_date.render_static_str(&values)?,
+            1 => self.format_singular.render_static_str(&values)?,
+            _ => self.format.render_static_str(&values)?,
+        });
+        self.output.set_state(match count {
+            0 => State::Idle,
+            _ => State::Info,
+        });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if !event.matches_name(&self.id) {
+            return Ok(());
+        }
+        match (event.button, &self.update_command) {
+            (MouseButton::Left, Some(command)) => {
+                // The update takes a while, so the bar must not wait for it
+                let command = command.clone();
+                let id = self.id.clone();
+                let tx = self.tx_update_request.clone();
+                thread::Builder::new()
+                    .name("flatpak".into())
+                    .spawn(move || {
+                        if Command::new("sh").args(&["-c", &command]).status().is_ok() {
+                            let _ = tx.send(Task {
+                                id,
+                                update_time: Instant::now(),
+                            });
+                        }
+                    })
+                    .block_error("flatpak", "failed to start the update command")?;
+            }
+            (MouseButton::Left, None) | (MouseButton::Right, _) => {
+                self.update()?;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left && self.update_command.is_some()
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::count_updates;
+
+    #[test]
+    fn test_count_updates() {
+        let output = "\
+app/org.gimp.GIMP/x86_64/stable
+app/org.mozilla.firefox/x86_64/stable
+runtime/org.freedesktop.Platform.GL.default/x86_64/23.08
+";
+        assert_eq!(count_updates(output), (2, 1));
+        assert_eq!(count_updates(""), (0, 0));
+    }
+}
@@ -1,6 +1,6 @@
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
@@ -9,11 +9,12 @@ use swayipc::{Connection, EventType};
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::Config;
+use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::scheduler::Task;
 use crate::util::pseudo_uuid;
 use crate::widget::I3BarWidget;
-use crate::widgets::text::TextWidget;
+use crate::widgets::rotatingtext::RotatingTextWidget;
 
 #[derive(Copy, Clone, Debug, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -23,12 +24,38 @@ pub enum MarksType {
     None,
 }
 
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Truncation {
+    End,
+    Middle,
+    Marquee,
+}
+
+/// Shortens a title to `max_width` characters by replacing its middle with an ellipsis.
+fn truncate_middle(text: &str, max_width: usize) -> String {
+    let length = text.chars().count();
+    if length <= max_width {
+        return text.to_string();
+    }
+    if max_width == 0 {
+        return String::new();
+    }
+    let tail = (max_width - 1) / 2;
+    let head = max_width - 1 - tail;
+    let mut truncated: String = text.chars().take(head).collect();
+    truncated.push('\u{2026}');
+    truncated.extend(text.chars().skip(length - tail));
+    truncated
+}
+
 pub struct FocusedWindow {
-    text: TextWidget,
+    text: RotatingTextWidget,
     title: Arc<Mutex<String>>,
     marks: Arc<Mutex<String>>,
     show_marks: MarksType,
     max_width: usize,
+    truncation: Truncation,
     id: String,
 }
 
@@ -42,6 +69,24 @@ pub struct FocusedWindowConfig {
     /// Show marks in place of title (if exist)
     #[serde(default = "FocusedWindowConfig::default_show_marks")]
     pub show_marks: MarksType,
+
+    /// How titles longer than max-width are shortened
+    #[serde(default = "FocusedWindowConfig::default_truncation")]
+    pub truncation: Truncation,
+
+    /// Marquee interval in seconds. This is the delay between each rotation.
+    #[serde(
+        default = "FocusedWindowConfig::default_marquee_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub marquee_interval: Duration,
+
+    /// Marquee speed in seconds. This is the scrolling time used per character.
+    #[serde(
+        default = "FocusedWindowConfig::default_marquee_speed",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub marquee_speed: Duration,
 }
 
 impl FocusedWindowConfig {
@@ -52,6 +97,18 @@ impl FocusedWindowConfig {
     fn default_show_marks() -> MarksType {
         MarksType::None
     }
+
+    fn default_truncation() -> Truncation {
+        Truncation::End
+    }
+
+    fn default_marquee_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn default_marquee_speed() -> Duration {
+        Duration::from_millis(500)
+    }
 }
 
 impl ConfigBlock for FocusedWindow {
@@ -167,21 +224,32 @@ impl ConfigBlock for FocusedWindow {
                         _ => false,
                     };
 
-                    if updated {
-                        tx.send(Task {
-                            id: id_clone.clone(),
-                            update_time: Instant::now(),
-                        })
-                        .expect("could not communicate with channel in `window` block");
+                    if updated
+                        && tx
+                            .send(Task {
+                                id: id_clone.clone(),
+                                update_time: Instant::now(),
+                            })
+                            .is_err()
+                    {
+                        return;
                     }
                 }
             })
             .expect("failed to start watching thread for `window` block");
 
         Ok(FocusedWindow {
+            text: RotatingTextWidget::new(
+                block_config.marquee_interval,
+                block_config.marquee_speed,
+                block_config.max_width,
+                true,
+                config,
+                &id,
+            ),
             id,
-            text: TextWidget::new(config),
             max_width: block_config.max_width,
+            truncation: block_config.truncation,
             show_marks: block_config.show_marks,
             title,
             marks,
@@ -191,18 +259,16 @@ impl ConfigBlock for FocusedWindow {
 
 impl Block for FocusedWindow {
     fn update(&mut self) -> Result<Option<Update>> {
-        let mut marks_string = (*self
+        let marks_string = (*self
             .marks
             .lock()
             .block_error("focused_window", "failed to acquire lock")?)
         .clone();
-        marks_string = marks_string.chars().take(self.max_width).collect();
-        let mut title_string = (*self
+        let title_string = (*self
             .title
             .lock()
             .block_error("focused_window", "failed to acquire lock")?)
         .clone();
-        title_string = title_string.chars().take(self.max_width).collect();
         let out_str = match self.show_marks {
             MarksType::None => title_string,
             _ => {
@@ -213,9 +279,20 @@ impl Block for FocusedWindow {
                 }
             }
         };
+        // The widget scrolls the text that is longer than max_width
+        let out_str = match self.truncation {
+            Truncation::End => out_str.chars().take(self.max_width).collect(),
+            Truncation::Middle => truncate_middle(&out_str, self.max_width),
+            Truncation::Marquee => out_str,
+        };
         self.text.set_text(out_str);
 
-        Ok(None)
+        if self.truncation == Truncation::Marquee {
+            let (_, next_rotation) = self.text.next()?;
+            Ok(next_rotation.map(|d| d.into()))
+        } else {
+            Ok(None)
+        }
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
@@ -235,3 +312,19 @@ impl Block for FocusedWindow {
         &self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::truncate_middle;
+
+    #[test]
+    fn test_truncate_middle() {
+        assert_eq!(truncate_middle("short", 10), "short");
+        assert_eq!(
+            truncate_middle("Pull requests - Mozilla Firefox", 11),
+            "Pull \u{2026}refox"
+        );
+        assert_eq!(truncate_middle("abcdef", 4), "ab\u{2026}f");
+        assert_eq!(truncate_middle("abcdef", 0), "");
+    }
+}
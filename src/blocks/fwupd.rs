@@ -0,0 +1,248 @@
+//! A block for the devices with firmware upgrades, from fwupd.
+
+use std::collections::HashMap;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use dbus::arg::{RefArg, Variant};
+use dbus::ffidisp::{BusType, Connection};
+use dbus::Message;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+const FWUPD: &str = "org.freedesktop.fwupd";
+
+/// https://fwupd.github.io/libfwupd/fwupd-enums.html#FWUPD-DEVICE-FLAG-UPDATABLE:CAPS
+const FWUPD_DEVICE_FLAG_UPDATABLE: u64 = 1 << 1;
+/// https://fwupd.github.io/libfwupd/fwupd-enums.html#FwupdReleaseUrgency
+const FWUPD_RELEASE_URGENCY_HIGH: u64 = 3;
+
+/// The properties of a device or a release.
+type Dict = HashMap<String, Variant<Box<dyn RefArg>>>;
+
+fn method(name: &str) -> Result<Message> {
+    Message::new_method_call(FWUPD, "/", FWUPD, name)
+        .block_error("fwupd", "failed to create D-Bus method call")
+}
+
+/// A device with an upgrade.
+struct Upgrade {
+    device: String,
+    version: String,
+    urgent: bool,
+}
+
+pub struct Fwupd {
+    id: String,
+    output: ButtonWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    format_singular: FormatTemplate,
+    format_up_to_date: FormatTemplate,
+    dbus_conn: Connection,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct FwupdConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "FwupdConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format override
+    #[serde(default = "FwupdConfig::default_format")]
+    pub format: String,
+
+    /// Alternative format override for when exactly 1 device has an upgrade
+    #[serde(default = "FwupdConfig::default_format")]
+    pub format_singular: String,
+
+    /// Alternative format override for when no device has an upgrade
+    #[serde(default = "FwupdConfig::default_format")]
+    pub format_up_to_date: String,
+}
+
+impl FwupdConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(60 * 60)
+    }
+
+    fn default_format() -> String {
+        "{count}".to_owned()
+    }
+}
+
+impl ConfigBlock for Fwupd {
+    type Config = FwupdConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+        let dbus_conn = Connection::get_private(BusType::System)
+            .block_error("fwupd", "failed to establish D-Bus connection")?;
+
+        // fwupd signals a change when devices come and go, and when the metadata is refreshed
+        let watcher_id = id.clone();
+        thread::Builder::new()
+            .name("fwupd".into())
+            .spawn(move || {
+                let c = Connection::get_private(BusType::System)
+                    .expect("Failed to establish D-Bus connection in thread");
+                c.add_match(&format!(
+                    "type='signal',interface='{}',member='Changed'",
+                    FWUPD
+                ))
+                .expect("Failed to add D-Bus match rule.");
+
+                loop {
+                    for msg in c.incoming(10_000) {
+                        if msg.member().map_or(false, |m| &*m == "Changed")
+                            && send
+                                .send(Task {
+                                    id: watcher_id.clone(),
+                                    update_time: Instant::now(),
+                                })
+                                .is_err()
+                        {
+                            return;
+                        }
+                    }
+                }
+            })
+            .unwrap();
+
+        Ok(Fwupd {
+            output: ButtonWidget::new(config, &id).with_icon("update"),
+            id,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("fwupd", "Invalid format specified for fwupd::format")?,
+            format_singular: FormatTemplate::from_string(&block_config.format_singular)
+                .block_error(
+                    "fwupd",
+                    "Invalid format specified for fwupd::format_singular",
+                )?,
+            format_up_to_date: FormatTemplate::from_string(&block_config.format_up_to_date)
+                .block_error(
+                    "fwupd",
+                    "Invalid format specified for fwupd::format_up_to_date",
+                )?,
+            dbus_conn,
+        })
+    }
+}
+
+impl Fwupd {
+    fn upgrades(&self) -> Result<Vec<Upgrade>> {
+        let devices: Vec<Dict> = self
+            .dbus_conn
+            .send_with_reply_and_block(method("GetDevices")?, 5000)
+            .block_error("fwupd", "failed to get the devices from fwupd")?
+            .get1()
+            .block_error("fwupd", "failed to read the devices")?;
+
+        let mut upgrades = Vec::new();
+        for device in devices {
+            let flags = device.get("Flags").and_then(|f| f.0.as_u64()).unwrap_or(0);
+            let device_id = match device.get("DeviceId").and_then(|d| d.0.as_str()) {
+                Some(device_id) if flags & FWUPD_DEVICE_FLAG_UPDATABLE != 0 => device_id,
+                _ => continue,
+            };
+            // fwupd answers with an error like `NothingToDo` if there is no upgrade
+            let releases: Vec<Dict> = match self
+                .dbus_conn
+                .send_with_reply_and_block(method("GetUpgrades")?.append1(device_id), 5000)
+                .ok()
+                .and_then(|reply| reply.get1())
+            {
+                Some(releases) => releases,
+                None => continue,
+            };
+            // The newest release comes first
+            if let Some(release) = releases.first() {
+                upgrades.push(Upgrade {
+                    device: device
+                        .get("Name")
+                        .and_then(|n| n.0.as_str())
+                        .unwrap_or(device_id)
+                        .to_string(),
+                    version: release
+                        .get("Version")
+                        .and_then(|v| v.0.as_str())
+                        .unwrap_or("")
+                        .to_string(),
+                    urgent: releases.iter().any(|release| {
+                        release
+                            .get("Urgency")
+                            .and_then(|u| u.0.as_u64())
+                            .map_or(false, |u| u >= FWUPD_RELEASE_URGENCY_HIGH)
+                    }),
+                });
+            }
+        }
+        Ok(upgrades)
+    }
+}
+
+impl Block for Fwupd {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let upgrades = self.upgrades()?;
+        let count = upgrades.len();
+
+        let values = map!(
+            "{count}" => count.to_string(),
+            "{devices}" => upgrades
+                .iter()
+                .map(|upgrade| upgrade.device.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            "{versions}" => upgrades
+                .iter()
+                .map(|upgrade| format!("{} {}", upgrade.device, upgrade.version))
+                .collect::<Vec<_>>()
+                .join(", ")
+        );
+        self.output.set_text(match count {
+            0 => self.format_up_to_date.render_static_str(&values)?,
+            1 => self.format_singular.render_static_str(&values)?,
+            _ => self.format.render_static_str(&values)?,
+        });
+        self.output
+            .set_state(if upgrades.iter().any(|upgrade| upgrade.urgent) {
+                State::Warning
+            } else if count > 0 {
+                State::Info
+            } else {
+                State::Idle
+            });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.matches_name(&self.id) && event.button == MouseButton::Left {
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
@@ -1,6 +1,5 @@
 use std::collections::HashMap;
-use std::process::Command;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crossbeam_channel::Sender;
 use lazy_static::lazy_static;
@@ -11,21 +10,30 @@ use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::Config;
 use crate::de::deserialize_duration;
 use crate::errors::*;
-use crate::input::I3BarEvent;
+use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::subprocess::spawn_child_async;
+use crate::util::{http_get, pseudo_uuid, read_token, FormatTemplate};
 use crate::widget::I3BarWidget;
-use crate::widgets::text::TextWidget;
+use crate::widgets::button::ButtonWidget;
 
 const GITHUB_TOKEN_ENV: &str = "I3RS_GITHUB_TOKEN";
 
+/// The longest wait between two updates after failures.
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
 pub struct Github {
-    text: TextWidget,
+    text: ButtonWidget,
     id: String,
     update_interval: Duration,
     api_server: String,
     token: String,
     format: FormatTemplate,
+    repos: Vec<String>,
+    reasons: Vec<String>,
+    notifications_url: String,
+    /// Number of updates that failed in a row
+    failures: u32,
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -44,6 +52,22 @@ pub struct GithubConfig {
     /// Format override
     #[serde(default = "GithubConfig::default_format")]
     pub format: String,
+
+    /// File that contains the token, instead of the environment variable
+    #[serde(default)]
+    pub token_file: Option<String>,
+
+    /// Only count the notifications of these repositories, like `owner/repo`
+    #[serde(default)]
+    pub repos: Vec<String>,
+
+    /// Only count the notifications with these reasons, like `mention`
+    #[serde(default)]
+    pub reasons: Vec<String>,
+
+    /// Page to open on a left click
+    #[serde(default = "GithubConfig::default_notifications_url")]
+    pub notifications_url: String,
 }
 
 impl GithubConfig {
@@ -58,42 +82,65 @@ impl GithubConfig {
     fn default_format() -> String {
         "{total}".to_owned()
     }
+
+    fn default_notifications_url() -> String {
+        "https://github.com/notifications".to_owned()
+    }
 }
 
 impl ConfigBlock for Github {
     type Config = GithubConfig;
 
     fn new(block_config: Self::Config, config: Config, _: Sender<Task>) -> Result<Self> {
-        let token = match std::env::var(GITHUB_TOKEN_ENV).ok() {
-            Some(v) => v,
-            None => {
+        let token = match (
+            &block_config.token_file,
+            std::env::var(GITHUB_TOKEN_ENV).ok(),
+        ) {
+            (Some(path), _) => read_token("github", path)?,
+            (None, Some(v)) => v,
+            (None, None) => {
                 return Err(BlockError(
                     "github".to_owned(),
-                    "missing I3RS_GITHUB_TOKEN environment variable".to_owned(),
+                    "missing token_file option or I3RS_GITHUB_TOKEN environment variable"
+                        .to_owned(),
                 ))
             }
         };
 
+        let id = pseudo_uuid();
         Ok(Github {
-            id: pseudo_uuid(),
+            text: ButtonWidget::new(config, &id)
+                .with_text("x")
+                .with_icon("github"),
+            id,
             update_interval: block_config.interval,
-            text: TextWidget::new(config).with_text("x").with_icon("github"),
             api_server: block_config.api_server,
             token,
             format: FormatTemplate::from_string(&block_config.format)
                 .block_error("github", "Invalid format specified")?,
+            repos: block_config.repos,
+            reasons: block_config.reasons,
+            notifications_url: block_config.notifications_url,
+            failures: 0,
         })
     }
 }
 
 impl Block for Github {
     fn update(&mut self) -> Result<Option<Update>> {
-        let aggregations = match Notifications::new(&self.api_server, &self.token).try_fold(
+        let mut notifications = Notifications::new(&self.api_server, &self.token);
+        let (repos, reasons) = (&self.repos, &self.reasons);
+        let aggregations = match (&mut notifications).try_fold(
             map!("total".to_owned() => 0),
             |mut acc,
              notif|
              -> std::result::Result<HashMap<String, u64>, Box<dyn std::error::Error>> {
                 let n = notif?;
+                if (!repos.is_empty() && !repos.contains(&n.repository.full_name))
+                    || (!reasons.is_empty() && !reasons.contains(&n.reason))
+                {
+                    return Ok(acc);
+                }
                 acc.entry(n.reason).and_modify(|v| *v += 1).or_insert(1);
                 acc.entry("total".to_owned()).and_modify(|v| *v += 1);
                 Ok(acc)
@@ -103,9 +150,15 @@ impl Block for Github {
             Err(_) => {
                 // If there is a error reported, set the value to x
                 self.text.set_text("x".to_owned());
-                return Ok(Some(self.update_interval.into()));
+                // Waits until the rate limit resets, or backs off exponentially otherwise
+                let wait = notifications.retry_after.unwrap_or_else(|| {
+                    self.failures += 1;
+                    (self.update_interval * 2u32.saturating_pow(self.failures)).min(MAX_BACKOFF)
+                });
+                return Ok(Some(wait.max(self.update_interval).into()));
             }
         };
+        self.failures = 0;
 
         let default: u64 = 0;
         let values = map!(
@@ -127,17 +180,30 @@ impl Block for Github {
 
         self.text.set_text(self.format.render_static_str(&values)?);
 
-        Ok(Some(self.update_interval.into()))
+        // GitHub asks clients not to poll more often than X-Poll-Interval
+        let interval = match notifications.poll_interval {
+            Some(poll_interval) => self.update_interval.max(poll_interval),
+            None => self.update_interval,
+        };
+        Ok(Some(interval.into()))
     }
 
     fn view(&self) -> Vec<&dyn I3BarWidget> {
         vec![&self.text]
     }
 
-    fn click(&mut self, _: &I3BarEvent) -> Result<()> {
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.matches_name(&self.id) && event.button == MouseButton::Left {
+            spawn_child_async("xdg-open", &[&self.notifications_url])
+                .block_error("github", "could not spawn child")?;
+        }
         Ok(())
     }
 
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left
+    }
+
     fn id(&self) -> &str {
         &self.id
     }
@@ -146,12 +212,22 @@ impl Block for Github {
 #[derive(Deserialize)]
 struct Notification {
     reason: String,
+    repository: Repository,
+}
+
+#[derive(Deserialize)]
+struct Repository {
+    full_name: String,
 }
 
 struct Notifications<'a> {
     notifications: <Vec<Notification> as IntoIterator>::IntoIter,
     token: &'a str,
     next_page_url: String,
+    /// The least time between two polls that GitHub asks for
+    poll_interval: Option<Duration>,
+    /// The time until the rate limit resets, if it has been exceeded
+    retry_after: Option<Duration>,
 }
 
 impl<'a> Iterator for Notifications<'a> {
@@ -172,6 +248,8 @@ impl<'a> Notifications<'a> {
             next_page_url: format!("{}/notifications", api_server),
             token,
             notifications: vec![].into_iter(),
+            poll_interval: None,
+            retry_after: None,
         }
     }
 
@@ -186,39 +264,31 @@ impl<'a> Notifications<'a> {
             return Ok(None);
         }
 
-        let result = Command::new("sh")
-            .args(&[
-                "-c",
-                &format!(
-                    "curl --silent --dump-header - --header \"Authorization: Bearer {token}\" -m 3 \"{next_page_url}\"",
-                    token = self.token,
-                    next_page_url = self.next_page_url,
-                ),
-            ])
-            .output()?;
-
-        // Catch all errors, if response status is not 200, then curl wont exit with status code 0.
-        if !result.status.success() {
-            return Err(Box::new(BlockError(
-                "github".to_owned(),
-                "curl status code different than 0".to_owned(),
-            )));
+        let headers = [format!("Authorization: Bearer {}", self.token)];
+        let response = http_get(
+            "github",
+            &self.next_page_url,
+            &headers,
+            Duration::from_secs(3),
+        )?;
+        let (status, headers) = (response.status, &response.headers);
+
+        if let Some(poll_interval) = headers.get("x-poll-interval").and_then(|v| v.parse().ok()) {
+            self.poll_interval = Some(Duration::from_secs(poll_interval));
         }
-
-        let output = String::from_utf8(result.stdout)?;
-
-        // Status / headers sections are separed by a blank line.
-        let split: Vec<&str> = output.split("\r\n\r\n").collect();
-        if split.len() != 2 {
+        if status != 200 {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_secs())
+                .unwrap_or(0);
+            self.retry_after = retry_after(status, headers, now);
             return Err(Box::new(BlockError(
                 "github".to_owned(),
-                "unexpected curl output".to_owned(),
+                format!("unexpected status {}", status),
             )));
         }
 
-        let (meta, body) = (split[0], split[1]);
-
-        let next = match meta.lines().find(|&l| l.starts_with("Link:")) {
+        let next = match headers.get("link") {
             Some(v) => match parse_links_header(v).get("next") {
                 Some(next) => next,
                 None => "",
@@ -227,13 +297,29 @@ impl<'a> Notifications<'a> {
         };
         self.next_page_url = next.to_owned();
 
-        let notifications: Vec<Notification> = serde_json::from_str(body)?;
+        let notifications: Vec<Notification> = serde_json::from_slice(&response.body)?;
         self.notifications = notifications.into_iter();
 
         Ok(self.notifications.next())
     }
 }
 
+/// The time to wait after the rate limit has been exceeded, which GitHub answers with 403
+/// or 429, and either `Retry-After` or the time the limit resets at.
+fn retry_after(status: u16, headers: &HashMap<String, String>, now: u64) -> Option<Duration> {
+    if status != 403 && status != 429 {
+        return None;
+    }
+    if let Some(seconds) = headers.get("retry-after").and_then(|v| v.parse().ok()) {
+        return Some(Duration::from_secs(seconds));
+    }
+    if headers.get("x-ratelimit-remaining").map(String::as_str) != Some("0") {
+        return None;
+    }
+    let reset: u64 = headers.get("x-ratelimit-reset")?.parse().ok()?;
+    Some(Duration::from_secs(reset.saturating_sub(now)))
+}
+
 fn parse_links_header(raw_links: &str) -> HashMap<&str, &str> {
     lazy_static! {
         static ref LINKS_REGEX: Regex =
@@ -272,4 +358,20 @@ mod tests {
             )
         );
     }
+
+    #[test]
+    fn it_waits_for_the_rate_limit() {
+        let headers = map_to_owned!(
+            "x-ratelimit-remaining" => "0",
+            "x-ratelimit-reset" => "1700000600"
+        );
+        assert_eq!(
+            retry_after(403, &headers, 1_700_000_000),
+            Some(Duration::from_secs(600))
+        );
+        let headers = map_to_owned!("retry-after" => "30");
+        assert_eq!(retry_after(429, &headers, 0), Some(Duration::from_secs(30)));
+        let headers = map_to_owned!("x-ratelimit-remaining" => "4999");
+        assert_eq!(retry_after(401, &headers, 0), None);
+    }
 }
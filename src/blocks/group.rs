@@ -0,0 +1,476 @@
+use std::collections::HashMap;
+use std::mem;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use crossbeam_channel::{Receiver, Sender};
+use serde_derive::Deserialize;
+use toml::value::Value;
+
+use crate::blocks::{catch_errors, create_block, Block, ConfigBlock, Update};
+use crate::config::{deserialize_blocks, Config};
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::ipc::{IpcCommand, IpcRequest};
+use crate::scheduler::{time_to_boundary, Task};
+use crate::util::pseudo_uuid;
+use crate::widget::I3BarWidget;
+use crate::widgets::button::ButtonWidget;
+
+pub struct Group {
+    id: String,
+    button: ButtonWidget,
+    text_collapsed: String,
+    text_expanded: String,
+    collapsed: bool,
+    children: Vec<Box<dyn Block>>,
+    /// When each child is due for its next update, if it is scheduled.
+    due: Vec<Option<Instant>>,
+    /// When each child asked to be updated, apart from its schedule.
+    requested_at: Vec<Option<Instant>>,
+    /// The update requests of the children by their ids, which the next update moves to
+    /// `requested_at`.
+    requested: Arc<Mutex<HashMap<String, Instant>>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct GroupConfig {
+    /// Whether the blocks of the group are hidden initially
+    #[serde(default = "GroupConfig::default_collapsed")]
+    pub collapsed: bool,
+
+    /// Text of the button while the group is collapsed
+    #[serde(default = "GroupConfig::default_text_collapsed")]
+    pub text_collapsed: String,
+
+    /// Text of the button while the group is expanded
+    #[serde(default = "GroupConfig::default_text_expanded")]
+    pub text_expanded: String,
+
+    /// The blocks of the group, configured like top-level blocks
+    #[serde(default, deserialize_with = "deserialize_blocks")]
+    pub blocks: Vec<(String, Value)>,
+}
+
+impl GroupConfig {
+    fn default_collapsed() -> bool {
+        true
+    }
+
+    fn default_text_collapsed() -> String {
+        "<".to_owned()
+    }
+
+    fn default_text_expanded() -> String {
+        ">".to_owned()
+    }
+}
+
+impl ConfigBlock for Group {
+    type Config = GroupConfig;
+
+    fn new(
+        block_config: Self::Config,
+        config: Config,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let id = pseudo_uuid();
+        let requested = Arc::new(Mutex::new(HashMap::new()));
+
+        // The children request updates by their own id, which only the group knows about.
+        // They are remembered, and the group is updated in their place at the time they asked
+        // for.
+        let (tx_children, rx_children) = crossbeam_channel::unbounded::<Task>();
+        let group_id = id.clone();
+        let group_requested = requested.clone();
+        thread::Builder::new()
+            .name("group".into())
+            .spawn(move || {
+                forward_requests(rx_children, group_requested, group_id, tx_update_request)
+            })
+            .block_error("group", "failed to start the forwarding thread")?;
+
+        // Like top-level blocks, a child that fails shows its error in its place, without
+        // stopping the updates of the others
+        let mut children = Vec::new();
+        for (block_name, child_config) in block_config.blocks {
+            let child = create_block(
+                &block_name,
+                child_config,
+                config.clone(),
+                tx_children.clone(),
+            )?;
+            children.push(catch_errors(child, config.clone(), tx_children.clone()));
+        }
+
+        let text = if block_config.collapsed {
+            &block_config.text_collapsed
+        } else {
+            &block_config.text_expanded
+        };
+        let now = Instant::now();
+        Ok(Group {
+            button: ButtonWidget::new(config, &id).with_text(text),
+            id,
+            text_collapsed: block_config.text_collapsed,
+            text_expanded: block_config.text_expanded,
+            collapsed: block_config.collapsed,
+            due: vec![Some(now); children.len()],
+            requested_at: vec![None; children.len()],
+            children,
+            requested,
+        })
+    }
+}
+
+/// Remembers the update requests of the children, and asks for an update of the group at the
+/// same time, until the group is gone.
+fn forward_requests(
+    rx_children: Receiver<Task>,
+    requested: Arc<Mutex<HashMap<String, Instant>>>,
+    group_id: String,
+    tx_update_request: Sender<Task>,
+) {
+    for task in rx_children {
+        {
+            let mut requested = match requested.lock() {
+                Ok(requested) => requested,
+                Err(_) => return,
+            };
+            let time = requested.entry(task.id).or_insert(task.update_time);
+            *time = (*time).min(task.update_time);
+        }
+        let task = Task {
+            id: group_id.clone(),
+            update_time: task.update_time,
+        };
+        if tx_update_request.send(task).is_err() {
+            return;
+        }
+    }
+}
+
+impl Group {
+    fn set_collapsed(&mut self, collapsed: bool) {
+        self.collapsed = collapsed;
+        self.button.set_text(if collapsed {
+            self.text_collapsed.clone()
+        } else {
+            self.text_expanded.clone()
+        });
+    }
+}
+
+impl Block for Group {
+    fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Updates the children that are due or requested an update, and asks to be updated
+    /// again when the next child is due.
+    fn update(&mut self) -> Result<Option<Update>> {
+        let requested = mem::take(
+            &mut *self
+                .requested
+                .lock()
+                .block_error("group", "failed to acquire lock")?,
+        );
+        let now = Instant::now();
+        for ((child, due), requested_at) in self
+            .children
+            .iter_mut()
+            .zip(self.due.iter_mut())
+            .zip(self.requested_at.iter_mut())
+        {
+            if let Some(&time) = requested.get(child.id()) {
+                *requested_at = Some(requested_at.map_or(time, |t| t.min(time)));
+            }
+            let is_due = due.map_or(false, |due| due <= now);
+            let is_requested = requested_at.map_or(false, |time| time <= now);
+            if !is_due && !is_requested {
+                continue;
+            }
+            if is_requested {
+                *requested_at = None;
+            }
+            let next = match child.update()? {
+                Some(Update::Every(interval)) => Some(now + interval),
+                Some(Update::Aligned(interval)) => Some(now + time_to_boundary(interval)),
+                _ => None,
+            };
+            // Like the scheduler, a requested update can only move the next update earlier
+            *due = match (is_due, *due, next) {
+                (false, Some(due), Some(next)) => Some(due.min(next)),
+                (false, Some(due), None) => Some(due),
+                _ => next,
+            };
+        }
+        Ok(self
+            .due
+            .iter()
+            .chain(self.requested_at.iter())
+            .flatten()
+            .min()
+            .map(|due| Update::Every(due.saturating_duration_since(Instant::now()))))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.button]
+    }
+
+    fn views(&self) -> Vec<(&str, Vec<&dyn I3BarWidget>)> {
+        let mut views = vec![(self.id(), self.view())];
+        if !self.collapsed {
+            for child in &self.children {
+                views.extend(child.views());
+            }
+        }
+        views
+    }
+
+    fn signal(&mut self, signal: i32) -> Result<()> {
+        for child in self.children.iter_mut() {
+            child.signal(signal)?;
+        }
+        Ok(())
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.matches_name(&self.id) && event.button == MouseButton::Left {
+            self.set_collapsed(!self.collapsed);
+        }
+        for child in self.children.iter_mut() {
+            child.click(event)?;
+        }
+        Ok(())
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left
+    }
+
+    /// The group itself is addressed by its id, see `Common`.
+    fn command(&mut self, request: &IpcRequest) -> Result<bool> {
+        if request.block == self.id {
+            match request.command {
+                IpcCommand::Collapse => self.set_collapsed(true),
+                IpcCommand::Expand => self.set_collapsed(false),
+                IpcCommand::ToggleCollapse => self.set_collapsed(!self.collapsed),
+                _ => return Ok(false),
+            }
+            return Ok(true);
+        }
+        let mut found = false;
+        for child in self.children.iter_mut() {
+            found |= child.command(request)?;
+        }
+        Ok(found)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// A block that counts its updates and asks for them at a fixed interval.
+    struct Child {
+        id: String,
+        interval: Duration,
+        updates: Arc<Mutex<u32>>,
+    }
+
+    impl Block for Child {
+        fn id(&self) -> &str {
+            &self.id
+        }
+
+        fn update(&mut self) -> Result<Option<Update>> {
+            *self.updates.lock().unwrap() += 1;
+            Ok(Some(Update::Every(self.interval)))
+        }
+
+        fn view(&self) -> Vec<&dyn I3BarWidget> {
+            vec![]
+        }
+    }
+
+    fn group(intervals: &[u64]) -> (Group, Arc<Mutex<u32>>) {
+        let updates = Arc::new(Mutex::new(0));
+        let children = intervals
+            .iter()
+            .enumerate()
+            .map(|(i, &interval)| {
+                Box::new(Child {
+                    id: format!("child{}", i),
+                    interval: Duration::from_secs(interval),
+                    updates: updates.clone(),
+                }) as Box<dyn Block>
+            })
+            .collect::<Vec<_>>();
+        let group = Group {
+            id: "group".to_string(),
+            button: ButtonWidget::new(Config::default(), "group"),
+            text_collapsed: "<".to_string(),
+            text_expanded: ">".to_string(),
+            collapsed: true,
+            due: vec![Some(Instant::now()); children.len()],
+            requested_at: vec![None; children.len()],
+            children,
+            requested: Arc::new(Mutex::new(HashMap::new())),
+        };
+        (group, updates)
+    }
+
+    fn request(block: &str, command: IpcCommand) -> IpcRequest {
+        IpcRequest {
+            block: block.to_string(),
+            command,
+        }
+    }
+
+    #[test]
+    fn test_collapse_over_ipc() {
+        let (mut group, _) = group(&[60]);
+        assert_eq!(group.views().len(), 1);
+
+        assert!(group
+            .command(&request("group", IpcCommand::ToggleCollapse))
+            .unwrap());
+        assert!(!group.collapsed);
+        assert_eq!(group.views().len(), 2);
+        assert_eq!(group.button.content(), ">");
+
+        assert!(group
+            .command(&request("group", IpcCommand::Expand))
+            .unwrap());
+        assert!(!group.collapsed);
+        assert!(group
+            .command(&request("group", IpcCommand::Collapse))
+            .unwrap());
+        assert!(group.collapsed);
+        assert_eq!(group.views().len(), 1);
+
+        // Commands for other blocks go to the children, which do not know them
+        assert!(!group
+            .command(&request("other", IpcCommand::Collapse))
+            .unwrap());
+        assert!(!group.command(&request("group", IpcCommand::Hide)).unwrap());
+    }
+
+    #[test]
+    fn test_update_schedules_earliest_child() {
+        let (mut group, updates) = group(&[60, 5]);
+        let next = match group.update().unwrap() {
+            Some(Update::Every(next)) => next,
+            other => panic!("unexpected update {:?}", other),
+        };
+        assert_eq!(*updates.lock().unwrap(), 2);
+        assert!(next <= Duration::from_secs(5));
+
+        // A requested update of the slow child does not put off its scheduled update
+        let scheduled = group.due[0];
+        group
+            .requested
+            .lock()
+            .unwrap()
+            .insert("child0".to_string(), Instant::now());
+        group.update().unwrap();
+        assert_eq!(*updates.lock().unwrap(), 3);
+        assert_eq!(group.due[0], scheduled);
+
+        // but brings a later one forward
+        group.due[0] = Some(Instant::now() + Duration::from_secs(3600));
+        group
+            .requested
+            .lock()
+            .unwrap()
+            .insert("child0".to_string(), Instant::now());
+        group.update().unwrap();
+        assert!(group.due[0].unwrap() <= Instant::now() + Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_future_request_waits() {
+        let (mut group, updates) = group(&[60]);
+        group.update().unwrap();
+        assert_eq!(*updates.lock().unwrap(), 1);
+
+        // A child that asks for an update later, like a retry after an error, is not updated
+        // before then, but the group is
+        let later = Instant::now() + Duration::from_secs(10);
+        group
+            .requested
+            .lock()
+            .unwrap()
+            .insert("child0".to_string(), later);
+        let next = match group.update().unwrap() {
+            Some(Update::Every(next)) => next,
+            other => panic!("unexpected update {:?}", other),
+        };
+        assert_eq!(*updates.lock().unwrap(), 1);
+        assert!(next <= Duration::from_secs(10));
+        assert!(next > Duration::from_secs(5));
+        assert_eq!(group.requested_at[0], Some(later));
+    }
+
+    #[test]
+    fn test_forward_requests_keeps_time() {
+        let requested = Arc::new(Mutex::new(HashMap::new()));
+        let (tx_children, rx_children) = crossbeam_channel::unbounded();
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let forwarded = requested.clone();
+        thread::spawn(move || forward_requests(rx_children, forwarded, "group".to_string(), tx));
+
+        let later = Instant::now() + Duration::from_secs(10);
+        tx_children
+            .send(Task {
+                id: "child0".to_string(),
+                update_time: later,
+            })
+            .unwrap();
+        let task = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(task.id, "group");
+        assert_eq!(task.update_time, later);
+        assert_eq!(requested.lock().unwrap()["child0"], later);
+    }
+
+    /// A block whose updates fail.
+    struct Failing;
+
+    impl Block for Failing {
+        fn id(&self) -> &str {
+            "failing"
+        }
+
+        fn update(&mut self) -> Result<Option<Update>> {
+            Err(BlockError("failing".to_string(), "no data".to_string()))
+        }
+
+        fn view(&self) -> Vec<&dyn I3BarWidget> {
+            vec![]
+        }
+    }
+
+    #[test]
+    fn test_failing_child_shows_its_error() {
+        let (mut group, updates) = group(&[60]);
+        let (tx, _rx) = crossbeam_channel::unbounded();
+        group
+            .children
+            .insert(0, catch_errors(Box::new(Failing), Config::default(), tx));
+        group.due.insert(0, Some(Instant::now()));
+        group.requested_at.insert(0, None);
+        group.set_collapsed(false);
+
+        // The other child is still updated, and the error is shown in place of the child
+        assert!(group.update().is_ok());
+        assert_eq!(*updates.lock().unwrap(), 1);
+        let views = group.views();
+        assert_eq!(views.len(), 3);
+        assert_eq!(views[1].0, "failing");
+        assert_eq!(views[1].1.len(), 1);
+    }
+}
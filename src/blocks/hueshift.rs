@@ -1,4 +1,4 @@
-use std::process::Command;
+use std::process::{Child, Command, Stdio};
 use std::time::Duration;
 
 use crossbeam_channel::Sender;
@@ -24,6 +24,8 @@ pub struct Hueshift {
     min_temp: u16,
     hue_shifter: Option<HueShifter>,
     click_temp: u16,
+    /// The wlsunset that the block started, which is replaced on every change
+    wlsunset: Option<Child>,
 
     //useful, but optional
     #[allow(dead_code)]
@@ -37,6 +39,8 @@ pub struct Hueshift {
 pub enum HueShifter {
     Redshift,
     Sct,
+    Gammastep,
+    Wlsunset,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -95,14 +99,7 @@ impl HueshiftConfig {
 
     /// Prefer any installed shifter, redshift is preferred though.
     fn default_hue_shifter() -> Option<HueShifter> {
-        let (redshift, sct) = what_is_supported();
-        if redshift {
-            return Some(HueShifter::Redshift);
-        } else if sct {
-            return Some(HueShifter::Sct);
-        }
-
-        None
+        what_is_supported()
     }
 
     fn default_click_temp() -> u16 {
@@ -144,6 +141,7 @@ impl ConfigBlock for Hueshift {
             current_temp,
             hue_shifter: block_config.hue_shifter,
             click_temp: block_config.click_temp,
+            wlsunset: None,
             config,
         })
     }
@@ -165,15 +163,15 @@ impl Block for Hueshift {
                 match event.button {
                     MouseButton::Left => {
                         self.current_temp = self.click_temp;
-                        update_hue(&self.hue_shifter, self.current_temp);
+                        update_hue(&self.hue_shifter, self.current_temp, &mut self.wlsunset);
                     }
                     MouseButton::Right => {
                         if self.max_temp > 6500 {
                             self.current_temp = 6500;
-                            reset_hue(&self.hue_shifter);
+                            reset_hue(&self.hue_shifter, &mut self.wlsunset);
                         } else {
                             self.current_temp = self.max_temp;
-                            update_hue(&self.hue_shifter, self.current_temp);
+                            update_hue(&self.hue_shifter, self.current_temp, &mut self.wlsunset);
                         }
                     }
                     mb => {
@@ -183,14 +181,14 @@ impl Block for Hueshift {
                             Some(Up) => {
                                 new_temp = self.current_temp + self.step;
                                 if new_temp <= self.max_temp {
-                                    update_hue(&self.hue_shifter, new_temp);
+                                    update_hue(&self.hue_shifter, new_temp, &mut self.wlsunset);
                                     self.current_temp = new_temp;
                                 }
                             }
                             Some(Down) => {
-                                new_temp = self.current_temp - self.step;
+                                new_temp = self.current_temp.saturating_sub(self.step);
                                 if new_temp >= self.min_temp {
-                                    update_hue(&self.hue_shifter, new_temp);
+                                    update_hue(&self.hue_shifter, new_temp, &mut self.wlsunset);
                                     self.current_temp = new_temp;
                                 }
                             }
@@ -203,29 +201,51 @@ impl Block for Hueshift {
         Ok(())
     }
 
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        matches!(
+            event.button,
+            MouseButton::Left | MouseButton::Right | MouseButton::WheelUp | MouseButton::WheelDown
+        )
+    }
+
     fn id(&self) -> &str {
         &self.id
     }
 }
 
-/// Currently, detects whether sct and redshift are installed.
+impl Drop for Hueshift {
+    fn drop(&mut self) {
+        // A reload of the configuration would otherwise leave it running next to the one of
+        // the new block
+        stop_child(&mut self.wlsunset);
+    }
+}
+
+/// Stops a shifter that the block started, and no instances of other programs.
+fn stop_child(child: &mut Option<Child>) {
+    if let Some(mut child) = child.take() {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// Finds the first installed shifter, in order of preference.
 #[inline]
-fn what_is_supported() -> (bool, bool) {
-    let has_redshift = match has_command("hueshift", "redshift") {
-        Ok(has_redshift) => has_redshift,
-        Err(_) => false,
-    };
-
-    let has_sct = match has_command("hueshift", "sct") {
-        Ok(has_sct) => has_sct,
-        Err(_) => false,
-    };
-
-    (has_redshift, has_sct)
+fn what_is_supported() -> Option<HueShifter> {
+    let shifters = [
+        ("redshift", HueShifter::Redshift),
+        ("sct", HueShifter::Sct),
+        ("gammastep", HueShifter::Gammastep),
+        ("wlsunset", HueShifter::Wlsunset),
+    ];
+    shifters
+        .iter()
+        .find(|(command, _)| has_command("hueshift", command).unwrap_or(false))
+        .map(|(_, shifter)| shifter.clone())
 }
 
 #[inline]
-fn update_hue(hue_shifter: &Option<HueShifter>, new_temp: u16) {
+fn update_hue(hue_shifter: &Option<HueShifter>, new_temp: u16, wlsunset: &mut Option<Child>) {
     match hue_shifter {
         Some(HueShifter::Redshift) => {
             Command::new("sh")
@@ -242,12 +262,44 @@ fn update_hue(hue_shifter: &Option<HueShifter>, new_temp: u16) {
                 .spawn()
                 .expect("Failed to set new color temperature using sct.");
         }
+        Some(HueShifter::Gammastep) => {
+            Command::new("sh")
+                .args(&[
+                    "-c",
+                    format!("gammastep -O {} -P >/dev/null 2>&1", new_temp).as_str(),
+                ])
+                .spawn()
+                .expect("Failed to set new color temperature using gammastep.");
+        }
+        // wlsunset has no one-shot mode, so it keeps running with the day and night
+        // temperatures close together
+        Some(HueShifter::Wlsunset) => {
+            stop_child(wlsunset);
+            *wlsunset = Some(
+                Command::new("wlsunset")
+                    .args(&wlsunset_args(new_temp))
+                    .stdout(Stdio::null())
+                    .stderr(Stdio::null())
+                    .spawn()
+                    .expect("Failed to set new color temperature using wlsunset."),
+            );
+        }
         None => {}
     }
 }
 
+/// The arguments of wlsunset for a fixed temperature, as it needs a higher day temperature.
+fn wlsunset_args(temp: u16) -> [String; 4] {
+    [
+        "-t".to_string(),
+        temp.to_string(),
+        "-T".to_string(),
+        (temp + 1).to_string(),
+    ]
+}
+
 #[inline]
-fn reset_hue(hue_shifter: &Option<HueShifter>) {
+fn reset_hue(hue_shifter: &Option<HueShifter>, wlsunset: &mut Option<Child>) {
     match hue_shifter {
         Some(HueShifter::Redshift) => {
             Command::new("sh")
@@ -261,6 +313,43 @@ fn reset_hue(hue_shifter: &Option<HueShifter>) {
                 .spawn()
                 .expect("Failed to set new color temperature using sct.");
         }
+        Some(HueShifter::Gammastep) => {
+            Command::new("sh")
+                .args(&["-c", "gammastep -x >/dev/null 2>&1"])
+                .spawn()
+                .expect("Failed to set new color temperature using gammastep.");
+        }
+        Some(HueShifter::Wlsunset) => stop_child(wlsunset),
         None => {}
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{stop_child, wlsunset_args};
+    use std::path::Path;
+    use std::process::Command;
+
+    #[test]
+    fn test_wlsunset_args() {
+        assert_eq!(wlsunset_args(4500), ["-t", "4500", "-T", "4501"]);
+    }
+
+    #[test]
+    fn test_stop_child() {
+        let mut started = Some(Command::new("sleep").arg("30").spawn().unwrap());
+        let pid = started.as_ref().unwrap().id();
+        let mut other = Command::new("sleep").arg("30").spawn().unwrap();
+
+        stop_child(&mut started);
+        assert!(started.is_none());
+        assert!(!Path::new(&format!("/proc/{}", pid)).exists());
+        // Instances that the block did not start keep running
+        assert!(other.try_wait().unwrap().is_none());
+        other.kill().unwrap();
+        other.wait().unwrap();
+
+        // Nothing to stop is fine as well
+        stop_child(&mut started);
+    }
+}
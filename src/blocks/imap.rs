@@ -0,0 +1,483 @@
+//! A block for the unread mails in folders of an IMAP server, which it watches with IDLE.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{ChildStdin, Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::subprocess::{ChildHandle, OwnedChild};
+use crate::util::{expand_home, pseudo_uuid, tls_client_args, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+/// How long to wait for an answer of the server.
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// How long to wait before connecting again after an error.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+
+lazy_static! {
+    static ref UNSEEN_REGEX: Regex = Regex::new(r"^\* STATUS .*\(.*\bUNSEEN (\d+)").unwrap();
+}
+
+/// The number of unseen mails in an untagged `STATUS` response.
+fn parse_unseen(line: &str) -> Option<u32> {
+    UNSEEN_REGEX.captures(line)?[1].parse().ok()
+}
+
+/// Whether an untagged response during IDLE means that the mailbox changed.
+fn is_change(line: &str) -> bool {
+    line.starts_with("* ")
+        && line.split_whitespace().nth(2).map_or(false, |kind| {
+            ["EXISTS", "EXPUNGE", "FETCH", "RECENT"].contains(&kind)
+        })
+}
+
+/// Quotes a string for an IMAP command.
+fn quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// The unread mails, or why they could not be counted, or `None` while connecting.
+type Status = Arc<Mutex<Option<std::result::Result<u32, String>>>>;
+
+/// The settings of the connection, which the thread of the block owns.
+#[derive(Clone)]
+struct Account {
+    host: String,
+    port: u16,
+    username: String,
+    password_command: Option<String>,
+    password_file: Option<String>,
+    folders: Vec<String>,
+    idle_timeout: Duration,
+}
+
+impl Account {
+    fn password(&self) -> std::result::Result<String, String> {
+        if let Some(ref command) = self.password_command {
+            let output = Command::new("sh")
+                .args(&["-c", command])
+                .output()
+                .map_err(|e| format!("failed to run the password command: {}", e))?;
+            if !output.status.success() {
+                return Err("the password command failed".to_string());
+            }
+            return Ok(String::from_utf8_lossy(&output.stdout).trim().to_string());
+        }
+        let path = self.password_file.as_deref().unwrap_or_default();
+        let path = expand_home(path);
+        fs::read_to_string(&path)
+            .map(|password| password.trim().to_string())
+            .map_err(|e| format!("failed to read the password from {}: {}", path.display(), e))
+    }
+}
+
+/// A connection to the server over TLS, through `openssl s_client`.
+struct Connection {
+    child: ChildHandle,
+    stdin: ChildStdin,
+    lines: Receiver<String>,
+    tag: u32,
+}
+
+impl Connection {
+    fn open(host: &str, port: u16, child: &ChildHandle) -> std::result::Result<Connection, String> {
+        let pipes = child
+            .spawn(
+                Command::new("openssl")
+                    .args(&tls_client_args(host, port, true))
+                    .stdin(Stdio::piped())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::null()),
+            )
+            .map_err(|e| format!("failed to run openssl: {}", e))?
+            .ok_or("the block is gone")?;
+        let stdin = pipes.stdin.ok_or("failed to open the connection")?;
+        let stdout = pipes.stdout.ok_or("failed to open the connection")?;
+
+        // The lines are read in a thread, so that waiting for them can time out
+        let (sender, lines) = crossbeam_channel::unbounded();
+        thread::Builder::new()
+            .name("imap reader".into())
+            .spawn(move || {
+                for line in BufReader::new(stdout).lines() {
+                    match line {
+                        Ok(line) if sender.send(line).is_ok() => {}
+                        _ => return,
+                    }
+                }
+            })
+            .map_err(|e| format!("failed to start the reading thread: {}", e))?;
+
+        let mut connection = Connection {
+            child: child.clone(),
+            stdin,
+            lines,
+            tag: 0,
+        };
+        let greeting = connection.read_line(TIMEOUT)?;
+        if !greeting.starts_with("* OK") {
+            return Err(format!("unexpected greeting: {}", greeting));
+        }
+        Ok(connection)
+    }
+
+    fn read_line(&self, timeout: Duration) -> std::result::Result<String, String> {
+        match self.lines.recv_timeout(timeout) {
+            Ok(line) => Ok(line.trim_end().to_string()),
+            Err(RecvTimeoutError::Timeout) => Err("the server did not answer".to_string()),
+            Err(RecvTimeoutError::Disconnected) => Err("the connection was closed".to_string()),
+        }
+    }
+
+    fn write_line(&mut self, line: &str) -> std::result::Result<(), String> {
+        write!(self.stdin, "{}\r\n", line)
+            .and_then(|_| self.stdin.flush())
+            .map_err(|_| "the connection was closed".to_string())
+    }
+
+    /// Sends a command, and returns its untagged responses once it completed.
+    fn command(&mut self, command: &str) -> std::result::Result<Vec<String>, String> {
+        self.tag += 1;
+        let tag = format!("a{}", self.tag);
+        self.write_line(&format!("{} {}", tag, command))?;
+        self.finish(&tag)
+    }
+
+    /// Reads the responses of a command until the one with its tag.
+    fn finish(&mut self, tag: &str) -> std::result::Result<Vec<String>, String> {
+        let mut responses = Vec::new();
+        loop {
+            let line = self.read_line(TIMEOUT)?;
+            match line
+                .strip_prefix(tag)
+                .and_then(|rest| rest.strip_prefix(' '))
+            {
+                Some(result) if result.starts_with("OK") => return Ok(responses),
+                Some(result) => return Err(result.to_string()),
+                None => responses.push(line),
+            }
+        }
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        self.child.kill();
+    }
+}
+
+/// Counts the unread mails until the connection fails. `notify` is called with each new
+/// count, and returns false when the block is gone.
+fn watch(
+    account: &Account,
+    child: &ChildHandle,
+    notify: &mut dyn FnMut(std::result::Result<u32, String>) -> bool,
+) -> std::result::Result<(), String> {
+    let mut connection = Connection::open(&account.host, account.port, child)?;
+    connection
+        .command(&format!(
+            "LOGIN {} {}",
+            quote(&account.username),
+            quote(&account.password()?)
+        ))
+        .map_err(|e| format!("login failed: {}", e))?;
+    let idle = connection
+        .command("CAPABILITY")?
+        .iter()
+        .any(|line| line.starts_with("* CAPABILITY") && line.split(' ').any(|c| c == "IDLE"));
+    // IDLE watches the first folder, the others are counted again when it times out
+    connection.command(&format!("EXAMINE {}", quote(&account.folders[0])))?;
+
+    loop {
+        let mut unread = 0;
+        for folder in &account.folders {
+            unread += connection
+                .command(&format!("STATUS {} (UNSEEN)", quote(folder)))?
+                .iter()
+                .find_map(|line| parse_unseen(line))
+                .unwrap_or(0);
+        }
+        if !notify(Ok(unread)) {
+            return Ok(());
+        }
+
+        if !idle {
+            thread::sleep(account.idle_timeout);
+            connection.command("NOOP")?;
+            continue;
+        }
+        connection.tag += 1;
+        let tag = format!("a{}", connection.tag);
+        connection.write_line(&format!("{} IDLE", tag))?;
+        let deadline = Instant::now() + account.idle_timeout;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            match connection.lines.recv_timeout(remaining) {
+                Ok(line) if is_change(&line) => break,
+                Ok(line) if line.starts_with(&tag) => {
+                    return Err(format!("IDLE failed: {}", line.trim_end()))
+                }
+                Ok(_) => {}
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => {
+                    return Err("the connection was closed".to_string())
+                }
+            }
+        }
+        connection.write_line("DONE")?;
+        connection.finish(&tag)?;
+    }
+}
+
+pub struct Imap {
+    id: String,
+    text: TextWidget,
+    format: FormatTemplate,
+    threshold_warning: u32,
+    threshold_critical: u32,
+    status: Status,
+    /// The connection, which is closed with the block
+    #[allow(dead_code)]
+    process: OwnedChild,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ImapConfig {
+    pub host: String,
+
+    #[serde(default = "ImapConfig::default_port")]
+    pub port: u16,
+
+    pub username: String,
+
+    /// Command that prints the password
+    #[serde(default)]
+    pub password_command: Option<String>,
+
+    /// File that contains the password
+    #[serde(default)]
+    pub password_file: Option<String>,
+
+    /// The folders in which to count the unread mails
+    #[serde(default = "ImapConfig::default_folders")]
+    pub folders: Vec<String>,
+
+    /// Interval in seconds after which IDLE is restarted and all folders are counted again
+    #[serde(
+        default = "ImapConfig::default_idle_timeout",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub idle_timeout: Duration,
+
+    /// Format string
+    #[serde(default = "ImapConfig::default_format")]
+    pub format: String,
+
+    #[serde(default = "ImapConfig::default_threshold_warning")]
+    pub threshold_warning: u32,
+
+    #[serde(default = "ImapConfig::default_threshold_critical")]
+    pub threshold_critical: u32,
+
+    #[serde(default = "ImapConfig::default_icon")]
+    pub icon: bool,
+}
+
+impl ImapConfig {
+    fn default_port() -> u16 {
+        993
+    }
+
+    fn default_folders() -> Vec<String> {
+        vec!["INBOX".to_owned()]
+    }
+
+    fn default_idle_timeout() -> Duration {
+        Duration::from_secs(600)
+    }
+
+    fn default_format() -> String {
+        "{unread}".to_owned()
+    }
+
+    fn default_threshold_warning() -> u32 {
+        1
+    }
+
+    fn default_threshold_critical() -> u32 {
+        10
+    }
+
+    fn default_icon() -> bool {
+        true
+    }
+}
+
+impl ConfigBlock for Imap {
+    type Config = ImapConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        if block_config.password_command.is_none() && block_config.password_file.is_none() {
+            return Err(BlockError(
+                "imap".to_string(),
+                "either 'password_command' or 'password_file' must be configured".to_string(),
+            ));
+        }
+        if block_config.folders.is_empty() {
+            return Err(BlockError(
+                "imap".to_string(),
+                "at least one folder must be configured".to_string(),
+            ));
+        }
+        let id = pseudo_uuid();
+        let account = Account {
+            host: block_config.host,
+            port: block_config.port,
+            username: block_config.username,
+            password_command: block_config.password_command,
+            password_file: block_config.password_file,
+            folders: block_config.folders,
+            idle_timeout: block_config.idle_timeout,
+        };
+
+        let status: Status = Arc::new(Mutex::new(None));
+        let thread_status = status.clone();
+        let thread_id = id.clone();
+        let process = OwnedChild::new();
+        let child = process.handle();
+        thread::Builder::new()
+            .name("imap".into())
+            .spawn(move || {
+                let mut notify = |unread: std::result::Result<u32, String>| {
+                    *thread_status
+                        .lock()
+                        .expect("lock has been poisoned in `imap` block") = Some(unread);
+                    send.send(Task {
+                        id: thread_id.clone(),
+                        update_time: Instant::now(),
+                    })
+                    .is_ok()
+                };
+                loop {
+                    match watch(&account, &child, &mut notify) {
+                        // The block is gone
+                        Ok(()) => return,
+                        Err(error) => {
+                            if !notify(Err(error)) {
+                                return;
+                            }
+                        }
+                    }
+                    thread::sleep(RETRY_DELAY);
+                }
+            })
+            .block_error("imap", "failed to start the watching thread")?;
+
+        let text = TextWidget::new(config).with_text("");
+        Ok(Imap {
+            id,
+            text: if block_config.icon {
+                text.with_icon("mail")
+            } else {
+                text
+            },
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("imap", "Invalid format specified")?,
+            threshold_warning: block_config.threshold_warning,
+            threshold_critical: block_config.threshold_critical,
+            status,
+            process,
+        })
+    }
+}
+
+impl Block for Imap {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let status = self
+            .status
+            .lock()
+            .block_error("imap", "failed to acquire lock")?
+            .clone();
+        match status {
+            Some(Ok(unread)) => {
+                let values = map!("{unread}" => unread);
+                self.text.set_text(self.format.render_static_str(&values)?);
+                self.text.set_state(if unread >= self.threshold_critical {
+                    State::Critical
+                } else if unread >= self.threshold_warning {
+                    State::Warning
+                } else {
+                    State::Idle
+                });
+            }
+            // Errors are shown until the thread connects again, instead of stopping the bar
+            Some(Err(_)) | None => {
+                let values = map!("{unread}" => "?");
+                self.text.set_text(self.format.render_static_str(&values)?);
+                self.text.set_state(if status.is_some() {
+                    State::Critical
+                } else {
+                    State::Idle
+                });
+            }
+        }
+
+        // The thread updates the block
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_change, parse_unseen, quote};
+
+    #[test]
+    fn test_parse_unseen() {
+        assert_eq!(parse_unseen("* STATUS INBOX (UNSEEN 3)"), Some(3));
+        assert_eq!(
+            parse_unseen("* STATUS \"Lists/Rust Users\" (MESSAGES 231 UNSEEN 12)"),
+            Some(12)
+        );
+        assert_eq!(
+            parse_unseen("* OK [UNSEEN 4] Message 4 is first unseen"),
+            None
+        );
+    }
+
+    #[test]
+    fn test_is_change() {
+        assert!(is_change("* 23 EXISTS"));
+        assert!(is_change("* 5 EXPUNGE"));
+        assert!(is_change("* 14 FETCH (FLAGS (\\Seen))"));
+        assert!(!is_change("+ idling"));
+        assert!(!is_change("* OK Still here"));
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("INBOX"), "\"INBOX\"");
+        assert_eq!(quote("pa\"ss\\word"), "\"pa\\\"ss\\\\word\"");
+    }
+}
@@ -0,0 +1,479 @@
+//! A block for the number of errors in the systemd journal over a sliding window.
+
+use std::collections::VecDeque;
+use std::ffi::CString;
+use std::os::raw::{c_char, c_int, c_void};
+use std::os::unix::io::RawFd;
+use std::ptr;
+use std::slice;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::reactor;
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+/// Flags of `sd_journal_open`, from `systemd/sd-journal.h`.
+const SD_JOURNAL_LOCAL_ONLY: c_int = 1 << 0;
+const SD_JOURNAL_SYSTEM: c_int = 1 << 2;
+const SD_JOURNAL_CURRENT_USER: c_int = 1 << 3;
+
+#[repr(C)]
+struct SdJournal {
+    _private: [u8; 0],
+}
+
+#[link(name = "systemd")]
+extern "C" {
+    fn sd_journal_open(ret: *mut *mut SdJournal, flags: c_int) -> c_int;
+    fn sd_journal_close(j: *mut SdJournal);
+    fn sd_journal_get_fd(j: *mut SdJournal) -> c_int;
+    fn sd_journal_process(j: *mut SdJournal) -> c_int;
+    fn sd_journal_add_match(j: *mut SdJournal, data: *const c_void, size: usize) -> c_int;
+    fn sd_journal_add_disjunction(j: *mut SdJournal) -> c_int;
+    fn sd_journal_add_conjunction(j: *mut SdJournal) -> c_int;
+    fn sd_journal_seek_realtime_usec(j: *mut SdJournal, usec: u64) -> c_int;
+    fn sd_journal_next(j: *mut SdJournal) -> c_int;
+    fn sd_journal_get_realtime_usec(j: *mut SdJournal, ret: *mut u64) -> c_int;
+    fn sd_journal_get_data(
+        j: *mut SdJournal,
+        field: *const c_char,
+        data: *mut *const c_void,
+        length: *mut usize,
+    ) -> c_int;
+}
+
+/// Turns the negative errno that sd-journal returns on errors into an error.
+fn check(result: c_int, message: &str) -> Result<c_int> {
+    if result < 0 {
+        Err(BlockError(
+            "journal".to_string(),
+            format!("{}: error {}", message, -result),
+        ))
+    } else {
+        Ok(result)
+    }
+}
+
+/// An open journal, which is closed when it is dropped.
+struct JournalReader(*mut SdJournal);
+
+// The journal is only used by one thread at a time, first by the block and then by the reactor
+unsafe impl Send for JournalReader {}
+
+impl Drop for JournalReader {
+    fn drop(&mut self) {
+        unsafe { sd_journal_close(self.0) }
+    }
+}
+
+impl JournalReader {
+    fn open(user: bool) -> Result<Self> {
+        let flags = SD_JOURNAL_LOCAL_ONLY
+            | if user {
+                SD_JOURNAL_CURRENT_USER
+            } else {
+                SD_JOURNAL_SYSTEM
+            };
+        let mut journal = ptr::null_mut();
+        check(
+            unsafe { sd_journal_open(&mut journal, flags) },
+            "failed to open the journal",
+        )?;
+        Ok(JournalReader(journal))
+    }
+
+    /// The file descriptor that is readable when the journal changed. It has to be asked for
+    /// before reading, so that no change is missed.
+    fn fd(&self) -> Result<RawFd> {
+        check(
+            unsafe { sd_journal_get_fd(self.0) },
+            "failed to watch the journal",
+        )
+    }
+
+    /// Picks up the changes of the journal after its file descriptor was readable.
+    fn process(&mut self) -> Result<()> {
+        check(
+            unsafe { sd_journal_process(self.0) },
+            "failed to follow the journal",
+        )?;
+        Ok(())
+    }
+
+    /// Only reads the entries of any of `priorities` and of any of `sources`.
+    fn add_matches(&mut self, priorities: &[String], sources: &[String]) -> Result<()> {
+        for priority in priorities {
+            self.add_match(priority)?;
+        }
+        check(
+            unsafe { sd_journal_add_conjunction(self.0) },
+            "failed to filter the journal",
+        )?;
+        // Matches of different fields would only match together, so each unit and identifier
+        // is an alternative of its own
+        for source in sources {
+            self.add_match(source)?;
+            check(
+                unsafe { sd_journal_add_disjunction(self.0) },
+                "failed to filter the journal",
+            )?;
+        }
+        Ok(())
+    }
+
+    fn add_match(&mut self, field: &str) -> Result<()> {
+        check(
+            unsafe { sd_journal_add_match(self.0, field.as_ptr() as *const c_void, field.len()) },
+            "failed to filter the journal",
+        )?;
+        Ok(())
+    }
+
+    fn seek_realtime(&mut self, micros: u64) -> Result<()> {
+        check(
+            unsafe { sd_journal_seek_realtime_usec(self.0, micros) },
+            "failed to seek in the journal",
+        )?;
+        Ok(())
+    }
+
+    /// Reads the entries after the current one, oldest first.
+    fn read_entries(&mut self) -> Result<Vec<(u64, String)>> {
+        let mut entries = Vec::new();
+        while check(
+            unsafe { sd_journal_next(self.0) },
+            "failed to read the journal",
+        )? > 0
+        {
+            let mut time = 0;
+            if unsafe { sd_journal_get_realtime_usec(self.0, &mut time) } < 0 {
+                continue;
+            }
+            entries.push((
+                time,
+                entry_message(self.field("SYSLOG_IDENTIFIER"), self.field("MESSAGE")),
+            ));
+        }
+        Ok(entries)
+    }
+
+    /// The value of a field of the current entry, which sd-journal returns as `FIELD=value`.
+    fn field(&self, name: &str) -> Option<String> {
+        let c_name = CString::new(name).ok()?;
+        let mut data = ptr::null();
+        let mut length = 0;
+        if unsafe { sd_journal_get_data(self.0, c_name.as_ptr(), &mut data, &mut length) } < 0 {
+            return None;
+        }
+        let data = unsafe { slice::from_raw_parts(data as *const u8, length) };
+        Some(String::from_utf8_lossy(data.get(name.len() + 1..)?).into_owned())
+    }
+}
+
+/// The numeric level of a syslog priority, like `3` for `err`.
+fn priority_level(priority: &str) -> Option<u8> {
+    let names = [
+        "emerg", "alert", "crit", "err", "warning", "notice", "info", "debug",
+    ];
+    names
+        .iter()
+        .position(|name| *name == priority)
+        .map(|level| level as u8)
+        .or_else(|| priority.parse().ok().filter(|level| *level < 8))
+}
+
+/// The journal matches of the entries to count: any priority up to `level`, and any of the
+/// units and identifiers.
+fn journal_matches(
+    level: u8,
+    units: &[String],
+    identifiers: &[String],
+) -> (Vec<String>, Vec<String>) {
+    let priorities = (0..=level).map(|l| format!("PRIORITY={}", l)).collect();
+    let sources = units
+        .iter()
+        .map(|u| format!("_SYSTEMD_UNIT={}", u))
+        .chain(
+            identifiers
+                .iter()
+                .map(|i| format!("SYSLOG_IDENTIFIER={}", i)),
+        )
+        .collect();
+    (priorities, sources)
+}
+
+/// The time of an entry in microseconds since the epoch, like `__REALTIME_TIMESTAMP`.
+fn now_micros() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_micros() as u64)
+        .unwrap_or(0)
+}
+
+/// The message of an entry, after the syslog identifier of its sender.
+fn entry_message(identifier: Option<String>, message: Option<String>) -> String {
+    let message = message.unwrap_or_default();
+    match identifier {
+        Some(ref identifier) if !identifier.is_empty() => format!("{}: {}", identifier, message),
+        _ => message,
+    }
+}
+
+/// The entries of the window, oldest first.
+type Entries = Arc<Mutex<VecDeque<(u64, String)>>>;
+
+pub struct Journal {
+    id: String,
+    output: ButtonWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    window: Duration,
+    warning: usize,
+    critical: usize,
+    entries: Entries,
+    count: usize,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct JournalConfig {
+    /// Update interval in seconds, to let old messages leave the window
+    #[serde(
+        default = "JournalConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// The sliding window to count messages in, in seconds
+    #[serde(
+        default = "JournalConfig::default_window",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub window: Duration,
+
+    /// The least important priority to count, like `err` or `crit`
+    #[serde(default = "JournalConfig::default_priority")]
+    pub priority: String,
+
+    /// Only count the messages of these units
+    #[serde(default)]
+    pub units: Vec<String>,
+
+    /// Only count the messages with these syslog identifiers
+    #[serde(default)]
+    pub identifiers: Vec<String>,
+
+    /// Whether to read the journal of the user instead of the system
+    #[serde(default = "JournalConfig::default_user")]
+    pub user: bool,
+
+    /// Format string
+    #[serde(default = "JournalConfig::default_format")]
+    pub format: String,
+
+    /// Number of messages, where state is set to warning
+    #[serde(default = "JournalConfig::default_warning")]
+    pub warning: usize,
+
+    /// Number of messages, where state is set to critical
+    #[serde(default = "JournalConfig::default_critical")]
+    pub critical: usize,
+}
+
+impl JournalConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn default_window() -> Duration {
+        Duration::from_secs(3600)
+    }
+
+    fn default_priority() -> String {
+        "err".to_owned()
+    }
+
+    fn default_user() -> bool {
+        false
+    }
+
+    fn default_format() -> String {
+        "{count} errors".to_owned()
+    }
+
+    fn default_warning() -> usize {
+        1
+    }
+
+    fn default_critical() -> usize {
+        10
+    }
+}
+
+impl ConfigBlock for Journal {
+    type Config = JournalConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+
+        let level = priority_level(&block_config.priority).block_error(
+            "journal",
+            &format!("unknown priority {}", block_config.priority),
+        )?;
+        let (priorities, sources) =
+            journal_matches(level, &block_config.units, &block_config.identifiers);
+
+        let mut journal = JournalReader::open(block_config.user)?;
+        let fd = journal.fd()?;
+        journal.add_matches(&priorities, &sources)?;
+        // Also counts the messages that are already in the window
+        journal
+            .seek_realtime(now_micros().saturating_sub(block_config.window.as_micros() as u64))?;
+        let entries: Entries = Arc::new(Mutex::new(journal.read_entries()?.into()));
+
+        // The reactor owns the journal from now on, and closes it once the block is gone
+        let thread_entries = Arc::downgrade(&entries);
+        let thread_id = id.clone();
+        reactor::watch(fd, move || {
+            if journal.process().is_err() {
+                return false;
+            }
+            let new_entries = match journal.read_entries() {
+                Ok(new_entries) => new_entries,
+                Err(_) => return false,
+            };
+            if new_entries.is_empty() {
+                return true;
+            }
+            match thread_entries.upgrade() {
+                Some(entries) => match entries.lock() {
+                    Ok(mut entries) => entries.extend(new_entries),
+                    Err(_) => return false,
+                },
+                None => return false,
+            }
+            send.send(Task {
+                id: thread_id.clone(),
+                update_time: Instant::now(),
+            })
+            .is_ok()
+        })?;
+
+        Ok(Journal {
+            output: ButtonWidget::new(config, &id),
+            id,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("journal", "Invalid format specified")?,
+            update_interval: block_config.interval,
+            window: block_config.window,
+            warning: block_config.warning,
+            critical: block_config.critical,
+            entries,
+            count: 0,
+        })
+    }
+}
+
+impl Block for Journal {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let oldest = now_micros().saturating_sub(self.window.as_micros() as u64);
+        let mut entries = self
+            .entries
+            .lock()
+            .block_error("journal", "failed to acquire lock")?;
+        while entries.front().map_or(false, |(time, _)| *time < oldest) {
+            entries.pop_front();
+        }
+        self.count = entries.len();
+
+        let values = map!(
+            "{count}" => self.count.to_string(),
+            "{last}" => entries.back().map(|(_, message)| message.clone()).unwrap_or_default()
+        );
+        drop(entries);
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
+        self.output.set_state(match self.count {
+            x if x >= self.critical => State::Critical,
+            x if x >= self.warning => State::Warning,
+            _ => State::Idle,
+        });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        // Marks the messages as seen
+        if event.matches_name(&self.id) && event.button == MouseButton::Left {
+            self.entries
+                .lock()
+                .block_error("journal", "failed to acquire lock")?
+                .clear();
+            self.update()?;
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{entry_message, journal_matches, priority_level};
+
+    #[test]
+    fn test_priority_level() {
+        assert_eq!(priority_level("err"), Some(3));
+        assert_eq!(priority_level("warning"), Some(4));
+        assert_eq!(priority_level("2"), Some(2));
+        assert_eq!(priority_level("8"), None);
+        assert_eq!(priority_level("error"), None);
+    }
+
+    #[test]
+    fn test_journal_matches() {
+        let (priorities, sources) = journal_matches(
+            2,
+            &["NetworkManager.service".to_string()],
+            &["kernel".to_string()],
+        );
+        assert_eq!(priorities, ["PRIORITY=0", "PRIORITY=1", "PRIORITY=2"]);
+        assert_eq!(
+            sources,
+            [
+                "_SYSTEMD_UNIT=NetworkManager.service",
+                "SYSLOG_IDENTIFIER=kernel"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_entry_message() {
+        assert_eq!(
+            entry_message(
+                Some("kernel".to_string()),
+                Some("nvme0: I/O error".to_string())
+            ),
+            "kernel: nvme0: I/O error"
+        );
+        assert_eq!(entry_message(None, Some("started".to_string())), "started");
+        assert_eq!(entry_message(Some(String::new()), None), "");
+    }
+}
@@ -1,4 +1,5 @@
-use std::process::Command;
+use std::io::{BufRead, BufReader};
+use std::process::{Command, Stdio};
 use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
@@ -19,10 +20,12 @@ use crate::blocks::{Block, ConfigBlock};
 use crate::config::Config;
 use crate::de::deserialize_duration;
 use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
+use crate::subprocess::OwnedChild;
 use crate::util::{pseudo_uuid, FormatTemplate};
 use crate::widget::I3BarWidget;
-use crate::widgets::text::TextWidget;
+use crate::widgets::button::ButtonWidget;
 
 #[derive(Deserialize, Debug, Clone)]
 #[serde(rename_all = "lowercase")]
@@ -31,6 +34,7 @@ pub enum KeyboardLayoutDriver {
     LocaleBus,
     KbddBus,
     Sway,
+    XkbSwitch,
 }
 
 impl Default for KeyboardLayoutDriver {
@@ -53,6 +57,12 @@ pub trait KeyboardLayoutMonitor {
     /// Monitor layout changes and send updates via the `update_request`
     /// channel. By default, this method does nothing.
     fn monitor(&self, _id: String, _update_request: Sender<Task>) {}
+
+    /// Switch to the next keyboard layout. By default, the driver cannot
+    /// switch layouts and a click does nothing.
+    fn next_layout(&self) -> Result<()> {
+        Ok(())
+    }
 }
 
 pub struct SetXkbMap;
@@ -171,13 +181,15 @@ impl KeyboardLayoutMonitor for LocaleBus {
                 loop {
                     // TODO: This actually seems to trigger twice for each localectl
                     // change.
-                    if con.incoming(10_000).next().is_some() {
-                        update_request
+                    if con.incoming(10_000).next().is_some()
+                        && update_request
                             .send(Task {
                                 id: id.clone(),
                                 update_time: Instant::now(),
                             })
-                            .unwrap();
+                            .is_err()
+                    {
+                        return;
                     }
                 }
             })
@@ -288,18 +300,38 @@ impl KeyboardLayoutMonitor for KbdDaemonBus {
                 loop {
                     for ci in c.iter(100_000) {
                         if let dbus::ffidisp::ConnectionItem::Signal(_) = ci {
-                            update_request
+                            if update_request
                                 .send(Task {
                                     id: id.clone(),
                                     update_time: Instant::now(),
                                 })
-                                .unwrap();
+                                .is_err()
+                            {
+                                return;
+                            }
                         }
                     }
                 }
             })
             .unwrap();
     }
+
+    fn next_layout(&self) -> Result<()> {
+        let c = dbus::ffidisp::Connection::get_private(dbus::ffidisp::BusType::Session)
+            .block_error("kbddaemonbus", "can't connect to dbus")?;
+
+        let send_msg = Message::new_method_call(
+            "ru.gentoo.KbddService",
+            "/ru/gentoo/KbddService",
+            "ru.gentoo.kbdd",
+            "next_layout",
+        )
+        .block_error("kbddaemonbus", "Create next-layout message failure")?;
+
+        c.send_with_reply_and_block(send_msg, 5000)
+            .block_error("kbddaemonbus", "Is kbdd running?")?;
+        Ok(())
+    }
 }
 
 struct KbddMessageHandler(Arc<Mutex<u32>>);
@@ -325,6 +357,7 @@ impl dbus::ffidisp::MsgHandler for KbddMessageHandler {
 }
 
 pub struct Sway {
+    sway_kb_identifier: String,
     sway_kb_layout: Arc<Mutex<String>>,
 }
 
@@ -355,6 +388,7 @@ impl Sway {
         };
 
         Ok(Sway {
+            sway_kb_identifier,
             sway_kb_layout: Arc::new(Mutex::new(layout)),
         })
     }
@@ -394,24 +428,30 @@ impl KeyboardLayoutMonitor for Sway {
                                     let mut layout = arc.lock().unwrap();
                                     *layout = name;
                                 }
-                                update_request
+                                if update_request
                                     .send(Task {
                                         id: id.clone(),
                                         update_time: Instant::now(),
                                     })
-                                    .unwrap();
+                                    .is_err()
+                                {
+                                    return;
+                                }
                             }
                             InputChange::XkbKeymap => {
                                 if let Some(name) = e.input.xkb_active_layout_name {
                                     let mut layout = arc.lock().unwrap();
                                     *layout = name;
                                 }
-                                update_request
+                                if update_request
                                     .send(Task {
                                         id: id.clone(),
                                         update_time: Instant::now(),
                                     })
-                                    .unwrap();
+                                    .is_err()
+                                {
+                                    return;
+                                }
                             }
                             _ => {}
                         },
@@ -421,6 +461,117 @@ impl KeyboardLayoutMonitor for Sway {
             })
             .unwrap();
     }
+
+    fn next_layout(&self) -> Result<()> {
+        let input = if self.sway_kb_identifier.is_empty() {
+            "type:keyboard"
+        } else {
+            self.sway_kb_identifier.as_str()
+        };
+        let outcomes = Connection::new()
+            .block_error("sway", "Failed to connect to sway.")?
+            .run_command(format!("input {} xkb_switch_layout next", input))
+            .block_error("sway", "Failed to switch the layout.")?;
+        if outcomes.iter().any(|outcome| outcome.is_err()) {
+            return Err(BlockError(
+                "sway".to_string(),
+                "Failed to switch the layout.".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Uses [xkb-switch](https://github.com/grwlf/xkb-switch), which talks to xkb directly and
+/// waits for layout changes.
+pub struct XkbSwitch {
+    layout: Arc<Mutex<String>>,
+    /// `xkb-switch -W`, which is killed with the block
+    process: OwnedChild,
+}
+
+impl XkbSwitch {
+    pub fn new() -> Result<Self> {
+        let output = Command::new("xkb-switch").arg("-p").output().block_error(
+            "xkb-switch",
+            "Failed to execute xkb-switch. Is it installed?",
+        )?;
+        let layout = String::from_utf8(output.stdout)
+            .block_error("xkb-switch", "Non-UTF8 input.")?
+            .trim()
+            .to_string();
+
+        Ok(XkbSwitch {
+            layout: Arc::new(Mutex::new(layout)),
+            process: OwnedChild::new(),
+        })
+    }
+}
+
+impl KeyboardLayoutMonitor for XkbSwitch {
+    fn keyboard_layout(&self) -> Result<String> {
+        let layout = self.layout.lock().unwrap();
+        Ok(layout.split('(').next().unwrap_or_default().to_string())
+    }
+
+    fn keyboard_variant(&self) -> Result<String> {
+        // xkb-switch prints the variant in parentheses after the layout, like "us(intl)"
+        let layout = self.layout.lock().unwrap();
+        Ok(layout
+            .split('(')
+            .nth(1)
+            .map_or("N/A", |variant| variant.trim_end_matches(')'))
+            .to_string())
+    }
+
+    fn must_poll(&self) -> bool {
+        false
+    }
+
+    /// Monitor layout changes in a separate thread and send updates
+    /// via the `update_request` channel.
+    fn monitor(&self, id: String, update_request: Sender<Task>) {
+        let arc = Arc::clone(&self.layout);
+        let child = self.process.handle();
+        thread::Builder::new()
+            .name("keyboard_layout".into())
+            .spawn(move || {
+                // With -W, xkb-switch prints the new layout each time it changes
+                let stdout = match child
+                    .spawn(Command::new("xkb-switch").arg("-W").stdout(Stdio::piped()))
+                    .expect("Failed to execute xkb-switch.")
+                {
+                    Some(pipes) => pipes.stdout.unwrap(),
+                    // The block is gone
+                    None => return,
+                };
+                for line in BufReader::new(stdout).lines() {
+                    let name = match line {
+                        Ok(name) => name,
+                        Err(_) => break,
+                    };
+                    *arc.lock().unwrap() = name.trim().to_string();
+                    if update_request
+                        .send(Task {
+                            id: id.clone(),
+                            update_time: Instant::now(),
+                        })
+                        .is_err()
+                    {
+                        return;
+                    }
+                }
+            })
+            .unwrap();
+    }
+
+    fn next_layout(&self) -> Result<()> {
+        Command::new("xkb-switch")
+            .arg("-n")
+            .output()
+            .block_error("xkb-switch", "Failed to execute xkb-switch.")?;
+        Ok(())
+    }
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -451,7 +602,7 @@ impl KeyboardLayoutConfig {
 
 pub struct KeyboardLayout {
     id: String,
-    output: TextWidget,
+    output: ButtonWidget,
     monitor: Box<dyn KeyboardLayoutMonitor>,
     update_interval: Option<Duration>,
     format: FormatTemplate,
@@ -479,6 +630,11 @@ impl ConfigBlock for KeyboardLayout {
                 monitor.monitor(id.clone(), send);
                 Box::new(monitor)
             }
+            KeyboardLayoutDriver::XkbSwitch => {
+                let monitor = XkbSwitch::new()?;
+                monitor.monitor(id.clone(), send);
+                Box::new(monitor)
+            }
         };
         let update_interval = if monitor.must_poll() {
             Some(block_config.interval)
@@ -486,8 +642,8 @@ impl ConfigBlock for KeyboardLayout {
             None
         };
         Ok(KeyboardLayout {
+            output: ButtonWidget::new(config, &id),
             id,
-            output: TextWidget::new(config),
             monitor,
             update_interval,
             format: FormatTemplate::from_string(&block_config.format).block_error(
@@ -519,4 +675,16 @@ impl Block for KeyboardLayout {
     fn view(&self) -> Vec<&dyn I3BarWidget> {
         vec![&self.output]
     }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        // The drivers that can switch layouts see the change and update the block
+        if event.matches_name(&self.id) && event.button == MouseButton::Left {
+            self.monitor.next_layout()?;
+        }
+        Ok(())
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left
+    }
 }
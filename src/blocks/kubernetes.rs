@@ -0,0 +1,314 @@
+//! A block for the health of a Kubernetes cluster, from `kubectl`.
+
+use std::process::Command;
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+/// The number of items in a list, like `kubectl get nodes -o json`.
+fn count(list: &Value) -> usize {
+    list["items"].as_array().map_or(0, |items| items.len())
+}
+
+/// Counts the nodes whose `Ready` condition is not true.
+fn count_not_ready_nodes(nodes: &Value) -> usize {
+    let empty = Vec::new();
+    nodes["items"]
+        .as_array()
+        .unwrap_or(&empty)
+        .iter()
+        .filter(|node| {
+            let conditions = node["status"]["conditions"].as_array().unwrap_or(&empty);
+            !conditions
+                .iter()
+                .any(|c| c["type"] == "Ready" && c["status"] == "True")
+        })
+        .count()
+}
+
+/// Counts the pods that are pending or failed, or running with a container that is not
+/// ready, like one in `CrashLoopBackOff`. Completed pods of jobs are fine.
+fn count_failing_pods(pods: &Value) -> usize {
+    let empty = Vec::new();
+    pods["items"]
+        .as_array()
+        .unwrap_or(&empty)
+        .iter()
+        .filter(|pod| match pod["status"]["phase"].as_str() {
+            Some("Succeeded") => false,
+            Some("Running") => pod["status"]["containerStatuses"]
+                .as_array()
+                .unwrap_or(&empty)
+                .iter()
+                .any(|container| container["ready"] != true),
+            _ => true,
+        })
+        .count()
+}
+
+/// The health of the cluster.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Cluster {
+    context: String,
+    nodes: usize,
+    not_ready: usize,
+    pods: usize,
+    failing: usize,
+}
+
+/// Runs kubectl, which finds the kubeconfig through `KUBECONFIG` like in a terminal.
+fn kubectl(context: Option<&str>, args: &[&str]) -> Result<String> {
+    let mut command = Command::new("kubectl");
+    command.arg("--request-timeout=5s");
+    if let Some(context) = context {
+        command.args(&["--context", context]);
+    }
+    let output = command
+        .args(args)
+        .output()
+        .block_error("kubernetes", "failed to run kubectl")?;
+    if !output.status.success() {
+        return Err(BlockError(
+            "kubernetes".to_string(),
+            String::from_utf8_lossy(&output.stderr).trim().to_string(),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+fn get(context: Option<&str>, args: &[&str]) -> Result<Value> {
+    let mut args = args.to_vec();
+    args.extend(&["--output", "json"]);
+    serde_json::from_str(&kubectl(context, &args)?)
+        .block_error("kubernetes", "failed to parse the output of kubectl")
+}
+
+fn fetch(context: Option<&str>, namespaces: &[String]) -> Result<Cluster> {
+    let context = match context {
+        Some(context) => context.to_string(),
+        None => kubectl(None, &["config", "current-context"])?
+            .trim()
+            .to_string(),
+    };
+
+    let nodes = get(Some(&context), &["get", "nodes"])?;
+    let mut pods = 0;
+    let mut failing = 0;
+    if namespaces.is_empty() {
+        let list = get(Some(&context), &["get", "pods", "--all-namespaces"])?;
+        pods += count(&list);
+        failing += count_failing_pods(&list);
+    }
+    for namespace in namespaces {
+        let list = get(Some(&context), &["get", "pods", "--namespace", namespace])?;
+        pods += count(&list);
+        failing += count_failing_pods(&list);
+    }
+    Ok(Cluster {
+        nodes: count(&nodes),
+        not_ready: count_not_ready_nodes(&nodes),
+        context,
+        pods,
+        failing,
+    })
+}
+
+/// The last health of the cluster, or the error of the last run of kubectl.
+type Shared = Mutex<Option<std::result::Result<Cluster, String>>>;
+
+/// Runs kubectl every `interval` until the block is gone, which drops the health.
+fn poll(
+    context: Option<&str>,
+    namespaces: &[String],
+    interval: Duration,
+    cluster: Weak<Shared>,
+    notify: &dyn Fn() -> bool,
+) {
+    loop {
+        // kubectl can take seconds to reach a cluster, so it does not run on the main thread
+        let result = fetch(context, namespaces).map_err(|e| e.to_string());
+        match cluster.upgrade() {
+            Some(shared) => match shared.lock() {
+                Ok(mut cluster) => *cluster = Some(result),
+                Err(_) => return,
+            },
+            None => return,
+        }
+        if !notify() {
+            return;
+        }
+        thread::sleep(interval);
+    }
+}
+
+pub struct Kubernetes {
+    id: String,
+    text: TextWidget,
+    format: FormatTemplate,
+    cluster: Arc<Shared>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct KubernetesConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "KubernetesConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// The context of the kubeconfig to use, the current context if not set
+    #[serde(default)]
+    pub context: Option<String>,
+
+    /// The namespaces to count the pods of, all namespaces if empty
+    #[serde(default)]
+    pub namespaces: Vec<String>,
+
+    /// Format string
+    #[serde(default = "KubernetesConfig::default_format")]
+    pub format: String,
+}
+
+impl KubernetesConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(30)
+    }
+
+    fn default_format() -> String {
+        "{context}".to_owned()
+    }
+}
+
+impl ConfigBlock for Kubernetes {
+    type Config = KubernetesConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+        let cluster = Arc::new(Mutex::new(None));
+        // The thread only holds on to the health while it updates it, so that it stops once
+        // a reload of the configuration dropped the block
+        let thread_cluster = Arc::downgrade(&cluster);
+        let thread_id = id.clone();
+        let interval = block_config.interval;
+        let context = block_config.context;
+        let namespaces = block_config.namespaces;
+        thread::Builder::new()
+            .name("kubernetes".into())
+            .spawn(move || {
+                let notify = || {
+                    send.send(Task {
+                        id: thread_id.clone(),
+                        update_time: Instant::now(),
+                    })
+                    .is_ok()
+                };
+                poll(
+                    context.as_deref(),
+                    &namespaces,
+                    interval,
+                    thread_cluster,
+                    &notify,
+                );
+            })
+            .block_error("kubernetes", "failed to start the polling thread")?;
+
+        Ok(Kubernetes {
+            id,
+            text: TextWidget::new(config).with_icon("cogs"),
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("kubernetes", "Invalid format specified")?,
+            cluster,
+        })
+    }
+}
+
+impl Block for Kubernetes {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let cluster = self
+            .cluster
+            .lock()
+            .block_error("kubernetes", "failed to acquire lock")?
+            .clone();
+        let cluster = match cluster {
+            Some(Ok(cluster)) => cluster,
+            Some(Err(error)) => return Err(BlockError("kubernetes".to_string(), error)),
+            // kubectl has not answered yet
+            None => return Ok(None),
+        };
+
+        let values = map!(
+            "{context}" => cluster.context,
+            "{nodes}" => cluster.nodes.to_string(),
+            "{not_ready}" => cluster.not_ready.to_string(),
+            "{pods}" => cluster.pods.to_string(),
+            "{failing}" => cluster.failing.to_string()
+        );
+        self.text.set_text(self.format.render_static_str(&values)?);
+        self.text.set_state(if cluster.not_ready > 0 {
+            State::Critical
+        } else if cluster.failing > 0 {
+            State::Warning
+        } else {
+            State::Idle
+        });
+
+        // The thread updates the block
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_failing_pods, count_not_ready_nodes};
+
+    #[test]
+    fn test_count_not_ready_nodes() {
+        let nodes = serde_json::json!({"items": [
+            {"status": {"conditions": [
+                {"type": "MemoryPressure", "status": "False"},
+                {"type": "Ready", "status": "True"}
+            ]}},
+            {"status": {"conditions": [{"type": "Ready", "status": "Unknown"}]}},
+            {"status": {}}
+        ]});
+        assert_eq!(count_not_ready_nodes(&nodes), 2);
+    }
+
+    #[test]
+    fn test_count_failing_pods() {
+        let pods = serde_json::json!({"items": [
+            {"status": {"phase": "Running", "containerStatuses": [{"ready": true}]}},
+            {"status": {"phase": "Running", "containerStatuses": [
+                {"ready": true},
+                {"ready": false, "state": {"waiting": {"reason": "CrashLoopBackOff"}}}
+            ]}},
+            {"status": {"phase": "Succeeded", "containerStatuses": [{"ready": false}]}},
+            {"status": {"phase": "Pending"}},
+            {"status": {"phase": "Failed"}}
+        ]});
+        assert_eq!(count_failing_pods(&pods), 3);
+    }
+}
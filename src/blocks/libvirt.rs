@@ -0,0 +1,187 @@
+//! A block for the virtual machines of libvirt, which can also start and stop one of them.
+
+use std::process::Command;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+/// The domains in the output of `virsh list --name`, which ends with an empty line.
+fn parse_names(output: &str) -> Vec<&str> {
+    output
+        .lines()
+        .map(|line| line.trim())
+        .filter(|line| !line.is_empty())
+        .collect()
+}
+
+pub struct Libvirt {
+    id: String,
+    output: ButtonWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    uri: String,
+    domain: Option<String>,
+    /// The state of the domain, like `running` or `shut off`
+    domain_state: String,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LibvirtConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "LibvirtConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// The connection to libvirt
+    #[serde(default = "LibvirtConfig::default_uri")]
+    pub uri: String,
+
+    /// A domain to show the state of, which a click starts and stops
+    #[serde(default)]
+    pub domain: Option<String>,
+
+    /// Format override
+    #[serde(default)]
+    pub format: Option<String>,
+}
+
+impl LibvirtConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn default_uri() -> String {
+        "qemu:///system".to_owned()
+    }
+}
+
+impl ConfigBlock for Libvirt {
+    type Config = LibvirtConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        let format = block_config.format.unwrap_or_else(|| {
+            if block_config.domain.is_some() {
+                "{domain} {state}".to_owned()
+            } else {
+                "{running}".to_owned()
+            }
+        });
+        let id = pseudo_uuid();
+        Ok(Libvirt {
+            output: ButtonWidget::new(config, &id).with_icon("cogs"),
+            id,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&format)
+                .block_error("libvirt", "Invalid format specified")?,
+            uri: block_config.uri,
+            domain: block_config.domain,
+            domain_state: String::new(),
+        })
+    }
+}
+
+impl Libvirt {
+    fn virsh(&self, args: &[&str]) -> Result<String> {
+        let output = Command::new("virsh")
+            .env("LC_ALL", "C")
+            .args(&["--connect", &self.uri])
+            .args(args)
+            .output()
+            .block_error("libvirt", "failed to run virsh")?;
+        if !output.status.success() {
+            return Err(BlockError(
+                "libvirt".to_string(),
+                String::from_utf8_lossy(&output.stderr).trim().to_string(),
+            ));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+}
+
+impl Block for Libvirt {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let running = parse_names(&self.virsh(&["list", "--name"])?).len();
+        let total = parse_names(&self.virsh(&["list", "--all", "--name"])?).len();
+
+        let mut values = map!(
+            "{running}" => running.to_string(),
+            "{total}" => total.to_string()
+        );
+        if let Some(ref domain) = self.domain {
+            self.domain_state = self.virsh(&["domstate", domain])?.trim().to_string();
+            self.output.set_state(match self.domain_state.as_str() {
+                "running" => State::Good,
+                "paused" | "pmsuspended" => State::Warning,
+                "crashed" => State::Critical,
+                "in shutdown" | "blocked" => State::Info,
+                // shut off and idle
+                _ => State::Idle,
+            });
+            values.insert("{domain}", domain.clone());
+            values.insert("{state}", self.domain_state.clone());
+        }
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if !event.matches_name(&self.id) || event.button != MouseButton::Left {
+            return Ok(());
+        }
+        let domain = match self.domain {
+            Some(ref domain) => domain,
+            None => return Ok(()),
+        };
+        // Shutting down asks the guest, so it can take a while until the state changes
+        let action = match self.domain_state.as_str() {
+            "running" | "blocked" => "shutdown",
+            "paused" => "resume",
+            _ => "start",
+        };
+        self.virsh(&[action, domain])?;
+        self.update()?;
+        Ok(())
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left && self.domain.is_some()
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_names;
+
+    #[test]
+    fn test_parse_names() {
+        assert_eq!(
+            parse_names("win11\nubuntu-server\n\n"),
+            vec!["win11", "ubuntu-server"]
+        );
+        assert!(parse_names("\n").is_empty());
+    }
+}
@@ -23,6 +23,7 @@ pub struct Load {
     minimum_info: f32,
     minimum_warning: f32,
     minimum_critical: f32,
+    normalize: bool,
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -47,6 +48,10 @@ pub struct LoadConfig {
     /// Minimum load, where state is set to critical
     #[serde(default = "LoadConfig::default_critical")]
     pub critical: f32,
+
+    /// Whether the thresholds are the load per logical core
+    #[serde(default = "LoadConfig::default_normalize")]
+    pub normalize: bool,
 }
 
 impl LoadConfig {
@@ -69,6 +74,10 @@ impl LoadConfig {
     fn default_critical() -> f32 {
         0.9
     }
+
+    fn default_normalize() -> bool {
+        true
+    }
 }
 
 impl ConfigBlock for Load {
@@ -89,7 +98,8 @@ impl ConfigBlock for Load {
         let logical_cores = content
             .lines()
             .filter(|l| l.starts_with("processor"))
-            .count() as u32;
+            .count()
+            .max(1) as u32;
 
         Ok(Load {
             id: pseudo_uuid(),
@@ -98,6 +108,7 @@ impl ConfigBlock for Load {
             minimum_info: block_config.info,
             minimum_warning: block_config.warning,
             minimum_critical: block_config.critical,
+            normalize: block_config.normalize,
             format: FormatTemplate::from_string(&block_config.format)
                 .block_error("load", "Invalid format specified for load")?,
             text,
@@ -120,16 +131,18 @@ impl Block for Load {
 
         let split: Vec<&str> = (&loadavg).split(' ').collect();
 
-        let values = map!("{1m}" => split[0],
-                          "{5m}" => split[1],
-                          "{15m}" => split[2]);
-
-        let used_perc = values["{1m}"]
+        let load = split[0]
             .parse::<f32>()
-            .block_error("load", "failed to parse float percentage")?
-            / self.logical_cores as f32;
+            .block_error("load", "failed to parse float percentage")?;
+        let per_core = load / self.logical_cores as f32;
+
+        let values = map!("{1m}" => split[0].to_string(),
+                          "{5m}" => split[1].to_string(),
+                          "{15m}" => split[2].to_string(),
+                          "{per_core}" => format!("{:.2}", per_core));
 
-        self.text.set_state(match used_perc {
+        let compared = if self.normalize { per_core } else { load };
+        self.text.set_state(match compared {
             x if x > self.minimum_critical => State::Critical,
             x if x > self.minimum_warning => State::Warning,
             x if x > self.minimum_info => State::Info,
@@ -0,0 +1,249 @@
+//! A block for the state of Caps Lock, Num Lock and Scroll Lock.
+
+use std::fs::read_dir;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, read_file, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+/// Which lock key a LED stands for, from its name like `input3::capslock`.
+fn led_key(name: &str) -> Option<&str> {
+    let key = name.rsplit("::").next()?;
+    match key {
+        "capslock" | "numlock" | "scrolllock" if key != name => Some(key),
+        _ => None,
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+struct LockState {
+    caps: bool,
+    num: bool,
+    scroll: bool,
+}
+
+impl LockState {
+    /// Reads the LEDs of all keyboards, a key is on if any of its LEDs is.
+    fn read(leds: &[(String, PathBuf)]) -> Self {
+        let mut state = LockState::default();
+        for (key, path) in leds {
+            let on = read_file("lock_keys", path).map_or(false, |brightness| brightness != "0");
+            match key.as_str() {
+                "capslock" => state.caps |= on,
+                "numlock" => state.num |= on,
+                _ => state.scroll |= on,
+            }
+        }
+        state
+    }
+}
+
+/// Finds the brightness files of the lock key LEDs of all keyboards.
+fn find_leds() -> Vec<(String, PathBuf)> {
+    let mut leds = Vec::new();
+    if let Ok(entries) = read_dir("/sys/class/leds") {
+        for entry in entries.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if let Some(key) = led_key(&name) {
+                leds.push((key.to_string(), entry.path().join("brightness")));
+            }
+        }
+    }
+    leds
+}
+
+/// Checks the LEDs every `interval` until the block is gone, and updates the block when a key
+/// changed. Keyboards plugged in later show up in `/sys/class/leds` as well.
+fn watch_leds(state: Weak<Mutex<LockState>>, interval: Duration, id: String, send: Sender<Task>) {
+    loop {
+        thread::sleep(interval);
+        let current = LockState::read(&find_leds());
+        let shared = match state.upgrade() {
+            Some(shared) => shared,
+            None => return,
+        };
+        let mut last = match shared.lock() {
+            Ok(last) => last,
+            Err(_) => return,
+        };
+        if *last == current {
+            continue;
+        }
+        *last = current;
+        drop(last);
+        let task = Task {
+            id: id.clone(),
+            update_time: Instant::now(),
+        };
+        if send.send(task).is_err() {
+            return;
+        }
+    }
+}
+
+pub struct LockKeys {
+    id: String,
+    output: TextWidget,
+    format: FormatTemplate,
+    /// The state that the thread saw last
+    state: Arc<Mutex<LockState>>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct LockKeysConfig {
+    /// How often the LEDs are checked, in seconds
+    #[serde(
+        default = "LockKeysConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format string
+    #[serde(default = "LockKeysConfig::default_format")]
+    pub format: String,
+}
+
+impl LockKeysConfig {
+    fn default_interval() -> Duration {
+        Duration::from_millis(100)
+    }
+
+    fn default_format() -> String {
+        "{caps} {num}".to_owned()
+    }
+}
+
+impl ConfigBlock for LockKeys {
+    type Config = LockKeysConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+        let leds = find_leds();
+        if leds.is_empty() {
+            return Err(BlockError(
+                "lock_keys".to_string(),
+                "No keyboard LEDs found in /sys/class/leds".to_string(),
+            ));
+        }
+
+        // The kernel does not announce LED changes, but reading a few sysfs files is cheap, and
+        // the block is only updated when a key changes
+        let state = Arc::new(Mutex::new(LockState::read(&leds)));
+        let watched = Arc::downgrade(&state);
+        let watcher_id = id.clone();
+        let interval = block_config.interval;
+        thread::Builder::new()
+            .name("lock_keys".into())
+            .spawn(move || watch_leds(watched, interval, watcher_id, send))
+            .block_error("lock_keys", "failed to start the watching thread")?;
+
+        Ok(LockKeys {
+            output: TextWidget::new(config).with_icon("keyboard"),
+            id,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("lock_keys", "Invalid format specified")?,
+            state,
+        })
+    }
+}
+
+impl Block for LockKeys {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let state = *self
+            .state
+            .lock()
+            .block_error("lock_keys", "failed to acquire lock")?;
+
+        let on = [
+            (state.caps, "CAPS"),
+            (state.num, "NUM"),
+            (state.scroll, "SCRL"),
+        ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .map(|(_, key)| *key)
+        .collect::<Vec<_>>()
+        .join(" ");
+        let values = map!(
+            "{caps}" => if state.caps { "CAPS" } else { "caps" },
+            "{num}" => if state.num { "NUM" } else { "num" },
+            "{scroll}" => if state.scroll { "SCRL" } else { "scrl" },
+            "{on}" => on.as_str()
+        );
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
+        self.output.set_state(if state.caps {
+            State::Warning
+        } else {
+            State::Idle
+        });
+
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{led_key, LockState};
+    use assert_fs::prelude::{FileWriteStr, PathChild};
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_led_key() {
+        assert_eq!(led_key("input3::capslock"), Some("capslock"));
+        assert_eq!(led_key("input12::numlock"), Some("numlock"));
+        assert_eq!(led_key("input3::kana"), None);
+        assert_eq!(led_key("capslock"), None);
+        assert_eq!(led_key("tpacpi::power"), None);
+    }
+
+    #[test]
+    fn test_read_leds() {
+        let dir = TempDir::new().unwrap();
+        let led = |name: &str, brightness: &str| {
+            let file = dir.child(name);
+            file.write_str(brightness).unwrap();
+            (
+                name.rsplit("::").next().unwrap().to_string(),
+                file.path().to_path_buf(),
+            )
+        };
+        // A key is on if the LED of any keyboard is
+        let leds = vec![
+            led("input3::capslock", "0\n"),
+            led("input7::capslock", "1\n"),
+            led("input3::numlock", "0\n"),
+            led("input3::scrolllock", "0\n"),
+        ];
+        assert_eq!(
+            LockState::read(&leds),
+            LockState {
+                caps: true,
+                num: false,
+                scroll: false,
+            }
+        );
+        assert_eq!(LockState::read(&[]), LockState::default());
+    }
+}
@@ -9,11 +9,11 @@ use crate::blocks::{Block, ConfigBlock};
 use crate::config::Config;
 use crate::de::deserialize_duration;
 use crate::errors::*;
-use crate::input::I3BarEvent;
+use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::util::pseudo_uuid;
 use crate::widget::{I3BarWidget, State};
-use crate::widgets::text::TextWidget;
+use crate::widgets::button::ButtonWidget;
 
 #[derive(Clone, Debug, Deserialize)]
 #[serde(rename_all = "lowercase")]
@@ -39,14 +39,66 @@ impl Default for MailType {
     }
 }
 
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MailBackend {
+    /// Counts the files in the maildirs
+    Files,
+    /// Counts the messages that match notmuch queries
+    Notmuch,
+}
+
+impl Default for MailBackend {
+    fn default() -> MailBackend {
+        MailBackend::Files
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MaildirQuery {
+    pub name: String,
+    /// A notmuch query, like `tag:unread and tag:inbox`
+    pub query: String,
+}
+
+#[cfg(feature = "notmuch")]
+fn count_query(database: &str, query: &str) -> Result<usize> {
+    let count = notmuch::Database::open(&database, notmuch::DatabaseMode::ReadOnly)
+        .and_then(|db| db.create_query(query)?.count_messages())
+        .map_err(|e| BlockError("maildir".to_string(), e.to_string()))?;
+    Ok(count as usize)
+}
+
+#[cfg(not(feature = "notmuch"))]
+fn count_query(_database: &str, _query: &str) -> Result<usize> {
+    Err(BlockError(
+        "maildir".to_string(),
+        "the notmuch backend needs i3status-rs to be built with the notmuch feature".to_string(),
+    ))
+}
+
+/// The text of the block, which names the query that was counted.
+fn mail_text(name: Option<&str>, count: usize) -> String {
+    match name {
+        Some(name) => format!("{}:{}", name, count),
+        None => format!("{}", count),
+    }
+}
+
 pub struct Maildir {
-    text: TextWidget,
+    text: ButtonWidget,
     id: String,
     update_interval: Duration,
     inboxes: Vec<String>,
     threshold_warning: usize,
     threshold_critical: usize,
     display_type: MailType,
+    backend: MailBackend,
+    database: String,
+    queries: Vec<MaildirQuery>,
+    /// The index of the query that is shown
+    current: usize,
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -58,6 +110,7 @@ pub struct MaildirConfig {
         deserialize_with = "deserialize_duration"
     )]
     pub interval: Duration,
+    #[serde(default)]
     pub inboxes: Vec<String>,
     #[serde(default = "MaildirConfig::default_threshold_warning")]
     pub threshold_warning: usize,
@@ -67,6 +120,14 @@ pub struct MaildirConfig {
     pub display_type: MailType,
     #[serde(default = "MaildirConfig::default_icon")]
     pub icon: bool,
+    #[serde(default)]
+    pub backend: MailBackend,
+    /// Path to the notmuch database
+    #[serde(default = "MaildirConfig::default_database")]
+    pub database: String,
+    /// The notmuch queries, which a click cycles through
+    #[serde(default)]
+    pub queries: Vec<MaildirQuery>,
 }
 
 impl MaildirConfig {
@@ -82,6 +143,9 @@ impl MaildirConfig {
     fn default_icon() -> bool {
         true
     }
+    fn default_database() -> String {
+        format!("{}/.mail", std::env::var("HOME").unwrap_or_default())
+    }
 }
 
 impl ConfigBlock for Maildir {
@@ -92,9 +156,25 @@ impl ConfigBlock for Maildir {
         config: Config,
         _tx_update_request: Sender<Task>,
     ) -> Result<Self> {
-        let widget = TextWidget::new(config).with_text("");
+        match block_config.backend {
+            MailBackend::Files if block_config.inboxes.is_empty() => {
+                return Err(BlockError(
+                    "maildir".to_string(),
+                    "at least one inbox must be configured".to_string(),
+                ))
+            }
+            MailBackend::Notmuch if block_config.queries.is_empty() => {
+                return Err(BlockError(
+                    "maildir".to_string(),
+                    "at least one query must be configured".to_string(),
+                ))
+            }
+            _ => {}
+        }
+        let id = pseudo_uuid();
+        let widget = ButtonWidget::new(config, &id).with_text("");
         Ok(Maildir {
-            id: pseudo_uuid(),
+            id,
             update_interval: block_config.interval,
             text: if block_config.icon {
                 widget.with_icon("mail")
@@ -105,18 +185,41 @@ impl ConfigBlock for Maildir {
             threshold_warning: block_config.threshold_warning,
             threshold_critical: block_config.threshold_critical,
             display_type: block_config.display_type,
+            backend: block_config.backend,
+            database: block_config.database,
+            queries: block_config.queries,
+            current: 0,
         })
     }
 }
 
+impl Maildir {
+    /// The notmuch query that is shown, or `None` if the maildirs are counted.
+    fn query(&self) -> Option<&MaildirQuery> {
+        match self.backend {
+            MailBackend::Files => None,
+            MailBackend::Notmuch => self.queries.get(self.current),
+        }
+    }
+}
+
 impl Block for Maildir {
     fn update(&mut self) -> Result<Option<Update>> {
         let mut newmails = 0;
-        for inbox in &self.inboxes {
-            let isl: &str = &inbox[..];
-            let maildir = ExtMaildir::from(isl);
-            newmails += self.display_type.count_mail(&maildir)
-        }
+        let name = match self.query() {
+            Some(query) => {
+                newmails = count_query(&self.database, &query.query)?;
+                Some(query.name.clone())
+            }
+            None => {
+                for inbox in &self.inboxes {
+                    let isl: &str = &inbox[..];
+                    let maildir = ExtMaildir::from(isl);
+                    newmails += self.display_type.count_mail(&maildir)
+                }
+                None
+            }
+        };
         let mut state = State::Idle;
         if newmails >= self.threshold_critical {
             state = State::Critical;
@@ -124,7 +227,7 @@ impl Block for Maildir {
             state = State::Warning;
         }
         self.text.set_state(state);
-        self.text.set_text(format!("{}", newmails));
+        self.text.set_text(mail_text(name.as_deref(), newmails));
         Ok(Some(self.update_interval.into()))
     }
 
@@ -132,11 +235,97 @@ impl Block for Maildir {
         vec![&self.text]
     }
 
-    fn click(&mut self, _: &I3BarEvent) -> Result<()> {
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.matches_name(&self.id)
+            && event.button == MouseButton::Left
+            && self.backend == MailBackend::Notmuch
+        {
+            self.current = (self.current + 1) % self.queries.len();
+            self.update()?;
+        }
         Ok(())
     }
 
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left && self.backend == MailBackend::Notmuch
+    }
+
     fn id(&self) -> &str {
         &self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn query(name: &str, query: &str) -> MaildirQuery {
+        MaildirQuery {
+            name: name.to_string(),
+            query: query.to_string(),
+        }
+    }
+
+    fn left_click(id: &str) -> I3BarEvent {
+        I3BarEvent {
+            name: Some(id.to_string()),
+            instance: None,
+            x: 0,
+            y: 0,
+            button: MouseButton::Left,
+            modifiers: vec![],
+        }
+    }
+
+    #[test]
+    fn test_mail_text() {
+        assert_eq!(mail_text(None, 3), "3");
+        assert_eq!(mail_text(Some("inbox"), 3), "inbox:3");
+        assert_eq!(mail_text(Some("work"), 0), "work:0");
+    }
+
+    #[test]
+    fn test_backend_needs_inboxes_or_queries() {
+        let (tx, _rx) = crossbeam_channel::unbounded();
+        assert!(Maildir::new(MaildirConfig::default(), Config::default(), tx.clone()).is_err());
+        let config = MaildirConfig {
+            backend: MailBackend::Notmuch,
+            inboxes: vec!["~/mail/inbox".to_string()],
+            ..MaildirConfig::default()
+        };
+        assert!(Maildir::new(config, Config::default(), tx).is_err());
+    }
+
+    #[test]
+    fn test_query_selection() {
+        let (tx, _rx) = crossbeam_channel::unbounded();
+        let config = MaildirConfig {
+            inboxes: vec!["~/mail/inbox".to_string()],
+            queries: vec![query("inbox", "tag:inbox")],
+            ..MaildirConfig::default()
+        };
+        let mut files = Maildir::new(config, Config::default(), tx.clone()).unwrap();
+        assert!(files.query().is_none());
+        let id = files.id().to_string();
+        files.click(&left_click(&id)).unwrap();
+        assert_eq!(files.current, 0);
+
+        let config = MaildirConfig {
+            backend: MailBackend::Notmuch,
+            database: "/nonexistent".to_string(),
+            queries: vec![
+                query("inbox", "tag:inbox and tag:unread"),
+                query("work", "tag:work"),
+            ],
+            ..MaildirConfig::default()
+        };
+        let mut notmuch = Maildir::new(config, Config::default(), tx).unwrap();
+        assert_eq!(notmuch.query().unwrap().name, "inbox");
+        let id = notmuch.id().to_string();
+        // Counting fails without a database, after the click selected the next query
+        let _ = notmuch.click(&left_click(&id));
+        assert_eq!(notmuch.query().unwrap().query, "tag:work");
+        let _ = notmuch.click(&left_click(&id));
+        assert_eq!(notmuch.query().unwrap().name, "inbox");
+    }
+}
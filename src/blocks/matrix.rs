@@ -0,0 +1,305 @@
+//! A block for the unread messages in the rooms of a Matrix account.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{http_get, pseudo_uuid, read_token, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+/// How long the server holds a sync request open while nothing happens, in milliseconds.
+const SYNC_TIMEOUT: u64 = 30_000;
+/// How long to wait before syncing again after an error.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+/// Keeps the first sync small, since only the counts of unread messages are needed.
+const SYNC_FILTER: &str = r#"{"presence":{"types":[]},"account_data":{"types":[]},"room":{"timeline":{"limit":1},"ephemeral":{"types":[]},"state":{"lazy_load_members":true}}}"#;
+
+/// The unread messages and the highlights, like mentions, of a room.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Counts {
+    unread: u64,
+    highlights: u64,
+}
+
+/// Percent-encodes a value for a query string.
+fn url_encode(value: &str) -> String {
+    value
+        .bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+/// Applies the answer of a sync to the counts of the rooms, and returns the token of the next
+/// sync. Rooms only appear in a sync when something changed in them.
+fn apply_sync(json: &Value, rooms: &mut HashMap<String, Counts>) -> Result<String> {
+    if let Some(error) = json["error"].as_str() {
+        return Err(BlockError(
+            "matrix".to_string(),
+            format!("API Error: {}", error),
+        ));
+    }
+    let next_batch = json["next_batch"]
+        .as_str()
+        .block_error("matrix", "Malformed JSON.")?;
+    if let Some(joined) = json
+        .pointer("/rooms/join")
+        .and_then(|rooms| rooms.as_object())
+    {
+        for (room, state) in joined {
+            let notifications = &state["unread_notifications"];
+            rooms.insert(
+                room.clone(),
+                Counts {
+                    unread: notifications["notification_count"].as_u64().unwrap_or(0),
+                    highlights: notifications["highlight_count"].as_u64().unwrap_or(0),
+                },
+            );
+        }
+    }
+    if let Some(left) = json
+        .pointer("/rooms/leave")
+        .and_then(|rooms| rooms.as_object())
+    {
+        for room in left.keys() {
+            rooms.remove(room);
+        }
+    }
+    Ok(next_batch.to_string())
+}
+
+/// Requests the url, with the HTTP status. Errors may come without JSON.
+fn fetch(url: &str, headers: &[String], timeout: u64) -> Result<(u16, Value)> {
+    let response = http_get("matrix", url, headers, Duration::from_secs(timeout))?;
+    Ok((
+        response.status,
+        response.json("matrix").unwrap_or(Value::Null),
+    ))
+}
+
+/// Syncs until an error, and calls `notify` with the counts of the rooms when they change.
+/// `notify` returns false when the block is gone.
+fn sync(
+    homeserver: &str,
+    token: &str,
+    notify: &mut dyn FnMut(std::result::Result<Counts, String>) -> bool,
+) -> Result<()> {
+    let headers = [format!("Authorization: Bearer {}", token)];
+    let mut rooms = HashMap::new();
+    let mut since: Option<String> = None;
+    let mut last = None;
+    loop {
+        let mut url = format!(
+            "{}/_matrix/client/v3/sync?filter={}&timeout={}",
+            homeserver.trim_end_matches('/'),
+            url_encode(SYNC_FILTER),
+            // The first sync answers right away
+            if since.is_some() { SYNC_TIMEOUT } else { 0 }
+        );
+        if let Some(ref since) = since {
+            url.push_str(&format!("&since={}", url_encode(since)));
+        }
+        let (status, json) = fetch(&url, &headers, SYNC_TIMEOUT / 1000 + 30)?;
+        if status >= 400 && json["error"].is_null() {
+            return Err(BlockError(
+                "matrix".to_string(),
+                format!("API Error: HTTP status {}", status),
+            ));
+        }
+        since = Some(apply_sync(&json, &mut rooms)?);
+
+        let total = rooms
+            .values()
+            .fold(Counts::default(), |total, counts| Counts {
+                unread: total.unread + counts.unread,
+                highlights: total.highlights + counts.highlights,
+            });
+        if last != Some(total) {
+            last = Some(total);
+            if !notify(Ok(total)) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// The unread messages, or why they could not be counted, or `None` before the first sync.
+type Status = Arc<Mutex<Option<std::result::Result<Counts, String>>>>;
+
+pub struct Matrix {
+    id: String,
+    output: ButtonWidget,
+    format: FormatTemplate,
+    status: Status,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MatrixConfig {
+    /// The url of the homeserver, like `https://matrix.org`
+    pub homeserver: String,
+
+    /// A file with the access token
+    pub access_token_file: String,
+
+    /// Format string
+    #[serde(default = "MatrixConfig::default_format")]
+    pub format: String,
+}
+
+impl MatrixConfig {
+    fn default_format() -> String {
+        "{unread}".to_owned()
+    }
+}
+
+impl ConfigBlock for Matrix {
+    type Config = MatrixConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let token = read_token("matrix", &block_config.access_token_file)?;
+
+        let id = pseudo_uuid();
+        let status: Status = Arc::new(Mutex::new(None));
+        let thread_status = status.clone();
+        let thread_id = id.clone();
+        let homeserver = block_config.homeserver;
+        thread::Builder::new()
+            .name("matrix".into())
+            .spawn(move || {
+                let mut notify = |counts: std::result::Result<Counts, String>| {
+                    *thread_status
+                        .lock()
+                        .expect("lock has been poisoned in `matrix` block") = Some(counts);
+                    send.send(Task {
+                        id: thread_id.clone(),
+                        update_time: Instant::now(),
+                    })
+                    .is_ok()
+                };
+                loop {
+                    match sync(&homeserver, &token, &mut notify) {
+                        // The block is gone
+                        Ok(()) => return,
+                        Err(error) => {
+                            if !notify(Err(error.to_string())) {
+                                return;
+                            }
+                        }
+                    }
+                    thread::sleep(RETRY_DELAY);
+                }
+            })
+            .block_error("matrix", "failed to start the syncing thread")?;
+
+        Ok(Matrix {
+            output: ButtonWidget::new(config, &id).with_icon("chat"),
+            id,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("matrix", "Invalid format specified")?,
+            status,
+        })
+    }
+}
+
+impl Block for Matrix {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let status = self
+            .status
+            .lock()
+            .block_error("matrix", "failed to acquire lock")?
+            .clone();
+        let values = match status {
+            Some(Ok(counts)) => map!(
+                "{unread}" => counts.unread.to_string(),
+                "{highlights}" => counts.highlights.to_string()
+            ),
+            // Errors are shown until the thread syncs again, instead of stopping the bar
+            _ => map!(
+                "{unread}" => "?".to_string(),
+                "{highlights}" => "?".to_string()
+            ),
+        };
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
+        self.output.set_state(match status {
+            Some(Ok(counts)) if counts.highlights > 0 => State::Warning,
+            Some(Ok(counts)) if counts.unread > 0 => State::Info,
+            Some(Err(_)) => State::Critical,
+            _ => State::Idle,
+        });
+
+        // The thread updates the block
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_sync, url_encode, Counts};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_apply_sync() {
+        let mut rooms = HashMap::new();
+        let json = serde_json::json!({
+            "next_batch": "s72595_4483_1934",
+            "rooms": {"join": {
+                "!a:example.org": {"unread_notifications": {"notification_count": 3, "highlight_count": 1}},
+                "!b:example.org": {"unread_notifications": {"notification_count": 2, "highlight_count": 0}}
+            }}
+        });
+        assert_eq!(apply_sync(&json, &mut rooms).unwrap(), "s72595_4483_1934");
+        assert_eq!(
+            rooms["!a:example.org"],
+            Counts {
+                unread: 3,
+                highlights: 1
+            }
+        );
+
+        // Only the rooms that changed are in the next sync
+        let json = serde_json::json!({
+            "next_batch": "s72596_4483_1934",
+            "rooms": {
+                "join": {"!b:example.org": {"unread_notifications": {}}},
+                "leave": {"!a:example.org": {}}
+            }
+        });
+        apply_sync(&json, &mut rooms).unwrap();
+        assert_eq!(rooms.len(), 1);
+        assert_eq!(rooms["!b:example.org"], Counts::default());
+
+        let json =
+            serde_json::json!({"errcode": "M_UNKNOWN_TOKEN", "error": "Invalid access token"});
+        assert!(apply_sync(&json, &mut rooms).is_err());
+    }
+
+    #[test]
+    fn test_url_encode() {
+        assert_eq!(url_encode(r#"{"a":[1]}"#), "%7B%22a%22%3A%5B1%5D%7D");
+        assert_eq!(url_encode("s72595_4483~1"), "s72595_4483~1");
+    }
+}
@@ -1,8 +1,10 @@
 use crossbeam_channel::Sender;
+use nix::unistd::{sysconf, SysconfVar};
 use serde_derive::Deserialize;
 use std::fmt;
-use std::fs::File;
+use std::fs::{read_dir, File};
 use std::io::{BufRead, BufReader};
+use std::path::Path;
 use std::str::FromStr;
 use std::time::{Duration, Instant};
 
@@ -87,6 +89,7 @@ impl Unit {
 struct Memstate {
     mem_total: (u64, bool),
     mem_free: (u64, bool),
+    mem_available: (u64, bool),
     buffers: (u64, bool),
     cached: (u64, bool),
     s_reclaimable: (u64, bool),
@@ -104,6 +107,16 @@ impl Memstate {
         self.mem_free.0
     }
 
+    /// The estimate of the kernel for memory that can be used without swapping, or `None`
+    /// on kernels older than 3.14.
+    fn mem_available(&self) -> Option<u64> {
+        if self.mem_available.1 {
+            Some(self.mem_available.0)
+        } else {
+            None
+        }
+    }
+
     fn buffers(&self) -> u64 {
         self.buffers.0
     }
@@ -132,6 +145,7 @@ impl Memstate {
         Memstate {
             mem_total: (0, false),
             mem_free: (0, false),
+            mem_available: (0, false),
             buffers: (0, false),
             cached: (0, false),
             s_reclaimable: (0, false),
@@ -144,6 +158,7 @@ impl Memstate {
     fn done(&self) -> bool {
         self.mem_total.1
             && self.mem_free.1
+            && self.mem_available.1
             && self.buffers.1
             && self.cached.1
             && self.s_reclaimable.1
@@ -153,6 +168,55 @@ impl Memstate {
     }
 }
 
+/// The sizes of all zram devices in bytes, as the original and the compressed size of the
+/// stored data.
+fn zram_sizes() -> (u64, u64) {
+    let mut sizes = (0, 0);
+    let entries = match read_dir("/sys/block") {
+        Ok(entries) => entries,
+        Err(_) => return sizes,
+    };
+    for entry in entries.flatten() {
+        if !entry.file_name().to_string_lossy().starts_with("zram") {
+            continue;
+        }
+        if let Ok(mm_stat) = read_file("memory", &entry.path().join("mm_stat")) {
+            if let Some((orig, compr)) = parse_mm_stat(&mm_stat) {
+                sizes.0 += orig;
+                sizes.1 += compr;
+            }
+        }
+    }
+    sizes
+}
+
+/// The original and compressed data size from the `mm_stat` file of a zram device.
+fn parse_mm_stat(mm_stat: &str) -> Option<(u64, u64)> {
+    let mut fields = mm_stat.split_whitespace();
+    let orig = fields.next()?.parse().ok()?;
+    let compr = fields.next()?.parse().ok()?;
+    Some((orig, compr))
+}
+
+/// The number of pages swapped in and out since boot, from `/proc/vmstat`.
+fn swapped_pages() -> Result<(u64, u64)> {
+    let vmstat = read_file("memory", Path::new("/proc/vmstat"))?;
+    let mut pages = (0, 0);
+    for line in vmstat.lines() {
+        let mut fields = line.split_whitespace();
+        let target = match fields.next() {
+            Some("pswpin") => &mut pages.0,
+            Some("pswpout") => &mut pages.1,
+            _ => continue,
+        };
+        *target = fields
+            .next()
+            .and_then(|n| n.parse().ok())
+            .block_error("memory", "failed to parse /proc/vmstat")?;
+    }
+    Ok(pages)
+}
+
 #[derive(Clone, Debug)]
 pub struct Memory {
     id: String,
@@ -164,6 +228,9 @@ pub struct Memory {
     tx_update_request: Sender<Task>,
     warning: (f64, f64),
     critical: (f64, f64),
+    page_size: u64,
+    /// The swapped pages of the last update, to compute the swap rates
+    last_swapped: Option<(Instant, (u64, u64))>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -184,7 +251,7 @@ pub struct MemoryConfig {
     #[serde(default = "MemoryConfig::default_icons")]
     pub icons: bool,
 
-    /// Whether the view should switch between memory and swap on click. Options are <br/> true, false
+    /// Whether the view should switch between memory and swap on a left click. Options are <br/> true, false
     #[serde(default = "MemoryConfig::default_clickable")]
     pub clickable: bool,
 
@@ -255,6 +322,26 @@ impl MemoryConfig {
 }
 
 impl Memory {
+    /// The swap-in and swap-out rates in KiB/s since the last update.
+    fn swap_rates(&mut self) -> Result<(u64, u64)> {
+        let now = Instant::now();
+        let swapped = swapped_pages()?;
+        let rates = match self.last_swapped {
+            Some((then, last)) => {
+                let secs = now.duration_since(then).as_secs_f64().max(1e-3);
+                let rate =
+                    |pages: u64| (pages as f64 * self.page_size as f64 / 1024.0 / secs) as u64;
+                (
+                    rate(swapped.0.saturating_sub(last.0)),
+                    rate(swapped.1.saturating_sub(last.1)),
+                )
+            }
+            None => (0, 0),
+        };
+        self.last_swapped = Some((now, swapped));
+        Ok(rates)
+    }
+
     fn format_insert_values(&mut self, mem_state: Memstate) -> Result<String> {
         let mem_total = Unit::KiB(mem_state.mem_total());
         let mem_free = Unit::KiB(mem_state.mem_free());
@@ -265,7 +352,21 @@ impl Memory {
         let buffers = Unit::KiB(mem_state.buffers());
         let cached = Unit::KiB(mem_state.cached() + mem_state.s_reclaimable() - mem_state.shmem());
         let mem_used = Unit::KiB(mem_total_used.n() - (buffers.n() + cached.n()));
-        let mem_avail = Unit::KiB(mem_total.n() - mem_used.n());
+        // Reclaimable memory is not all that is cached, so the kernel knows better
+        let mem_avail = Unit::KiB(
+            mem_state
+                .mem_available()
+                .unwrap_or_else(|| mem_total.n() - mem_used.n()),
+        );
+        let mem_unavail = Unit::KiB(mem_total.n().saturating_sub(mem_avail.n()));
+        let (zram_orig, zram_compr) = zram_sizes();
+        let zram_compr = Unit::KiB(zram_compr / 1024);
+        let zram_ratio = if zram_compr.n() > 0 {
+            zram_orig as f32 / 1024.0 / zram_compr.n() as f32
+        } else {
+            0.0
+        };
+        let (swap_in, swap_out) = self.swap_rates()?;
 
         let values = map!(
             "{MTg}" => format!("{:.1}", mem_total.gib()),
@@ -290,6 +391,11 @@ impl Memory {
             "{MAp}" => format!("{:.2}", mem_avail.percent(mem_total)),
             "{MApi}" => format!("{:02}", mem_avail.percent(mem_total) as i32),
             "{MApb}" => format_percent_bar(mem_avail.percent(mem_total)),
+            "{Mag}" => format!("{:.1}", mem_unavail.gib()),
+            "{Mam}" => format!("{}", mem_unavail.mib()),
+            "{Map}" => format!("{:.2}", mem_unavail.percent(mem_total)),
+            "{Mapi}" => format!("{:02}", mem_unavail.percent(mem_total) as i32),
+            "{Mapb}" => format_percent_bar(mem_unavail.percent(mem_total)),
             "{STg}" => format!("{:.1}", swap_total.gib()),
             "{STm}" => format!("{}", swap_total.mib()),
             "{SFg}" => format!("{:.1}", swap_free.gib()),
@@ -302,6 +408,11 @@ impl Memory {
             "{SUp}" => format!("{:.2}", swap_used.percent(swap_total)),
             "{SUpi}" => format!("{:02}", swap_used.percent(swap_total) as i32),
             "{SUpb}" => format_percent_bar(swap_used.percent(swap_total)),
+            "{SIk}" => format!("{}", swap_in),
+            "{SOk}" => format!("{}", swap_out),
+            "{Zg}" => format!("{:.1}", zram_compr.gib()),
+            "{Zm}" => format!("{}", zram_compr.mib()),
+            "{Zr}" => format!("{:.1}", zram_ratio),
             "{Bg}" => format!("{:.1}", buffers.gib()),
             "{Bm}" => format!("{}", buffers.mib()),
             "{Bp}" => format!("{:.2}", buffers.percent(mem_total)),
@@ -348,10 +459,15 @@ impl ConfigBlock for Memory {
     type Config = MemoryConfig;
 
     fn new(block_config: Self::Config, config: Config, tx: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
         let icons: bool = block_config.icons;
-        let widget = ButtonWidget::new(config, "memory").with_text("");
+        let widget = ButtonWidget::new(config, &id).with_text("");
+        let page_size = match sysconf(SysconfVar::PAGE_SIZE) {
+            Ok(Some(size)) => size as u64,
+            _ => 4096,
+        };
         Ok(Memory {
-            id: pseudo_uuid(),
+            id,
             memtype: block_config.display_type,
             output: if icons {
                 (
@@ -370,6 +486,8 @@ impl ConfigBlock for Memory {
             tx_update_request: tx,
             warning: (block_config.warning_mem, block_config.warning_swap),
             critical: (block_config.critical_mem, block_config.critical_swap),
+            page_size,
+            last_swapped: None,
         })
     }
 }
@@ -414,6 +532,14 @@ impl Block for Memory {
                     );
                     continue;
                 }
+                Some(&"MemAvailable:") => {
+                    mem_state.mem_available = (
+                        u64::from_str(line[1])
+                            .block_error("memory", "failed to parse mem_available")?,
+                        true,
+                    );
+                    continue;
+                }
                 Some(&"Buffers:") => {
                     mem_state.buffers = (
                         u64::from_str(line[1]).block_error("memory", "failed to parse buffers")?,
@@ -477,20 +603,22 @@ impl Block for Memory {
     }
 
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
-        if let Some(ref s) = event.name {
-            if self.clickable && event.button == MouseButton::Left && *s == "memory" {
-                self.switch();
-                self.update()?;
-                self.tx_update_request.send(Task {
-                    id: self.id.clone(),
-                    update_time: Instant::now(),
-                })?;
-            }
+        if self.clickable && event.button == MouseButton::Left && event.matches_name(&self.id) {
+            self.switch();
+            self.update()?;
+            self.tx_update_request.send(Task {
+                id: self.id.clone(),
+                update_time: Instant::now(),
+            })?;
         }
 
         Ok(())
     }
 
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left && self.clickable
+    }
+
     fn view(&self) -> Vec<&dyn I3BarWidget> {
         vec![match self.memtype {
             Memtype::Memory => &self.output.0,
@@ -498,3 +626,19 @@ impl Block for Memory {
         }]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::parse_mm_stat;
+
+    #[test]
+    fn test_parse_mm_stat() {
+        assert_eq!(
+            parse_mm_stat(
+                "  4096000   1024000   1200000        0  1500000      12       0       34       0"
+            ),
+            Some((4096000, 1024000))
+        );
+        assert_eq!(parse_mm_stat(""), None);
+    }
+}
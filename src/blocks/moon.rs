@@ -0,0 +1,164 @@
+//! A block for the phase of the moon, computed without a network connection.
+
+use std::f64::consts::PI;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::I3BarWidget;
+use crate::widgets::text::TextWidget;
+
+/// The mean time from one new moon to the next, in days.
+const SYNODIC_MONTH: f64 = 29.530_588_853;
+/// A new moon, on 2000-01-06 at 18:14 UTC.
+const NEW_MOON: i64 = 947_182_440;
+
+/// The phases, from the new moon on, with their emoji for the northern hemisphere.
+const PHASES: [(&str, &str); 8] = [
+    ("new moon", "\u{1f311}"),
+    ("waxing crescent", "\u{1f312}"),
+    ("first quarter", "\u{1f313}"),
+    ("waxing gibbous", "\u{1f314}"),
+    ("full moon", "\u{1f315}"),
+    ("waning gibbous", "\u{1f316}"),
+    ("last quarter", "\u{1f317}"),
+    ("waning crescent", "\u{1f318}"),
+];
+
+/// The days since the last new moon, from the mean length of the month, which is within
+/// about a day of the true phase.
+fn moon_age(unix_time: i64) -> f64 {
+    let days = (unix_time - NEW_MOON) as f64 / 86400.0;
+    days.rem_euclid(SYNODIC_MONTH)
+}
+
+/// The illuminated part of the moon, between 0 and 1.
+fn illumination(age: f64) -> f64 {
+    (1.0 - (2.0 * PI * age / SYNODIC_MONTH).cos()) / 2.0
+}
+
+/// The index of the phase in `PHASES`, each of which lasts an eighth of the month.
+fn phase(age: f64) -> usize {
+    (age / SYNODIC_MONTH * 8.0).round() as usize % 8
+}
+
+pub struct Moon {
+    id: String,
+    text: TextWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    southern: bool,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MoonConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "MoonConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format string
+    #[serde(default = "MoonConfig::default_format")]
+    pub format: String,
+
+    /// Whether to show the moon as seen from the southern hemisphere
+    #[serde(default)]
+    pub southern: bool,
+}
+
+impl MoonConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(600)
+    }
+
+    fn default_format() -> String {
+        "{icon} {illumination}%".to_owned()
+    }
+}
+
+impl ConfigBlock for Moon {
+    type Config = MoonConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        Ok(Moon {
+            id: pseudo_uuid(),
+            text: TextWidget::new(config),
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("moon", "Invalid format specified")?,
+            southern: block_config.southern,
+        })
+    }
+}
+
+impl Block for Moon {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .block_error("moon", "the clock is before 1970")?
+            .as_secs() as i64;
+        let age = moon_age(now);
+        let phase = phase(age);
+        let (name, _) = PHASES[phase];
+        // The moon is mirrored in the southern hemisphere, so a waxing moon looks waning
+        let (_, icon) = if self.southern {
+            PHASES[(8 - phase) % 8]
+        } else {
+            PHASES[phase]
+        };
+        let half_month = SYNODIC_MONTH / 2.0;
+
+        let values = map!(
+            "{icon}" => icon.to_string(),
+            "{phase}" => name.to_string(),
+            "{illumination}" => format!("{:.0}", illumination(age) * 100.0),
+            "{age}" => format!("{:.0}", age.floor()),
+            "{days_to_full}" => format!("{:.0}", (half_month - age).rem_euclid(SYNODIC_MONTH).floor()),
+            "{days_to_new}" => format!("{:.0}", (SYNODIC_MONTH - age).floor())
+        );
+        self.text.set_text(self.format.render_static_str(&values)?);
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{illumination, moon_age, phase, PHASES};
+
+    #[test]
+    fn test_moon_age() {
+        // The full moon of 2020-12-30 03:28 UTC
+        let age = moon_age(1_609_298_880);
+        assert!((age - 14.77).abs() < 1.0, "{}", age);
+        assert!(illumination(age) > 0.99);
+        assert_eq!(PHASES[phase(age)].0, "full moon");
+
+        // The new moon of 2021-01-13 05:00 UTC
+        let age = moon_age(1_610_514_000);
+        assert!(age > 28.5 || age < 1.0, "{}", age);
+        assert!(illumination(age) < 0.01);
+        assert_eq!(PHASES[phase(age)].0, "new moon");
+
+        // Before the reference new moon
+        assert!((moon_age(947_182_440 - 86400) - 28.53).abs() < 0.01);
+    }
+}
@@ -0,0 +1,406 @@
+//! A block for the Music Player Daemon, which it talks to over its own protocol.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::{Config, LogicalDirection, Scrolling};
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Delay before the listener tries to reach MPD again.
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+/// Reads the answer to a command, `key: value` lines that end with `OK`, or an `ACK` error.
+fn read_response<R: BufRead>(reader: &mut R) -> Result<HashMap<String, String>> {
+    let mut pairs = HashMap::new();
+    loop {
+        let mut line = String::new();
+        let read = reader
+            .read_line(&mut line)
+            .block_error("mpd", "Failed to read from MPD")?;
+        if read == 0 {
+            return Err(BlockError(
+                "mpd".to_string(),
+                "MPD closed the connection".to_string(),
+            ));
+        }
+        let line = line.trim_end();
+        if line == "OK" {
+            return Ok(pairs);
+        }
+        if line.starts_with("ACK ") {
+            return Err(BlockError(
+                "mpd".to_string(),
+                format!("MPD answered {}", &line[4..]),
+            ));
+        }
+        let mut pair = line.splitn(2, ": ");
+        if let (Some(key), Some(value)) = (pair.next(), pair.next()) {
+            pairs.insert(key.to_string(), value.to_string());
+        }
+    }
+}
+
+struct MpdClient {
+    stream: TcpStream,
+    reader: BufReader<TcpStream>,
+}
+
+impl MpdClient {
+    /// Connects to MPD, waiting at most `TIMEOUT` for each of the addresses of `host`, so
+    /// that an unreachable host does not block the bar. With `timeout`, answers are waited
+    /// for as long, while the listener waits in `idle` for as long as it takes.
+    fn connect(host: &str, port: u16, password: Option<&str>, timeout: bool) -> Result<Self> {
+        let addrs = (host, port)
+            .to_socket_addrs()
+            .block_error("mpd", &format!("Failed to resolve {}", host))?;
+        let stream = addrs
+            .filter_map(|addr| TcpStream::connect_timeout(&addr, TIMEOUT).ok())
+            .next()
+            .block_error("mpd", "Failed to reach MPD")?;
+        if timeout {
+            stream
+                .set_read_timeout(Some(TIMEOUT))
+                .block_error("mpd", "Failed to set a timeout")?;
+            stream
+                .set_write_timeout(Some(TIMEOUT))
+                .block_error("mpd", "Failed to set a timeout")?;
+        }
+        let mut reader = BufReader::new(
+            stream
+                .try_clone()
+                .block_error("mpd", "Failed to clone the connection")?,
+        );
+
+        let mut greeting = String::new();
+        reader
+            .read_line(&mut greeting)
+            .block_error("mpd", "Failed to read from MPD")?;
+        if !greeting.starts_with("OK MPD") {
+            return Err(BlockError(
+                "mpd".to_string(),
+                "Unexpected greeting, is this MPD?".to_string(),
+            ));
+        }
+
+        let mut client = MpdClient { stream, reader };
+        if let Some(password) = password {
+            client.command(&format!("password {}", quote(password)))?;
+        }
+        Ok(client)
+    }
+
+    fn command(&mut self, command: &str) -> Result<HashMap<String, String>> {
+        self.stream
+            .write_all(format!("{}\n", command).as_bytes())
+            .block_error("mpd", "Failed to send to MPD")?;
+        read_response(&mut self.reader)
+    }
+}
+
+/// Quotes an argument of a command.
+fn quote(argument: &str) -> String {
+    format!(
+        "\"{}\"",
+        argument.replace('\\', "\\\\").replace('"', "\\\"")
+    )
+}
+
+/// Formats a time in seconds, like `elapsed: 65.193`, as `M:SS`.
+fn format_seconds(seconds: Option<&String>) -> String {
+    match seconds.and_then(|seconds| seconds.parse::<f64>().ok()) {
+        Some(seconds) => {
+            let seconds = seconds as u64;
+            format!("{}:{:02}", seconds / 60, seconds % 60)
+        }
+        None => "".to_string(),
+    }
+}
+
+pub struct Mpd {
+    id: String,
+    output: ButtonWidget,
+    format: FormatTemplate,
+    format_stopped: FormatTemplate,
+    host: String,
+    port: u16,
+    password: Option<String>,
+    step_width: u32,
+    scrolling: Scrolling,
+    /// Whether the format shows the elapsed time, which MPD does not announce changes of
+    poll_elapsed: bool,
+    client: Option<MpdClient>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MpdConfig {
+    /// Host that runs MPD
+    #[serde(default = "MpdConfig::default_host")]
+    pub host: String,
+
+    /// Port of MPD
+    #[serde(default = "MpdConfig::default_port")]
+    pub port: u16,
+
+    /// Password of MPD
+    #[serde(default)]
+    pub password: Option<String>,
+
+    /// Format string while a song is playing or paused
+    #[serde(default = "MpdConfig::default_format")]
+    pub format: String,
+
+    /// Format string while nothing is playing
+    #[serde(default = "MpdConfig::default_format_stopped")]
+    pub format_stopped: String,
+
+    /// The steps volume is in/decreased when scrolling
+    #[serde(default = "MpdConfig::default_step_width")]
+    pub step_width: u32,
+}
+
+impl MpdConfig {
+    fn default_host() -> String {
+        "localhost".to_owned()
+    }
+
+    fn default_port() -> u16 {
+        6600
+    }
+
+    fn default_format() -> String {
+        "{artist} - {title}".to_owned()
+    }
+
+    fn default_format_stopped() -> String {
+        "".to_owned()
+    }
+
+    fn default_step_width() -> u32 {
+        5
+    }
+}
+
+impl ConfigBlock for Mpd {
+    type Config = MpdConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+
+        // MPD tells a client in idle mode about changes, which needs a connection of its own
+        let (host, port, password) = (
+            block_config.host.clone(),
+            block_config.port,
+            block_config.password.clone(),
+        );
+        let listener_id = id.clone();
+        thread::Builder::new()
+            .name("mpd".into())
+            .spawn(move || loop {
+                if let Ok(mut client) = MpdClient::connect(&host, port, password.as_deref(), false)
+                {
+                    while client.command("idle player mixer options").is_ok() {
+                        let task = Task {
+                            id: listener_id.clone(),
+                            update_time: Instant::now(),
+                        };
+                        if send.send(task).is_err() {
+                            return;
+                        }
+                    }
+                }
+                thread::sleep(RECONNECT_DELAY);
+            })
+            .unwrap();
+
+        let format = FormatTemplate::from_string(&block_config.format)
+            .block_error("mpd", "Invalid format specified")?;
+        Ok(Mpd {
+            output: ButtonWidget::new(config.clone(), &id).with_icon("music"),
+            id,
+            poll_elapsed: format.has_placeholder("{elapsed}"),
+            format,
+            format_stopped: FormatTemplate::from_string(&block_config.format_stopped)
+                .block_error("mpd", "Invalid format_stopped specified")?,
+            host: block_config.host,
+            port: block_config.port,
+            password: block_config.password,
+            step_width: block_config.step_width.min(50),
+            scrolling: config.scrolling,
+            client: None,
+        })
+    }
+}
+
+impl Mpd {
+    /// Sends a command, over a new connection if there is none or the last one failed.
+    fn command(&mut self, command: &str) -> Result<HashMap<String, String>> {
+        if self.client.is_none() {
+            self.client = Some(MpdClient::connect(
+                &self.host,
+                self.port,
+                self.password.as_deref(),
+                true,
+            )?);
+        }
+        let result = self.client.as_mut().unwrap().command(command);
+        if result.is_err() {
+            self.client = None;
+        }
+        result
+    }
+}
+
+impl Block for Mpd {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let status = self.command("status")?;
+        let song = self.command("currentsong")?;
+
+        let state = status.get("state").map_or("stop", |state| state.as_str());
+        let field = |key: &str| song.get(key).cloned().unwrap_or_default();
+        let values = map!(
+            "{artist}" => field("Artist"),
+            "{title}" => song
+                .get("Title")
+                .or_else(|| song.get("file"))
+                .cloned()
+                .unwrap_or_default(),
+            "{album}" => field("Album"),
+            "{elapsed}" => format_seconds(status.get("elapsed")),
+            "{duration}" => format_seconds(status.get("duration").or_else(|| song.get("Time"))),
+            "{volume}" => status.get("volume").cloned().unwrap_or_default(),
+            "{state}" => match state {
+                "play" => "playing",
+                "pause" => "paused",
+                _ => "stopped",
+            }
+            .to_string()
+        );
+
+        match state {
+            "play" | "pause" => {
+                self.output
+                    .set_text(self.format.render_static_str(&values)?);
+                self.output.set_icon(if state == "play" {
+                    "music_play"
+                } else {
+                    "music_pause"
+                });
+            }
+            _ => {
+                self.output
+                    .set_text(self.format_stopped.render_static_str(&values)?);
+                self.output.set_icon("music");
+            }
+        }
+        self.output.set_state(if state == "play" {
+            State::Info
+        } else {
+            State::Idle
+        });
+
+        if self.poll_elapsed && state == "play" {
+            Ok(Some(Update::Every(Duration::from_secs(1))))
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if !event.matches_name(&self.id) {
+            return Ok(());
+        }
+        // The listener sees the changes and updates the block
+        match event.button {
+            MouseButton::Left => {
+                let status = self.command("status")?;
+                if status.get("state").map(|state| state.as_str()) == Some("play") {
+                    self.command("pause 1")?;
+                } else {
+                    self.command("play")?;
+                }
+            }
+            MouseButton::Right => {
+                self.command("next")?;
+            }
+            MouseButton::Middle => {
+                self.command("previous")?;
+            }
+            _ => {
+                let step = match self.scrolling.to_logical_direction(event.button) {
+                    Some(LogicalDirection::Up) => self.step_width as i64,
+                    Some(LogicalDirection::Down) => -(self.step_width as i64),
+                    None => return Ok(()),
+                };
+                let status = self.command("status")?;
+                // Without a mixer, MPD reports a volume of -1
+                let volume = match status.get("volume").and_then(|v| v.parse::<i64>().ok()) {
+                    Some(volume) if volume >= 0 => volume,
+                    _ => return Ok(()),
+                };
+                self.command(&format!("setvol {}", (volume + step).max(0).min(100)))?;
+            }
+        }
+        Ok(())
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        matches!(
+            event.button,
+            MouseButton::Left
+                | MouseButton::Right
+                | MouseButton::Middle
+                | MouseButton::WheelUp
+                | MouseButton::WheelDown
+        )
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_seconds, quote, read_response};
+
+    #[test]
+    fn test_read_response() {
+        let mut response = "state: play\nelapsed: 65.193\nTitle: Artist: Title\nOK\n".as_bytes();
+        let pairs = read_response(&mut response).unwrap();
+        assert_eq!(pairs["state"], "play");
+        assert_eq!(pairs["Title"], "Artist: Title");
+
+        let mut response = "ACK [3@0] {} incorrect password\n".as_bytes();
+        assert!(read_response(&mut response).is_err());
+    }
+
+    #[test]
+    fn test_format_seconds() {
+        assert_eq!(format_seconds(Some(&"65.193".to_string())), "1:05");
+        assert_eq!(format_seconds(None), "");
+    }
+
+    #[test]
+    fn test_quote() {
+        assert_eq!(quote("se\"cret"), "\"se\\\"cret\"");
+    }
+}
@@ -0,0 +1,389 @@
+//! A block for the latest messages of MQTT topics, like the sensors of a home automation.
+
+use std::collections::HashMap;
+use std::env;
+use std::fs::{self, DirBuilder, OpenOptions};
+use std::io::{self, BufRead, BufReader, Write};
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt};
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_opt_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::subprocess::OwnedChild;
+use crate::util::{json_display, json_pointer, pseudo_uuid, xdg_config_home, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+/// Whether the topic matches the filter of a subscription, where `+` stands for one level
+/// and `#` for all remaining levels.
+fn topic_matches(filter: &str, topic: &str) -> bool {
+    let mut topic_levels = topic.split('/');
+    for filter_level in filter.split('/') {
+        match (filter_level, topic_levels.next()) {
+            ("#", _) => return true,
+            ("+", Some(_)) => {}
+            (filter_level, Some(topic_level)) if filter_level == topic_level => {}
+            _ => return false,
+        }
+    }
+    topic_levels.next().is_none()
+}
+
+/// Writes a configuration file for mosquitto_sub into `dir`, which it reads when it is the
+/// `XDG_CONFIG_HOME`. It has the options of the user's own configuration file and the password,
+/// which would show up in the process list on the command line. Only the user can read it.
+fn write_options(dir: &Path, password: &str) -> io::Result<()> {
+    if password.contains('\n') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "the password contains a line break",
+        ));
+    }
+    let mut options =
+        fs::read_to_string(xdg_config_home().join("mosquitto_sub")).unwrap_or_default();
+    if !options.is_empty() && !options.ends_with('\n') {
+        options.push('\n');
+    }
+    options.push_str(&format!("-P {}\n", password));
+
+    DirBuilder::new().mode(0o700).create(dir)?;
+    OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(dir.join("mosquitto_sub"))?
+        .write_all(options.as_bytes())
+}
+
+/// The payload, or the value at the JSON pointer in it.
+fn extract(payload: &str, pointer: Option<&str>) -> String {
+    let pointer = match pointer {
+        Some(pointer) => pointer,
+        None => return payload.to_string(),
+    };
+    let value: Option<Value> = serde_json::from_str(payload).ok();
+    json_display(value.as_ref().and_then(|value| value.pointer(pointer)))
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TopicConfig {
+    /// The placeholder of the topic
+    pub name: String,
+
+    /// The topic, which may contain the wildcards `+` and `#`
+    pub topic: String,
+
+    /// The path of the value in a JSON payload, the whole payload if not set
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// The latest values of the topics, by placeholder, with the time they arrived.
+type Values = Arc<Mutex<HashMap<String, (String, Instant)>>>;
+
+pub struct Mqtt {
+    id: String,
+    text: TextWidget,
+    format: FormatTemplate,
+    /// The names of the topics, with their placeholders
+    names: Vec<(String, String)>,
+    stale_after: Option<Duration>,
+    values: Values,
+    /// mosquitto_sub, which is killed with the block
+    #[allow(dead_code)]
+    process: OwnedChild,
+    /// The directory of the configuration file with the password
+    options_dir: Option<PathBuf>,
+}
+
+impl Drop for Mqtt {
+    fn drop(&mut self) {
+        if let Some(ref dir) = self.options_dir {
+            let _ = fs::remove_dir_all(dir);
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct MqttConfig {
+    /// The broker
+    #[serde(default = "MqttConfig::default_host")]
+    pub host: String,
+
+    #[serde(default = "MqttConfig::default_port")]
+    pub port: u16,
+
+    /// The user to log in to the broker as
+    #[serde(default)]
+    pub username: Option<String>,
+
+    /// The password of the user
+    #[serde(default)]
+    pub password: Option<String>,
+
+    /// The topics to subscribe to
+    pub topics: Vec<TopicConfig>,
+
+    /// Format string
+    #[serde(default)]
+    pub format: Option<String>,
+
+    /// Time in seconds after which a value is stale without a new message
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    pub stale_after: Option<Duration>,
+}
+
+impl MqttConfig {
+    fn default_host() -> String {
+        "localhost".to_owned()
+    }
+
+    fn default_port() -> u16 {
+        1883
+    }
+}
+
+impl ConfigBlock for Mqtt {
+    type Config = MqttConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+        let format = block_config.format.unwrap_or_else(|| {
+            block_config
+                .topics
+                .iter()
+                .map(|topic| format!("{{{}}}", topic.name))
+                .collect::<Vec<_>>()
+                .join(" ")
+        });
+
+        let format =
+            FormatTemplate::from_string(&format).block_error("mqtt", "Invalid format specified")?;
+
+        let mut args = vec![
+            "-h".to_string(),
+            block_config.host,
+            "-p".to_string(),
+            block_config.port.to_string(),
+            // Prints the topic before the payload
+            "-v".to_string(),
+        ];
+        if let Some(username) = block_config.username {
+            args.push("-u".to_string());
+            args.push(username);
+        }
+        let options_dir = match block_config.password {
+            Some(ref password) => {
+                let dir = env::var("XDG_RUNTIME_DIR")
+                    .map(PathBuf::from)
+                    .unwrap_or_else(|_| env::temp_dir())
+                    .join(format!("i3status-rs-mqtt-{}", id));
+                write_options(&dir, password)
+                    .block_error("mqtt", "failed to write the password for mosquitto_sub")?;
+                Some(dir)
+            }
+            None => None,
+        };
+        for topic in &block_config.topics {
+            args.push("-t".to_string());
+            args.push(topic.topic.clone());
+        }
+
+        let values: Values = Arc::new(Mutex::new(HashMap::new()));
+        let thread_values = values.clone();
+        let thread_id = id.clone();
+        let topics = block_config.topics.clone();
+        let config_home = options_dir.clone();
+        let process = OwnedChild::new();
+        let child = process.handle();
+        thread::Builder::new()
+            .name("mqtt".into())
+            .spawn(move || loop {
+                // mosquitto_sub reconnects by itself, but it exits if the broker cannot be
+                // reached when it starts
+                let mut command = Command::new("mosquitto_sub");
+                if let Some(ref dir) = config_home {
+                    command.env("XDG_CONFIG_HOME", dir);
+                }
+                let stdout = match child.spawn(
+                    command
+                        .args(&args)
+                        .stdin(Stdio::null())
+                        .stdout(Stdio::piped())
+                        .stderr(Stdio::null()),
+                ) {
+                    Ok(Some(pipes)) => pipes.stdout,
+                    // The block is gone
+                    Ok(None) => return,
+                    Err(_) => None,
+                };
+                if let Some(stdout) = stdout {
+                    for line in BufReader::new(stdout).lines() {
+                        let line = match line {
+                            Ok(line) => line,
+                            Err(_) => break,
+                        };
+                        let mut parts = line.splitn(2, ' ');
+                        let (topic, payload) = match (parts.next(), parts.next()) {
+                            (Some(topic), Some(payload)) => (topic, payload),
+                            (Some(topic), None) => (topic, ""),
+                            _ => continue,
+                        };
+                        let mut values = thread_values
+                            .lock()
+                            .expect("lock has been poisoned in `mqtt` block");
+                        for config in topics.iter().filter(|t| topic_matches(&t.topic, topic)) {
+                            let pointer = config.path.as_deref().map(json_pointer);
+                            values.insert(
+                                config.name.clone(),
+                                (extract(payload, pointer.as_deref()), Instant::now()),
+                            );
+                        }
+                        drop(values);
+                        if send
+                            .send(Task {
+                                id: thread_id.clone(),
+                                update_time: Instant::now(),
+                            })
+                            .is_err()
+                        {
+                            return;
+                        }
+                    }
+                }
+                child.kill();
+                thread::sleep(Duration::from_secs(10));
+            })
+            .block_error("mqtt", "failed to start the subscribing thread")?;
+
+        Ok(Mqtt {
+            id,
+            text: TextWidget::new(config),
+            format,
+            names: block_config
+                .topics
+                .into_iter()
+                .map(|topic| {
+                    let placeholder = format!("{{{}}}", topic.name);
+                    (topic.name, placeholder)
+                })
+                .collect(),
+            stale_after: block_config.stale_after,
+            values,
+            process,
+            options_dir,
+        })
+    }
+}
+
+impl Block for Mqtt {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let values = self
+            .values
+            .lock()
+            .block_error("mqtt", "failed to acquire lock")?;
+
+        let mut placeholders = HashMap::new();
+        let mut stale = false;
+        for (name, placeholder) in &self.names {
+            let value = match values.get(name) {
+                Some((value, time)) => {
+                    if self
+                        .stale_after
+                        .map_or(false, |after| time.elapsed() > after)
+                    {
+                        stale = true;
+                    }
+                    value.clone()
+                }
+                // No message yet
+                None => {
+                    stale = true;
+                    String::new()
+                }
+            };
+            placeholders.insert(placeholder.as_str(), value);
+        }
+        drop(values);
+
+        self.text
+            .set_text(self.format.render_static_str(&placeholders)?);
+        self.text
+            .set_state(if stale { State::Warning } else { State::Idle });
+
+        // New messages update the block right away, this only notices stale values
+        Ok(self.stale_after.map(|_| Duration::from_secs(5).into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract, topic_matches, write_options};
+    use crate::util::json_pointer;
+    use assert_fs::TempDir;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+
+    #[test]
+    fn test_topic_matches() {
+        assert!(topic_matches("tele/kitchen/SENSOR", "tele/kitchen/SENSOR"));
+        assert!(topic_matches("tele/+/SENSOR", "tele/kitchen/SENSOR"));
+        assert!(topic_matches("tele/#", "tele/kitchen/SENSOR"));
+        assert!(topic_matches("#", "tele/kitchen/SENSOR"));
+        assert!(!topic_matches("tele/+", "tele/kitchen/SENSOR"));
+        assert!(!topic_matches(
+            "tele/kitchen/SENSOR/x",
+            "tele/kitchen/SENSOR"
+        ));
+        assert!(!topic_matches("stat/+/SENSOR", "tele/kitchen/SENSOR"));
+    }
+
+    #[test]
+    fn test_extract() {
+        let payload =
+            r#"{"Time":"2023-10-15T12:00:00","AM2301":{"Temperature":21.4,"Humidity":48.2}}"#;
+        let pointer = json_pointer(".AM2301.Temperature");
+        assert_eq!(extract(payload, Some(&pointer)), "21.4");
+        assert_eq!(extract(payload, Some("/Time")), "2023-10-15T12:00:00");
+        assert_eq!(extract(payload, Some("/missing")), "");
+        assert_eq!(extract("ON", None), "ON");
+        assert_eq!(extract("ON", Some("/state")), "");
+    }
+
+    #[test]
+    fn test_write_options() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path().join("options");
+        write_options(&dir, "secret word").unwrap();
+        let file = dir.join("mosquitto_sub");
+        assert!(fs::read_to_string(&file)
+            .unwrap()
+            .ends_with("-P secret word\n"));
+        assert_eq!(
+            fs::metadata(&file).unwrap().permissions().mode() & 0o777,
+            0o600
+        );
+        assert!(write_options(&temp_dir.path().join("other"), "two\nlines").is_err());
+    }
+}
@@ -4,7 +4,7 @@ use std::sync::{Arc, Mutex};
 use std::thread;
 use std::time::{Duration, Instant};
 
-use crate::util::pseudo_uuid;
+use crate::util::{pseudo_uuid, FormatTemplate};
 use crossbeam_channel::Sender;
 use dbus::{
     arg::{Array, RefArg},
@@ -34,6 +34,18 @@ struct Player {
     playback_status: PlaybackStatus,
     artist: Option<String>,
     title: Option<String>,
+    album: Option<String>,
+    /// Length of the song in microseconds
+    length: Option<i64>,
+}
+
+/// The parts of the metadata of a song that are shown.
+#[derive(Debug, Default)]
+struct Metadata {
+    title: String,
+    artist: String,
+    album: String,
+    length: Option<i64>,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -58,17 +70,18 @@ pub struct Music {
     next: Option<ButtonWidget>,
     on_collapsed_click_widget: ButtonWidget,
     on_collapsed_click: Option<String>,
-    on_click: Option<String>,
     dbus_conn: Connection,
     marquee: bool,
     marquee_interval: Duration,
     smart_trim: bool,
     max_width: usize,
     separator: String,
+    format: Option<FormatTemplate>,
+    /// Whether the format shows the position, which players do not signal changes of
+    poll_position: bool,
     seek_step: i64,
     config: Config,
     players: Arc<Mutex<Vec<Player>>>,
-    hide_when_empty: bool,
     send: Sender<Task>,
 }
 
@@ -188,6 +201,11 @@ pub struct MusicConfig {
     #[serde(default = "MusicConfig::default_separator")]
     pub separator: String,
 
+    /// Format string, which replaces the title and artist joined by `separator`.
+    /// placeholders: {artist}, {title}, {album}, {position}, {length} and {player}
+    #[serde(default = "MusicConfig::default_format")]
+    pub format: Option<String>,
+
     /// Array of control buttons to be displayed. Options are prev (previous title),
     /// play (play/pause) and next (next title).
     #[serde(default = "MusicConfig::default_buttons")]
@@ -196,9 +214,6 @@ pub struct MusicConfig {
     #[serde(default = "MusicConfig::default_on_collapsed_click")]
     pub on_collapsed_click: Option<String>,
 
-    #[serde(default = "MusicConfig::default_on_click")]
-    pub on_click: Option<String>,
-
     // Number of microseconds to seek forward/backward when scrolling on the bar.
     #[serde(default = "MusicConfig::default_seek_step")]
     pub seek_step: i64,
@@ -206,9 +221,6 @@ pub struct MusicConfig {
     /// MPRIS interface name regex patterns to ignore.
     #[serde(default = "MusicConfig::default_interface_name_exclude_patterns")]
     pub interface_name_exclude: Vec<String>,
-
-    #[serde(default = "MusicConfig::default_hide_when_empty")]
-    pub hide_when_empty: bool,
 }
 
 impl MusicConfig {
@@ -240,6 +252,10 @@ impl MusicConfig {
         " - ".to_string()
     }
 
+    fn default_format() -> Option<String> {
+        None
+    }
+
     fn default_buttons() -> Vec<String> {
         vec![]
     }
@@ -248,10 +264,6 @@ impl MusicConfig {
         None
     }
 
-    fn default_on_click() -> Option<String> {
-        None
-    }
-
     fn default_seek_step() -> i64 {
         1000
     }
@@ -259,10 +271,6 @@ impl MusicConfig {
     fn default_interface_name_exclude_patterns() -> Vec<String> {
         vec![]
     }
-
-    fn default_hide_when_empty() -> bool {
-        false
-    }
 }
 
 impl ConfigBlock for Music {
@@ -321,11 +329,9 @@ impl ConfigBlock for Music {
                 // Get current media info, if any
                 let p = c.with_path(name, "/org/mpris/MediaPlayer2", 500);
                 let data = p.get("org.mpris.MediaPlayer2.Player", "Metadata");
-                let (title, artist) = match data {
-                    Err(_) => (String::new(), String::new()),
-                    Ok(data) => {
-                        extract_from_metadata(&data).unwrap_or((String::new(), String::new()))
-                    }
+                let metadata = match data {
+                    Err(_) => Metadata::default(),
+                    Ok(data) => extract_from_metadata(&data).unwrap_or_default(),
                 };
 
                 // Get current playback status
@@ -342,8 +348,10 @@ impl ConfigBlock for Music {
                     bus_name: bn.to_string(),
                     interface_name: name.to_string(),
                     playback_status: status,
-                    artist: Some(artist),
-                    title: Some(title),
+                    artist: Some(metadata.artist),
+                    title: Some(metadata.title),
+                    album: Some(metadata.album),
+                    length: metadata.length,
                 });
             }
         }
@@ -373,14 +381,21 @@ impl ConfigBlock for Music {
                             let mut updated = false;
                             let raw_metadata = signal.changed_properties.get("Metadata");
                             if let Some(data) = raw_metadata {
-                                let (title, artist) =
-                                    extract_from_metadata(&data.0).unwrap_or((String::new(), String::new()));
-                                if p.artist != Some(artist.clone()) {
-                                    p.artist = Some(artist);
+                                let metadata = extract_from_metadata(&data.0).unwrap_or_default();
+                                if p.artist != Some(metadata.artist.clone()) {
+                                    p.artist = Some(metadata.artist);
                                     updated = true;
                                 }
-                                if p.title != Some(title.clone()) {
-                                    p.title = Some(title);
+                                if p.title != Some(metadata.title.clone()) {
+                                    p.title = Some(metadata.title);
+                                    updated = true;
+                                }
+                                if p.album != Some(metadata.album.clone()) {
+                                    p.album = Some(metadata.album);
+                                    updated = true;
+                                }
+                                if p.length != metadata.length {
+                                    p.length = metadata.length;
                                     updated = true;
                                 }
                             };
@@ -392,12 +407,15 @@ impl ConfigBlock for Music {
                                     updated = true;
                                 }
                             };
-                            if updated {
-                                send.send(Task {
-                                    id: id.clone(),
-                                    update_time: Instant::now(),
-                                })
-                                .unwrap();
+                            if updated
+                                && send
+                                    .send(Task {
+                                        id: id.clone(),
+                                        update_time: Instant::now(),
+                                    })
+                                    .is_err()
+                            {
+                                return;
                             }
                         }
                     }
@@ -422,11 +440,15 @@ impl ConfigBlock for Music {
                          if !old_owner.is_empty() && new_owner.is_empty() {
                              if let Some(pos) = players.iter().position(|p| p.bus_name == old_owner) {
                                  players.remove(pos);
-                                 send2.send(Task {
-                                     id: id_copy3.clone(),
-                                     update_time: Instant::now(),
-                                 })
-                                 .unwrap();
+                                 if send2
+                                     .send(Task {
+                                         id: id_copy3.clone(),
+                                         update_time: Instant::now(),
+                                     })
+                                     .is_err()
+                                 {
+                                     return;
+                                 }
                              }
                          } else if old_owner.is_empty() && !new_owner.is_empty() && !ignored_player(name, &interface_name_exclude_regexps, preferred_player.clone()) && !players.iter().any(|p| p.bus_name == new_owner) {
                          players.push(Player {
@@ -435,12 +457,18 @@ impl ConfigBlock for Music {
                              playback_status: PlaybackStatus::Unknown,
                              artist: None,
                              title: None,
+                             album: None,
+                             length: None,
                          });
-                         send2.send(Task {
-                             id: id_copy3.clone(),
-                             update_time: Instant::now(),
-                         })
-                         .unwrap();
+                         if send2
+                             .send(Task {
+                                 id: id_copy3.clone(),
+                                 update_time: Instant::now(),
+                             })
+                             .is_err()
+                         {
+                             return;
+                         }
                          }
                     }
                 }
@@ -493,6 +521,13 @@ impl ConfigBlock for Music {
         }
 
         let id_collapsed = format!("{}_COLLAPSED", id_copy);
+        let format = match block_config.format {
+            Some(format) => Some(
+                FormatTemplate::from_string(&format)
+                    .block_error("music", "Invalid format specified")?,
+            ),
+            None => None,
+        };
         Ok(Music {
             id: id_copy,
             current_song_widget: RotatingTextWidget::new(
@@ -508,7 +543,6 @@ impl ConfigBlock for Music {
             prev,
             play,
             next,
-            on_click: block_config.on_click,
             on_collapsed_click_widget: ButtonWidget::new(config.clone(), &id_collapsed)
                 .with_icon("music")
                 .with_state(State::Info)
@@ -521,10 +555,13 @@ impl ConfigBlock for Music {
             smart_trim: block_config.smart_trim,
             max_width: block_config.max_width,
             separator: block_config.separator,
+            poll_position: format
+                .as_ref()
+                .map_or(false, |format| format.has_placeholder("{position}")),
+            format,
             seek_step: block_config.seek_step,
             config,
             players: players_copy,
-            hide_when_empty: block_config.hide_when_empty,
             send: send3,
         })
     }
@@ -560,6 +597,32 @@ impl Block for Music {
         if !(rotation_in_progress) {
             if title.is_empty() && artist.is_empty() {
                 self.current_song_widget.set_text(String::new());
+            } else if let Some(ref format) = self.format {
+                let position = if self.poll_position {
+                    self.dbus_conn
+                        .with_path(
+                            metadata.interface_name.clone(),
+                            "/org/mpris/MediaPlayer2",
+                            500,
+                        )
+                        .get::<i64>("org.mpris.MediaPlayer2.Player", "Position")
+                        .ok()
+                } else {
+                    None
+                };
+                let values = map!(
+                    "{artist}" => artist,
+                    "{title}" => title,
+                    "{album}" => metadata.album.clone().unwrap_or_default(),
+                    "{position}" => position.map(format_song_time).unwrap_or_default(),
+                    "{length}" => metadata.length.map(format_song_time).unwrap_or_default(),
+                    "{player}" => metadata
+                        .interface_name
+                        .trim_start_matches("org.mpris.MediaPlayer2.")
+                        .to_string()
+                );
+                self.current_song_widget
+                    .set_text(format.render_static_str(&values)?);
             } else if (title.chars().count()
                 + self.separator.chars().count()
                 + artist.chars().count())
@@ -583,11 +646,18 @@ impl Block for Music {
             })
         }
 
+        // The position is shown every second while playing, but not in the middle of a rotation
+        let poll = self.poll_position
+            && metadata.playback_status == PlaybackStatus::Playing
+            && !rotation_in_progress;
+
         // If `marquee` is enabled then we need to schedule an update for the text rotation.
         // (time_to_next_rotation is always None if marquee is disabled)
         if let Some(t) = time_to_next_rotation {
             Ok(Some(Update::Every(t)))
         // We just finished a rotation so we wait before starting again
+        } else if poll {
+            Ok(Some(Update::Every(Duration::from_secs(1))))
         } else if self.marquee {
             Ok(Some(self.marquee_interval.into()))
         // Otherwise we do not need to schedule anything as the block will auto-update itself after
@@ -633,11 +703,6 @@ impl Block for Music {
                         let command = self.on_collapsed_click.as_ref().unwrap();
                         spawn_child_async("sh", &["-c", command])
                             .block_error("music", "could not spawn child")?;
-                    } else if event.matches_name(self.id()) {
-                        if let Some(ref cmd) = self.on_click {
-                            spawn_child_async("sh", &["-c", cmd])
-                                .block_error("music", "could not spawn child")?;
-                        }
                     }
                 }
                 MouseButton::Right => {
@@ -681,14 +746,19 @@ impl Block for Music {
         Ok(())
     }
 
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        matches!(
+            event.button,
+            MouseButton::Right | MouseButton::WheelUp | MouseButton::WheelDown
+        )
+    }
+
     fn view(&self) -> Vec<&dyn I3BarWidget> {
         let players = self
             .players
             .lock()
             .expect("failed to acquire lock for `players`");
-        if self.current_song_widget.is_empty() && self.hide_when_empty {
-            vec![]
-        } else if players.len() > 0 && !self.current_song_widget.is_empty() {
+        if players.len() > 0 && !self.current_song_widget.is_empty() {
             let mut elements: Vec<&dyn I3BarWidget> = Vec::new();
             elements.push(&self.current_song_widget);
             if let Some(ref prev) = self.prev {
@@ -736,10 +806,24 @@ fn extract_artist_from_value(value: &dyn RefArg) -> Result<&str> {
     }
 }
 
+/// Formats a time in microseconds as `M:SS`, or `H:MM:SS` from an hour on.
+fn format_song_time(microseconds: i64) -> String {
+    let seconds = microseconds.max(0) / 1_000_000;
+    if seconds >= 3600 {
+        format!(
+            "{}:{:02}:{:02}",
+            seconds / 3600,
+            seconds / 60 % 60,
+            seconds % 60
+        )
+    } else {
+        format!("{}:{:02}", seconds / 60, seconds % 60)
+    }
+}
+
 #[allow(clippy::borrowed_box)] // TODO: remove clippy workaround
-fn extract_from_metadata(metadata: &Box<dyn RefArg>) -> Result<(String, String)> {
-    let mut title = String::new();
-    let mut artist = String::new();
+fn extract_from_metadata(metadata: &Box<dyn RefArg>) -> Result<Metadata> {
+    let mut result = Metadata::default();
 
     let mut iter = metadata
         .as_iter()
@@ -753,18 +837,20 @@ fn extract_from_metadata(metadata: &Box<dyn RefArg>) -> Result<(String, String)>
             .as_str()
             .block_error("music", "failed to extract metadata")?
         {
-            "xesam:artist" => artist = String::from(extract_artist_from_value(value)?),
+            "xesam:artist" => result.artist = String::from(extract_artist_from_value(value)?),
             "xesam:title" => {
-                title = String::from(
+                result.title = String::from(
                     value
                         .as_str()
                         .block_error("music", "failed to extract metadata")?,
                 )
             }
+            "xesam:album" => result.album = value.as_str().unwrap_or_default().to_string(),
+            "mpris:length" => result.length = value.as_i64(),
             _ => {}
         };
     }
-    Ok((title, artist))
+    Ok(result)
 }
 
 fn ignored_player(
@@ -792,3 +878,15 @@ fn ignored_player(
 
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::format_song_time;
+
+    #[test]
+    fn test_format_song_time() {
+        assert_eq!(format_song_time(0), "0:00");
+        assert_eq!(format_song_time(65_500_000), "1:05");
+        assert_eq!(format_song_time(3_725_000_000), "1:02:05");
+    }
+}
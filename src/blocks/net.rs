@@ -3,12 +3,15 @@ use std::fmt;
 use std::fs::{read_to_string, OpenOptions};
 use std::io::prelude::*;
 use std::io::ErrorKind;
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 use std::process::Command;
 use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use lazy_static::lazy_static;
+use nix::ifaddrs::getifaddrs;
+use nix::sys::socket::SockAddr;
 use regex::bytes::Regex;
 use serde_derive::Deserialize;
 
@@ -17,20 +20,15 @@ use crate::blocks::{Block, ConfigBlock};
 use crate::config::Config;
 use crate::de::deserialize_duration;
 use crate::errors::*;
-use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::subprocess::spawn_child_async;
 use crate::util::{
     escape_pango_text, format_percent_bar, format_speed, format_vec_to_bar_graph, pseudo_uuid,
-    FormatTemplate,
+    FormatTemplate, Number,
 };
 use crate::widget::{I3BarWidget, Spacing};
 use crate::widgets::button::ButtonWidget;
 
 lazy_static! {
-    static ref DEFAULT_DEV_REGEX: Regex = Regex::new("default.*dev (\\w*).*").unwrap();
-    static ref WHITESPACE_REGEX: Regex = Regex::new("\\s+").unwrap();
-    static ref ETHTOOL_SPEED_REGEX: Regex = Regex::new("Speed: (\\d+\\w\\w/s)").unwrap();
     static ref IW_SSID_REGEX: Regex = Regex::new("SSID: (.*)").unwrap();
     static ref WPA_SSID_REGEX: Regex = Regex::new("ssid=([[:alnum:]]+)").unwrap();
     static ref IWCTL_SSID_REGEX: Regex = Regex::new("Connected network\\s+([[:alnum:]]+)").unwrap();
@@ -86,25 +84,19 @@ impl NetworkDevice {
         self.device.clone()
     }
 
-    /// Grab the name of the 'default' device.
+    /// Grab the name of the 'default' device, the one of the IPv4 default route, or of the
+    /// IPv6 default route if there is none.
     /// A default device is usually selected by the network manager
     /// and will change when the status of devices change.
     pub fn default_device() -> Option<String> {
-        String::from_utf8(
-            Command::new("ip")
-                .args(&["route", "show", "default"])
-                .output()
-                .ok()
-                .and_then(|o| {
-                    let mut captures = DEFAULT_DEV_REGEX.captures_iter(&o.stdout);
-                    if let Some(cap) = captures.next() {
-                        cap.get(1).map(|x| x.as_bytes().to_vec())
-                    } else {
-                        None
-                    }
-                })?,
-        )
-        .ok()
+        read_to_string("/proc/net/route")
+            .ok()
+            .and_then(|routes| default_route_device(&routes))
+            .or_else(|| {
+                read_to_string("/proc/net/ipv6_route")
+                    .ok()
+                    .and_then(|routes| default_route6_device(&routes))
+            })
     }
 
     /// Check whether the device exists.
@@ -197,6 +189,11 @@ impl NetworkDevice {
             ));
         }
 
+        if let Some(dbm) = wireless::signal_dbm(&self.device) {
+            return Ok(Some(dbm));
+        }
+
+        // Fall back to `iw` on kernels without wireless extensions
         let iw_output = Command::new("iw")
             .args(&["dev", &self.device, "link"])
             .output()
@@ -221,85 +218,55 @@ impl NetworkDevice {
     }
 
     fn relative_signal_strength(&self) -> Result<Option<u32>> {
-        let xbm = if let Some(xbm) = self.absolute_signal_strength()? {
-            xbm as f64
-        } else {
-            return Ok(None);
-        };
-
-        // Code inspired by https://github.com/NetworkManager/NetworkManager/blob/master/src/platform/wifi/nm-wifi-utils-nl80211.c
-        const NOISE_FLOOR_DBM: f64 = -90.;
-        const SIGNAL_MAX_DBM: f64 = -20.;
-
-        let xbm = if xbm < NOISE_FLOOR_DBM {
-            NOISE_FLOOR_DBM
-        } else if xbm > SIGNAL_MAX_DBM {
-            SIGNAL_MAX_DBM
-        } else {
-            xbm
-        };
-
-        let result = 100. - 70. * ((SIGNAL_MAX_DBM - xbm) / (SIGNAL_MAX_DBM - NOISE_FLOOR_DBM));
-        let result = result as u32;
-        Ok(Some(result))
+        Ok(self.absolute_signal_strength()?.map(signal_quality))
     }
 
-    /// Queries the inet IP of this device (using `ip`).
+    /// Queries the inet IP of this device.
     pub fn ip_addr(&self) -> Result<Option<String>> {
         if !self.is_up()? {
             return Ok(None);
         }
-        let output = Command::new("ip")
-            .args(&["-json", "-family", "inet", "address", "show", &self.device])
-            .output()
-            .block_error("net", "Failed to execute IP address query.")
-            .and_then(|raw_output| {
-                String::from_utf8(raw_output.stdout)
-                    .block_error("net", "Response contained non-UTF8 characters.")
-            })?;
-
-        let ip_devs: Vec<IpDev> =
-            serde_json::from_str(&output).block_error("net", "Failed to parse JSON response")?;
-
-        if ip_devs.is_empty() {
-            return Ok(Some("".to_string()));
-        }
-
-        let ip = ip_devs
-            .iter()
-            .flat_map(|dev| &dev.addr_info)
-            .filter_map(|addr| addr.local.clone())
-            .next();
-        Ok(ip)
+        Ok(Some(
+            self.addresses()?
+                .into_iter()
+                .find(IpAddr::is_ipv4)
+                .map(|ip| ip.to_string())
+                .unwrap_or_default(),
+        ))
     }
 
-    /// Queries the inet IPv6 of this device (using `ip`).
+    /// Queries the inet IPv6 of this device, preferring addresses that are not link-local.
     pub fn ipv6_addr(&self) -> Result<Option<String>> {
         if !self.is_up()? {
             return Ok(None);
         }
-        let output = Command::new("ip")
-            .args(&["-json", "-family", "inet6", "address", "show", &self.device])
-            .output()
-            .block_error("net", "Failed to execute IP address query.")
-            .and_then(|raw_output| {
-                String::from_utf8(raw_output.stdout)
-                    .block_error("net", "Response contained non-UTF8 characters.")
-            })?;
-
-        let ip_devs: Vec<IpDev> =
-            serde_json::from_str(&output).block_error("net", "Failed to parse JSON response")?;
-
-        if ip_devs.is_empty() {
-            return Ok(Some("".to_string()));
-        }
+        let mut addresses = self
+            .addresses()?
+            .into_iter()
+            .filter_map(|ip| match ip {
+                IpAddr::V6(ip) => Some(ip),
+                IpAddr::V4(_) => None,
+            })
+            .collect::<Vec<_>>();
+        addresses.sort_by_key(|ip| ip.segments()[0] & 0xffc0 == 0xfe80);
+        Ok(Some(
+            addresses
+                .first()
+                .map(|ip| ip.to_string())
+                .unwrap_or_default(),
+        ))
+    }
 
-        let ip = ip_devs
-            .iter()
-            .flat_map(|dev| &dev.addr_info)
-            .filter_map(|addr| addr.local.clone())
-            .next();
-        Ok(ip)
+    /// The addresses of this device, as reported by `getifaddrs`.
+    fn addresses(&self) -> Result<Vec<IpAddr>> {
+        Ok(getifaddrs()
+            .block_error("net", "Failed to query the IP addresses.")?
+            .filter(|ifaddr| ifaddr.interface_name == self.device)
+            .filter_map(|ifaddr| match ifaddr.address {
+                Some(SockAddr::Inet(addr)) => Some(addr.to_std().ip()),
+                _ => None,
+            })
+            .collect())
     }
 
     /// Queries the bitrate of this device
@@ -310,6 +277,11 @@ impl NetworkDevice {
             return Ok(None);
         }
         if self.wireless {
+            if let Some(bitrate) = wireless::bitrate(&self.device) {
+                return Ok(Some(format!("{:.1} MBit/s", bitrate as f64 / 1e6)));
+            }
+
+            // Fall back to `iw` on kernels without wireless extensions
             let bitrate_output = Command::new("iw")
                 .args(&["dev", &self.device, "link"])
                 .output()
@@ -328,20 +300,10 @@ impl NetworkDevice {
                 Ok(None)
             }
         } else {
-            let output = Command::new("ethtool")
-                .arg(&self.device)
-                .output()
-                .block_error("net", "Failed to execute bitrate query with ethtool")?
-                .stdout;
-            if let Some(rate) = ETHTOOL_SPEED_REGEX.captures_iter(&output).next() {
-                let rate = rate
-                    .get(1)
-                    .block_error("net", "Invalid ethtool output: no speed")?;
-                String::from_utf8(rate.as_bytes().to_vec())
-                    .block_error("net", "Non-UTF8 bitrate")
-                    .map(Some)
-            } else {
-                Ok(None)
+            // The speed is in Mb/s, and unknown speeds are reported as -1 or not at all
+            match read_file(&self.device_path.join("speed")).map(|speed| speed.parse::<i64>()) {
+                Ok(Ok(speed)) if speed > 0 => Ok(Some(format!("{}Mb/s", speed))),
+                _ => Ok(None),
             }
         }
     }
@@ -371,6 +333,9 @@ pub struct Net {
     rx_buff: Vec<u64>,
     tx_bytes: u64,
     rx_bytes: u64,
+    /// The bytes per second of the last update, for specifications of the speeds
+    tx_rate: u64,
+    rx_rate: u64,
     use_bits: bool,
     speed_min_unit: Unit,
     speed_digits: usize,
@@ -379,7 +344,6 @@ pub struct Net {
     hide_inactive: bool,
     hide_missing: bool,
     last_update: Instant,
-    on_click: Option<String>,
 }
 
 #[derive(Copy, Clone, Debug, Deserialize)]
@@ -486,9 +450,6 @@ pub struct NetConfig {
     /// Whether to show the download throughput graph of active networks.
     #[serde(default = "NetConfig::default_graph_down")]
     pub graph_down: bool,
-
-    #[serde(default = "NetConfig::default_on_click")]
-    pub on_click: Option<String>,
 }
 
 impl NetConfig {
@@ -574,10 +535,6 @@ impl NetConfig {
     fn default_speed_digits() -> usize {
         3
     }
-
-    fn default_on_click() -> Option<String> {
-        None
-    }
 }
 
 impl ConfigBlock for Net {
@@ -588,7 +545,13 @@ impl ConfigBlock for Net {
         config: Config,
         _tx_update_request: Sender<Task>,
     ) -> Result<Self> {
-        let device = NetworkDevice::from_device(block_config.device);
+        // `device = "auto"` follows the default route like `auto_device`
+        let auto_device = block_config.auto_device || block_config.device == "auto";
+        let device = NetworkDevice::from_device(if block_config.device == "auto" {
+            NetConfig::default_device()
+        } else {
+            block_config.device
+        });
         let init_rx_bytes = device.rx_bytes().unwrap_or(0);
         let init_tx_bytes = device.tx_bytes().unwrap_or(0);
         let wireless = device.is_wireless();
@@ -612,11 +575,13 @@ impl ConfigBlock for Net {
             block_config.format
         };
 
+        let format =
+            FormatTemplate::from_string(&format).block_error("net", "Invalid format specified")?;
+
         Ok(Net {
             id: id.clone(),
             update_interval: block_config.interval,
-            format: FormatTemplate::from_string(&format)
-                .block_error("net", "Invalid format specified")?,
+            format,
             output: ButtonWidget::new(config.clone(), "")
                 .with_text("")
                 .with_spacing(Spacing::Inline),
@@ -631,36 +596,36 @@ impl ConfigBlock for Net {
             } else {
                 "net_wired"
             }),
-            // Might want to signal an error if the user wants the SSID of a
-            // wired connection instead.
-            ssid: if wireless && format.contains("{ssid}") {
+            // The device may change to a wireless one later, so these only depend on the format.
+            // They stay empty while the device is wired.
+            ssid: if format.has_placeholder("{ssid}") {
                 Some(" ".to_string())
             } else {
                 None
             },
             max_ssid_width: block_config.max_ssid_width,
-            signal_strength: if wireless && format.contains("{signal_strength}") {
+            signal_strength: if format.has_placeholder("{signal_strength}") {
                 Some(0.to_string())
             } else {
                 None
             },
-            signal_strength_bar: if wireless && format.contains("{signal_strength_bar}") {
+            signal_strength_bar: if format.has_placeholder("{signal_strength_bar}") {
                 Some("".to_string())
             } else {
                 None
             },
             // TODO: a better way to deal with this?
-            bitrate: if format.contains("{bitrate}") {
+            bitrate: if format.has_placeholder("{bitrate}") {
                 Some("".to_string())
             } else {
                 None
             },
-            ip_addr: if format.contains("{ip}") {
+            ip_addr: if format.has_placeholder("{ip}") {
                 Some("".to_string())
             } else {
                 None
             },
-            ipv6_addr: if format.contains("{ipv6}") {
+            ipv6_addr: if format.has_placeholder("{ipv6}") {
                 Some("".to_string())
             } else {
                 None
@@ -670,17 +635,18 @@ impl ConfigBlock for Net {
             graph_tx: Some("".to_string()),
             graph_rx: Some("".to_string()),
             device,
-            auto_device: block_config.auto_device,
+            auto_device,
             rx_buff: vec![0; 10],
             tx_buff: vec![0; 10],
             rx_bytes: init_rx_bytes,
             tx_bytes: init_tx_bytes,
+            tx_rate: 0,
+            rx_rate: 0,
             active: true,
             exists: true,
             hide_inactive: block_config.hide_inactive,
             hide_missing: block_config.hide_missing,
             last_update: Instant::now() - Duration::from_secs(30),
-            on_click: block_config.on_click,
         })
     }
 }
@@ -739,6 +705,11 @@ impl Net {
             let dev = NetConfig::default_device();
             if self.device.device() != dev {
                 self.device = NetworkDevice::from_device(dev);
+                // The counters of the previous device mean nothing for the new one
+                self.tx_bytes = self.device.tx_bytes().unwrap_or(0);
+                self.rx_bytes = self.device.rx_bytes().unwrap_or(0);
+                // Refresh the SSID, signal strength and addresses right away
+                self.last_update = Instant::now() - Duration::from_secs(30);
                 self.network.set_icon(if self.device.is_wireless() {
                     "net_wireless"
                 } else if self.device.is_vpn() {
@@ -762,6 +733,10 @@ impl Net {
 
     fn update_ssid(&mut self) -> Result<()> {
         if let Some(ref mut ssid_string) = self.ssid {
+            if !self.device.is_wireless() {
+                ssid_string.clear();
+                return Ok(());
+            }
             let ssid = self.device.ssid()?;
             if let Some(s) = ssid {
                 let mut truncated = s;
@@ -774,6 +749,15 @@ impl Net {
     }
 
     fn update_signal_strength(&mut self) -> Result<()> {
+        if !self.device.is_wireless() {
+            if let Some(ref mut signal_strength_string) = self.signal_strength {
+                signal_strength_string.clear();
+            }
+            if let Some(ref mut signal_strength_bar_string) = self.signal_strength_bar {
+                signal_strength_bar_string.clear();
+            }
+            return Ok(());
+        }
         if self.signal_strength.is_some() || self.signal_strength_bar.is_some() {
             let value = self.device.relative_signal_strength()?;
             if let Some(ref mut signal_strength_string) = self.signal_strength {
@@ -814,8 +798,10 @@ impl Net {
             + (self.update_interval.subsec_nanos() as f64 / 1_000_000_000.0);
         if self.output_tx.is_some() || self.graph_tx.is_some() {
             let current_tx = self.device.tx_bytes()?;
-            let tx_bytes = ((current_tx - self.tx_bytes) as f64 / update_interval) as u64;
+            let tx_bytes =
+                (current_tx.saturating_sub(self.tx_bytes) as f64 / update_interval) as u64;
             self.tx_bytes = current_tx;
+            self.tx_rate = tx_bytes;
 
             if let Some(ref mut tx) = self.output_tx {
                 *tx = format_speed(
@@ -834,8 +820,10 @@ impl Net {
         }
         if self.output_rx.is_some() || self.graph_rx.is_some() {
             let current_rx = self.device.rx_bytes()?;
-            let rx_bytes = ((current_rx - self.rx_bytes) as f64 / update_interval) as u64;
+            let rx_bytes =
+                (current_rx.saturating_sub(self.rx_bytes) as f64 / update_interval) as u64;
             self.rx_bytes = current_rx;
+            self.rx_rate = rx_bytes;
 
             if let Some(ref mut rx) = self.output_rx {
                 *rx = format_speed(
@@ -940,8 +928,19 @@ impl Block for Net {
             "{graph_down}" =>  self.graph_rx.as_ref().unwrap_or(&empty_string)
         );
 
+        // Specifications like `{speed_down:5;K}` format the speeds themselves
+        let (factor, unit) = if self.use_bits {
+            (8.0, "b")
+        } else {
+            (1.0, "B")
+        };
+        let numbers = map!(
+            "{speed_up}" => Number { value: self.tx_rate as f64 * factor, unit },
+            "{speed_down}" => Number { value: self.rx_rate as f64 * factor, unit }
+        );
+
         self.output
-            .set_text(self.format.render_static_str(&values)?);
+            .set_text(self.format.render_numbers(&values, &numbers)?);
 
         Ok(Some(self.update_interval.into()))
     }
@@ -956,37 +955,16 @@ impl Block for Net {
         }
     }
 
-    fn click(&mut self, e: &I3BarEvent) -> Result<()> {
-        if let Some(ref name) = e.name {
-            if name.as_str() == self.id {
-                if let MouseButton::Left = e.button {
-                    if let Some(ref cmd) = self.on_click {
-                        spawn_child_async("sh", &["-c", cmd])
-                            .block_error("net", "could not spawn child")?;
-                    }
-                }
-            }
-        }
-
-        Ok(())
-    }
-
     fn id(&self) -> &str {
         &self.id
     }
 }
 
-#[derive(Deserialize)]
-struct IpDev {
-    addr_info: Vec<IpAddrInfo>,
-}
-
-#[derive(Deserialize)]
-struct IpAddrInfo {
-    local: Option<String>,
-}
-
 fn get_ssid(dev: &NetworkDevice) -> Result<Option<String>> {
+    if let Some(res) = wireless::ssid(&dev.device) {
+        return Ok(Some(res));
+    }
+
     if let Some(res) = get_iw_ssid(dev)? {
         return Ok(Some(res));
     }
@@ -1137,3 +1115,223 @@ fn maybe_ssid_convert(raw: Option<&[u8]>) -> Result<Option<String>> {
         Ok(None)
     }
 }
+
+/// Converts a signal level in dBm to a quality in percent.
+pub fn signal_quality(dbm: i32) -> u32 {
+    // Code inspired by https://github.com/NetworkManager/NetworkManager/blob/master/src/platform/wifi/nm-wifi-utils-nl80211.c
+    const NOISE_FLOOR_DBM: f64 = -90.;
+    const SIGNAL_MAX_DBM: f64 = -20.;
+
+    let xbm = (dbm as f64).max(NOISE_FLOOR_DBM).min(SIGNAL_MAX_DBM);
+    let result = 100. - 70. * ((SIGNAL_MAX_DBM - xbm) / (SIGNAL_MAX_DBM - NOISE_FLOOR_DBM));
+    result as u32
+}
+
+/// Finds the device of the IPv4 default route with the lowest metric in the contents of
+/// `/proc/net/route`.
+fn default_route_device(routes: &str) -> Option<String> {
+    const RTF_UP: u32 = 0x1;
+    routes
+        .lines()
+        .skip(1)
+        .filter_map(|route| {
+            let fields = route.split_whitespace().collect::<Vec<_>>();
+            let flags = u32::from_str_radix(fields.get(3)?, 16).ok()?;
+            let metric = fields.get(6)?.parse::<u32>().ok()?;
+            if fields[1] == "00000000" && *fields.get(7)? == "00000000" && flags & RTF_UP != 0 {
+                Some((metric, fields[0]))
+            } else {
+                None
+            }
+        })
+        .min()
+        .map(|(_, device)| device.to_string())
+}
+
+/// Finds the device of the IPv6 default route with the lowest metric in the contents of
+/// `/proc/net/ipv6_route`.
+fn default_route6_device(routes: &str) -> Option<String> {
+    const RTF_UP: u32 = 0x1;
+    const RTF_REJECT: u32 = 0x200;
+    routes
+        .lines()
+        .filter_map(|route| {
+            let fields = route.split_whitespace().collect::<Vec<_>>();
+            let metric = u32::from_str_radix(fields.get(5)?, 16).ok()?;
+            let flags = u32::from_str_radix(fields.get(8)?, 16).ok()?;
+            if fields[0].bytes().all(|b| b == b'0')
+                && fields[1] == "00"
+                && flags & RTF_UP != 0
+                && flags & RTF_REJECT == 0
+            {
+                Some((metric, *fields.get(9)?))
+            } else {
+                None
+            }
+        })
+        .min()
+        .map(|(_, device)| device.to_string())
+}
+
+/// Queries of wireless devices through the wireless extensions ioctls of `linux/wireless.h`,
+/// which cfg80211 drivers still support for compatibility.
+mod wireless {
+    use std::mem;
+
+    use nix::libc::{self, c_void};
+
+    const SIOCGIWRATE: u32 = 0x8B21;
+    const SIOCGIWESSID: u32 = 0x8B1B;
+    const SIOCGIWSTATS: u32 = 0x8B0F;
+    const IW_ESSID_MAX_SIZE: usize = 32;
+    const IW_QUAL_LEVEL_INVALID: u8 = 0x20;
+    const IW_QUAL_DBM: u8 = 0x08;
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct IwPoint {
+        pointer: *mut c_void,
+        length: u16,
+        flags: u16,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    struct IwParam {
+        value: i32,
+        fixed: u8,
+        disabled: u8,
+        flags: u16,
+    }
+
+    #[repr(C)]
+    #[derive(Clone, Copy)]
+    union IwReqData {
+        point: IwPoint,
+        param: IwParam,
+    }
+
+    #[repr(C)]
+    struct IwReq {
+        name: [u8; libc::IFNAMSIZ],
+        data: IwReqData,
+    }
+
+    #[repr(C)]
+    #[derive(Default)]
+    struct IwStatistics {
+        status: u16,
+        quality: u8,
+        level: u8,
+        noise: u8,
+        updated: u8,
+        discarded: [u32; 5],
+        missed: u32,
+    }
+
+    /// Runs an ioctl on the device and returns the data that it filled in.
+    fn ioctl(device: &str, request: u32, data: IwReqData) -> Option<IwReqData> {
+        let mut req = IwReq {
+            name: [0; libc::IFNAMSIZ],
+            data,
+        };
+        // The name has to be null-terminated
+        let name = device.as_bytes();
+        if name.len() >= libc::IFNAMSIZ {
+            return None;
+        }
+        req.name[..name.len()].copy_from_slice(name);
+        unsafe {
+            let socket = libc::socket(libc::AF_INET, libc::SOCK_DGRAM, 0);
+            if socket < 0 {
+                return None;
+            }
+            let result = libc::ioctl(socket, request as _, &mut req);
+            libc::close(socket);
+            if result < 0 {
+                None
+            } else {
+                Some(req.data)
+            }
+        }
+    }
+
+    fn point<T>(buffer: &mut T) -> IwReqData {
+        IwReqData {
+            point: IwPoint {
+                pointer: buffer as *mut T as *mut c_void,
+                length: mem::size_of::<T>() as u16,
+                flags: 0,
+            },
+        }
+    }
+
+    /// The SSID of the network the device is associated with.
+    pub fn ssid(device: &str) -> Option<String> {
+        let mut essid = [0u8; IW_ESSID_MAX_SIZE + 1];
+        let data = ioctl(device, SIOCGIWESSID, point(&mut essid))?;
+        let length = (unsafe { data.point.length } as usize).min(IW_ESSID_MAX_SIZE);
+        if length == 0 {
+            return None;
+        }
+        String::from_utf8(essid[..length].to_vec()).ok()
+    }
+
+    /// The signal level in dBm.
+    pub fn signal_dbm(device: &str) -> Option<i32> {
+        let mut stats = IwStatistics::default();
+        ioctl(device, SIOCGIWSTATS, point(&mut stats))?;
+        if stats.updated & IW_QUAL_LEVEL_INVALID != 0 || stats.updated & IW_QUAL_DBM == 0 {
+            return None;
+        }
+        // The level is a signed value stored in an unsigned byte
+        Some(i32::from(stats.level as i8))
+    }
+
+    /// The transmit bitrate in bit/s.
+    pub fn bitrate(device: &str) -> Option<i32> {
+        let data = IwReqData {
+            param: IwParam {
+                value: 0,
+                fixed: 0,
+                disabled: 0,
+                flags: 0,
+            },
+        };
+        let data = ioctl(device, SIOCGIWRATE, data)?;
+        let param = unsafe { data.param };
+        if param.disabled != 0 || param.value <= 0 {
+            None
+        } else {
+            Some(param.value)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{default_route6_device, default_route_device};
+
+    #[test]
+    fn test_default_route_device() {
+        let routes = concat!(
+            "Iface\tDestination\tGateway \tFlags\tRefCnt\tUse\tMetric\tMask\t\tMTU\tWindow\tIRTT\n",
+            "wlan0\t00000000\t0101A8C0\t0003\t0\t0\t600\t00000000\t0\t0\t0\n",
+            "eth0\t00000000\t0100A8C0\t0003\t0\t0\t100\t00000000\t0\t0\t0\n",
+            "eth0\t0000A8C0\t00000000\t0001\t0\t0\t100\t00FFFFFF\t0\t0\t0\n",
+        );
+        assert_eq!(default_route_device(routes), Some("eth0".to_string()));
+        assert_eq!(default_route_device("Iface\tDestination\n"), None);
+    }
+
+    #[test]
+    fn test_default_route6_device() {
+        let routes = concat!(
+            "00000000000000000000000000000000 00 00000000000000000000000000000000 00 ",
+            "fe800000000000000000000000000001 00000258 00000001 00000000 00000003 wlan0\n",
+            "00000000000000000000000000000000 00 00000000000000000000000000000000 00 ",
+            "00000000000000000000000000000000 ffffffff 00000001 00000000 00200200 lo\n",
+        );
+        assert_eq!(default_route6_device(routes), Some("wlan0".to_string()));
+    }
+}
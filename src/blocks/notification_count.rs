@@ -0,0 +1,159 @@
+//! A block for the notifications that dunst holds back or keeps in its history.
+
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use dbus::ffidisp::stdintf::org_freedesktop_dbus::Properties;
+use dbus::ffidisp::{BusType, Connection};
+use dbus::Message;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+const DUNST_INTERFACE: &str = "org.dunstproject.cmd0";
+
+pub struct NotificationCount {
+    id: String,
+    output: ButtonWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    dbus_conn: Connection,
+    send: Sender<Task>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationCountConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "NotificationCountConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format string
+    #[serde(default = "NotificationCountConfig::default_format")]
+    pub format: String,
+}
+
+impl NotificationCountConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    fn default_format() -> String {
+        "{count}".to_owned()
+    }
+}
+
+impl ConfigBlock for NotificationCount {
+    type Config = NotificationCountConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+        Ok(NotificationCount {
+            output: ButtonWidget::new(config, &id).with_icon("bell"),
+            id,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("notification_count", "Invalid format specified")?,
+            dbus_conn: Connection::get_private(BusType::Session)
+                .block_error("notification_count", "failed to establish D-Bus connection")?,
+            send,
+        })
+    }
+}
+
+impl NotificationCount {
+    fn length(&self, property: &str) -> Result<u32> {
+        self.dbus_conn
+            .with_path(
+                "org.freedesktop.Notifications",
+                "/org/freedesktop/Notifications",
+                1000,
+            )
+            .get(DUNST_INTERFACE, property)
+            .block_error(
+                "notification_count",
+                "Failed to get the notifications of dunst. Is it running?",
+            )
+    }
+
+    fn call(&self, method: &str) -> Result<()> {
+        let m = Message::new_method_call(
+            "org.freedesktop.Notifications",
+            "/org/freedesktop/Notifications",
+            DUNST_INTERFACE,
+            method,
+        )
+        .block_error("notification_count", "failed to create D-Bus method call")?;
+        self.dbus_conn
+            .send_with_reply_and_block(m, 1000)
+            .block_error("notification_count", &format!("dunst failed to {}", method))?;
+        Ok(())
+    }
+}
+
+impl Block for NotificationCount {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let waiting = self.length("waitingLength")?;
+        let displayed = self.length("displayedLength")?;
+        let history = self.length("historyLength")?;
+
+        let values = map!(
+            "{count}" => (waiting + history).to_string(),
+            "{waiting}" => waiting.to_string(),
+            "{displayed}" => displayed.to_string(),
+            "{history}" => history.to_string()
+        );
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
+        self.output.set_state(if waiting + history > 0 {
+            State::Info
+        } else {
+            State::Idle
+        });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if !event.matches_name(&self.id) {
+            return Ok(());
+        }
+        match event.button {
+            // Shows the last notification again, like `dunstctl history-pop`
+            MouseButton::Left => self.call("NotificationShow")?,
+            MouseButton::Right => {
+                self.call("NotificationCloseAll")?;
+                self.call("NotificationClearHistory")?;
+            }
+            _ => return Ok(()),
+        }
+        self.send.send(Task {
+            id: self.id.clone(),
+            update_time: Instant::now(),
+        })?;
+        Ok(())
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        matches!(event.button, MouseButton::Left | MouseButton::Right)
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
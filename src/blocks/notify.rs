@@ -1,6 +1,7 @@
+use std::process::Command;
 use std::sync::{Arc, Mutex};
 use std::thread;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use dbus::ffidisp::stdintf::org_freedesktop_dbus::{Properties, PropertiesPropertiesChanged};
@@ -10,6 +11,7 @@ use serde_derive::Deserialize;
 
 use crate::blocks::{Block, ConfigBlock, Update};
 use crate::config::Config;
+use crate::de::deserialize_opt_duration;
 use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
@@ -17,14 +19,29 @@ use crate::util::{pseudo_uuid, FormatTemplate};
 use crate::widget::I3BarWidget;
 use crate::widgets::button::ButtonWidget;
 
-// TODO
-// Add driver option so can choose between dunst, mako, etc.
-
 pub struct Notify {
     id: String,
-    paused: Arc<Mutex<i64>>,
+    driver: NotifyDriver,
+    /// Interval to poll the state at, or `None` while it is followed over D-Bus
+    poll_interval: Option<Duration>,
+    mako_mode: String,
+    paused: Arc<Mutex<bool>>,
     format: FormatTemplate,
     output: ButtonWidget,
+    send: Sender<Task>,
+}
+
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyDriver {
+    Dunst,
+    Mako,
+}
+
+impl Default for NotifyDriver {
+    fn default() -> Self {
+        NotifyDriver::Dunst
+    }
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -33,6 +50,18 @@ pub struct NotifyConfig {
     /// Format string for displaying phone information.
     #[serde(default = "NotifyConfig::default_format")]
     pub format: String,
+
+    /// The notification daemon
+    #[serde(default)]
+    pub driver: NotifyDriver,
+
+    /// Poll the state at this interval instead of following D-Bus signals
+    #[serde(default, deserialize_with = "deserialize_opt_duration")]
+    pub interval: Option<Duration>,
+
+    /// The mode of mako that stands for do not disturb
+    #[serde(default = "NotifyConfig::default_mako_mode")]
+    pub mako_mode: String,
 }
 
 impl NotifyConfig {
@@ -40,73 +69,119 @@ impl NotifyConfig {
         // display just the bell icon
         "".into()
     }
+
+    fn default_mako_mode() -> String {
+        "do-not-disturb".into()
+    }
 }
 
-impl ConfigBlock for Notify {
-    type Config = NotifyConfig;
+/// Whether the output of `makoctl mode`, one mode per line, contains the mode.
+fn has_mako_mode(output: &str, mode: &str) -> bool {
+    output.lines().any(|line| line.trim() == mode)
+}
 
-    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
-        let id: String = pseudo_uuid();
-        let id1 = id.clone();
+fn dunst_paused() -> Result<bool> {
+    let c = Connection::get_private(BusType::Session)
+        .block_error("notify", "Failed to establish D-Bus connection")?;
+    c.with_path(
+        "org.freedesktop.Notifications",
+        "/org/freedesktop/Notifications",
+        5000,
+    )
+    .get("org.dunstproject.cmd0", "paused")
+    .block_error("notify", "Failed to get dunst state. Is it running?")
+}
 
-        let c = Connection::get_private(BusType::Session).block_error(
-            "notify",
-            &"Failed to establish D-Bus connection".to_string(),
-        )?;
+fn mako_paused(mode: &str) -> Result<bool> {
+    let output = Command::new("makoctl")
+        .arg("mode")
+        .output()
+        .block_error("notify", "Failed to run makoctl. Is mako installed?")?;
+    if !output.status.success() {
+        return Err(BlockError(
+            "notify".to_string(),
+            "Failed to get mako modes. Is it running?".to_string(),
+        ));
+    }
+    Ok(has_mako_mode(
+        &String::from_utf8_lossy(&output.stdout),
+        mode,
+    ))
+}
 
-        let p = c.with_path(
-            "org.freedesktop.Notifications",
-            "/org/freedesktop/Notifications",
-            5000,
-        );
-        let initial_state: bool = p.get("org.dunstproject.cmd0", "paused").block_error(
-            "notify",
-            &"Failed to get dunst state. Is it running?".to_string(),
-        )?;
+/// Follows the pause state of dunst, which it announces with a signal.
+fn watch_dunst(paused: Arc<Mutex<bool>>, id: String, send: Sender<Task>) {
+    thread::Builder::new()
+        .name("notify".into())
+        .spawn(move || {
+            let c = Connection::get_private(BusType::Session)
+                .expect("Failed to establish D-Bus connection in thread");
 
-        let icon = if initial_state { "bell-slash" } else { "bell" };
+            let matched_signal = PropertiesPropertiesChanged::match_str(
+                Some(&"org.freedesktop.Notifications".into()),
+                None,
+            );
+            c.add_match(&matched_signal).unwrap();
+            loop {
+                for msg in c.incoming(1000) {
+                    if let Some(signal) = PropertiesPropertiesChanged::from_message(&msg) {
+                        // Other properties of dunst change as well
+                        let status = match signal
+                            .changed_properties
+                            .get("paused")
+                            .and_then(|value| value.0.as_i64())
+                        {
+                            Some(status) => status,
+                            None => continue,
+                        };
+                        *paused.lock().unwrap() = status == 1;
 
-        // TODO: revisit this lint
-        #[allow(clippy::mutex_atomic)]
-        let state = Arc::new(Mutex::new(initial_state as i64));
-        let state_copy = state.clone();
-
-        thread::Builder::new()
-            .name("notify".into())
-            .spawn(move || {
-                let c = Connection::get_private(BusType::Session)
-                    .expect("Failed to establish D-Bus connection in thread");
-
-                let matched_signal = PropertiesPropertiesChanged::match_str(
-                    Some(&"org.freedesktop.Notifications".into()),
-                    None,
-                );
-                c.add_match(&matched_signal).unwrap();
-                loop {
-                    for msg in c.incoming(1000) {
-                        if let Some(signal) = PropertiesPropertiesChanged::from_message(&msg) {
-                            let value = signal.changed_properties.get("paused").unwrap();
-                            let status = &value.0.as_i64().unwrap();
-                            let mut paused = state_copy.lock().unwrap();
-                            *paused = *status;
-
-                            // Tell block to update now.
-                            send.send(Task {
-                                id: id1.clone(),
+                        // Tell block to update now.
+                        if send
+                            .send(Task {
+                                id: id.clone(),
                                 update_time: Instant::now(),
                             })
-                            .unwrap();
+                            .is_err()
+                        {
+                            return;
                         }
                     }
                 }
-            })
-            .unwrap();
+            }
+        })
+        .unwrap();
+}
+
+impl ConfigBlock for Notify {
+    type Config = NotifyConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let id: String = pseudo_uuid();
+
+        // mako does not announce a change of mode, so it is always polled
+        let poll_interval = match block_config.driver {
+            NotifyDriver::Dunst => block_config.interval,
+            NotifyDriver::Mako => block_config.interval.or(Some(Duration::from_secs(5))),
+        };
+
+        // TODO: revisit this lint
+        #[allow(clippy::mutex_atomic)]
+        let paused = Arc::new(Mutex::new(false));
+        if poll_interval.is_none() {
+            *paused.lock().unwrap() = dunst_paused()?;
+            watch_dunst(paused.clone(), id.clone(), send.clone());
+        }
 
         Ok(Notify {
+            output: ButtonWidget::new(config, &id).with_icon("bell"),
             id,
-            paused: state,
+            driver: block_config.driver,
+            poll_interval,
+            mako_mode: block_config.mako_mode,
+            paused,
             format: FormatTemplate::from_string(&block_config.format)?,
-            output: ButtonWidget::new(config, "notify").with_icon(icon),
+            send,
         })
     }
 }
@@ -117,22 +192,28 @@ impl Block for Notify {
     }
 
     fn update(&mut self) -> Result<Option<Update>> {
-        let paused = *self
+        let mut paused = self
             .paused
             .lock()
             .block_error("notify", "failed to acquire lock for `state`")?;
+        if self.poll_interval.is_some() {
+            *paused = match self.driver {
+                NotifyDriver::Dunst => dunst_paused()?,
+                NotifyDriver::Mako => mako_paused(&self.mako_mode)?,
+            };
+        }
 
         let values = map!(
-            "{state}" => paused.to_string()
+            "{state}" => (*paused as i64).to_string()
         );
 
         self.output
             .set_text(self.format.render_static_str(&values)?);
 
-        let icon = if paused == 1 { "bell-slash" } else { "bell" };
+        let icon = if *paused { "bell-slash" } else { "bell" };
         self.output.set_icon(icon);
 
-        Ok(None)
+        Ok(self.poll_interval.map(|d| d.into()))
     }
 
     // Returns the view of the block, comprised of widgets.
@@ -141,34 +222,58 @@ impl Block for Notify {
     }
 
     fn click(&mut self, e: &I3BarEvent) -> Result<()> {
-        if e.name.as_ref().map(|s| s == "notify").unwrap_or(false) && e.button == MouseButton::Left
-        {
-            let c = Connection::get_private(BusType::Session).block_error(
-                "notify",
-                &"Failed to establish D-Bus connection".to_string(),
-            )?;
-
-            let p = c.with_path(
-                "org.freedesktop.Notifications",
-                "/org/freedesktop/Notifications",
-                5000,
-            );
-
+        if e.matches_name(&self.id) && e.button == MouseButton::Left {
             let paused = *self
                 .paused
                 .lock()
                 .block_error("notify", "failed to acquire lock")?;
 
-            if paused == 1 {
-                p.set("org.dunstproject.cmd0", "paused", false)
-                    .block_error("notify", &"Failed to query D-Bus".to_string())?;
-            } else {
-                p.set("org.dunstproject.cmd0", "paused", true)
-                    .block_error("notify", &"Failed to query D-Bus".to_string())?;
+            match self.driver {
+                NotifyDriver::Dunst => {
+                    let c = Connection::get_private(BusType::Session)
+                        .block_error("notify", "Failed to establish D-Bus connection")?;
+
+                    let p = c.with_path(
+                        "org.freedesktop.Notifications",
+                        "/org/freedesktop/Notifications",
+                        5000,
+                    );
+                    p.set("org.dunstproject.cmd0", "paused", !paused)
+                        .block_error("notify", "Failed to query D-Bus")?;
+                }
+                NotifyDriver::Mako => {
+                    let flag = if paused { "-r" } else { "-a" };
+                    Command::new("makoctl")
+                        .args(&["mode", flag, &self.mako_mode])
+                        .output()
+                        .block_error("notify", "Failed to run makoctl")?;
+                }
             }
 
-            // block will auto-update due to monitoring the bus
+            // While following D-Bus, the block updates from the signal
+            if self.poll_interval.is_some() {
+                self.send.send(Task {
+                    id: self.id.clone(),
+                    update_time: Instant::now(),
+                })?;
+            }
         }
         Ok(())
     }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::has_mako_mode;
+
+    #[test]
+    fn test_has_mako_mode() {
+        assert!(has_mako_mode("default\ndo-not-disturb\n", "do-not-disturb"));
+        assert!(!has_mako_mode("default\n", "do-not-disturb"));
+        assert!(!has_mako_mode("", "do-not-disturb"));
+    }
 }
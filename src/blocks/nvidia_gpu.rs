@@ -482,6 +482,10 @@ impl Block for NvidiaGpu {
         Ok(())
     }
 
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left
+    }
+
     fn id(&self) -> &str {
         &self.id
     }
@@ -4,7 +4,8 @@ use std::fs;
 use std::os::unix::fs::symlink;
 use std::path::Path;
 use std::process::Command;
-use std::time::Duration;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use crossbeam_channel::Sender;
 use regex::Regex;
@@ -31,6 +32,8 @@ pub struct Pacman {
     warning_updates_regex: Option<Regex>,
     critical_updates_regex: Option<Regex>,
     watched: Watched,
+    update_command: Option<String>,
+    tx_update_request: Sender<Task>,
 }
 
 #[derive(Debug, PartialEq, Eq)]
@@ -78,6 +81,11 @@ pub struct PacmanConfig {
     /// Optional AUR command, listing available updates
     #[serde()]
     pub aur_command: Option<String>,
+
+    /// Optional command to run on a left click, like a terminal running the upgrade.
+    /// The updates are checked again once it exits.
+    #[serde(default)]
+    pub update_command: Option<String>,
 }
 
 impl PacmanConfig {
@@ -146,10 +154,10 @@ impl ConfigBlock for Pacman {
     fn new(
         block_config: Self::Config,
         config: Config,
-        _tx_update_request: Sender<Task>,
+        tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        let id = pseudo_uuid();
         Ok(Pacman {
-            id: pseudo_uuid(),
             update_interval: block_config.interval,
             format: FormatTemplate::from_string(&block_config.format)
                 .block_error("pacman", "Invalid format specified for pacman::format")?,
@@ -163,7 +171,8 @@ impl ConfigBlock for Pacman {
                     "pacman",
                     "Invalid format specified for pacman::format_up_to_date",
                 )?,
-            output: ButtonWidget::new(config, "pacman").with_icon("update"),
+            output: ButtonWidget::new(config, &id).with_icon("update"),
+            id,
             warning_updates_regex: match block_config.warning_updates_regex {
                 None => None, // no regex configured
                 Some(regex_str) => {
@@ -200,6 +209,8 @@ impl ConfigBlock for Pacman {
                 &block_config.format_up_to_date,
                 block_config.aur_command,
             )?,
+            update_command: block_config.update_command,
+            tx_update_request,
         })
     }
 }
@@ -399,14 +410,39 @@ impl Block for Pacman {
     }
 
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
-        if event.name.as_ref().map(|s| s == "pacman").unwrap_or(false)
-            && event.button == MouseButton::Left
-        {
-            self.update()?;
+        if !event.matches_name(&self.id) {
+            return Ok(());
+        }
+        match (event.button, &self.update_command) {
+            (MouseButton::Left, Some(command)) => {
+                // The upgrade takes a while, so the bar must not wait for it
+                let command = command.clone();
+                let id = self.id.clone();
+                let tx = self.tx_update_request.clone();
+                thread::Builder::new()
+                    .name("pacman".into())
+                    .spawn(move || {
+                        if run_command(&command).is_ok() {
+                            let _ = tx.send(Task {
+                                id,
+                                update_time: Instant::now(),
+                            });
+                        }
+                    })
+                    .block_error("pacman", "failed to start the update command")?;
+            }
+            (MouseButton::Left, None) | (MouseButton::Right, _) => {
+                self.update()?;
+            }
+            _ => {}
         }
 
         Ok(())
     }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left && self.update_command.is_some()
+    }
 }
 
 #[cfg(test)]
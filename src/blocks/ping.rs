@@ -0,0 +1,410 @@
+//! A block for the latency and packet loss to some hosts.
+
+use std::collections::VecDeque;
+use std::io;
+use std::net::{IpAddr, SocketAddr, TcpStream, ToSocketAddrs, UdpSocket};
+use std::os::unix::io::FromRawFd;
+use std::process;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use nix::libc;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Method {
+    Icmp,
+    Tcp,
+}
+
+/// The results of the latest round of probes.
+#[derive(Debug, Default, Clone)]
+struct Summary {
+    /// Average round trip time in ms, if any probe was answered
+    rtt: Option<f64>,
+    /// Percentage of the probes that were not answered
+    loss: f64,
+    /// Average round trip time of the last rounds in ms
+    average: Option<f64>,
+}
+
+pub struct Ping {
+    id: String,
+    text: TextWidget,
+    format: FormatTemplate,
+    summary: Arc<Mutex<Option<Summary>>>,
+    rtt_warning: f64,
+    rtt_critical: f64,
+    loss_warning: f64,
+    loss_critical: f64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PingConfig {
+    /// Hosts to probe
+    #[serde(default = "PingConfig::default_hosts")]
+    pub hosts: Vec<String>,
+
+    /// Whether to probe with ICMP echo requests or TCP connections
+    #[serde(default = "PingConfig::default_method")]
+    pub method: Method,
+
+    /// Port of the TCP connections
+    #[serde(default = "PingConfig::default_port")]
+    pub port: u16,
+
+    /// Number of probes sent to each host per update
+    #[serde(default = "PingConfig::default_count")]
+    pub count: u32,
+
+    /// Time to wait for an answer to a probe
+    #[serde(
+        default = "PingConfig::default_timeout",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub timeout: Duration,
+
+    /// Update interval in seconds
+    #[serde(
+        default = "PingConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Number of updates that `{average}` covers
+    #[serde(default = "PingConfig::default_history")]
+    pub history: usize,
+
+    #[serde(default = "PingConfig::default_format")]
+    pub format: String,
+
+    /// Round trip time in ms at or above which the state is warning
+    #[serde(default = "PingConfig::default_rtt_warning")]
+    pub rtt_warning: f64,
+
+    /// Round trip time in ms at or above which the state is critical
+    #[serde(default = "PingConfig::default_rtt_critical")]
+    pub rtt_critical: f64,
+
+    /// Packet loss in percent at or above which the state is warning
+    #[serde(default = "PingConfig::default_loss_warning")]
+    pub loss_warning: f64,
+
+    /// Packet loss in percent at or above which the state is critical
+    #[serde(default = "PingConfig::default_loss_critical")]
+    pub loss_critical: f64,
+}
+
+impl PingConfig {
+    fn default_hosts() -> Vec<String> {
+        vec!["1.1.1.1".to_owned()]
+    }
+
+    fn default_method() -> Method {
+        Method::Icmp
+    }
+
+    fn default_port() -> u16 {
+        443
+    }
+
+    fn default_count() -> u32 {
+        3
+    }
+
+    fn default_timeout() -> Duration {
+        Duration::from_secs(1)
+    }
+
+    fn default_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn default_history() -> usize {
+        10
+    }
+
+    fn default_format() -> String {
+        "{rtt}".to_owned()
+    }
+
+    fn default_rtt_warning() -> f64 {
+        100.
+    }
+
+    fn default_rtt_critical() -> f64 {
+        300.
+    }
+
+    fn default_loss_warning() -> f64 {
+        10.
+    }
+
+    fn default_loss_critical() -> f64 {
+        50.
+    }
+}
+
+/// The internet checksum of RFC 1071.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum = data
+        .chunks(2)
+        .map(|word| u32::from(word[0]) << 8 | u32::from(*word.get(1).unwrap_or(&0)))
+        .sum::<u32>();
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Opens an ICMP socket. Unprivileged datagram sockets are allowed for the groups in
+/// `net.ipv4.ping_group_range`, raw sockets need `CAP_NET_RAW`. Returns whether the
+/// socket is raw.
+fn icmp_socket(v6: bool) -> io::Result<(UdpSocket, bool)> {
+    let (domain, protocol) = if v6 {
+        (libc::AF_INET6, libc::IPPROTO_ICMPV6)
+    } else {
+        (libc::AF_INET, libc::IPPROTO_ICMP)
+    };
+    for &(kind, raw) in &[(libc::SOCK_DGRAM, false), (libc::SOCK_RAW, true)] {
+        let fd = unsafe { libc::socket(domain, kind | libc::SOCK_CLOEXEC, protocol) };
+        if fd >= 0 {
+            // The socket is used like a UDP socket, with `send_to` and `recv_from`
+            return Ok((unsafe { UdpSocket::from_raw_fd(fd) }, raw));
+        }
+    }
+    Err(io::Error::last_os_error())
+}
+
+/// Sends an ICMP echo request and waits for the reply.
+fn icmp_probe(address: IpAddr, seq: u16, timeout: Duration) -> io::Result<Option<Duration>> {
+    let v6 = address.is_ipv6();
+    let (socket, raw) = icmp_socket(v6)?;
+    let id = process::id() as u16;
+    let mut request = [0u8; 16];
+    request[0] = if v6 { 128 } else { 8 };
+    request[4..6].copy_from_slice(&id.to_be_bytes());
+    request[6..8].copy_from_slice(&seq.to_be_bytes());
+    // The kernel computes the checksum of ICMPv6 itself
+    if !v6 {
+        let sum = checksum(&request);
+        request[2..4].copy_from_slice(&sum.to_be_bytes());
+    }
+
+    let start = Instant::now();
+    socket.send_to(&request, SocketAddr::new(address, 0))?;
+    let mut buffer = [0u8; 1500];
+    loop {
+        let remaining = match timeout.checked_sub(start.elapsed()) {
+            Some(remaining) if remaining > Duration::from_millis(0) => remaining,
+            _ => return Ok(None),
+        };
+        socket.set_read_timeout(Some(remaining))?;
+        let (len, from) = match socket.recv_from(&mut buffer) {
+            Ok(received) => received,
+            Err(ref e)
+                if e.kind() == io::ErrorKind::WouldBlock || e.kind() == io::ErrorKind::TimedOut =>
+            {
+                return Ok(None)
+            }
+            Err(e) => return Err(e),
+        };
+        if from.ip() != address {
+            continue;
+        }
+        // Raw IPv4 sockets receive the IP header as well
+        let header = if raw && !v6 {
+            usize::from(buffer[0] & 0x0f) * 4
+        } else {
+            0
+        };
+        let reply = &buffer[header.min(len)..len];
+        // Datagram sockets replace the id, and only receive their own replies
+        let echo_reply = if v6 { 129 } else { 0 };
+        if reply.len() >= 8
+            && reply[0] == echo_reply
+            && (!raw || reply[4..6] == id.to_be_bytes())
+            && reply[6..8] == seq.to_be_bytes()
+        {
+            return Ok(Some(start.elapsed()));
+        }
+    }
+}
+
+/// Opens a TCP connection. A refused connection counts as an answer too.
+fn tcp_probe(address: SocketAddr, timeout: Duration) -> Option<Duration> {
+    let start = Instant::now();
+    match TcpStream::connect_timeout(&address, timeout) {
+        Ok(_) => Some(start.elapsed()),
+        Err(ref e) if e.kind() == io::ErrorKind::ConnectionRefused => Some(start.elapsed()),
+        Err(_) => None,
+    }
+}
+
+/// Probes every host `count` times, and returns the round trip times of the answers and
+/// the number of probes.
+fn probe_hosts(config: &PingConfig, seq: &mut u16) -> (Vec<Duration>, u32) {
+    let mut rtts = Vec::new();
+    let mut sent = 0;
+    for host in &config.hosts {
+        // Hosts that cannot be resolved count as lost
+        let address = (host.as_str(), config.port)
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addresses| addresses.next());
+        for _ in 0..config.count {
+            sent += 1;
+            *seq = seq.wrapping_add(1);
+            let rtt = match (address, config.method) {
+                (Some(address), Method::Icmp) => {
+                    icmp_probe(address.ip(), *seq, config.timeout).unwrap_or(None)
+                }
+                (Some(address), Method::Tcp) => tcp_probe(address, config.timeout),
+                (None, _) => None,
+            };
+            rtts.extend(rtt);
+        }
+    }
+    (rtts, sent)
+}
+
+fn millis(duration: &Duration) -> f64 {
+    duration.as_secs() as f64 * 1000. + f64::from(duration.subsec_nanos()) / 1_000_000.
+}
+
+impl ConfigBlock for Ping {
+    type Config = PingConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        if block_config.method == Method::Icmp {
+            icmp_socket(false).block_error(
+                "ping",
+                "ICMP needs your group in net.ipv4.ping_group_range or CAP_NET_RAW, \
+                 use method = \"tcp\" otherwise",
+            )?;
+        }
+
+        let id = pseudo_uuid();
+        let summary = Arc::new(Mutex::new(None));
+        let thread_summary = summary.clone();
+        let thread_id = id.clone();
+        let thread_config = block_config.clone();
+        thread::Builder::new()
+            .name("ping".into())
+            .spawn(move || {
+                let mut seq = 0;
+                let mut history = VecDeque::new();
+                loop {
+                    let (rtts, sent) = probe_hosts(&thread_config, &mut seq);
+                    let rtt = if rtts.is_empty() {
+                        None
+                    } else {
+                        Some(rtts.iter().map(millis).sum::<f64>() / rtts.len() as f64)
+                    };
+                    if let Some(rtt) = rtt {
+                        history.push_back(rtt);
+                        while history.len() > thread_config.history.max(1) {
+                            history.pop_front();
+                        }
+                    }
+                    let average = if history.is_empty() {
+                        None
+                    } else {
+                        Some(history.iter().sum::<f64>() / history.len() as f64)
+                    };
+                    let loss = if sent == 0 {
+                        0.
+                    } else {
+                        100. * f64::from(sent - rtts.len() as u32) / f64::from(sent)
+                    };
+                    *thread_summary.lock().unwrap() = Some(Summary { rtt, loss, average });
+
+                    let task = Task {
+                        id: thread_id.clone(),
+                        update_time: Instant::now(),
+                    };
+                    if send.send(task).is_err() {
+                        break;
+                    }
+                    thread::sleep(thread_config.interval);
+                }
+            })
+            .unwrap();
+
+        Ok(Ping {
+            text: TextWidget::new(config).with_icon("ping"),
+            id,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("ping", "Invalid format specified")?,
+            summary,
+            rtt_warning: block_config.rtt_warning,
+            rtt_critical: block_config.rtt_critical,
+            loss_warning: block_config.loss_warning,
+            loss_critical: block_config.loss_critical,
+        })
+    }
+}
+
+impl Block for Ping {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let summary = match *self.summary.lock().unwrap() {
+            Some(ref summary) => summary.clone(),
+            // The first round of probes is not done yet
+            None => return Ok(None),
+        };
+
+        let format_ms = |ms: Option<f64>| ms.map_or("-".to_string(), |ms| format!("{:.0}ms", ms));
+        let values = map!(
+            "{rtt}" => format_ms(summary.rtt),
+            "{average}" => format_ms(summary.average),
+            "{loss}" => format!("{:.0}%", summary.loss)
+        );
+        self.text.set_text(self.format.render_static_str(&values)?);
+
+        let rtt = summary.rtt.unwrap_or(std::f64::INFINITY);
+        self.text.set_state(
+            if rtt >= self.rtt_critical || summary.loss >= self.loss_critical {
+                State::Critical
+            } else if rtt >= self.rtt_warning || summary.loss >= self.loss_warning {
+                State::Warning
+            } else {
+                State::Good
+            },
+        );
+
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::checksum;
+
+    #[test]
+    fn test_checksum() {
+        assert_eq!(checksum(&[8, 0, 0, 0, 0, 1, 0, 1]), 0xf7fd);
+        // An odd length is padded with a zero byte
+        assert_eq!(checksum(&[0xff, 0xff, 0x01]), 0xfeff);
+    }
+}
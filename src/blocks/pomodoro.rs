@@ -12,7 +12,7 @@ use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
 use crate::subprocess::spawn_child_async;
 use crate::util::pseudo_uuid;
-use crate::widget::I3BarWidget;
+use crate::widget::{I3BarWidget, State as WidgetState};
 use crate::widgets::button::ButtonWidget;
 
 enum State {
@@ -71,12 +71,41 @@ pub struct Pomodoro {
     count: usize,
     use_nag: bool,
     nag_path: std::path::PathBuf,
+    notify_cmd: Option<String>,
+    tx_update_request: Sender<Task>,
 }
 
 impl Pomodoro {
     fn set_text(&mut self) {
         self.time
             .set_text(format!("{} | {}", self.count, self.state));
+        self.time.set_state(match self.state {
+            State::Stopped => WidgetState::Idle,
+            State::Started(_) => WidgetState::Info,
+            State::Paused(_) => WidgetState::Warning,
+            State::OnBreak(_) => WidgetState::Good,
+        });
+    }
+
+    /// Announces a phase transition with i3-nagbar and/or the configured command.
+    fn notify(&self, message: &str, level: &str) -> Result<()> {
+        if self.use_nag {
+            self.nag(message, level);
+        }
+        if let Some(ref cmd) = self.notify_cmd {
+            spawn_child_async("sh", &["-c", &cmd.replace("{msg}", message)])
+                .block_error("pomodoro", "could not spawn notify command")?;
+        }
+        Ok(())
+    }
+
+    /// Time left in the current work or break phase, if the timer is running.
+    fn remaining(&self) -> Option<Duration> {
+        match self.state {
+            State::Started(_) => Some(self.length.checked_sub(self.state.elapsed())?),
+            State::OnBreak(_) => Some(self.break_length.checked_sub(self.state.elapsed())?),
+            _ => None,
+        }
     }
 
     fn nag(&self, message: &str, level: &str) {
@@ -103,6 +132,9 @@ pub struct PomodoroConfig {
     pub use_nag: bool,
     #[serde(default = "PomodoroConfig::default_nag_path")]
     pub nag_path: std::path::PathBuf,
+    /// Shell command run on every phase transition, `{msg}` is replaced by the message
+    #[serde(default = "PomodoroConfig::default_notify_cmd")]
+    pub notify_cmd: Option<String>,
 }
 
 impl PomodoroConfig {
@@ -129,12 +161,16 @@ impl PomodoroConfig {
     fn default_nag_path() -> std::path::PathBuf {
         std::path::PathBuf::from("i3-nagbar")
     }
+
+    fn default_notify_cmd() -> Option<String> {
+        None
+    }
 }
 
 impl ConfigBlock for Pomodoro {
     type Config = PomodoroConfig;
 
-    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
         let id: String = pseudo_uuid();
 
         Ok(Pomodoro {
@@ -149,6 +185,8 @@ impl ConfigBlock for Pomodoro {
             use_nag: block_config.use_nag,
             count: 0,
             nag_path: block_config.nag_path,
+            notify_cmd: block_config.notify_cmd,
+            tx_update_request: send,
         })
     }
 }
@@ -159,30 +197,29 @@ impl Block for Pomodoro {
     }
 
     fn update(&mut self) -> Result<Option<Update>> {
-        self.set_text();
         match &self.state {
             State::Started(_) => {
                 if self.state.elapsed() >= self.length {
-                    if self.use_nag {
-                        self.nag(&self.message, "error");
-                    }
-
+                    self.notify(&self.message, "error")?;
                     self.state = State::OnBreak(Instant::now());
                 }
             }
             State::OnBreak(_) => {
                 if self.state.elapsed() >= self.break_length {
-                    if self.use_nag {
-                        self.nag(&self.break_message, "warning");
-                    }
+                    self.notify(&self.break_message, "warning")?;
                     self.state = State::Stopped;
                     self.count += 1;
                 }
             }
             _ => {}
         }
+        self.set_text();
 
-        Ok(Some(self.update_interval.into()))
+        // Only wake up while the timer is running, and exactly when the phase ends.
+        Ok(Some(match self.remaining() {
+            Some(remaining) => self.update_interval.min(remaining).into(),
+            None => Update::Once,
+        }))
     }
 
     fn click(&mut self, event: &I3BarEvent) -> Result<()> {
@@ -210,6 +247,11 @@ impl Block for Pomodoro {
                         }
                     },
                 }
+                // Put the block back on the schedule now that the timer may be running.
+                self.tx_update_request.send(Task {
+                    id: self.id.clone(),
+                    update_time: Instant::now(),
+                })?;
             }
         }
 
@@ -217,6 +259,10 @@ impl Block for Pomodoro {
         Ok(())
     }
 
+    fn handles_click(&self, _event: &I3BarEvent) -> bool {
+        true
+    }
+
     fn view(&self) -> Vec<&dyn I3BarWidget> {
         vec![&self.time]
     }
@@ -0,0 +1,186 @@
+//! A block for the pressure stall information of the kernel.
+
+use std::path::Path;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, read_file, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+const RESOURCES: [&str; 3] = ["cpu", "memory", "io"];
+
+/// The `avg10` and `avg60` values of the `some` line of a file in `/proc/pressure`, which are
+/// the share of time in percent that at least one task was stalled on the resource.
+fn parse_pressure(content: &str) -> Option<(f32, f32)> {
+    let line = content.lines().find(|l| l.starts_with("some "))?;
+    let mut avg10 = None;
+    let mut avg60 = None;
+    for field in line.split_whitespace().skip(1) {
+        let mut kv = field.splitn(2, '=');
+        match (kv.next(), kv.next()) {
+            (Some("avg10"), Some(v)) => avg10 = v.parse().ok(),
+            (Some("avg60"), Some(v)) => avg60 = v.parse().ok(),
+            _ => {}
+        }
+    }
+    Some((avg10?, avg60?))
+}
+
+pub struct Pressure {
+    id: String,
+    text: TextWidget,
+    format: FormatTemplate,
+    update_interval: Duration,
+    /// The warning and critical thresholds of cpu, memory and io
+    thresholds: [(f32, f32); 3],
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PressureConfig {
+    /// Format string
+    #[serde(default = "PressureConfig::default_format")]
+    pub format: String,
+
+    /// Update interval in seconds
+    #[serde(
+        default = "PressureConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Percentage of time stalled on the CPU, where state is set to warning
+    #[serde(default = "PressureConfig::default_warning")]
+    pub warning_cpu: f32,
+
+    /// Percentage of time stalled on memory, where state is set to warning
+    #[serde(default = "PressureConfig::default_warning")]
+    pub warning_memory: f32,
+
+    /// Percentage of time stalled on IO, where state is set to warning
+    #[serde(default = "PressureConfig::default_warning")]
+    pub warning_io: f32,
+
+    /// Percentage of time stalled on the CPU, where state is set to critical
+    #[serde(default = "PressureConfig::default_critical")]
+    pub critical_cpu: f32,
+
+    /// Percentage of time stalled on memory, where state is set to critical
+    #[serde(default = "PressureConfig::default_critical")]
+    pub critical_memory: f32,
+
+    /// Percentage of time stalled on IO, where state is set to critical
+    #[serde(default = "PressureConfig::default_critical")]
+    pub critical_io: f32,
+}
+
+impl PressureConfig {
+    fn default_format() -> String {
+        "cpu {cpu}% mem {memory}% io {io}%".to_owned()
+    }
+
+    fn default_interval() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    fn default_warning() -> f32 {
+        10.0
+    }
+
+    fn default_critical() -> f32 {
+        30.0
+    }
+}
+
+impl ConfigBlock for Pressure {
+    type Config = PressureConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        if !Path::new("/proc/pressure").exists() {
+            return Err(BlockError(
+                "pressure".to_string(),
+                "/proc/pressure does not exist, the kernel needs CONFIG_PSI".to_string(),
+            ));
+        }
+
+        Ok(Pressure {
+            id: pseudo_uuid(),
+            text: TextWidget::new(config).with_icon("cogs"),
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("pressure", "Invalid format specified")?,
+            update_interval: block_config.interval,
+            thresholds: [
+                (block_config.warning_cpu, block_config.critical_cpu),
+                (block_config.warning_memory, block_config.critical_memory),
+                (block_config.warning_io, block_config.critical_io),
+            ],
+        })
+    }
+}
+
+impl Block for Pressure {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mut avgs = Vec::with_capacity(RESOURCES.len());
+        let mut state = State::Idle;
+
+        for (resource, &(warning, critical)) in RESOURCES.iter().zip(self.thresholds.iter()) {
+            let path = Path::new("/proc/pressure").join(resource);
+            let (avg10, avg60) = parse_pressure(&read_file("pressure", &path)?).block_error(
+                "pressure",
+                &format!("failed to parse {}", path.to_string_lossy()),
+            )?;
+
+            // The worst resource decides the state
+            state = match (avg10, state) {
+                (x, _) if x > critical => State::Critical,
+                (_, State::Critical) => State::Critical,
+                (x, _) if x > warning => State::Warning,
+                (_, state) => state,
+            };
+            avgs.push((format!("{:.0}", avg10), format!("{:.0}", avg60)));
+        }
+
+        let values = map!(
+            "{cpu}" => &avgs[0].0,
+            "{cpu_60}" => &avgs[0].1,
+            "{memory}" => &avgs[1].0,
+            "{memory_60}" => &avgs[1].1,
+            "{io}" => &avgs[2].0,
+            "{io_60}" => &avgs[2].1
+        );
+        self.text.set_text(self.format.render_static_str(&values)?);
+        self.text.set_state(state);
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse_pressure;
+
+    #[test]
+    fn test_parse_pressure() {
+        let content = "some avg10=3.21 avg60=0.50 avg300=0.10 total=123456\n\
+                       full avg10=1.00 avg60=0.20 avg300=0.05 total=23456";
+        assert_eq!(parse_pressure(content), Some((3.21, 0.5)));
+        assert_eq!(parse_pressure("some avg10=0.00"), None);
+        assert_eq!(parse_pressure(""), None);
+    }
+}
@@ -0,0 +1,234 @@
+//! A block for the public IP address and its location, as seen by a web service.
+
+use std::net::IpAddr;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::net::NetworkDevice;
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{http_get, pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+/// Delay before the first retry of a failed query. Every further failure multiplies it by four.
+const RETRY_DELAY: Duration = Duration::from_secs(15);
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Default, PartialEq)]
+struct Location {
+    ip: String,
+    country: String,
+    city: String,
+}
+
+/// Reads the response of the endpoint, either a JSON object like those of ipapi.co and
+/// ifconfig.co, or just the address like that of api.ipify.org.
+fn parse_response(body: &str) -> Option<Location> {
+    let body = body.trim();
+    let json = match serde_json::from_str::<serde_json::Value>(body) {
+        Ok(json) => json,
+        Err(_) if body.parse::<IpAddr>().is_ok() => {
+            return Some(Location {
+                ip: body.to_string(),
+                ..Default::default()
+            })
+        }
+        Err(_) => return None,
+    };
+    let field = |keys: &[&str]| {
+        keys.iter()
+            .filter_map(|key| json.get(key).and_then(|value| value.as_str()))
+            .next()
+            .unwrap_or_default()
+            .to_string()
+    };
+    let location = Location {
+        ip: field(&["ip"]),
+        country: field(&["country_name", "country"]),
+        city: field(&["city"]),
+    };
+    if location.ip.is_empty() {
+        None
+    } else {
+        Some(location)
+    }
+}
+
+pub struct PublicIp {
+    id: String,
+    output: ButtonWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    endpoint: String,
+    /// The last successful answer, shown while the endpoint cannot be reached
+    cached: Option<Location>,
+    failures: u32,
+    tx_update_request: Sender<Task>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct PublicIpConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "PublicIpConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format string
+    #[serde(default = "PublicIpConfig::default_format")]
+    pub format: String,
+
+    /// URL that answers with the address of the client
+    #[serde(default = "PublicIpConfig::default_endpoint")]
+    pub endpoint: String,
+}
+
+impl PublicIpConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(300)
+    }
+
+    fn default_format() -> String {
+        "{ip} {country}".to_owned()
+    }
+
+    fn default_endpoint() -> String {
+        "https://ipapi.co/json/".to_owned()
+    }
+}
+
+impl ConfigBlock for PublicIp {
+    type Config = PublicIpConfig;
+
+    fn new(
+        block_config: Self::Config,
+        config: Config,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let id = pseudo_uuid();
+        Ok(PublicIp {
+            output: ButtonWidget::new(config, &id).with_icon("net_wired"),
+            id,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("public_ip", "Invalid format specified")?,
+            endpoint: block_config.endpoint,
+            cached: None,
+            failures: 0,
+            tx_update_request,
+        })
+    }
+}
+
+impl PublicIp {
+    fn query(&self) -> Result<Location> {
+        // Without a default route there is no point in waiting for the endpoint
+        if NetworkDevice::default_device().is_none() {
+            return Err(BlockError("public_ip".to_owned(), "offline".to_owned()));
+        }
+        let response = http_get("public_ip", &self.endpoint, &[], Duration::from_secs(3))?;
+        if !response.is_success() {
+            return Err(BlockError(
+                "public_ip".to_owned(),
+                "Failed to reach the endpoint".to_owned(),
+            ));
+        }
+        parse_response(&response.text())
+            .block_error("public_ip", "Unexpected response of the endpoint")
+    }
+
+    fn retry_delay(&self) -> Duration {
+        let factor = 4u32.saturating_pow(self.failures.saturating_sub(1));
+        RETRY_DELAY
+            .checked_mul(factor)
+            .unwrap_or(MAX_RETRY_DELAY)
+            .min(MAX_RETRY_DELAY)
+    }
+}
+
+impl Block for PublicIp {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let delay = match self.query() {
+            Ok(location) => {
+                self.failures = 0;
+                self.cached = Some(location);
+                self.output.set_state(State::Idle);
+                self.update_interval
+            }
+            Err(_) => {
+                self.failures += 1;
+                // The cached address may be stale, which the state points out
+                self.output.set_state(if self.cached.is_some() {
+                    State::Warning
+                } else {
+                    State::Critical
+                });
+                self.retry_delay()
+            }
+        };
+
+        let location = self.cached.as_ref();
+        let values = map!(
+            "{ip}" => location.map_or("", |l| l.ip.as_str()),
+            "{country}" => location.map_or("", |l| l.country.as_str()),
+            "{city}" => location.map_or("", |l| l.city.as_str())
+        );
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
+
+        Ok(Some(delay.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.matches_name(&self.id) && event.button == MouseButton::Left {
+            self.tx_update_request.send(Task {
+                id: self.id.clone(),
+                update_time: Instant::now(),
+            })?;
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_response, Location};
+
+    #[test]
+    fn test_parse_response() {
+        assert_eq!(
+            parse_response("{\"ip\": \"192.0.2.1\", \"city\": \"Berlin\", \"country\": \"DE\", \"country_name\": \"Germany\"}"),
+            Some(Location {
+                ip: "192.0.2.1".to_string(),
+                country: "Germany".to_string(),
+                city: "Berlin".to_string(),
+            })
+        );
+        assert_eq!(
+            parse_response("2001:db8::1\n"),
+            Some(Location {
+                ip: "2001:db8::1".to_string(),
+                ..Default::default()
+            })
+        );
+        assert_eq!(parse_response("<html>Too many requests</html>"), None);
+        assert_eq!(parse_response("{\"error\": true}"), None);
+    }
+}
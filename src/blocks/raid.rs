@@ -0,0 +1,277 @@
+//! A block for the state of Linux software RAID arrays, from `/proc/mdstat`.
+
+use std::path::Path;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, read_file, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+/// An array in `/proc/mdstat`.
+#[derive(Debug, Default, PartialEq)]
+struct Array {
+    name: String,
+    active: bool,
+    level: String,
+    /// The number of members the array should have, and has working
+    members: (u32, u32),
+    /// Like `[UU_]`, where `_` is a missing member
+    health: String,
+    /// The members marked with `(F)`
+    failed: u32,
+    /// A running recovery, resync, reshape or check, with its progress in percent
+    sync: Option<(String, f32)>,
+}
+
+impl Array {
+    fn degraded(&self) -> bool {
+        self.members.1 < self.members.0
+    }
+}
+
+/// Parses the arrays in the content of `/proc/mdstat`.
+fn parse_mdstat(mdstat: &str) -> Vec<Array> {
+    let mut arrays: Vec<Array> = Vec::new();
+    for line in mdstat.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() >= 3 && fields[1] == ":" && fields[0].starts_with("md") {
+            // md0 : active raid1 sdb1[1] sda1[0](F)
+            let active = fields[2] == "active";
+            let mut rest = fields[3..].iter().skip_while(|f| f.starts_with('('));
+            let level = if active {
+                rest.next().map(|l| l.to_string()).unwrap_or_default()
+            } else {
+                String::new()
+            };
+            arrays.push(Array {
+                name: fields[0].to_string(),
+                active,
+                level,
+                failed: rest.filter(|member| member.ends_with("(F)")).count() as u32,
+                ..Array::default()
+            });
+            continue;
+        }
+        let array = match arrays.last_mut() {
+            Some(array) if line.starts_with(char::is_whitespace) => array,
+            _ => continue,
+        };
+        for (i, field) in fields.iter().enumerate() {
+            if field.starts_with('[') && field.ends_with(']') {
+                let inner = &field[1..field.len() - 1];
+                let mut counts = inner.splitn(2, '/');
+                match (
+                    counts.next().and_then(|n| n.parse().ok()),
+                    counts.next().and_then(|n| n.parse().ok()),
+                ) {
+                    (Some(total), Some(working)) => array.members = (total, working),
+                    _ if inner.chars().all(|c| c == 'U' || c == '_') && !inner.is_empty() => {
+                        array.health = field.to_string()
+                    }
+                    _ => {}
+                }
+            } else if *field == "=" && i > 0 && i + 1 < fields.len() {
+                // recovery = 17.4% (170067328/976630464) finish=64.7min speed=207666K/sec
+                let percent = fields[i + 1].trim_end_matches('%').parse().unwrap_or(0.);
+                array.sync = Some((fields[i - 1].to_string(), percent));
+            }
+        }
+    }
+    arrays
+}
+
+pub struct Raid {
+    id: String,
+    update_interval: Duration,
+    format: FormatTemplate,
+    arrays: Vec<String>,
+    config: Config,
+    widgets: Vec<TextWidget>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RaidConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "RaidConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// The arrays to show, like `md0`, all arrays if empty
+    #[serde(default)]
+    pub arrays: Vec<String>,
+
+    /// Format string
+    #[serde(default = "RaidConfig::default_format")]
+    pub format: String,
+}
+
+impl RaidConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn default_format() -> String {
+        "{name} {status}".to_owned()
+    }
+}
+
+impl ConfigBlock for Raid {
+    type Config = RaidConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        Ok(Raid {
+            id: pseudo_uuid(),
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("raid", "Invalid format specified")?,
+            arrays: block_config.arrays,
+            config,
+            widgets: Vec::new(),
+        })
+    }
+}
+
+impl Block for Raid {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mdstat = read_file("raid", Path::new("/proc/mdstat"))?;
+
+        let mut widgets = Vec::new();
+        for array in parse_mdstat(&mdstat) {
+            if !self.arrays.is_empty() && !self.arrays.contains(&array.name) {
+                continue;
+            }
+
+            let status = match array.sync {
+                Some((ref action, percent)) => format!("{} {:.1}%", action, percent),
+                None if !array.active => "inactive".to_string(),
+                None if array.failed > 0 || array.degraded() => "degraded".to_string(),
+                None => "clean".to_string(),
+            };
+            let state = if !array.active || array.failed > 0 {
+                State::Critical
+            } else if array.degraded() {
+                // Rebuilding is better than nothing, but the array has no redundancy yet
+                if array.sync.is_some() {
+                    State::Warning
+                } else {
+                    State::Critical
+                }
+            } else if array.sync.is_some() {
+                State::Info
+            } else {
+                State::Idle
+            };
+
+            let values = map!(
+                "{name}" => array.name.clone(),
+                "{level}" => array.level.clone(),
+                "{status}" => status,
+                "{members}" => format!("{}/{}", array.members.1, array.members.0),
+                "{health}" => array.health.clone(),
+                "{failed}" => array.failed.to_string(),
+                "{progress}" => array
+                    .sync
+                    .as_ref()
+                    .map(|(_, percent)| format!("{:.1}", percent))
+                    .unwrap_or_default()
+            );
+            widgets.push(
+                TextWidget::new(self.config.clone())
+                    .with_icon("disk_drive")
+                    .with_text(&self.format.render_static_str(&values)?)
+                    .with_state(state),
+            );
+        }
+        self.widgets = widgets;
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.widgets
+            .iter()
+            .map(|widget| widget as &dyn I3BarWidget)
+            .collect()
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_mdstat, Array};
+
+    #[test]
+    fn test_clean_and_recovering() {
+        let mdstat = "\
+Personalities : [raid1] [raid6] [raid5] [raid4]
+md1 : active raid5 sde1[3] sdd1[1] sdc1[0]
+      1953260928 blocks super 1.2 level 5, 512k chunk, algorithm 2 [3/3] [UUU]
+      bitmap: 0/8 pages [0KB], 65536KB chunk
+
+md0 : active raid1 sdb1[2] sda1[0]
+      976630464 blocks super 1.2 [2/1] [U_]
+      [===>.................]  recovery = 17.4% (170067328/976630464) finish=64.7min speed=207666K/sec
+      bitmap: 2/8 pages [8KB], 65536KB chunk
+
+unused devices: <none>
+";
+        assert_eq!(
+            parse_mdstat(mdstat),
+            vec![
+                Array {
+                    name: "md1".to_string(),
+                    active: true,
+                    level: "raid5".to_string(),
+                    members: (3, 3),
+                    health: "[UUU]".to_string(),
+                    failed: 0,
+                    sync: None,
+                },
+                Array {
+                    name: "md0".to_string(),
+                    active: true,
+                    level: "raid1".to_string(),
+                    members: (2, 1),
+                    health: "[U_]".to_string(),
+                    failed: 0,
+                    sync: Some(("recovery".to_string(), 17.4)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_failed_and_inactive() {
+        let mdstat = "\
+Personalities : [raid1]
+md127 : active (auto-read-only) raid1 sdb1[1](F) sda1[0]
+      976630464 blocks super 1.2 [2/1] [U_]
+
+md126 : inactive sdc1[0](S)
+      976630464 blocks super 1.2
+
+unused devices: <none>
+";
+        let arrays = parse_mdstat(mdstat);
+        assert_eq!(arrays.len(), 2);
+        assert_eq!(arrays[0].level, "raid1");
+        assert_eq!(arrays[0].failed, 1);
+        assert!(arrays[0].degraded());
+        assert!(!arrays[1].active);
+        assert_eq!(arrays[1].failed, 0);
+    }
+}
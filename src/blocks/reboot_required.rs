@@ -0,0 +1,151 @@
+//! A block that shows when a reboot is needed to finish an upgrade.
+
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, read_file, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+/// Whether the package of the running kernel has been upgraded or removed, which takes its
+/// modules in `modules` along. Other kernels next to it, like an LTS kernel or a newer kernel
+/// that Fedora installs without removing the running one, do not matter.
+fn kernel_replaced(modules: &Path, running: &str) -> bool {
+    // Systems without the usual layout, like NixOS, are never flagged
+    modules.is_dir() && !modules.join(running).join("kernel").is_dir()
+}
+
+/// The reason for a reboot, if one is needed.
+fn reboot_reason() -> Result<Option<String>> {
+    // Debian and Ubuntu packages create this file in their postinst scripts
+    if Path::new("/var/run/reboot-required").exists() {
+        let packages = fs::read_to_string("/var/run/reboot-required.pkgs").unwrap_or_default();
+        let mut packages: Vec<&str> = packages.lines().collect();
+        packages.dedup();
+        return Ok(Some(if packages.is_empty() {
+            "upgrade".to_string()
+        } else {
+            packages.join(", ")
+        }));
+    }
+
+    // Elsewhere, the modules of the running kernel are gone once its package was upgraded
+    let running = read_file("reboot_required", Path::new("/proc/sys/kernel/osrelease"))?;
+    Ok(
+        if kernel_replaced(Path::new("/usr/lib/modules"), running.trim()) {
+            Some("kernel".to_string())
+        } else {
+            None
+        },
+    )
+}
+
+pub struct RebootRequired {
+    id: String,
+    text: TextWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    required: bool,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RebootRequiredConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "RebootRequiredConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format string
+    #[serde(default = "RebootRequiredConfig::default_format")]
+    pub format: String,
+}
+
+impl RebootRequiredConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    fn default_format() -> String {
+        "reboot".to_owned()
+    }
+}
+
+impl ConfigBlock for RebootRequired {
+    type Config = RebootRequiredConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        Ok(RebootRequired {
+            id: pseudo_uuid(),
+            text: TextWidget::new(config)
+                .with_icon("update")
+                .with_state(State::Warning),
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("reboot_required", "Invalid format specified")?,
+            required: false,
+        })
+    }
+}
+
+impl Block for RebootRequired {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let reason = reboot_reason()?;
+        self.required = reason.is_some();
+        if let Some(reason) = reason {
+            let values = map!("{reason}" => reason);
+            self.text.set_text(self.format.render_static_str(&values)?);
+        }
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.required {
+            vec![&self.text]
+        } else {
+            vec![]
+        }
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::kernel_replaced;
+    use assert_fs::prelude::PathChild;
+    use assert_fs::TempDir;
+    use std::fs;
+
+    #[test]
+    fn test_kernel_replaced() {
+        let modules = TempDir::new().unwrap();
+        fs::create_dir_all(modules.child("6.6.10-arch1-1/kernel").path()).unwrap();
+        fs::create_dir_all(modules.child("6.1.71-1-lts/kernel").path()).unwrap();
+        assert!(!kernel_replaced(modules.path(), "6.6.10-arch1-1"));
+        // A newer kernel next to the running one needs no reboot
+        assert!(!kernel_replaced(modules.path(), "6.1.71-1-lts"));
+        // Leftovers of DKMS keep the directory of a removed kernel
+        fs::create_dir_all(modules.child("6.6.9-arch1-1/updates/dkms").path()).unwrap();
+        assert!(kernel_replaced(modules.path(), "6.6.9-arch1-1"));
+        assert!(kernel_replaced(modules.path(), "6.6.8-arch1-1"));
+        assert!(!kernel_replaced(
+            &modules.path().join("missing"),
+            "6.6.10-arch1-1"
+        ));
+    }
+}
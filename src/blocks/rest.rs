@@ -0,0 +1,197 @@
+//! A block for values from a JSON API, like a home automation or a weather station.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{http_get, json_display, json_pointer, pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+pub struct Rest {
+    id: String,
+    text: TextWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    url: String,
+    headers: Vec<String>,
+    timeout: Duration,
+    /// The placeholders, with the JSON pointers of their values
+    values: Vec<(String, String)>,
+    state_value: Option<String>,
+    warning: Option<f64>,
+    critical: Option<f64>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct RestConfig {
+    /// The URL to fetch
+    pub url: String,
+
+    /// Update interval in seconds
+    #[serde(
+        default = "RestConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Time to wait for the answer, in seconds
+    #[serde(
+        default = "RestConfig::default_timeout",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub timeout: Duration,
+
+    /// Headers to send, like `Authorization: Bearer ...`
+    #[serde(default)]
+    pub headers: Vec<String>,
+
+    /// The placeholders, with the paths of their values in the answer
+    #[serde(default)]
+    pub values: HashMap<String, String>,
+
+    /// Format string
+    #[serde(default = "RestConfig::default_format")]
+    pub format: String,
+
+    /// The placeholder whose value sets the state
+    #[serde(default)]
+    pub state_value: Option<String>,
+
+    /// Value at or above which the state is warning, or at or below if below `critical`
+    #[serde(default)]
+    pub warning: Option<f64>,
+
+    /// Value at or above which the state is critical, or at or below if below `warning`
+    #[serde(default)]
+    pub critical: Option<f64>,
+}
+
+impl RestConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    fn default_timeout() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    fn default_format() -> String {
+        "{value}".to_owned()
+    }
+}
+
+impl ConfigBlock for Rest {
+    type Config = RestConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        // Without any paths, `{value}` is the whole answer
+        let mut values: Vec<(String, String)> = block_config
+            .values
+            .iter()
+            .map(|(name, path)| (format!("{{{}}}", name), json_pointer(path)))
+            .collect();
+        if values.is_empty() {
+            values.push(("{value}".to_string(), String::new()));
+        }
+        if let Some(ref name) = block_config.state_value {
+            if !block_config.values.contains_key(name) {
+                return Err(BlockError(
+                    "rest".to_string(),
+                    format!("state_value {} is not one of the values", name),
+                ));
+            }
+        }
+
+        Ok(Rest {
+            id: pseudo_uuid(),
+            text: TextWidget::new(config),
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("rest", "Invalid format specified")?,
+            url: block_config.url,
+            headers: block_config.headers,
+            timeout: block_config.timeout,
+            values,
+            state_value: block_config.state_value.map(|name| format!("{{{}}}", name)),
+            warning: block_config.warning,
+            critical: block_config.critical,
+        })
+    }
+}
+
+impl Rest {
+    fn fetch(&self) -> Result<Value> {
+        let response = http_get("rest", &self.url, &self.headers, self.timeout)?;
+        if !response.is_success() {
+            return Err(BlockError(
+                "rest".to_string(),
+                format!("failed to fetch {}", self.url),
+            ));
+        }
+        serde_json::from_slice(&response.body).block_error("rest", "the answer is not JSON")
+    }
+
+    fn state(&self, value: f64) -> State {
+        // Lower values are worse if the critical threshold is below the warning one
+        let lower_is_worse = match (self.warning, self.critical) {
+            (Some(warning), Some(critical)) => critical < warning,
+            _ => false,
+        };
+        let reached = |threshold: Option<f64>| {
+            threshold.map_or(false, |threshold| {
+                if lower_is_worse {
+                    value <= threshold
+                } else {
+                    value >= threshold
+                }
+            })
+        };
+        if reached(self.critical) {
+            State::Critical
+        } else if reached(self.warning) {
+            State::Warning
+        } else {
+            State::Idle
+        }
+    }
+}
+
+impl Block for Rest {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let answer = self.fetch()?;
+
+        let mut values = HashMap::new();
+        for (name, pointer) in &self.values {
+            values.insert(name.as_str(), json_display(answer.pointer(pointer)));
+        }
+        self.text.set_text(self.format.render_static_str(&values)?);
+
+        let state = self
+            .state_value
+            .as_ref()
+            .and_then(|name| values.get(name.as_str()))
+            .and_then(|value| value.parse::<f64>().ok())
+            .map_or(State::Idle, |value| self.state(value));
+        self.text.set_state(state);
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
@@ -0,0 +1,196 @@
+use std::thread;
+use std::time::Instant;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use swayipc::reply::{Node, NodeType};
+use swayipc::{Connection, EventType};
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::{Config, LogicalDirection, Scrolling};
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::I3BarWidget;
+use crate::widgets::button::ButtonWidget;
+
+/// The windows below a container, as their id and title.
+fn windows(node: &Node) -> Vec<(i64, String)> {
+    let is_window = match node.node_type {
+        NodeType::Con | NodeType::FloatingCon => true,
+        _ => false,
+    };
+    if is_window && node.nodes.is_empty() && node.floating_nodes.is_empty() {
+        return vec![(node.id, node.name.clone().unwrap_or_default())];
+    }
+    node.nodes
+        .iter()
+        .chain(node.floating_nodes.iter())
+        .flat_map(windows)
+        .collect()
+}
+
+/// The windows in the scratchpad, which is the workspace `__i3_scratch` in the tree.
+fn scratchpad_windows(tree: &Node) -> Vec<(i64, String)> {
+    if tree.name.as_deref() == Some("__i3_scratch") {
+        return windows(tree);
+    }
+    tree.nodes.iter().flat_map(scratchpad_windows).collect()
+}
+
+pub struct Scratchpad {
+    id: String,
+    output: ButtonWidget,
+    format: FormatTemplate,
+    show_title: bool,
+    scrolling: Scrolling,
+    windows: Vec<(i64, String)>,
+    /// The window that is shown on click, chosen by scrolling
+    selected: usize,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScratchpadConfig {
+    /// Format string
+    #[serde(default = "ScratchpadConfig::default_format")]
+    pub format: String,
+}
+
+impl ScratchpadConfig {
+    fn default_format() -> String {
+        "{count}".to_owned()
+    }
+}
+
+impl ConfigBlock for Scratchpad {
+    type Config = ScratchpadConfig;
+
+    fn new(block_config: Self::Config, config: Config, tx: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+        let id_clone = id.clone();
+
+        let _test_conn =
+            Connection::new().block_error("scratchpad", "failed to acquire connect to IPC")?;
+
+        thread::Builder::new()
+            .name("scratchpad".into())
+            .spawn(move || {
+                let conn = Connection::new().expect("failed to open connection with swayipc");
+
+                // Windows move to and from the scratchpad, or close while in it
+                let events = conn
+                    .subscribe(&[EventType::Window])
+                    .expect("could not subscribe to window events");
+
+                for event in events {
+                    if event.is_ok()
+                        && tx
+                            .send(Task {
+                                id: id_clone.clone(),
+                                update_time: Instant::now(),
+                            })
+                            .is_err()
+                    {
+                        return;
+                    }
+                }
+            })
+            .expect("failed to start watching thread for `scratchpad` block");
+
+        let format = FormatTemplate::from_string(&block_config.format)
+            .block_error("scratchpad", "Invalid format specified")?;
+        Ok(Scratchpad {
+            output: ButtonWidget::new(config.clone(), &id),
+            id,
+            show_title: format.has_placeholder("{title}"),
+            format,
+            scrolling: config.scrolling,
+            windows: Vec::new(),
+            selected: 0,
+        })
+    }
+}
+
+impl Scratchpad {
+    fn render(&mut self) -> Result<()> {
+        let title = self
+            .windows
+            .get(self.selected)
+            .map(|(_, title)| title.clone())
+            .unwrap_or_default();
+        let values = map!(
+            "{count}" => self.windows.len().to_string(),
+            "{title}" => title
+        );
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
+        Ok(())
+    }
+}
+
+impl Block for Scratchpad {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let tree = Connection::new()
+            .block_error("scratchpad", "failed to acquire connect to IPC")?
+            .get_tree()
+            .block_error("scratchpad", "failed to get the tree")?;
+        self.windows = scratchpad_windows(&tree);
+        if self.selected >= self.windows.len() {
+            self.selected = 0;
+        }
+        self.render()?;
+
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if !event.matches_name(&self.id) {
+            return Ok(());
+        }
+        match event.button {
+            MouseButton::Left => {
+                // Shows the window that was scrolled to, or cycles through all of them
+                let command = match self.windows.get(self.selected) {
+                    Some((con_id, _)) if self.show_title => {
+                        format!("[con_id={}] scratchpad show", con_id)
+                    }
+                    _ => "scratchpad show".to_string(),
+                };
+                Connection::new()
+                    .block_error("scratchpad", "failed to acquire connect to IPC")?
+                    .run_command(command)
+                    .block_error("scratchpad", "failed to show the scratchpad")?;
+            }
+            _ => {
+                if self.windows.is_empty() {
+                    return Ok(());
+                }
+                let count = self.windows.len();
+                self.selected = match self.scrolling.to_logical_direction(event.button) {
+                    Some(LogicalDirection::Up) => (self.selected + 1) % count,
+                    Some(LogicalDirection::Down) => (self.selected + count - 1) % count,
+                    None => return Ok(()),
+                };
+                self.render()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        matches!(
+            event.button,
+            MouseButton::Left | MouseButton::WheelUp | MouseButton::WheelDown
+        )
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
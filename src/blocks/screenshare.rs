@@ -0,0 +1,302 @@
+//! A block that shows when the screen is being recorded or shared.
+
+use std::collections::HashMap;
+use std::fs::read_dir;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use dbus::ffidisp::{BusType, Connection};
+use dbus::message::MessageType;
+use dbus::Message;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, read_file, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+/// The portal sessions that are casting the screen, by their object path, with the unique bus
+/// name and the name of the program that started them.
+type Sessions = Arc<Mutex<HashMap<String, (String, String)>>>;
+
+/// Whether the command line of a process grabs the X11 screen, like
+/// `ffmpeg -f x11grab -i :0`. Its arguments are separated by NUL bytes.
+fn grabs_x11(cmdline: &str) -> bool {
+    cmdline.split('\0').any(|argument| argument == "x11grab")
+}
+
+/// The names of the programs that record the screen without the portal, either known
+/// recorders or anything that uses the `x11grab` device of ffmpeg.
+fn x11_recorders(recorders: &[String]) -> Vec<String> {
+    let own_pid = std::process::id().to_string();
+    let mut found = Vec::new();
+    let processes = match read_dir("/proc") {
+        Ok(processes) => processes,
+        Err(_) => return found,
+    };
+    for process in processes.flatten() {
+        let pid = process.file_name().to_string_lossy().into_owned();
+        if pid == own_pid || !pid.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        let name = match read_file("screenshare", &process.path().join("comm")) {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        let records = recorders.contains(&name)
+            || std::fs::read(process.path().join("cmdline")).map_or(false, |cmdline| {
+                grabs_x11(&String::from_utf8_lossy(&cmdline))
+            });
+        if records && !found.contains(&name) {
+            found.push(name);
+        }
+    }
+    found
+}
+
+/// The name of the program behind a unique bus name, like `:1.42`.
+fn program_name(c: &Connection, sender: &str) -> Option<String> {
+    let m = Message::new_method_call(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus",
+        "GetConnectionUnixProcessID",
+    )
+    .ok()?
+    .append1(sender);
+    let pid: u32 = c.send_with_reply_and_block(m, 1000).ok()?.get1()?;
+    read_file("screenshare", Path::new(&format!("/proc/{}/comm", pid))).ok()
+}
+
+/// The messages about ScreenCast portal sessions. The portal talks to each program privately,
+/// so they can only be seen by a monitor of the session bus.
+const MATCH_RULES: &[&str] = &[
+    "type='method_call',interface='org.freedesktop.portal.ScreenCast',member='Start'",
+    "type='method_call',interface='org.freedesktop.portal.Session',member='Close'",
+    "type='signal',interface='org.freedesktop.portal.Session',member='Closed'",
+    "type='signal',interface='org.freedesktop.DBus',member='NameOwnerChanged'",
+];
+
+/// Turns the connection into a monitor of the messages that match `MATCH_RULES`. A monitor
+/// can no longer send messages itself.
+fn become_monitor(monitor: &Connection) -> Option<()> {
+    let rules: Vec<&str> = MATCH_RULES.to_vec();
+    let m = Message::new_method_call(
+        "org.freedesktop.DBus",
+        "/org/freedesktop/DBus",
+        "org.freedesktop.DBus.Monitoring",
+        "BecomeMonitor",
+    )
+    .ok()?
+    .append2(rules, 0u32);
+    monitor.send_with_reply_and_block(m, 1000).ok()?;
+    Some(())
+}
+
+/// Records a session that is started or closed, and tells whether the sessions changed.
+fn handle_message(c: &Connection, msg: &Message, sessions: &Sessions) -> bool {
+    let member = msg.member().map(|m| m.to_string()).unwrap_or_default();
+    match (msg.msg_type(), member.as_str()) {
+        (MessageType::MethodCall, "Start") => match (msg.get1::<dbus::Path>(), msg.sender()) {
+            (Some(session), Some(sender)) => {
+                let sender = sender.to_string();
+                let name = program_name(c, &sender).unwrap_or_else(|| sender.clone());
+                sessions
+                    .lock()
+                    .unwrap()
+                    .insert(session.to_string(), (sender, name));
+                true
+            }
+            _ => false,
+        },
+        (MessageType::MethodCall, "Close") | (MessageType::Signal, "Closed") => {
+            msg.path().map_or(false, |session| {
+                sessions
+                    .lock()
+                    .unwrap()
+                    .remove(&session.to_string())
+                    .is_some()
+            })
+        }
+        // A program that quits or crashes leaves its sessions behind
+        (MessageType::Signal, "NameOwnerChanged") => match msg.get3::<String, String, String>() {
+            (Some(name), _, Some(new_owner)) if new_owner.is_empty() => {
+                let mut sessions = sessions.lock().unwrap();
+                let before = sessions.len();
+                sessions.retain(|_, (sender, _)| *sender != name);
+                sessions.len() != before
+            }
+            _ => false,
+        },
+        _ => false,
+    }
+}
+
+/// Keeps track of the sessions that programs start and close through the ScreenCast portal.
+/// Without a session bus, only the X11 recorders are found.
+fn watch_portal(sessions: Sessions, id: String, send: Sender<Task>) {
+    thread::Builder::new()
+        .name("screenshare".into())
+        .spawn(move || {
+            // The names of the programs are asked on a second connection, as the monitor
+            // cannot send messages
+            let (c, monitor) = match (
+                Connection::get_private(BusType::Session),
+                Connection::get_private(BusType::Session),
+            ) {
+                (Ok(c), Ok(monitor)) => (c, monitor),
+                _ => return,
+            };
+            if become_monitor(&monitor).is_none() {
+                return;
+            }
+            loop {
+                for msg in monitor.incoming(100_000) {
+                    if handle_message(&c, &msg, &sessions) {
+                        let task = Task {
+                            id: id.clone(),
+                            update_time: Instant::now(),
+                        };
+                        if send.send(task).is_err() {
+                            return;
+                        }
+                    }
+                }
+            }
+        })
+        .unwrap();
+}
+
+pub struct Screenshare {
+    id: String,
+    output: TextWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    recorders: Vec<String>,
+    sessions: Sessions,
+    active: bool,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ScreenshareConfig {
+    /// Update interval in seconds, for the recorders that do not use the portal
+    #[serde(
+        default = "ScreenshareConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format string while the screen is shared
+    #[serde(default = "ScreenshareConfig::default_format")]
+    pub format: String,
+
+    /// Names of programs that record the screen on X11
+    #[serde(default = "ScreenshareConfig::default_recorders")]
+    pub recorders: Vec<String>,
+}
+
+impl ScreenshareConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    fn default_format() -> String {
+        "".to_owned()
+    }
+
+    fn default_recorders() -> Vec<String> {
+        vec![
+            "obs".to_owned(),
+            "simplescreenrec".to_owned(),
+            "peek".to_owned(),
+            "kazam".to_owned(),
+            "recordmydesktop".to_owned(),
+            "vokoscreenNG".to_owned(),
+        ]
+    }
+}
+
+impl ConfigBlock for Screenshare {
+    type Config = ScreenshareConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+        let sessions: Sessions = Arc::new(Mutex::new(HashMap::new()));
+        watch_portal(sessions.clone(), id.clone(), send);
+
+        Ok(Screenshare {
+            output: TextWidget::new(config).with_icon("recording"),
+            id,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("screenshare", "Invalid format specified")?,
+            recorders: block_config.recorders,
+            sessions,
+            active: false,
+        })
+    }
+}
+
+impl Block for Screenshare {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mut programs = x11_recorders(&self.recorders);
+        let sessions = self
+            .sessions
+            .lock()
+            .block_error("screenshare", "failed to acquire lock")?;
+        for (_, name) in sessions.values() {
+            if !programs.contains(name) {
+                programs.push(name.clone());
+            }
+        }
+        drop(sessions);
+        programs.sort();
+        self.active = !programs.is_empty();
+
+        let values = map!(
+            "{programs}" => programs.join(", "),
+            "{count}" => programs.len().to_string()
+        );
+        if self.active {
+            self.output
+                .set_text(self.format.render_static_str(&values)?);
+            self.output.set_state(State::Critical);
+        } else {
+            self.output.set_text(String::new());
+            self.output.set_state(State::Idle);
+        }
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if !self.active {
+            return Vec::new();
+        }
+        vec![&self.output]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::grabs_x11;
+
+    #[test]
+    fn test_grabs_x11() {
+        assert!(grabs_x11("ffmpeg\0-f\0x11grab\0-i\0:0\0out.mp4\0"));
+        assert!(!grabs_x11("ffmpeg\0-i\0x11grab.mp4\0out.webm\0"));
+        assert!(!grabs_x11(""));
+    }
+}
@@ -0,0 +1,311 @@
+//! A block for the SMART health of disks, as reported by `smartctl`.
+
+use std::process::Command;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+/// The ATA attribute with the number of sectors that were moved because they could not be read.
+const REALLOCATED_SECTOR_COUNT: u32 = 5;
+
+/// The parts of the JSON output of `smartctl -j -H -A` that the block uses.
+#[derive(Deserialize, Debug, Default)]
+struct SmartctlOutput {
+    smart_status: Option<SmartStatus>,
+    temperature: Option<Temperature>,
+    ata_smart_attributes: Option<AtaAttributes>,
+    nvme_smart_health_information_log: Option<NvmeHealth>,
+}
+
+#[derive(Deserialize, Debug)]
+struct SmartStatus {
+    passed: bool,
+}
+
+#[derive(Deserialize, Debug)]
+struct Temperature {
+    current: i64,
+}
+
+#[derive(Deserialize, Debug)]
+struct AtaAttributes {
+    table: Vec<AtaAttribute>,
+}
+
+#[derive(Deserialize, Debug)]
+struct AtaAttribute {
+    id: u32,
+    /// Empty, or when the attribute crossed its threshold, like `FAILING_NOW` or `past`
+    #[serde(default)]
+    when_failed: String,
+    raw: AtaRawValue,
+}
+
+#[derive(Deserialize, Debug)]
+struct AtaRawValue {
+    value: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct NvmeHealth {
+    critical_warning: u64,
+    percentage_used: u64,
+}
+
+/// The health of one disk.
+#[derive(Debug, PartialEq)]
+struct Health {
+    passed: bool,
+    /// Whether an attribute is failing now, even if the overall assessment passed
+    failing: bool,
+    temperature: Option<i64>,
+    reallocated: Option<u64>,
+    percentage_used: Option<u64>,
+}
+
+impl Health {
+    fn from_json(json: &str) -> Result<Self> {
+        let output: SmartctlOutput =
+            serde_json::from_str(json).block_error("smart", "smartctl output is invalid")?;
+        let status = output.smart_status.block_error(
+            "smart",
+            "smartctl reported no health, does the block have access to the device?",
+        )?;
+        let attributes = output
+            .ata_smart_attributes
+            .map(|a| a.table)
+            .unwrap_or_default();
+        let nvme = output.nvme_smart_health_information_log;
+
+        Ok(Health {
+            passed: status.passed,
+            failing: attributes.iter().any(|a| a.when_failed == "FAILING_NOW")
+                || nvme.as_ref().map_or(false, |n| n.critical_warning != 0),
+            temperature: output.temperature.map(|t| t.current),
+            reallocated: attributes
+                .iter()
+                .find(|a| a.id == REALLOCATED_SECTOR_COUNT)
+                .map(|a| a.raw.value),
+            percentage_used: nvme.map(|n| n.percentage_used),
+        })
+    }
+}
+
+pub struct Smart {
+    id: String,
+    update_interval: Duration,
+    format: FormatTemplate,
+    command: String,
+    warning_temperature: i64,
+    warning_percentage_used: u64,
+    /// The devices with their widgets
+    disks: Vec<(String, TextWidget)>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SmartConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "SmartConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// The devices to check, like `/dev/sda` or `/dev/nvme0`
+    pub devices: Vec<String>,
+
+    /// Format string
+    #[serde(default = "SmartConfig::default_format")]
+    pub format: String,
+
+    /// The command to run smartctl, which needs to be allowed to read the devices
+    #[serde(default = "SmartConfig::default_command")]
+    pub command: String,
+
+    /// Temperature in degrees Celsius, where state is set to warning
+    #[serde(default = "SmartConfig::default_warning_temperature")]
+    pub warning_temperature: i64,
+
+    /// Percentage of the rated endurance of an NVMe disk, where state is set to warning
+    #[serde(default = "SmartConfig::default_warning_percentage_used")]
+    pub warning_percentage_used: u64,
+}
+
+impl SmartConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(600)
+    }
+
+    fn default_format() -> String {
+        "{health} {temperature}°C".to_owned()
+    }
+
+    fn default_command() -> String {
+        "smartctl".to_owned()
+    }
+
+    fn default_warning_temperature() -> i64 {
+        60
+    }
+
+    fn default_warning_percentage_used() -> u64 {
+        90
+    }
+}
+
+impl ConfigBlock for Smart {
+    type Config = SmartConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        if block_config.devices.is_empty() {
+            return Err(BlockError(
+                "smart".to_string(),
+                "No devices configured".to_string(),
+            ));
+        }
+
+        Ok(Smart {
+            id: pseudo_uuid(),
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("smart", "Invalid format specified")?,
+            command: block_config.command,
+            warning_temperature: block_config.warning_temperature,
+            warning_percentage_used: block_config.warning_percentage_used,
+            disks: block_config
+                .devices
+                .into_iter()
+                .map(|device| {
+                    let widget = TextWidget::new(config.clone()).with_icon("disk_drive");
+                    (device, widget)
+                })
+                .collect(),
+        })
+    }
+}
+
+impl Smart {
+    fn health(&self, device: &str) -> Result<Health> {
+        // smartctl sets bits of its exit status when the disk has problems, so only the output
+        // tells whether it worked
+        let output = Command::new("sh")
+            .args(&["-c", &format!("{} -j -H -A {}", self.command, device)])
+            .output()
+            .block_error("smart", "failed to run smartctl")?;
+        Health::from_json(&String::from_utf8_lossy(&output.stdout))
+    }
+
+    fn state(&self, health: &Health) -> State {
+        if !health.passed || health.failing {
+            State::Critical
+        } else if health.temperature.unwrap_or(0) > self.warning_temperature
+            || health.reallocated.unwrap_or(0) > 0
+            || health.percentage_used.unwrap_or(0) >= self.warning_percentage_used
+        {
+            State::Warning
+        } else {
+            State::Idle
+        }
+    }
+}
+
+impl Block for Smart {
+    fn update(&mut self) -> Result<Option<Update>> {
+        for i in 0..self.disks.len() {
+            let device = self.disks[i].0.clone();
+            let health = self.health(&device)?;
+            let state = self.state(&health);
+
+            let optional = |value: Option<String>| value.unwrap_or_else(|| "-".to_string());
+            let values = map!(
+                "{device}" => device.clone(),
+                "{health}" => if health.passed { "PASSED" } else { "FAILED" }.to_string(),
+                "{temperature}" => optional(health.temperature.map(|t| t.to_string())),
+                "{reallocated}" => optional(health.reallocated.map(|r| r.to_string())),
+                "{percentage_used}" => optional(health.percentage_used.map(|p| p.to_string()))
+            );
+            let text = self.format.render_static_str(&values)?;
+
+            let widget = &mut self.disks[i].1;
+            widget.set_text(text);
+            widget.set_state(state);
+        }
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.disks
+            .iter()
+            .map(|(_, widget)| widget as &dyn I3BarWidget)
+            .collect()
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Health;
+
+    #[test]
+    fn test_ata_health() {
+        let json = r#"{
+            "smart_status": {"passed": true},
+            "temperature": {"current": 38},
+            "ata_smart_attributes": {"table": [
+                {"id": 5, "name": "Reallocated_Sector_Ct", "when_failed": "", "raw": {"value": 8, "string": "8"}},
+                {"id": 9, "name": "Power_On_Hours", "when_failed": "", "raw": {"value": 12345, "string": "12345"}}
+            ]}
+        }"#;
+        assert_eq!(
+            Health::from_json(json).unwrap(),
+            Health {
+                passed: true,
+                failing: false,
+                temperature: Some(38),
+                reallocated: Some(8),
+                percentage_used: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_nvme_health() {
+        let json = r#"{
+            "smart_status": {"passed": true},
+            "temperature": {"current": 45},
+            "nvme_smart_health_information_log": {"critical_warning": 4, "temperature": 45, "percentage_used": 12}
+        }"#;
+        assert_eq!(
+            Health::from_json(json).unwrap(),
+            Health {
+                passed: true,
+                failing: true,
+                temperature: Some(45),
+                reallocated: None,
+                percentage_used: Some(12),
+            }
+        );
+    }
+
+    #[test]
+    fn test_no_access() {
+        let json =
+            r#"{"smartctl": {"exit_status": 2, "messages": [{"string": "Permission denied"}]}}"#;
+        assert!(Health::from_json(json).is_err());
+    }
+}
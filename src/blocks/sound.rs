@@ -2,8 +2,8 @@
 use {
     crate::pulse::callbacks::ListResult,
     crate::pulse::context::{
-        flags, introspect::ServerInfo, introspect::SinkInfo, introspect::SourceInfo,
-        subscribe::subscription_masks, subscribe::Facility,
+        flags, introspect::ServerInfo, introspect::SinkInfo, introspect::SinkInputInfo,
+        introspect::SourceInfo, subscribe::subscription_masks, subscribe::Facility,
         subscribe::Operation as SubscribeOperation, Context, State as PulseState,
     },
     crate::pulse::mainloop::standard::IterateResult,
@@ -36,7 +36,7 @@ use crate::config::{Config, LogicalDirection};
 use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::subprocess::spawn_child_async;
+use crate::subprocess::OwnedChild;
 use crate::util::{format_percent_bar, pseudo_uuid, FormatTemplate};
 use crate::widget::{I3BarWidget, Spacing, State};
 use crate::widgets::button::ButtonWidget;
@@ -45,11 +45,26 @@ trait SoundDevice {
     fn volume(&self) -> u32;
     fn muted(&self) -> bool;
     fn output_name(&self) -> String;
+    fn output_description(&self) -> String {
+        self.output_name()
+    }
+    /// Whether there is something to show, which an application that is not playing is not.
+    fn available(&self) -> bool {
+        true
+    }
 
     fn get_info(&mut self) -> Result<()>;
     fn set_volume(&mut self, step: i32, max_vol: Option<u32>) -> Result<()>;
     fn toggle(&mut self) -> Result<()>;
     fn monitor(&mut self, id: String, tx_update_request: Sender<Task>) -> Result<()>;
+
+    /// Makes the next (or previous) device of the same kind the default one.
+    fn cycle_default(&mut self, _forward: bool) -> Result<()> {
+        Err(BlockError(
+            "sound".into(),
+            "switching devices requires PulseAudio".into(),
+        ))
+    }
 }
 
 struct AlsaSoundDevice {
@@ -58,6 +73,8 @@ struct AlsaSoundDevice {
     natural_mapping: bool,
     volume: u32,
     muted: bool,
+    /// `alsactl monitor`, which is killed with the block
+    process: OwnedChild,
 }
 
 impl AlsaSoundDevice {
@@ -68,6 +85,7 @@ impl AlsaSoundDevice {
             natural_mapping,
             volume: 0,
             muted: false,
+            process: OwnedChild::new(),
         };
         sd.get_info()?;
 
@@ -164,30 +182,44 @@ impl SoundDevice for AlsaSoundDevice {
 
     fn monitor(&mut self, id: String, tx_update_request: Sender<Task>) -> Result<()> {
         // Monitor volume changes in a separate thread.
+        let child = self.process.handle();
         thread::Builder::new()
             .name("sound_alsa".into())
             .spawn(move || {
                 // Line-buffer to reduce noise.
-                let mut monitor = Command::new("stdbuf")
-                    .args(&["-oL", "alsactl", "monitor"])
-                    .stdout(Stdio::piped())
-                    .spawn()
+                let mut monitor = match child
+                    .spawn(
+                        Command::new("stdbuf")
+                            .args(&["-oL", "alsactl", "monitor"])
+                            .stdout(Stdio::piped()),
+                    )
                     .expect("Failed to start alsactl monitor")
-                    .stdout
-                    .expect("Failed to pipe alsactl monitor output");
+                {
+                    Some(pipes) => pipes.stdout.expect("Failed to pipe alsactl monitor output"),
+                    // The block is gone
+                    None => return,
+                };
 
                 let mut buffer = [0; 1024]; // Should be more than enough.
                 loop {
                     // Block until we get some output. Doesn't really matter what
                     // the output actually is -- these are events -- we just update
                     // the sound information if *something* happens.
-                    if monitor.read(&mut buffer).is_ok() {
-                        tx_update_request
-                            .send(Task {
-                                id: id.clone(),
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
+                    match monitor.read(&mut buffer) {
+                        // alsactl was killed with the block
+                        Ok(0) => return,
+                        Ok(_) => {
+                            if tx_update_request
+                                .send(Task {
+                                    id: id.clone(),
+                                    update_time: Instant::now(),
+                                })
+                                .is_err()
+                            {
+                                return;
+                            }
+                        }
+                        Err(_) => {}
                     }
                     // Don't update too often. Wait 1/4 second, fast enough for
                     // volume button mashing but slow enough to skip event spam.
@@ -214,6 +246,9 @@ struct PulseAudioClient {
 #[cfg(feature = "pulseaudio")]
 struct PulseAudioSoundDevice {
     name: Option<String>,
+    description: Option<String>,
+    /// The key of the stream of the application, for sink inputs
+    stream: Option<String>,
     device_kind: DeviceKind,
     volume: Option<ChannelVolumes>,
     volume_avg: u32,
@@ -223,9 +258,13 @@ struct PulseAudioSoundDevice {
 #[cfg(feature = "pulseaudio")]
 #[derive(Debug)]
 struct PulseAudioVolInfo {
+    index: u32,
     volume: ChannelVolumes,
     mute: bool,
     name: String,
+    description: Option<String>,
+    /// The binary of the application, for sink inputs
+    binary: Option<String>,
 }
 
 #[cfg(feature = "pulseaudio")]
@@ -236,9 +275,12 @@ impl TryFrom<&SourceInfo<'_>> for PulseAudioVolInfo {
         match source_info.name.as_ref() {
             None => Err(()),
             Some(name) => Ok(PulseAudioVolInfo {
+                index: source_info.index,
                 volume: source_info.volume,
                 mute: source_info.mute,
                 name: name.to_string(),
+                description: source_info.description.as_ref().map(|d| d.to_string()),
+                binary: None,
             }),
         }
     }
@@ -252,22 +294,50 @@ impl TryFrom<&SinkInfo<'_>> for PulseAudioVolInfo {
         match sink_info.name.as_ref() {
             None => Err(()),
             Some(name) => Ok(PulseAudioVolInfo {
+                index: sink_info.index,
                 volume: sink_info.volume,
                 mute: sink_info.mute,
                 name: name.to_string(),
+                description: sink_info.description.as_ref().map(|d| d.to_string()),
+                binary: None,
             }),
         }
     }
 }
 
+#[cfg(feature = "pulseaudio")]
+impl TryFrom<&SinkInputInfo<'_>> for PulseAudioVolInfo {
+    type Error = ();
+
+    /// Sink inputs are known by their index, and described by their application.
+    fn try_from(sink_input_info: &SinkInputInfo) -> std::result::Result<Self, Self::Error> {
+        Ok(PulseAudioVolInfo {
+            index: sink_input_info.index,
+            volume: sink_input_info.volume,
+            mute: sink_input_info.mute,
+            name: sink_input_info.index.to_string(),
+            description: sink_input_info
+                .proplist
+                .get_str(properties::APPLICATION_NAME),
+            binary: sink_input_info
+                .proplist
+                .get_str(properties::APPLICATION_PROCESS_BINARY),
+        })
+    }
+}
+
 #[cfg(feature = "pulseaudio")]
 #[derive(Debug)]
 enum PulseAudioClientRequest {
     GetDefaultDevice,
+    GetDevices(DeviceKind),
     GetInfoByIndex(DeviceKind, u32),
     GetInfoByName(DeviceKind, String),
     SetVolumeByName(DeviceKind, String, ChannelVolumes),
     SetMuteByName(DeviceKind, String, bool),
+    SetDefaultDevice(DeviceKind, String),
+    MoveSinkInputs(String),
+    MoveSinkInput(u32, String),
 }
 
 #[cfg(feature = "pulseaudio")]
@@ -395,6 +465,20 @@ impl PulseAudioClient {
                                     introspector
                                         .get_server_info(PulseAudioClient::server_info_callback);
                                 }
+                                GetDevices(DeviceKind::Sink) => {
+                                    introspector
+                                        .get_sink_info_list(PulseAudioClient::sink_info_callback);
+                                }
+                                GetDevices(DeviceKind::Source) => {
+                                    introspector.get_source_info_list(
+                                        PulseAudioClient::source_info_callback,
+                                    );
+                                }
+                                GetDevices(DeviceKind::SinkInput) => {
+                                    introspector.get_sink_input_info_list(
+                                        PulseAudioClient::sink_input_info_callback,
+                                    );
+                                }
                                 GetInfoByIndex(DeviceKind::Sink, index) => {
                                     introspector.get_sink_info_by_index(
                                         index,
@@ -407,6 +491,12 @@ impl PulseAudioClient {
                                         PulseAudioClient::source_info_callback,
                                     );
                                 }
+                                GetInfoByIndex(DeviceKind::SinkInput, index) => {
+                                    introspector.get_sink_input_info(
+                                        index,
+                                        PulseAudioClient::sink_input_info_callback,
+                                    );
+                                }
                                 GetInfoByName(DeviceKind::Sink, name) => {
                                     introspector.get_sink_info_by_name(
                                         &name,
@@ -419,18 +509,67 @@ impl PulseAudioClient {
                                         PulseAudioClient::source_info_callback,
                                     );
                                 }
+                                // The names of sink inputs are their indices
+                                GetInfoByName(DeviceKind::SinkInput, name) => {
+                                    if let Ok(index) = name.parse() {
+                                        introspector.get_sink_input_info(
+                                            index,
+                                            PulseAudioClient::sink_input_info_callback,
+                                        );
+                                    }
+                                }
                                 SetVolumeByName(DeviceKind::Sink, name, volumes) => {
                                     introspector.set_sink_volume_by_name(&name, &volumes, None);
                                 }
                                 SetVolumeByName(DeviceKind::Source, name, volumes) => {
                                     introspector.set_source_volume_by_name(&name, &volumes, None);
                                 }
+                                SetVolumeByName(DeviceKind::SinkInput, name, volumes) => {
+                                    if let Ok(index) = name.parse() {
+                                        introspector.set_sink_input_volume(index, &volumes, None);
+                                    }
+                                }
                                 SetMuteByName(DeviceKind::Sink, name, mute) => {
                                     introspector.set_sink_mute_by_name(&name, mute, None);
                                 }
                                 SetMuteByName(DeviceKind::Source, name, mute) => {
                                     introspector.set_source_mute_by_name(&name, mute, None);
                                 }
+                                SetMuteByName(DeviceKind::SinkInput, name, mute) => {
+                                    if let Ok(index) = name.parse() {
+                                        introspector.set_sink_input_mute(index, mute, None);
+                                    }
+                                }
+                                SetDefaultDevice(DeviceKind::Sink, name) => {
+                                    connection
+                                        .context
+                                        .borrow_mut()
+                                        .set_default_sink(&name, |_| {});
+                                }
+                                SetDefaultDevice(DeviceKind::Source, name) => {
+                                    connection
+                                        .context
+                                        .borrow_mut()
+                                        .set_default_source(&name, |_| {});
+                                }
+                                SetDefaultDevice(DeviceKind::SinkInput, _) => {}
+                                MoveSinkInputs(name) => {
+                                    // The streams are moved one by one, once they are listed
+                                    introspector.get_sink_input_info_list(
+                                        move |result: ListResult<&SinkInputInfo>| {
+                                            if let ListResult::Item(info) = result {
+                                                PulseAudioClient::send(MoveSinkInput(
+                                                    info.index,
+                                                    name.clone(),
+                                                ))
+                                                .ok();
+                                            }
+                                        },
+                                    );
+                                }
+                                MoveSinkInput(index, name) => {
+                                    introspector.move_sink_input_by_name(index, &name, None);
+                                }
                             };
 
                             // send request and receive response
@@ -457,7 +596,8 @@ impl PulseAudioClient {
                 connection.context.borrow_mut().subscribe(
                     subscription_masks::SERVER
                         | subscription_masks::SINK
-                        | subscription_masks::SOURCE,
+                        | subscription_masks::SOURCE
+                        | subscription_masks::SINK_INPUT,
                     |_| {},
                 );
 
@@ -466,6 +606,11 @@ impl PulseAudioClient {
             .unwrap();
         thread_result()?;
 
+        // Devices other than the default ones are only needed to switch between them
+        send_req.send(PulseAudioClientRequest::GetDevices(DeviceKind::Sink))?;
+        send_req.send(PulseAudioClientRequest::GetDevices(DeviceKind::Source))?;
+        send_req.send(PulseAudioClientRequest::GetDevices(DeviceKind::SinkInput))?;
+
         Ok(PulseAudioClient { sender: send_req })
     }
 
@@ -483,12 +628,24 @@ impl PulseAudioClient {
     }
 
     fn server_info_callback(server_info: &ServerInfo) {
+        // A new default device is only known after its info arrived, so it is requested
+        // right away instead of waiting for it to change.
         if let Some(default_sink) = server_info.default_sink_name.as_ref() {
             *PULSEAUDIO_DEFAULT_SINK.lock().unwrap() = default_sink.to_string();
+            PulseAudioClient::send(PulseAudioClientRequest::GetInfoByName(
+                DeviceKind::Sink,
+                default_sink.to_string(),
+            ))
+            .ok();
         }
 
         if let Some(default_source) = server_info.default_source_name.as_ref() {
             *PULSEAUDIO_DEFAULT_SOURCE.lock().unwrap() = default_source.to_string();
+            PulseAudioClient::send(PulseAudioClientRequest::GetInfoByName(
+                DeviceKind::Source,
+                default_source.to_string(),
+            ))
+            .ok();
         }
 
         PulseAudioClient::send_update_event();
@@ -525,11 +682,38 @@ impl PulseAudioClient {
         }
     }
 
+    fn sink_input_info_callback(result: ListResult<&SinkInputInfo>) {
+        if let Some(vol_info) = Self::get_info_callback(result) {
+            PULSEAUDIO_DEVICES
+                .lock()
+                .unwrap()
+                .insert((DeviceKind::SinkInput, vol_info.name.to_string()), vol_info);
+
+            PulseAudioClient::send_update_event();
+        }
+    }
+
     fn subscribe_callback(
         facility: Option<Facility>,
-        _operation: Option<SubscribeOperation>,
+        operation: Option<SubscribeOperation>,
         index: u32,
     ) {
+        // Removed devices cannot be queried anymore, and are forgotten instead
+        if let Some(SubscribeOperation::Removed) = operation {
+            let device_kind = match facility {
+                Some(Facility::Sink) => DeviceKind::Sink,
+                Some(Facility::Source) => DeviceKind::Source,
+                Some(Facility::SinkInput) => DeviceKind::SinkInput,
+                _ => return,
+            };
+            PULSEAUDIO_DEVICES
+                .lock()
+                .unwrap()
+                .retain(|(kind, _), info| *kind != device_kind || info.index != index);
+            PulseAudioClient::send_update_event();
+            return;
+        }
+
         match facility {
             None => {}
             Some(facility) => match facility {
@@ -550,20 +734,31 @@ impl PulseAudioClient {
                     ))
                     .ok();
                 }
+                Facility::SinkInput => {
+                    PulseAudioClient::send(PulseAudioClientRequest::GetInfoByIndex(
+                        DeviceKind::SinkInput,
+                        index,
+                    ))
+                    .ok();
+                }
                 _ => {}
             },
         }
     }
 
     fn send_update_event() {
-        for (id, tx_update_request) in &*PULSEAUDIO_EVENT_LISTENER.lock().unwrap() {
-            tx_update_request
-                .send(Task {
-                    id: id.clone(),
-                    update_time: Instant::now(),
-                })
-                .unwrap();
-        }
+        // Blocks of a configuration that was reloaded are gone, and so are their receivers
+        PULSEAUDIO_EVENT_LISTENER
+            .lock()
+            .unwrap()
+            .retain(|id, tx_update_request| {
+                tx_update_request
+                    .send(Task {
+                        id: id.clone(),
+                        update_time: Instant::now(),
+                    })
+                    .is_ok()
+            });
     }
 }
 
@@ -574,16 +769,21 @@ impl PulseAudioSoundDevice {
 
         let device = PulseAudioSoundDevice {
             name: None,
+            description: None,
+            stream: None,
             device_kind,
             volume: None,
             volume_avg: 0,
             muted: false,
         };
 
-        PulseAudioClient::send(PulseAudioClientRequest::GetInfoByName(
-            device_kind,
-            device.name(),
-        ))?;
+        // The streams of applications are all listed when connecting
+        if device_kind != DeviceKind::SinkInput {
+            PulseAudioClient::send(PulseAudioClientRequest::GetInfoByName(
+                device_kind,
+                device.name(),
+            ))?;
+        }
 
         Ok(device)
     }
@@ -599,6 +799,14 @@ impl PulseAudioSoundDevice {
             .unwrap_or_else(|| self.device_kind.default_name())
     }
 
+    /// The name that PulseAudio knows the device by.
+    fn target(&self) -> String {
+        match self.device_kind {
+            DeviceKind::SinkInput => self.stream.clone().unwrap_or_default(),
+            _ => self.name(),
+        }
+    }
+
     fn volume(&mut self, volume: ChannelVolumes) {
         self.volume = Some(volume);
         self.volume_avg = (volume.avg().0 as f32 / VOLUME_NORM.0 as f32 * 100.0).round() as u32;
@@ -619,12 +827,41 @@ impl SoundDevice for PulseAudioSoundDevice {
         self.name()
     }
 
+    fn output_description(&self) -> String {
+        self.description.clone().unwrap_or_else(|| self.name())
+    }
+
+    fn available(&self) -> bool {
+        self.device_kind != DeviceKind::SinkInput || self.stream.is_some()
+    }
+
     fn get_info(&mut self) -> Result<()> {
         let devices = PULSEAUDIO_DEVICES.lock().unwrap();
 
-        if let Some(info) = devices.get(&(self.device_kind, self.name())) {
+        // An application is matched by its name or binary, and the oldest of its streams is used
+        if self.device_kind == DeviceKind::SinkInput {
+            let application = self.name().to_lowercase();
+            self.stream = devices
+                .iter()
+                .filter(|((kind, _), info)| {
+                    *kind == DeviceKind::SinkInput
+                        && (info.description.as_ref().map(|d| d.to_lowercase())
+                            == Some(application.clone())
+                            || info.binary.as_ref().map(|b| b.to_lowercase())
+                                == Some(application.clone()))
+                })
+                .min_by_key(|(_, info)| info.index)
+                .map(|(_, info)| info.name.clone());
+            if self.stream.is_none() {
+                self.volume = None;
+                self.muted = false;
+            }
+        }
+
+        if let Some(info) = devices.get(&(self.device_kind, self.target())) {
             self.volume(info.volume);
             self.muted = info.mute;
+            self.description = info.description.clone();
         }
 
         Ok(())
@@ -655,7 +892,7 @@ impl SoundDevice for PulseAudioSoundDevice {
         self.volume(volume);
         PulseAudioClient::send(PulseAudioClientRequest::SetVolumeByName(
             self.device_kind,
-            self.name(),
+            self.target(),
             volume,
         ))?;
 
@@ -663,11 +900,14 @@ impl SoundDevice for PulseAudioSoundDevice {
     }
 
     fn toggle(&mut self) -> Result<()> {
+        if !self.available() {
+            return Ok(());
+        }
         self.muted = !self.muted;
 
         PulseAudioClient::send(PulseAudioClientRequest::SetMuteByName(
             self.device_kind,
-            self.name(),
+            self.target(),
             self.muted,
         ))?;
 
@@ -681,6 +921,41 @@ impl SoundDevice for PulseAudioSoundDevice {
             .insert(id, tx_update_request);
         Ok(())
     }
+
+    fn cycle_default(&mut self, forward: bool) -> Result<()> {
+        if self.device_kind == DeviceKind::SinkInput {
+            return Ok(());
+        }
+        // The monitors of the sinks are sources too, but not ones to record from
+        let mut devices = PULSEAUDIO_DEVICES
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|((kind, name), _)| *kind == self.device_kind && !name.ends_with(".monitor"))
+            .map(|(_, info)| (info.index, info.name.clone()))
+            .collect::<Vec<_>>();
+        devices.sort();
+        if devices.is_empty() {
+            return Ok(());
+        }
+
+        let current = self.device_kind.default_name();
+        let next = match devices.iter().position(|(_, name)| *name == current) {
+            Some(position) if forward => (position + 1) % devices.len(),
+            Some(position) => (position + devices.len() - 1) % devices.len(),
+            None => 0,
+        };
+        let name = devices[next].1.clone();
+
+        PulseAudioClient::send(PulseAudioClientRequest::SetDefaultDevice(
+            self.device_kind,
+            name.clone(),
+        ))?;
+        if self.device_kind == DeviceKind::Sink {
+            PulseAudioClient::send(PulseAudioClientRequest::MoveSinkInputs(name))?;
+        }
+        Ok(())
+    }
 }
 
 // TODO: Use the alsa control bindings to implement push updates
@@ -692,11 +967,11 @@ pub struct Sound {
     step_width: u32,
     format: FormatTemplate,
     config: Config,
-    on_click: Option<String>,
     show_volume_when_muted: bool,
     bar: bool,
     mappings: Option<BTreeMap<String, String>>,
     max_vol: Option<u32>,
+    critical_when_live: bool,
 }
 
 #[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, PartialEq)]
@@ -704,6 +979,9 @@ pub struct Sound {
 pub enum DeviceKind {
     Sink,
     Source,
+    /// The stream of an application
+    #[serde(rename = "sink_input")]
+    SinkInput,
 }
 
 #[cfg(feature = "pulseaudio")]
@@ -712,6 +990,7 @@ impl DeviceKind {
         match self {
             Self::Sink => PULSEAUDIO_DEFAULT_SINK.lock().unwrap().to_string(),
             Self::Source => PULSEAUDIO_DEFAULT_SOURCE.lock().unwrap().to_string(),
+            Self::SinkInput => String::new(),
         }
     }
 }
@@ -755,9 +1034,6 @@ pub struct SoundConfig {
     #[serde(default = "SoundConfig::default_format")]
     pub format: String,
 
-    #[serde(default = "SoundConfig::default_on_click")]
-    pub on_click: Option<String>,
-
     #[serde(default = "SoundConfig::default_show_volume_when_muted")]
     pub show_volume_when_muted: bool,
 
@@ -770,6 +1046,10 @@ pub struct SoundConfig {
 
     #[serde(default = "SoundConfig::default_max_vol")]
     pub max_vol: Option<u32>,
+
+    /// Show a source that is not muted as critical, so that a live microphone stands out
+    #[serde(default = "SoundConfig::default_critical_when_live")]
+    pub critical_when_live: bool,
 }
 
 #[derive(Deserialize, Copy, Clone, Debug)]
@@ -808,10 +1088,6 @@ impl SoundConfig {
         "{volume}%".into()
     }
 
-    fn default_on_click() -> Option<String> {
-        None
-    }
-
     fn default_show_volume_when_muted() -> bool {
         false
     }
@@ -827,13 +1103,17 @@ impl SoundConfig {
     fn default_max_vol() -> Option<u32> {
         None
     }
+
+    fn default_critical_when_live() -> bool {
+        true
+    }
 }
 
 impl Sound {
     fn icon(&self, volume: u32) -> String {
         let prefix = match self.device_kind {
             DeviceKind::Source => "microphone",
-            DeviceKind::Sink => "volume",
+            DeviceKind::Sink | DeviceKind::SinkInput => "volume",
         };
 
         let suffix = match volume {
@@ -846,9 +1126,22 @@ impl Sound {
         format!("{}_{}", prefix, suffix)
     }
 
+    /// Whether the device is a microphone that is shown as critical while it is live.
+    fn shows_live(&self) -> bool {
+        self.critical_when_live && self.device_kind == DeviceKind::Source
+    }
+
     fn display(&mut self) -> Result<()> {
         self.device.get_info()?;
 
+        if !self.device.available() {
+            self.text.set_icon(&self.icon(0));
+            self.text.set_text("");
+            self.text.set_spacing(Spacing::Hidden);
+            self.text.set_state(State::Idle);
+            return Ok(());
+        }
+
         let volume = self.device.volume();
         let output_name = self.device.output_name();
         let mapped_output_name = if let Some(m) = &self.mappings {
@@ -860,7 +1153,8 @@ impl Sound {
             output_name
         };
         let values = map!("{volume}" => format!("{:02}", volume),
-                          "{output_name}" => mapped_output_name
+                          "{output_name}" => mapped_output_name,
+                          "{output_description}" => self.device.output_description()
         );
         let text = self.format.render_static_str(&values)?;
 
@@ -877,7 +1171,12 @@ impl Sound {
                 self.text.set_text("");
                 self.text.set_spacing(Spacing::Hidden);
             }
-            self.text.set_state(State::Warning);
+            // A muted microphone is the safe state
+            self.text.set_state(if self.shows_live() {
+                State::Idle
+            } else {
+                State::Warning
+            });
         } else {
             self.text.set_icon(&self.icon(volume));
             self.text.set_text(if self.bar {
@@ -886,7 +1185,11 @@ impl Sound {
                 text
             });
             self.text.set_spacing(Spacing::Normal);
-            self.text.set_state(State::Idle);
+            self.text.set_state(if self.shows_live() {
+                State::Critical
+            } else {
+                State::Idle
+            });
         }
 
         Ok(())
@@ -901,6 +1204,13 @@ impl ConfigBlock for Sound {
         config: Config,
         tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        if block_config.device_kind == DeviceKind::SinkInput && block_config.name.is_none() {
+            return Err(BlockError(
+                "sound".into(),
+                "`device_kind = \"sink_input\"` requires the `name` of an application".into(),
+            ));
+        }
+
         let id = pseudo_uuid();
         let mut step_width = block_config.step_width;
         if step_width > 50 {
@@ -930,6 +1240,8 @@ impl ConfigBlock for Sound {
         // prefer PulseAudio if available and selected, fallback to ALSA
         let device: Box<dyn SoundDevice> = match pulseaudio_device {
             Ok(dev) => Box::new(dev),
+            // ALSA knows nothing of applications
+            Err(err) if block_config.device_kind == DeviceKind::SinkInput => return Err(err),
             Err(_) => Box::new(AlsaSoundDevice::new(
                 block_config.name.unwrap_or_else(|| "Master".into()),
                 block_config.device.unwrap_or_else(|| "default".into()),
@@ -945,11 +1257,11 @@ impl ConfigBlock for Sound {
             format: FormatTemplate::from_string(&block_config.format)?,
             step_width,
             config,
-            on_click: block_config.on_click,
             show_volume_when_muted: block_config.show_volume_when_muted,
             bar: block_config.bar,
             mappings: block_config.mappings,
             max_vol: block_config.max_vol,
+            critical_when_live: block_config.critical_when_live,
         };
 
         sound.device.monitor(id, tx_update_request)?;
@@ -975,11 +1287,21 @@ impl Block for Sound {
         if let Some(ref name) = e.name {
             if name.as_str() == self.id {
                 match e.button {
-                    MouseButton::Right => self.device.toggle()?,
-                    MouseButton::Left => {
-                        if let Some(ref cmd) = self.on_click {
-                            spawn_child_async("sh", &["-c", cmd])
-                                .block_error("sound", "could not spawn child")?;
+                    MouseButton::Right | MouseButton::Middle => self.device.toggle()?,
+                    MouseButton::Left if e.has_modifier("Shift") => {
+                        let volume = self.device.volume() as i32;
+                        self.device.set_volume(100 - volume, Some(100))?
+                    }
+                    // Muting is what a microphone is clicked for, e.g. during calls
+                    MouseButton::Left if self.device_kind == DeviceKind::Source => {
+                        self.device.toggle()?
+                    }
+                    _ if e.has_modifier("Shift") => {
+                        use LogicalDirection::*;
+                        match self.config.scrolling.to_logical_direction(e.button) {
+                            Some(Up) => self.device.cycle_default(true)?,
+                            Some(Down) => self.device.cycle_default(false)?,
+                            None => (),
                         }
                     }
                     _ => {
@@ -1002,6 +1324,16 @@ impl Block for Sound {
         Ok(())
     }
 
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        match event.button {
+            MouseButton::Left => {
+                event.has_modifier("Shift") || self.device_kind == DeviceKind::Source
+            }
+            MouseButton::Right | MouseButton::Middle => true,
+            _ => matches!(event.button, MouseButton::WheelUp | MouseButton::WheelDown),
+        }
+    }
+
     fn id(&self) -> &str {
         &self.id
     }
@@ -140,11 +140,15 @@ fn make_thread(
 
                             *update = true;
 
-                            done.send(Task {
-                                id: id.clone(),
-                                update_time: Instant::now(),
-                            })
-                            .unwrap();
+                            if done
+                                .send(Task {
+                                    id: id.clone(),
+                                    update_time: Instant::now(),
+                                })
+                                .is_err()
+                            {
+                                return;
+                            }
                         }
                     }
                 }
@@ -248,6 +252,10 @@ impl Block for SpeedTest {
         Ok(())
     }
 
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left
+    }
+
     fn view(&self) -> Vec<&dyn I3BarWidget> {
         let mut new: Vec<&dyn I3BarWidget> = Vec::with_capacity(self.text.len());
         for w in &self.text {
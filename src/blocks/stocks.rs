@@ -0,0 +1,413 @@
+//! A block for the prices of stocks and indices, from Yahoo Finance or Alpha Vantage.
+
+use std::collections::HashMap;
+use std::time::Duration;
+
+use chrono::{DateTime, Datelike, NaiveTime, TimeZone, Utc, Weekday};
+use chrono_tz::Tz;
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::{Config, LogicalDirection, Scrolling};
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::I3BarEvent;
+use crate::scheduler::Task;
+use crate::util::{http_get, pseudo_uuid, read_token, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+// Yahoo Finance refuses the user agent of curl
+const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux x86_64; rv:128.0) Gecko/20100101 Firefox/128.0";
+
+#[derive(Copy, Clone, Debug, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum StocksService {
+    Yahoo,
+    AlphaVantage,
+}
+
+impl Default for StocksService {
+    fn default() -> Self {
+        StocksService::Yahoo
+    }
+}
+
+/// The price of a symbol, with its change in percent since the previous close.
+#[derive(Clone, Debug, PartialEq)]
+struct Quote {
+    price: f64,
+    change_percent: Option<f64>,
+}
+
+fn malformed_json_error() -> Error {
+    BlockError("stocks".to_string(), "Malformed JSON.".to_string())
+}
+
+/// The quote in the answer of `/v8/finance/chart/` of Yahoo Finance.
+fn parse_yahoo(json: &Value) -> Result<Quote> {
+    if let Some(description) = json
+        .pointer("/chart/error/description")
+        .and_then(|v| v.as_str())
+    {
+        return Err(BlockError(
+            "stocks".to_string(),
+            format!("API Error: {}", description),
+        ));
+    }
+    let meta = json
+        .pointer("/chart/result/0/meta")
+        .ok_or_else(malformed_json_error)?;
+    let price = meta["regularMarketPrice"]
+        .as_f64()
+        .ok_or_else(malformed_json_error)?;
+    let previous_close = meta["previousClose"]
+        .as_f64()
+        .or_else(|| meta["chartPreviousClose"].as_f64());
+    Ok(Quote {
+        price,
+        change_percent: previous_close
+            .filter(|close| *close > 0.0)
+            .map(|close| (price - close) / close * 100.0),
+    })
+}
+
+/// The quote in the answer of `GLOBAL_QUOTE` of Alpha Vantage, which has numbers as strings.
+fn parse_alphavantage(json: &Value) -> Result<Quote> {
+    // The rate limit and wrong keys are answered with a note instead of a quote
+    if let Some(note) = json["Information"]
+        .as_str()
+        .or_else(|| json["Note"].as_str())
+        .or_else(|| json["Error Message"].as_str())
+    {
+        return Err(BlockError(
+            "stocks".to_string(),
+            format!("API Error: {}", note),
+        ));
+    }
+    let quote = &json["Global Quote"];
+    Ok(Quote {
+        price: quote["05. price"]
+            .as_str()
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(malformed_json_error)?,
+        change_percent: quote["10. change percent"]
+            .as_str()
+            .and_then(|s| s.trim_end_matches('%').parse().ok()),
+    })
+}
+
+fn is_trading_day(weekday: Weekday) -> bool {
+    weekday != Weekday::Sat && weekday != Weekday::Sun
+}
+
+/// The next time the market opens, or `None` while it is open. Markets are open on weekdays,
+/// without knowing about holidays.
+fn next_open(now: DateTime<Tz>, open: NaiveTime, close: NaiveTime) -> Option<DateTime<Tz>> {
+    let time = now.time();
+    let mut date = now.date().naive_local();
+    if is_trading_day(date.weekday()) {
+        if time >= open && time < close {
+            return None;
+        }
+        if time < open {
+            return now
+                .timezone()
+                .from_local_datetime(&date.and_time(open))
+                .earliest();
+        }
+    }
+    date = date.succ();
+    while !is_trading_day(date.weekday()) {
+        date = date.succ();
+    }
+    now.timezone()
+        .from_local_datetime(&date.and_time(open))
+        .earliest()
+}
+
+/// Fetches a JSON document as a browser, as Yahoo refuses other clients.
+fn fetch(url: &str) -> Result<Value> {
+    let headers = [format!("User-Agent: {}", USER_AGENT)];
+    let response = http_get("stocks", url, &headers, Duration::from_secs(10))?;
+    if !response.is_success() {
+        return Err(BlockError(
+            "stocks".to_string(),
+            format!("API Error: HTTP status {}", response.status),
+        ));
+    }
+    response.json("stocks")
+}
+
+pub struct Stocks {
+    id: String,
+    output: ButtonWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    service: StocksService,
+    symbols: Vec<String>,
+    api_key: Option<String>,
+    scrolling: Scrolling,
+    market_timezone: Tz,
+    market_open: NaiveTime,
+    market_close: NaiveTime,
+    /// The index of the symbol that is shown
+    current: usize,
+    /// The latest quotes, which scrolling shows without asking the API again
+    quotes: HashMap<String, Quote>,
+    /// Whether the quotes were fetched after the market closed, which gives the closing prices
+    fetched_closed: bool,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct StocksConfig {
+    /// Update interval in seconds while the market is open
+    #[serde(
+        default = "StocksConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// The API to ask
+    #[serde(default)]
+    pub service: StocksService,
+
+    /// The symbols, like `AAPL` or `^GSPC`
+    pub symbols: Vec<String>,
+
+    /// A file with the API key of Alpha Vantage
+    #[serde(default)]
+    pub api_key_file: Option<String>,
+
+    /// Format string
+    #[serde(default = "StocksConfig::default_format")]
+    pub format: String,
+
+    /// The timezone of the market
+    #[serde(default = "StocksConfig::default_market_timezone")]
+    pub market_timezone: Tz,
+
+    /// The time the market opens, as `HH:MM`
+    #[serde(default = "StocksConfig::default_market_open")]
+    pub market_open: String,
+
+    /// The time the market closes, as `HH:MM`
+    #[serde(default = "StocksConfig::default_market_close")]
+    pub market_close: String,
+}
+
+impl StocksConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(300)
+    }
+
+    fn default_format() -> String {
+        "{symbol} {price} {change_percent}%".to_owned()
+    }
+
+    fn default_market_timezone() -> Tz {
+        chrono_tz::America::New_York
+    }
+
+    fn default_market_open() -> String {
+        "09:30".to_owned()
+    }
+
+    fn default_market_close() -> String {
+        "16:00".to_owned()
+    }
+}
+
+impl ConfigBlock for Stocks {
+    type Config = StocksConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        if block_config.symbols.is_empty() {
+            return Err(BlockError(
+                "stocks".to_string(),
+                "at least one symbol must be configured".to_string(),
+            ));
+        }
+        let api_key = block_config
+            .api_key_file
+            .as_deref()
+            .map(|path| read_token("stocks", path))
+            .transpose()?;
+        if block_config.service == StocksService::AlphaVantage && api_key.is_none() {
+            return Err(BlockError(
+                "stocks".to_string(),
+                "Alpha Vantage needs an 'api_key_file'".to_string(),
+            ));
+        }
+        let parse_time = |time: &str| {
+            NaiveTime::parse_from_str(time, "%H:%M")
+                .block_error("stocks", &format!("invalid time {}, expected HH:MM", time))
+        };
+
+        let id = pseudo_uuid();
+        Ok(Stocks {
+            scrolling: config.scrolling,
+            output: ButtonWidget::new(config, &id),
+            id,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("stocks", "Invalid format specified")?,
+            service: block_config.service,
+            symbols: block_config.symbols,
+            api_key,
+            market_timezone: block_config.market_timezone,
+            market_open: parse_time(&block_config.market_open)?,
+            market_close: parse_time(&block_config.market_close)?,
+            current: 0,
+            quotes: HashMap::new(),
+            fetched_closed: false,
+        })
+    }
+}
+
+impl Stocks {
+    fn fetch_quote(&self, symbol: &str) -> Result<Quote> {
+        match self.service {
+            StocksService::Yahoo => parse_yahoo(&fetch(&format!(
+                "https://query1.finance.yahoo.com/v8/finance/chart/{}?range=1d&interval=1d",
+                // Indices start with a caret, like `^GSPC`
+                symbol.replace("^", "%5E")
+            ))?),
+            StocksService::AlphaVantage => parse_alphavantage(&fetch(&format!(
+                "https://www.alphavantage.co/query?function=GLOBAL_QUOTE&symbol={}&apikey={}",
+                symbol,
+                self.api_key.as_deref().unwrap_or_default()
+            ))?),
+        }
+    }
+
+    fn render(&mut self) -> Result<()> {
+        let symbol = &self.symbols[self.current];
+        let quote = self.quotes.get(symbol);
+        let values = map!(
+            "{symbol}" => symbol.clone(),
+            "{price}" => quote.map_or("-".to_string(), |q| format!("{:.2}", q.price)),
+            "{change_percent}" => quote
+                .and_then(|q| q.change_percent)
+                .map_or("-".to_string(), |change| format!("{:+.2}", change))
+        );
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
+        self.output
+            .set_state(match quote.and_then(|q| q.change_percent) {
+                Some(change) if change > 0.0 => State::Good,
+                Some(change) if change < 0.0 => State::Critical,
+                _ => State::Idle,
+            });
+        Ok(())
+    }
+}
+
+impl Block for Stocks {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let now = Utc::now().with_timezone(&self.market_timezone);
+        let next_open = next_open(now, self.market_open, self.market_close);
+
+        // Outside trading hours, the quotes are fetched once for the closing prices
+        if next_open.is_none() || !self.fetched_closed {
+            for symbol in &self.symbols {
+                let quote = self.fetch_quote(symbol)?;
+                self.quotes.insert(symbol.clone(), quote);
+            }
+            self.fetched_closed = next_open.is_some();
+        }
+        self.render()?;
+
+        Ok(Some(match next_open {
+            None => self.update_interval.into(),
+            Some(next_open) => (next_open.with_timezone(&Utc) - Utc::now())
+                .to_std()
+                .unwrap_or(self.update_interval)
+                .into(),
+        }))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if !event.matches_name(&self.id) {
+            return Ok(());
+        }
+        let count = self.symbols.len();
+        self.current = match self.scrolling.to_logical_direction(event.button) {
+            Some(LogicalDirection::Up) => (self.current + 1) % count,
+            Some(LogicalDirection::Down) => (self.current + count - 1) % count,
+            None => return Ok(()),
+        };
+        self.render()
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{next_open, parse_alphavantage, parse_yahoo};
+    use chrono::{NaiveTime, TimeZone};
+    use chrono_tz::America::New_York;
+
+    #[test]
+    fn test_parse_yahoo() {
+        let json = serde_json::json!({"chart": {"result": [{"meta": {
+            "currency": "USD",
+            "symbol": "AAPL",
+            "regularMarketPrice": 189.0,
+            "chartPreviousClose": 180.0
+        }}], "error": null}});
+        let quote = parse_yahoo(&json).unwrap();
+        assert_eq!(quote.price, 189.0);
+        assert!((quote.change_percent.unwrap() - 5.0).abs() < 1e-9);
+
+        let json = serde_json::json!({"chart": {"result": null, "error": {
+            "code": "Not Found", "description": "No data found, symbol may be delisted"
+        }}});
+        assert!(parse_yahoo(&json).is_err());
+    }
+
+    #[test]
+    fn test_parse_alphavantage() {
+        let json = serde_json::json!({"Global Quote": {
+            "01. symbol": "IBM",
+            "05. price": "168.5500",
+            "10. change percent": "-0.4550%"
+        }});
+        let quote = parse_alphavantage(&json).unwrap();
+        assert_eq!(quote.price, 168.55);
+        assert_eq!(quote.change_percent, Some(-0.455));
+
+        let json = serde_json::json!({"Information": "Our standard API rate limit is 25 requests per day."});
+        assert!(parse_alphavantage(&json).is_err());
+    }
+
+    #[test]
+    fn test_next_open() {
+        let open = NaiveTime::from_hms(9, 30, 0);
+        let close = NaiveTime::from_hms(16, 0, 0);
+        // Wednesday
+        let during = New_York.ymd(2024, 3, 13).and_hms(11, 0, 0);
+        assert_eq!(next_open(during, open, close), None);
+        let before = New_York.ymd(2024, 3, 13).and_hms(8, 0, 0);
+        assert_eq!(
+            next_open(before, open, close),
+            Some(New_York.ymd(2024, 3, 13).and_hms(9, 30, 0))
+        );
+        // Friday evening
+        let after = New_York.ymd(2024, 3, 15).and_hms(16, 0, 0);
+        assert_eq!(
+            next_open(after, open, close),
+            Some(New_York.ymd(2024, 3, 18).and_hms(9, 30, 0))
+        );
+    }
+}
@@ -0,0 +1,182 @@
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::pseudo_uuid;
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+pub struct Stopwatch {
+    id: String,
+    text: ButtonWidget,
+    /// Time accumulated before the current run.
+    elapsed: Duration,
+    /// Start of the current run, if the stopwatch is running.
+    started: Option<Instant>,
+    tx_update_request: Sender<Task>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct StopwatchConfig {}
+
+/// Formats a time as `H:MM:SS`.
+fn format_elapsed(elapsed: Duration) -> String {
+    let secs = elapsed.as_secs();
+    format!("{}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+/// The time until the shown second changes.
+fn next_second(elapsed: Duration) -> Duration {
+    Duration::from_nanos(1_000_000_000 - elapsed.subsec_nanos() as u64)
+}
+
+impl Stopwatch {
+    fn elapsed(&self) -> Duration {
+        match self.started {
+            Some(start) => self.elapsed + start.elapsed(),
+            None => self.elapsed,
+        }
+    }
+}
+
+impl ConfigBlock for Stopwatch {
+    type Config = StopwatchConfig;
+
+    fn new(
+        _block_config: Self::Config,
+        config: Config,
+        tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let id = pseudo_uuid();
+        Ok(Stopwatch {
+            text: ButtonWidget::new(config, &id).with_icon("time"),
+            id,
+            elapsed: Duration::from_secs(0),
+            started: None,
+            tx_update_request,
+        })
+    }
+}
+
+impl Block for Stopwatch {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let elapsed = self.elapsed();
+        self.text.set_text(format_elapsed(elapsed));
+        self.text.set_state(match self.started {
+            Some(_) => State::Info,
+            None => State::Idle,
+        });
+
+        // While running, wake up exactly when the displayed second changes.
+        // While stopped, do not schedule any updates.
+        Ok(Some(match self.started {
+            Some(_) => next_second(elapsed).into(),
+            None => Update::Once,
+        }))
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.matches_name(&self.id) {
+            match event.button {
+                MouseButton::Left => match self.started.take() {
+                    Some(start) => self.elapsed += start.elapsed(),
+                    None => self.started = Some(Instant::now()),
+                },
+                MouseButton::Right => {
+                    self.elapsed = Duration::from_secs(0);
+                    self.started = None;
+                }
+                _ => return Ok(()),
+            }
+            self.tx_update_request.send(Task {
+                id: self.id.clone(),
+                update_time: Instant::now(),
+            })?;
+        }
+        Ok(())
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        matches!(event.button, MouseButton::Left | MouseButton::Right)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn click(id: &str, button: MouseButton) -> I3BarEvent {
+        I3BarEvent {
+            name: Some(id.to_string()),
+            instance: None,
+            x: 0,
+            y: 0,
+            button,
+            modifiers: vec![],
+        }
+    }
+
+    #[test]
+    fn test_format_elapsed() {
+        assert_eq!(format_elapsed(Duration::from_secs(0)), "0:00:00");
+        assert_eq!(format_elapsed(Duration::from_millis(61_900)), "0:01:01");
+        assert_eq!(format_elapsed(Duration::from_secs(36_000 + 59)), "10:00:59");
+    }
+
+    #[test]
+    fn test_next_second() {
+        assert_eq!(
+            next_second(Duration::from_millis(2_250)),
+            Duration::from_millis(750)
+        );
+        assert_eq!(next_second(Duration::from_secs(3)), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_clicks() {
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let mut stopwatch =
+            Stopwatch::new(StopwatchConfig::default(), Config::default(), tx).unwrap();
+        let id = stopwatch.id().to_string();
+
+        // Stopped, the block asks for no updates
+        assert_eq!(stopwatch.update().unwrap(), Some(Update::Once));
+
+        stopwatch.click(&click(&id, MouseButton::Left)).unwrap();
+        assert!(stopwatch.started.is_some());
+        assert_eq!(rx.try_recv().unwrap().id, id);
+        match stopwatch.update().unwrap() {
+            Some(Update::Every(delay)) => assert!(delay <= Duration::from_secs(1)),
+            update => panic!("unexpected update {:?}", update),
+        }
+
+        // Stopping keeps the time, and a right click resets it
+        stopwatch.click(&click(&id, MouseButton::Left)).unwrap();
+        assert!(stopwatch.started.is_none());
+        stopwatch.elapsed = Duration::from_secs(90);
+        stopwatch.click(&click(&id, MouseButton::Right)).unwrap();
+        assert_eq!(stopwatch.elapsed(), Duration::from_secs(0));
+
+        // Other buttons and clicks on other blocks are ignored
+        rx.try_iter().count();
+        stopwatch.click(&click(&id, MouseButton::Middle)).unwrap();
+        stopwatch.click(&click("other", MouseButton::Left)).unwrap();
+        assert!(rx.try_recv().is_err());
+        assert!(stopwatch.started.is_none());
+    }
+}
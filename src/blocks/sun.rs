@@ -0,0 +1,308 @@
+//! A block for the times of sunrise and sunset, computed without a network connection.
+
+use std::time::Duration;
+
+use chrono::offset::Local;
+use chrono::{NaiveDate, TimeZone};
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate, Locator};
+use crate::widget::I3BarWidget;
+use crate::widgets::text::TextWidget;
+
+/// How often the location from GeoClue2 is looked up again
+const LOCATE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// The Julian date of 2000-01-01 12:00 UTC.
+const J2000: f64 = 2_451_545.0;
+/// The Julian date of the unix epoch.
+const JULIAN_UNIX_EPOCH: f64 = 2_440_587.5;
+
+/// When the sun rises and sets on a day.
+#[derive(Debug, PartialEq)]
+enum Daylight {
+    /// The unix times of sunrise and sunset
+    SunriseSunset(i64, i64),
+    /// The sun does not set
+    PolarDay,
+    /// The sun does not rise
+    PolarNight,
+}
+
+/// Computes sunrise and sunset with the sunrise equation, which is within a minute or two
+/// away from the poles. Longitudes are positive towards the east.
+/// https://en.wikipedia.org/wiki/Sunrise_equation
+fn sun_times(date: NaiveDate, latitude: f64, longitude: f64) -> Daylight {
+    let days = date
+        .signed_duration_since(NaiveDate::from_ymd(2000, 1, 1))
+        .num_days() as f64;
+    let mean_solar_time = days - longitude / 360.0;
+    let anomaly = (357.5291 + 0.985_600_28 * mean_solar_time) % 360.0;
+    let anomaly_rad = anomaly.to_radians();
+    let center = 1.9148 * anomaly_rad.sin()
+        + 0.02 * (2.0 * anomaly_rad).sin()
+        + 0.0003 * (3.0 * anomaly_rad).sin();
+    let ecliptic_longitude = ((anomaly + center + 180.0 + 102.9372) % 360.0).to_radians();
+    let transit = J2000 + mean_solar_time + 0.0053 * anomaly_rad.sin()
+        - 0.0069 * (2.0 * ecliptic_longitude).sin();
+    let sin_declination = ecliptic_longitude.sin() * 23.4397_f64.to_radians().sin();
+    let declination = sin_declination.asin();
+
+    // The sun is at -0.833° at sunrise and sunset, because of refraction and its size
+    let latitude = latitude.to_radians();
+    let cos_hour_angle = ((-0.833_f64).to_radians().sin() - latitude.sin() * sin_declination)
+        / (latitude.cos() * declination.cos());
+    if cos_hour_angle < -1.0 {
+        return Daylight::PolarDay;
+    }
+    if cos_hour_angle > 1.0 {
+        return Daylight::PolarNight;
+    }
+    let hour_angle = cos_hour_angle.acos().to_degrees();
+    let unix = |julian: f64| ((julian - JULIAN_UNIX_EPOCH) * 86400.0).round() as i64;
+    Daylight::SunriseSunset(
+        unix(transit - hour_angle / 360.0),
+        unix(transit + hour_angle / 360.0),
+    )
+}
+
+/// Shows seconds as hours and minutes, like `5:07`.
+fn format_duration(seconds: i64) -> String {
+    let minutes = seconds.max(0) / 60;
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+pub struct Sun {
+    id: String,
+    text: TextWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    time_format: String,
+    coordinates: Option<(f64, f64)>,
+    /// Looks for the coordinates without `latitude` and `longitude`
+    locator: Option<Locator>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SunConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "SunConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Latitude, from GeoClue2 if not set
+    #[serde(default)]
+    pub latitude: Option<f64>,
+
+    /// Longitude, positive towards the east, from GeoClue2 if not set
+    #[serde(default)]
+    pub longitude: Option<f64>,
+
+    /// Format string
+    #[serde(default = "SunConfig::default_format")]
+    pub format: String,
+
+    /// Format of the times, see chrono's strftime
+    #[serde(default = "SunConfig::default_time_format")]
+    pub time_format: String,
+}
+
+impl SunConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    fn default_format() -> String {
+        "\u{2600} {sunrise} \u{2192} {sunset}".to_owned()
+    }
+
+    fn default_time_format() -> String {
+        "%H:%M".to_owned()
+    }
+}
+
+impl ConfigBlock for Sun {
+    type Config = SunConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let coordinates = match (block_config.latitude, block_config.longitude) {
+            (Some(latitude), Some(longitude)) => Some((latitude, longitude)),
+            (None, None) => None,
+            _ => {
+                return Err(BlockError(
+                    "sun".to_string(),
+                    "Both 'latitude' and 'longitude' must be provided.".to_string(),
+                ))
+            }
+        };
+
+        let id = pseudo_uuid();
+        // The times are computed offline, so only GeoClue2 is asked
+        let locator = match coordinates {
+            Some(_) => None,
+            None => Some(Locator::start(
+                "sun",
+                id.clone(),
+                send,
+                LOCATE_INTERVAL,
+                false,
+            )?),
+        };
+
+        Ok(Sun {
+            id,
+            text: TextWidget::new(config),
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("sun", "Invalid format specified")?,
+            time_format: block_config.time_format,
+            coordinates,
+            locator,
+        })
+    }
+}
+
+impl Sun {
+    /// The configured coordinates, or those from GeoClue2, which are looked up again every
+    /// hour and kept if that fails. There are none while GeoClue2 looks for them.
+    fn coordinates(&self) -> Result<Option<(f64, f64)>> {
+        if let Some(coordinates) = self.coordinates {
+            return Ok(Some(coordinates));
+        }
+        Ok(match self.locator {
+            Some(ref locator) => locator
+                .location()?
+                .map(|location| (location.latitude, location.longitude)),
+            None => None,
+        })
+    }
+
+    fn format_time(&self, time: i64) -> String {
+        Local
+            .timestamp(time, 0)
+            .format(&self.time_format)
+            .to_string()
+    }
+}
+
+impl Block for Sun {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let (latitude, longitude) = match self.coordinates()? {
+            Some(coordinates) => coordinates,
+            // The locator updates the block once it found the location
+            None => return Ok(Some(self.update_interval.into())),
+        };
+        let now = Local::now();
+        let today = now.date().naive_local();
+
+        // After sunset, the times of tomorrow are shown
+        let mut daylight = sun_times(today, latitude, longitude);
+        if let Daylight::SunriseSunset(_, sunset) = daylight {
+            if now.timestamp() >= sunset {
+                daylight = sun_times(today.succ(), latitude, longitude);
+            }
+        }
+
+        let none = || "-".to_string();
+        let values = match daylight {
+            Daylight::SunriseSunset(sunrise, sunset) => {
+                let (next, at) = if now.timestamp() < sunrise {
+                    ("sunrise", sunrise)
+                } else {
+                    ("sunset", sunset)
+                };
+                map!(
+                    "{sunrise}" => self.format_time(sunrise),
+                    "{sunset}" => self.format_time(sunset),
+                    "{next}" => next.to_string(),
+                    "{until}" => format_duration(at - now.timestamp()),
+                    "{day_length}" => format_duration(sunset - sunrise)
+                )
+            }
+            Daylight::PolarDay | Daylight::PolarNight => map!(
+                "{sunrise}" => none(),
+                "{sunset}" => none(),
+                "{next}" => none(),
+                "{until}" => none(),
+                "{day_length}" => if daylight == Daylight::PolarDay {
+                    "24:00".to_string()
+                } else {
+                    "0:00".to_string()
+                }
+            ),
+        };
+        self.text.set_text(self.format.render_static_str(&values)?);
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_duration, sun_times, Daylight};
+    use chrono::{NaiveDate, TimeZone, Utc};
+
+    /// Checks the times against those of timeanddate.com, as December days, hours and minutes
+    /// in UTC.
+    fn assert_close(daylight: Daylight, sunrise: (u32, u32, u32), sunset: (u32, u32, u32)) {
+        let time = |(day, hour, minute): (u32, u32, u32)| {
+            Utc.ymd(2020, 12, day).and_hms(hour, minute, 0).timestamp()
+        };
+        match daylight {
+            Daylight::SunriseSunset(rise, set) => {
+                assert!((rise - time(sunrise)).abs() <= 120, "sunrise {}", rise);
+                assert!((set - time(sunset)).abs() <= 120, "sunset {}", set);
+            }
+            _ => panic!("no sunrise and sunset: {:?}", daylight),
+        }
+    }
+
+    #[test]
+    fn test_sun_times() {
+        // New York, 07:16 and 16:31 in EST
+        assert_close(
+            sun_times(NaiveDate::from_ymd(2020, 12, 21), 40.7128, -74.0060),
+            (21, 12, 16),
+            (21, 21, 31),
+        );
+        // Sydney, 05:41 and 20:05 in AEDT, so sunrise is the day before in UTC
+        assert_close(
+            sun_times(NaiveDate::from_ymd(2020, 12, 21), -33.8688, 151.2093),
+            (20, 18, 41),
+            (21, 9, 5),
+        );
+        // Tromsø
+        assert_eq!(
+            sun_times(NaiveDate::from_ymd(2020, 12, 21), 69.6492, 18.9553),
+            Daylight::PolarNight
+        );
+        assert_eq!(
+            sun_times(NaiveDate::from_ymd(2020, 6, 21), 69.6492, 18.9553),
+            Daylight::PolarDay
+        );
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(0), "0:00");
+        assert_eq!(format_duration(5 * 3600 + 7 * 60 + 59), "5:07");
+        assert_eq!(format_duration(-30), "0:00");
+    }
+}
@@ -0,0 +1,323 @@
+//! A block for the state of a systemd unit, which can also start, stop and restart it.
+
+use std::os::raw::c_void;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Instant;
+
+use crossbeam_channel::Sender;
+use dbus::ffidisp::stdintf::org_freedesktop_dbus::Properties;
+use dbus::ffidisp::{BusType, Connection};
+use dbus::Message;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+const SYSTEMD: &str = "org.freedesktop.systemd1";
+const SYSTEMD_PATH: &str = "/org/freedesktop/systemd1";
+const MANAGER_INTERFACE: &str = "org.freedesktop.systemd1.Manager";
+const UNIT_INTERFACE: &str = "org.freedesktop.systemd1.Unit";
+/// How long to wait for systemd to start, stop or restart the unit, in milliseconds, which
+/// includes the time it takes to enter a password for polkit.
+const JOB_TIMEOUT: i32 = 300_000;
+
+// dbus-rs does not expose this, but it links to libdbus
+extern "C" {
+    fn dbus_message_set_allow_interactive_authorization(message: *mut c_void, allow: u32);
+}
+
+fn manager_call(method: &str) -> Result<Message> {
+    Message::new_method_call(SYSTEMD, SYSTEMD_PATH, MANAGER_INTERFACE, method)
+        .block_error("systemd", "failed to create D-Bus method call")
+}
+
+/// The call that starts, stops or restarts the unit. It allows polkit to ask for a password,
+/// as systemd otherwise refuses right away for users that have to authenticate.
+fn job_call(method: &str, unit: &str) -> Result<Message> {
+    let m = manager_call(method)?.append2(unit, "replace");
+    unsafe {
+        dbus_message_set_allow_interactive_authorization(m.ptr() as *mut c_void, 1);
+    }
+    Ok(m)
+}
+
+/// The method that a click with `button` calls while the unit is in `active_state`.
+fn click_method(button: MouseButton, active_state: &str) -> Option<&'static str> {
+    match button {
+        MouseButton::Left => match active_state {
+            "active" | "activating" | "reloading" => Some("StopUnit"),
+            _ => Some("StartUnit"),
+        },
+        MouseButton::Right => Some("RestartUnit"),
+        _ => None,
+    }
+}
+
+fn bus_type(user: bool) -> BusType {
+    if user {
+        BusType::Session
+    } else {
+        BusType::System
+    }
+}
+
+pub struct Systemd {
+    id: String,
+    output: ButtonWidget,
+    format: FormatTemplate,
+    unit: String,
+    /// The object path of the unit
+    path: String,
+    active_state: String,
+    user: bool,
+    dbus_conn: Connection,
+    send: Sender<Task>,
+    /// The error of the last job, which the next update shows
+    job_error: Arc<Mutex<Option<String>>>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct SystemdConfig {
+    /// The unit to show, like `docker.service`
+    pub unit: String,
+
+    /// Whether the unit is a unit of the user instance of systemd
+    #[serde(default = "SystemdConfig::default_user")]
+    pub user: bool,
+
+    /// Format string
+    #[serde(default = "SystemdConfig::default_format")]
+    pub format: String,
+}
+
+impl SystemdConfig {
+    fn default_user() -> bool {
+        false
+    }
+
+    fn default_format() -> String {
+        "{unit} {state}".to_owned()
+    }
+}
+
+impl ConfigBlock for Systemd {
+    type Config = SystemdConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+        let user = block_config.user;
+        let dbus_conn = Connection::get_private(bus_type(user))
+            .block_error("systemd", "failed to establish D-Bus connection")?;
+
+        // Loads the unit if it is not, so that a stopped unit has an object as well
+        let path: dbus::Path = dbus_conn
+            .send_with_reply_and_block(
+                manager_call("LoadUnit")?.append1(block_config.unit.as_str()),
+                1000,
+            )
+            .block_error("systemd", &format!("unit {} not found", block_config.unit))?
+            .get1()
+            .block_error("systemd", "systemd did not return the unit")?;
+        let path = path.to_string();
+
+        let watched_path = path.clone();
+        let watcher_id = id.clone();
+        let watcher_send = send.clone();
+        thread::Builder::new()
+            .name("systemd".into())
+            .spawn(move || {
+                let c = Connection::get_private(bus_type(user))
+                    .expect("Failed to establish D-Bus connection in thread");
+
+                // systemd only sends signals while a client has subscribed
+                c.send_with_reply_and_block(manager_call("Subscribe").unwrap(), 1000)
+                    .expect("Failed to subscribe to systemd");
+                c.add_match(&format!(
+                    "type='signal',path='{}',interface='org.freedesktop.DBus.Properties',\
+                     member='PropertiesChanged',arg0='{}'",
+                    watched_path, UNIT_INTERFACE
+                ))
+                .expect("Failed to add D-Bus match rule.");
+
+                loop {
+                    for msg in c.incoming(10_000) {
+                        if msg.path().map_or(false, |p| *p == *watched_path)
+                            && watcher_send
+                                .send(Task {
+                                    id: watcher_id.clone(),
+                                    update_time: Instant::now(),
+                                })
+                                .is_err()
+                        {
+                            return;
+                        }
+                    }
+                }
+            })
+            .unwrap();
+
+        Ok(Systemd {
+            output: ButtonWidget::new(config, &id),
+            id,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("systemd", "Invalid format specified")?,
+            unit: block_config.unit,
+            path,
+            active_state: String::new(),
+            user,
+            dbus_conn,
+            send,
+            job_error: Arc::new(Mutex::new(None)),
+        })
+    }
+}
+
+impl Systemd {
+    fn property(&self, name: &str) -> Result<String> {
+        self.dbus_conn
+            .with_path(SYSTEMD, self.path.as_str(), 1000)
+            .get(UNIT_INTERFACE, name)
+            .block_error(
+                "systemd",
+                &format!("failed to get the {} of the unit", name),
+            )
+    }
+
+    /// Starts, stops or restarts the unit. systemd asks polkit about system units, which may
+    /// ask for a password, so the call waits for the job on a thread of its own.
+    fn call(&self, method: &'static str) -> Result<()> {
+        let user = self.user;
+        let unit = self.unit.clone();
+        let id = self.id.clone();
+        let send = self.send.clone();
+        let job_error = self.job_error.clone();
+        thread::Builder::new()
+            .name("systemd".into())
+            .spawn(move || {
+                let result = job_call(method, &unit).and_then(|m| {
+                    Connection::get_private(bus_type(user))
+                        .and_then(|c| c.send_with_reply_and_block(m, JOB_TIMEOUT))
+                        .block_error(
+                            "systemd",
+                            &format!("systemd refused to {} the unit", method),
+                        )
+                });
+                // A change of the unit updates the block, but an error has to ask for it
+                if let Err(error) = result {
+                    if let Ok(mut job_error) = job_error.lock() {
+                        *job_error = Some(error.to_string());
+                    }
+                    let _ = send.send(Task {
+                        id,
+                        update_time: Instant::now(),
+                    });
+                }
+            })
+            .block_error("systemd", "failed to start a thread for the job")?;
+        Ok(())
+    }
+}
+
+impl Block for Systemd {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let job_error = self
+            .job_error
+            .lock()
+            .block_error("systemd", "failed to acquire lock")?
+            .take();
+        if let Some(error) = job_error {
+            return Err(BlockError("systemd".to_string(), error));
+        }
+
+        self.active_state = self.property("ActiveState")?;
+        let sub_state = self.property("SubState")?;
+
+        let values = map!(
+            "{unit}" => self.unit.clone(),
+            "{state}" => self.active_state.clone(),
+            "{sub_state}" => sub_state
+        );
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
+        self.output.set_state(match self.active_state.as_str() {
+            "active" => State::Good,
+            "failed" => State::Critical,
+            "inactive" => State::Idle,
+            // activating, deactivating and reloading
+            _ => State::Info,
+        });
+
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if !event.matches_name(&self.id) {
+            return Ok(());
+        }
+        match click_method(event.button, &self.active_state) {
+            Some(method) => self.call(method),
+            None => Ok(()),
+        }
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        click_method(event.button, &self.active_state).is_some()
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    extern "C" {
+        fn dbus_message_get_allow_interactive_authorization(message: *mut c_void) -> u32;
+    }
+
+    #[test]
+    fn test_click_method() {
+        assert_eq!(click_method(MouseButton::Left, "active"), Some("StopUnit"));
+        assert_eq!(
+            click_method(MouseButton::Left, "activating"),
+            Some("StopUnit")
+        );
+        assert_eq!(
+            click_method(MouseButton::Left, "inactive"),
+            Some("StartUnit")
+        );
+        assert_eq!(click_method(MouseButton::Left, "failed"), Some("StartUnit"));
+        assert_eq!(
+            click_method(MouseButton::Right, "active"),
+            Some("RestartUnit")
+        );
+        assert_eq!(click_method(MouseButton::Middle, "active"), None);
+    }
+
+    #[test]
+    fn test_job_call_allows_interactive_authorization() {
+        let m = job_call("StartUnit", "docker.service").unwrap();
+        assert_eq!(m.member().unwrap().to_string(), "StartUnit");
+        assert_eq!(
+            m.get2::<&str, &str>(),
+            (Some("docker.service"), Some("replace"))
+        );
+        let allowed =
+            unsafe { dbus_message_get_allow_interactive_authorization(m.ptr() as *mut c_void) };
+        assert_eq!(allowed, 1);
+    }
+}
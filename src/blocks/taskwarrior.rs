@@ -3,6 +3,7 @@ use std::time::Duration;
 
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
+use serde_json::Value;
 
 use crate::blocks::Update;
 use crate::blocks::{Block, ConfigBlock};
@@ -21,8 +22,11 @@ pub struct Taskwarrior {
     update_interval: Duration,
     warning_threshold: u32,
     critical_threshold: u32,
-    filter_tags: Vec<String>,
-    block_mode: TaskwarriorBlockMode,
+    filters: Vec<TaskwarriorFilter>,
+    /// The index of the filter that is shown
+    current_filter: usize,
+    /// The most urgent task of the current filter, which a middle click marks as done
+    top_task: Option<String>,
     format: FormatTemplate,
     format_singular: FormatTemplate,
     format_everything_done: FormatTemplate,
@@ -56,6 +60,10 @@ pub struct TaskwarriorConfig {
     #[serde(default = "TaskwarriorConfig::default_filter_tags")]
     pub filter_tags: Vec<String>,
 
+    /// Named filters of taskwarrior, which a right click cycles through
+    #[serde(default)]
+    pub filters: Vec<TaskwarriorFilter>,
+
     /// Format override
     #[serde(default = "TaskwarriorConfig::default_format")]
     pub format: String,
@@ -69,11 +77,12 @@ pub struct TaskwarriorConfig {
     pub format_everything_done: String,
 }
 
-enum TaskwarriorBlockMode {
-    // Show only the tasks which are filtered by the set tags and which are not completed.
-    OnlyFilteredPendingTasks,
-    // Show all pending tasks and ignore the filtering tags.
-    AllPendingTasks,
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TaskwarriorFilter {
+    pub name: String,
+    /// A filter of taskwarrior, like `due:today` or `+next`
+    pub filter: String,
 }
 
 impl TaskwarriorConfig {
@@ -106,13 +115,29 @@ impl ConfigBlock for Taskwarrior {
         config: Config,
         tx_update_request: Sender<Task>,
     ) -> Result<Self> {
+        // Without named filters, the tags are toggled with all pending tasks
+        let filters = if block_config.filters.is_empty() {
+            vec![
+                TaskwarriorFilter {
+                    name: "filtered".to_owned(),
+                    filter: tags_to_filter(&block_config.filter_tags),
+                },
+                TaskwarriorFilter {
+                    name: "all".to_owned(),
+                    filter: String::new(),
+                },
+            ]
+        } else {
+            block_config.filters
+        };
         Ok(Taskwarrior {
             id: pseudo_uuid(),
             update_interval: block_config.interval,
             warning_threshold: block_config.warning_threshold,
             critical_threshold: block_config.critical_threshold,
-            filter_tags: block_config.filter_tags,
-            block_mode: TaskwarriorBlockMode::OnlyFilteredPendingTasks,
+            filters,
+            current_filter: 0,
+            top_task: None,
             output: ButtonWidget::new(config.clone(), "taskwarrior")
                 .with_icon("tasks")
                 .with_text("-"),
@@ -161,30 +186,72 @@ fn tags_to_filter(tags: &[String]) -> String {
         .join(" ")
 }
 
-fn get_number_of_pending_tasks(tags: &[String]) -> Result<u32> {
-    String::from_utf8(
-        Command::new("sh")
-            .args(&[
-                "-c",
-                &format!(
-                    "task rc.gc=off -COMPLETED -DELETED {} count",
-                    tags_to_filter(tags)
-                ),
-            ])
-            .output()
-            .block_error(
-                "taskwarrior",
-                "failed to run taskwarrior for getting the number of pending tasks",
-            )?
-            .stdout,
-    )
-    .block_error(
-        "taskwarrior",
-        "failed to get the number of pending tasks from taskwarrior",
-    )?
-    .trim()
-    .parse::<u32>()
-    .block_error("taskwarrior", "could not parse the result of taskwarrior")
+/// A pending task, as exported by taskwarrior.
+#[derive(Debug, Clone, PartialEq)]
+struct PendingTask {
+    uuid: String,
+    description: String,
+    urgency: f64,
+}
+
+/// Reads the tasks of `task export`, and returns their number and the most urgent of them.
+fn parse_export(export: &str) -> Result<(u32, Option<PendingTask>)> {
+    let tasks: Vec<Value> =
+        serde_json::from_str(export).block_error("taskwarrior", "Malformed JSON.")?;
+    let top = tasks
+        .iter()
+        .filter_map(|task| {
+            Some(PendingTask {
+                uuid: task["uuid"].as_str()?.to_string(),
+                description: task["description"].as_str().unwrap_or("").to_string(),
+                urgency: task["urgency"].as_f64().unwrap_or(0.0),
+            })
+        })
+        .fold(None, |top: Option<PendingTask>, task| match top {
+            Some(ref top) if top.urgency >= task.urgency => Some(top.clone()),
+            _ => Some(task),
+        });
+    Ok((tasks.len() as u32, top))
+}
+
+fn get_pending_tasks(filter: &str) -> Result<(u32, Option<PendingTask>)> {
+    // The parentheses keep an `or` in the filter from also matching completed tasks
+    let filter = if filter.trim().is_empty() {
+        String::new()
+    } else {
+        format!("'(' {} ')'", filter)
+    };
+    let output = Command::new("sh")
+        .args(&[
+            "-c",
+            &format!(
+                "task rc.gc=off rc.verbose=nothing rc.json.array=on -COMPLETED -DELETED {} export",
+                filter
+            ),
+        ])
+        .output()
+        .block_error(
+            "taskwarrior",
+            "failed to run taskwarrior for getting the pending tasks",
+        )?;
+    parse_export(&String::from_utf8_lossy(&output.stdout))
+}
+
+fn mark_done(uuid: &str) -> Result<()> {
+    let output = Command::new("task")
+        .args(&["rc.confirmation=off", "rc.verbose=nothing", uuid, "done"])
+        .output()
+        .block_error(
+            "taskwarrior",
+            "failed to run taskwarrior for marking the task done",
+        )?;
+    if !output.status.success() {
+        return Err(BlockError(
+            "taskwarrior".to_string(),
+            "taskwarrior failed to mark the task done".to_string(),
+        ));
+    }
+    Ok(())
 }
 
 impl Block for Taskwarrior {
@@ -192,12 +259,17 @@ impl Block for Taskwarrior {
         if !has_taskwarrior()? {
             self.output.set_text("?")
         } else {
-            let filter_tags = match self.block_mode {
-                TaskwarriorBlockMode::OnlyFilteredPendingTasks => self.filter_tags.clone(),
-                TaskwarriorBlockMode::AllPendingTasks => vec![],
-            };
-            let number_of_pending_tasks = get_number_of_pending_tasks(&filter_tags)?;
-            let values = map!("{count}" => number_of_pending_tasks);
+            let filter = &self.filters[self.current_filter];
+            let (number_of_pending_tasks, top_task) = get_pending_tasks(&filter.filter)?;
+            let values = map!(
+                "{count}" => number_of_pending_tasks.to_string(),
+                "{filter_name}" => filter.name.clone(),
+                "{description}" => top_task
+                    .as_ref()
+                    .map(|task| task.description.clone())
+                    .unwrap_or_default()
+            );
+            self.top_task = top_task.map(|task| task.uuid);
             self.output.set_text(match number_of_pending_tasks {
                 0 => self.format_everything_done.render_static_str(&values)?,
                 1 => self.format_singular.render_static_str(&values)?,
@@ -232,16 +304,15 @@ impl Block for Taskwarrior {
                     self.update()?;
                 }
                 MouseButton::Right => {
-                    match self.block_mode {
-                        TaskwarriorBlockMode::OnlyFilteredPendingTasks => {
-                            self.block_mode = TaskwarriorBlockMode::AllPendingTasks
-                        }
-                        TaskwarriorBlockMode::AllPendingTasks => {
-                            self.block_mode = TaskwarriorBlockMode::OnlyFilteredPendingTasks
-                        }
-                    }
+                    self.current_filter = (self.current_filter + 1) % self.filters.len();
                     self.update()?;
                 }
+                MouseButton::Middle => {
+                    if let Some(uuid) = self.top_task.take() {
+                        mark_done(&uuid)?;
+                        self.update()?;
+                    }
+                }
                 _ => {}
             }
         }
@@ -253,3 +324,25 @@ impl Block for Taskwarrior {
         &self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::parse_export;
+
+    #[test]
+    fn test_parse_export() {
+        let export = r#"[
+            {"uuid": "a1", "description": "Water the plants", "urgency": 1.8},
+            {"uuid": "b2", "description": "File the taxes", "urgency": 12.3},
+            {"uuid": "c3", "description": "Call the bank", "urgency": 4}
+        ]"#;
+        let (count, top) = parse_export(export).unwrap();
+        assert_eq!(count, 3);
+        let top = top.unwrap();
+        assert_eq!(top.uuid, "b2");
+        assert_eq!(top.description, "File the taxes");
+
+        assert_eq!(parse_export("[]").unwrap(), (0, None));
+        assert!(parse_export("No matches.").is_err());
+    }
+}
@@ -0,0 +1,343 @@
+//! A block for the messages that a Telegram bot received, like the chats that a bot forwards
+//! notifications to.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, Weak};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{http_get, pseudo_uuid, read_token, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+/// How long the Bot API holds a request for updates open while nothing happens, in seconds.
+const POLL_TIMEOUT: u64 = 30;
+/// How long to wait before asking again after an error.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+/// Only messages are counted, as `["message","channel_post"]`.
+const ALLOWED_UPDATES: &str = "%5B%22message%22%2C%22channel_post%22%5D";
+
+/// The messages received since the last reset, and the error of the last request.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct Unread {
+    /// The number of messages per chat
+    chats: HashMap<i64, u64>,
+    /// The number of messages which mention the user
+    mentions: u64,
+    error: Option<String>,
+}
+
+impl Unread {
+    fn messages(&self) -> u64 {
+        self.chats.values().sum()
+    }
+}
+
+/// Counts the messages in an answer of `getUpdates`, and returns the offset of the next
+/// request, which confirms these updates to Telegram.
+fn apply_updates(
+    json: &Value,
+    chats: &[i64],
+    mention: Option<&str>,
+    unread: &mut Unread,
+) -> Result<Option<i64>> {
+    if json["ok"].as_bool() != Some(true) {
+        return Err(BlockError(
+            "telegram".to_string(),
+            format!(
+                "API Error: {}",
+                json["description"].as_str().unwrap_or("unknown error")
+            ),
+        ));
+    }
+    let updates = json["result"]
+        .as_array()
+        .block_error("telegram", "Malformed JSON.")?;
+    let mut offset = None;
+    for update in updates {
+        if let Some(id) = update["update_id"].as_i64() {
+            offset = Some(offset.unwrap_or(id).max(id + 1));
+        }
+        let message = if update["message"].is_object() {
+            &update["message"]
+        } else {
+            &update["channel_post"]
+        };
+        let chat = match message["chat"]["id"].as_i64() {
+            Some(chat) => chat,
+            None => continue,
+        };
+        if !chats.is_empty() && !chats.contains(&chat) {
+            continue;
+        }
+        *unread.chats.entry(chat).or_insert(0) += 1;
+
+        let text = message["text"]
+            .as_str()
+            .or_else(|| message["caption"].as_str())
+            .unwrap_or("")
+            .to_lowercase();
+        if let Some(mention) = mention {
+            if text.contains(&format!(
+                "@{}",
+                mention.trim_start_matches('@').to_lowercase()
+            )) {
+                unread.mentions += 1;
+            }
+        }
+    }
+    Ok(offset)
+}
+
+/// Asks the Bot API for updates. Errors of the API come with a JSON body as well.
+fn fetch(url: &str) -> Result<Value> {
+    http_get("telegram", url, &[], Duration::from_secs(POLL_TIMEOUT + 30))?.json("telegram")
+}
+
+/// Polls for updates until the block is gone, which drops the counts.
+fn poll(
+    token: &str,
+    chats: &[i64],
+    mention: Option<&str>,
+    unread: Weak<Mutex<Unread>>,
+    notify: &dyn Fn() -> bool,
+) {
+    let mut offset: Option<i64> = None;
+    loop {
+        let mut url = format!(
+            "https://api.telegram.org/bot{}/getUpdates?timeout={}&allowed_updates={}",
+            token, POLL_TIMEOUT, ALLOWED_UPDATES
+        );
+        if let Some(offset) = offset {
+            url.push_str(&format!("&offset={}", offset));
+        }
+        let result = fetch(&url);
+
+        let shared = match unread.upgrade() {
+            Some(shared) => shared,
+            None => return,
+        };
+        let mut counts = shared
+            .lock()
+            .expect("lock has been poisoned in `telegram` block");
+        let before = counts.clone();
+        let result = result.and_then(|json| apply_updates(&json, chats, mention, &mut counts));
+        let failed = match result {
+            Ok(next) => {
+                offset = next.or(offset);
+                counts.error = None;
+                false
+            }
+            Err(error) => {
+                counts.error = Some(error.to_string());
+                true
+            }
+        };
+        let changed = *counts != before;
+        drop(counts);
+        drop(shared);
+
+        if changed && !notify() {
+            return;
+        }
+        if failed {
+            thread::sleep(RETRY_DELAY);
+        }
+    }
+}
+
+pub struct Telegram {
+    id: String,
+    output: ButtonWidget,
+    format: FormatTemplate,
+    hide_when_zero: bool,
+    unread: Arc<Mutex<Unread>>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TelegramConfig {
+    /// A file with the token of the bot
+    pub bot_token_file: String,
+
+    /// Only count the messages of these chats
+    #[serde(default)]
+    pub chats: Vec<i64>,
+
+    /// The username to count mentions of, like `@me`
+    #[serde(default)]
+    pub mention: Option<String>,
+
+    /// Format string
+    #[serde(default = "TelegramConfig::default_format")]
+    pub format: String,
+
+    /// Whether to hide the block while there are no messages
+    #[serde(default = "TelegramConfig::default_hide_when_zero")]
+    pub hide_when_zero: bool,
+}
+
+impl TelegramConfig {
+    fn default_format() -> String {
+        "{messages}".to_owned()
+    }
+
+    fn default_hide_when_zero() -> bool {
+        true
+    }
+}
+
+impl ConfigBlock for Telegram {
+    type Config = TelegramConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let token = read_token("telegram", &block_config.bot_token_file)?;
+
+        let id = pseudo_uuid();
+        let unread = Arc::new(Mutex::new(Unread::default()));
+        // The thread only holds on to the counts while it updates them, so that it stops
+        // asking for updates once a reload of the configuration dropped the block
+        let thread_unread = Arc::downgrade(&unread);
+        let thread_id = id.clone();
+        let chats = block_config.chats;
+        let mention = block_config.mention;
+        thread::Builder::new()
+            .name("telegram".into())
+            .spawn(move || {
+                let notify = || {
+                    send.send(Task {
+                        id: thread_id.clone(),
+                        update_time: Instant::now(),
+                    })
+                    .is_ok()
+                };
+                poll(&token, &chats, mention.as_deref(), thread_unread, &notify);
+            })
+            .block_error("telegram", "failed to start the polling thread")?;
+
+        Ok(Telegram {
+            output: ButtonWidget::new(config, &id).with_icon("chat"),
+            id,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("telegram", "Invalid format specified")?,
+            hide_when_zero: block_config.hide_when_zero,
+            unread,
+        })
+    }
+}
+
+impl Block for Telegram {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let unread = self
+            .unread
+            .lock()
+            .block_error("telegram", "failed to acquire lock")?
+            .clone();
+        let values = if unread.error.is_some() {
+            // Errors are shown until the thread asks again, instead of stopping the bar
+            map!(
+                "{messages}" => "?".to_string(),
+                "{chats}" => "?".to_string(),
+                "{mentions}" => "?".to_string()
+            )
+        } else {
+            map!(
+                "{messages}" => unread.messages().to_string(),
+                "{chats}" => unread.chats.len().to_string(),
+                "{mentions}" => unread.mentions.to_string()
+            )
+        };
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
+        self.output.set_state(if unread.error.is_some() {
+            State::Critical
+        } else if unread.mentions > 0 {
+            State::Warning
+        } else if unread.messages() > 0 {
+            State::Info
+        } else {
+            State::Idle
+        });
+
+        // The thread updates the block
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        let unread = self
+            .unread
+            .lock()
+            .expect("lock has been poisoned in `telegram` block");
+        if self.hide_when_zero && unread.error.is_none() && unread.messages() == 0 {
+            vec![]
+        } else {
+            vec![&self.output]
+        }
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if !event.matches_name(&self.id) || event.button != MouseButton::Left {
+            return Ok(());
+        }
+        // The Bot API does not know what was read in a client, so a click marks all as read,
+        // also when the common `on_click` opens a client
+        {
+            let mut unread = self
+                .unread
+                .lock()
+                .block_error("telegram", "failed to acquire lock")?;
+            unread.chats.clear();
+            unread.mentions = 0;
+        }
+        self.update()?;
+        Ok(())
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{apply_updates, Unread};
+
+    #[test]
+    fn test_apply_updates() {
+        let json = serde_json::json!({"ok": true, "result": [
+            {"update_id": 7, "message": {"chat": {"id": 42}, "text": "backup done"}},
+            {"update_id": 8, "message": {"chat": {"id": -100}, "text": "ping @Me"}},
+            {"update_id": 9, "channel_post": {"chat": {"id": 42}, "caption": "graph for @me"}},
+            {"update_id": 10, "edited_message": {"chat": {"id": 42}, "text": "edited"}}
+        ]});
+        let mut unread = Unread::default();
+        assert_eq!(
+            apply_updates(&json, &[], Some("@me"), &mut unread).unwrap(),
+            Some(11)
+        );
+        assert_eq!(unread.messages(), 3);
+        assert_eq!(unread.chats[&42], 2);
+        assert_eq!(unread.mentions, 2);
+
+        let mut unread = Unread::default();
+        apply_updates(&json, &[42], None, &mut unread).unwrap();
+        assert_eq!(unread.messages(), 2);
+        assert_eq!(unread.mentions, 0);
+
+        let empty = serde_json::json!({"ok": true, "result": []});
+        assert_eq!(apply_updates(&empty, &[], None, &mut unread).unwrap(), None);
+
+        let error =
+            serde_json::json!({"ok": false, "error_code": 401, "description": "Unauthorized"});
+        assert!(apply_updates(&error, &[], None, &mut unread).is_err());
+    }
+}
@@ -248,6 +248,10 @@ impl Block for Temperature {
             };
 
             self.text.set_state(state);
+            self.text.set_value(Some(
+                (max - self.maximum_good) as f64
+                    / (self.maximum_warning - self.maximum_good).max(1) as f64,
+            ));
         }
 
         Ok(Some(self.update_interval.into()))
@@ -274,6 +278,10 @@ impl Block for Temperature {
         Ok(())
     }
 
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left
+    }
+
     fn id(&self) -> &str {
         &self.id
     }
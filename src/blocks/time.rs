@@ -3,7 +3,7 @@ use std::time::Duration;
 
 use chrono::{
     offset::{Local, Utc},
-    Locale,
+    Datelike, Locale, NaiveDate,
 };
 use chrono_tz::Tz;
 use crossbeam_channel::Sender;
@@ -15,7 +15,6 @@ use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::subprocess::spawn_child_async;
 use crate::util::pseudo_uuid;
 use crate::widget::I3BarWidget;
 use crate::widgets::button::ButtonWidget;
@@ -24,10 +23,25 @@ pub struct Time {
     time: ButtonWidget,
     id: String,
     update_interval: Duration,
+    align: bool,
     format: String,
-    on_click: Option<String>,
+    format_long: Option<String>,
+    show_long: bool,
     timezone: Option<Tz>,
     locale: Option<String>,
+    calendar: Option<Calendar>,
+}
+
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Calendar {
+    /// Tabular Islamic calendar
+    Hijri,
+    Hebrew,
+    /// Solar Hijri calendar
+    Persian,
+    /// Chinese lunisolar calendar
+    Lunar,
 }
 
 #[derive(Deserialize, Debug, Default, Clone)]
@@ -37,6 +51,14 @@ pub struct TimeConfig {
     #[serde(default = "TimeConfig::default_format")]
     pub format: String,
 
+    /// Short format string, replaces `format` when set.
+    #[serde(default = "TimeConfig::default_format_short")]
+    pub format_short: Option<String>,
+
+    /// Long format string. When set, left-clicking the block toggles between the short and long formats.
+    #[serde(default = "TimeConfig::default_format_long")]
+    pub format_long: Option<String>,
+
     /// Update interval in seconds
     #[serde(
         default = "TimeConfig::default_interval",
@@ -44,14 +66,19 @@ pub struct TimeConfig {
     )]
     pub interval: Duration,
 
-    #[serde(default = "TimeConfig::default_on_click")]
-    pub on_click: Option<String>,
+    /// Schedule updates on wall-clock multiples of `interval` instead of relative to the last update
+    #[serde(default = "TimeConfig::default_align")]
+    pub align: bool,
 
     #[serde(default = "TimeConfig::default_timezone")]
     pub timezone: Option<Tz>,
 
     #[serde(default = "TimeConfig::default_locale")]
     pub locale: Option<String>,
+
+    /// Calendar used for the `{alt_date}`, `{alt_day}`, `{alt_month}` and `{alt_year}` placeholders
+    #[serde(default = "TimeConfig::default_calendar")]
+    pub calendar: Option<Calendar>,
 }
 
 impl TimeConfig {
@@ -59,12 +86,20 @@ impl TimeConfig {
         "%a %d/%m %R".to_owned()
     }
 
+    fn default_format_short() -> Option<String> {
+        None
+    }
+
+    fn default_format_long() -> Option<String> {
+        None
+    }
+
     fn default_interval() -> Duration {
         Duration::from_secs(5)
     }
 
-    fn default_on_click() -> Option<String> {
-        None
+    fn default_align() -> bool {
+        false
     }
 
     fn default_timezone() -> Option<Tz> {
@@ -74,6 +109,10 @@ impl TimeConfig {
     fn default_locale() -> Option<String> {
         None
     }
+
+    fn default_calendar() -> Option<Calendar> {
+        None
+    }
 }
 
 impl ConfigBlock for Time {
@@ -87,20 +126,27 @@ impl ConfigBlock for Time {
         let i = pseudo_uuid();
         Ok(Time {
             id: i.clone(),
-            format: block_config.format,
+            format: block_config.format_short.unwrap_or(block_config.format),
+            format_long: block_config.format_long,
+            show_long: false,
             time: ButtonWidget::new(config, i.as_str())
                 .with_text("")
                 .with_icon("time"),
             update_interval: block_config.interval,
-            on_click: block_config.on_click,
+            align: block_config.align,
             timezone: block_config.timezone,
             locale: block_config.locale,
+            calendar: block_config.calendar,
         })
     }
 }
 
 impl Block for Time {
     fn update(&mut self) -> Result<Option<Update>> {
+        let format = match self.format_long {
+            Some(ref format_long) if self.show_long => format_long,
+            _ => &self.format,
+        };
         let time = match &self.locale {
             Some(l) => {
                 let locale: Locale = l
@@ -110,33 +156,51 @@ impl Block for Time {
                 match self.timezone {
                     Some(tz) => Utc::now()
                         .with_timezone(&tz)
-                        .format_localized(&self.format, locale),
-                    None => Local::now().format_localized(&self.format, locale),
+                        .format_localized(format, locale),
+                    None => Local::now().format_localized(format, locale),
                 }
             }
             None => match self.timezone {
-                Some(tz) => Utc::now().with_timezone(&tz).format(&self.format),
-                None => Local::now().format(&self.format),
+                Some(tz) => Utc::now().with_timezone(&tz).format(format),
+                None => Local::now().format(format),
             },
         };
-        self.time.set_text(format!("{}", time));
-        Ok(Some(self.update_interval.into()))
+        let mut text = format!("{}", time);
+        if let Some(calendar) = self.calendar {
+            let today = match self.timezone {
+                Some(tz) => Utc::now().with_timezone(&tz).date().naive_local(),
+                None => Local::now().date().naive_local(),
+            };
+            let alt = AltDate::new(calendar, today);
+            text = text
+                .replace(
+                    "{alt_date}",
+                    &format!("{} {} {}", alt.day, alt.month, alt.year),
+                )
+                .replace("{alt_day}", &alt.day.to_string())
+                .replace("{alt_month}", &alt.month)
+                .replace("{alt_year}", &alt.year);
+        }
+        self.time.set_text(text);
+        if self.align {
+            Ok(Some(Update::Aligned(self.update_interval)))
+        } else {
+            Ok(Some(self.update_interval.into()))
+        }
     }
 
     fn click(&mut self, e: &I3BarEvent) -> Result<()> {
-        if let Some(ref name) = e.name {
-            if name.as_str() == self.id {
-                if let MouseButton::Left = e.button {
-                    if let Some(ref cmd) = self.on_click {
-                        spawn_child_async("sh", &["-c", cmd])
-                            .block_error("time", "could not spawn child")?;
-                    }
-                }
-            }
+        if e.matches_name(&self.id) && e.button == MouseButton::Left && self.format_long.is_some() {
+            self.show_long = !self.show_long;
+            self.update()?;
         }
         Ok(())
     }
 
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left && self.format_long.is_some()
+    }
+
     fn view(&self) -> Vec<&dyn I3BarWidget> {
         vec![&self.time]
     }
@@ -145,3 +209,468 @@ impl Block for Time {
         &self.id
     }
 }
+
+/// A date converted to one of the supported non-Gregorian calendars, ready for display.
+struct AltDate {
+    day: i64,
+    month: String,
+    year: String,
+}
+
+impl AltDate {
+    fn new(calendar: Calendar, date: NaiveDate) -> Self {
+        // Days since 0001-01-01 (Gregorian), which is day 1. This is the "fixed date"
+        // (R.D.) used by the algorithms in Reingold & Dershowitz' Calendrical Calculations.
+        let fixed = date.num_days_from_ce() as i64;
+        match calendar {
+            Calendar::Hijri => {
+                let (y, m, d) = hijri_from_fixed(fixed);
+                AltDate {
+                    day: d,
+                    month: HIJRI_MONTHS[m as usize - 1].to_string(),
+                    year: y.to_string(),
+                }
+            }
+            Calendar::Hebrew => {
+                let (y, m, d) = hebrew_from_fixed(fixed);
+                let month = match m {
+                    12 if hebrew_leap_year(y) => "Adar I",
+                    13 => "Adar II",
+                    _ => HEBREW_MONTHS[m as usize - 1],
+                };
+                AltDate {
+                    day: d,
+                    month: month.to_string(),
+                    year: y.to_string(),
+                }
+            }
+            Calendar::Persian => {
+                let (y, m, d) = persian_from_gregorian(date);
+                AltDate {
+                    day: d,
+                    month: PERSIAN_MONTHS[m as usize - 1].to_string(),
+                    year: y.to_string(),
+                }
+            }
+            Calendar::Lunar => {
+                let (y, m, leap, d) = chinese_from_fixed(fixed);
+                AltDate {
+                    day: d,
+                    month: if leap {
+                        format!("Leap Month {}", m)
+                    } else {
+                        format!("Month {}", m)
+                    },
+                    year: format!(
+                        "{}-{}",
+                        CHINESE_STEMS[((y - 1) % 10) as usize],
+                        CHINESE_BRANCHES[((y - 1) % 12) as usize]
+                    ),
+                }
+            }
+        }
+    }
+}
+
+const HIJRI_MONTHS: [&str; 12] = [
+    "Muharram",
+    "Safar",
+    "Rabi' al-awwal",
+    "Rabi' al-thani",
+    "Jumada al-awwal",
+    "Jumada al-thani",
+    "Rajab",
+    "Sha'ban",
+    "Ramadan",
+    "Shawwal",
+    "Dhu al-Qi'dah",
+    "Dhu al-Hijjah",
+];
+
+/// Months counted from Nisan, as in Calendrical Calculations; the civil year starts with Tishrei.
+const HEBREW_MONTHS: [&str; 12] = [
+    "Nisan", "Iyar", "Sivan", "Tammuz", "Av", "Elul", "Tishrei", "Heshvan", "Kislev", "Tevet",
+    "Shevat", "Adar",
+];
+
+const PERSIAN_MONTHS: [&str; 12] = [
+    "Farvardin",
+    "Ordibehesht",
+    "Khordad",
+    "Tir",
+    "Mordad",
+    "Shahrivar",
+    "Mehr",
+    "Aban",
+    "Azar",
+    "Dey",
+    "Bahman",
+    "Esfand",
+];
+
+const CHINESE_STEMS: [&str; 10] = [
+    "Jia", "Yi", "Bing", "Ding", "Wu", "Ji", "Geng", "Xin", "Ren", "Gui",
+];
+
+const CHINESE_BRANCHES: [&str; 12] = [
+    "Zi", "Chou", "Yin", "Mao", "Chen", "Si", "Wu", "Wei", "Shen", "You", "Xu", "Hai",
+];
+
+const HIJRI_EPOCH: i64 = 227_015;
+
+fn fixed_from_hijri(year: i64, month: i64, day: i64) -> i64 {
+    day + 29 * (month - 1)
+        + (6 * month - 1).div_euclid(11)
+        + (year - 1) * 354
+        + (3 + 11 * year).div_euclid(30)
+        + HIJRI_EPOCH
+        - 1
+}
+
+fn hijri_from_fixed(fixed: i64) -> (i64, i64, i64) {
+    let year = (30 * (fixed - HIJRI_EPOCH) + 10646).div_euclid(10631);
+    let prior_days = fixed - fixed_from_hijri(year, 1, 1);
+    let month = (11 * prior_days + 330).div_euclid(325);
+    let day = fixed - fixed_from_hijri(year, month, 1) + 1;
+    (year, month, day)
+}
+
+const HEBREW_EPOCH: i64 = -1_373_427;
+
+fn hebrew_leap_year(year: i64) -> bool {
+    (7 * year + 1).rem_euclid(19) < 7
+}
+
+fn hebrew_elapsed_days(year: i64) -> i64 {
+    let months_elapsed = (235 * year - 234).div_euclid(19);
+    let parts_elapsed = 12084 + 13753 * months_elapsed;
+    let days = 29 * months_elapsed + parts_elapsed.div_euclid(25920);
+    if (3 * (days + 1)).rem_euclid(7) < 3 {
+        days + 1
+    } else {
+        days
+    }
+}
+
+fn hebrew_new_year(year: i64) -> i64 {
+    let ny0 = hebrew_elapsed_days(year - 1);
+    let ny1 = hebrew_elapsed_days(year);
+    let ny2 = hebrew_elapsed_days(year + 1);
+    let correction = if ny2 - ny1 == 356 {
+        2
+    } else if ny1 - ny0 == 382 {
+        1
+    } else {
+        0
+    };
+    HEBREW_EPOCH + ny1 + correction
+}
+
+fn hebrew_month_length(year: i64, month: i64) -> i64 {
+    let year_length = hebrew_new_year(year + 1) - hebrew_new_year(year);
+    let short = match month {
+        2 | 4 | 6 | 10 | 13 => true,
+        12 => !hebrew_leap_year(year),
+        8 => year_length % 10 != 5,
+        9 => year_length % 10 == 3,
+        _ => false,
+    };
+    if short {
+        29
+    } else {
+        30
+    }
+}
+
+fn fixed_from_hebrew(year: i64, month: i64, day: i64) -> i64 {
+    let last_month = if hebrew_leap_year(year) { 13 } else { 12 };
+    let months: i64 = if month < 7 {
+        (7..=last_month)
+            .chain(1..month)
+            .map(|m| hebrew_month_length(year, m))
+            .sum()
+    } else {
+        (7..month).map(|m| hebrew_month_length(year, m)).sum()
+    };
+    hebrew_new_year(year) + months + day - 1
+}
+
+fn hebrew_from_fixed(fixed: i64) -> (i64, i64, i64) {
+    let approx = ((fixed - HEBREW_EPOCH) as f64 / (35_975_351.0 / 98_496.0)).floor() as i64 + 1;
+    let year = if hebrew_new_year(approx) <= fixed {
+        approx
+    } else {
+        approx - 1
+    };
+    let start = if fixed < fixed_from_hebrew(year, 1, 1) {
+        7
+    } else {
+        1
+    };
+    let last_month = if hebrew_leap_year(year) { 13 } else { 12 };
+    let month = (start..=last_month)
+        .chain(1..start)
+        .find(|&m| fixed <= fixed_from_hebrew(year, m, hebrew_month_length(year, m)))
+        .unwrap_or(start);
+    let day = fixed - fixed_from_hebrew(year, month, 1) + 1;
+    (year, month, day)
+}
+
+/// Solar Hijri conversion using the 33-year cycle algorithm by Kazimierz Borkowski,
+/// which matches the astronomical calendar for years 1178 to 1633 AP.
+fn persian_from_gregorian(date: NaiveDate) -> (i64, i64, i64) {
+    const BREAKS: [i64; 20] = [
+        -61, 9, 38, 199, 426, 686, 756, 818, 1111, 1181, 1210, 1635, 2060, 2097, 2192, 2262, 2324,
+        2394, 2456, 3178,
+    ];
+    let gy = date.year() as i64;
+    let mut jy = gy - 621;
+
+    let mut leap_j = -14;
+    let mut jp = BREAKS[0];
+    let mut jump = 0;
+    for &jm in BREAKS.iter().skip(1) {
+        jump = jm - jp;
+        if jy < jm {
+            break;
+        }
+        leap_j += jump / 33 * 8 + jump % 33 / 4;
+        jp = jm;
+    }
+    let mut n = jy - jp;
+    leap_j += n / 33 * 8 + (n % 33 + 3) / 4;
+    if jump % 33 == 4 && jump - n == 4 {
+        leap_j += 1;
+    }
+    let leap_g = gy / 4 - (gy / 100 + 1) * 3 / 4 - 150;
+    let march = 20 + leap_j - leap_g;
+    if jump - n < 6 {
+        n = n - jump + (jump + 4) / 33 * 33;
+    }
+    let mut leap = ((n + 1) % 33 - 1) % 4;
+    if leap == -1 {
+        leap = 4;
+    }
+
+    let farvardin_first = NaiveDate::from_ymd(gy as i32, 3, march as u32);
+    let mut k = date.signed_duration_since(farvardin_first).num_days();
+    if k >= 0 {
+        if k <= 185 {
+            return (jy, 1 + k / 31, k % 31 + 1);
+        }
+        k -= 186;
+    } else {
+        jy -= 1;
+        k += 179;
+        if leap == 1 {
+            k += 1;
+        }
+    }
+    (jy, 7 + k / 30, k % 30 + 1)
+}
+
+const MEAN_SYNODIC_MONTH: f64 = 29.530_588_861;
+const MEAN_TROPICAL_YEAR: f64 = 365.242_189;
+/// Fixed date of the start of the Chinese sexagenary year count (2637 BCE).
+const CHINESE_EPOCH: i64 = -963_099;
+/// Offset of Beijing standard time from UT, in days.
+const CHINA_OFFSET: f64 = 8.0 / 24.0;
+
+/// Converts a moment (in fractional fixed days, UT) to a Julian Ephemeris Day.
+fn jde_from_moment(moment: f64) -> f64 {
+    // Rough ΔT for the current era, about 69 seconds.
+    moment + 1_721_424.5 + 69.0 / 86_400.0
+}
+
+fn moment_from_jde(jde: f64) -> f64 {
+    jde - 1_721_424.5 - 69.0 / 86_400.0
+}
+
+/// Apparent geocentric longitude of the sun in degrees (Meeus, chapter 25).
+fn solar_longitude(moment: f64) -> f64 {
+    let t = (jde_from_moment(moment) - 2_451_545.0) / 36_525.0;
+    let l0 = 280.46646 + 36_000.769_83 * t + 0.000_303_2 * t * t;
+    let m = (357.52911 + 35_999.050_29 * t - 0.000_153_7 * t * t).to_radians();
+    let c = (1.914_602 - 0.004_817 * t - 0.000_014 * t * t) * m.sin()
+        + (0.019_993 - 0.000_101 * t) * (2.0 * m).sin()
+        + 0.000_289 * (3.0 * m).sin();
+    let omega = (125.04 - 1_934.136 * t).to_radians();
+    (l0 + c - 0.005_69 - 0.004_78 * omega.sin()).rem_euclid(360.0)
+}
+
+/// Moment of the `k`-th new moon after January 6th 2000 (Meeus, chapter 49).
+fn nth_new_moon(k: i64) -> f64 {
+    let k = k as f64;
+    let t = k / 1_236.85;
+    let jde = 2_451_550.097_66 + MEAN_SYNODIC_MONTH * k + 0.000_154_37 * t * t
+        - 0.000_000_150 * t * t * t
+        + 0.000_000_000_73 * t * t * t * t;
+    let e = 1.0 - 0.002_516 * t - 0.000_007_4 * t * t;
+    let m = (2.5534 + 29.105_356_70 * k - 0.000_001_4 * t * t).to_radians();
+    let mp = (201.5643 + 385.816_935_28 * k + 0.010_758_2 * t * t + 0.000_012_38 * t * t * t)
+        .to_radians();
+    let f = (160.7108 + 390.670_502_84 * k - 0.001_611_8 * t * t - 0.000_002_27 * t * t * t)
+        .to_radians();
+    let omega = (124.7746 - 1.563_755_88 * k + 0.002_067_2 * t * t).to_radians();
+    let correction = -0.40720 * mp.sin()
+        + 0.17241 * e * m.sin()
+        + 0.01608 * (2.0 * mp).sin()
+        + 0.01039 * (2.0 * f).sin()
+        + 0.00739 * e * (mp - m).sin()
+        - 0.00514 * e * (mp + m).sin()
+        + 0.00208 * e * e * (2.0 * m).sin()
+        - 0.00111 * (mp - 2.0 * f).sin()
+        - 0.00057 * (mp + 2.0 * f).sin()
+        + 0.00056 * e * (2.0 * mp + m).sin()
+        - 0.00042 * (3.0 * mp).sin()
+        + 0.00042 * e * (m + 2.0 * f).sin()
+        + 0.00038 * e * (m - 2.0 * f).sin()
+        - 0.00024 * e * (2.0 * mp - m).sin()
+        - 0.00017 * omega.sin()
+        - 0.00007 * (mp + 2.0 * m).sin()
+        + 0.00004 * (2.0 * mp - 2.0 * f).sin()
+        + 0.00004 * (3.0 * m).sin()
+        + 0.00003 * (mp + m - 2.0 * f).sin()
+        + 0.00003 * (2.0 * mp + 2.0 * f).sin()
+        - 0.00003 * (mp + m + 2.0 * f).sin()
+        + 0.00003 * (mp - m + 2.0 * f).sin()
+        - 0.00002 * (mp - m - 2.0 * f).sin()
+        - 0.00002 * (3.0 * mp + m).sin()
+        + 0.00002 * (4.0 * mp).sin();
+    moment_from_jde(jde + correction)
+}
+
+/// Index of the last new moon strictly before `moment`.
+fn new_moon_index_before(moment: f64) -> i64 {
+    let mut k = ((jde_from_moment(moment) - 2_451_550.097_66) / MEAN_SYNODIC_MONTH).floor() as i64;
+    while nth_new_moon(k + 1) < moment {
+        k += 1;
+    }
+    while nth_new_moon(k) >= moment {
+        k -= 1;
+    }
+    k
+}
+
+/// Fixed date in China of the first new moon on or after the given fixed date.
+fn chinese_new_moon_on_or_after(fixed: i64) -> i64 {
+    let k = new_moon_index_before(fixed as f64 - CHINA_OFFSET) + 1;
+    (nth_new_moon(k) + CHINA_OFFSET).floor() as i64
+}
+
+/// Fixed date in China of the last new moon before the given fixed date.
+fn chinese_new_moon_before(fixed: i64) -> i64 {
+    let k = new_moon_index_before(fixed as f64 - CHINA_OFFSET);
+    (nth_new_moon(k) + CHINA_OFFSET).floor() as i64
+}
+
+fn chinese_major_solar_term(fixed: i64) -> i64 {
+    let s = solar_longitude(fixed as f64 - CHINA_OFFSET);
+    (2 + (s / 30.0).floor() as i64 - 1).rem_euclid(12) + 1
+}
+
+fn chinese_no_major_solar_term(fixed: i64) -> bool {
+    chinese_major_solar_term(fixed)
+        == chinese_major_solar_term(chinese_new_moon_on_or_after(fixed + 1))
+}
+
+fn chinese_prior_leap_month(start: i64, month: i64) -> bool {
+    month >= start
+        && (chinese_no_major_solar_term(month)
+            || chinese_prior_leap_month(start, chinese_new_moon_before(month)))
+}
+
+/// Fixed date in China of the winter solstice on or before the given fixed date.
+fn chinese_winter_solstice_on_or_before(fixed: i64) -> i64 {
+    let midnight = |day: i64| day as f64 - CHINA_OFFSET;
+    // Estimate the moment the sun last reached 270 degrees, then search forward.
+    let rate = MEAN_TROPICAL_YEAR / 360.0;
+    let moment = midnight(fixed + 1);
+    let tau = moment - rate * (solar_longitude(moment) - 270.0).rem_euclid(360.0);
+    let delta = (solar_longitude(tau) - 270.0 + 180.0).rem_euclid(360.0) - 180.0;
+    let approx = moment.min(tau - rate * delta);
+    let mut day = approx.floor() as i64 - 1;
+    loop {
+        let longitude = solar_longitude(midnight(day + 1));
+        if longitude > 270.0 && longitude < 300.0 {
+            return day;
+        }
+        day += 1;
+    }
+}
+
+/// Converts a fixed date to a Chinese (year of cycle, month, leap month, day) tuple.
+fn chinese_from_fixed(fixed: i64) -> (i64, i64, bool, i64) {
+    let s1 = chinese_winter_solstice_on_or_before(fixed);
+    let s2 = chinese_winter_solstice_on_or_before(s1 + 370);
+    let m12 = chinese_new_moon_on_or_after(s1 + 1);
+    let next_m11 = chinese_new_moon_before(s2 + 1);
+    let m = chinese_new_moon_before(fixed + 1);
+    let leap_year = ((next_m11 - m12) as f64 / MEAN_SYNODIC_MONTH).round() as i64 == 12;
+    let offset = if leap_year && chinese_prior_leap_month(m12, m) {
+        1
+    } else {
+        0
+    };
+    let month =
+        (((m - m12) as f64 / MEAN_SYNODIC_MONTH).round() as i64 - offset - 1).rem_euclid(12) + 1;
+    let leap_month = leap_year
+        && chinese_no_major_solar_term(m)
+        && !chinese_prior_leap_month(m12, chinese_new_moon_before(m));
+    let elapsed_years = (1.5 - month as f64 / 12.0
+        + (fixed - CHINESE_EPOCH) as f64 / MEAN_TROPICAL_YEAR)
+        .floor() as i64;
+    let year = (elapsed_years - 1).rem_euclid(60) + 1;
+    let day = fixed - m + 1;
+    (year, month, leap_month, day)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixed(y: i32, m: u32, d: u32) -> i64 {
+        NaiveDate::from_ymd(y, m, d).num_days_from_ce() as i64
+    }
+
+    #[test]
+    fn test_hijri() {
+        // The epoch, 16 July 622 (Julian).
+        assert_eq!(hijri_from_fixed(fixed(622, 7, 19)), (1, 1, 1));
+        assert_eq!(hijri_from_fixed(fixed(2000, 1, 1)), (1420, 9, 24));
+    }
+
+    #[test]
+    fn test_hebrew() {
+        assert_eq!(hebrew_from_fixed(fixed(2024, 10, 3)), (5785, 7, 1));
+        assert_eq!(hebrew_from_fixed(fixed(2025, 4, 13)), (5785, 1, 15));
+        assert_eq!(hebrew_from_fixed(fixed(2024, 3, 24)), (5784, 13, 14));
+    }
+
+    #[test]
+    fn test_persian() {
+        assert_eq!(
+            persian_from_gregorian(NaiveDate::from_ymd(2025, 3, 21)),
+            (1404, 1, 1)
+        );
+        assert_eq!(
+            persian_from_gregorian(NaiveDate::from_ymd(2024, 3, 19)),
+            (1402, 12, 29)
+        );
+        assert_eq!(
+            persian_from_gregorian(NaiveDate::from_ymd(2024, 12, 21)),
+            (1403, 10, 1)
+        );
+    }
+
+    #[test]
+    fn test_chinese() {
+        // Year of the dragon (Jia-Chen) starts 10 February 2024.
+        assert_eq!(chinese_from_fixed(fixed(2024, 2, 10)), (41, 1, false, 1));
+        assert_eq!(chinese_from_fixed(fixed(2024, 2, 9)), (40, 12, false, 30));
+        // 2023 had a leap second month, 2025 a leap sixth month.
+        assert_eq!(chinese_from_fixed(fixed(2023, 3, 22)), (40, 2, true, 1));
+        assert_eq!(chinese_from_fixed(fixed(2025, 7, 25)), (42, 6, true, 1));
+        assert_eq!(chinese_from_fixed(fixed(2025, 10, 6)), (42, 8, false, 15));
+    }
+}
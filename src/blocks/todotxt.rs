@@ -0,0 +1,293 @@
+//! A block for the open tasks of a todo.txt file, which it watches with inotify.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use inotify::{Inotify, WatchMask};
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::reactor;
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::util::{expand_home, pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+/// The open tasks of a todo.txt file.
+#[derive(Clone, Debug, Default, PartialEq)]
+struct TodoList {
+    count: u32,
+    /// The number of tasks per priority, from `A` to `Z`
+    priorities: HashMap<char, u32>,
+    /// The priority and the text of the most important task, which is the first task of the
+    /// highest priority, or the first task without priorities
+    top: Option<(Option<char>, String)>,
+}
+
+/// Splits a task into its priority and its text without the creation date.
+fn parse_task(line: &str) -> (Option<char>, &str) {
+    let bytes = line.as_bytes();
+    let (priority, rest) = if bytes.len() > 4
+        && bytes[0] == b'('
+        && bytes[1].is_ascii_uppercase()
+        && bytes[2] == b')'
+        && bytes[3] == b' '
+    {
+        (Some(bytes[1] as char), &line[4..])
+    } else {
+        (None, line)
+    };
+    let rest = rest.trim_start();
+    let is_date = |date: &str| {
+        date.len() == 10
+            && date.char_indices().all(|(i, c)| {
+                if i == 4 || i == 7 {
+                    c == '-'
+                } else {
+                    c.is_ascii_digit()
+                }
+            })
+    };
+    let mut words = rest.splitn(2, ' ');
+    let text = match (words.next(), words.next()) {
+        (Some(date), Some(text)) if is_date(date) => text.trim_start(),
+        _ => rest,
+    };
+    (priority, text)
+}
+
+fn parse_todo(content: &str) -> TodoList {
+    let mut todo = TodoList::default();
+    for line in content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+    {
+        // Completed tasks start with `x`
+        if line.starts_with("x ") {
+            continue;
+        }
+        let (priority, text) = parse_task(line);
+        todo.count += 1;
+        if let Some(priority) = priority {
+            *todo.priorities.entry(priority).or_insert(0) += 1;
+        }
+        let more_important = match todo.top {
+            None => true,
+            // `A` is the highest priority, and any priority is higher than none
+            Some((top, _)) => priority.is_some() && (top.is_none() || priority < top),
+        };
+        if more_important {
+            todo.top = Some((priority, text.to_string()));
+        }
+    }
+    todo
+}
+
+pub struct Todotxt {
+    id: String,
+    output: ButtonWidget,
+    format: FormatTemplate,
+    file: PathBuf,
+    editor_command: Option<String>,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TodotxtConfig {
+    /// The todo.txt file
+    #[serde(default = "TodotxtConfig::default_file")]
+    pub file: String,
+
+    /// Format string
+    #[serde(default = "TodotxtConfig::default_format")]
+    pub format: String,
+
+    /// Command to run on a left click, with the file as its argument
+    #[serde(default = "TodotxtConfig::default_editor_command")]
+    pub editor_command: Option<String>,
+}
+
+impl TodotxtConfig {
+    fn default_file() -> String {
+        "~/todo.txt".to_owned()
+    }
+
+    fn default_format() -> String {
+        "{count}".to_owned()
+    }
+
+    fn default_editor_command() -> Option<String> {
+        Some("i3-sensible-terminal -e \"${EDITOR:-vi}\"".to_owned())
+    }
+}
+
+impl ConfigBlock for Todotxt {
+    type Config = TodotxtConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let file = expand_home(&block_config.file);
+        let file_name = file
+            .file_name()
+            .block_error("todotxt", "the todo.txt file has no name")?
+            .to_owned();
+        let directory = match file.parent() {
+            Some(directory) if !directory.as_os_str().is_empty() => directory.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+
+        // Editors often replace the file instead of writing to it, so its directory is
+        // watched for the file being written, created, moved or deleted
+        let mut notify = Inotify::init().block_error("todotxt", "Failed to start inotify")?;
+        notify
+            .add_watch(
+                &directory,
+                WatchMask::CLOSE_WRITE
+                    | WatchMask::CREATE
+                    | WatchMask::MOVED_TO
+                    | WatchMask::MOVED_FROM
+                    | WatchMask::DELETE,
+            )
+            .block_error(
+                "todotxt",
+                &format!("Failed to watch {}", directory.display()),
+            )?;
+        let id = pseudo_uuid();
+        reactor::request_updates(
+            notify,
+            id.clone(),
+            send,
+            Duration::from_secs(0),
+            move |notify| {
+                let mut buffer = [0; 1024];
+                let events = notify.read_events(&mut buffer)?;
+                Ok(events
+                    .into_iter()
+                    .any(|event| event.name == Some(file_name.as_os_str())))
+            },
+        )?;
+
+        Ok(Todotxt {
+            output: ButtonWidget::new(config, &id).with_icon("tasks"),
+            id,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("todotxt", "Invalid format specified")?,
+            file,
+            editor_command: block_config.editor_command,
+        })
+    }
+}
+
+impl Block for Todotxt {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let content = match fs::read_to_string(&self.file) {
+            Ok(content) => content,
+            // There are no tasks before the first one creates the file
+            Err(ref e) if e.kind() == ErrorKind::NotFound => String::new(),
+            Err(_) => {
+                return Err(BlockError(
+                    "todotxt".to_string(),
+                    format!("failed to read {}", self.file.display()),
+                ))
+            }
+        };
+        let todo = parse_todo(&content);
+        let priority = |priority| todo.priorities.get(&priority).cloned().unwrap_or(0);
+        let (top_priority, top) = todo.top.clone().unwrap_or((None, String::new()));
+        let values = map!(
+            "{count}" => todo.count.to_string(),
+            "{a}" => priority('A').to_string(),
+            "{b}" => priority('B').to_string(),
+            "{c}" => priority('C').to_string(),
+            "{top}" => top,
+            "{top_priority}" => top_priority.map(|p| p.to_string()).unwrap_or_default()
+        );
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
+        self.output.set_state(if priority('A') > 0 {
+            State::Warning
+        } else if todo.count > 0 {
+            State::Info
+        } else {
+            State::Idle
+        });
+
+        // The watcher updates the block when the file changes
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if !event.matches_name(&self.id) || event.button != MouseButton::Left {
+            return Ok(());
+        }
+        if let Some(ref editor_command) = self.editor_command {
+            // The file is passed as `$0`, so that its path needs no quoting
+            let command = format!("{} \"$0\"", editor_command);
+            spawn_child_async("sh", &["-c", &command, &self.file.to_string_lossy()])
+                .block_error("todotxt", "could not spawn child")?;
+        }
+        Ok(())
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left && self.editor_command.is_some()
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_task, parse_todo};
+
+    #[test]
+    fn test_parse_task() {
+        assert_eq!(
+            parse_task("(A) 2021-03-02 Call the bank +finance"),
+            (Some('A'), "Call the bank +finance")
+        );
+        assert_eq!(
+            parse_task("2021-03-02 Water the plants"),
+            (None, "Water the plants")
+        );
+        assert_eq!(
+            parse_task("(b) Not a priority"),
+            (None, "(b) Not a priority")
+        );
+        assert_eq!(parse_task("(C)No space"), (None, "(C)No space"));
+    }
+
+    #[test]
+    fn test_parse_todo() {
+        let todo = parse_todo(
+            "Water the plants\n\
+             (B) File the taxes @home\n\
+             x 2021-03-01 (A) Renew the passport\n\
+             \n\
+             (A) Call the bank\n\
+             (A) Book the flights\n",
+        );
+        assert_eq!(todo.count, 4);
+        assert_eq!(todo.priorities[&'A'], 2);
+        assert_eq!(todo.priorities[&'B'], 1);
+        assert_eq!(todo.top, Some((Some('A'), "Call the bank".to_string())));
+
+        let todo = parse_todo("Water the plants\nFile the taxes\n");
+        assert_eq!(todo.top, Some((None, "Water the plants".to_string())));
+        assert_eq!(parse_todo("x Done\n").top, None);
+    }
+}
@@ -147,6 +147,10 @@ impl Block for Toggle {
         Ok(())
     }
 
+    fn handles_click(&self, _event: &I3BarEvent) -> bool {
+        true
+    }
+
     fn id(&self) -> &str {
         &self.id
     }
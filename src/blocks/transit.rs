@@ -0,0 +1,387 @@
+//! A block for the next departures at a public transit stop, from Transport for London or a
+//! HAFAS REST API like the one of Deutsche Bahn.
+
+use std::env;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{http_get, pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+const TFL_APP_KEY_ENV: &str = "TFL_APP_KEY";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "name", rename_all = "lowercase")]
+pub enum TransitService {
+    /// The arrivals API of Transport for London
+    Tfl {
+        #[serde(default = "TransitService::getenv_tfl_app_key")]
+        app_key: Option<String>,
+    },
+    /// A `hafas-rest-api`, like the ones of transport.rest
+    Hafas {
+        #[serde(default = "TransitService::default_hafas_url")]
+        url: String,
+    },
+}
+
+impl TransitService {
+    fn getenv_tfl_app_key() -> Option<String> {
+        env::var(TFL_APP_KEY_ENV).ok()
+    }
+
+    fn default_hafas_url() -> String {
+        "https://v6.db.transport.rest".to_owned()
+    }
+}
+
+/// A departure at the stop.
+#[derive(Clone, Debug, PartialEq)]
+struct Departure {
+    line: String,
+    direction: String,
+    time: DateTime<Utc>,
+}
+
+fn malformed_json_error() -> Error {
+    BlockError("transit".to_string(), "Malformed JSON.".to_string())
+}
+
+fn parse_time(time: &Value) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(time.as_str()?)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// The departures in the answer of `/StopPoint/{id}/Arrivals` of TfL, which are the
+/// arrivals at the stop.
+fn parse_tfl(json: &Value) -> Result<Vec<Departure>> {
+    if let Some(message) = json["message"].as_str() {
+        return Err(BlockError(
+            "transit".to_string(),
+            format!("API Error: {}", message),
+        ));
+    }
+    Ok(json
+        .as_array()
+        .ok_or_else(malformed_json_error)?
+        .iter()
+        .filter_map(|arrival| {
+            Some(Departure {
+                line: arrival["lineName"].as_str()?.to_string(),
+                direction: arrival["destinationName"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                time: parse_time(&arrival["expectedArrival"])?,
+            })
+        })
+        .collect())
+}
+
+/// The departures in the answer of `/stops/{id}/departures` of a HAFAS REST API, without the
+/// cancelled ones, which have no time.
+fn parse_hafas(json: &Value) -> Result<Vec<Departure>> {
+    if let Some(message) = json["msg"].as_str() {
+        return Err(BlockError(
+            "transit".to_string(),
+            format!("API Error: {}", message),
+        ));
+    }
+    // Older versions of the API answer with the list alone
+    let departures = json["departures"]
+        .as_array()
+        .or_else(|| json.as_array())
+        .ok_or_else(malformed_json_error)?;
+    Ok(departures
+        .iter()
+        .filter_map(|departure| {
+            Some(Departure {
+                line: departure.pointer("/line/name")?.as_str()?.to_string(),
+                direction: departure["direction"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                time: parse_time(&departure["when"])?,
+            })
+        })
+        .collect())
+}
+
+/// The next `count` departures that can still be reached in `walk_time`, with the minutes
+/// until each leaves.
+fn upcoming(
+    mut departures: Vec<Departure>,
+    now: DateTime<Utc>,
+    walk_time: i64,
+    count: usize,
+) -> Vec<(Departure, i64)> {
+    departures.sort_by_key(|departure| departure.time);
+    departures
+        .into_iter()
+        .map(|departure| {
+            let minutes = (departure.time - now).num_minutes();
+            (departure, minutes)
+        })
+        .filter(|(_, minutes)| *minutes >= walk_time)
+        .take(count)
+        .collect()
+}
+
+fn fetch(url: &str) -> Result<Value> {
+    http_get("transit", url, &[], Duration::from_secs(10))?.json("transit")
+}
+
+pub struct Transit {
+    id: String,
+    text: TextWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    departure_format: FormatTemplate,
+    service: TransitService,
+    stop: String,
+    count: usize,
+    walk_time: i64,
+    warning: i64,
+    critical: i64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct TransitConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "TransitConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// The service to ask
+    pub service: TransitService,
+
+    /// The id of the stop in the service
+    pub stop: String,
+
+    /// The number of departures to show
+    #[serde(default = "TransitConfig::default_count")]
+    pub count: usize,
+
+    /// The minutes it takes to reach the stop
+    #[serde(default)]
+    pub walk_time: i64,
+
+    /// Format string
+    #[serde(default = "TransitConfig::default_format")]
+    pub format: String,
+
+    /// Format string of each departure
+    #[serde(default = "TransitConfig::default_departure_format")]
+    pub departure_format: String,
+
+    /// Minutes to leave at or below which the state is warning
+    #[serde(default = "TransitConfig::default_warning")]
+    pub warning: i64,
+
+    /// Minutes to leave at or below which the state is critical
+    #[serde(default = "TransitConfig::default_critical")]
+    pub critical: i64,
+}
+
+impl TransitConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    fn default_count() -> usize {
+        3
+    }
+
+    fn default_format() -> String {
+        "{departures}".to_owned()
+    }
+
+    fn default_departure_format() -> String {
+        "{line} {minutes}'".to_owned()
+    }
+
+    fn default_warning() -> i64 {
+        5
+    }
+
+    fn default_critical() -> i64 {
+        2
+    }
+}
+
+impl ConfigBlock for Transit {
+    type Config = TransitConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        Ok(Transit {
+            id: pseudo_uuid(),
+            text: TextWidget::new(config),
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("transit", "Invalid format specified")?,
+            departure_format: FormatTemplate::from_string(&block_config.departure_format)
+                .block_error("transit", "Invalid departure_format specified")?,
+            service: block_config.service,
+            stop: block_config.stop,
+            count: block_config.count,
+            walk_time: block_config.walk_time,
+            warning: block_config.warning,
+            critical: block_config.critical,
+        })
+    }
+}
+
+impl Transit {
+    fn fetch_departures(&self) -> Result<Vec<Departure>> {
+        match self.service {
+            TransitService::Tfl { ref app_key } => {
+                let mut url = format!("https://api.tfl.gov.uk/StopPoint/{}/Arrivals", self.stop);
+                if let Some(app_key) = app_key {
+                    url.push_str(&format!("?app_key={}", app_key));
+                }
+                parse_tfl(&fetch(&url)?)
+            }
+            TransitService::Hafas { ref url } => parse_hafas(&fetch(&format!(
+                "{}/stops/{}/departures?duration=120&results={}",
+                url.trim_end_matches('/'),
+                self.stop,
+                // The departures that cannot be reached anymore are left out
+                self.count * 3 + 5
+            ))?),
+        }
+    }
+}
+
+impl Block for Transit {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let departures = upcoming(
+            self.fetch_departures()?,
+            Utc::now(),
+            self.walk_time,
+            self.count,
+        );
+
+        let mut texts = Vec::new();
+        for (departure, minutes) in &departures {
+            let values = map!(
+                "{line}" => departure.line.clone(),
+                "{direction}" => departure.direction.clone(),
+                "{minutes}" => minutes.to_string(),
+                "{leave}" => (minutes - self.walk_time).to_string()
+            );
+            texts.push(self.departure_format.render_static_str(&values)?);
+        }
+        // The time to leave for the first departure that can be reached
+        let leave = departures
+            .first()
+            .map(|(_, minutes)| minutes - self.walk_time);
+        let values = map!(
+            "{departures}" => if texts.is_empty() {
+                "-".to_string()
+            } else {
+                texts.join(" ")
+            },
+            "{leave}" => leave.map_or("-".to_string(), |leave| leave.to_string())
+        );
+        self.text.set_text(self.format.render_static_str(&values)?);
+        self.text.set_state(match leave {
+            Some(leave) if leave <= self.critical => State::Critical,
+            Some(leave) if leave <= self.warning => State::Warning,
+            _ => State::Idle,
+        });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_hafas, parse_tfl, upcoming, Departure};
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn test_parse_tfl() {
+        let json = serde_json::json!([{
+            "lineName": "73",
+            "destinationName": "Oxford Circus",
+            "timeToStation": 312,
+            "expectedArrival": "2024-03-13T11:05:12Z"
+        }]);
+        assert_eq!(
+            parse_tfl(&json).unwrap(),
+            vec![Departure {
+                line: "73".to_string(),
+                direction: "Oxford Circus".to_string(),
+                time: Utc.ymd(2024, 3, 13).and_hms(11, 5, 12),
+            }]
+        );
+        let json = serde_json::json!({"httpStatusCode": 404, "message": "The following stop point is not recognised"});
+        assert!(parse_tfl(&json).is_err());
+    }
+
+    #[test]
+    fn test_parse_hafas() {
+        let json = serde_json::json!({"departures": [
+            {
+                "when": "2024-03-13T12:05:00+01:00",
+                "plannedWhen": "2024-03-13T12:03:00+01:00",
+                "delay": 120,
+                "direction": "S Potsdam Hauptbahnhof",
+                "line": {"name": "S 7"}
+            },
+            {
+                "when": null,
+                "cancelled": true,
+                "direction": "S Ahrensfelde",
+                "line": {"name": "S 7"}
+            }
+        ]});
+        let departures = parse_hafas(&json).unwrap();
+        assert_eq!(departures.len(), 1);
+        assert_eq!(departures[0].line, "S 7");
+        assert_eq!(departures[0].time, Utc.ymd(2024, 3, 13).and_hms(11, 5, 0));
+    }
+
+    #[test]
+    fn test_upcoming() {
+        let now = Utc.ymd(2024, 3, 13).and_hms(11, 0, 0);
+        let departure = |line: &str, minute| Departure {
+            line: line.to_string(),
+            direction: String::new(),
+            time: Utc.ymd(2024, 3, 13).and_hms(11, minute, 30),
+        };
+        let departures = vec![
+            departure("b", 12),
+            departure("a", 2),
+            departure("c", 20),
+            departure("d", 7),
+        ];
+        let lines: Vec<(String, i64)> = upcoming(departures, now, 3, 2)
+            .into_iter()
+            .map(|(departure, minutes)| (departure.line, minutes))
+            .collect();
+        assert_eq!(lines, vec![("d".to_string(), 7), ("b".to_string(), 12)]);
+    }
+}
@@ -0,0 +1,277 @@
+//! A block for a UPS, as reported by the `upsd` daemon of Network UPS Tools.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Reads a line of the answer to `LIST VAR`, like `VAR myups battery.charge "100"`.
+fn parse_var(line: &str) -> Option<(String, String)> {
+    let mut parts = line.splitn(4, ' ');
+    if parts.next()? != "VAR" {
+        return None;
+    }
+    let _ups = parts.next()?;
+    let name = parts.next()?;
+    let value = parts.next()?.trim();
+    if value.len() < 2 || !value.starts_with('"') || !value.ends_with('"') {
+        return None;
+    }
+    let value = value[1..value.len() - 1]
+        .replace("\\\"", "\"")
+        .replace("\\\\", "\\");
+    Some((name.to_string(), value))
+}
+
+/// Asks `upsd` for all variables of the UPS.
+fn list_vars(host: &str, port: u16, ups: &str) -> Result<HashMap<String, String>> {
+    let addr = (host, port)
+        .to_socket_addrs()
+        .block_error("ups", "Failed to resolve the upsd host")?
+        .next()
+        .block_error("ups", "Failed to resolve the upsd host")?;
+    let mut stream =
+        TcpStream::connect_timeout(&addr, TIMEOUT).block_error("ups", "Failed to reach upsd")?;
+    stream
+        .set_read_timeout(Some(TIMEOUT))
+        .block_error("ups", "Failed to set a timeout")?;
+    stream
+        .write_all(format!("LIST VAR {}\n", ups).as_bytes())
+        .block_error("ups", "Failed to send to upsd")?;
+
+    let mut vars = HashMap::new();
+    for line in BufReader::new(&stream).lines() {
+        let line = line.block_error("ups", "Failed to read from upsd")?;
+        if line.starts_with("ERR ") {
+            return Err(BlockError(
+                "ups".to_string(),
+                format!("upsd answered {}", &line[4..]),
+            ));
+        }
+        if line.starts_with("END LIST VAR") {
+            break;
+        }
+        if let Some((name, value)) = parse_var(&line) {
+            vars.insert(name, value);
+        }
+    }
+    // Not being able to say goodbye is of no consequence
+    let _ = stream.write_all(b"LOGOUT\n");
+    Ok(vars)
+}
+
+/// Formats a time in seconds as `H:MM`.
+fn format_runtime(seconds: u64) -> String {
+    let minutes = seconds / 60;
+    format!("{}:{:02}", minutes / 60, minutes % 60)
+}
+
+pub struct Ups {
+    id: String,
+    output: TextWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    host: String,
+    port: u16,
+    name: String,
+    on_battery_command: Option<String>,
+    /// Whether the UPS was on battery at the last update, if it was reached
+    on_battery: Option<bool>,
+    charge_warning: f64,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct UpsConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "UpsConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format string
+    #[serde(default = "UpsConfig::default_format")]
+    pub format: String,
+
+    /// Host that runs upsd
+    #[serde(default = "UpsConfig::default_host")]
+    pub host: String,
+
+    /// Port of upsd
+    #[serde(default = "UpsConfig::default_port")]
+    pub port: u16,
+
+    /// Name of the UPS, as configured in ups.conf
+    #[serde(default = "UpsConfig::default_name")]
+    pub name: String,
+
+    /// Shell command that is run when the UPS switches to battery
+    #[serde(default)]
+    pub on_battery: Option<String>,
+
+    /// Charge in percent below which the state is warning, on line power
+    #[serde(default = "UpsConfig::default_charge_warning")]
+    pub charge_warning: f64,
+}
+
+impl UpsConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn default_format() -> String {
+        "{charge}% {runtime}".to_owned()
+    }
+
+    fn default_host() -> String {
+        "localhost".to_owned()
+    }
+
+    fn default_port() -> u16 {
+        3493
+    }
+
+    fn default_name() -> String {
+        "ups".to_owned()
+    }
+
+    fn default_charge_warning() -> f64 {
+        50.0
+    }
+}
+
+impl ConfigBlock for Ups {
+    type Config = UpsConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        Ok(Ups {
+            id: pseudo_uuid(),
+            output: TextWidget::new(config).with_icon("ups"),
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("ups", "Invalid format specified")?,
+            host: block_config.host,
+            port: block_config.port,
+            name: block_config.name,
+            on_battery_command: block_config.on_battery,
+            on_battery: None,
+            charge_warning: block_config.charge_warning,
+        })
+    }
+}
+
+impl Block for Ups {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let vars = match list_vars(&self.host, self.port, &self.name) {
+            Ok(vars) => vars,
+            Err(error) => {
+                self.on_battery = None;
+                return Err(error);
+            }
+        };
+
+        // The status is a list of flags like "OL CHRG" or "OB LB"
+        let flags = vars
+            .get("ups.status")
+            .map(|status| status.split_whitespace().collect::<Vec<_>>())
+            .unwrap_or_default();
+        let on_battery = flags.contains(&"OB");
+        let low_battery = flags.contains(&"LB");
+        let status = if low_battery {
+            "low battery"
+        } else if on_battery {
+            "on battery"
+        } else if flags.contains(&"OL") {
+            "online"
+        } else {
+            "unknown"
+        };
+
+        // Only a switch is reported, so that restarting the bar during a power cut does not
+        // run the command again
+        if on_battery && self.on_battery == Some(false) {
+            if let Some(ref command) = self.on_battery_command {
+                spawn_child_async("sh", &["-c", command])
+                    .block_error("ups", "could not spawn the on_battery command")?;
+            }
+        }
+        self.on_battery = Some(on_battery);
+
+        let number = |name: &str| vars.get(name).and_then(|value| value.parse::<f64>().ok());
+        let charge = number("battery.charge");
+        let values = map!(
+            "{name}" => self.name.clone(),
+            "{status}" => status.to_string(),
+            "{charge}" => charge.map_or("×".to_string(), |charge| format!("{:.0}", charge)),
+            "{load}" => number("ups.load").map_or("×".to_string(), |load| format!("{:.0}", load)),
+            "{runtime}" => number("battery.runtime")
+                .map_or("×".to_string(), |runtime| format_runtime(runtime as u64))
+        );
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
+
+        self.output.set_state(if on_battery || low_battery {
+            State::Critical
+        } else if charge.map_or(false, |charge| charge < self.charge_warning) {
+            State::Warning
+        } else {
+            State::Idle
+        });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{format_runtime, parse_var};
+
+    #[test]
+    fn test_parse_var() {
+        assert_eq!(
+            parse_var("VAR myups battery.charge \"100\""),
+            Some(("battery.charge".to_string(), "100".to_string()))
+        );
+        assert_eq!(
+            parse_var("VAR myups ups.status \"OB LB\""),
+            Some(("ups.status".to_string(), "OB LB".to_string()))
+        );
+        assert_eq!(
+            parse_var("VAR myups ups.model \"Back-UPS \\\"XS\\\"\""),
+            Some(("ups.model".to_string(), "Back-UPS \"XS\"".to_string()))
+        );
+        assert_eq!(parse_var("BEGIN LIST VAR myups"), None);
+        assert_eq!(parse_var("VAR myups battery.charge 100"), None);
+    }
+
+    #[test]
+    fn test_format_runtime() {
+        assert_eq!(format_runtime(0), "0:00");
+        assert_eq!(format_runtime(1830), "0:30");
+        assert_eq!(format_runtime(5400), "1:30");
+    }
+}
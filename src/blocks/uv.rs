@@ -0,0 +1,278 @@
+//! A block for the UV index at a location, from OpenUV or OpenWeatherMap.
+
+use std::env;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+use serde_json::Value;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::util::{http_get, pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+const OPENUV_API_KEY_ENV: &str = "OPENUV_API_KEY";
+const OPENWEATHERMAP_API_KEY_ENV: &str = "OPENWEATHERMAP_API_KEY";
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(tag = "name", rename_all = "lowercase")]
+pub enum UvService {
+    OpenUv {
+        #[serde(default = "UvService::getenv_openuv_api_key")]
+        api_key: Option<String>,
+    },
+    /// The One Call API of OpenWeatherMap
+    OpenWeatherMap {
+        #[serde(default = "UvService::getenv_openweathermap_api_key")]
+        api_key: Option<String>,
+    },
+}
+
+impl UvService {
+    fn getenv_openuv_api_key() -> Option<String> {
+        env::var(OPENUV_API_KEY_ENV).ok()
+    }
+
+    fn getenv_openweathermap_api_key() -> Option<String> {
+        env::var(OPENWEATHERMAP_API_KEY_ENV).ok()
+    }
+}
+
+fn malformed_json_error() -> Error {
+    BlockError("uv".to_string(), "Malformed JSON.".to_string())
+}
+
+/// The current and the highest UV index of the day, from OpenUV.
+fn parse_openuv(json: &Value) -> Result<(f64, f64)> {
+    if let Some(error) = json["error"].as_str() {
+        return Err(BlockError(
+            "uv".to_string(),
+            format!("API Error: {}", error),
+        ));
+    }
+    Ok((
+        json.pointer("/result/uv")
+            .and_then(|v| v.as_f64())
+            .ok_or_else(malformed_json_error)?,
+        json.pointer("/result/uv_max")
+            .and_then(|v| v.as_f64())
+            .ok_or_else(malformed_json_error)?,
+    ))
+}
+
+/// The current and the highest UV index of the day, from the One Call API of OpenWeatherMap.
+fn parse_onecall(json: &Value) -> Result<(f64, f64)> {
+    if let Some(message) = json["message"].as_str() {
+        return Err(BlockError(
+            "uv".to_string(),
+            format!("API Error: {}", message),
+        ));
+    }
+    Ok((
+        json.pointer("/current/uvi")
+            .and_then(|v| v.as_f64())
+            .ok_or_else(malformed_json_error)?,
+        json.pointer("/daily/0/uvi")
+            .and_then(|v| v.as_f64())
+            .ok_or_else(malformed_json_error)?,
+    ))
+}
+
+/// The risk of the WHO for a UV index.
+fn risk(uv: f64) -> &'static str {
+    match uv.round() as i64 {
+        i64::MIN..=2 => "low",
+        3..=5 => "moderate",
+        6..=7 => "high",
+        8..=10 => "very high",
+        _ => "extreme",
+    }
+}
+
+pub struct Uv {
+    id: String,
+    text: TextWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    service: UvService,
+    latitude: f64,
+    longitude: f64,
+    warning: f64,
+    critical: f64,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct UvConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "UvConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// The service to ask
+    pub service: UvService,
+
+    pub latitude: f64,
+
+    pub longitude: f64,
+
+    /// Format string
+    #[serde(default = "UvConfig::default_format")]
+    pub format: String,
+
+    /// UV index at or above which the state is warning
+    #[serde(default = "UvConfig::default_warning")]
+    pub warning: f64,
+
+    /// UV index at or above which the state is critical
+    #[serde(default = "UvConfig::default_critical")]
+    pub critical: f64,
+}
+
+impl UvConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(1800)
+    }
+
+    fn default_format() -> String {
+        "UV {uv}".to_owned()
+    }
+
+    fn default_warning() -> f64 {
+        6.0
+    }
+
+    fn default_critical() -> f64 {
+        8.0
+    }
+}
+
+impl ConfigBlock for Uv {
+    type Config = UvConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        Ok(Uv {
+            id: pseudo_uuid(),
+            text: TextWidget::new(config),
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("uv", "Invalid format specified")?,
+            service: block_config.service,
+            latitude: block_config.latitude,
+            longitude: block_config.longitude,
+            warning: block_config.warning,
+            critical: block_config.critical,
+        })
+    }
+}
+
+fn fetch(url: &str, headers: &[String]) -> Result<Value> {
+    http_get("uv", url, headers, Duration::from_secs(10))?.json("uv")
+}
+
+fn missing_api_key(env: &str) -> Error {
+    BlockError(
+        "uv".to_string(),
+        format!(
+            "Missing member 'service.api_key'. Add the member or configure with the environment variable {}",
+            env
+        ),
+    )
+}
+
+impl Block for Uv {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let (uv, uv_max) = match self.service {
+            UvService::OpenUv { ref api_key } => {
+                let api_key = api_key
+                    .as_ref()
+                    .ok_or_else(|| missing_api_key(OPENUV_API_KEY_ENV))?;
+                parse_openuv(&fetch(
+                    &format!(
+                        "https://api.openuv.io/api/v1/uv?lat={}&lng={}",
+                        self.latitude, self.longitude
+                    ),
+                    &[format!("x-access-token: {}", api_key)],
+                )?)?
+            }
+            UvService::OpenWeatherMap { ref api_key } => {
+                let api_key = api_key
+                    .as_ref()
+                    .ok_or_else(|| missing_api_key(OPENWEATHERMAP_API_KEY_ENV))?;
+                parse_onecall(&fetch(
+                    &format!(
+                        "https://api.openweathermap.org/data/3.0/onecall?lat={}&lon={}&appid={}&exclude=minutely,hourly,alerts",
+                        self.latitude, self.longitude, api_key
+                    ),
+                    &[],
+                )?)?
+            }
+        };
+
+        let values = map!(
+            "{uv}" => format!("{:.0}", uv),
+            "{uv_max}" => format!("{:.0}", uv_max),
+            "{risk}" => risk(uv).to_string()
+        );
+        self.text.set_text(self.format.render_static_str(&values)?);
+        self.text.set_state(if uv >= self.critical {
+            State::Critical
+        } else if uv >= self.warning {
+            State::Warning
+        } else {
+            State::Idle
+        });
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.text]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_onecall, parse_openuv, risk};
+
+    #[test]
+    fn test_parse_openuv() {
+        let json = serde_json::json!({"result": {
+            "uv": 4.2, "uv_time": "2023-06-01T11:00:00.000Z", "uv_max": 7.8
+        }});
+        assert_eq!(parse_openuv(&json).unwrap(), (4.2, 7.8));
+        let json = serde_json::json!({"error": "Daily API quota exceeded."});
+        assert!(parse_openuv(&json).is_err());
+    }
+
+    #[test]
+    fn test_parse_onecall() {
+        let json = serde_json::json!({
+            "current": {"temp": 25.0, "uvi": 9.1},
+            "daily": [{"uvi": 10.4}, {"uvi": 8.0}]
+        });
+        assert_eq!(parse_onecall(&json).unwrap(), (9.1, 10.4));
+        let json = serde_json::json!({"cod": 401, "message": "Invalid API key."});
+        assert!(parse_onecall(&json).is_err());
+    }
+
+    #[test]
+    fn test_risk() {
+        assert_eq!(risk(0.0), "low");
+        assert_eq!(risk(2.4), "low");
+        assert_eq!(risk(5.6), "high");
+        assert_eq!(risk(10.0), "very high");
+        assert_eq!(risk(11.3), "extreme");
+    }
+}
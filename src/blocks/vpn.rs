@@ -0,0 +1,380 @@
+//! A block for the status of VPN connections, from NetworkManager or the network interfaces.
+
+use std::collections::HashMap;
+use std::fs::read_dir;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::Sender;
+use dbus::arg::{RefArg, Variant};
+use dbus::ffidisp::{BusType, Connection, ConnectionItem};
+use dbus::{Message, Path};
+use serde_derive::Deserialize;
+
+use crate::blocks::net::NetworkDevice;
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+const NM_SERVICE: &str = "org.freedesktop.NetworkManager";
+const NM_PATH: &str = "/org/freedesktop/NetworkManager";
+const NM_ACTIVE_CONNECTION: &str = "org.freedesktop.NetworkManager.Connection.Active";
+
+/// https://developer.gnome.org/NetworkManager/stable/nm-dbus-types.html#NMActiveConnectionState
+const NM_ACTIVE_CONNECTION_STATE_ACTIVATED: u32 = 2;
+
+struct ActiveVpn {
+    path: Path<'static>,
+    id: String,
+    activated: bool,
+}
+
+/// The calls to NetworkManager that this block needs.
+struct NetworkManagerVpn {
+    conn: Connection,
+}
+
+impl NetworkManagerVpn {
+    /// Connects to NetworkManager, if it is running.
+    fn connect() -> Option<Self> {
+        let conn = Connection::get_private(BusType::System).ok()?;
+        let m = Message::new_method_call(
+            "org.freedesktop.DBus",
+            "/org/freedesktop/DBus",
+            "org.freedesktop.DBus",
+            "NameHasOwner",
+        )
+        .ok()?
+        .append1(NM_SERVICE);
+        let running: bool = conn.send_with_reply_and_block(m, 1000).ok()?.get1()?;
+        if running {
+            Some(NetworkManagerVpn { conn })
+        } else {
+            None
+        }
+    }
+
+    fn call(&self, m: Message) -> Result<Message> {
+        self.conn
+            .send_with_reply_and_block(m, 1000)
+            .block_error("vpn", "NetworkManager did not answer")
+    }
+
+    fn method(path: &Path, interface: &str, method: &str) -> Result<Message> {
+        Message::new_method_call(NM_SERVICE, path.clone(), interface, method)
+            .block_error("vpn", "Failed to create message")
+    }
+
+    fn property(&self, path: &Path, interface: &str, property: &str) -> Result<Message> {
+        let m = Self::method(path, "org.freedesktop.DBus.Properties", "Get")?
+            .append2(interface, property);
+        self.call(m)
+    }
+
+    fn nm_path() -> Path<'static> {
+        Path::new(NM_PATH).unwrap()
+    }
+
+    /// The active connections of type VPN or WireGuard.
+    fn active_vpns(&self) -> Result<Vec<ActiveVpn>> {
+        let m = self.property(&Self::nm_path(), NM_SERVICE, "ActiveConnections")?;
+        let paths: Variant<Vec<Path>> = m
+            .get1()
+            .block_error("vpn", "Failed to read active connections")?;
+
+        let mut vpns = Vec::new();
+        for path in paths.0 {
+            let path = path.into_static();
+            let kind: Variant<String> = self
+                .property(&path, NM_ACTIVE_CONNECTION, "Type")?
+                .get1()
+                .block_error("vpn", "Failed to read connection type")?;
+            let vpn: Variant<bool> = self
+                .property(&path, NM_ACTIVE_CONNECTION, "Vpn")?
+                .get1()
+                .block_error("vpn", "Failed to read connection type")?;
+            if !vpn.0 && kind.0 != "wireguard" {
+                continue;
+            }
+            let id: Variant<String> = self
+                .property(&path, NM_ACTIVE_CONNECTION, "Id")?
+                .get1()
+                .block_error("vpn", "Failed to read connection id")?;
+            let state: Variant<u32> = self
+                .property(&path, NM_ACTIVE_CONNECTION, "State")?
+                .get1()
+                .block_error("vpn", "Failed to read connection state")?;
+            vpns.push(ActiveVpn {
+                path,
+                id: id.0,
+                activated: state.0 == NM_ACTIVE_CONNECTION_STATE_ACTIVATED,
+            });
+        }
+        Ok(vpns)
+    }
+
+    /// Finds the saved connection with the id.
+    fn saved_connection(&self, id: &str) -> Result<Option<Path<'static>>> {
+        let settings_path = Path::new(format!("{}/Settings", NM_PATH)).unwrap();
+        let m = self.call(Self::method(
+            &settings_path,
+            "org.freedesktop.NetworkManager.Settings",
+            "ListConnections",
+        )?)?;
+        let paths: Vec<Path> = m
+            .get1()
+            .block_error("vpn", "Failed to read saved connections")?;
+
+        for path in paths {
+            let path = path.into_static();
+            let settings: HashMap<String, HashMap<String, Variant<Box<dyn RefArg>>>> = self
+                .call(Self::method(
+                    &path,
+                    "org.freedesktop.NetworkManager.Settings.Connection",
+                    "GetSettings",
+                )?)?
+                .get1()
+                .block_error("vpn", "Failed to read connection settings")?;
+            let saved_id = settings
+                .get("connection")
+                .and_then(|connection| connection.get("id"))
+                .and_then(|saved_id| saved_id.0.as_str());
+            if saved_id == Some(id) {
+                return Ok(Some(path));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Disconnects the connection if it is active, and connects it otherwise.
+    fn toggle(&self, id: &str) -> Result<()> {
+        if let Some(active) = self.active_vpns()?.into_iter().find(|vpn| vpn.id == id) {
+            let m = Self::method(&Self::nm_path(), NM_SERVICE, "DeactivateConnection")?
+                .append1(active.path);
+            self.call(m)?;
+            return Ok(());
+        }
+        let saved = self.saved_connection(id)?.block_error(
+            "vpn",
+            &format!("NetworkManager has no connection named `{}`", id),
+        )?;
+        let any = Path::new("/").unwrap();
+        let m = Self::method(&Self::nm_path(), NM_SERVICE, "ActivateConnection")?.append3(
+            saved,
+            any.clone(),
+            any,
+        );
+        self.call(m)?;
+        Ok(())
+    }
+}
+
+/// The names of the VPN interfaces (WireGuard, tun/tap and ppp) that are up.
+fn vpn_interfaces() -> Vec<String> {
+    let mut interfaces = read_dir("/sys/class/net")
+        .map(|entries| {
+            entries
+                .flatten()
+                .map(|entry| entry.file_name().to_string_lossy().into_owned())
+                .filter(|name| {
+                    let device = NetworkDevice::from_device(name.clone());
+                    device.is_vpn() && device.is_up().unwrap_or(false)
+                })
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    interfaces.sort();
+    interfaces
+}
+
+pub struct Vpn {
+    id: String,
+    output: ButtonWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    format_disconnected: FormatTemplate,
+    interfaces: bool,
+    connection: Option<String>,
+    networkmanager: Option<NetworkManagerVpn>,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct VpnConfig {
+    /// Update interval in seconds, for the interfaces
+    #[serde(
+        default = "VpnConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format string while a VPN is up
+    #[serde(default = "VpnConfig::default_format")]
+    pub format: String,
+
+    /// Format string while no VPN is up
+    #[serde(default = "VpnConfig::default_format_disconnected")]
+    pub format_disconnected: String,
+
+    /// Whether to look at the VPN interfaces when NetworkManager has no active VPN
+    #[serde(default = "VpnConfig::default_interfaces")]
+    pub interfaces: bool,
+
+    /// NetworkManager connection that is connected or disconnected on click
+    #[serde(default)]
+    pub connection: Option<String>,
+}
+
+impl VpnConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn default_format() -> String {
+        "{name}".to_owned()
+    }
+
+    fn default_format_disconnected() -> String {
+        "".to_owned()
+    }
+
+    fn default_interfaces() -> bool {
+        true
+    }
+}
+
+impl ConfigBlock for Vpn {
+    type Config = VpnConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+        let networkmanager = NetworkManagerVpn::connect();
+
+        if networkmanager.is_some() {
+            let id = id.clone();
+            thread::Builder::new()
+                .name("vpn".into())
+                .spawn(move || {
+                    let c = Connection::get_private(BusType::System).unwrap();
+                    c.add_match(
+                        "type='signal',\
+                         interface='org.freedesktop.NetworkManager.Connection.Active',\
+                         member='StateChanged'",
+                    )
+                    .unwrap();
+                    c.add_match(
+                        "type='signal',\
+                         path='/org/freedesktop/NetworkManager',\
+                         interface='org.freedesktop.NetworkManager',\
+                         member='PropertiesChanged'",
+                    )
+                    .unwrap();
+                    loop {
+                        for event in c.iter(300_000) {
+                            if let ConnectionItem::Nothing = event {
+                                continue;
+                            }
+                            if send
+                                .send(Task {
+                                    id: id.clone(),
+                                    update_time: Instant::now(),
+                                })
+                                .is_err()
+                            {
+                                return;
+                            }
+                        }
+                    }
+                })
+                .unwrap();
+        } else if block_config.connection.is_some() {
+            return Err(BlockError(
+                "vpn".to_owned(),
+                "`connection` requires NetworkManager".to_owned(),
+            ));
+        }
+
+        Ok(Vpn {
+            output: ButtonWidget::new(config, &id).with_icon("net_vpn"),
+            id,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("vpn", "Invalid format specified")?,
+            format_disconnected: FormatTemplate::from_string(&block_config.format_disconnected)
+                .block_error("vpn", "Invalid format_disconnected specified")?,
+            interfaces: block_config.interfaces,
+            connection: block_config.connection,
+            networkmanager,
+        })
+    }
+}
+
+impl Block for Vpn {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let vpns = match self.networkmanager {
+            Some(ref networkmanager) => networkmanager.active_vpns()?,
+            None => Vec::new(),
+        };
+
+        // Connections of NetworkManager have interfaces too, so these are only shown
+        // when NetworkManager knows of no VPN.
+        let (names, state) = if !vpns.is_empty() {
+            let activating = vpns.iter().any(|vpn| !vpn.activated);
+            let names = vpns.into_iter().map(|vpn| vpn.id).collect::<Vec<_>>();
+            (
+                names,
+                if activating {
+                    State::Warning
+                } else {
+                    State::Good
+                },
+            )
+        } else if self.interfaces {
+            (vpn_interfaces(), State::Good)
+        } else {
+            (Vec::new(), State::Good)
+        };
+
+        if names.is_empty() {
+            let values = map!("{name}" => "");
+            self.output
+                .set_text(self.format_disconnected.render_static_str(&values)?);
+            self.output.set_state(State::Critical);
+        } else {
+            let values = map!("{name}" => names.join(", "));
+            self.output
+                .set_text(self.format.render_static_str(&values)?);
+            self.output.set_state(state);
+        }
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if !event.matches_name(&self.id) || event.button != MouseButton::Left {
+            return Ok(());
+        }
+        if let (Some(networkmanager), Some(connection)) = (&self.networkmanager, &self.connection) {
+            networkmanager.toggle(connection)?;
+        }
+        Ok(())
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
@@ -1,8 +1,7 @@
 use std::fs::File;
 use std::io::BufReader;
 use std::path::PathBuf;
-use std::thread;
-use std::time::{Duration, Instant};
+use std::time::Duration;
 
 use crate::blocks::Update;
 use crate::blocks::{Block, ConfigBlock};
@@ -11,6 +10,7 @@ use crate::de::deserialize_duration;
 use crate::de::deserialize_local_timestamp;
 use crate::errors::*;
 use crate::input::I3BarEvent;
+use crate::reactor;
 use crate::scheduler::Task;
 use crate::util::{pseudo_uuid, xdg_config_home};
 use crate::widget::{I3BarWidget, State};
@@ -80,52 +80,36 @@ impl ConfigBlock for Watson {
             prev_state: None,
         };
 
-        // Spin up a thread to watch for changes to the brightness file for the
-        // device, and schedule an update if needed.
-        thread::spawn(move || {
-            // Split filepath into filename and parent directory
-            let (file_name, parent_dir) = {
-                let name = block_config
-                    .state_path
-                    .file_name()
-                    .expect("watson state file had no name")
-                    .to_owned();
+        // Split filepath into filename and parent directory
+        let file_name = block_config
+            .state_path
+            .file_name()
+            .block_error("watson", "watson state file had no name")?
+            .to_owned();
+        let mut parent_dir = block_config.state_path;
+        parent_dir.pop();
 
-                let mut s = block_config.state_path;
-                s.pop();
-                (name, s)
-            };
-            let mut notify = Inotify::init().expect("failed to start inotify");
-
-            // We have to watch the parent directory because watson never modifies the state file,
-            // but rather write to a temporary file, ensures its not corrupted, backups the
-            // previous state file and then renames the new state file. This means that we're
-            // always looking for `CREATE` events with the name of the state file.
-            notify
-                .add_watch(&parent_dir, WatchMask::CREATE)
-                .expect("failed to watch watson state file");
-
-            let mut buffer = [0; 1024];
-            loop {
-                let events = notify
-                    .read_events_blocking(&mut buffer)
-                    .expect("error while reading inotify events");
-
-                for event in events {
-                    match event.mask {
-                        EventMask::CREATE if event.name == Some(&file_name) => {
-                            tx_update_request
-                                .send(Task {
-                                    id: id.clone(),
-                                    update_time: Instant::now(),
-                                })
-                                .expect("unable to send task from watson watcher");
-                        }
-                        _ => {}
-                    }
-                }
-            }
-        });
+        // We have to watch the parent directory because watson never modifies the state file,
+        // but rather write to a temporary file, ensures its not corrupted, backups the
+        // previous state file and then renames the new state file. This means that we're
+        // always looking for `CREATE` events with the name of the state file.
+        let mut notify = Inotify::init().block_error("watson", "failed to start inotify")?;
+        notify
+            .add_watch(&parent_dir, WatchMask::CREATE)
+            .block_error("watson", "failed to watch watson state file")?;
+        reactor::request_updates(
+            notify,
+            id,
+            tx_update_request,
+            Duration::from_secs(0),
+            move |notify| {
+                let mut buffer = [0; 1024];
+                let events = notify.read_events(&mut buffer)?;
+                Ok(events.into_iter().any(|event| {
+                    event.mask == EventMask::CREATE && event.name == Some(file_name.as_os_str())
+                }))
+            },
+        )?;
 
         Ok(watson)
     }
@@ -1,9 +1,10 @@
+use chrono::DateTime;
 use crossbeam_channel::Sender;
 use serde_derive::Deserialize;
+use serde_json::Value;
 use std::collections::HashMap;
 use std::env;
-use std::process::Command;
-use std::time::Duration;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 use crate::blocks::Update;
 use crate::blocks::{Block, ConfigBlock};
@@ -12,7 +13,7 @@ use crate::de::deserialize_duration;
 use crate::errors::*;
 use crate::input::{I3BarEvent, MouseButton};
 use crate::scheduler::Task;
-use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::util::{http_get, pseudo_uuid, FormatTemplate, Location, Locator};
 use crate::widget::I3BarWidget;
 use crate::widgets::button::ButtonWidget;
 
@@ -32,6 +33,28 @@ pub enum WeatherService {
         place: Option<String>,
         units: OpenWeatherMapUnits,
     },
+    #[serde(rename = "openweathermap_onecall")]
+    OpenWeatherMapOneCall {
+        #[serde(default = "WeatherService::getenv_openweathermap_api_key")]
+        api_key: Option<String>,
+        #[serde(default)]
+        latitude: Option<f64>,
+        #[serde(default)]
+        longitude: Option<f64>,
+        #[serde(default)]
+        location: Option<String>,
+        units: OpenWeatherMapUnits,
+    },
+    MetNo {
+        #[serde(default)]
+        latitude: Option<f64>,
+        #[serde(default)]
+        longitude: Option<f64>,
+        #[serde(default)]
+        location: Option<String>,
+        #[serde(default)]
+        units: OpenWeatherMapUnits,
+    },
 }
 
 impl WeatherService {
@@ -44,6 +67,27 @@ impl WeatherService {
     fn getenv_openweathermap_place() -> Option<String> {
         env::var(OPENWEATHERMAP_PLACE_ENV).ok()
     }
+
+    /// Whether the service has a location to fall back to without `autolocate`.
+    fn has_location(&self) -> bool {
+        match *self {
+            WeatherService::OpenWeatherMap {
+                ref city_id,
+                ref place,
+                ..
+            } => city_id.is_some() || place.is_some(),
+            WeatherService::OpenWeatherMapOneCall {
+                latitude,
+                longitude,
+                ..
+            }
+            | WeatherService::MetNo {
+                latitude,
+                longitude,
+                ..
+            } => latitude.is_some() && longitude.is_some(),
+        }
+    }
 }
 
 #[derive(Copy, Clone, Debug, Deserialize)]
@@ -53,67 +97,376 @@ pub enum OpenWeatherMapUnits {
     Imperial,
 }
 
+impl Default for OpenWeatherMapUnits {
+    fn default() -> Self {
+        OpenWeatherMapUnits::Metric
+    }
+}
+
+/// The current weather, in the units of the block.
+#[derive(Debug, PartialEq)]
+struct Conditions {
+    /// Like the `main` of OpenWeatherMap, e.g. "Clear", "Clouds" or "Rain"
+    weather: String,
+    temp: f64,
+    humidity: f64,
+    wind_speed: f64,
+    wind_direction: Option<f64>,
+}
+
+/// The weather forecast for a unix time, in the units of the block.
+#[derive(Debug, PartialEq)]
+struct Forecast {
+    time: i64,
+    temp: f64,
+    weather: String,
+}
+
+fn malformed_json_error() -> Error {
+    BlockError("weather".to_string(), "Malformed JSON.".to_string())
+}
+
+/// Fetches a JSON document. Errors that are likely temporary, like being offline or the
+/// service being down, give nothing instead of an error.
+fn fetch(url: &str) -> Result<Option<Value>> {
+    let response = match http_get("weather", url, &[], Duration::from_secs(3)) {
+        Ok(response) => response,
+        Err(_) => return Ok(None),
+    };
+
+    // All 300-399 and >500 http codes should be considered as temporary error,
+    // and not result in block error, i.e. leave the output empty.
+    let status_code = response.status;
+    if (300..400).contains(&status_code) || status_code >= 500 || response.body.is_empty() {
+        return Ok(None);
+    }
+
+    let json: Value = serde_json::from_slice(&response.body)
+        .block_error("weather", "Failed to parse JSON response.")?;
+
+    // Try to convert an API error into a block error.
+    if status_code >= 400 {
+        return Err(BlockError(
+            "weather".to_string(),
+            match json.get("message").and_then(|v| v.as_str()) {
+                Some(message) => format!("API Error: {}", message),
+                None => format!("API Error: HTTP status {}", status_code),
+            },
+        ));
+    }
+    Ok(Some(json))
+}
+
+/// The current weather from `/data/2.5/weather` of OpenWeatherMap, with the location.
+fn parse_openweathermap(json: &Value) -> Result<(String, Conditions)> {
+    let raw_weather = json
+        .pointer("/weather/0/main")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .ok_or_else(malformed_json_error)?;
+
+    let raw_temp = json
+        .pointer("/main/temp")
+        .and_then(|v| v.as_f64())
+        .ok_or_else(malformed_json_error)?;
+
+    let raw_humidity = json
+        .pointer("/main/humidity")
+        .map_or(Some(0.0), |v| v.as_f64()) // provide default value 0.0
+        .ok_or_else(malformed_json_error)?;
+
+    let raw_wind_speed: f64 = json
+        .pointer("/wind/speed")
+        .map_or(Some(0.0), |v| v.as_f64()) // provide default value 0.0
+        .ok_or_else(malformed_json_error)?; // error when conversion to f64 fails
+
+    let raw_wind_direction: Option<f64> = json
+        .pointer("/wind/deg")
+        .map_or(Some(None), |v| v.as_f64().map(Some)) // provide default value None
+        .ok_or_else(malformed_json_error)?; // error when conversion to f64 fails
+
+    let raw_location = json
+        .pointer("/name")
+        .and_then(|v| v.as_str())
+        .map(|s| s.to_string())
+        .ok_or_else(malformed_json_error)?;
+
+    Ok((
+        raw_location,
+        Conditions {
+            weather: raw_weather,
+            temp: raw_temp,
+            humidity: raw_humidity,
+            wind_speed: raw_wind_speed,
+            wind_direction: raw_wind_direction,
+        },
+    ))
+}
+
+/// The 3-hourly forecast from `/data/2.5/forecast` of OpenWeatherMap.
+fn parse_openweathermap_forecast(json: &Value) -> Vec<Forecast> {
+    let empty = Vec::new();
+    json["list"]
+        .as_array()
+        .unwrap_or(&empty)
+        .iter()
+        .filter_map(|entry| {
+            Some(Forecast {
+                time: entry["dt"].as_i64()?,
+                temp: entry.pointer("/main/temp")?.as_f64()?,
+                weather: entry.pointer("/weather/0/main")?.as_str()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// The current weather and the hourly forecast from the One Call API of OpenWeatherMap.
+fn parse_onecall(json: &Value) -> Result<(Conditions, Vec<Forecast>)> {
+    let current = &json["current"];
+    let conditions = Conditions {
+        weather: current
+            .pointer("/weather/0/main")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string())
+            .ok_or_else(malformed_json_error)?,
+        temp: current["temp"].as_f64().ok_or_else(malformed_json_error)?,
+        humidity: current["humidity"].as_f64().unwrap_or(0.0),
+        wind_speed: current["wind_speed"].as_f64().unwrap_or(0.0),
+        wind_direction: current["wind_deg"].as_f64(),
+    };
+
+    let empty = Vec::new();
+    let forecast = json["hourly"]
+        .as_array()
+        .unwrap_or(&empty)
+        .iter()
+        .filter_map(|hour| {
+            Some(Forecast {
+                time: hour["dt"].as_i64()?,
+                temp: hour["temp"].as_f64()?,
+                weather: hour.pointer("/weather/0/main")?.as_str()?.to_string(),
+            })
+        })
+        .collect();
+    Ok((conditions, forecast))
+}
+
+/// Turns a symbol code of met.no, like "lightrainshowers_day", into a weather like the `main`
+/// of OpenWeatherMap.
+fn metno_weather(symbol: &str) -> &'static str {
+    if symbol.contains("thunder") {
+        "Thunderstorm"
+    } else if symbol.contains("snow") || symbol.contains("sleet") {
+        "Snow"
+    } else if symbol.contains("rain") {
+        "Rain"
+    } else if symbol.contains("fog") {
+        "Fog"
+    } else if symbol.contains("cloudy") {
+        "Clouds"
+    } else {
+        // clearsky and fair
+        "Clear"
+    }
+}
+
+/// The current weather and the forecast from the compact location forecast of met.no, which
+/// is always metric.
+fn parse_metno(json: &Value, units: OpenWeatherMapUnits) -> Result<(Conditions, Vec<Forecast>)> {
+    let convert_temp = |celsius: f64| match units {
+        OpenWeatherMapUnits::Metric => celsius,
+        OpenWeatherMapUnits::Imperial => celsius * 1.8 + 32.0,
+    };
+    let convert_speed = |meters_per_second: f64| match units {
+        OpenWeatherMapUnits::Metric => meters_per_second,
+        OpenWeatherMapUnits::Imperial => meters_per_second / 0.447,
+    };
+    let symbol = |entry: &Value| {
+        entry
+            .pointer("/data/next_1_hours/summary/symbol_code")
+            .or_else(|| entry.pointer("/data/next_6_hours/summary/symbol_code"))
+            .and_then(|v| v.as_str())
+            .map(metno_weather)
+    };
+
+    let timeseries = json
+        .pointer("/properties/timeseries")
+        .and_then(|v| v.as_array())
+        .ok_or_else(malformed_json_error)?;
+    let now = timeseries.first().ok_or_else(malformed_json_error)?;
+    let details = now
+        .pointer("/data/instant/details")
+        .ok_or_else(malformed_json_error)?;
+    let conditions = Conditions {
+        weather: symbol(now).unwrap_or("").to_string(),
+        temp: convert_temp(
+            details["air_temperature"]
+                .as_f64()
+                .ok_or_else(malformed_json_error)?,
+        ),
+        humidity: details["relative_humidity"].as_f64().unwrap_or(0.0),
+        wind_speed: convert_speed(details["wind_speed"].as_f64().unwrap_or(0.0)),
+        wind_direction: details["wind_from_direction"].as_f64(),
+    };
+
+    let forecast = timeseries
+        .iter()
+        .filter_map(|entry| {
+            Some(Forecast {
+                time: DateTime::parse_from_rfc3339(entry["time"].as_str()?)
+                    .ok()?
+                    .timestamp(),
+                temp: convert_temp(
+                    entry
+                        .pointer("/data/instant/details/air_temperature")?
+                        .as_f64()?,
+                ),
+                weather: symbol(entry)?.to_string(),
+            })
+        })
+        .collect();
+    Ok((conditions, forecast))
+}
+
+/// The highest and lowest temperature until `hours` from now, with the weather at that time.
+fn summarize_forecast(forecast: &[Forecast], now: i64, hours: u64) -> Option<(f64, f64, &str)> {
+    let end = now + hours as i64 * 3600;
+    let upcoming: Vec<&Forecast> = forecast.iter().filter(|f| f.time <= end).collect();
+    let last = upcoming.last()?;
+    let max = upcoming
+        .iter()
+        .map(|f| f.temp)
+        .fold(f64::NEG_INFINITY, f64::max);
+    let min = upcoming
+        .iter()
+        .map(|f| f.temp)
+        .fold(f64::INFINITY, f64::min);
+    Some((max, min, last.weather.as_str()))
+}
+
+fn weather_icon(weather: &str) -> &'static str {
+    match weather {
+        "Clear" => "weather_sun",
+        "Rain" | "Drizzle" => "weather_rain",
+        "Clouds" | "Fog" | "Mist" => "weather_clouds",
+        "Thunderstorm" => "weather_thunder",
+        "Snow" => "weather_snow",
+        _ => "weather_default",
+    }
+}
+
+// Convert wind direction in azimuth degrees to abbreviation names
+fn convert_wind_direction(direction_opt: Option<f64>) -> String {
+    match direction_opt {
+        Some(direction) => match direction.round() as i64 {
+            24..=68 => "NE".to_string(),
+            69..=113 => "E".to_string(),
+            114..=158 => "SE".to_string(),
+            159..=203 => "S".to_string(),
+            204..=248 => "SW".to_string(),
+            249..=293 => "W".to_string(),
+            294..=338 => "NW".to_string(),
+            _ => "N".to_string(),
+        },
+        None => "-".to_string(),
+    }
+}
+
+/// Computes the Australian Apparent Temperature (AT), using the metric formula found on
+/// Wikipedia. If using imperial units, we must first convert to metric.
+fn apparent_temperature(conditions: &Conditions, units: OpenWeatherMapUnits) -> f64 {
+    let metric = match units {
+        OpenWeatherMapUnits::Metric => true,
+        OpenWeatherMapUnits::Imperial => false,
+    };
+
+    let temp_celsius = if metric {
+        conditions.temp
+    } else {
+        // convert Fahrenheit to Celsius
+        (conditions.temp - 32.0) * 0.556
+    };
+
+    let exponent = 17.27 * temp_celsius / (237.7 + temp_celsius);
+    let water_vapor_pressure = conditions.humidity * 0.06105 * exponent.exp();
+
+    let metric_wind_speed = if metric {
+        conditions.wind_speed
+    } else {
+        // convert mph to m/s
+        conditions.wind_speed * 0.447
+    };
+
+    let metric_apparent_temp =
+        temp_celsius + 0.33 * water_vapor_pressure - 0.7 * metric_wind_speed - 4.0;
+    if metric {
+        metric_apparent_temp
+    } else {
+        1.8 * metric_apparent_temp + 32.0
+    }
+}
+
 pub struct Weather {
     id: String,
     weather: ButtonWidget,
-    format: String,
+    format: FormatTemplate,
     weather_keys: HashMap<String, String>,
     service: WeatherService,
     update_interval: Duration,
-    autolocate: bool,
-}
-
-fn malformed_json_error() -> Error {
-    BlockError("weather".to_string(), "Malformed JSON.".to_string())
+    /// Looks for the location with `autolocate`
+    locator: Option<Locator>,
+    forecast_hours: u64,
+    icons: HashMap<String, String>,
 }
 
 impl Weather {
+    /// The coordinates of the service, or of the location found by `autolocate`.
+    fn coordinates(
+        latitude: Option<f64>,
+        longitude: Option<f64>,
+        located: Option<&Location>,
+    ) -> Result<(f64, f64)> {
+        match (located, latitude, longitude) {
+            (Some(location), _, _) => Ok((location.latitude, location.longitude)),
+            (None, Some(latitude), Some(longitude)) => Ok((latitude, longitude)),
+            _ => Err(BlockError(
+                "weather".to_string(),
+                "Both 'service.latitude' and 'service.longitude' must be provided.".to_string(),
+            )),
+        }
+    }
+
     fn update_weather(&mut self) -> Result<()> {
-        match self.service {
+        // Without a location, the block falls back to the location of the service
+        let located = match self.locator {
+            Some(ref locator) => locator.location().unwrap_or(None),
+            None => None,
+        };
+        if self.locator.is_some() && located.is_none() && !self.service.has_location() {
+            // The thread updates the block once it found the location
+            self.weather_keys = HashMap::new();
+            return Ok(());
+        }
+        let located_city = located.as_ref().and_then(|l| l.city.clone());
+        let wants_forecast = self
+            .format
+            .placeholders()
+            .iter()
+            .any(|key| key.starts_with("{forecast_"));
+
+        let report = match self.service.clone() {
             WeatherService::OpenWeatherMap {
-                api_key: Some(ref api_key),
-                ref city_id,
-                ref place,
-                ref units,
+                api_key: Some(api_key),
+                city_id,
+                place,
+                units,
             } => {
-                // TODO: might be good to allow for different geolocation services to be used, similar to how we have `service` for the weather API
-                let geoip_city = if self.autolocate {
-                    let geoip_output = match Command::new("sh")
-                        .args(&["-c", "curl --max-time 3 --silent 'https://ipapi.co/json/'"])
-                        .output()
-                    {
-                        Ok(raw_output) => String::from_utf8(raw_output.stdout)
-                            .block_error("weather", "Failed to decode")?,
-                        Err(_) => {
-                            // We don't want the bar to crash if we can't reach the geoip service
-                            String::from("")
-                        }
-                    };
-
-                    if geoip_output.is_empty() {
-                        None
-                    } else {
-                        let geoip_json: serde_json::value::Value =
-                            serde_json::from_str(&geoip_output).block_error(
-                                "weather",
-                                "Failed to parse JSON response from geoip service.",
-                            )?;
-
-                        geoip_json
-                            .pointer("/city")
-                            .and_then(|v| v.as_str())
-                            .map(|s| s.to_string())
-                    }
-                } else {
-                    None
-                };
-
-                let location_query = if let Some(city) = geoip_city {
-                    format!("q={}", city)
-                } else if city_id.is_some() {
-                    format!("id={}", city_id.as_ref().unwrap())
-                } else if place.is_some() {
-                    format!("q={}", place.as_ref().unwrap())
+                let location_query = if let Some(ref location) = located {
+                    format!("lat={}&lon={}", location.latitude, location.longitude)
+                } else if let Some(city_id) = city_id {
+                    format!("id={}", city_id)
+                } else if let Some(place) = place {
+                    format!("q={}", place)
                 } else {
                     return Err(BlockError(
                         "weather".to_string(),
@@ -124,173 +477,150 @@ impl Weather {
                         ),
                     ));
                 };
-                let output = Command::new("sh")
-                    .args(&[
-                        "-c",
-                        // with these options curl will print http response body to stdout, http status code to stderr
-                        &format!(
-                            r#"curl -m 3 --silent \
-                                "https://api.openweathermap.org/data/2.5/weather?{location_query}&appid={api_key}&units={units}" \
-                                --write-out "%{{stderr}} %{{http_code}}""#,
-                            location_query = location_query,
-                            api_key = api_key,
-                            units = match *units {
-                                OpenWeatherMapUnits::Metric => "metric",
-                                OpenWeatherMapUnits::Imperial => "imperial",
-                            },
-                        ),
-                    ])
-                    .output()
-                    .block_error("weather", "Failed to execute curl.")
-                    .and_then(|raw_output| {
-                        let status_code = String::from_utf8(raw_output.stderr)
-                            .block_error("weather", "Invalid curl output")
-                            .and_then(|out|
-                                out.trim().parse::<i32>()
-                                    .block_error("weather", &format!("Unexpected curl output {}", out))
-                            )?;
-
-                        // All 300-399 and >500 http codes should be considered as temporary error,
-                        // and not result in block error, i.e. leave the output empty.
-                        match status_code {
-                            code if (code >= 300 && code < 400) || code >= 500 => Ok("".to_string()),
-                            _ => String::from_utf8(raw_output.stdout)
-                                .block_error("weather", "Received non-UTF8 characters in response."),
-                        }
-                    })?;
-
-                // Don't error out on empty responses e.g. for when not
-                // connected to the internet.
-                if output.is_empty() {
-                    self.weather.set_icon("weather_default");
-                    self.weather_keys = HashMap::new();
-                    return Ok(());
-                }
-
-                let json: serde_json::value::Value = serde_json::from_str(&output)
-                    .block_error("weather", "Failed to parse JSON response.")?;
-
-                // Try to convert an API error into a block error.
-                if let Some(val) = json.get("message") {
-                    return Err(BlockError(
-                        "weather".to_string(),
-                        format!("API Error: {}", val.as_str().unwrap()),
-                    ));
-                };
-                let raw_weather = json
-                    .pointer("/weather/0/main")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .ok_or_else(malformed_json_error)?;
-
-                let raw_temp = json
-                    .pointer("/main/temp")
-                    .and_then(|v| v.as_f64())
-                    .ok_or_else(malformed_json_error)?;
-
-                let raw_humidity = json
-                    .pointer("/main/humidity")
-                    .map_or(Some(0.0), |v| v.as_f64()) // provide default value 0.0
-                    .ok_or_else(malformed_json_error)?;
-
-                let raw_wind_speed: f64 = json
-                    .pointer("/wind/speed")
-                    .map_or(Some(0.0), |v| v.as_f64()) // provide default value 0.0
-                    .ok_or_else(malformed_json_error)?; // error when conversion to f64 fails
-
-                let raw_wind_direction: Option<f64> = json
-                    .pointer("/wind/deg")
-                    .map_or(Some(None), |v| v.as_f64().map(Some)) // provide default value None
-                    .ok_or_else(malformed_json_error)?; // error when conversion to f64 fails
-
-                let raw_location = json
-                    .pointer("/name")
-                    .and_then(|v| v.as_str())
-                    .map(|s| s.to_string())
-                    .ok_or_else(malformed_json_error)?;
-
-                // Compute the Australian Apparent Temperature (AT),
-                // using the metric formula found on Wikipedia.
-                // If using imperial units, we must first convert to metric.
-                let metric = match *units {
-                    OpenWeatherMapUnits::Metric => true,
-                    OpenWeatherMapUnits::Imperial => false,
-                };
-
-                let temp_celsius = if metric {
-                    raw_temp
-                } else {
-                    // convert Fahrenheit to Celsius
-                    (raw_temp - 32.0) * 0.556
-                };
-
-                let exponent = 17.27 * temp_celsius / (237.7 + temp_celsius);
-                let water_vapor_pressure = raw_humidity * 0.06105 * exponent.exp();
-
-                let metric_wind_speed = if metric {
-                    raw_wind_speed
-                } else {
-                    // convert mph to m/s
-                    raw_wind_speed * 0.447
-                };
-
-                let metric_apparent_temp =
-                    temp_celsius + 0.33 * water_vapor_pressure - 0.7 * metric_wind_speed - 4.0;
-                let apparent_temp = if metric {
-                    metric_apparent_temp
+                let query = format!(
+                    "{location_query}&appid={api_key}&units={units}",
+                    location_query = location_query,
+                    api_key = api_key,
+                    units = match units {
+                        OpenWeatherMapUnits::Metric => "metric",
+                        OpenWeatherMapUnits::Imperial => "imperial",
+                    },
+                );
+
+                let json = fetch(&format!(
+                    "https://api.openweathermap.org/data/2.5/weather?{}",
+                    query
+                ))?;
+                // The forecast is a second request, so it is only fetched if it is shown
+                let forecast = if wants_forecast {
+                    fetch(&format!(
+                        "https://api.openweathermap.org/data/2.5/forecast?{}",
+                        query
+                    ))?
                 } else {
-                    1.8 * metric_apparent_temp + 32.0
+                    None
                 };
-
-                // Convert wind direction in azimuth degrees to abbreviation names
-                fn convert_wind_direction(direction_opt: Option<f64>) -> String {
-                    match direction_opt {
-                        Some(direction) => match direction.round() as i64 {
-                            24..=68 => "NE".to_string(),
-                            69..=113 => "E".to_string(),
-                            114..=158 => "SE".to_string(),
-                            159..=203 => "S".to_string(),
-                            204..=248 => "SW".to_string(),
-                            249..=293 => "W".to_string(),
-                            294..=338 => "NW".to_string(),
-                            _ => "N".to_string(),
-                        },
-                        None => "-".to_string(),
+                match json {
+                    Some(json) => {
+                        let (location, conditions) = parse_openweathermap(&json)?;
+                        let forecast = forecast
+                            .as_ref()
+                            .map(parse_openweathermap_forecast)
+                            .unwrap_or_default();
+                        Some((location, conditions, forecast, units))
                     }
+                    None => None,
                 }
-
-                self.weather.set_icon(match raw_weather.as_str() {
-                    "Clear" => "weather_sun",
-                    "Rain" | "Drizzle" => "weather_rain",
-                    "Clouds" | "Fog" | "Mist" => "weather_clouds",
-                    "Thunderstorm" => "weather_thunder",
-                    "Snow" => "weather_snow",
-                    _ => "weather_default",
-                });
-
-                self.weather_keys = map_to_owned!("{weather}" => raw_weather,
-                                  "{temp}" => format!("{:.0}", raw_temp),
-                                  "{humidity}" => format!("{:.0}", raw_humidity),
-                                  "{apparent}" => format!("{:.0}",apparent_temp),
-                                  "{wind}" => format!("{:.1}", raw_wind_speed),
-                                  "{direction}" => convert_wind_direction(raw_wind_direction),
-                                  "{location}" => raw_location);
-                Ok(())
             }
-            WeatherService::OpenWeatherMap { ref api_key, .. } => {
-                if api_key.is_none() {
-                    Err(BlockError(
-                        "weather".to_string(),
-                        format!(
-                            "Missing member 'service.api_key'. Add the member or configure with the environment variable {}",
-                            OPENWEATHERMAP_API_KEY_ENV.to_string()
-                        ),
-                    ))
-                } else {
-                    Ok(())
+            WeatherService::OpenWeatherMapOneCall {
+                api_key: Some(api_key),
+                latitude,
+                longitude,
+                location,
+                units,
+            } => {
+                let (latitude, longitude) =
+                    Weather::coordinates(latitude, longitude, located.as_ref())?;
+                let json = fetch(&format!(
+                    "https://api.openweathermap.org/data/3.0/onecall?lat={}&lon={}&appid={}&units={}&exclude=minutely,daily,alerts",
+                    latitude,
+                    longitude,
+                    api_key,
+                    match units {
+                        OpenWeatherMapUnits::Metric => "metric",
+                        OpenWeatherMapUnits::Imperial => "imperial",
+                    },
+                ))?;
+                match json {
+                    Some(json) => {
+                        let (conditions, forecast) = parse_onecall(&json)?;
+                        let location = location
+                            .or_else(|| located_city.clone())
+                            .unwrap_or_else(|| format!("{:.2}, {:.2}", latitude, longitude));
+                        Some((location, conditions, forecast, units))
+                    }
+                    None => None,
                 }
             }
-        }
+            WeatherService::MetNo {
+                latitude,
+                longitude,
+                location,
+                units,
+            } => {
+                let (latitude, longitude) =
+                    Weather::coordinates(latitude, longitude, located.as_ref())?;
+                // met.no asks for at most four decimals, which also helps its caching
+                let json = fetch(&format!(
+                    "https://api.met.no/weatherapi/locationforecast/2.0/compact?lat={:.4}&lon={:.4}",
+                    latitude, longitude,
+                ))?;
+                match json {
+                    Some(json) => {
+                        let (conditions, forecast) = parse_metno(&json, units)?;
+                        let location = location
+                            .or_else(|| located_city.clone())
+                            .unwrap_or_else(|| format!("{:.2}, {:.2}", latitude, longitude));
+                        Some((location, conditions, forecast, units))
+                    }
+                    None => None,
+                }
+            }
+            WeatherService::OpenWeatherMap { .. }
+            | WeatherService::OpenWeatherMapOneCall { .. } => {
+                return Err(BlockError(
+                    "weather".to_string(),
+                    format!(
+                        "Missing member 'service.api_key'. Add the member or configure with the environment variable {}",
+                        OPENWEATHERMAP_API_KEY_ENV.to_string()
+                    ),
+                ));
+            }
+        };
+
+        // Don't error out on empty responses e.g. for when not
+        // connected to the internet.
+        let (location, conditions, forecast, units) = match report {
+            Some(report) => report,
+            None => {
+                self.weather.set_icon("weather_default");
+                self.weather_keys = HashMap::new();
+                return Ok(());
+            }
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let (forecast_max, forecast_min, forecast_symbol) =
+            match summarize_forecast(&forecast, now, self.forecast_hours) {
+                Some((max, min, weather)) => (
+                    format!("{:.0}", max),
+                    format!("{:.0}", min),
+                    self.icons
+                        .get(weather_icon(weather))
+                        .cloned()
+                        .unwrap_or_else(|| "".to_string())
+                        .trim()
+                        .to_string(),
+                ),
+                None => ("-".to_string(), "-".to_string(), "-".to_string()),
+            };
+
+        self.weather.set_icon(weather_icon(&conditions.weather));
+
+        self.weather_keys = map_to_owned!("{weather}" => conditions.weather.clone(),
+                          "{temp}" => format!("{:.0}", conditions.temp),
+                          "{humidity}" => format!("{:.0}", conditions.humidity),
+                          "{apparent}" => format!("{:.0}", apparent_temperature(&conditions, units)),
+                          "{wind}" => format!("{:.1}", conditions.wind_speed),
+                          "{direction}" => convert_wind_direction(conditions.wind_direction),
+                          "{location}" => location,
+                          "{forecast_max}" => forecast_max,
+                          "{forecast_min}" => forecast_min,
+                          "{forecast_symbol}" => forecast_symbol);
+        Ok(())
     }
 }
 
@@ -307,6 +637,15 @@ pub struct WeatherConfig {
     pub service: WeatherService,
     #[serde(default = "WeatherConfig::default_autolocate")]
     pub autolocate: bool,
+    /// How often `autolocate` looks for the location again, in seconds
+    #[serde(
+        default = "WeatherConfig::default_autolocate_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub autolocate_interval: Duration,
+    /// Hours ahead that the forecast placeholders cover
+    #[serde(default = "WeatherConfig::default_forecast_hours")]
+    pub forecast_hours: u64,
 }
 
 impl WeatherConfig {
@@ -321,6 +660,14 @@ impl WeatherConfig {
     fn default_autolocate() -> bool {
         false
     }
+
+    fn default_autolocate_interval() -> Duration {
+        Duration::from_secs(1800)
+    }
+
+    fn default_forecast_hours() -> u64 {
+        12
+    }
 }
 
 impl ConfigBlock for Weather {
@@ -329,17 +676,32 @@ impl ConfigBlock for Weather {
     fn new(
         block_config: Self::Config,
         config: Config,
-        _tx_update_request: Sender<Task>,
+        tx_update_request: Sender<Task>,
     ) -> Result<Self> {
         let id = pseudo_uuid();
+        let locator = if block_config.autolocate {
+            Some(Locator::start(
+                "weather",
+                id.clone(),
+                tx_update_request,
+                block_config.autolocate_interval,
+                true,
+            )?)
+        } else {
+            None
+        };
+
         Ok(Weather {
             id: id.clone(),
+            icons: config.icons.clone(),
             weather: ButtonWidget::new(config, &id),
-            format: block_config.format,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("weather", "Invalid format specified")?,
             weather_keys: HashMap::new(),
             service: block_config.service,
             update_interval: block_config.interval,
-            autolocate: block_config.autolocate,
+            locator,
+            forecast_hours: block_config.forecast_hours,
         })
     }
 }
@@ -352,8 +714,8 @@ impl Block for Weather {
         if self.weather_keys.keys().len() == 0 {
             self.weather.set_text("×".to_string());
         } else {
-            let fmt = FormatTemplate::from_string(&self.format)?;
-            self.weather.set_text(fmt.render(&self.weather_keys));
+            self.weather
+                .set_text(self.format.render(&self.weather_keys));
         }
         Ok(Some(self.update_interval.into()))
     }
@@ -375,3 +737,107 @@ impl Block for Weather {
         &self.id
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        metno_weather, parse_metno, parse_onecall, summarize_forecast, Forecast,
+        OpenWeatherMapUnits,
+    };
+
+    #[test]
+    fn test_metno_weather() {
+        assert_eq!(metno_weather("clearsky_day"), "Clear");
+        assert_eq!(metno_weather("fair_night"), "Clear");
+        assert_eq!(metno_weather("partlycloudy_day"), "Clouds");
+        assert_eq!(metno_weather("lightrainshowers_day"), "Rain");
+        assert_eq!(metno_weather("heavysleet"), "Snow");
+        assert_eq!(metno_weather("rainandthunder"), "Thunderstorm");
+        assert_eq!(metno_weather("fog"), "Fog");
+    }
+
+    #[test]
+    fn test_parse_metno() {
+        let json = serde_json::json!({"properties": {"timeseries": [
+            {
+                "time": "2020-06-01T12:00:00Z",
+                "data": {
+                    "instant": {"details": {
+                        "air_temperature": 20.0,
+                        "relative_humidity": 55.0,
+                        "wind_speed": 4.47,
+                        "wind_from_direction": 90.0
+                    }},
+                    "next_1_hours": {"summary": {"symbol_code": "partlycloudy_day"}}
+                }
+            },
+            {
+                "time": "2020-06-01T13:00:00Z",
+                "data": {
+                    "instant": {"details": {"air_temperature": 25.0}},
+                    "next_6_hours": {"summary": {"symbol_code": "rain"}}
+                }
+            }
+        ]}});
+        let (conditions, forecast) = parse_metno(&json, OpenWeatherMapUnits::Imperial).unwrap();
+        assert_eq!(conditions.weather, "Clouds");
+        assert!((conditions.temp - 68.0).abs() < 0.01);
+        assert!((conditions.wind_speed - 10.0).abs() < 0.01);
+        assert_eq!(conditions.wind_direction, Some(90.0));
+        assert_eq!(
+            forecast[1],
+            Forecast {
+                time: 1591016400,
+                temp: 77.0,
+                weather: "Rain".to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_onecall() {
+        let json = serde_json::json!({
+            "current": {
+                "temp": 18.3,
+                "humidity": 70,
+                "wind_speed": 3.1,
+                "weather": [{"main": "Clouds"}]
+            },
+            "hourly": [
+                {"dt": 1591016400, "temp": 18.0, "weather": [{"main": "Clouds"}]},
+                {"dt": 1591020000, "temp": 19.5, "weather": [{"main": "Rain"}]}
+            ]
+        });
+        let (conditions, forecast) = parse_onecall(&json).unwrap();
+        assert_eq!(conditions.weather, "Clouds");
+        assert_eq!(conditions.humidity, 70.0);
+        assert_eq!(conditions.wind_direction, None);
+        assert_eq!(forecast.len(), 2);
+        assert_eq!(forecast[1].weather, "Rain");
+    }
+
+    #[test]
+    fn test_summarize_forecast() {
+        let forecast: Vec<Forecast> = [
+            (0, 10.0, "Clear"),
+            (3600, 14.0, "Clouds"),
+            (7200, 8.0, "Rain"),
+        ]
+        .iter()
+        .map(|&(time, temp, weather)| Forecast {
+            time,
+            temp,
+            weather: weather.to_string(),
+        })
+        .collect();
+        assert_eq!(
+            summarize_forecast(&forecast, 0, 1),
+            Some((14.0, 10.0, "Clouds"))
+        );
+        assert_eq!(
+            summarize_forecast(&forecast, 0, 12),
+            Some((14.0, 8.0, "Rain"))
+        );
+        assert_eq!(summarize_forecast(&forecast, -7200, 1), None);
+    }
+}
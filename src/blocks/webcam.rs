@@ -0,0 +1,195 @@
+//! A block that shows when a camera is in use, and by which programs.
+
+use std::fs::{read_dir, read_link};
+use std::path::Path;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use inotify::{Inotify, WatchMask};
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::reactor;
+use crate::scheduler::Task;
+use crate::util::{pseudo_uuid, read_file, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+/// Whether a file is a V4L2 device, like `/dev/video0`.
+fn is_video_device(name: &str) -> bool {
+    name.len() > 5 && name.starts_with("video") && name[5..].chars().all(|c| c.is_ascii_digit())
+}
+
+/// The names of the processes that have a video device open. Only the processes of the user
+/// can be seen, which includes browsers and video call clients.
+fn camera_users() -> Vec<String> {
+    let own_pid = std::process::id().to_string();
+    let mut users = Vec::new();
+    let processes = match read_dir("/proc") {
+        Ok(processes) => processes,
+        Err(_) => return users,
+    };
+    for process in processes.flatten() {
+        let pid = process.file_name().to_string_lossy().into_owned();
+        if pid == own_pid || !pid.chars().all(|c| c.is_ascii_digit()) {
+            continue;
+        }
+        let fds = match read_dir(process.path().join("fd")) {
+            Ok(fds) => fds,
+            Err(_) => continue,
+        };
+        let uses_camera = fds.flatten().any(|fd| {
+            read_link(fd.path()).map_or(false, |target| {
+                target.parent() == Some(Path::new("/dev"))
+                    && target
+                        .file_name()
+                        .map_or(false, |name| is_video_device(&name.to_string_lossy()))
+            })
+        });
+        if uses_camera {
+            if let Ok(name) = read_file("webcam", &process.path().join("comm")) {
+                if !users.contains(&name) {
+                    users.push(name);
+                }
+            }
+        }
+    }
+    users.sort();
+    users
+}
+
+pub struct Webcam {
+    id: String,
+    output: TextWidget,
+    update_interval: Duration,
+    format: FormatTemplate,
+    hide_unused: bool,
+    in_use: bool,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WebcamConfig {
+    /// Update interval in seconds, in case an open is missed
+    #[serde(
+        default = "WebcamConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format string while a camera is in use
+    #[serde(default = "WebcamConfig::default_format")]
+    pub format: String,
+
+    /// Whether to hide the block while no camera is in use
+    #[serde(default = "WebcamConfig::default_hide_unused")]
+    pub hide_unused: bool,
+}
+
+impl WebcamConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(30)
+    }
+
+    fn default_format() -> String {
+        "{processes}".to_owned()
+    }
+
+    fn default_hide_unused() -> bool {
+        true
+    }
+}
+
+impl ConfigBlock for Webcam {
+    type Config = WebcamConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+
+        // Watching /dev reports the devices that are opened or closed, including cameras
+        // that are plugged in later.
+        let mut notify = Inotify::init().block_error("webcam", "Failed to start inotify")?;
+        notify
+            .add_watch(
+                "/dev",
+                WatchMask::OPEN | WatchMask::CLOSE_WRITE | WatchMask::CLOSE_NOWRITE,
+            )
+            .block_error("webcam", "Failed to watch /dev")?;
+        // Programs open the device several times while setting it up
+        reactor::request_updates(
+            notify,
+            id.clone(),
+            send,
+            Duration::from_millis(250),
+            |notify| {
+                let mut buffer = [0; 1024];
+                let events = notify.read_events(&mut buffer)?;
+                Ok(events.into_iter().any(|event| {
+                    event
+                        .name
+                        .map_or(false, |name| is_video_device(&name.to_string_lossy()))
+                }))
+            },
+        )?;
+
+        Ok(Webcam {
+            output: TextWidget::new(config).with_icon("webcam"),
+            id,
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("webcam", "Invalid format specified")?,
+            hide_unused: block_config.hide_unused,
+            in_use: false,
+        })
+    }
+}
+
+impl Block for Webcam {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let users = camera_users();
+        self.in_use = !users.is_empty();
+
+        let values = map!(
+            "{processes}" => users.join(", "),
+            "{count}" => users.len().to_string()
+        );
+        if self.in_use {
+            self.output
+                .set_text(self.format.render_static_str(&values)?);
+            self.output.set_state(State::Critical);
+        } else {
+            self.output.set_text(String::new());
+            self.output.set_state(State::Idle);
+        }
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if !self.in_use && self.hide_unused {
+            return Vec::new();
+        }
+        vec![&self.output]
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::is_video_device;
+
+    #[test]
+    fn test_is_video_device() {
+        assert!(is_video_device("video0"));
+        assert!(is_video_device("video12"));
+        assert!(!is_video_device("video"));
+        assert!(!is_video_device("vhost-net"));
+        assert!(!is_video_device("video0.lock"));
+    }
+}
@@ -0,0 +1,786 @@
+//! A block for the buffers with highlights and private messages in WeeChat, which it watches
+//! through the relay of WeeChat.
+
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{Shutdown, TcpStream};
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Receiver, RecvTimeoutError, Sender};
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::scheduler::Task;
+use crate::subprocess::{ChildHandle, OwnedChild};
+use crate::util::{expand_home, pseudo_uuid, tls_client_args, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+/// How long to wait for an answer of the relay.
+const TIMEOUT: Duration = Duration::from_secs(30);
+/// How long to wait before connecting again after an error.
+const RETRY_DELAY: Duration = Duration::from_secs(30);
+/// How often new lines may ask for the hotlist, while a busy channel talks.
+const MIN_DELAY: Duration = Duration::from_secs(1);
+/// The index of private messages and of highlights in the counts of the hotlist.
+const PRIVATE: usize = 2;
+const HIGHLIGHT: usize = 3;
+
+/// An object of the binary protocol of the relay.
+#[derive(Clone, Debug, PartialEq)]
+enum Object {
+    Chr(u8),
+    Int(i32),
+    Lon(i64),
+    Str(Option<String>),
+    Buf(Option<Vec<u8>>),
+    Ptr(String),
+    Tim(i64),
+    Htb(Vec<(Object, Object)>),
+    Hda(Vec<HashMap<String, Object>>),
+    Inf(Option<String>, Option<String>),
+    Inl(Vec<HashMap<String, Object>>),
+    Arr(Vec<Object>),
+}
+
+impl Object {
+    fn as_int(&self) -> Option<i64> {
+        match *self {
+            Object::Chr(value) => Some(value.into()),
+            Object::Int(value) => Some(value.into()),
+            Object::Lon(value) | Object::Tim(value) => Some(value),
+            _ => None,
+        }
+    }
+}
+
+/// A message of the relay, with the id of the command that it answers, or of an event.
+#[derive(Clone, Debug, PartialEq)]
+struct Message {
+    id: Option<String>,
+    objects: Vec<Object>,
+}
+
+/// Reads the objects of a message.
+struct Parser<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Parser<'a> {
+    fn take(&mut self, len: usize) -> std::result::Result<&'a [u8], String> {
+        if len > self.data.len() {
+            return Err("the message of the relay is truncated".to_string());
+        }
+        let (taken, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(taken)
+    }
+
+    fn int(&mut self) -> std::result::Result<i32, String> {
+        let bytes = self.take(4)?;
+        Ok(i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn buffer(&mut self) -> std::result::Result<Option<Vec<u8>>, String> {
+        match self.int()? {
+            len if len < 0 => Ok(None),
+            len => Ok(Some(self.take(len as usize)?.to_vec())),
+        }
+    }
+
+    fn string(&mut self) -> std::result::Result<Option<String>, String> {
+        Ok(self
+            .buffer()?
+            .map(|bytes| String::from_utf8_lossy(&bytes).into_owned()))
+    }
+
+    /// Reads the strings with a length of one byte, which hold numbers and pointers.
+    fn short_string(&mut self) -> std::result::Result<String, String> {
+        let len = self.take(1)?[0] as usize;
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn object_type(&mut self) -> std::result::Result<String, String> {
+        Ok(String::from_utf8_lossy(self.take(3)?).into_owned())
+    }
+
+    fn number(&mut self) -> std::result::Result<i64, String> {
+        let number = self.short_string()?;
+        number
+            .parse()
+            .map_err(|_| format!("invalid number in the message of the relay: {}", number))
+    }
+
+    fn count(&mut self) -> std::result::Result<usize, String> {
+        Ok(self.int()?.max(0) as usize)
+    }
+
+    fn object(&mut self, object_type: &str) -> std::result::Result<Object, String> {
+        Ok(match object_type {
+            "chr" => Object::Chr(self.take(1)?[0]),
+            "int" => Object::Int(self.int()?),
+            "lon" => Object::Lon(self.number()?),
+            "str" => Object::Str(self.string()?),
+            "buf" => Object::Buf(self.buffer()?),
+            "ptr" => Object::Ptr(self.short_string()?),
+            "tim" => Object::Tim(self.number()?),
+            "htb" => {
+                let key_type = self.object_type()?;
+                let value_type = self.object_type()?;
+                let mut items = Vec::new();
+                for _ in 0..self.count()? {
+                    items.push((self.object(&key_type)?, self.object(&value_type)?));
+                }
+                Object::Htb(items)
+            }
+            "hda" => {
+                let path = self.string()?.unwrap_or_default();
+                let keys: Vec<(String, String)> = self
+                    .string()?
+                    .unwrap_or_default()
+                    .split(',')
+                    .filter_map(|key| {
+                        let mut parts = key.splitn(2, ':');
+                        Some((parts.next()?.to_string(), parts.next()?.to_string()))
+                    })
+                    .collect();
+                let mut items = Vec::new();
+                for _ in 0..self.count()? {
+                    // Each item starts with the pointers of its path
+                    for _ in path.split('/') {
+                        self.short_string()?;
+                    }
+                    let mut item = HashMap::new();
+                    for (name, key_type) in &keys {
+                        item.insert(name.clone(), self.object(key_type)?);
+                    }
+                    items.push(item);
+                }
+                Object::Hda(items)
+            }
+            "inf" => Object::Inf(self.string()?, self.string()?),
+            "inl" => {
+                self.string()?;
+                let mut items = Vec::new();
+                for _ in 0..self.count()? {
+                    let mut item = HashMap::new();
+                    for _ in 0..self.count()? {
+                        let name = self.string()?.unwrap_or_default();
+                        let value_type = self.object_type()?;
+                        item.insert(name, self.object(&value_type)?);
+                    }
+                    items.push(item);
+                }
+                Object::Inl(items)
+            }
+            "arr" => {
+                let value_type = self.object_type()?;
+                let mut values = Vec::new();
+                for _ in 0..self.count()? {
+                    values.push(self.object(&value_type)?);
+                }
+                Object::Arr(values)
+            }
+            _ => {
+                return Err(format!(
+                    "unknown object type {:?} from the relay",
+                    object_type
+                ))
+            }
+        })
+    }
+}
+
+/// Parses a message of the relay, without the length in front of it.
+fn parse_message(data: &[u8]) -> std::result::Result<Message, String> {
+    let mut parser = Parser { data };
+    if parser.take(1)?[0] != 0 {
+        return Err("the relay compressed the message".to_string());
+    }
+    let id = parser.string()?;
+    let mut objects = Vec::new();
+    while !parser.data.is_empty() {
+        let object_type = parser.object_type()?;
+        objects.push(parser.object(&object_type)?);
+    }
+    Ok(Message { id, objects })
+}
+
+/// The number of buffers in the hotlist with highlights, with private messages, and with either.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+struct Hotlist {
+    highlights: u32,
+    private: u32,
+    buffers: u32,
+}
+
+/// Counts the buffers in the answer to `hdata hotlist:gui_hotlist(*)`.
+fn count_hotlist(message: &Message) -> std::result::Result<Hotlist, String> {
+    let items = match message.objects.first() {
+        Some(Object::Hda(items)) => items,
+        _ => return Err("unexpected answer of the relay".to_string()),
+    };
+    let mut hotlist = Hotlist::default();
+    for item in items {
+        let (private, highlight) = match item.get("count") {
+            Some(Object::Arr(counts)) if counts.len() > HIGHLIGHT => (
+                counts[PRIVATE].as_int().unwrap_or(0) > 0,
+                counts[HIGHLIGHT].as_int().unwrap_or(0) > 0,
+            ),
+            // Without the counts, the priority is the most important kind of message
+            _ => {
+                let priority = item.get("priority").and_then(Object::as_int);
+                (
+                    priority == Some(PRIVATE as i64),
+                    priority == Some(HIGHLIGHT as i64),
+                )
+            }
+        };
+        hotlist.private += private as u32;
+        hotlist.highlights += highlight as u32;
+        hotlist.buffers += (private || highlight) as u32;
+    }
+    Ok(hotlist)
+}
+
+/// The `init` command, which escapes the commas that separate its options.
+fn init_command(password: Option<&str>) -> String {
+    match password {
+        Some(password) => format!(
+            "init password={},compression=off",
+            password.replace('\\', "\\\\").replace(',', "\\,")
+        ),
+        None => "init compression=off".to_string(),
+    }
+}
+
+/// The buffers with highlights and private messages, or why they could not be counted, or
+/// `None` while connecting.
+type Status = Arc<Mutex<Option<std::result::Result<Hotlist, String>>>>;
+
+/// The settings of the connection, which the thread of the block owns.
+#[derive(Clone)]
+struct Relay {
+    host: String,
+    port: u16,
+    ssl: bool,
+    ssl_verify: bool,
+    password_command: Option<String>,
+    password_file: Option<String>,
+    interval: Duration,
+}
+
+impl Relay {
+    fn password(&self) -> std::result::Result<Option<String>, String> {
+        if let Some(ref command) = self.password_command {
+            let output = Command::new("sh")
+                .args(&["-c", command])
+                .output()
+                .map_err(|e| format!("failed to run the password command: {}", e))?;
+            if !output.status.success() {
+                return Err("the password command failed".to_string());
+            }
+            return Ok(Some(
+                String::from_utf8_lossy(&output.stdout).trim().to_string(),
+            ));
+        }
+        let path = match self.password_file {
+            Some(ref path) => expand_home(path),
+            None => return Ok(None),
+        };
+        fs::read_to_string(&path)
+            .map(|password| Some(password.trim().to_string()))
+            .map_err(|e| format!("failed to read the password from {}: {}", path.display(), e))
+    }
+}
+
+/// The TCP stream of the current connection, which is shut down with the block.
+#[derive(Default)]
+struct SharedStream {
+    closed: bool,
+    stream: Option<TcpStream>,
+}
+
+/// What the thread connects through, so that the block can close it when it is dropped.
+#[derive(Clone)]
+struct Link {
+    child: ChildHandle,
+    stream: Arc<Mutex<SharedStream>>,
+}
+
+/// A connection to the relay, over TCP or over TLS through `openssl s_client`.
+struct Connection {
+    writer: Box<dyn Write + Send>,
+    messages: Receiver<std::result::Result<Message, String>>,
+    stream: Option<TcpStream>,
+    link: Link,
+}
+
+impl Connection {
+    fn open(relay: &Relay, link: &Link) -> std::result::Result<Connection, String> {
+        let mut stream = None;
+        let (reader, writer): (Box<dyn Read + Send>, Box<dyn Write + Send>) = if relay.ssl {
+            let pipes = link
+                .child
+                .spawn(
+                    Command::new("openssl")
+                        .args(&tls_client_args(&relay.host, relay.port, relay.ssl_verify))
+                        .stdin(Stdio::piped())
+                        .stdout(Stdio::piped())
+                        .stderr(Stdio::null()),
+                )
+                .map_err(|e| format!("failed to run openssl: {}", e))?
+                .ok_or("the block is gone")?;
+            let stdin = pipes.stdin.ok_or("failed to open the connection")?;
+            let stdout = pipes.stdout.ok_or("failed to open the connection")?;
+            (Box::new(stdout), Box::new(stdin))
+        } else {
+            let tcp = TcpStream::connect((relay.host.as_str(), relay.port))
+                .map_err(|e| format!("failed to connect to the relay: {}", e))?;
+            let reader = tcp.try_clone().map_err(|e| e.to_string())?;
+            let writer = tcp.try_clone().map_err(|e| e.to_string())?;
+            let mut shared = link
+                .stream
+                .lock()
+                .map_err(|_| "lock has been poisoned".to_string())?;
+            if shared.closed {
+                return Err("the block is gone".to_string());
+            }
+            shared.stream = Some(tcp.try_clone().map_err(|e| e.to_string())?);
+            stream = Some(tcp);
+            (Box::new(reader), Box::new(writer))
+        };
+
+        // The messages are read in a thread, so that waiting for them can time out
+        let (sender, messages) = crossbeam_channel::unbounded();
+        thread::Builder::new()
+            .name("weechat reader".into())
+            .spawn(move || {
+                let mut reader = reader;
+                loop {
+                    let mut len = [0; 4];
+                    if reader.read_exact(&mut len).is_err() {
+                        return;
+                    }
+                    let len = u32::from_be_bytes(len) as usize;
+                    let mut data = vec![0; len.saturating_sub(4)];
+                    let message = match reader.read_exact(&mut data) {
+                        Ok(()) => parse_message(&data),
+                        Err(_) => return,
+                    };
+                    let failed = message.is_err();
+                    if sender.send(message).is_err() || failed {
+                        return;
+                    }
+                }
+            })
+            .map_err(|e| format!("failed to start the reading thread: {}", e))?;
+
+        Ok(Connection {
+            writer,
+            messages,
+            stream,
+            link: link.clone(),
+        })
+    }
+
+    fn command(&mut self, command: &str) -> std::result::Result<(), String> {
+        writeln!(self.writer, "{}", command)
+            .and_then(|_| self.writer.flush())
+            .map_err(|_| "the connection was closed".to_string())
+    }
+}
+
+impl Drop for Connection {
+    fn drop(&mut self) {
+        if let Some(ref stream) = self.stream {
+            let _ = stream.shutdown(Shutdown::Both);
+            if let Ok(mut shared) = self.link.stream.lock() {
+                shared.stream = None;
+            }
+        }
+        self.link.child.kill();
+    }
+}
+
+/// Counts the buffers in the hotlist until the connection fails. The relay does not tell when
+/// the hotlist changes, so it is asked for at each interval and after new lines. `notify` is
+/// called with each new count, and returns false when the block is gone.
+fn watch(
+    relay: &Relay,
+    link: &Link,
+    notify: &mut dyn FnMut(std::result::Result<Hotlist, String>) -> bool,
+) -> std::result::Result<(), String> {
+    let mut connection = Connection::open(relay, link)?;
+    // The relay does not answer `init`, it closes the connection when the password is wrong
+    connection.command(&init_command(relay.password()?.as_deref()))?;
+    connection.command("sync * buffer")?;
+
+    let mut last = None;
+    let mut asked: Option<Instant> = None;
+    let mut next = Instant::now();
+    loop {
+        if asked.is_none() && Instant::now() >= next {
+            connection.command("(hotlist) hdata hotlist:gui_hotlist(*) priority,count")?;
+            asked = Some(Instant::now());
+            next = Instant::now() + relay.interval;
+        }
+        let wait = match asked {
+            Some(asked) => (asked + TIMEOUT).saturating_duration_since(Instant::now()),
+            None => next.saturating_duration_since(Instant::now()),
+        };
+        match connection.messages.recv_timeout(wait) {
+            Ok(Ok(message)) => match message.id.as_deref() {
+                Some("hotlist") => {
+                    asked = None;
+                    let hotlist = count_hotlist(&message)?;
+                    if last != Some(hotlist) {
+                        last = Some(hotlist);
+                        if !notify(Ok(hotlist)) {
+                            return Ok(());
+                        }
+                    }
+                }
+                Some("_buffer_line_added") => {
+                    next = next.min(Instant::now() + MIN_DELAY);
+                }
+                _ => {}
+            },
+            Ok(Err(error)) => return Err(error),
+            Err(RecvTimeoutError::Timeout) if asked.is_some() => {
+                return Err("the relay did not answer".to_string())
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => {
+                return Err(if last.is_none() {
+                    "the relay closed the connection, is the password right?".to_string()
+                } else {
+                    "the connection was closed".to_string()
+                })
+            }
+        }
+    }
+}
+
+pub struct Weechat {
+    id: String,
+    text: TextWidget,
+    format: FormatTemplate,
+    hide_when_zero: bool,
+    status: Status,
+    hidden: bool,
+    /// The connection of the thread, which is closed with the block
+    #[allow(dead_code)]
+    process: OwnedChild,
+    stream: Arc<Mutex<SharedStream>>,
+}
+
+impl Drop for Weechat {
+    fn drop(&mut self) {
+        // The process is killed by its own drop
+        if let Ok(mut shared) = self.stream.lock() {
+            shared.closed = true;
+            if let Some(stream) = shared.stream.take() {
+                let _ = stream.shutdown(Shutdown::Both);
+            }
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WeechatConfig {
+    #[serde(default = "WeechatConfig::default_host")]
+    pub host: String,
+
+    pub port: u16,
+
+    /// Whether the relay uses SSL
+    #[serde(default = "WeechatConfig::default_ssl")]
+    pub ssl: bool,
+
+    /// Whether to check the certificate of the relay
+    #[serde(default = "WeechatConfig::default_ssl_verify")]
+    pub ssl_verify: bool,
+
+    /// Command that prints the password
+    #[serde(default)]
+    pub password_command: Option<String>,
+
+    /// File that contains the password
+    #[serde(default)]
+    pub password_file: Option<String>,
+
+    /// Interval in seconds at which the hotlist is asked for, to notice buffers that were read
+    #[serde(
+        default = "WeechatConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format string
+    #[serde(default = "WeechatConfig::default_format")]
+    pub format: String,
+
+    /// Whether to hide the block while no buffer has highlights or private messages
+    #[serde(default = "WeechatConfig::default_hide_when_zero")]
+    pub hide_when_zero: bool,
+}
+
+impl WeechatConfig {
+    fn default_host() -> String {
+        "localhost".to_owned()
+    }
+
+    fn default_ssl() -> bool {
+        false
+    }
+
+    fn default_ssl_verify() -> bool {
+        true
+    }
+
+    fn default_interval() -> Duration {
+        Duration::from_secs(10)
+    }
+
+    fn default_format() -> String {
+        "{buffers}".to_owned()
+    }
+
+    fn default_hide_when_zero() -> bool {
+        false
+    }
+}
+
+impl ConfigBlock for Weechat {
+    type Config = WeechatConfig;
+
+    fn new(block_config: Self::Config, config: Config, send: Sender<Task>) -> Result<Self> {
+        let id = pseudo_uuid();
+        let relay = Relay {
+            host: block_config.host,
+            port: block_config.port,
+            ssl: block_config.ssl,
+            ssl_verify: block_config.ssl_verify,
+            password_command: block_config.password_command,
+            password_file: block_config.password_file,
+            interval: block_config.interval,
+        };
+
+        let status: Status = Arc::new(Mutex::new(None));
+        let thread_status = status.clone();
+        let thread_id = id.clone();
+        let process = OwnedChild::new();
+        let stream: Arc<Mutex<SharedStream>> = Arc::default();
+        let link = Link {
+            child: process.handle(),
+            stream: stream.clone(),
+        };
+        thread::Builder::new()
+            .name("weechat".into())
+            .spawn(move || {
+                let mut notify = |hotlist: std::result::Result<Hotlist, String>| {
+                    *thread_status
+                        .lock()
+                        .expect("lock has been poisoned in `weechat` block") = Some(hotlist);
+                    send.send(Task {
+                        id: thread_id.clone(),
+                        update_time: Instant::now(),
+                    })
+                    .is_ok()
+                };
+                loop {
+                    match watch(&relay, &link, &mut notify) {
+                        // The block is gone
+                        Ok(()) => return,
+                        Err(error) => {
+                            if !notify(Err(error)) {
+                                return;
+                            }
+                        }
+                    }
+                    thread::sleep(RETRY_DELAY);
+                }
+            })
+            .block_error("weechat", "failed to start the watching thread")?;
+
+        Ok(Weechat {
+            id,
+            text: TextWidget::new(config).with_icon("chat"),
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("weechat", "Invalid format specified")?,
+            hide_when_zero: block_config.hide_when_zero,
+            status,
+            hidden: block_config.hide_when_zero,
+            process,
+            stream,
+        })
+    }
+}
+
+impl Block for Weechat {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let status = self
+            .status
+            .lock()
+            .block_error("weechat", "failed to acquire lock")?
+            .clone();
+        match status {
+            Some(Ok(hotlist)) => {
+                let values = map!(
+                    "{buffers}" => hotlist.buffers,
+                    "{highlights}" => hotlist.highlights,
+                    "{private}" => hotlist.private
+                );
+                self.text.set_text(self.format.render_static_str(&values)?);
+                self.text.set_state(if hotlist.buffers > 0 {
+                    State::Warning
+                } else {
+                    State::Idle
+                });
+                self.hidden = self.hide_when_zero && hotlist.buffers == 0;
+            }
+            // Errors are shown until the thread connects again, instead of stopping the bar
+            Some(Err(_)) | None => {
+                let values = map!(
+                    "{buffers}" => "?",
+                    "{highlights}" => "?",
+                    "{private}" => "?"
+                );
+                self.text.set_text(self.format.render_static_str(&values)?);
+                self.text.set_state(if status.is_some() {
+                    State::Critical
+                } else {
+                    State::Idle
+                });
+                self.hidden = self.hide_when_zero && status.is_none();
+            }
+        }
+
+        // The thread updates the block
+        Ok(None)
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        if self.hidden {
+            vec![]
+        } else {
+            vec![&self.text]
+        }
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{count_hotlist, init_command, parse_message, Hotlist, Object};
+
+    fn string(value: &str) -> Vec<u8> {
+        let mut bytes = (value.len() as i32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(value.as_bytes());
+        bytes
+    }
+
+    fn short_string(value: &str) -> Vec<u8> {
+        let mut bytes = vec![value.len() as u8];
+        bytes.extend_from_slice(value.as_bytes());
+        bytes
+    }
+
+    fn hotlist_item(pointer: &str, priority: i32, counts: [i32; 4]) -> Vec<u8> {
+        let mut bytes = short_string(pointer);
+        bytes.extend_from_slice(&priority.to_be_bytes());
+        bytes.extend_from_slice(b"int");
+        bytes.extend_from_slice(&4i32.to_be_bytes());
+        for count in &counts {
+            bytes.extend_from_slice(&count.to_be_bytes());
+        }
+        bytes
+    }
+
+    #[test]
+    fn test_parse_message() {
+        let mut data = vec![0];
+        data.extend(string("hotlist"));
+        data.extend_from_slice(b"hda");
+        data.extend(string("hotlist"));
+        data.extend(string("priority:int,count:arr"));
+        data.extend_from_slice(&3i32.to_be_bytes());
+        data.extend(hotlist_item("55d0a8a0c6e0", 1, [4, 2, 0, 0]));
+        data.extend(hotlist_item("55d0a8a1f3c0", 3, [0, 7, 0, 1]));
+        data.extend(hotlist_item("55d0a8a2b980", 3, [0, 0, 2, 1]));
+
+        let message = parse_message(&data).unwrap();
+        assert_eq!(message.id.as_deref(), Some("hotlist"));
+        match message.objects[0] {
+            Object::Hda(ref items) => {
+                assert_eq!(items.len(), 3);
+                assert_eq!(items[0]["priority"], Object::Int(1));
+            }
+            ref object => panic!("unexpected object {:?}", object),
+        }
+        assert_eq!(
+            count_hotlist(&message).unwrap(),
+            Hotlist {
+                highlights: 2,
+                private: 1,
+                buffers: 2,
+            }
+        );
+
+        // A truncated message
+        assert!(parse_message(&data[..data.len() - 2]).is_err());
+    }
+
+    #[test]
+    fn test_parse_objects() {
+        let mut data = vec![0];
+        data.extend_from_slice(&(-1i32).to_be_bytes());
+        data.extend_from_slice(b"lon");
+        data.extend(short_string("-1234567890"));
+        data.extend_from_slice(b"str");
+        data.extend_from_slice(&(-1i32).to_be_bytes());
+        data.extend_from_slice(b"htb");
+        data.extend_from_slice(b"strchr");
+        data.extend_from_slice(&1i32.to_be_bytes());
+        data.extend(string("key"));
+        data.push(b'A');
+
+        let message = parse_message(&data).unwrap();
+        assert_eq!(message.id, None);
+        assert_eq!(
+            message.objects,
+            vec![
+                Object::Lon(-1234567890),
+                Object::Str(None),
+                Object::Htb(vec![(
+                    Object::Str(Some("key".to_string())),
+                    Object::Chr(b'A')
+                )]),
+            ]
+        );
+
+        // Compressed messages are not supported
+        assert!(parse_message(&[1, 0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_init_command() {
+        assert_eq!(init_command(None), "init compression=off");
+        assert_eq!(
+            init_command(Some("se,cr\\et")),
+            "init password=se\\,cr\\\\et,compression=off"
+        );
+    }
+}
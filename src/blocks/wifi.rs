@@ -0,0 +1,577 @@
+//! A block for the connection of a wireless device, queried from the kernel over nl80211.
+
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::net::signal_quality;
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::util::{escape_pango_text, pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+pub struct Wifi {
+    id: String,
+    output: ButtonWidget,
+    update_interval: Duration,
+    device: Option<String>,
+    format: FormatTemplate,
+    format_disconnected: FormatTemplate,
+    max_ssid_width: usize,
+    good: u32,
+    warning: u32,
+    critical: u32,
+    connection_editor: String,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct WifiConfig {
+    /// The wireless device, by default the first one that is connected
+    #[serde(default)]
+    pub device: Option<String>,
+
+    /// Update interval in seconds
+    #[serde(
+        default = "WifiConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// Format string while the device is connected
+    #[serde(default = "WifiConfig::default_format")]
+    pub format: String,
+
+    /// Format string while the device is not connected
+    #[serde(default = "WifiConfig::default_format_disconnected")]
+    pub format_disconnected: String,
+
+    /// Truncates the SSID to this many characters
+    #[serde(default = "WifiConfig::default_max_ssid_width")]
+    pub max_ssid_width: usize,
+
+    /// The signal quality in percent at or above which the state is good
+    #[serde(default = "WifiConfig::default_good")]
+    pub good: u32,
+
+    /// The signal quality in percent below which the state is warning
+    #[serde(default = "WifiConfig::default_warning")]
+    pub warning: u32,
+
+    /// The signal quality in percent below which the state is critical
+    #[serde(default = "WifiConfig::default_critical")]
+    pub critical: u32,
+
+    /// Command that is run when the block is clicked
+    #[serde(default = "WifiConfig::default_connection_editor")]
+    pub connection_editor: String,
+}
+
+impl WifiConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(5)
+    }
+
+    fn default_format() -> String {
+        "{ssid} {signal_strength}".to_owned()
+    }
+
+    fn default_format_disconnected() -> String {
+        "".to_owned()
+    }
+
+    fn default_max_ssid_width() -> usize {
+        21
+    }
+
+    fn default_good() -> u32 {
+        60
+    }
+
+    fn default_warning() -> u32 {
+        40
+    }
+
+    fn default_critical() -> u32 {
+        20
+    }
+
+    fn default_connection_editor() -> String {
+        "nm-connection-editor".to_owned()
+    }
+}
+
+impl ConfigBlock for Wifi {
+    type Config = WifiConfig;
+
+    fn new(
+        block_config: Self::Config,
+        config: Config,
+        _tx_update_request: Sender<Task>,
+    ) -> Result<Self> {
+        let id = pseudo_uuid();
+        Ok(Wifi {
+            output: ButtonWidget::new(config, &id).with_icon("net_wireless"),
+            id,
+            update_interval: block_config.interval,
+            device: block_config.device,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("wifi", "Invalid format specified")?,
+            format_disconnected: FormatTemplate::from_string(&block_config.format_disconnected)
+                .block_error("wifi", "Invalid format_disconnected specified")?,
+            max_ssid_width: block_config.max_ssid_width,
+            good: block_config.good,
+            warning: block_config.warning,
+            critical: block_config.critical,
+            connection_editor: block_config.connection_editor,
+        })
+    }
+}
+
+impl Wifi {
+    fn state(&self, quality: Option<u32>) -> State {
+        match quality {
+            Some(quality) if quality < self.critical => State::Critical,
+            Some(quality) if quality < self.warning => State::Warning,
+            Some(quality) if quality >= self.good => State::Good,
+            _ => State::Idle,
+        }
+    }
+}
+
+impl Block for Wifi {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mut socket = nl80211::Socket::open().block_error("wifi", "Failed to open nl80211")?;
+        let interfaces = socket
+            .interfaces()
+            .block_error("wifi", "Failed to query the wireless devices")?;
+        let interface = match self.device {
+            Some(ref device) => interfaces.into_iter().find(|i| &i.name == device),
+            None => {
+                let connected = interfaces.iter().position(|i| i.ssid.is_some());
+                interfaces.into_iter().nth(connected.unwrap_or(0))
+            }
+        };
+
+        let interface = match interface {
+            Some(interface) if interface.ssid.is_some() => interface,
+            interface => {
+                let device = interface.map(|i| i.name).unwrap_or_default();
+                let values = map!("{device}" => device);
+                self.output
+                    .set_text(self.format_disconnected.render_static_str(&values)?);
+                self.output.set_state(State::Idle);
+                return Ok(Some(self.update_interval.into()));
+            }
+        };
+
+        let station = socket
+            .station(interface.index)
+            .block_error("wifi", "Failed to query the connection")?;
+        let signal = station.as_ref().and_then(|s| s.signal);
+        let quality = signal.map(signal_quality);
+        let bitrate = station.as_ref().and_then(|s| s.bitrate);
+
+        let mut ssid = interface.ssid.clone().unwrap_or_default();
+        if let Some((end, _)) = ssid.char_indices().nth(self.max_ssid_width) {
+            ssid.truncate(end);
+        }
+        let values = map!(
+            "{device}" => interface.name.clone(),
+            // SSIDs can contain chars that need escaping
+            "{ssid}" => escape_pango_text(ssid),
+            "{signal_strength}" => quality.map(|q| format!("{}%", q)).unwrap_or_default(),
+            "{signal_dbm}" => signal.map(|s| format!("{} dBm", s)).unwrap_or_default(),
+            "{frequency}" => interface
+                .frequency
+                .map(|f| format!("{:.1} GHz", f as f64 / 1000.))
+                .unwrap_or_default(),
+            "{bitrate}" => bitrate
+                .map(|b| format!("{:.1} MBit/s", b as f64 / 10.))
+                .unwrap_or_default()
+        );
+        self.output
+            .set_text(self.format.render_static_str(&values)?);
+        self.output.set_state(self.state(quality));
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        vec![&self.output]
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.matches_name(&self.id)
+            && event.button == MouseButton::Left
+            && !self.connection_editor.is_empty()
+        {
+            spawn_child_async("sh", &["-c", &self.connection_editor])
+                .block_error("wifi", "could not spawn the connection editor")?;
+        }
+        Ok(())
+    }
+
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        event.button == MouseButton::Left && !self.connection_editor.is_empty()
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+/// A minimal client of the nl80211 generic netlink family, see `linux/nl80211.h`.
+mod nl80211 {
+    use std::convert::TryInto;
+    use std::io;
+    use std::mem;
+    use std::os::unix::io::RawFd;
+
+    use nix::libc;
+
+    const NLM_F_REQUEST: u16 = 0x1;
+    const NLM_F_ACK: u16 = 0x4;
+    const NLM_F_DUMP: u16 = 0x300;
+    const NLMSG_ERROR: u16 = 0x2;
+    const NLMSG_DONE: u16 = 0x3;
+    const NLMSG_HDRLEN: usize = 16;
+    const GENL_HDRLEN: usize = 4;
+    const NLA_HDRLEN: usize = 4;
+    const NLA_TYPE_MASK: u16 = 0x3fff;
+
+    const GENL_ID_CTRL: u16 = 0x10;
+    const CTRL_CMD_GETFAMILY: u8 = 3;
+    const CTRL_ATTR_FAMILY_ID: u16 = 1;
+    const CTRL_ATTR_FAMILY_NAME: u16 = 2;
+
+    const NL80211_CMD_GET_INTERFACE: u8 = 5;
+    const NL80211_CMD_GET_STATION: u8 = 17;
+    const NL80211_ATTR_IFINDEX: u16 = 3;
+    const NL80211_ATTR_IFNAME: u16 = 4;
+    const NL80211_ATTR_STA_INFO: u16 = 21;
+    const NL80211_ATTR_WIPHY_FREQ: u16 = 38;
+    const NL80211_ATTR_SSID: u16 = 52;
+    const NL80211_STA_INFO_SIGNAL: u16 = 7;
+    const NL80211_STA_INFO_TX_BITRATE: u16 = 8;
+    const NL80211_RATE_INFO_BITRATE: u16 = 1;
+    const NL80211_RATE_INFO_BITRATE32: u16 = 5;
+
+    pub struct Interface {
+        pub index: u32,
+        pub name: String,
+        /// The SSID of the network the interface is connected to
+        pub ssid: Option<String>,
+        /// The frequency of the channel in MHz
+        pub frequency: Option<u32>,
+    }
+
+    pub struct Station {
+        /// The signal level in dBm
+        pub signal: Option<i32>,
+        /// The transmit bitrate in 100 kbit/s
+        pub bitrate: Option<u32>,
+    }
+
+    pub struct Socket {
+        fd: RawFd,
+        seq: u32,
+        family: u16,
+    }
+
+    fn align(len: usize) -> usize {
+        (len + 3) & !3
+    }
+
+    fn other_error(message: &str) -> io::Error {
+        io::Error::new(io::ErrorKind::Other, message)
+    }
+
+    /// Splits a buffer of netlink attributes into their types and payloads.
+    fn attributes(mut buffer: &[u8]) -> Vec<(u16, &[u8])> {
+        let mut attributes = Vec::new();
+        while buffer.len() >= NLA_HDRLEN {
+            let len = u16::from_ne_bytes([buffer[0], buffer[1]]) as usize;
+            let kind = u16::from_ne_bytes([buffer[2], buffer[3]]) & NLA_TYPE_MASK;
+            if len < NLA_HDRLEN || len > buffer.len() {
+                break;
+            }
+            attributes.push((kind, &buffer[NLA_HDRLEN..len]));
+            buffer = &buffer[align(len).min(buffer.len())..];
+        }
+        attributes
+    }
+
+    fn u32_of(payload: &[u8]) -> Option<u32> {
+        Some(u32::from_ne_bytes(payload.get(..4)?.try_into().ok()?))
+    }
+
+    fn u16_of(payload: &[u8]) -> Option<u16> {
+        Some(u16::from_ne_bytes(payload.get(..2)?.try_into().ok()?))
+    }
+
+    fn string_of(payload: &[u8]) -> String {
+        let end = payload
+            .iter()
+            .position(|&b| b == 0)
+            .unwrap_or(payload.len());
+        String::from_utf8_lossy(&payload[..end]).into_owned()
+    }
+
+    impl Socket {
+        /// Opens a generic netlink socket and resolves the id of the nl80211 family.
+        pub fn open() -> io::Result<Self> {
+            let fd = unsafe {
+                libc::socket(
+                    libc::AF_NETLINK,
+                    libc::SOCK_RAW | libc::SOCK_CLOEXEC,
+                    libc::NETLINK_GENERIC,
+                )
+            };
+            if fd < 0 {
+                return Err(io::Error::last_os_error());
+            }
+            let mut socket = Socket {
+                fd,
+                seq: 0,
+                family: GENL_ID_CTRL,
+            };
+
+            // Don't let a kernel that never answers freeze the bar
+            let timeout = libc::timeval {
+                tv_sec: 1,
+                tv_usec: 0,
+            };
+            let mut address: libc::sockaddr_nl = unsafe { mem::zeroed() };
+            address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+            let failed = unsafe {
+                libc::setsockopt(
+                    fd,
+                    libc::SOL_SOCKET,
+                    libc::SO_RCVTIMEO,
+                    &timeout as *const _ as *const libc::c_void,
+                    mem::size_of::<libc::timeval>() as libc::socklen_t,
+                ) < 0
+                    || libc::bind(
+                        fd,
+                        &address as *const _ as *const libc::sockaddr,
+                        mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+                    ) < 0
+            };
+            if failed {
+                return Err(io::Error::last_os_error());
+            }
+
+            let replies = socket.request(
+                CTRL_CMD_GETFAMILY,
+                0,
+                &[(CTRL_ATTR_FAMILY_NAME, &b"nl80211\0"[..])],
+            )?;
+            socket.family = replies
+                .iter()
+                .flat_map(|reply| attributes(reply))
+                .find(|&(kind, _)| kind == CTRL_ATTR_FAMILY_ID)
+                .and_then(|(_, payload)| u16_of(payload))
+                .ok_or_else(|| other_error("nl80211 is not available"))?;
+            Ok(socket)
+        }
+
+        /// All wireless interfaces.
+        pub fn interfaces(&mut self) -> io::Result<Vec<Interface>> {
+            let replies = self.request(NL80211_CMD_GET_INTERFACE, NLM_F_DUMP, &[])?;
+            Ok(replies
+                .iter()
+                .filter_map(|reply| {
+                    let mut interface = Interface {
+                        index: 0,
+                        name: String::new(),
+                        ssid: None,
+                        frequency: None,
+                    };
+                    for (kind, payload) in attributes(reply) {
+                        match kind {
+                            NL80211_ATTR_IFINDEX => interface.index = u32_of(payload)?,
+                            NL80211_ATTR_IFNAME => interface.name = string_of(payload),
+                            NL80211_ATTR_SSID => interface.ssid = Some(string_of(payload)),
+                            NL80211_ATTR_WIPHY_FREQ => interface.frequency = u32_of(payload),
+                            _ => {}
+                        }
+                    }
+                    // Interfaces without a netdev, such as P2P devices, have no index
+                    if interface.index == 0 {
+                        None
+                    } else {
+                        Some(interface)
+                    }
+                })
+                .collect())
+        }
+
+        /// The station the interface is connected to, if any.
+        pub fn station(&mut self, index: u32) -> io::Result<Option<Station>> {
+            let replies = self.request(
+                NL80211_CMD_GET_STATION,
+                NLM_F_DUMP,
+                &[(NL80211_ATTR_IFINDEX, &index.to_ne_bytes()[..])],
+            )?;
+            let info = match replies.iter().find_map(|reply| {
+                attributes(reply)
+                    .into_iter()
+                    .find(|&(kind, _)| kind == NL80211_ATTR_STA_INFO)
+                    .map(|(_, payload)| payload)
+            }) {
+                Some(info) => info,
+                None => return Ok(None),
+            };
+
+            let mut station = Station {
+                signal: None,
+                bitrate: None,
+            };
+            for (kind, payload) in attributes(info) {
+                match kind {
+                    // The level is a signed value stored in an unsigned byte
+                    NL80211_STA_INFO_SIGNAL => {
+                        station.signal = payload.first().map(|&s| i32::from(s as i8))
+                    }
+                    NL80211_STA_INFO_TX_BITRATE => {
+                        let rate = attributes(payload);
+                        let rate_of = |wanted: u16| rate.iter().find(|&&(kind, _)| kind == wanted);
+                        station.bitrate = rate_of(NL80211_RATE_INFO_BITRATE32)
+                            .and_then(|&(_, payload)| u32_of(payload))
+                            .or_else(|| {
+                                rate_of(NL80211_RATE_INFO_BITRATE)
+                                    .and_then(|&(_, payload)| u16_of(payload))
+                                    .map(u32::from)
+                            });
+                    }
+                    _ => {}
+                }
+            }
+            Ok(Some(station))
+        }
+
+        /// Sends a request to the family of the socket and returns the payloads of the
+        /// replies, without their generic netlink header.
+        fn request(
+            &mut self,
+            command: u8,
+            flags: u16,
+            attributes: &[(u16, &[u8])],
+        ) -> io::Result<Vec<Vec<u8>>> {
+            self.seq = self.seq.wrapping_add(1);
+            // Dumps end with `NLMSG_DONE`, other requests with an acknowledgement
+            let flags = if flags & NLM_F_DUMP != 0 {
+                NLM_F_REQUEST | flags
+            } else {
+                NLM_F_REQUEST | flags | NLM_F_ACK
+            };
+
+            let mut message = vec![0; NLMSG_HDRLEN];
+            message.extend_from_slice(&[command, 1, 0, 0]);
+            for &(kind, payload) in attributes {
+                message.extend_from_slice(&((NLA_HDRLEN + payload.len()) as u16).to_ne_bytes());
+                message.extend_from_slice(&kind.to_ne_bytes());
+                message.extend_from_slice(payload);
+                message.resize(align(message.len()), 0);
+            }
+            let len = message.len() as u32;
+            message[0..4].copy_from_slice(&len.to_ne_bytes());
+            message[4..6].copy_from_slice(&self.family.to_ne_bytes());
+            message[6..8].copy_from_slice(&flags.to_ne_bytes());
+            message[8..12].copy_from_slice(&self.seq.to_ne_bytes());
+
+            let sent = unsafe {
+                libc::send(
+                    self.fd,
+                    message.as_ptr() as *const libc::c_void,
+                    message.len(),
+                    0,
+                )
+            };
+            if sent < 0 {
+                return Err(io::Error::last_os_error());
+            }
+
+            let mut replies = Vec::new();
+            let mut buffer = vec![0u8; 32 * 1024];
+            loop {
+                let received = unsafe {
+                    libc::recv(
+                        self.fd,
+                        buffer.as_mut_ptr() as *mut libc::c_void,
+                        buffer.len(),
+                        0,
+                    )
+                };
+                if received < 0 {
+                    return Err(io::Error::last_os_error());
+                }
+                let mut messages = &buffer[..received as usize];
+                while messages.len() >= NLMSG_HDRLEN {
+                    let len = u32_of(messages).unwrap_or(0) as usize;
+                    if len < NLMSG_HDRLEN || len > messages.len() {
+                        return Err(other_error("truncated netlink message"));
+                    }
+                    let kind = u16_of(&messages[4..]).unwrap_or(0);
+                    let seq = u32_of(&messages[8..]).unwrap_or(0);
+                    let payload = &messages[NLMSG_HDRLEN..len];
+                    messages = &messages[align(len).min(messages.len())..];
+                    if seq != self.seq {
+                        continue;
+                    }
+                    match kind {
+                        NLMSG_DONE => return Ok(replies),
+                        NLMSG_ERROR => {
+                            // The error is negated, and zero for an acknowledgement
+                            let error = u32_of(payload).unwrap_or(0) as i32;
+                            if error == 0 {
+                                return Ok(replies);
+                            }
+                            return Err(io::Error::from_raw_os_error(-error));
+                        }
+                        _ if payload.len() >= GENL_HDRLEN => {
+                            replies.push(payload[GENL_HDRLEN..].to_vec())
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+    }
+
+    impl Drop for Socket {
+        fn drop(&mut self) {
+            unsafe {
+                libc::close(self.fd);
+            }
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::attributes;
+
+        #[test]
+        fn test_attributes() {
+            let mut buffer = Vec::new();
+            buffer.extend_from_slice(&6u16.to_ne_bytes());
+            buffer.extend_from_slice(&52u16.to_ne_bytes());
+            buffer.extend_from_slice(b"ab\0\0");
+            buffer.extend_from_slice(&8u16.to_ne_bytes());
+            buffer.extend_from_slice(&(0x8000u16 | 21).to_ne_bytes());
+            buffer.extend_from_slice(&[1, 2, 3, 4]);
+            assert_eq!(
+                attributes(&buffer),
+                vec![(52, &b"ab"[..]), (21, &[1, 2, 3, 4][..])]
+            );
+        }
+    }
+}
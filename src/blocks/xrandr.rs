@@ -209,7 +209,7 @@ impl Xrandr {
                 if self.icons {
                     "{display} {brightness_icon} {brightness} {res_icon} {resolution}"
                 } else {
-                    "{display}: {brightness} [{resolution}]"
+                    "{display}: {brightness} \\[{resolution}\\]"
                 }
             } else if self.icons {
                 "{display} {brightness_icon} {brightness}"
@@ -308,6 +308,13 @@ impl Block for Xrandr {
         Ok(())
     }
 
+    fn handles_click(&self, event: &I3BarEvent) -> bool {
+        matches!(
+            event.button,
+            MouseButton::Left | MouseButton::WheelUp | MouseButton::WheelDown
+        )
+    }
+
     fn id(&self) -> &str {
         &self.id
     }
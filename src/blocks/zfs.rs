@@ -0,0 +1,275 @@
+//! A block for the health and capacity of ZFS pools.
+
+use std::process::Command;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use serde_derive::Deserialize;
+
+use crate::blocks::{Block, ConfigBlock, Update};
+use crate::config::Config;
+use crate::de::deserialize_duration;
+use crate::errors::*;
+use crate::input::{I3BarEvent, MouseButton};
+use crate::scheduler::Task;
+use crate::subprocess::spawn_child_async;
+use crate::util::{pseudo_uuid, FormatTemplate};
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::button::ButtonWidget;
+
+/// A line of `zpool list -Hp -o name,health,size,allocated,capacity`.
+#[derive(Debug, PartialEq)]
+struct PoolList {
+    name: String,
+    health: String,
+    size: u64,
+    allocated: u64,
+    capacity: u64,
+}
+
+fn parse_pool_list(line: &str) -> Option<PoolList> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() < 5 {
+        return None;
+    }
+    Some(PoolList {
+        name: fields[0].to_string(),
+        health: fields[1].to_string(),
+        size: fields[2].parse().ok()?,
+        allocated: fields[3].parse().ok()?,
+        capacity: fields[4].parse().ok()?,
+    })
+}
+
+/// A short summary of the `scan:` line of `zpool status`.
+fn parse_scrub(status: &str) -> &'static str {
+    let scan = match status
+        .lines()
+        .map(|l| l.trim())
+        .find(|l| l.starts_with("scan:"))
+    {
+        Some(scan) => scan,
+        None => return "none",
+    };
+    if scan.contains("in progress") {
+        "in progress"
+    } else if scan.contains("canceled") {
+        "canceled"
+    } else if scan.contains("with 0 errors") {
+        "ok"
+    } else if scan.contains("errors") {
+        "errors"
+    } else {
+        "none"
+    }
+}
+
+fn zpool(args: &[&str]) -> Result<String> {
+    let output = Command::new("zpool")
+        .args(args)
+        .output()
+        .block_error("zfs", "failed to run zpool")?;
+    if !output.status.success() {
+        return Err(BlockError(
+            "zfs".to_string(),
+            format!(
+                "zpool failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            ),
+        ));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+pub struct Zfs {
+    id: String,
+    update_interval: Duration,
+    format: FormatTemplate,
+    pools: Vec<String>,
+    warning: u64,
+    critical: u64,
+    pool_command: Option<String>,
+    widgets: Vec<ButtonWidget>,
+    config: Config,
+}
+
+#[derive(Deserialize, Debug, Default, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct ZfsConfig {
+    /// Update interval in seconds
+    #[serde(
+        default = "ZfsConfig::default_interval",
+        deserialize_with = "deserialize_duration"
+    )]
+    pub interval: Duration,
+
+    /// The pools to show, all imported pools if empty
+    #[serde(default)]
+    pub pools: Vec<String>,
+
+    /// Format string
+    #[serde(default = "ZfsConfig::default_format")]
+    pub format: String,
+
+    /// Percentage of the pool used, where state is set to warning
+    #[serde(default = "ZfsConfig::default_warning")]
+    pub warning: u64,
+
+    /// Percentage of the pool used, where state is set to critical
+    #[serde(default = "ZfsConfig::default_critical")]
+    pub critical: u64,
+
+    /// Command to run on a left click on a pool, with `{pool}` replaced by the pool
+    #[serde(default)]
+    pub pool_command: Option<String>,
+}
+
+impl ZfsConfig {
+    fn default_interval() -> Duration {
+        Duration::from_secs(60)
+    }
+
+    fn default_format() -> String {
+        "{name} {health} {capacity}%".to_owned()
+    }
+
+    fn default_warning() -> u64 {
+        80
+    }
+
+    fn default_critical() -> u64 {
+        90
+    }
+}
+
+impl ConfigBlock for Zfs {
+    type Config = ZfsConfig;
+
+    fn new(block_config: Self::Config, config: Config, _send: Sender<Task>) -> Result<Self> {
+        Ok(Zfs {
+            id: pseudo_uuid(),
+            update_interval: block_config.interval,
+            format: FormatTemplate::from_string(&block_config.format)
+                .block_error("zfs", "Invalid format specified")?,
+            pools: block_config.pools,
+            warning: block_config.warning,
+            critical: block_config.critical,
+            pool_command: block_config.pool_command,
+            widgets: Vec::new(),
+            config,
+        })
+    }
+}
+
+impl Zfs {
+    /// The name of the widget of a pool, to tell which pool was clicked.
+    fn widget_name(&self, pool: &str) -> String {
+        format!("{}:{}", self.id, pool)
+    }
+}
+
+impl Block for Zfs {
+    fn update(&mut self) -> Result<Option<Update>> {
+        let mut args = vec!["list", "-Hp", "-o", "name,health,size,allocated,capacity"];
+        args.extend(self.pools.iter().map(|p| p.as_str()));
+        let list = zpool(&args)?;
+
+        let mut widgets = Vec::new();
+        for pool in list.lines().filter_map(parse_pool_list) {
+            let scrub = parse_scrub(&zpool(&["status", &pool.name])?);
+            let values = map!(
+                "{name}" => pool.name.clone(),
+                "{health}" => pool.health.clone(),
+                "{capacity}" => pool.capacity.to_string(),
+                "{size}" => format!("{:.1}", pool.size as f64 / 1e9),
+                "{allocated}" => format!("{:.1}", pool.allocated as f64 / 1e9),
+                "{scrub}" => scrub.to_string()
+            );
+
+            let state =
+                if pool.health != "ONLINE" || scrub == "errors" || pool.capacity >= self.critical {
+                    State::Critical
+                } else if pool.capacity >= self.warning {
+                    State::Warning
+                } else {
+                    State::Idle
+                };
+
+            widgets.push(
+                ButtonWidget::new(self.config.clone(), &self.widget_name(&pool.name))
+                    .with_icon("disk_drive")
+                    .with_text(&self.format.render_static_str(&values)?)
+                    .with_state(state),
+            );
+        }
+        self.widgets = widgets;
+
+        Ok(Some(self.update_interval.into()))
+    }
+
+    fn view(&self) -> Vec<&dyn I3BarWidget> {
+        self.widgets
+            .iter()
+            .map(|widget| widget as &dyn I3BarWidget)
+            .collect()
+    }
+
+    fn click(&mut self, event: &I3BarEvent) -> Result<()> {
+        if event.button != MouseButton::Left {
+            return Ok(());
+        }
+        let name = match event.name {
+            Some(ref name) => name,
+            None => return Ok(()),
+        };
+        let prefix = format!("{}:", self.id);
+        if let (Some(command), true) = (&self.pool_command, name.starts_with(&prefix)) {
+            let pool = &name[prefix.len()..];
+            spawn_child_async("sh", &["-c", &command.replace("{pool}", pool)])
+                .block_error("zfs", "could not spawn child")?;
+        }
+        Ok(())
+    }
+
+    fn id(&self) -> &str {
+        &self.id
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_pool_list, parse_scrub, PoolList};
+
+    #[test]
+    fn test_parse_pool_list() {
+        assert_eq!(
+            parse_pool_list("tank\tDEGRADED\t3985729650688\t1992864825344\t50"),
+            Some(PoolList {
+                name: "tank".to_string(),
+                health: "DEGRADED".to_string(),
+                size: 3985729650688,
+                allocated: 1992864825344,
+                capacity: 50,
+            })
+        );
+        assert_eq!(parse_pool_list("tank\tONLINE"), None);
+    }
+
+    #[test]
+    fn test_parse_scrub() {
+        let status = "  pool: tank
+ state: ONLINE
+  scan: scrub repaired 0B in 01:02:03 with 0 errors on Sun Oct 11 01:26:04 2026
+config:";
+        assert_eq!(parse_scrub(status), "ok");
+        assert_eq!(
+            parse_scrub("  scan: scrub in progress since Sun Oct 11 00:24:01 2026"),
+            "in progress"
+        );
+        assert_eq!(
+            parse_scrub("  scan: scrub repaired 4K in 01:02:03 with 2 errors on Sun"),
+            "errors"
+        );
+        assert_eq!(parse_scrub("  scan: none requested"), "none");
+    }
+}
@@ -0,0 +1,157 @@
+//! Validation of a configuration file without running the bar.
+
+use std::path::Path;
+
+use serde::de::Deserialize;
+use toml::value::Value;
+
+use crate::blocks::check_block;
+use crate::config::{load_config, Config};
+use crate::icons;
+use crate::themes::{Theme, ThemeOverrides};
+use crate::util::{color_from_rgba, FormatTemplate};
+
+/// Loads the configuration and checks the settings of its blocks, without creating them.
+/// Returns a description of every problem that was found.
+pub fn check_config(config_path: &Path) -> Vec<String> {
+    let config = match load_config(config_path) {
+        Ok(config) => config,
+        Err(error) => return vec![format!("{:?}", error)],
+    };
+
+    let mut problems = Vec::new();
+    check_colors(&config.theme, None, "theme", &mut problems);
+    let icon_sets = [
+        &*icons::NONE,
+        &*icons::AWESOME,
+        &*icons::AWESOME5,
+        &*icons::MATERIAL,
+    ];
+    for name in config.icons.keys() {
+        if !icon_sets.iter().any(|icons| icons.contains_key(name)) {
+            problems.push(format!("icons: unknown icon `{}`", name));
+        }
+    }
+
+    for (i, &(ref block_name, ref block_config)) in config.blocks.iter().enumerate() {
+        let context = format!("block {} (`{}`)", i + 1, block_name);
+        check_block_config(&config, block_name, block_config, &context, &mut problems);
+    }
+    problems
+}
+
+/// Checks the settings and format strings of a block, and those of the blocks in a group.
+fn check_block_config(
+    config: &Config,
+    block_name: &str,
+    block_config: &Value,
+    context: &str,
+    problems: &mut Vec<String>,
+) {
+    if let Some(table) = block_config.as_table() {
+        for (key, value) in table {
+            if let (true, Some(format)) = (key.starts_with("format"), value.as_str()) {
+                if let Err(error) = FormatTemplate::from_string(format) {
+                    problems.push(format!("{}: `{}`: {:?}", context, key, error));
+                }
+            }
+        }
+        if let Some(overrides) = table.get("theme_overrides") {
+            check_theme_overrides(config, overrides, context, problems);
+        }
+        let children = match (block_name, table.get("block").and_then(Value::as_array)) {
+            ("group", Some(children)) => children.as_slice(),
+            _ => &[][..],
+        };
+        for (i, child) in children.iter().enumerate() {
+            let mut child = child.clone();
+            let name = match child.as_table_mut().and_then(|t| t.remove("block")) {
+                Some(Value::String(name)) => name,
+                _ => continue,
+            };
+            let context = format!("{} > block {} (`{}`)", context, i + 1, name);
+            check_block_config(config, &name, &child, &context, problems);
+        }
+    }
+    if let Err(error) = check_block(block_name, block_config.clone()) {
+        problems.push(format!("{}: {:?}", context, error));
+    }
+}
+
+fn check_theme_overrides(
+    config: &Config,
+    overrides: &Value,
+    context: &str,
+    problems: &mut Vec<String>,
+) {
+    // Errors in the overrides themselves are reported when the block is created
+    if let Ok(overrides) = ThemeOverrides::deserialize(overrides.clone()) {
+        let theme = config.theme.clone().with_overrides(overrides);
+        check_colors(&theme, Some(&config.theme), context, problems);
+    }
+}
+
+/// Checks the colors of a theme, except those that are the same as in `base`.
+fn check_colors(theme: &Theme, base: Option<&Theme>, context: &str, problems: &mut Vec<String>) {
+    let base_colors = base.map(Theme::colors);
+    for (i, &(key, color)) in theme.colors().iter().enumerate() {
+        let color = match color {
+            Some("auto") | None => continue,
+            Some(color) => color,
+        };
+        if base_colors.map_or(false, |base| base[i].1 == Some(color)) {
+            continue;
+        }
+        if color_from_rgba(color).is_err() {
+            problems.push(format!(
+                "{}: `{}`: invalid color `{}`, expected `#RRGGBB` or `#RRGGBBAA`",
+                context, key, color
+            ));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::check_config;
+    use assert_fs::prelude::{FileWriteStr, PathChild};
+    use assert_fs::TempDir;
+
+    #[test]
+    fn test_check_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_file_path = temp_dir.child("status.toml");
+        config_file_path
+            .write_str(concat!(
+                "[theme]\n",
+                "name = \"plain\"\n",
+                "[theme.overrides]\n",
+                "idle_bg = \"#12345\"\n",
+                "[[block]]\n",
+                "block = \"load\"\n",
+                "intervall = 1\n",
+                "[[block]]\n",
+                "block = \"time\"\n",
+                "[block.theme_overrides]\n",
+                "good_fg = \"green\"\n",
+                "[[block]]\n",
+                "block = \"group\"\n",
+                "[[block.block]]\n",
+                "block = \"load\"\n",
+                "intervall = 1\n",
+                // Without a password, only creating the block fails
+                "[[block]]\n",
+                "block = \"imap\"\n",
+                "host = \"localhost\"\n",
+                "username = \"user\"\n",
+            ))
+            .unwrap();
+        let problems = check_config(config_file_path.path());
+        assert_eq!(problems.len(), 4);
+        assert!(problems[0].starts_with("theme: `idle_bg`"));
+        assert!(problems[1].starts_with("block 1 (`load`)"));
+        assert!(problems[1].contains("intervall"));
+        assert!(problems[2].starts_with("block 2 (`time`): `good_fg`"));
+        assert!(problems[3].starts_with("block 3 (`group`) > block 1 (`load`)"));
+    }
+}
@@ -3,6 +3,11 @@ use std::marker::PhantomData;
 use std::ops::Deref;
 use std::path::Path;
 use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
+
+use crossbeam_channel::Sender;
+use inotify::{Inotify, WatchMask};
 
 use serde::de::{Deserialize, Deserializer, Error};
 use serde_derive::Deserialize;
@@ -27,6 +32,16 @@ pub struct Config {
     /// motion which is undesired for sliders. Use "natural" to invert this.
     #[serde(default = "Scrolling::default", rename = "scrolling")]
     pub scrolling: Scrolling,
+    /// Path of a unix socket on which the bar accepts commands.
+    #[serde(default)]
+    pub ipc_socket: Option<String>,
+    /// Interpolate widget colors between good, warning and critical based on the
+    /// value the block reports, instead of using fixed state colors.
+    #[serde(default)]
+    pub gradient: bool,
+    /// Double the retry interval of a failing block after every failed update.
+    #[serde(default)]
+    pub error_backoff: bool,
     #[serde(rename = "block", deserialize_with = "deserialize_blocks")]
     pub blocks: Vec<(String, value::Value)>,
 }
@@ -37,11 +52,26 @@ impl Default for Config {
             icons: icons::default(),
             theme: Theme::default(),
             scrolling: Scrolling::default(),
+            ipc_socket: None,
+            gradient: false,
+            error_backoff: false,
             blocks: Vec::new(),
         }
     }
 }
 
+impl Config {
+    /// Whether the blocks of both configurations look and behave the same, apart from the
+    /// settings of each block.
+    pub fn same_block_settings(&self, other: &Config) -> bool {
+        self.icons == other.icons
+            && self.theme == other.theme
+            && self.scrolling == other.scrolling
+            && self.gradient == other.gradient
+            && self.error_backoff == other.error_backoff
+    }
+}
+
 impl From<LegacyConfig> for Config {
     fn from(legacy_config: LegacyConfig) -> Self {
         Config {
@@ -51,6 +81,9 @@ impl From<LegacyConfig> for Config {
                 .and_then(|s| Theme::from_name(s.as_str()))
                 .unwrap_or_default(),
             scrolling: legacy_config.scrolling,
+            ipc_socket: legacy_config.ipc_socket,
+            gradient: legacy_config.gradient,
+            error_backoff: legacy_config.error_backoff,
             blocks: legacy_config.blocks,
         }
     }
@@ -69,6 +102,12 @@ pub struct LegacyConfig {
     /// motion which is undesired for sliders. Use "natural" to invert this.
     #[serde(default = "Scrolling::default", rename = "scrolling")]
     pub scrolling: Scrolling,
+    #[serde(default)]
+    pub ipc_socket: Option<String>,
+    #[serde(default)]
+    pub gradient: bool,
+    #[serde(default)]
+    pub error_backoff: bool,
     #[serde(rename = "block", deserialize_with = "deserialize_blocks")]
     pub blocks: Vec<(String, value::Value)>,
 }
@@ -79,12 +118,15 @@ impl Default for LegacyConfig {
             icons: icons::default(),
             theme: None,
             scrolling: Scrolling::default(),
+            ipc_socket: None,
+            gradient: false,
+            error_backoff: false,
             blocks: Vec::new(),
         }
     }
 }
 
-#[derive(Deserialize, Copy, Clone, Debug)]
+#[derive(Deserialize, Copy, Clone, Debug, PartialEq)]
 #[serde(rename_all = "lowercase")]
 pub enum Scrolling {
     Reverse,
@@ -116,7 +158,7 @@ impl Default for Scrolling {
     }
 }
 
-fn deserialize_blocks<'de, D>(deserializer: D) -> Result<Vec<(String, value::Value)>, D::Error>
+pub fn deserialize_blocks<'de, D>(deserializer: D) -> Result<Vec<(String, value::Value)>, D::Error>
 where
     D: Deserializer<'de>,
 {
@@ -155,12 +197,68 @@ where
 // this function may belong somewhere else...
 pub fn load_config(config_path: &Path) -> errors::Result<Config> {
     let config: errors::Result<Config> = deserialize_file(config_path.to_str().unwrap());
-    config.or_else(|_| {
+    config.or_else(|error| {
         let legacy_config: errors::Result<LegacyConfig> =
             deserialize_file(config_path.to_str().unwrap());
-        legacy_config.map(|legacy| legacy.into())
+        // If neither format fits, the error of the current format is the helpful one
+        legacy_config.map(|legacy| legacy.into()).map_err(|_| error)
     })
 }
+
+/// Starts a thread that watches the config file and sends `SIGUSR2` on the provided channel
+/// whenever it changes, so that the bar reloads its configuration.
+pub fn watch_config(config_path: &Path, sender: Sender<i32>) {
+    // Follow symlinks, and watch the directory since most editors replace files on save.
+    let config_path = config_path
+        .canonicalize()
+        .unwrap_or_else(|_| config_path.to_path_buf());
+    let (dir, file_name) = match (config_path.parent(), config_path.file_name()) {
+        (Some(dir), Some(file_name)) => (dir.to_path_buf(), file_name.to_os_string()),
+        _ => return,
+    };
+
+    thread::Builder::new()
+        .name("config".into())
+        .spawn(move || {
+            let mut notify = match Inotify::init() {
+                Ok(notify) => notify,
+                Err(e) => return eprintln!("Failed to start inotify: {}", e),
+            };
+            if let Err(e) = notify.add_watch(
+                &dir,
+                WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE,
+            ) {
+                return eprintln!("Failed to watch {}: {}", dir.display(), e);
+            }
+
+            let mut buffer = [0; 1024];
+            loop {
+                let changed = match notify.read_events_blocking(&mut buffer) {
+                    Ok(mut events) => events.any(|event| event.name == Some(&file_name)),
+                    Err(e) => return eprintln!("Error while reading inotify events: {}", e),
+                };
+                if changed {
+                    // Editors may write in several steps, so wait until the file stays
+                    // unchanged for a moment and reload only once.
+                    loop {
+                        thread::sleep(Duration::from_millis(250));
+                        let changed_again = match notify.read_events(&mut buffer) {
+                            Ok(mut events) => events.any(|event| event.name == Some(&file_name)),
+                            Err(_) => false,
+                        };
+                        if !changed_again {
+                            break;
+                        }
+                    }
+                    if sender.send(signal_hook::SIGUSR2).is_err() {
+                        return;
+                    }
+                }
+            }
+        })
+        .unwrap();
+}
+
 #[cfg(test)]
 mod tests {
     use crate::config::load_config;
@@ -209,4 +307,27 @@ mod tests {
         let config = load_config(config_file_path.path());
         config.unwrap();
     }
+
+    #[test]
+    fn test_load_config_icon_overrides() {
+        let temp_dir = TempDir::new().unwrap();
+        let config_file_path = temp_dir.child("status.toml");
+        config_file_path
+            .write_str(
+                concat!(
+                    "theme = \"plain\"\n",
+                    "[icons]\n",
+                    "name = \"nerd_fonts\"\n",
+                    "[icons.overrides]\n",
+                    "time = \" T \"\n",
+                    "[[block]]\n",
+                    "block = \"time\"\n",
+                )
+                .as_ref(),
+            )
+            .unwrap();
+        let config = load_config(config_file_path.path()).unwrap();
+        assert_eq!(config.icons["time"], " T ");
+        assert_eq!(config.icons["docker"], " \u{e7b0} ");
+    }
 }
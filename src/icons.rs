@@ -22,10 +22,14 @@ lazy_static! {
         "bell" => " ON ",
         "bell-slash" => " OFF ",
         "bluetooth" => " BT",
+        "caffeine_off" => " CAF OFF ",
+        "caffeine_on" => " CAF ON ",
+        "chat" => " CHAT ",
         "cogs" => " LOAD ",
         "cpu" => " CPU ",
         "disk_drive" => " DISK ",
         "docker" => " DOCKER ",
+        "error" => " ERR ",
         "github" => " GITHUB ",
         "gpu" => " GPU ",
         "headphones" => " HEAD",
@@ -50,6 +54,7 @@ lazy_static! {
         "phone_disconnected" => " PHONE ",
         "ping" => " PING ",
         "pomodoro" => " POMODORO ",
+        "recording" => " REC ",
         "resolution" => " RES ",
         "tasks" => " TSK ",
         "thermometer" => " TEMP ",
@@ -57,6 +62,7 @@ lazy_static! {
         "toggle_off" => " OFF ",
         "toggle_on" => " ON ",
         "update" => " UPD ",
+        "ups" => " UPS ",
         "uptime" => " UP ",
         "volume_empty" => " VOL ",
         "volume_full" => " VOL ",
@@ -72,6 +78,7 @@ lazy_static! {
         "weather_snow" => " SNOW ",
         "weather_sun" => " SUNNY ",
         "weather_thunder" => " STORM ",
+        "webcam" => " CAM ",
         "xrandr" => " SCREEN "
     };
 
@@ -94,10 +101,14 @@ lazy_static! {
         "bell" => " \u{f0f3} ",
         "bell-slash" => " \u{f1f7} ",
         "bluetooth" => " \u{f294}",
+        "caffeine_off" => " \u{f186} ", // fa-moon-o
+        "caffeine_on" => " \u{f0f4} ", // fa-coffee
+        "chat" => " \u{f086} ", // fa-comments
         "cogs" => " \u{f085} ",
         "cpu" => " \u{f0e4} ",
         "disk_drive" => " \u{f0a0} ",
         "docker" => " \u{f21a} ",
+        "error" => " \u{f071} ",
         "github" => " \u{f09b} ",
         "gpu" => " \u{f26c} ",
         "headphones" => " \u{f025}",
@@ -124,6 +135,7 @@ lazy_static! {
         "phone_disconnected" => " \u{1f4f5} ",
         "ping" => " \u{21ba} ",
         "pomodoro" => " \u{1f345} ",
+        "recording" => " \u{f111} ", // fa-circle
         "resolution" => " \u{f096} ", // fa-square-o
         "tasks" => " \u{f0ae} ",
         "thermometer" => " \u{f2c8} ",
@@ -132,6 +144,7 @@ lazy_static! {
         "toggle_on" => " \u{f205} ",
         "unknown" => " \u{f128} ",
         "update" => " \u{f062} ", // Same as time symbol.
+        "ups" => " \u{f1e6} ",
         "uptime" => " \u{f017} ",
         "volume_empty" => " \u{f026} ",
         "volume_full" => " \u{f028} ",
@@ -147,6 +160,7 @@ lazy_static! {
         "weather_snow" => " \u{f2dc} ",
         "weather_sun" => " \u{f185} ",
         "weather_thunder" => " \u{f0e7} ",
+        "webcam" => " \u{f03d} ",
         "xrandr" => " \u{f26c} "
     };
 
@@ -168,10 +182,14 @@ lazy_static! {
         "bell" => " \u{f0f3} ",
         "bell-slash" => " \u{f1f6} ",
         "bluetooth" => " \u{f294}",
+        "caffeine_off" => " \u{f0f4} ", // fa-coffee
+        "caffeine_on" => " \u{f7b6} ", // fa-mug-hot
+        "chat" => " \u{f086} ", // fa-comments
         "cogs" => " \u{f085} ",
         "cpu" => " \u{f3fd} ",
         "disk_drive" => " \u{f8b5} ",
         "docker" => " \u{f21a} ",
+        "error" => " \u{f071} ",
         "github" => " \u{f09b} ",
         "gpu" => " \u{f26c} ",
         "headphones" => " \u{f025}",
@@ -198,6 +216,7 @@ lazy_static! {
         "phone_disconnected" => " \u{1f4f5} ",
         "ping" => " \u{f362} ",
         "pomodoro" => " \u{1f345} ",
+        "recording" => " \u{f111} ", // fa-circle
         "resolution" => " \u{f096} ", // fa-square-o
         "tasks" => " \u{f0ae} ",
         "thermometer" => " \u{f2c8} ",
@@ -206,6 +225,7 @@ lazy_static! {
         "toggle_on" => " \u{f205} ",
         "unknown" => " \u{f128} ",
         "update" => " \u{f062} ",
+        "ups" => " \u{f1e6} ",
         "uptime" => " \u{f2f2} ",
         "volume_empty" => " \u{f026} ",
         "volume_full" => " \u{f028} ",
@@ -221,9 +241,27 @@ lazy_static! {
         "weather_snow" => " \u{f2dc} ",
         "weather_sun" => " \u{f185} ",
         "weather_thunder" => " \u{f0e7} ",
+        "webcam" => " \u{f03d} ",
         "xrandr" => " \u{f26c} "
     };
 
+    /// Nerd Fonts include Font Awesome 4 at its original code points, so only the icons
+    /// for which the Awesome set uses emoji or a poor match are replaced.
+    pub static ref NERD_FONTS: Map<String, String> = {
+        let mut icons = AWESOME.clone();
+        icons.extend(map_to_owned! {
+            "backlight_empty" => " \u{f042} ",
+            "backlight_full" => " \u{f185} ",
+            "backlight_partial1" => " \u{f042} ",
+            "backlight_partial2" => " \u{f042} ",
+            "backlight_partial3" => " \u{f185} ",
+            "docker" => " \u{e7b0} ",
+            "phone_disconnected" => " \u{f10b} ",
+            "pomodoro" => " \u{f254} "
+        });
+        icons
+    };
+
     pub static ref MATERIAL: Map<String, String> = map_to_owned! {
         "" => "",
         "bat_charging" => " \u{e1a3} ",
@@ -236,10 +274,14 @@ lazy_static! {
         "bell" => " \u{e7f4} ",
         "bell-slash" => " \u{e7f8} ",
         "bluetooth" => " \u{e1a7}",
+        "caffeine_off" => " \u{eb44} ", // free-breakfast
+        "caffeine_on" => " \u{e541} ", // local-cafe
+        "chat" => " \u{e0b7} ", // chat
         "cogs" => " \u{e8b8} ",
         "cpu" => " \u{e640} ",
         "disk_drive" => " \u{e1db} ",
         "docker" => " \u{e532} ",
+        "error" => " \u{e002} ",
         "github" => " \u{e86f} ",
         "gpu" => " \u{e333} ",
         "headphones" => " \u{e60f}",
@@ -258,6 +300,7 @@ lazy_static! {
         "phone" => " \u{e324} ",
         "phone_disconnected" => " \u{1f4f5} ",
         "pomodoro" => " \u{1f345} ",
+        "recording" => " \u{e061} ", // fiber-manual-record
         "resolution" => " \u{f152} ", // crop-square-rounded
         "tasks" => " \u{e8f9} ",
         "thermometer" => " \u{f2c8} ", // TODO
@@ -265,11 +308,13 @@ lazy_static! {
         "toggle_off" => " \u{e836} ",
         "toggle_on" => " \u{e837} ",
         "update" => " \u{e8d7} ",
+        "ups" => " \u{e63c} ",
         "uptime" => " \u{e192} ", // Same as time symbol.
         "volume_empty" => " \u{e04e} ",
         "volume_full" => " \u{e050} ",
         "volume_half" => " \u{e04d} ",
         "volume_muted" => " \u{e04e} \u{e04f} ",
+        "webcam" => " \u{e04b} ",
         "xrandr" => " \u{e31e} "
     };
 }
@@ -279,7 +324,8 @@ pub fn get_icons(name: &str) -> Option<Map<String, String>> {
         "material" => Some(MATERIAL.clone()),
         "awesome" => Some(AWESOME.clone()),
         "awesome5" => Some(AWESOME5.clone()),
-        "none" => Some(NONE.clone()),
+        "nerd_fonts" => Some(NERD_FONTS.clone()),
+        "none" | "ascii" => Some(NONE.clone()),
         _ => None,
     }
 }
@@ -8,7 +8,8 @@ use crossbeam_channel::Sender;
 use serde::{de, Deserializer};
 use serde_derive::Deserialize;
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Deserialize, Debug, Copy, Clone, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
 pub enum MouseButton {
     Left,
     Middle,
@@ -29,6 +30,10 @@ pub struct I3BarEvent {
 
     #[serde(deserialize_with = "deserialize_mousebutton")]
     pub button: MouseButton,
+
+    /// Modifier keys held during the click, as named by i3bar (e.g. `Shift`, `Control`, `Mod1`)
+    #[serde(default)]
+    pub modifiers: Vec<String>,
 }
 
 impl I3BarEvent {
@@ -38,21 +43,74 @@ impl I3BarEvent {
             _ => false,
         }
     }
+
+    pub fn has_modifier(&self, modifier: &str) -> bool {
+        self.modifiers
+            .iter()
+            .any(|m| m.eq_ignore_ascii_case(modifier))
+    }
+}
+
+/// Turns a line read from stdin into a click event, if it describes one.
+pub type EventParser = fn(&str) -> Option<I3BarEvent>;
+
+/// Parses a click event sent by i3bar or swaybar.
+pub fn parse_i3bar_event(line: &str) -> Option<I3BarEvent> {
+    // Take only the valid JSON object betweem curly braces (cut off leading bracket, commas and whitespace)
+    let slice = line.trim_start_matches(|c| c != '{');
+    let slice = slice.trim_end_matches(|c| c != '}');
+
+    if slice.is_empty() {
+        return None;
+    }
+    serde_json::from_str(slice).ok()
+}
+
+/// The command that lemonbar prints when the given button is clicked on a widget.
+pub fn lemonbar_command(button: u8, instance: &str, name: Option<&str>) -> String {
+    match name {
+        Some(name) => format!("i3status-rs {} {} {}", button, instance, name),
+        None => format!("i3status-rs {} {}", button, instance),
+    }
 }
 
-pub fn process_events(sender: Sender<I3BarEvent>) {
+/// Parses a command printed by lemonbar for a click area, see `lemonbar_command`.
+pub fn parse_lemonbar_event(line: &str) -> Option<I3BarEvent> {
+    let mut words = line.split_whitespace();
+    if words.next() != Some("i3status-rs") {
+        return None;
+    }
+    let button = match words.next()?.parse::<u8>().ok()? {
+        1 => MouseButton::Left,
+        2 => MouseButton::Middle,
+        3 => MouseButton::Right,
+        4 => MouseButton::WheelUp,
+        5 => MouseButton::WheelDown,
+        _ => MouseButton::Unknown,
+    };
+    Some(I3BarEvent {
+        instance: Some(words.next()?.to_string()),
+        name: words.next().map(String::from),
+        x: 0,
+        y: 0,
+        button,
+        modifiers: Vec::new(),
+    })
+}
+
+pub fn process_events(sender: Sender<I3BarEvent>, parse: EventParser) {
     thread::Builder::new()
         .name("input".into())
         .spawn(move || loop {
             let mut input = String::new();
-            io::stdin().read_line(&mut input).unwrap();
-
-            // Take only the valid JSON object betweem curly braces (cut off leading bracket, commas and whitespace)
-            let slice = input.trim_start_matches(|c| c != '{');
-            let slice = slice.trim_end_matches(|c| c != '}');
+            if io::stdin().read_line(&mut input).unwrap() == 0 {
+                // No more events will come, but the sender is kept so that the bar keeps running
+                loop {
+                    thread::park();
+                }
+            }
 
-            if !slice.is_empty() {
-                let e: I3BarEvent = serde_json::from_str(slice).unwrap();
+            if let Some(e) = parse(&input) {
                 sender.send(e).unwrap();
             }
         })
@@ -93,3 +151,22 @@ where
 
     deserializer.deserialize_any(MouseButtonVisitor)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_lemonbar_event() {
+        let event = parse_lemonbar_event(&lemonbar_command(3, "abc", Some("next"))).unwrap();
+        assert_eq!(event.button, MouseButton::Right);
+        assert_eq!(event.instance.as_deref(), Some("abc"));
+        assert_eq!(event.name.as_deref(), Some("next"));
+
+        let event = parse_lemonbar_event("i3status-rs 4 abc\n").unwrap();
+        assert_eq!(event.button, MouseButton::WheelUp);
+        assert_eq!(event.name, None);
+
+        assert!(parse_lemonbar_event("some other command").is_none());
+    }
+}
@@ -0,0 +1,280 @@
+//! A unix socket through which scripts control the running bar.
+//!
+//! Every line sent to the socket is one command, answered by a line that is either `ok` or
+//! starts with `error:`. Blocks are addressed by their `ipc_name` option:
+//!
+//! - `refresh <name>` updates the block immediately
+//! - `set_text <name> <text>` shows the text instead of the block, an empty text restores it
+//! - `show <name>`, `hide <name>` and `toggle <name>` change the visibility of the block
+//! - `collapse <name>`, `expand <name>` and `toggle_collapse <name>` change a `group` block
+
+use std::fs;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::os::unix::fs::FileTypeExt;
+use std::os::unix::net::{UnixListener, UnixStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+
+use crossbeam_channel::Sender;
+
+use crate::errors::*;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum IpcCommand {
+    Refresh,
+    SetText(String),
+    Show,
+    Hide,
+    Toggle,
+    Collapse,
+    Expand,
+    ToggleCollapse,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct IpcRequest {
+    /// Name of the addressed block
+    pub block: String,
+    pub command: IpcCommand,
+}
+
+/// A request together with the channel on which the reply is expected.
+pub type IpcMessage = (IpcRequest, Sender<String>);
+
+impl IpcRequest {
+    pub fn parse(line: &str) -> ::std::result::Result<Self, String> {
+        let line = line.trim_end_matches(&['\n', '\r'][..]);
+        let mut parts = line.splitn(3, ' ');
+        let command = parts.next().unwrap_or_default();
+        let block = match parts.next() {
+            Some(block) if !block.is_empty() => block.to_string(),
+            _ => return Err(format!("missing block name for '{}'", command)),
+        };
+        let rest = parts.next();
+        let command = match (command, rest) {
+            ("refresh", None) => IpcCommand::Refresh,
+            ("set_text", text) => IpcCommand::SetText(text.unwrap_or_default().to_string()),
+            ("show", None) => IpcCommand::Show,
+            ("hide", None) => IpcCommand::Hide,
+            ("toggle", None) => IpcCommand::Toggle,
+            ("collapse", None) => IpcCommand::Collapse,
+            ("expand", None) => IpcCommand::Expand,
+            ("toggle_collapse", None) => IpcCommand::ToggleCollapse,
+            (_, Some(_)) => return Err(format!("too many arguments for '{}'", command)),
+            _ => return Err(format!("unknown command '{}'", command)),
+        };
+        Ok(IpcRequest { block, command })
+    }
+}
+
+/// The socket of the running bar. Dropping it stops accepting commands and removes the
+/// socket, e.g. when a reload moves it.
+pub struct IpcSocket {
+    path: PathBuf,
+    stopped: Arc<AtomicBool>,
+}
+
+impl Drop for IpcSocket {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::SeqCst);
+        // Wake up the thread, which waits for the next client
+        let _ = UnixStream::connect(&self.path);
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+/// Removes a socket that a previous run left behind, which would make binding fail. Anything
+/// else at the path, like a file or the socket of another running bar, is left alone.
+fn remove_stale_socket(path: &Path) -> Result<()> {
+    let metadata = match fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        Err(_) => {
+            return Err(ConfigurationError(
+                format!("failed to check {}", path.display()),
+                (String::new(), String::new()),
+            ))
+        }
+    };
+    if !metadata.file_type().is_socket() {
+        return Err(ConfigurationError(
+            format!("{} exists and is not a socket", path.display()),
+            (String::new(), String::new()),
+        ));
+    }
+    if UnixStream::connect(path).is_ok() {
+        return Err(ConfigurationError(
+            format!("another bar is listening on {}", path.display()),
+            (String::new(), String::new()),
+        ));
+    }
+    fs::remove_file(path).internal_error("ipc", "failed to remove stale socket")
+}
+
+/// Starts a thread that accepts commands on the socket and sends them on the provided
+/// channel, waiting for the reply of the main loop. Every client is served by a thread of its
+/// own, so that a client that keeps the connection open does not hold up the others.
+pub fn process_ipc(path: &Path, sender: Sender<IpcMessage>) -> Result<IpcSocket> {
+    remove_stale_socket(path)?;
+    let listener = UnixListener::bind(path).internal_error("ipc", "failed to bind socket")?;
+    let stopped = Arc::new(AtomicBool::new(false));
+    let thread_stopped = stopped.clone();
+
+    thread::Builder::new()
+        .name("ipc".into())
+        .spawn(move || {
+            for stream in listener.incoming().flatten() {
+                if thread_stopped.load(Ordering::SeqCst) {
+                    // The socket was moved, or the bar is shutting down.
+                    return;
+                }
+                let sender = sender.clone();
+                // A client that the thread cannot be started for is dropped
+                let _ = thread::Builder::new()
+                    .name("ipc client".into())
+                    .spawn(move || handle_client(stream, &sender));
+            }
+        })
+        .internal_error("ipc", "failed to start the socket thread")?;
+    Ok(IpcSocket {
+        path: path.to_path_buf(),
+        stopped,
+    })
+}
+
+fn handle_client(stream: UnixStream, sender: &Sender<IpcMessage>) -> ::std::result::Result<(), ()> {
+    let mut writer = match stream.try_clone() {
+        Ok(writer) => writer,
+        Err(_) => return Ok(()),
+    };
+    for line in BufReader::new(stream).lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if line.trim().is_empty() {
+            continue;
+        }
+        let reply = match IpcRequest::parse(&line) {
+            Ok(request) => {
+                let (tx_reply, rx_reply) = crossbeam_channel::bounded(1);
+                sender.send((request, tx_reply)).map_err(|_| ())?;
+                rx_reply.recv().map_err(|_| ())?
+            }
+            Err(message) => format!("error: {}", message),
+        };
+        if writeln!(writer, "{}", reply).is_err() {
+            break;
+        }
+    }
+    Ok(())
+}
+
+/// Sends a single command to the socket of a running bar and returns its reply.
+pub fn send_command(path: &Path, command: &str) -> Result<String> {
+    let mut stream = UnixStream::connect(path).configuration_error(&format!(
+        "failed to connect to the bar at {}",
+        path.display()
+    ))?;
+    writeln!(stream, "{}", command).internal_error("ipc", "failed to send command")?;
+    stream
+        .shutdown(::std::net::Shutdown::Write)
+        .internal_error("ipc", "failed to send command")?;
+    let mut reply = String::new();
+    BufReader::new(stream)
+        .read_line(&mut reply)
+        .internal_error("ipc", "failed to read reply")?;
+    Ok(reply.trim_end().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            IpcRequest::parse("set_text weather it rains\n"),
+            Ok(IpcRequest {
+                block: "weather".to_string(),
+                command: IpcCommand::SetText("it rains".to_string()),
+            })
+        );
+        assert_eq!(
+            IpcRequest::parse("toggle cpu").map(|r| r.command),
+            Ok(IpcCommand::Toggle)
+        );
+        assert_eq!(
+            IpcRequest::parse("toggle_collapse sys").map(|r| r.command),
+            Ok(IpcCommand::ToggleCollapse)
+        );
+        assert!(IpcRequest::parse("refresh").is_err());
+        assert!(IpcRequest::parse("refresh cpu now").is_err());
+        assert!(IpcRequest::parse("explode cpu").is_err());
+    }
+
+    fn answer(rx: crossbeam_channel::Receiver<IpcMessage>) {
+        thread::spawn(move || {
+            for (request, reply) in rx {
+                reply.send(format!("ok {}", request.block)).unwrap();
+            }
+        });
+    }
+
+    #[test]
+    fn test_only_stale_sockets_are_replaced() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+
+        // A file at a mistyped path is kept
+        let file = temp_dir.path().join("config.toml");
+        fs::write(&file, "theme = \"plain\"").unwrap();
+        let (tx, _rx) = crossbeam_channel::unbounded::<IpcMessage>();
+        assert!(process_ipc(&file, tx.clone()).is_err());
+        assert_eq!(fs::read_to_string(&file).unwrap(), "theme = \"plain\"");
+
+        // The socket of another bar is kept as well
+        let path = temp_dir.path().join("bar.sock");
+        let (tx_running, rx_running) = crossbeam_channel::unbounded::<IpcMessage>();
+        let running = process_ipc(&path, tx_running).unwrap();
+        answer(rx_running);
+        assert!(process_ipc(&path, tx.clone()).is_err());
+        assert_eq!(send_command(&path, "refresh cpu").unwrap(), "ok cpu");
+        drop(running);
+
+        // A socket that nobody listens on is left from a previous run
+        drop(UnixListener::bind(&path).unwrap());
+        assert!(path.exists());
+        let (tx, rx) = crossbeam_channel::unbounded::<IpcMessage>();
+        let _socket = process_ipc(&path, tx).unwrap();
+        answer(rx);
+        assert_eq!(send_command(&path, "refresh mem").unwrap(), "ok mem");
+    }
+
+    #[test]
+    fn test_idle_client_does_not_block_others() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let path = temp_dir.path().join("bar.sock");
+        let (tx, rx) = crossbeam_channel::unbounded::<IpcMessage>();
+        let _socket = process_ipc(&path, tx).unwrap();
+        answer(rx);
+
+        let _idle = UnixStream::connect(&path).unwrap();
+        assert_eq!(send_command(&path, "refresh cpu").unwrap(), "ok cpu");
+    }
+
+    #[test]
+    fn test_socket_is_removed_on_drop() {
+        let temp_dir = assert_fs::TempDir::new().unwrap();
+        let path = temp_dir.path().join("bar.sock");
+        let (tx, rx) = crossbeam_channel::unbounded::<IpcMessage>();
+        let socket = process_ipc(&path, tx).unwrap();
+        answer(rx);
+        assert_eq!(send_command(&path, "refresh cpu").unwrap(), "ok cpu");
+
+        drop(socket);
+        assert!(!path.exists());
+        assert!(send_command(&path, "refresh cpu").is_err());
+    }
+}
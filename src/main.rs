@@ -8,10 +8,14 @@ mod de;
 #[macro_use]
 mod util;
 pub mod blocks;
+mod check;
 mod config;
 mod errors;
 mod icons;
 mod input;
+mod ipc;
+mod output;
+mod reactor;
 mod scheduler;
 mod signals;
 mod subprocess;
@@ -24,20 +28,21 @@ use cpuprofiler::PROFILER;
 
 use std::collections::HashMap;
 use std::ops::DerefMut;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use clap::{crate_authors, crate_description, App, Arg, ArgMatches};
-use crossbeam_channel::{select, Receiver, Sender};
+use crossbeam_channel::{Receiver, Select, Sender};
 
-use crate::blocks::create_block;
 use crate::blocks::Block;
-use crate::config::{load_config, Config};
+use crate::blocks::{catch_errors, create_block};
+use crate::check::check_config;
+use crate::config::{load_config, watch_config, Config};
 use crate::errors::*;
 use crate::input::{process_events, I3BarEvent};
+use crate::ipc::{process_ipc, send_command, IpcMessage};
+use crate::output::{print_blocks, Output};
 use crate::scheduler::{Task, UpdateScheduler};
 use crate::signals::process_signals;
-use crate::widget::{I3BarWidget, State};
-use crate::widgets::text::TextWidget;
 
 fn main() {
     let ver = if env!("GIT_COMMIT_HASH").is_empty() || env!("GIT_COMMIT_DATE").is_empty() {
@@ -74,11 +79,33 @@ fn main() {
                 .takes_value(false),
         )
         .arg(
-            Arg::with_name("one-shot")
-                .help("Print blocks once and exit")
-                .long("one-shot")
-                .takes_value(false)
-                .hidden(true),
+            Arg::with_name("ipc")
+                .value_name("COMMAND")
+                .help("Send a command to the running bar, e.g. \"refresh weather\", and print the reply")
+                .long("ipc")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("output")
+                .value_name("PROTOCOL")
+                .help("Sets the protocol in which the bar is printed")
+                .long("output")
+                .takes_value(true)
+                .possible_values(&output::NAMES)
+                .default_value("i3bar"),
+        )
+        .arg(
+            Arg::with_name("check")
+                .help("Check the configuration for errors and exit")
+                .long("check")
+                .takes_value(false),
+        )
+        .arg(
+            Arg::with_name("once")
+                .help("Update all blocks once, print a single line and exit")
+                .long("once")
+                .alias("one-shot")
+                .takes_value(false),
         );
 
     if_debug!({
@@ -100,22 +127,50 @@ fn main() {
 
     let matches = builder.get_matches();
     let exit_on_error = matches.is_present("exit-on-error");
+    let once = matches.is_present("once");
+
+    if let Some(command) = matches.value_of("ipc") {
+        match run_ipc_client(&matches, command) {
+            Ok(reply) => {
+                println!("{}", reply);
+                if reply.starts_with("error:") {
+                    ::std::process::exit(1);
+                }
+            }
+            Err(error) => {
+                eprintln!("{:?}", error);
+                ::std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if matches.is_present("check") {
+        let problems = check_config(&config_path(&matches));
+        for problem in &problems {
+            println!("{}", problem);
+        }
+        if !problems.is_empty() {
+            ::std::process::exit(1);
+        }
+        println!("Configuration is valid");
+        return;
+    }
+
+    let output = output::from_name(
+        matches.value_of("output").unwrap(),
+        matches.is_present("never-pause"),
+    )
+    .unwrap();
 
     // Run and match for potential error
-    if let Err(error) = run(&matches) {
-        if exit_on_error {
+    if let Err(error) = run(&matches, &*output) {
+        if exit_on_error || once {
             eprintln!("{:?}", error);
             ::std::process::exit(1);
         }
 
-        let error_widget = TextWidget::new(Default::default())
-            .with_state(State::Critical)
-            .with_text(&format!("{:?}", error));
-        let error_rendered = error_widget.get_rendered();
-        println!(
-            "{}",
-            serde_json::to_string(&[error_rendered]).expect("failed to serialize error message")
-        );
+        println!("{}", output.render_error(&format!("{:?}", error)));
 
         eprintln!("\n\n{:?}", error);
         // Do nothing, so the error message keeps displayed
@@ -125,31 +180,20 @@ fn main() {
     }
 }
 
-fn run(matches: &ArgMatches) -> Result<()> {
-    // Now we can start to run the i3bar protocol
-    let initialise = if matches.is_present("never-pause") {
-        format!(
-            "\"version\": 1, \"click_events\": true, \"stop_signal\": {}",
-            nix::sys::signal::Signal::SIGCONT as i8
-        )
-    } else {
-        "\"version\": 1, \"click_events\": true".to_string()
-    };
-    print!("{{{}}}\n[", initialise);
+fn run(matches: &ArgMatches, output: &dyn Output) -> Result<()> {
+    // Now we can start to run the protocol, unless only a single line is printed
+    let once = matches.is_present("once");
+    if let Some(header) = output.header().filter(|_| !once) {
+        print!("{}", header);
+    }
 
     // Read & parse the config file
-    let config_path = match matches.value_of("config") {
-        Some(config_path) => std::path::PathBuf::from(config_path),
-        None => util::xdg_config_home().join("i3status-rust/config.toml"),
-    };
-    let config = load_config(&config_path)?;
-
-    // Update request channel
-    let (tx_update_requests, rx_update_requests): (Sender<Task>, Receiver<Task>) =
-        crossbeam_channel::unbounded();
+    let config_path = config_path(matches);
+    let mut config = load_config(&config_path)?;
 
     // In dev build, we might diverge into profiling blocks here
     if let Some(name) = matches.value_of("profile") {
+        let (tx_update_requests, _rx_update_requests) = crossbeam_channel::unbounded();
         profile_config(
             name,
             matches.value_of("profile-runs").unwrap(),
@@ -160,94 +204,299 @@ fn run(matches: &ArgMatches) -> Result<()> {
     }
 
     // Initialize the blocks
-    let mut blocks: Vec<Box<dyn Block>> = Vec::new();
+    let exit_on_error = matches.is_present("exit-on-error");
+    let mut blocks = Vec::new();
+    create_blocks(&config, &mut blocks, false, exit_on_error)?;
+
+    if once {
+        return print_once(output, &config, &mut blocks);
+    }
+
+    // We wait for click events in a separate thread, to avoid blocking to wait for stdin
+    let (tx_clicks, rx_clicks): (Sender<I3BarEvent>, Receiver<I3BarEvent>) =
+        crossbeam_channel::unbounded();
+    if let Some(parse) = output.event_parser() {
+        process_events(tx_clicks, parse);
+    }
+
+    // We wait for signals in a separate thread
+    let (tx_signals, rx_signals): (Sender<i32>, Receiver<i32>) = crossbeam_channel::unbounded();
+    process_signals(tx_signals.clone());
+
+    // Changes to the config file are reported like a SIGUSR2
+    watch_config(&config_path, tx_signals);
+
+    // Commands from scripts arrive on the IPC socket, if there is one
+    let (tx_ipc, rx_ipc): (Sender<IpcMessage>, Receiver<IpcMessage>) =
+        crossbeam_channel::unbounded();
+    let mut ipc_socket = match config.ipc_socket {
+        Some(ref socket) => Some(process_ipc(std::path::Path::new(socket), tx_ipc.clone())?),
+        None => None,
+    };
+
+    while run_blocks(
+        output,
+        &config,
+        &mut blocks,
+        &rx_clicks,
+        &rx_signals,
+        &rx_ipc,
+    )? {
+        // Only replace the running blocks once the new configuration is known to work
+        match load_config(&config_path).and_then(|new_config| {
+            let keep_unchanged = new_config.same_block_settings(&config);
+            create_blocks(&new_config, &mut blocks, keep_unchanged, exit_on_error)?;
+            Ok(new_config)
+        }) {
+            Ok(new_config) => {
+                if new_config.ipc_socket != config.ipc_socket {
+                    // The old socket has to be gone before a new one can take its path
+                    drop(ipc_socket.take());
+                    if let Some(ref socket) = new_config.ipc_socket {
+                        match process_ipc(std::path::Path::new(socket), tx_ipc.clone()) {
+                            Ok(socket) => ipc_socket = Some(socket),
+                            Err(error) => eprintln!("Failed to open the IPC socket: {:?}", error),
+                        }
+                    }
+                }
+                config = new_config;
+            }
+            Err(error) => eprintln!("Failed to reload configuration: {:?}", error),
+        }
+    }
+    Ok(())
+}
+
+fn config_path(matches: &ArgMatches) -> std::path::PathBuf {
+    match matches.value_of("config") {
+        Some(config_path) => std::path::PathBuf::from(config_path),
+        None => util::xdg_config_home().join("i3status-rust/config.toml"),
+    }
+}
+
+/// Sends a command to the IPC socket named in the configuration.
+fn run_ipc_client(matches: &ArgMatches, command: &str) -> Result<String> {
+    let config = load_config(&config_path(matches))?;
+    let socket = config.ipc_socket.ok_or_else(|| {
+        ConfigurationError(
+            "no `ipc_socket` is set in the configuration".to_string(),
+            (String::new(), String::new()),
+        )
+    })?;
+    send_command(std::path::Path::new(&socket), command)
+}
+
+/// A block of the bar, with the settings it was created from and the channel on which it
+/// requests updates.
+struct RunningBlock {
+    name: String,
+    config: toml::Value,
+    block: Box<dyn Block>,
+    update_requests: Receiver<Task>,
+    /// Keeps the channel open for blocks that never request updates
+    #[allow(dead_code)]
+    update_request: Sender<Task>,
+}
+
+/// Replaces the running blocks with those of a configuration. With `keep_unchanged`, a block
+/// whose settings did not change is kept as it is, with its state. The other blocks are
+/// dropped, which kills their processes and closes their channel, so that their threads stop.
+/// If a block cannot be created, the running blocks are left alone.
+/// Unless `exit_on_error` is set, errors of the blocks are shown on the bar.
+fn create_blocks(
+    config: &Config,
+    blocks: &mut Vec<RunningBlock>,
+    keep_unchanged: bool,
+    exit_on_error: bool,
+) -> Result<()> {
+    enum Slot {
+        Kept(usize),
+        Created(RunningBlock),
+    }
+
+    let mut kept = vec![false; blocks.len()];
+    let mut slots = Vec::new();
     for &(ref block_name, ref block_config) in &config.blocks {
-        blocks.push(create_block(
-            block_name,
-            block_config.clone(),
-            config.clone(),
-            tx_update_requests.clone(),
-        )?);
+        let unchanged = (0..blocks.len()).find(|&i| {
+            keep_unchanged
+                && !kept[i]
+                && blocks[i].name == *block_name
+                && blocks[i].config == *block_config
+        });
+        if let Some(i) = unchanged {
+            kept[i] = true;
+            slots.push(Slot::Kept(i));
+            continue;
+        }
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let block = create_block(block_name, block_config.clone(), config.clone(), tx.clone())?;
+        slots.push(Slot::Created(RunningBlock {
+            name: block_name.clone(),
+            config: block_config.clone(),
+            block: if exit_on_error {
+                block
+            } else {
+                catch_errors(block, config.clone(), tx.clone())
+            },
+            update_requests: rx,
+            update_request: tx,
+        }));
+    }
+
+    let mut old = blocks.drain(..).map(Some).collect::<Vec<_>>();
+    for slot in slots {
+        blocks.push(match slot {
+            Slot::Kept(i) => old[i].take().unwrap(),
+            Slot::Created(block) => block,
+        });
+    }
+    Ok(())
+}
+
+/// Updates every block once and prints a single line.
+fn print_once(output: &dyn Output, config: &Config, blocks: &mut [RunningBlock]) -> Result<()> {
+    for running in blocks.iter_mut() {
+        running.block.update()?;
     }
+    let rendered = blocks
+        .iter()
+        .flat_map(|running| running.block.views())
+        .filter(|(_, widgets)| !widgets.is_empty())
+        .collect::<Vec<_>>();
+    // Without the rest of the stream, the comma that i3bar expects between lines is dropped
+    println!(
+        "{}",
+        output.render(&rendered, config)?.trim_end_matches(',')
+    );
+    Ok(())
+}
 
+/// Runs the bar until a reload of the configuration is requested, in which case `true` is
+/// returned.
+fn run_blocks(
+    output: &dyn Output,
+    config: &Config,
+    blocks: &mut [RunningBlock],
+    rx_clicks: &Receiver<I3BarEvent>,
+    rx_signals: &Receiver<i32>,
+    rx_ipc: &Receiver<IpcMessage>,
+) -> Result<bool> {
     // We save the order of the blocks here,
     // because they will be passed to an unordered HashMap
     let order = blocks
         .iter()
-        .map(|x| String::from(x.id()))
+        .map(|x| String::from(x.block.id()))
         .collect::<Vec<_>>();
 
-    let mut scheduler = UpdateScheduler::new(&blocks);
+    let mut scheduler = UpdateScheduler::new(&order);
+
+    // Each block requests updates on its own channel
+    let update_requests = blocks
+        .iter()
+        .map(|x| x.update_requests.clone())
+        .collect::<Vec<_>>();
 
     let mut block_map: HashMap<String, &mut dyn Block> = HashMap::new();
 
-    for block in &mut blocks {
-        block_map.insert(String::from(block.id()), (*block).deref_mut());
+    for running in blocks.iter_mut() {
+        block_map.insert(String::from(running.block.id()), running.block.deref_mut());
     }
 
-    // We wait for click events in a separate thread, to avoid blocking to wait for stdin
-    let (tx_clicks, rx_clicks): (Sender<I3BarEvent>, Receiver<I3BarEvent>) =
-        crossbeam_channel::unbounded();
-    process_events(tx_clicks);
-
-    // We wait for signals in a separate thread
-    let (tx_signals, rx_signals): (Sender<i32>, Receiver<i32>) = crossbeam_channel::unbounded();
-    process_signals(tx_signals);
-
     // Time to next update channel.
     // Fires immediately for first updates
     let mut ttnu = crossbeam_channel::after(Duration::from_millis(0));
 
-    let one_shot = matches.is_present("one-shot");
     loop {
         // We use the message passing concept of channel selection
         // to avoid busy wait
-        select! {
+        let mut select = Select::new();
+        let clicks = select.recv(rx_clicks);
+        let timer = select.recv(&ttnu);
+        let ipc = select.recv(rx_ipc);
+        let signals = select.recv(rx_signals);
+        for rx in &update_requests {
+            select.recv(rx);
+        }
+        let operation = select.select();
+        match operation.index() {
             // Receive click events
-            recv(rx_clicks) -> res => if let Ok(event) = res {
+            i if i == clicks => {
+                if let Ok(event) = operation.recv(rx_clicks) {
                     for block in block_map.values_mut() {
                         block.click(&event)?;
                     }
-                    util::print_blocks(&order, &block_map, &config)?;
-            },
-            // Receive async update requests
-            recv(rx_update_requests) -> request => if let Ok(req) = request {
-                // Process immediately and forget
-                block_map
-                    .get_mut(&req.id)
-                    .internal_error("scheduler", "could not get required block")?
-                    .update()?;
-                util::print_blocks(&order, &block_map, &config)?;
-            },
+                    print_blocks(output, &order, &block_map, config)?;
+                }
+            }
             // Receive update timer events
-            recv(ttnu) -> _ => {
+            i if i == timer => {
+                let _ = operation.recv(&ttnu);
                 scheduler.do_scheduled_updates(&mut block_map)?;
                 // redraw the blocks, state changed
-                util::print_blocks(&order, &block_map, &config)?;
-            },
+                print_blocks(output, &order, &block_map, config)?;
+            }
+            // Receive commands from the IPC socket
+            i if i == ipc => {
+                if let Ok((request, reply)) = operation.recv(rx_ipc) {
+                    let mut found = false;
+                    for block in block_map.values_mut() {
+                        found |= block.command(&request)?;
+                    }
+                    reply
+                        .send(if found {
+                            "ok".to_string()
+                        } else {
+                            format!("error: no block named '{}'", request.block)
+                        })
+                        .ok();
+                    print_blocks(output, &order, &block_map, config)?;
+                }
+            }
             // Receive signal events
-            recv(rx_signals) -> res => if let Ok(sig) = res {
-                match sig {
-                    signal_hook::SIGUSR1 => {
-                        //USR1 signal that updates every block in the bar
-                        for block in block_map.values_mut() {
-                            block.update()?;
+            i if i == signals => {
+                if let Ok(sig) = operation.recv(rx_signals) {
+                    match sig {
+                        signal_hook::SIGUSR1 => {
+                            //USR1 signal that updates every block in the bar
+                            for (id, block) in block_map.iter_mut() {
+                                if let Some(update) = block.update()? {
+                                    scheduler.schedule(id, update);
+                                }
+                            }
+                            print_blocks(output, &order, &block_map, config)?;
+                        }
+                        signal_hook::SIGUSR2 => {
+                            //USR2 signal that reloads the config
+                            return Ok(true);
                         }
-                        util::print_blocks(&order, &block_map, &config)?;
-                    },
-                    signal_hook::SIGUSR2 => {
-                        //USR2 signal that should reload the config
-                        //TODO not implemented
-                        //unimplemented!("SIGUSR2 is meant to be used to reload the config toml, but this feature is yet not implemented");
-                    },
-                    _ => {
-                        //Real time signal that updates only the blocks listening
-                        //for that signal
-                        for block in block_map.values_mut() {
-                            block.signal(sig)?;
+                        _ => {
+                            //Real time signal that updates only the blocks listening
+                            //for that signal
+                            for block in block_map.values_mut() {
+                                block.signal(sig)?;
+                            }
                         }
-                    },
-                };
+                    };
+                }
+            }
+            // Receive async update requests
+            i => {
+                if let Ok(req) = operation.recv(&update_requests[i - signals - 1]) {
+                    if req.update_time > Instant::now() {
+                        // Requests for later go on the schedule
+                        scheduler.schedule_task(req);
+                    } else {
+                        // Process immediately, and put blocks that asked for it back on the schedule
+                        if let Some(update) = block_map
+                            .get_mut(&req.id)
+                            .internal_error("scheduler", "could not get required block")?
+                            .update()?
+                        {
+                            scheduler.schedule(&req.id, update);
+                        }
+                        print_blocks(output, &order, &block_map, config)?;
+                    }
+                }
             }
         }
 
@@ -255,9 +504,6 @@ fn run(matches: &ArgMatches) -> Result<()> {
         if let Some(time) = scheduler.time_to_next_update() {
             ttnu = crossbeam_channel::after(time)
         }
-        if one_shot {
-            break Ok(());
-        }
     }
 }
 
@@ -311,3 +557,47 @@ fn profile_config(_name: &str, _runs: &str, _config: &Config, _update: Sender<Ta
         None,
     ))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time_block(format: &str) -> (String, toml::Value) {
+        let mut table = toml::value::Table::new();
+        table.insert("format".to_string(), toml::Value::from(format));
+        ("time".to_string(), toml::Value::Table(table))
+    }
+
+    #[test]
+    fn test_reload_keeps_unchanged_blocks() {
+        let mut config = Config {
+            blocks: vec![time_block("%H:%M"), time_block("%d")],
+            ..Config::default()
+        };
+        let mut blocks = Vec::new();
+        create_blocks(&config, &mut blocks, false, true).unwrap();
+        let ids = blocks
+            .iter()
+            .map(|x| String::from(x.block.id()))
+            .collect::<Vec<_>>();
+
+        config.blocks = vec![time_block("%d"), time_block("%H")];
+        create_blocks(&config, &mut blocks, true, true).unwrap();
+        assert_eq!(blocks[0].block.id(), ids[1]);
+        assert_ne!(blocks[1].block.id(), ids[0]);
+
+        // The running blocks stay when one of the new ones cannot be created
+        config.blocks.push((
+            "unknown".to_string(),
+            toml::Value::Table(Default::default()),
+        ));
+        assert!(create_blocks(&config, &mut blocks, true, true).is_err());
+        assert_eq!(blocks.len(), 2);
+        assert_eq!(blocks[0].block.id(), ids[1]);
+
+        // Another theme starts all blocks over
+        let mut new_config = config.clone();
+        new_config.theme.separator = "|".to_string();
+        assert!(!new_config.same_block_settings(&config));
+    }
+}
@@ -0,0 +1,537 @@
+//! The protocols in which the bar can be printed.
+
+use std::collections::HashMap;
+
+use crate::blocks::Block;
+use crate::config::Config;
+use crate::errors::*;
+use crate::input::{lemonbar_command, parse_i3bar_event, parse_lemonbar_event, EventParser};
+use crate::themes::Theme;
+use crate::util::add_colors;
+use crate::widget::{I3BarWidget, State};
+use crate::widgets::text::TextWidget;
+
+/// The visible blocks of the bar, as block id and widgets, in order.
+pub type RenderedBlocks<'a> = [(&'a str, Vec<&'a dyn I3BarWidget>)];
+
+pub trait Output {
+    /// Printed once, before the first bar.
+    fn header(&self) -> Option<String> {
+        None
+    }
+
+    /// Renders one state of the bar as a single line.
+    fn render(&self, blocks: &RenderedBlocks, config: &Config) -> Result<String>;
+
+    /// Renders an error that stopped the bar.
+    fn render_error(&self, message: &str) -> String;
+
+    /// How to read the click events that the consumer sends on stdin, if it sends any.
+    fn event_parser(&self) -> Option<EventParser> {
+        None
+    }
+}
+
+pub const NAMES: [&str; 6] = [
+    "i3bar",
+    "waybar",
+    "waybar-full",
+    "plain",
+    "tmux",
+    "lemonbar",
+];
+
+pub fn from_name(name: &str, never_pause: bool) -> Option<Box<dyn Output>> {
+    match name {
+        "i3bar" => Some(Box::new(I3Bar { never_pause })),
+        "waybar" => Some(Box::new(Waybar { full: false })),
+        "waybar-full" => Some(Box::new(Waybar { full: true })),
+        "plain" => Some(Box::new(Plain { tmux: false })),
+        "tmux" => Some(Box::new(Plain { tmux: true })),
+        "lemonbar" => Some(Box::new(Lemonbar)),
+        _ => None,
+    }
+}
+
+/// Prints the visible blocks in the given order.
+pub fn print_blocks(
+    output: &dyn Output,
+    order: &[String],
+    block_map: &HashMap<String, &mut dyn Block>,
+    config: &Config,
+) -> Result<()> {
+    let mut blocks = Vec::new();
+    for block_id in order {
+        let block = block_map
+            .get(block_id)
+            .internal_error("util", "couldn't get block by id")?;
+        blocks.extend(
+            block
+                .views()
+                .into_iter()
+                .filter(|(_, widgets)| !widgets.is_empty()),
+        );
+    }
+    println!("{}", output.render(&blocks, config)?);
+    Ok(())
+}
+
+/// The i3bar protocol, which is also understood by swaybar.
+pub struct I3Bar {
+    never_pause: bool,
+}
+
+impl Output for I3Bar {
+    fn header(&self) -> Option<String> {
+        let initialise = if self.never_pause {
+            format!(
+                "\"version\": 1, \"click_events\": true, \"stop_signal\": {}",
+                nix::sys::signal::Signal::SIGCONT as i8
+            )
+        } else {
+            "\"version\": 1, \"click_events\": true".to_string()
+        };
+        Some(format!("{{{}}}\n[", initialise))
+    }
+
+    fn render(&self, blocks: &RenderedBlocks, config: &Config) -> Result<String> {
+        let mut last_bg: Option<String> = None;
+
+        let mut rendered_blocks = vec![];
+
+        /* To always start with the same alternating tint on the right side of the
+         * bar it is easiest to flip the starting tint if an even number of blocks
+         * is visible. This way, the last block should always be untinted.
+         */
+        let mut alternator = blocks.len() % 2 == 0;
+
+        for (block_id, widgets) in blocks {
+            // Get the final JSON from all the widgets for this block
+            let mut rendered_widgets = widgets
+                .iter()
+                .map(|widget| {
+                    let mut w_json: serde_json::Value = widget.get_rendered().to_owned();
+                    // Lets click events be traced back to the block
+                    w_json["instance"] = json!(block_id);
+                    if alternator {
+                        // Apply tint for all widgets of every second block
+                        *w_json.get_mut("background").unwrap() = json!(add_colors(
+                            w_json["background"].as_str(),
+                            config.theme.alternating_tint_bg.as_deref()
+                        )
+                        .unwrap());
+                    }
+                    w_json
+                })
+                .collect::<Vec<serde_json::Value>>();
+
+            alternator = !alternator;
+
+            if config.theme.native_separators {
+                // Re-add native separator on last widget for native theme
+                *rendered_widgets
+                    .last_mut()
+                    .unwrap()
+                    .get_mut("separator")
+                    .unwrap() = json!(null);
+                *rendered_widgets
+                    .last_mut()
+                    .unwrap()
+                    .get_mut("separator_block_width")
+                    .unwrap() = json!(null);
+            }
+
+            // Serialize and concatenate widgets
+            let block_str = rendered_widgets
+                .iter()
+                .map(|w| w.to_string())
+                .collect::<Vec<String>>()
+                .join(",");
+
+            if config.theme.native_separators {
+                // Skip separator block for native theme
+                rendered_blocks.push(block_str.to_string());
+                continue;
+            }
+
+            // The first widget's BG is used to get the FG color for the current separator
+            let first_bg = rendered_widgets.first().unwrap()["background"]
+                .as_str()
+                .internal_error("util", "couldn't get background color")?;
+
+            let sep_fg = if config.theme.separator_fg == Some("auto".to_string()) {
+                Some(first_bg.to_string())
+            } else {
+                config.theme.separator_fg.clone()
+            };
+
+            // The separator's BG is the last block's last widget's BG
+            let sep_bg = if config.theme.separator_bg == Some("auto".to_string()) {
+                last_bg
+            } else {
+                config.theme.separator_bg.clone()
+            };
+
+            let separator = json!({
+                "full_text": config.theme.separator,
+                "separator": false,
+                "separator_block_width": 0,
+                "background": sep_bg,
+                "color": sep_fg,
+                "markup": "pango"
+            });
+
+            rendered_blocks.push(format!("{},{}", separator, block_str));
+
+            // The last widget's BG is used to get the BG color for the next separator
+            last_bg = Some(
+                rendered_widgets.last().unwrap()["background"]
+                    .as_str()
+                    .internal_error("util", "couldn't get background color")?
+                    .to_string(),
+            );
+        }
+
+        Ok(format!("[{}],", rendered_blocks.join(",")))
+    }
+
+    fn render_error(&self, message: &str) -> String {
+        let error = TextWidget::new(Default::default())
+            .with_state(State::Critical)
+            .with_text(message);
+        serde_json::to_string(&[error.get_rendered()]).expect("failed to serialize error message")
+    }
+
+    fn event_parser(&self) -> Option<EventParser> {
+        Some(parse_i3bar_event)
+    }
+}
+
+/// JSON for a waybar `custom` module with `"return-type": "json"`.
+///
+/// By default the text is left uncolored and the most severe state of the widgets is
+/// reported as `class`, so that waybar's CSS styles the module. With `full`, every block is
+/// colored by the theme instead, so that a single module can replace a whole bar.
+pub struct Waybar {
+    full: bool,
+}
+
+fn severity(state: State) -> u8 {
+    match state {
+        State::Idle => 0,
+        State::Good => 1,
+        State::Info => 2,
+        State::Warning => 3,
+        State::Critical => 4,
+    }
+}
+
+fn state_class(state: State) -> &'static str {
+    match state {
+        State::Idle => "idle",
+        State::Good => "good",
+        State::Info => "info",
+        State::Warning => "warning",
+        State::Critical => "critical",
+    }
+}
+
+fn full_text(widget: &dyn I3BarWidget) -> &str {
+    widget.get_rendered()["full_text"].as_str().unwrap_or("")
+}
+
+/// Concatenates the widgets of all blocks, with separators between the blocks, where `text_of`
+/// gives the text of a widget in a block and `span` gives the text with the given background
+/// and foreground colors.
+fn render_colored<T, F>(blocks: &RenderedBlocks, config: &Config, text_of: T, span: F) -> String
+where
+    T: Fn(&str, &dyn I3BarWidget) -> String,
+    F: Fn(&str, Option<&str>, Option<&str>) -> String,
+{
+    let theme = &config.theme;
+    let mut text = String::new();
+    let mut last_bg: Option<&str> = None;
+    for (block_id, widgets) in blocks {
+        let first_bg = widgets[0].get_rendered()["background"].as_str();
+        if !theme.native_separators && !text.is_empty() {
+            let sep_fg = match theme.separator_fg.as_deref() {
+                Some("auto") => first_bg,
+                fg => fg,
+            };
+            let sep_bg = match theme.separator_bg.as_deref() {
+                Some("auto") => last_bg,
+                bg => bg,
+            };
+            text.push_str(&span(&theme.separator, sep_bg, sep_fg));
+        }
+        for widget in widgets {
+            let rendered = widget.get_rendered();
+            let bg = rendered["background"].as_str();
+            text.push_str(&span(
+                &text_of(*block_id, *widget),
+                bg,
+                rendered["color"].as_str(),
+            ));
+            last_bg = bg;
+        }
+    }
+    text
+}
+
+/// Wraps text in a pango span with the given colors. Pango ignores the alpha channel.
+fn pango_span(text: &str, background: Option<&str>, foreground: Option<&str>) -> String {
+    let mut attributes = String::new();
+    if let Some(bg) = background {
+        attributes.push_str(&format!(" background=\"{}\"", bg.get(..7).unwrap_or(bg)));
+    }
+    if let Some(fg) = foreground {
+        attributes.push_str(&format!(" foreground=\"{}\"", fg.get(..7).unwrap_or(fg)));
+    }
+    if attributes.is_empty() {
+        text.to_string()
+    } else {
+        format!("<span{}>{}</span>", attributes, text)
+    }
+}
+
+impl Output for Waybar {
+    fn render(&self, blocks: &RenderedBlocks, config: &Config) -> Result<String> {
+        let widgets = || blocks.iter().flat_map(|(_, widgets)| widgets.iter());
+        let state = widgets()
+            .map(|widget| widget.state())
+            .max_by_key(|&state| severity(state))
+            .unwrap_or(State::Idle);
+        let percentage = widgets().find_map(|widget| widget.percentage());
+        let tooltip = blocks
+            .iter()
+            .map(|(_, widgets)| {
+                widgets
+                    .iter()
+                    .map(|widget| full_text(*widget))
+                    .collect::<String>()
+                    .trim()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join("\n");
+        let text = if self.full {
+            render_colored(
+                blocks,
+                config,
+                |_, widget| full_text(widget).to_string(),
+                pango_span,
+            )
+        } else {
+            widgets()
+                .map(|widget| full_text(*widget))
+                .collect::<String>()
+                .trim()
+                .to_string()
+        };
+
+        let mut module = json!({
+            "text": text,
+            "tooltip": tooltip,
+            "class": state_class(state),
+        });
+        if let Some(percentage) = percentage {
+            module["percentage"] = json!(percentage.clamp(0.0, 100.0).round() as u8);
+        }
+        Ok(module.to_string())
+    }
+
+    fn render_error(&self, message: &str) -> String {
+        json!({
+            "text": message,
+            "tooltip": message,
+            "class": "critical",
+        })
+        .to_string()
+    }
+}
+
+/// A single line of text, for terminals, logs or the status line of tmux.
+///
+/// Plain text drops all colors and pango markup. For tmux, the colors of the theme are kept
+/// as `#[fg=..,bg=..]` codes.
+pub struct Plain {
+    tmux: bool,
+}
+
+/// Removes the tags from pango markup and resolves its character entities.
+fn strip_markup(text: &str) -> String {
+    let mut stripped = String::new();
+    let mut in_tag = false;
+    for c in text.chars() {
+        match c {
+            '<' => in_tag = true,
+            '>' if in_tag => in_tag = false,
+            c if !in_tag => stripped.push(c),
+            _ => {}
+        }
+    }
+    stripped
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&apos;", "'")
+        .replace("&amp;", "&")
+}
+
+fn plain_text(widget: &dyn I3BarWidget) -> String {
+    let rendered = widget.get_rendered();
+    if rendered["markup"].as_str() == Some("pango") {
+        strip_markup(full_text(widget))
+    } else {
+        full_text(widget).to_string()
+    }
+}
+
+/// Prefixes text with tmux color codes. Like pango, tmux has no alpha channel.
+fn tmux_span(text: &str, background: Option<&str>, foreground: Option<&str>) -> String {
+    let color = |color: Option<&str>| match color {
+        Some(color) => color.get(..7).unwrap_or(color).to_string(),
+        None => "default".to_string(),
+    };
+    format!(
+        "#[fg={},bg={}]{}",
+        color(foreground),
+        color(background),
+        text.replace('#', "##")
+    )
+}
+
+impl Output for Plain {
+    fn render(&self, blocks: &RenderedBlocks, config: &Config) -> Result<String> {
+        if self.tmux {
+            let text = render_colored(blocks, config, |_, widget| plain_text(widget), tmux_span);
+            return Ok(format!("{}#[default]", text));
+        }
+        Ok(blocks
+            .iter()
+            .map(|(_, widgets)| {
+                widgets
+                    .iter()
+                    .map(|widget| plain_text(*widget))
+                    .collect::<String>()
+                    .trim()
+                    .to_string()
+            })
+            .collect::<Vec<_>>()
+            .join(" | "))
+    }
+
+    fn render_error(&self, message: &str) -> String {
+        message.replace('\n', " ")
+    }
+}
+
+/// Formatting codes for lemonbar, which are also mostly understood by dzen2 forks.
+///
+/// Every widget is a click area for each mouse button, whose command is printed by lemonbar
+/// and can be sent back on stdin, e.g. through a named pipe.
+pub struct Lemonbar;
+
+/// Converts a theme color to lemonbar's `#AARRGGBB`, or `-` for the default color.
+fn lemonbar_color(color: Option<&str>) -> String {
+    match color {
+        Some(color) if color.len() == 9 => format!("#{}{}", &color[7..], &color[1..7]),
+        Some(color) => color.to_string(),
+        None => "-".to_string(),
+    }
+}
+
+fn lemonbar_span(text: &str, background: Option<&str>, foreground: Option<&str>) -> String {
+    format!(
+        "%{{B{}}}%{{F{}}}{}",
+        lemonbar_color(background),
+        lemonbar_color(foreground),
+        text
+    )
+}
+
+fn lemonbar_widget(block_id: &str, widget: &dyn I3BarWidget) -> String {
+    let name = widget.get_rendered()["name"].as_str();
+    let mut text = plain_text(widget).replace('%', "%%");
+    for button in 1..=5 {
+        text = format!(
+            "%{{A{}:{}:}}{}%{{A}}",
+            button,
+            lemonbar_command(button, block_id, name).replace(':', "\\:"),
+            text
+        );
+    }
+    text
+}
+
+impl Output for Lemonbar {
+    fn render(&self, blocks: &RenderedBlocks, config: &Config) -> Result<String> {
+        let text = render_colored(blocks, config, lemonbar_widget, lemonbar_span);
+        Ok(format!("%{{r}}{}%{{B-}}%{{F-}}", text))
+    }
+
+    fn render_error(&self, message: &str) -> String {
+        let theme = Theme::default();
+        let (bg, fg) = State::Critical.theme_keys(&theme);
+        lemonbar_span(
+            &message.replace('\n', " ").replace('%', "%%"),
+            bg.as_deref(),
+            fg.as_deref(),
+        )
+    }
+
+    fn event_parser(&self) -> Option<EventParser> {
+        Some(parse_lemonbar_event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_markup() {
+        assert_eq!(
+            strip_markup("<span foreground=\"#ff0000\">a &amp; b</span> &lt;c&gt;"),
+            "a & b <c>"
+        );
+        assert_eq!(strip_markup("50% too"), "50% too");
+    }
+
+    #[test]
+    fn test_tmux_span() {
+        assert_eq!(
+            tmux_span("#1", Some("#112233FF"), None),
+            "#[fg=default,bg=#112233]##1"
+        );
+    }
+
+    #[test]
+    fn test_lemonbar_span() {
+        assert_eq!(
+            lemonbar_span("a", Some("#11223380"), None),
+            "%{B#80112233}%{F-}a"
+        );
+    }
+
+    #[test]
+    fn test_waybar_percentage() {
+        let config = Config::default();
+        let mut battery = TextWidget::new(config.clone()).with_text("80%");
+        // The gradient value of a full battery is good, which is not its charge
+        battery.set_value(Some(0.0));
+        battery.set_percentage(Some(80.0));
+        let label = TextWidget::new(config.clone()).with_text("bat");
+        let blocks = [("battery", vec![&label as &dyn I3BarWidget, &battery])];
+        let rendered: serde_json::Value =
+            serde_json::from_str(&Waybar { full: false }.render(&blocks, &config).unwrap())
+                .unwrap();
+        assert_eq!(rendered["percentage"], 80);
+
+        let blocks = [("label", vec![&label as &dyn I3BarWidget])];
+        let rendered: serde_json::Value =
+            serde_json::from_str(&Waybar { full: false }.render(&blocks, &config).unwrap())
+                .unwrap();
+        assert!(rendered.get("percentage").is_none());
+    }
+}
@@ -0,0 +1,252 @@
+//! A single thread that waits on the file descriptors of all blocks at once.
+//!
+//! Blocks that are driven by events, like inotify, register their file descriptor here instead
+//! of blocking a thread of their own on it. The thread sleeps in `poll` until one of them is
+//! readable, so idle blocks cause no wakeups, and it asks for an update of the block right
+//! away.
+//!
+//! Only the inotify blocks and the journal are on the reactor so far. The D-Bus blocks still
+//! wait in `incoming` on a thread each, waking up at its timeout, and PulseAudio runs its own
+//! mainloop thread. Moving them here is left for a follow-up: a D-Bus connection of `ffidisp`
+//! cannot be sent to another thread, so the reactor would have to open it on its own thread.
+
+use std::io;
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use crossbeam_channel::{Receiver, Sender};
+use lazy_static::lazy_static;
+use nix::errno::Errno;
+use nix::fcntl::OFlag;
+use nix::poll::{poll, PollFd, PollFlags};
+use nix::unistd::{pipe2, read, write};
+
+use crate::errors::*;
+use crate::scheduler::Task;
+
+/// Called on the reactor thread while its file descriptor is readable, until it returns
+/// `false`. It owns what the file descriptor belongs to, which is closed when it is dropped.
+type Handler = Box<dyn FnMut() -> bool + Send>;
+
+struct Source {
+    fd: RawFd,
+    handler: Handler,
+}
+
+/// The handle to the reactor thread, which is started on first use.
+struct Reactor {
+    sources: Sender<Source>,
+    /// The write end of a pipe that wakes the thread up to pick up new sources.
+    wake: RawFd,
+}
+
+lazy_static! {
+    static ref REACTOR: Mutex<Option<Reactor>> = Mutex::new(None);
+}
+
+/// Calls `handler` on the reactor thread whenever `fd` is readable, until it returns `false`
+/// or the file descriptor is closed on the other end. `handler` should own what `fd` belongs
+/// to, so that it stays open while it is watched.
+pub fn watch<F>(fd: RawFd, handler: F) -> Result<()>
+where
+    F: FnMut() -> bool + Send + 'static,
+{
+    let mut reactor = REACTOR
+        .lock()
+        .internal_error("reactor", "failed to acquire lock")?;
+    if reactor.is_none() {
+        *reactor = Some(start()?);
+    }
+    let reactor = reactor
+        .as_ref()
+        .internal_error("reactor", "the reactor is not running")?;
+    reactor
+        .sources
+        .send(Source {
+            fd,
+            handler: Box::new(handler),
+        })
+        .internal_error("reactor", "the reactor thread has stopped")?;
+    match write(reactor.wake, &[0]) {
+        // A full pipe already wakes the thread up
+        Ok(_) | Err(nix::Error::Sys(Errno::EAGAIN)) => Ok(()),
+        Err(e) => Err(e).internal_error("reactor", "failed to wake up the reactor thread"),
+    }
+}
+
+/// Requests an update of the block `id` whenever `source` is readable and `changed` says that
+/// the block changed, until the block is gone or reading fails.
+///
+/// Updates are requested at most once every `min_delay`. A change within that time is
+/// requested for the end of it, so that a burst of events only updates the block once more.
+pub fn request_updates<S, F>(
+    source: S,
+    id: String,
+    send: Sender<Task>,
+    min_delay: Duration,
+    mut changed: F,
+) -> Result<()>
+where
+    S: AsRawFd + Send + 'static,
+    F: FnMut(&mut S) -> io::Result<bool> + Send + 'static,
+{
+    let fd = source.as_raw_fd();
+    let mut source = source;
+    let mut last: Option<Instant> = None;
+    watch(fd, move || {
+        match changed(&mut source) {
+            Ok(true) => {}
+            Ok(false) => return true,
+            Err(ref e) if e.kind() == io::ErrorKind::WouldBlock => return true,
+            Err(_) => return false,
+        }
+        let now = Instant::now();
+        let update_time = match last {
+            Some(last) if now < last + min_delay => last + min_delay,
+            _ => now,
+        };
+        last = Some(update_time);
+        send.send(Task {
+            id: id.clone(),
+            update_time,
+        })
+        .is_ok()
+    })
+}
+
+fn start() -> Result<Reactor> {
+    let (wakeup, wake) = pipe2(OFlag::O_NONBLOCK | OFlag::O_CLOEXEC)
+        .internal_error("reactor", "failed to create the wakeup pipe")?;
+    let (tx, rx) = crossbeam_channel::unbounded();
+    thread::Builder::new()
+        .name("reactor".into())
+        .spawn(move || run(wakeup, rx))
+        .internal_error("reactor", "failed to start the reactor thread")?;
+    Ok(Reactor { sources: tx, wake })
+}
+
+fn run(wakeup: RawFd, new_sources: Receiver<Source>) {
+    let mut sources: Vec<Source> = Vec::new();
+    loop {
+        let mut fds = Vec::with_capacity(sources.len() + 1);
+        fds.push(PollFd::new(wakeup, PollFlags::POLLIN));
+        fds.extend(
+            sources
+                .iter()
+                .map(|source| PollFd::new(source.fd, PollFlags::POLLIN)),
+        );
+        match poll(&mut fds, -1) {
+            Ok(_) => {}
+            // Signals for the main thread can interrupt the wait
+            Err(nix::Error::Sys(Errno::EINTR)) => continue,
+            Err(e) => {
+                eprintln!("The reactor thread failed to wait for events: {}", e);
+                return;
+            }
+        }
+        let events = fds
+            .iter()
+            .map(|fd| fd.revents().unwrap_or_else(PollFlags::empty))
+            .collect::<Vec<_>>();
+
+        // Dropping a source closes its file descriptor
+        sources = sources
+            .into_iter()
+            .zip(events[1..].iter())
+            .filter_map(|(mut source, &events)| {
+                if events.contains(PollFlags::POLLIN) && !(source.handler)() {
+                    return None;
+                }
+                let closed = PollFlags::POLLERR | PollFlags::POLLHUP | PollFlags::POLLNVAL;
+                if events.intersects(closed) {
+                    return None;
+                }
+                Some(source)
+            })
+            .collect();
+
+        if events[0].contains(PollFlags::POLLIN) {
+            let mut buffer = [0; 64];
+            while let Ok(n) = read(wakeup, &mut buffer) {
+                if n == 0 {
+                    break;
+                }
+            }
+            sources.extend(new_sources.try_iter());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::io::{Read, Write};
+    use std::os::unix::io::FromRawFd;
+
+    fn pipe() -> (File, File) {
+        let (reader, writer) = pipe2(OFlag::O_NONBLOCK | OFlag::O_CLOEXEC).unwrap();
+        unsafe { (File::from_raw_fd(reader), File::from_raw_fd(writer)) }
+    }
+
+    #[test]
+    fn test_request_updates() {
+        let (reader, mut writer) = pipe();
+        let (tx, rx) = crossbeam_channel::unbounded();
+        request_updates(
+            reader,
+            "pipe".to_string(),
+            tx,
+            Duration::from_secs(10),
+            |reader| {
+                let mut buffer = [0; 16];
+                let n = reader.read(&mut buffer)?;
+                Ok(buffer[..n].contains(&b'!'))
+            },
+        )
+        .unwrap();
+
+        writer.write_all(b"!").unwrap();
+        let first = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(first.id, "pipe");
+
+        // Data that is no change requests nothing
+        writer.write_all(b".").unwrap();
+        assert!(rx.recv_timeout(Duration::from_millis(200)).is_err());
+
+        // A change right after the first one is requested for later
+        writer.write_all(b"!").unwrap();
+        let second = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(
+            second.update_time,
+            first.update_time + Duration::from_secs(10)
+        );
+    }
+
+    #[test]
+    fn test_closed_source_is_dropped() {
+        struct Dropped(Sender<()>);
+        impl Drop for Dropped {
+            fn drop(&mut self) {
+                self.0.send(()).unwrap();
+            }
+        }
+
+        let (reader, writer) = pipe();
+        let (tx, rx) = crossbeam_channel::unbounded();
+        let dropped = Dropped(tx);
+        let mut reader = reader;
+        watch(reader.as_raw_fd(), move || {
+            let _ = &dropped;
+            let mut buffer = [0; 16];
+            reader.read(&mut buffer).is_ok()
+        })
+        .unwrap();
+
+        // Closing the write end hangs up the pipe, which drops the handler
+        drop(writer);
+        assert!(rx.recv_timeout(Duration::from_secs(5)).is_ok());
+    }
+}
@@ -2,8 +2,7 @@ use crate::blocks::Update;
 use std::cmp;
 use std::collections::{BinaryHeap, HashMap};
 use std::fmt;
-use std::thread;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use crate::blocks::Block;
 use crate::errors::*;
@@ -45,13 +44,13 @@ pub struct UpdateScheduler {
 }
 
 impl UpdateScheduler {
-    pub fn new(blocks: &[Box<dyn Block>]) -> UpdateScheduler {
+    pub fn new(ids: &[String]) -> UpdateScheduler {
         let mut schedule = BinaryHeap::new();
 
         let now = Instant::now();
-        for block in blocks.iter() {
+        for id in ids {
             schedule.push(Task {
-                id: String::from(block.id()),
+                id: id.clone(),
                 update_time: now,
             });
         }
@@ -74,24 +73,18 @@ impl UpdateScheduler {
         }
     }
 
+    /// Updates all blocks whose update is due. This never blocks: if the timer fired early,
+    /// nothing is updated and the caller simply waits for the next update time again.
     pub fn do_scheduled_updates(
         &mut self,
         block_map: &mut HashMap<String, &mut dyn Block>,
     ) -> Result<()> {
-        let t = self
-            .schedule
-            .pop()
-            .internal_error("scheduler", "schedule is empty")?;
-        let mut tasks_next = vec![t.clone()];
-
-        while !self.schedule.is_empty()
-            && t.update_time
-                == self
-                    .schedule
-                    .peek()
-                    .internal_error("scheduler", "schedule is empty")?
-                    .update_time
-        {
+        let now = Instant::now();
+        let mut tasks_next = vec![];
+        while let Some(task) = self.schedule.peek() {
+            if task.update_time > now {
+                break;
+            }
             tasks_next.push(
                 self.schedule
                     .pop()
@@ -99,29 +92,121 @@ impl UpdateScheduler {
             )
         }
 
-        let now = Instant::now();
-        if t.update_time > now {
-            thread::sleep(t.update_time - now);
-        }
-
-        let now = Instant::now();
-
         for task in tasks_next {
             if let Some(dur) = block_map
                 .get_mut(&task.id)
                 .internal_error("scheduler", "could not get required block")?
                 .update()?
             {
-                match dur {
-                    Update::Every(d) => self.schedule.push(Task {
-                        id: task.id,
-                        update_time: now + d,
-                    }),
-                    Update::Once => {} // do not schedule this task again
-                }
+                self.push(task.id, dur, now);
             }
         }
 
         Ok(())
     }
+
+    /// Schedules the next update of a block that was updated outside of the schedule,
+    /// e.g. after an async update request. A block that is already scheduled is updated at
+    /// the earlier of both times.
+    pub fn schedule(&mut self, id: &str, update: Update) {
+        let now = Instant::now();
+        let update_time = match update {
+            Update::Every(d) => now + d,
+            Update::Aligned(d) => now + time_to_boundary(d),
+            Update::Once => return,
+        };
+        self.schedule_task(Task {
+            id: id.to_string(),
+            update_time,
+        });
+    }
+
+    /// Schedules an update request for later, like a block asking for a check shortly
+    /// after a click. A block is only updated once at the earliest of its update times.
+    pub fn schedule_task(&mut self, task: Task) {
+        if self
+            .schedule
+            .iter()
+            .any(|t| t.id == task.id && t.update_time <= task.update_time)
+        {
+            return;
+        }
+        let id = task.id.clone();
+        self.schedule = self.schedule.drain().filter(|t| t.id != id).collect();
+        self.schedule.push(task);
+    }
+
+    fn push(&mut self, id: String, update: Update, now: Instant) {
+        match update {
+            Update::Every(d) => self.schedule.push(Task {
+                id,
+                update_time: now + d,
+            }),
+            Update::Aligned(d) => self.schedule.push(Task {
+                id,
+                update_time: now + time_to_boundary(d),
+            }),
+            Update::Once => {} // do not schedule this task again
+        }
+    }
+}
+
+/// Returns the time left until the wall clock reaches the next multiple of `interval`.
+pub fn time_to_boundary(interval: Duration) -> Duration {
+    let interval_nanos = interval.as_nanos();
+    if interval_nanos == 0 {
+        return interval;
+    }
+    let since_epoch = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos();
+    Duration::from_nanos((interval_nanos - since_epoch % interval_nanos) as u64)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn task(id: &str, update_time: Instant) -> Task {
+        Task {
+            id: id.to_string(),
+            update_time,
+        }
+    }
+
+    #[test]
+    fn test_schedule_takes_minimum() {
+        let now = Instant::now();
+        let mut scheduler = UpdateScheduler::new(&[]);
+        scheduler.schedule_task(task("a", now + Duration::from_secs(60)));
+        // A block that asks for a shorter interval after a requested update gets it
+        scheduler.schedule("a", Update::Every(Duration::from_secs(1)));
+        let next = scheduler.time_to_next_update().unwrap();
+        assert!(next <= Duration::from_secs(1));
+
+        scheduler.schedule("a", Update::Every(Duration::from_secs(30)));
+        scheduler.schedule("a", Update::Once);
+        assert_eq!(scheduler.schedule.len(), 1);
+        assert!(scheduler.time_to_next_update().unwrap() <= Duration::from_secs(1));
+    }
+
+    #[test]
+    fn test_schedule_task_keeps_earliest() {
+        let now = Instant::now();
+        let mut scheduler = UpdateScheduler::new(&[]);
+        scheduler.schedule_task(task("a", now + Duration::from_secs(10)));
+        scheduler.schedule_task(task("b", now + Duration::from_secs(5)));
+        scheduler.schedule_task(task("a", now + Duration::from_secs(1)));
+        scheduler.schedule_task(task("a", now + Duration::from_secs(20)));
+
+        let tasks = scheduler.schedule.into_sorted_vec();
+        assert_eq!(tasks.len(), 2);
+        assert!(tasks
+            .iter()
+            .any(|t| t.id == "a" && t.update_time == now + Duration::from_secs(1)));
+        assert!(tasks
+            .iter()
+            .any(|t| t.id == "b" && t.update_time == now + Duration::from_secs(5)));
+    }
 }
@@ -1,5 +1,6 @@
 use std::io;
-use std::process::{Command, Stdio};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::{Arc, Mutex};
 use std::thread;
 
 /// Spawns a new child process. This closes stdin and stdout, and returns to the caller after the
@@ -16,3 +17,106 @@ pub fn spawn_child_async(name: &str, args: &[&str]) -> io::Result<()> {
         .unwrap();
     Ok(())
 }
+
+#[derive(Default)]
+struct Slot {
+    stopped: bool,
+    child: Option<Child>,
+}
+
+fn kill(child: Option<Child>) {
+    if let Some(mut child) = child {
+        let _ = child.kill();
+        let _ = child.wait();
+    }
+}
+
+/// The long running process of a block, like a monitor whose output is read in a thread.
+/// It is killed when the block drops this, e.g. when a reload removes the block, rather than
+/// running on until the thread notices that the block is gone.
+#[derive(Default)]
+pub struct OwnedChild(Arc<Mutex<Slot>>);
+
+/// Lets the thread that reads the process of an `OwnedChild` start it.
+#[derive(Clone)]
+pub struct ChildHandle(Arc<Mutex<Slot>>);
+
+/// The pipes of a process started through a `ChildHandle`.
+pub struct ChildPipes {
+    pub stdin: Option<ChildStdin>,
+    pub stdout: Option<ChildStdout>,
+}
+
+impl OwnedChild {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn handle(&self) -> ChildHandle {
+        ChildHandle(self.0.clone())
+    }
+}
+
+impl Drop for OwnedChild {
+    fn drop(&mut self) {
+        if let Ok(mut slot) = self.0.lock() {
+            slot.stopped = true;
+            kill(slot.child.take());
+        }
+    }
+}
+
+impl ChildHandle {
+    /// Spawns the command in place of the previous process. Once the owner is gone, nothing is
+    /// spawned and `None` is returned.
+    pub fn spawn(&self, command: &mut Command) -> io::Result<Option<ChildPipes>> {
+        let mut slot = self
+            .0
+            .lock()
+            .map_err(|_| io::Error::new(io::ErrorKind::Other, "lock has been poisoned"))?;
+        if slot.stopped {
+            return Ok(None);
+        }
+        kill(slot.child.take());
+        let mut child = command.spawn()?;
+        let pipes = ChildPipes {
+            stdin: child.stdin.take(),
+            stdout: child.stdout.take(),
+        };
+        slot.child = Some(child);
+        Ok(Some(pipes))
+    }
+
+    /// Kills the process, if it is still running.
+    pub fn kill(&self) {
+        if let Ok(mut slot) = self.0.lock() {
+            kill(slot.child.take());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Read;
+
+    #[test]
+    fn test_owned_child_is_killed_on_drop() {
+        let owner = OwnedChild::new();
+        let handle = owner.handle();
+        let mut stdout = handle
+            .spawn(Command::new("sleep").arg("30").stdout(Stdio::piped()))
+            .unwrap()
+            .unwrap()
+            .stdout
+            .unwrap();
+        drop(owner);
+        // The pipe is closed once the process is gone
+        let mut output = String::new();
+        assert_eq!(stdout.read_to_string(&mut output).unwrap(), 0);
+        assert!(handle
+            .spawn(Command::new("sleep").arg("30"))
+            .unwrap()
+            .is_none());
+    }
+}
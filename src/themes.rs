@@ -207,7 +207,7 @@ lazy_static! {
 
 }
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 #[serde(deny_unknown_fields)]
 pub struct Theme {
     pub native_separators: bool,
@@ -270,6 +270,47 @@ impl Theme {
     }
 }
 
+impl Theme {
+    /// The configured colors, by the name of their key.
+    pub fn colors(&self) -> [(&'static str, Option<&str>); 13] {
+        [
+            ("idle_bg", self.idle_bg.as_deref()),
+            ("idle_fg", self.idle_fg.as_deref()),
+            ("info_bg", self.info_bg.as_deref()),
+            ("info_fg", self.info_fg.as_deref()),
+            ("good_bg", self.good_bg.as_deref()),
+            ("good_fg", self.good_fg.as_deref()),
+            ("warning_bg", self.warning_bg.as_deref()),
+            ("warning_fg", self.warning_fg.as_deref()),
+            ("critical_bg", self.critical_bg.as_deref()),
+            ("critical_fg", self.critical_fg.as_deref()),
+            ("separator_bg", self.separator_bg.as_deref()),
+            ("separator_fg", self.separator_fg.as_deref()),
+            ("alternating_tint_bg", self.alternating_tint_bg.as_deref()),
+        ]
+    }
+
+    /// Returns the theme with the colors and separators that are set in the overrides replaced.
+    pub fn with_overrides(self, overrides: ThemeOverrides) -> Theme {
+        let mut theme = self;
+        theme.idle_bg = overrides.idle_bg.or(theme.idle_bg);
+        theme.idle_fg = overrides.idle_fg.or(theme.idle_fg);
+        theme.info_bg = overrides.info_bg.or(theme.info_bg);
+        theme.info_fg = overrides.info_fg.or(theme.info_fg);
+        theme.good_bg = overrides.good_bg.or(theme.good_bg);
+        theme.good_fg = overrides.good_fg.or(theme.good_fg);
+        theme.warning_bg = overrides.warning_bg.or(theme.warning_bg);
+        theme.warning_fg = overrides.warning_fg.or(theme.warning_fg);
+        theme.critical_bg = overrides.critical_bg.or(theme.critical_bg);
+        theme.critical_fg = overrides.critical_fg.or(theme.critical_fg);
+        theme.separator = overrides.separator.unwrap_or(theme.separator);
+        theme.separator_bg = overrides.separator_bg.or(theme.separator_bg);
+        theme.separator_fg = overrides.separator_fg.or(theme.separator_fg);
+        theme.alternating_tint_bg = overrides.alternating_tint_bg.or(theme.alternating_tint_bg);
+        theme
+    }
+}
+
 #[derive(Deserialize, Debug, Default, Clone)]
 #[serde(deny_unknown_fields)]
 pub struct ThemeOverrides {
@@ -307,20 +348,7 @@ impl ThemeConfig {
             None
         }?;
         if let Some(overrides) = self.overrides {
-            theme.idle_bg = overrides.idle_bg.or(theme.idle_bg);
-            theme.idle_fg = overrides.idle_fg.or(theme.idle_fg);
-            theme.info_bg = overrides.info_bg.or(theme.info_bg);
-            theme.info_fg = overrides.info_fg.or(theme.info_fg);
-            theme.good_bg = overrides.good_bg.or(theme.good_bg);
-            theme.good_fg = overrides.good_fg.or(theme.good_fg);
-            theme.warning_bg = overrides.warning_bg.or(theme.warning_bg);
-            theme.warning_fg = overrides.warning_fg.or(theme.warning_fg);
-            theme.critical_bg = overrides.critical_bg.or(theme.critical_bg);
-            theme.critical_fg = overrides.critical_fg.or(theme.critical_fg);
-            theme.separator = overrides.separator.unwrap_or(theme.separator);
-            theme.separator_bg = overrides.separator_bg.or(theme.separator_bg);
-            theme.separator_fg = overrides.separator_fg.or(theme.separator_fg);
-            theme.alternating_tint_bg = overrides.alternating_tint_bg.or(theme.alternating_tint_bg);
+            theme = theme.with_overrides(overrides);
         }
         Some(theme)
     }
@@ -1,19 +1,27 @@
 use num_traits::{clamp, ToPrimitive};
+use std::cell::RefCell;
 use std::collections::HashMap;
 use std::fmt::Display;
-use std::fs::{File, OpenOptions};
+use std::fs::{self, File, OpenOptions};
 use std::io::prelude::*;
 use std::io::BufReader;
 use std::path::{Path, PathBuf};
 use std::prelude::v1::String;
-use std::process::Command;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use crossbeam_channel::Sender;
+use dbus::arg::RefArg;
+use dbus::ffidisp::stdintf::org_freedesktop_dbus::Properties;
+use dbus::ffidisp::{BusType, Connection};
+use dbus::Message;
 use regex::Regex;
 use serde::de::DeserializeOwned;
 
-use crate::blocks::Block;
-use crate::config::Config;
 use crate::errors::*;
+use crate::scheduler::Task;
 
 pub const USR_SHARE_PATH: &str = "/usr/share/i3status-rust";
 
@@ -118,6 +126,234 @@ pub fn xdg_config_home() -> PathBuf {
     PathBuf::from(&config_path)
 }
 
+pub fn xdg_cache_home() -> PathBuf {
+    let cache_path = std::env::var("XDG_CACHE_HOME").unwrap_or(format!(
+        "{}/.cache",
+        std::env::var("HOME").unwrap_or_else(|_| "".to_string())
+    ));
+    PathBuf::from(&cache_path)
+}
+
+/// Expands a leading `~/` of a path to the home directory of the user.
+pub fn expand_home(path: &str) -> PathBuf {
+    if path.starts_with("~/") {
+        PathBuf::from(format!(
+            "{}{}",
+            std::env::var("HOME").unwrap_or_default(),
+            &path[1..]
+        ))
+    } else {
+        PathBuf::from(path)
+    }
+}
+
+/// Reads a token or a key from a file, where `~` stands for the home directory.
+pub fn read_token(block: &str, path: &str) -> Result<String> {
+    let path = expand_home(path);
+    let token = fs::read_to_string(&path).block_error(
+        block,
+        &format!("failed to read the token from {}", path.display()),
+    )?;
+    Ok(token.trim().to_string())
+}
+
+// Some services, like met.no and Nominatim, block requests without a user agent that
+// identifies the application
+const USER_AGENT: &str = concat!(
+    "i3status-rust/",
+    env!("CARGO_PKG_VERSION"),
+    " https://github.com/greshake/i3status-rust"
+);
+
+/// An HTTP request for `http_request`.
+pub struct HttpRequest<'a> {
+    pub method: &'a str,
+    pub url: &'a str,
+    /// Headers like `Authorization: Bearer <token>`
+    pub headers: &'a [String],
+    pub body: Option<&'a str>,
+    /// The user and password for basic authentication, like `user:password`
+    pub user: Option<&'a str>,
+    pub timeout: Duration,
+}
+
+/// The answer to an HTTP request, with the names of the headers in lowercase.
+#[derive(Debug, PartialEq)]
+pub struct HttpResponse {
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    pub body: Vec<u8>,
+}
+
+impl HttpResponse {
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+
+    pub fn text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn json(&self, block: &str) -> Result<serde_json::Value> {
+        serde_json::from_slice(&self.body).block_error(block, "Malformed JSON.")
+    }
+}
+
+/// Quotes a value for the configuration file of curl.
+fn curl_quote(value: &str) -> String {
+    format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+/// Splits what `curl --dump-header -` prints into the response. Redirects that curl followed
+/// come first, each with headers of its own.
+fn parse_http_response(output: &[u8]) -> Option<HttpResponse> {
+    let mut rest = output;
+    loop {
+        let end = rest.windows(4).position(|w| w == b"\r\n\r\n")?;
+        let head = String::from_utf8_lossy(&rest[..end]);
+        rest = &rest[end + 4..];
+        let mut lines = head.lines();
+        let status: u16 = lines.next()?.split_whitespace().nth(1)?.parse().ok()?;
+        if (status < 200 || (300..400).contains(&status)) && rest.starts_with(b"HTTP/") {
+            continue;
+        }
+        let headers = lines
+            .filter_map(|line| {
+                let mut parts = line.splitn(2, ':');
+                Some((
+                    parts.next()?.trim().to_lowercase(),
+                    parts.next()?.trim().to_string(),
+                ))
+            })
+            .collect();
+        return Some(HttpResponse {
+            status,
+            headers,
+            body: rest.to_vec(),
+        });
+    }
+}
+
+/// Makes an HTTP request with curl. The request goes through stdin, so that keys and tokens
+/// in the url or the headers do not show up in the process list. Only failing to reach the
+/// server is an error, the status of the response is up to the caller.
+pub fn http_request(block: &str, request: &HttpRequest) -> Result<HttpResponse> {
+    let mut config = format!(
+        "url = {}\nrequest = {}\nuser-agent = {}\n",
+        curl_quote(request.url),
+        curl_quote(request.method),
+        curl_quote(USER_AGENT)
+    );
+    for header in request.headers {
+        config.push_str(&format!("header = {}\n", curl_quote(header)));
+    }
+    if let Some(body) = request.body {
+        config.push_str(&format!("data-binary = {}\n", curl_quote(body)));
+    }
+    if let Some(user) = request.user {
+        config.push_str(&format!("user = {}\n", curl_quote(user)));
+    }
+
+    let mut child = Command::new("curl")
+        .args(&[
+            "--silent",
+            "--location",
+            "--compressed",
+            "--dump-header",
+            "-",
+            "-m",
+            &request.timeout.as_secs().max(1).to_string(),
+            "--config",
+            "-",
+        ])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .block_error(block, "failed to run curl")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        stdin
+            .write_all(config.as_bytes())
+            .block_error(block, "failed to write to curl")?;
+    }
+    let output = child
+        .wait_with_output()
+        .block_error(block, "failed to run curl")?;
+    if !output.status.success() {
+        return Err(BlockError(
+            block.to_string(),
+            "failed to reach the server".to_string(),
+        ));
+    }
+    parse_http_response(&output.stdout).block_error(block, "Invalid curl output")
+}
+
+/// Makes a GET request with curl, like `http_request`.
+pub fn http_get(
+    block: &str,
+    url: &str,
+    headers: &[String],
+    timeout: Duration,
+) -> Result<HttpResponse> {
+    http_request(
+        block,
+        &HttpRequest {
+            method: "GET",
+            url,
+            headers,
+            body: None,
+            user: None,
+            timeout,
+        },
+    )
+}
+
+/// The arguments of `openssl s_client` for a TLS connection to `host`. With `verify`, the
+/// connection fails unless the certificate is trusted and issued for `host`, as `-servername`
+/// only asks for the certificate of `host` without checking it.
+pub fn tls_client_args(host: &str, port: u16, verify: bool) -> Vec<String> {
+    let mut args = vec![
+        "s_client".to_string(),
+        "-quiet".to_string(),
+        "-connect".to_string(),
+        format!("{}:{}", host, port),
+        "-servername".to_string(),
+        host.to_string(),
+    ];
+    if verify {
+        args.extend(vec![
+            "-verify_return_error".to_string(),
+            "-verify_hostname".to_string(),
+            host.to_string(),
+        ]);
+    }
+    args
+}
+
+/// Turns a path like `.list[0].main.temp` into the JSON pointer `/list/0/main/temp`.
+/// Paths that start with `/` already are JSON pointers.
+pub fn json_pointer(path: &str) -> String {
+    if path.starts_with('/') || path.is_empty() {
+        return path.to_string();
+    }
+    path.trim_start_matches('.')
+        .replace('[', ".")
+        .replace(']', "")
+        .split('.')
+        .filter(|key| !key.is_empty())
+        // `~` and `/` are escaped in JSON pointers
+        .map(|key| format!("/{}", key.replace('~', "~0").replace('/', "~1")))
+        .collect()
+}
+
+/// Shows strings without quotes, and missing values and `null` as nothing.
+pub fn json_display(value: Option<&serde_json::Value>) -> String {
+    match value {
+        None | Some(serde_json::Value::Null) => String::new(),
+        Some(serde_json::Value::String(s)) => s.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
 pub fn deserialize_file<T>(file: &str) -> Result<T>
 where
     T: DeserializeOwned,
@@ -160,6 +396,244 @@ pub fn has_command(block_name: &str, command: &str) -> Result<bool> {
     Ok(exit_status.success())
 }
 
+const GEOCLUE: &str = "org.freedesktop.GeoClue2";
+const GEOCLUE_CLIENT: &str = "org.freedesktop.GeoClue2.Client";
+const GEOCLUE_LOCATION: &str = "org.freedesktop.GeoClue2.Location";
+/// https://www.freedesktop.org/software/geoclue/docs/geoclue-gclue-enums.html#GClueAccuracyLevel
+const GEOCLUE_ACCURACY_LEVEL_CITY: u32 = 4;
+/// How long to wait for GeoClue2 to find the location before giving up
+const GEOCLUE_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long to wait before looking for the location again while it is not known
+const LOCATE_RETRY: Duration = Duration::from_secs(60);
+
+/// A location found by a `Locator`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Location {
+    pub latitude: f64,
+    pub longitude: f64,
+    pub city: Option<String>,
+}
+
+/// Asks GeoClue2 for the location, which may come from WiFi networks, GPS or the IP address,
+/// depending on the device and on what the user allowed.
+fn geoclue_location() -> std::result::Result<Location, String> {
+    fn error<E>(message: &'static str) -> impl FnOnce(E) -> String {
+        move |_| message.to_string()
+    }
+
+    let c = Connection::get_private(BusType::System)
+        .map_err(error("Failed to establish D-Bus connection"))?;
+    let client: dbus::Path = c
+        .send_with_reply_and_block(
+            Message::new_method_call(
+                GEOCLUE,
+                "/org/freedesktop/GeoClue2/Manager",
+                "org.freedesktop.GeoClue2.Manager",
+                "GetClient",
+            )
+            .map_err(error("Failed to create D-Bus method call"))?,
+            1000,
+        )
+        .map_err(error("GeoClue2 is not available"))?
+        .get1()
+        .ok_or_else(|| "GeoClue2 did not return a client".to_string())?;
+    let client = client.to_string();
+
+    // GeoClue2 refuses to start clients without a desktop id
+    let client_proxy = c.with_path(GEOCLUE, &client, 1000);
+    client_proxy
+        .set(GEOCLUE_CLIENT, "DesktopId", "i3status-rs".to_string())
+        .map_err(error("Failed to configure the GeoClue2 client"))?;
+    client_proxy
+        .set(
+            GEOCLUE_CLIENT,
+            "RequestedAccuracyLevel",
+            GEOCLUE_ACCURACY_LEVEL_CITY,
+        )
+        .map_err(error("Failed to configure the GeoClue2 client"))?;
+    let client_call = |method: &str| {
+        let m = Message::new_method_call(GEOCLUE, &client, GEOCLUE_CLIENT, method)
+            .map_err(error("Failed to create D-Bus method call"))?;
+        c.send_with_reply_and_block(m, 1000)
+            .map(|_| ())
+            .map_err(error("Failed to query GeoClue2"))
+    };
+    client_call("Start")?;
+
+    // The location is "/" until the first one is found
+    let start = Instant::now();
+    let location = loop {
+        let location = client_proxy
+            .get::<Box<dyn RefArg>>(GEOCLUE_CLIENT, "Location")
+            .ok()
+            .and_then(|path| path.as_str().map(|s| s.to_string()))
+            .unwrap_or_else(|| "/".to_string());
+        if location != "/" {
+            break location;
+        }
+        if start.elapsed() > GEOCLUE_TIMEOUT {
+            let _ = client_call("Stop");
+            return Err("GeoClue2 did not find the location".to_string());
+        }
+        thread::sleep(Duration::from_millis(250));
+    };
+
+    let location_proxy = c.with_path(GEOCLUE, &location, 1000);
+    let coordinate = |name: &str| -> std::result::Result<f64, String> {
+        location_proxy
+            .get(GEOCLUE_LOCATION, name)
+            .map_err(error("Failed to query GeoClue2"))
+    };
+    let location = Location {
+        latitude: coordinate("Latitude")?,
+        longitude: coordinate("Longitude")?,
+        city: None,
+    };
+    let _ = client_call("Stop");
+    Ok(location)
+}
+
+/// Fetches a JSON document, or nothing if that fails.
+fn fetch_json(url: &str) -> Option<serde_json::Value> {
+    http_get("locator", url, &[], Duration::from_secs(3))
+        .ok()
+        .filter(HttpResponse::is_success)
+        .and_then(|response| response.json("locator").ok())
+}
+
+/// The location in the answer of ipapi.co.
+fn parse_ip_location(json: &serde_json::Value) -> Option<Location> {
+    Some(Location {
+        latitude: json.pointer("/latitude")?.as_f64()?,
+        longitude: json.pointer("/longitude")?.as_f64()?,
+        city: json
+            .pointer("/city")
+            .and_then(|v| v.as_str())
+            .map(|s| s.to_string()),
+    })
+}
+
+/// The city, town or village in the answer of Nominatim's reverse geocoding.
+fn parse_reverse_geocoding(json: &serde_json::Value) -> Option<String> {
+    let address = json.get("address")?;
+    ["city", "town", "village", "municipality"]
+        .iter()
+        .filter_map(|key| address.get(*key).and_then(|v| v.as_str()))
+        .next()
+        .map(|s| s.to_string())
+}
+
+/// Finds the location through GeoClue2. With `online`, the city of a location from GeoClue2
+/// comes from OpenStreetMap, and the IP address is the fallback if GeoClue2 is not available.
+fn find_location(online: bool) -> std::result::Result<Location, String> {
+    match geoclue_location() {
+        Ok(mut location) if online => {
+            // GeoClue2 only knows the coordinates
+            location.city = fetch_json(&format!(
+                "https://nominatim.openstreetmap.org/reverse?format=jsonv2&zoom=10&lat={:.4}&lon={:.4}",
+                location.latitude, location.longitude
+            ))
+            .and_then(|json| parse_reverse_geocoding(&json));
+            Ok(location)
+        }
+        Err(_) if online => fetch_json("https://ipapi.co/json/")
+            .and_then(|json| parse_ip_location(&json))
+            .ok_or_else(|| "Failed to find the location".to_string()),
+        result => result,
+    }
+}
+
+/// The last location that was found, and the error of the last search.
+#[derive(Default)]
+struct Located {
+    location: Option<Location>,
+    error: Option<String>,
+}
+
+/// Looks for the location on a thread of its own, because GeoClue2 can take seconds to find
+/// it. The last location is kept when a later search fails, like while offline.
+pub struct Locator {
+    block: &'static str,
+    located: Arc<Mutex<Located>>,
+}
+
+impl Locator {
+    /// Looks for the location every `interval`, and asks for an update of the block `id`
+    /// when it changes. With `online`, the IP location service and OpenStreetMap are used
+    /// as well.
+    pub fn start(
+        block: &'static str,
+        id: String,
+        send: Sender<Task>,
+        interval: Duration,
+        online: bool,
+    ) -> Result<Locator> {
+        let located = Arc::new(Mutex::new(Located::default()));
+        // The thread only holds on to the location while it updates it, so that it stops
+        // once a reload of the configuration dropped the block
+        let thread_located = Arc::downgrade(&located);
+        thread::Builder::new()
+            .name(format!("{}-locator", block))
+            .spawn(move || loop {
+                let found = find_location(online);
+                let shared = match thread_located.upgrade() {
+                    Some(shared) => shared,
+                    None => return,
+                };
+                let known = match shared.lock() {
+                    Ok(mut located) => {
+                        let changed = match found {
+                            Ok(location) => {
+                                let changed = located.location.as_ref() != Some(&location)
+                                    || located.error.is_some();
+                                located.location = Some(location);
+                                located.error = None;
+                                changed
+                            }
+                            // Errors only matter while there is no location
+                            Err(error) => {
+                                let changed = located.location.is_none()
+                                    && located.error.as_ref() != Some(&error);
+                                located.error = Some(error);
+                                changed
+                            }
+                        };
+                        if changed
+                            && send
+                                .send(Task {
+                                    id: id.clone(),
+                                    update_time: Instant::now(),
+                                })
+                                .is_err()
+                        {
+                            return;
+                        }
+                        located.location.is_some()
+                    }
+                    Err(_) => return,
+                };
+                drop(shared);
+                thread::sleep(if known { interval } else { LOCATE_RETRY });
+            })
+            .block_error(block, "failed to start the locating thread")?;
+        Ok(Locator { block, located })
+    }
+
+    /// The last location that was found, nothing while the first search is still running,
+    /// or the error of the last search if no location was found yet.
+    pub fn location(&self) -> Result<Option<Location>> {
+        let located = self
+            .located
+            .lock()
+            .block_error(self.block, "failed to acquire lock")?;
+        match (&located.location, &located.error) {
+            (Some(location), _) => Ok(Some(location.clone())),
+            (None, Some(error)) => Err(BlockError(self.block.to_string(), error.clone())),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
 macro_rules! match_range {
     ($a:expr, default: ($default:expr) {$($lower:expr ; $upper:expr => $e:expr),+}) => (
         match $a {
@@ -195,128 +669,6 @@ macro_rules! map_to_owned (
      };
 );
 
-pub fn print_blocks(
-    order: &[String],
-    block_map: &HashMap<String, &mut dyn Block>,
-    config: &Config,
-) -> Result<()> {
-    let mut last_bg: Option<String> = None;
-
-    let mut rendered_blocks = vec![];
-
-    /* To always start with the same alternating tint on the right side of the
-     * bar it is easiest to calculate the number of visible blocks here and
-     * flip the starting tint if an even number of blocks is visible. This way,
-     * the last block should always be untinted.
-     */
-    let visible_count = order
-        .iter()
-        .filter(|block_id| {
-            let block = block_map.get(block_id.as_str()).unwrap();
-            !block.view().is_empty()
-        })
-        .count();
-
-    let mut alternator = visible_count % 2 == 0;
-
-    for block_id in order {
-        let block = &(*(block_map
-            .get(block_id)
-            .internal_error("util", "couldn't get block by id")?));
-        let widgets = block.view();
-        if widgets.is_empty() {
-            continue;
-        }
-
-        // Get the final JSON from all the widgets for this block
-        let mut rendered_widgets = widgets
-            .iter()
-            .map(|widget| {
-                let mut w_json: serde_json::Value = widget.get_rendered().to_owned();
-                if alternator {
-                    // Apply tint for all widgets of every second block
-                    *w_json.get_mut("background").unwrap() = json!(add_colors(
-                        w_json["background"].as_str(),
-                        config.theme.alternating_tint_bg.as_deref()
-                    )
-                    .unwrap());
-                }
-                w_json
-            })
-            .collect::<Vec<serde_json::Value>>();
-
-        alternator = !alternator;
-
-        if config.theme.native_separators {
-            // Re-add native separator on last widget for native theme
-            *rendered_widgets
-                .last_mut()
-                .unwrap()
-                .get_mut("separator")
-                .unwrap() = json!(null);
-            *rendered_widgets
-                .last_mut()
-                .unwrap()
-                .get_mut("separator_block_width")
-                .unwrap() = json!(null);
-        }
-
-        // Serialize and concatenate widgets
-        let block_str = rendered_widgets
-            .iter()
-            .map(|w| w.to_string())
-            .collect::<Vec<String>>()
-            .join(",");
-
-        if config.theme.native_separators {
-            // Skip separator block for native theme
-            rendered_blocks.push(block_str.to_string());
-            continue;
-        }
-
-        // The first widget's BG is used to get the FG color for the current separator
-        let first_bg = rendered_widgets.first().unwrap()["background"]
-            .as_str()
-            .internal_error("util", "couldn't get background color")?;
-
-        let sep_fg = if config.theme.separator_fg == Some("auto".to_string()) {
-            Some(first_bg.to_string())
-        } else {
-            config.theme.separator_fg.clone()
-        };
-
-        // The separator's BG is the last block's last widget's BG
-        let sep_bg = if config.theme.separator_bg == Some("auto".to_string()) {
-            last_bg
-        } else {
-            config.theme.separator_bg.clone()
-        };
-
-        let separator = json!({
-            "full_text": config.theme.separator,
-            "separator": false,
-            "separator_block_width": 0,
-            "background": sep_bg,
-            "color": sep_fg,
-            "markup": "pango"
-        });
-
-        rendered_blocks.push(format!("{},{}", separator.to_string(), block_str));
-
-        // The last widget's BG is used to get the BG color for the next separator
-        last_bg = Some(
-            rendered_widgets.last().unwrap()["background"]
-                .as_str()
-                .internal_error("util", "couldn't get background color")?
-                .to_string(),
-        );
-    }
-
-    println!("[{}],", rendered_blocks.join(","));
-
-    Ok(())
-}
-
 pub fn color_from_rgba(
     color: &str,
 ) -> ::std::result::Result<(u8, u8, u8, u8), Box<dyn std::error::Error>> {
@@ -335,6 +687,24 @@ pub fn color_to_rgba(color: (u8, u8, u8, u8)) -> String {
     )
 }
 
+/// Linearly interpolates between two colors, `t` going from 0 (`a`) to 1 (`b`).
+pub fn mix_colors(
+    a: &str,
+    b: &str,
+    t: f64,
+) -> ::std::result::Result<String, Box<dyn std::error::Error>> {
+    let a = color_from_rgba(a)?;
+    let b = color_from_rgba(b)?;
+    let t = t.clamp(0.0, 1.0);
+    let mix = |x: u8, y: u8| (f64::from(x) + (f64::from(y) - f64::from(x)) * t).round() as u8;
+    Ok(color_to_rgba((
+        mix(a.0, b.0),
+        mix(a.1, b.1),
+        mix(a.2, b.2),
+        mix(a.3, b.3),
+    )))
+}
+
 // TODO: Allow for other non-additive tints
 pub fn add_colors(
     a: Option<&str>,
@@ -357,32 +727,82 @@ pub fn add_colors(
     }
 }
 
+thread_local! {
+    /// The numbers behind the placeholders rendered on this thread, while
+    /// `collect_rendered_numbers` runs.
+    static RENDERED_NUMBERS: RefCell<Option<Vec<f64>>> = RefCell::new(None);
+}
+
+/// Runs `f` and returns the numbers behind the placeholders that format templates rendered
+/// meanwhile, before precision or prefixes were applied, like the bytes per second of
+/// `{speed;M}`. Numbers that are collected by a nested call also count for the outer one.
+pub fn collect_rendered_numbers<T, F: FnOnce() -> T>(f: F) -> (T, Vec<f64>) {
+    let outer = RENDERED_NUMBERS.with(|numbers| numbers.replace(Some(Vec::new())));
+    let result = f();
+    let collected = RENDERED_NUMBERS
+        .with(|numbers| numbers.replace(outer))
+        .unwrap_or_default();
+    RENDERED_NUMBERS.with(|numbers| {
+        if let Some(ref mut outer) = *numbers.borrow_mut() {
+            outer.extend(&collected);
+        }
+    });
+    (result, collected)
+}
+
+/// Adds the number behind a rendered value to those being collected, if it is one.
+fn record_number(value: &str, number: Option<Number>) {
+    RENDERED_NUMBERS.with(|numbers| {
+        if let Some(ref mut numbers) = *numbers.borrow_mut() {
+            if let Some(n) = number
+                .map(|n| n.value)
+                .or_else(|| value.trim().parse().ok())
+            {
+                numbers.push(n);
+            }
+        }
+    });
+}
+
+/// The numbers that appear in a text, e.g. `[1.5, -3.0]` for "1.5MB/s at -3°C".
+pub fn numbers_in(text: &str) -> Vec<f64> {
+    let mut numbers = Vec::new();
+    let mut number = String::new();
+    for c in text.chars().chain(Some(' ')) {
+        if c.is_ascii_digit() || (c == '.' && !number.is_empty()) || (c == '-' && number.is_empty())
+        {
+            number.push(c);
+        } else {
+            if let Ok(n) = number.parse() {
+                numbers.push(n);
+            }
+            number.clear();
+        }
+    }
+    numbers
+}
+
 pub fn format_percent_bar(percent: f32) -> String {
+    format_bar(percent, 10)
+}
+
+/// Draws a percentage as a bar of `cells` characters, each filled with an eighth block
+/// according to its share of the value.
+pub fn format_bar(percent: f32, cells: usize) -> String {
+    // (x * one eighth block) https://en.wikipedia.org/wiki/Block_Elements
+    let bars = [
+        '\u{2581}', '\u{2582}', '\u{2583}', '\u{2584}', '\u{2585}', '\u{2586}', '\u{2587}',
+        '\u{2588}',
+    ];
     let percent = percent.min(100.0);
     let percent = percent.max(0.0);
+    let cell = 100.0 / cells as f32;
 
-    (0..10)
+    (0..cells)
         .map(|index| {
-            let bucket_min = (index * 10) as f32;
-            let fraction = percent - bucket_min;
-            //println!("Fraction: {}", fraction);
-            if fraction < 1.25 {
-                '\u{2581}' // 1/8 block for empty so the whole bar is always visible
-            } else if fraction < 2.5 {
-                '\u{2582}' // 2/8 block
-            } else if fraction < 3.75 {
-                '\u{2583}' // 3/8 block
-            } else if fraction < 5.0 {
-                '\u{2584}' // 4/8 block
-            } else if fraction < 6.25 {
-                '\u{2585}' // 5/8 block
-            } else if fraction < 7.5 {
-                '\u{2586}' // 6/8 block
-            } else if fraction < 8.75 {
-                '\u{2587}' // 7/8 block
-            } else {
-                '\u{2588}' // Full block
-            }
+            let fraction = (percent - index as f32 * cell) / cell;
+            // The 1/8 block is used for empty cells so the whole bar is always visible
+            bars[((fraction * 8.0) as usize).min(7)]
         })
         .collect()
 }
@@ -418,102 +838,373 @@ where
     }
 }
 
+/// How the value of a placeholder is formatted, written after the name as in
+/// `{speed:05.1;K}`: an optional `0` to pad with zeros, a minimum width, a precision and
+/// a minimum prefix. Alternatively, `{volume:bar(5)}` draws a percentage as a bar.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FormatSpec {
+    /// Number of cells of the bar
+    bar: Option<usize>,
+    zero_pad: bool,
+    min_width: usize,
+    precision: Option<usize>,
+    prefix: Option<Prefix>,
+}
+
+/// Scales numbers to the largest prefix that keeps them at or above 1, but at least `min`
+/// (an exponent of 1000, or 1024 when `binary`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Prefix {
+    min: i32,
+    binary: bool,
+}
+
+const PREFIXES: [&str; 7] = ["", "K", "M", "G", "T", "P", "E"];
+
+impl Prefix {
+    fn from_str(s: &str) -> Option<Self> {
+        let (letter, binary) = match s.strip_suffix('i') {
+            Some(letter) => (letter, true),
+            None => (s, false),
+        };
+        let min = if letter == "1" {
+            0
+        } else {
+            PREFIXES[1..].iter().position(|p| *p == letter)? as i32 + 1
+        };
+        Some(Prefix { min, binary })
+    }
+
+    /// Returns the scaled value and its prefix.
+    fn apply(self, value: f64) -> (f64, String) {
+        let base: f64 = if self.binary { 1024.0 } else { 1000.0 };
+        let mut exp = self.min;
+        while exp + 1 < PREFIXES.len() as i32 && value.abs() >= base.powi(exp + 1) {
+            exp += 1;
+        }
+        let mut prefix = PREFIXES[exp as usize].to_string();
+        if self.binary && exp > 0 {
+            prefix.push('i');
+        }
+        (value / base.powi(exp), prefix)
+    }
+}
+
+impl FormatSpec {
+    fn parse(captures: &regex::Captures) -> Result<Self> {
+        let width = captures.name("width").map_or("", |m| m.as_str());
+        let precision = captures.name("precision").map(|m| m.as_str());
+        let prefix = captures.name("prefix").map(|m| m.as_str());
+        Ok(FormatSpec {
+            bar: captures.name("bar").map(|_| {
+                captures
+                    .name("cells")
+                    .map_or(1, |m| m.as_str().parse().unwrap_or(1))
+                    .max(1)
+            }),
+            zero_pad: width.starts_with('0'),
+            min_width: width.parse().unwrap_or(0),
+            precision: precision.map(|p| p.parse().unwrap_or(0)),
+            prefix: match prefix {
+                Some(prefix) => Some(Prefix::from_str(prefix).ok_or_else(|| {
+                    ConfigurationError(
+                        format!("invalid prefix '{}' in format string", prefix),
+                        (String::new(), String::new()),
+                    )
+                })?),
+                None => None,
+            },
+        })
+    }
+
+    /// Whether the spec formats numbers, and not only pads the text.
+    fn formats_numbers(&self) -> bool {
+        self.bar.is_some() || self.precision.is_some() || self.prefix.is_some()
+    }
+
+    /// Applies the spec to a rendered value. Precision, prefix and bar are only applied to
+    /// values that are numbers, the width to all values.
+    fn apply(&self, value: String) -> String {
+        let number = if self.formats_numbers() {
+            value.trim().parse::<f64>().ok()
+        } else {
+            None
+        };
+        match number {
+            Some(number) => self.apply_number(number, ""),
+            None => self.pad(value, false),
+        }
+    }
+
+    /// Formats a number followed by its unit, like `1.2MB` for `{x;M}` and a unit of `B`.
+    fn apply_number(&self, number: f64, unit: &str) -> String {
+        if let Some(cells) = self.bar {
+            return self.pad(format_bar(number as f32, cells), false);
+        }
+        let (number, prefix) = match self.prefix {
+            Some(p) => p.apply(number),
+            None => (number, String::new()),
+        };
+        let precision = self.precision.unwrap_or_else(|| {
+            if prefix.is_empty() && number.fract() == 0.0 {
+                0
+            } else {
+                1
+            }
+        });
+        self.pad(format!("{:.*}{}{}", precision, number, prefix, unit), true)
+    }
+
+    fn pad(&self, value: String, numeric: bool) -> String {
+        let len = value.chars().count();
+        if len >= self.min_width {
+            return value;
+        }
+        let padding = self.min_width - len;
+        if self.zero_pad && (numeric || value.trim().parse::<f64>().is_ok()) {
+            let sign = if value.starts_with('-') { "-" } else { "" };
+            format!("{}{}{}", sign, "0".repeat(padding), &value[sign.len()..])
+        } else {
+            format!("{}{}", " ".repeat(padding), value)
+        }
+    }
+}
+
+/// The number behind the text of a placeholder, like the bytes per second of a speed that is
+/// shown as `1.2MB`. Specifications that format numbers, like `{speed:5;M}`, format it with
+/// its unit instead of the text, which they could not change.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Number {
+    pub value: f64,
+    pub unit: &'static str,
+}
+
+#[derive(Debug, Clone)]
+enum Token {
+    Str(String),
+    /// A placeholder, stored with its braces as in the maps of values, and the text shown
+    /// in its place when the value is missing, empty or zero.
+    Var(String, FormatSpec, Option<String>),
+    /// A section in square brackets, dropped if all placeholders in it are empty or zero.
+    Section(Vec<Token>),
+}
+
+/// Whether a rendered value counts as missing for sections and fallbacks.
+fn is_blank(value: &str) -> bool {
+    let value = value.trim();
+    value.is_empty() || value.parse::<f64>() == Ok(0.0)
+}
+
 #[derive(Debug, Clone)]
-pub enum FormatTemplate {
-    Str(String, Option<Box<FormatTemplate>>),
-    Var(String, Option<Box<FormatTemplate>>),
+pub struct FormatTemplate {
+    tokens: Vec<Token>,
 }
 
 impl FormatTemplate {
     pub fn from_string(s: &str) -> Result<FormatTemplate> {
-        let s_as_bytes = s.as_bytes();
-
-        //valid var tokens: {} containing any amount of alphanumericals
-        let re = Regex::new(r"\{[a-zA-Z0-9_-]+?\}").internal_error("util", "invalid regex")?;
-
-        let mut token_vec: Vec<FormatTemplate> = vec![];
-        let mut start: usize = 0;
-
-        for re_match in re.find_iter(&s) {
-            if re_match.start() != start {
-                let str_vec: Vec<u8> = (&s_as_bytes)[start..re_match.start()].to_vec();
-                token_vec.push(FormatTemplate::Str(
-                    String::from_utf8(str_vec)
-                        .internal_error("util", "failed to convert string from UTF8")?,
-                    None,
-                ));
+        //valid var tokens: {} containing any amount of alphanumericals, optionally followed by
+        //a spec and a fallback
+        let re = Regex::new(
+            r"^\{(?P<name>[a-zA-Z0-9_-]+?)(?::(?:(?P<bar>bar)(?:\((?P<cells>[0-9]+)\))?|(?P<width>0?[0-9]*)(?:\.(?P<precision>[0-9]+))?))?(?:;(?P<prefix>[A-Z1]i?))?(?:\|(?P<fallback>[^}]*))?\}",
+        )
+        .internal_error("util", "invalid regex")?;
+        let (tokens, _) = Self::parse(s, &re, false)?;
+        Ok(FormatTemplate { tokens })
+    }
+
+    /// Parses tokens up to the end of the string, or up to the closing bracket of a
+    /// section. Returns the tokens and the number of bytes consumed.
+    fn parse(s: &str, re: &Regex, in_section: bool) -> Result<(Vec<Token>, usize)> {
+        let mut tokens = vec![];
+        let mut text = String::new();
+        let mut i = 0;
+        while let Some(c) = s[i..].chars().next() {
+            match c {
+                // Only the characters with a meaning in format strings are escaped, other
+                // backslashes are kept as they are
+                '\\' => {
+                    if let Some(escaped) =
+                        s[i + 1..].chars().next().filter(|c| "\\[]{}|".contains(*c))
+                    {
+                        text.push(escaped);
+                        i += 1 + escaped.len_utf8();
+                        continue;
+                    }
+                }
+                ']' if in_section => {
+                    if !text.is_empty() {
+                        tokens.push(Token::Str(text));
+                    }
+                    return Ok((tokens, i + 1));
+                }
+                '[' => {
+                    let (section, len) = Self::parse(&s[i + 1..], re, true)?;
+                    // An unclosed bracket is kept as text.
+                    if i + 1 + len <= s.len() {
+                        match Self::literal(&section) {
+                            // Brackets without placeholders are text, as in formats that
+                            // predate sections
+                            Some(literal) => {
+                                text.push('[');
+                                text.push_str(&literal);
+                                text.push(']');
+                            }
+                            None => {
+                                if !text.is_empty() {
+                                    tokens.push(Token::Str(text.split_off(0)));
+                                }
+                                tokens.push(Token::Section(section));
+                            }
+                        }
+                        i += 1 + len;
+                        continue;
+                    }
+                }
+                '{' => {
+                    if let Some(captures) = re.captures(&s[i..]) {
+                        if !text.is_empty() {
+                            tokens.push(Token::Str(text.split_off(0)));
+                        }
+                        tokens.push(Token::Var(
+                            format!("{{{}}}", &captures["name"]),
+                            FormatSpec::parse(&captures)?,
+                            captures.name("fallback").map(|m| m.as_str().to_string()),
+                        ));
+                        i += captures[0].len();
+                        continue;
+                    }
+                }
+                _ => {}
             }
-            token_vec.push(FormatTemplate::Var(re_match.as_str().to_string(), None));
-            start = re_match.end();
+            text.push(c);
+            i += c.len_utf8();
         }
-        let str_vec: Vec<u8> = (&s_as_bytes)[start..].to_vec();
-        token_vec.push(FormatTemplate::Str(
-            String::from_utf8(str_vec)
-                .internal_error("util", "failed to convert string from UTF8")?,
-            None,
-        ));
-        let mut template: FormatTemplate = match token_vec.pop() {
-            Some(token) => token,
-            _ => FormatTemplate::Str("".to_string(), None),
-        };
-        while let Some(token) = token_vec.pop() {
-            template = match token {
-                FormatTemplate::Str(s, _) => FormatTemplate::Str(s, Some(Box::new(template))),
-                FormatTemplate::Var(s, _) => FormatTemplate::Var(s, Some(Box::new(template))),
-            }
+        if in_section {
+            // Signal the missing closing bracket with a length past the end.
+            return Ok((tokens, s.len() + 1));
         }
-        Ok(template)
+        if !text.is_empty() {
+            tokens.push(Token::Str(text));
+        }
+        Ok((tokens, i))
     }
 
-    // TODO: Make this function tail-recursive for compiler optimization, also only use the version below, static_str
-    pub fn render<T: Display>(&self, vars: &HashMap<String, T>) -> String {
-        use self::FormatTemplate::*;
+    /// The text of tokens without placeholders. Sections without placeholders are already
+    /// turned into text when they are parsed.
+    fn literal(tokens: &[Token]) -> Option<String> {
+        tokens
+            .iter()
+            .map(|token| match *token {
+                Token::Str(ref s) => Some(s.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Renders tokens and returns whether any placeholder had a value that is not blank,
+    /// or whether there were no placeholders at all.
+    fn render_tokens<F>(tokens: &[Token], lookup: &F) -> Result<(String, bool)>
+    where
+        F: Fn(&str) -> Option<(String, Option<Number>)>,
+    {
         let mut rendered = String::new();
-        match *self {
-            Str(ref s, ref next) => {
-                rendered.push_str(s);
-                if let Some(ref next) = *next {
-                    rendered.push_str(&*next.render(vars));
-                };
-            }
-            Var(ref key, ref next) => {
-                rendered.push_str(&format!(
-                    "{}",
-                    vars.get(key)
-                        .unwrap_or_else(|| panic!("Unknown placeholder in format string: {}", key))
-                ));
-                if let Some(ref next) = *next {
-                    rendered.push_str(&*next.render(vars));
-                };
+        let mut has_vars = false;
+        let mut has_value = false;
+        for token in tokens {
+            match *token {
+                Token::Str(ref s) => rendered.push_str(s),
+                Token::Var(ref key, ref spec, ref fallback) => {
+                    has_vars = true;
+                    let value = match (lookup(key), fallback) {
+                        (Some((ref value, _)), Some(fallback)) if is_blank(value) => {
+                            fallback.clone()
+                        }
+                        (None, Some(fallback)) => fallback.clone(),
+                        (Some((value, number)), _) => {
+                            has_value |= !is_blank(&value);
+                            record_number(&value, number);
+                            match number {
+                                Some(number) if spec.formats_numbers() => {
+                                    spec.apply_number(number.value, number.unit)
+                                }
+                                _ => spec.apply(value),
+                            }
+                        }
+                        (None, None) => {
+                            return Err(InternalError(
+                                "util".to_string(),
+                                format!("Unknown placeholder in format string: {}", key),
+                                None,
+                            ))
+                        }
+                    };
+                    if fallback.is_some() {
+                        has_value |= !value.is_empty();
+                    }
+                    rendered.push_str(&value);
+                }
+                Token::Section(ref section) => {
+                    let (text, section_has_value) = Self::render_tokens(section, lookup)?;
+                    if section_has_value {
+                        rendered.push_str(&text);
+                    }
+                    has_vars = true;
+                    has_value |= section_has_value;
+                }
             }
-        };
-        rendered
+        }
+        Ok((rendered, has_value || !has_vars))
+    }
+
+    fn render_with<F>(&self, lookup: F) -> Result<String>
+    where
+        F: Fn(&str) -> Option<(String, Option<Number>)>,
+    {
+        Self::render_tokens(&self.tokens, &lookup).map(|(rendered, _)| rendered)
+    }
+
+    pub fn render<T: Display>(&self, vars: &HashMap<String, T>) -> String {
+        self.render_with(|key| vars.get(key).map(|v| (v.to_string(), None)))
+            .unwrap_or_else(|e| panic!("{}", e))
     }
 
     pub fn render_static_str<T: Display>(&self, vars: &HashMap<&str, T>) -> Result<String> {
-        use self::FormatTemplate::*;
-        let mut rendered = String::new();
-        match *self {
-            Str(ref s, ref next) => {
-                rendered.push_str(s);
-                if let Some(ref next) = *next {
-                    rendered.push_str(&*next.render_static_str(vars)?);
-                };
-            }
-            Var(ref key, ref next) => {
-                rendered.push_str(&format!(
-                    "{}",
-                    vars.get(&**key).internal_error(
-                        "util",
-                        &format!("Unknown placeholder in format string: {}", key)
-                    )?
-                ));
-                if let Some(ref next) = *next {
-                    rendered.push_str(&*next.render_static_str(vars)?);
-                };
+        self.render_with(|key| vars.get(key).map(|v| (v.to_string(), None)))
+    }
+
+    /// Like `render_static_str`, with the numbers behind some of the values.
+    pub fn render_numbers<T: Display>(
+        &self,
+        vars: &HashMap<&str, T>,
+        numbers: &HashMap<&str, Number>,
+    ) -> Result<String> {
+        self.render_with(|key| {
+            vars.get(key)
+                .map(|v| (v.to_string(), numbers.get(key).cloned()))
+        })
+    }
+
+    /// The placeholders in the template, with their braces as in the maps of values, but
+    /// without their specifications and fallbacks.
+    pub fn placeholders(&self) -> Vec<&str> {
+        fn collect<'a>(tokens: &'a [Token], keys: &mut Vec<&'a str>) {
+            for token in tokens {
+                match *token {
+                    Token::Str(_) => {}
+                    Token::Var(ref key, ..) => keys.push(key),
+                    Token::Section(ref section) => collect(section, keys),
+                }
             }
-        };
-        Ok(rendered)
+        }
+        let mut keys = Vec::new();
+        collect(&self.tokens, &mut keys);
+        keys
+    }
+
+    /// Whether the template shows the placeholder `key`, like `{ssid}`, so that blocks only
+    /// gather the values that are shown.
+    pub fn has_placeholder(&self, key: &str) -> bool {
+        self.placeholders().contains(&key)
     }
 }
 
@@ -523,7 +1214,12 @@ macro_rules! if_debug {
 
 #[cfg(test)]
 mod tests {
-    use crate::util::{color_from_rgba, has_command};
+    use crate::util::{
+        color_from_rgba, expand_home, format_percent_bar, has_command, json_display, json_pointer,
+        mix_colors, numbers_in, parse_http_response, parse_ip_location, parse_reverse_geocoding,
+        tls_client_args, FormatTemplate, Location, Number,
+    };
+    use std::path::PathBuf;
 
     #[test]
     // we assume sh is always available
@@ -542,6 +1238,13 @@ mod tests {
         let has_command = has_command.unwrap();
         assert!(!has_command)
     }
+    #[test]
+    fn test_numbers_in() {
+        assert_eq!(numbers_in("1.5MB/s at -3°C"), vec![1.5, -3.0]);
+        assert_eq!(numbers_in("0B/s - 12%"), vec![0.0, 12.0]);
+        assert!(numbers_in("no numbers").is_empty());
+    }
+
     #[test]
     fn test_color_from_rgba() {
         let valid_rgb = "#AABBCC"; //rgb
@@ -566,4 +1269,219 @@ mod tests {
         let rgba = color_from_rgba(invalid);
         assert!(rgba.is_err());
     }
+
+    #[test]
+    fn test_mix_colors() {
+        assert_eq!(mix_colors("#000000", "#FF8000", 0.0).unwrap(), "#000000FF");
+        assert_eq!(mix_colors("#000000", "#FF8000", 0.5).unwrap(), "#804000FF");
+        assert_eq!(mix_colors("#000000", "#FF8000", 2.0).unwrap(), "#FF8000FF");
+        assert!(mix_colors("#000000", "auto", 0.5).is_err());
+    }
+
+    #[test]
+    fn test_format_spec() {
+        let render = |format: &str, value: &str| {
+            FormatTemplate::from_string(format)
+                .unwrap()
+                .render_static_str(&map!("{x}" => value))
+                .unwrap()
+        };
+        assert_eq!(render("{x}", "12.345"), "12.345");
+        assert_eq!(render("{x:.1}", "12.345"), "12.3");
+        assert_eq!(render("{x:5}", "ab"), "   ab");
+        assert_eq!(render("{x:04}", "-7"), "-007");
+        assert_eq!(render("{x;K}", "2500"), "2.5K");
+        assert_eq!(render("{x;K}", "25"), "0.0K");
+        assert_eq!(render("{x:.2;M}", "1234567"), "1.23M");
+        assert_eq!(render("{x;1i}", "1536"), "1.5Ki");
+        assert_eq!(render("{x;1}", "999"), "999");
+        assert_eq!(render("{x:6.0;Ki}", "1048576"), "   1Mi");
+        assert_eq!(render("{x:.1}", "n/a"), "n/a");
+        assert!(FormatTemplate::from_string("{x;Q}").is_err());
+    }
+
+    #[test]
+    fn test_format_numbers() {
+        let render = |format: &str| {
+            let speed = Number {
+                value: 1_234_567.0,
+                unit: "B",
+            };
+            FormatTemplate::from_string(format)
+                .unwrap()
+                .render_numbers(&map!("{x}" => "1.2MB"), &map!("{x}" => speed))
+                .unwrap()
+        };
+        // The text is shown as it is without a spec, or with only a width
+        assert_eq!(render("{x}"), "1.2MB");
+        assert_eq!(render("{x:7}"), "  1.2MB");
+        assert_eq!(render("{x:.2;K}"), "1.23MB");
+        assert_eq!(render("{x:6;K}"), " 1.2MB");
+        assert_eq!(render("{x:.0}"), "1234567B");
+        assert_eq!(render("{x:.0;1}"), "1MB");
+    }
+
+    #[test]
+    fn test_placeholders() {
+        let format =
+            FormatTemplate::from_string("{ssid:10} [{signal_strength|} ]{speed_down:5;K}").unwrap();
+        assert_eq!(
+            format.placeholders(),
+            vec!["{ssid}", "{signal_strength}", "{speed_down}"]
+        );
+        assert!(format.has_placeholder("{ssid}"));
+        assert!(format.has_placeholder("{speed_down}"));
+        assert!(!format.has_placeholder("{ip}"));
+        assert!(!format.has_placeholder("{speed_down:5;K}"));
+    }
+
+    #[test]
+    fn test_format_bar() {
+        let render = |format: &str, value: &str| {
+            FormatTemplate::from_string(format)
+                .unwrap()
+                .render_static_str(&map!("{x}" => value))
+                .unwrap()
+        };
+        assert_eq!(render("{x:bar}", "50"), "\u{2585}");
+        assert_eq!(
+            render("{x:bar(4)}", "60"),
+            "\u{2588}\u{2588}\u{2584}\u{2581}"
+        );
+        assert_eq!(render("{x:bar(2)}", "150"), "\u{2588}\u{2588}");
+        assert_eq!(format_percent_bar(0.0), "\u{2581}".repeat(10));
+    }
+
+    #[test]
+    fn test_format_sections() {
+        let render = |format: &str, artist: &str| {
+            FormatTemplate::from_string(format)
+                .unwrap()
+                .render_static_str(&map!("{artist}" => artist, "{title}" => "Song"))
+                .unwrap()
+        };
+        assert_eq!(render("[{artist} - ]{title}", "Band"), "Band - Song");
+        assert_eq!(render("[{artist} - ]{title}", ""), "Song");
+        assert_eq!(render("[{artist} - ]{title}", "0"), "Song");
+        assert_eq!(render("[[{artist}] {title}]", ""), " Song");
+        assert_eq!(render("{artist|unknown}: {title}", ""), "unknown: Song");
+        assert_eq!(render("{ip|offline}", ""), "offline");
+        assert_eq!(render("[{ip|}]", ""), "");
+        assert_eq!(render("\\[{artist}\\] [x", "Band"), "[Band] [x");
+        // Brackets without placeholders and other backslashes are text
+        assert_eq!(render("[ok] {title}", ""), "[ok] Song");
+        assert_eq!(render("[{artist} [live]]", ""), "");
+        assert_eq!(render("[{artist} [live]]", "Band"), "Band [live]");
+        assert_eq!(
+            render("C:\\Music\\Rock {title}", ""),
+            "C:\\Music\\Rock Song"
+        );
+    }
+
+    #[test]
+    fn test_parse_ip_location() {
+        let json = serde_json::json!({"city": "Oslo", "latitude": 59.9127, "longitude": 10.7461});
+        assert_eq!(
+            parse_ip_location(&json),
+            Some(Location {
+                latitude: 59.9127,
+                longitude: 10.7461,
+                city: Some("Oslo".to_string())
+            })
+        );
+        assert_eq!(
+            parse_ip_location(&serde_json::json!({"error": true, "reason": "RateLimited"})),
+            None
+        );
+    }
+
+    #[test]
+    fn test_parse_reverse_geocoding() {
+        let json = serde_json::json!({"name": "Grünerløkka", "address": {
+            "suburb": "Grünerløkka", "city": "Oslo", "country": "Norge"
+        }});
+        assert_eq!(parse_reverse_geocoding(&json), Some("Oslo".to_string()));
+        let json = serde_json::json!({"address": {"village": "Reine", "county": "Nordland"}});
+        assert_eq!(parse_reverse_geocoding(&json), Some("Reine".to_string()));
+        assert_eq!(
+            parse_reverse_geocoding(&serde_json::json!({"error": "Unable to geocode"})),
+            None
+        );
+    }
+
+    #[test]
+    fn test_tls_client_args() {
+        assert_eq!(
+            tls_client_args("imap.example.com", 993, true),
+            vec![
+                "s_client",
+                "-quiet",
+                "-connect",
+                "imap.example.com:993",
+                "-servername",
+                "imap.example.com",
+                "-verify_return_error",
+                "-verify_hostname",
+                "imap.example.com",
+            ]
+        );
+        let args = tls_client_args("localhost", 9001, false);
+        assert!(!args.iter().any(|arg| arg.starts_with("-verify")));
+    }
+
+    #[test]
+    fn test_parse_http_response() {
+        let response = parse_http_response(
+            b"HTTP/1.1 301 Moved Permanently\r\nLocation: https://example.org/\r\n\r\n\
+              HTTP/2 200\r\nContent-Type: application/json\r\nX-Poll-Interval: 60\r\n\r\n\
+              {\"ok\": true}",
+        )
+        .unwrap();
+        assert_eq!(response.status, 200);
+        assert!(response.is_success());
+        assert_eq!(response.headers["content-type"], "application/json");
+        assert_eq!(response.headers["x-poll-interval"], "60");
+        assert_eq!(response.text(), "{\"ok\": true}");
+
+        let response = parse_http_response(b"HTTP/2 404\r\nserver: nginx\r\n\r\n").unwrap();
+        assert_eq!(response.status, 404);
+        assert!(!response.is_success());
+        assert!(response.body.is_empty());
+
+        assert_eq!(parse_http_response(b""), None);
+    }
+
+    #[test]
+    fn test_expand_home() {
+        let home = std::env::var("HOME").unwrap_or_default();
+        assert_eq!(
+            expand_home("~/todo.txt"),
+            PathBuf::from(format!("{}/todo.txt", home))
+        );
+        assert_eq!(expand_home("/etc/hosts"), PathBuf::from("/etc/hosts"));
+        assert_eq!(expand_home("~user/file"), PathBuf::from("~user/file"));
+    }
+
+    #[test]
+    fn test_json_pointer() {
+        assert_eq!(json_pointer(".main.temp"), "/main/temp");
+        assert_eq!(
+            json_pointer("list[0].weather[1].id"),
+            "/list/0/weather/1/id"
+        );
+        assert_eq!(json_pointer("/sensors/a~1b"), "/sensors/a~1b");
+        assert_eq!(json_pointer("state"), "/state");
+        assert_eq!(json_pointer(""), "");
+    }
+
+    #[test]
+    fn test_json_display() {
+        let answer = serde_json::json!({"name": "kitchen", "temp": 21.5, "count": 3, "on": true, "unit": null});
+        assert_eq!(json_display(answer.pointer("/name")), "kitchen");
+        assert_eq!(json_display(answer.pointer("/temp")), "21.5");
+        assert_eq!(json_display(answer.pointer("/count")), "3");
+        assert_eq!(json_display(answer.pointer("/on")), "true");
+        assert_eq!(json_display(answer.pointer("/unit")), "");
+        assert_eq!(json_display(answer.pointer("/missing")), "");
+    }
 }
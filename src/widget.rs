@@ -6,6 +6,7 @@ use serde_derive::Deserialize;
 use serde_json::value::Value;
 
 use crate::themes::Theme;
+use crate::util::mix_colors;
 
 #[derive(Debug, Copy, Clone, Deserialize)]
 pub enum Spacing {
@@ -37,6 +38,36 @@ impl State {
             Critical => (&theme.critical_bg, &theme.critical_fg),
         }
     }
+
+    /// Colors of a widget in this state. If a value between 0 (good) and 1 (critical)
+    /// is given, the colors are instead interpolated along good, warning and critical.
+    pub fn colors(self, theme: &Theme, value: Option<f64>) -> (Option<String>, Option<String>) {
+        let (bg, fg) = self.theme_keys(theme);
+        match value {
+            Some(value) => (
+                gradient(
+                    &[&theme.good_bg, &theme.warning_bg, &theme.critical_bg],
+                    value,
+                )
+                .or_else(|| bg.clone()),
+                gradient(
+                    &[&theme.good_fg, &theme.warning_fg, &theme.critical_fg],
+                    value,
+                )
+                .or_else(|| fg.clone()),
+            ),
+            None => (bg.clone(), fg.clone()),
+        }
+    }
+}
+
+fn gradient(stops: &[&Option<String>; 3], value: f64) -> Option<String> {
+    let position = value.clamp(0.0, 1.0) * 2.0;
+    let i = (position as usize).min(1);
+    match (stops[i], stops[i + 1]) {
+        (Some(a), Some(b)) => mix_colors(a, b, position - i as f64).ok(),
+        _ => None,
+    }
 }
 
 impl FromStr for State {
@@ -51,4 +82,19 @@ impl FromStr for State {
 pub trait I3BarWidget {
     fn to_string(&self) -> String;
     fn get_rendered(&self) -> &Value;
+
+    fn state(&self) -> State {
+        State::Idle
+    }
+
+    /// The actual value of the widget in percent, like the charge of a battery, for outputs
+    /// that show it separately.
+    fn percentage(&self) -> Option<f64> {
+        None
+    }
+
+    /// The text of the widget, without its icon.
+    fn content(&self) -> &str {
+        ""
+    }
 }
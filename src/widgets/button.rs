@@ -10,6 +10,8 @@ pub struct ButtonWidget {
     content: Option<String>,
     icon: Option<String>,
     state: State,
+    value: Option<f64>,
+    percentage: Option<f64>,
     spacing: Spacing,
     id: String,
     rendered: Value,
@@ -23,6 +25,8 @@ impl ButtonWidget {
             content: None,
             icon: None,
             state: State::Idle,
+            value: None,
+            percentage: None,
             spacing: Spacing::Normal,
             id: String::from(id),
             rendered: json!({
@@ -83,13 +87,26 @@ impl ButtonWidget {
         self.update();
     }
 
+    /// Sets the value, from 0 (good) to 1 (critical), that the colors follow in
+    /// gradient mode.
+    pub fn set_value(&mut self, value: Option<f64>) {
+        self.value = value;
+        self.update();
+    }
+
+    /// Sets the actual value in percent, which is reported separately by some outputs.
+    pub fn set_percentage(&mut self, percentage: Option<f64>) {
+        self.percentage = percentage;
+    }
+
     pub fn set_spacing(&mut self, spacing: Spacing) {
         self.spacing = spacing;
         self.update();
     }
 
     fn update(&mut self) {
-        let (key_bg, key_fg) = self.state.theme_keys(&self.config.theme);
+        let gradient_value = self.value.filter(|_| self.config.gradient);
+        let (key_bg, key_fg) = self.state.colors(&self.config.theme, gradient_value);
 
         // When rendered inline, remove the leading space
         self.rendered = json!({
@@ -128,4 +145,16 @@ impl I3BarWidget for ButtonWidget {
     fn get_rendered(&self) -> &Value {
         &self.rendered
     }
+
+    fn state(&self) -> State {
+        self.state
+    }
+
+    fn percentage(&self) -> Option<f64> {
+        self.percentage
+    }
+
+    fn content(&self) -> &str {
+        self.content.as_deref().unwrap_or("")
+    }
 }
@@ -135,4 +135,12 @@ impl I3BarWidget for GraphWidget {
     fn get_rendered(&self) -> &Value {
         &self.rendered
     }
+
+    fn state(&self) -> State {
+        self.state
+    }
+
+    fn content(&self) -> &str {
+        self.content.as_deref().unwrap_or("")
+    }
 }
@@ -220,4 +220,12 @@ impl I3BarWidget for RotatingTextWidget {
     fn get_rendered(&self) -> &Value {
         &self.rendered
     }
+
+    fn state(&self) -> State {
+        self.state
+    }
+
+    fn content(&self) -> &str {
+        &self.content
+    }
 }
@@ -10,6 +10,8 @@ pub struct TextWidget {
     content: Option<String>,
     icon: Option<String>,
     state: State,
+    value: Option<f64>,
+    percentage: Option<f64>,
     spacing: Spacing,
     rendered: Value,
     cached_output: Option<String>,
@@ -22,6 +24,8 @@ impl TextWidget {
             content: None,
             icon: None,
             state: State::Idle,
+            value: None,
+            percentage: None,
             spacing: Spacing::Normal,
             rendered: json!({
                 "full_text": "",
@@ -74,13 +78,26 @@ impl TextWidget {
         self.update();
     }
 
+    /// Sets the value, from 0 (good) to 1 (critical), that the colors follow in
+    /// gradient mode.
+    pub fn set_value(&mut self, value: Option<f64>) {
+        self.value = value;
+        self.update();
+    }
+
+    /// Sets the actual value in percent, which is reported separately by some outputs.
+    pub fn set_percentage(&mut self, percentage: Option<f64>) {
+        self.percentage = percentage;
+    }
+
     pub fn set_spacing(&mut self, spacing: Spacing) {
         self.spacing = spacing;
         self.update();
     }
 
     fn update(&mut self) {
-        let (key_bg, key_fg) = self.state.theme_keys(&self.config.theme);
+        let gradient_value = self.value.filter(|_| self.config.gradient);
+        let (key_bg, key_fg) = self.state.colors(&self.config.theme, gradient_value);
 
         self.rendered = json!({
             "full_text": format!("{}{}{}",
@@ -98,8 +115,8 @@ impl TextWidget {
                             ),
             "separator": false,
             "separator_block_width": 0,
-            "background": key_bg.to_owned(),
-            "color": key_fg.to_owned()
+            "background": key_bg,
+            "color": key_fg
         });
 
         self.cached_output = Some(self.rendered.to_string());
@@ -116,4 +133,16 @@ impl I3BarWidget for TextWidget {
     fn get_rendered(&self) -> &Value {
         &self.rendered
     }
+
+    fn state(&self) -> State {
+        self.state
+    }
+
+    fn percentage(&self) -> Option<f64> {
+        self.percentage
+    }
+
+    fn content(&self) -> &str {
+        self.content.as_deref().unwrap_or("")
+    }
 }